@@ -0,0 +1,182 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// Benchmarks of the filters over large generated inputs. Each command is
+// run as a process, as it would be used, from the release build in
+// target/release, or the directory named by V7_BIN. The system's command
+// of the same name, if there is one, is run on the same input as a point
+// of reference. The commands that map regular files into memory are also
+// run on their standard input, which they have to read, to show what the
+// mapping saves. Run bench/compare.sh, which builds the commands first.
+use std::env;
+use std::fs;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+
+/// The sizes of the text and binary inputs.
+const TEXT_SIZE: usize = 16 << 20;
+const BINARY_SIZE: usize = 4 << 20;
+
+const WORDS: &[&str] = &[
+    "the", "of", "and", "to", "in", "is", "that", "for", "it", "as", "was", "with", "be", "by",
+    "on", "not", "he", "this", "are", "or", "his", "from", "at", "which", "but", "have", "an",
+    "had", "they", "you", "were", "their", "one", "all", "we", "can", "her", "has", "there",
+    "been", "if", "more", "when", "will", "would", "who", "so", "no", "unix", "kernel", "pipe",
+];
+
+/// A generator of pseudo-random numbers, so that the inputs are the same
+/// from one run to the next.
+struct Lcg(u64);
+
+impl Lcg {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        self.0 >> 33
+    }
+}
+
+/// The generated inputs.
+struct Fixtures {
+    dir: PathBuf,
+    text: PathBuf,
+    /// A copy of the text, for cmp.
+    copy: PathBuf,
+    binary: PathBuf,
+}
+
+impl Fixtures {
+    /// Writes the inputs, unless a previous run already has.
+    fn new() -> Fixtures {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("target/fixtures");
+        fs::create_dir_all(&dir).unwrap();
+        let fixtures = Fixtures {
+            text: dir.join("text"),
+            copy: dir.join("copy"),
+            binary: dir.join("binary"),
+            dir,
+        };
+        if !fixtures.text.exists() {
+            let mut rng = Lcg(1);
+            let mut text = String::with_capacity(TEXT_SIZE + 100);
+            while text.len() < TEXT_SIZE {
+                let words = 1 + rng.next() % 12;
+                for i in 0..words {
+                    if i > 0 {
+                        text.push(' ');
+                    }
+                    text.push_str(WORDS[rng.next() as usize % WORDS.len()]);
+                }
+                text.push('\n');
+            }
+            fs::write(&fixtures.text, text).unwrap();
+            fs::copy(&fixtures.text, &fixtures.copy).unwrap();
+        }
+        if !fixtures.binary.exists() {
+            let mut rng = Lcg(2);
+            let data: Vec<u8> = (0..BINARY_SIZE).map(|_| rng.next() as u8).collect();
+            fs::write(&fixtures.binary, data).unwrap();
+        }
+        fixtures
+    }
+}
+
+/// Returns the directory holding the commands to benchmark.
+fn bin_dir() -> PathBuf {
+    match env::var_os("V7_BIN") {
+        Some(dir) => PathBuf::from(dir),
+        None => Path::new(env!("CARGO_MANIFEST_DIR")).join("../target/release"),
+    }
+}
+
+/// Returns the path of the system's command, found in PATH.
+fn system(name: &str) -> Option<PathBuf> {
+    let path = env::var_os("PATH")?;
+    env::split_paths(&path).map(|dir| dir.join(name)).find(|path| path.is_file())
+}
+
+/// Runs a command with its input from the file and its output discarded.
+fn run(program: &Path, args: &[&Path], input: &Path) {
+    let status = Command::new(program)
+        .args(args)
+        .env("LC_ALL", "C")
+        .stdin(File::open(input).unwrap())
+        .stdout(Stdio::null())
+        .status()
+        .unwrap();
+    assert!(status.success(), "{}: {}", program.display(), status);
+}
+
+/// Benchmarks the command, and the system's, with the arguments, reading
+/// the input.
+fn bench(c: &mut Criterion, name: &str, args: &[&Path], input: &Path) {
+    let program = bin_dir().join(name);
+    assert!(program.is_file(), "{}: not built; run bench/compare.sh", program.display());
+    let mut group = c.benchmark_group(name);
+    group.sample_size(10);
+    group.throughput(Throughput::Bytes(fs::metadata(input).unwrap().len()));
+    group.bench_function("v7", |b| b.iter(|| run(&program, args, input)));
+    if let Some(program) = system(name) {
+        group.bench_function("system", |b| b.iter(|| run(&program, args, input)));
+    }
+    group.finish();
+}
+
+/// Benchmarks the command reading the input as a named file, which it maps
+/// into memory, and as its standard input, which it has to read.
+fn bench_mapped(c: &mut Criterion, name: &str, args: &[&Path], input: &Path) {
+    let program = bin_dir().join(name);
+    let mut group = c.benchmark_group(format!("{}-mmap", name));
+    group.sample_size(10);
+    group.throughput(Throughput::Bytes(fs::metadata(input).unwrap().len()));
+    let named: Vec<&Path> = args.iter().copied().chain([input]).collect();
+    let read: Vec<&Path> = args.iter().copied().chain([Path::new("-")]).collect();
+    group.bench_function("mapped", |b| b.iter(|| run(&program, &named, input)));
+    group.bench_function("read", |b| b.iter(|| run(&program, &read, input)));
+    group.finish();
+}
+
+fn cat(c: &mut Criterion) {
+    let fixtures = Fixtures::new();
+    bench(c, "cat", &[&fixtures.text], &fixtures.text);
+}
+
+fn od(c: &mut Criterion) {
+    let fixtures = Fixtures::new();
+    bench(c, "od", &[Path::new("-b"), &fixtures.binary], &fixtures.binary);
+    bench_mapped(c, "od", &[Path::new("-b")], &fixtures.binary);
+}
+
+fn cmp(c: &mut Criterion) {
+    let fixtures = Fixtures::new();
+    bench(c, "cmp", &[&fixtures.copy, &fixtures.text], &fixtures.text);
+    bench_mapped(c, "cmp", &[&fixtures.copy], &fixtures.text);
+}
+
+fn strings(c: &mut Criterion) {
+    let fixtures = Fixtures::new();
+    bench(c, "strings", &[&fixtures.binary], &fixtures.binary);
+    bench_mapped(c, "strings", &[], &fixtures.binary);
+}
+
+fn sort(c: &mut Criterion) {
+    let fixtures = Fixtures::new();
+    bench(c, "sort", &[&fixtures.text], &fixtures.text);
+    bench_mapped(c, "sort", &[], &fixtures.text);
+}
+
+fn wc(c: &mut Criterion) {
+    let fixtures = Fixtures::new();
+    bench(c, "wc", &[&fixtures.text], &fixtures.text);
+}
+
+fn tee(c: &mut Criterion) {
+    let fixtures = Fixtures::new();
+    let copy = fixtures.dir.join("tee");
+    bench(c, "tee", &[&copy], &fixtures.text);
+}
+
+criterion_group!(benches, cat, cmp, od, sort, strings, wc, tee);
+criterion_main!(benches);
@@ -0,0 +1,10 @@
+#!/bin/sh
+# Builds the commands for release and benchmarks them, and the system's
+# commands of the same names, as a reference. Arguments are passed to
+# cargo bench, so that, for example, "compare.sh sort" runs only sort's
+# benchmarks. Criterion's report is in bench/target/criterion/report.
+set -e
+cd "$(dirname "$0")/.."
+cargo build --release --bins
+cd bench
+exec cargo bench --bench filters -- "$@"
@@ -0,0 +1,14 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// Fuzzes the parsing of ar archives.
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use rust_v7_cmd::ar;
+
+fuzz_target!(|data: &[u8]| {
+    if ar::is_archive(data) {
+        let _ = ar::members(data);
+    }
+});
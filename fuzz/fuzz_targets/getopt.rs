@@ -0,0 +1,21 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// Fuzzes the parsing of options. The input is the option spec followed by
+// the arguments, each ended by a NUL.
+#![no_main]
+use std::ffi::OsString;
+use std::os::unix::ffi::OsStrExt;
+
+use libfuzzer_sys::fuzz_target;
+
+use rust_v7_cmd::args::GetOpt;
+
+fuzz_target!(|data: &[u8]| {
+    let mut parts = data.split(|&b| b == 0);
+    let Some(Ok(spec)) = parts.next().map(std::str::from_utf8) else {
+        return;
+    };
+    let args: Vec<OsString> = parts.map(|arg| std::ffi::OsStr::from_bytes(arg).to_owned()).collect();
+    for _ in GetOpt::new(spec, args.into_iter()) {}
+});
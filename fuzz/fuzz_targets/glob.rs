@@ -0,0 +1,17 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// Fuzzes the matching of glob patterns. The input is the pattern and the
+// name, separated by a NUL.
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use rust_v7_cmd::glob;
+
+fuzz_target!(|data: &[u8]| {
+    let mut parts = data.splitn(2, |&b| b == 0);
+    let pat = parts.next().unwrap_or_default();
+    let name = parts.next().unwrap_or_default();
+    let _ = glob::is_pattern(pat);
+    let _ = glob::matches(pat, name);
+});
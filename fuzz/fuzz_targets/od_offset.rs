@@ -0,0 +1,12 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// Fuzzes od's [+]offset[.][b] operand.
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use rust_v7_cmd::cmd::od;
+
+fuzz_target!(|data: &[u8]| {
+    od::fuzz(data);
+});
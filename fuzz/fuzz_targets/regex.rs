@@ -0,0 +1,30 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// Fuzzes the compiling and matching of regular expressions. The first byte
+// chooses the syntax and whether case is ignored, and the rest is the
+// pattern and the text to match it against, separated by a NUL.
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use rust_v7_cmd::regex::{Regex, Syntax};
+
+fuzz_target!(|data: &[u8]| {
+    let Some((&flags, rest)) = data.split_first() else {
+        return;
+    };
+    let syntax = if flags & 1 == 0 { Syntax::Basic } else { Syntax::Extended };
+    let mut parts = rest.splitn(2, |&b| b == 0);
+    let pattern = parts.next().unwrap_or_default();
+    let text = parts.next().unwrap_or_default();
+    if let Ok(re) = Regex::with_syntax(pattern, syntax, flags & 2 != 0) {
+        let _ = re.is_match(text);
+        let mut start = 0;
+        while let Some(m) = re.find_at(text, start) {
+            start = m.end().max(start + 1);
+            if start > text.len() {
+                break;
+            }
+        }
+    }
+});
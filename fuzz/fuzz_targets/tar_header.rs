@@ -0,0 +1,24 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// Fuzzes the reading of tar archives: the parsing of their headers,
+// including the long names of GNU and pax archives, and the skipping of
+// the data between them.
+#![no_main]
+use std::io;
+
+use libfuzzer_sys::fuzz_target;
+
+use rust_v7_cmd::archive::{Header, Reader, BLOCK_SIZE};
+
+fuzz_target!(|data: &[u8]| {
+    if data.len() >= BLOCK_SIZE {
+        let _ = Header::parse(&data[..BLOCK_SIZE]);
+    }
+    let mut reader = Reader::new(data);
+    while let Ok(Some(_)) = reader.next_header() {
+        if reader.copy_data(&mut io::sink()).is_err() {
+            break;
+        }
+    }
+});
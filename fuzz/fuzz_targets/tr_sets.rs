@@ -0,0 +1,13 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// Fuzzes tr's expansion of ranges, classes and repeats in its sets. The
+// input is the two sets, separated by a NUL.
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use rust_v7_cmd::cmd::tr;
+
+fuzz_target!(|data: &[u8]| {
+    tr::fuzz(data);
+});
@@ -0,0 +1,110 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+//! Reading of ar archives, in the common format with either the System V
+//! (GNU) or the BSD convention for long member names.
+use std::io;
+
+const MAGIC: &[u8] = b"!<arch>\n";
+/// The size of a member header.
+const HEADER_SIZE: usize = 60;
+
+/// Returns whether the data starts like an ar archive.
+pub fn is_archive(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+/// An archive member.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Member<'a> {
+    pub name: String,
+    pub data: &'a [u8],
+}
+
+/// Returns a header field with its trailing blanks removed.
+fn field(header: &[u8]) -> &[u8] {
+    let end = header.iter().rposition(|&c| c != b' ').map_or(0, |i| i + 1);
+    &header[..end]
+}
+
+fn number(field: &[u8]) -> io::Result<usize> {
+    std::str::from_utf8(field).ok().and_then(|s| s.parse().ok())
+        .ok_or_else(|| invalid("malformed archive member header"))
+}
+
+/// Returns the members of an archive, other than the symbol table and
+/// the table of long names.
+pub fn members(data: &[u8]) -> io::Result<Vec<Member<'_>>> {
+    if !is_archive(data) {
+        return Err(invalid("file format not recognized"));
+    }
+    let mut members = Vec::new();
+    // The System V table of long names, which are terminated by "/\n".
+    let mut long_names: &[u8] = &[];
+    let mut offset = MAGIC.len();
+    while offset < data.len() {
+        let header = data.get(offset..offset + HEADER_SIZE)
+            .ok_or_else(|| invalid("truncated archive"))?;
+        if &header[58..60] != b"`\n" {
+            return Err(invalid("malformed archive member header"));
+        }
+        let size = number(field(&header[48..58]))?;
+        let start = offset + HEADER_SIZE;
+        let mut contents = start.checked_add(size).and_then(|end| data.get(start..end))
+            .ok_or_else(|| invalid("truncated archive"))?;
+        offset = start + size + size % 2;
+
+        let name = field(&header[0..16]);
+        let name = match name {
+            b"/" | b"/SYM64/" | b"__.SYMDEF" | b"__.SYMDEF SORTED" => continue,
+            b"//" => {
+                long_names = contents;
+                continue;
+            },
+            _ if name.starts_with(b"#1/") => {
+                let len = number(&name[3..])?;
+                let name = contents.get(..len).ok_or_else(|| invalid("truncated archive"))?;
+                contents = &contents[len..];
+                let end = name.iter().position(|&c| c == 0).unwrap_or(name.len());
+                &name[..end]
+            },
+            _ if name.len() > 1 && name[0] == b'/' => {
+                let name = long_names.get(number(&name[1..])?..)
+                    .ok_or_else(|| invalid("bad long member name"))?;
+                let end = name.windows(2).position(|w| w == b"/\n").unwrap_or(name.len());
+                &name[..end]
+            },
+            _ => name.strip_suffix(b"/").unwrap_or(name),
+        };
+        members.push(Member { name: String::from_utf8_lossy(name).into_owned(), data: contents });
+    }
+    Ok(members)
+}
+
+#[test]
+fn test_members() {
+    let header = |name: &str, size: usize| {
+        format!("{:<16}{:<12}{:<6}{:<6}{:<8}{:<10}`\n", name, 0, 0, 0, 644, size)
+    };
+    let mut data = MAGIC.to_vec();
+    data.extend(header("/", 4).bytes());
+    data.extend(b"\0\0\0\0");
+    data.extend(header("//", 22).bytes());
+    data.extend(b"a_rather_long_name.o/\n");
+    data.extend(header("short.o/", 3).bytes());
+    data.extend(b"abc\n");
+    data.extend(header("/0", 2).bytes());
+    data.extend(b"de");
+    data.extend(header("#1/6", 7).bytes());
+    data.extend(b"bsd.o\0f\n");
+    assert_eq!(members(&data).unwrap(), vec![
+        Member { name: "short.o".to_string(), data: b"abc" },
+        Member { name: "a_rather_long_name.o".to_string(), data: b"de" },
+        Member { name: "bsd.o".to_string(), data: b"f" },
+    ]);
+    assert!(members(&data[..data.len() - 3]).is_err());
+}
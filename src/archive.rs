@@ -0,0 +1,458 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+//! Reading and writing of tar archives in the POSIX ustar format. Old
+//! (Seventh Edition) archives, GNU long names and the path and linkpath
+//! records of pax extended headers are also understood when reading.
+use std::io;
+use std::io::{Read, Write};
+
+/// The size of a block; headers and data are padded to a whole number of
+/// blocks.
+pub const BLOCK_SIZE: usize = 512;
+/// The number of blocks in a record; archives are padded to a whole
+/// number of records.
+pub const BLOCKING_FACTOR: usize = 20;
+
+/// The type of an archive member.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    File,
+    HardLink,
+    Symlink,
+    CharDevice,
+    BlockDevice,
+    Dir,
+    Fifo,
+    /// Any other type flag.
+    Other(u8),
+}
+
+impl Kind {
+    fn from_flag(flag: u8) -> Kind {
+        match flag {
+            b'0' | b'\0' | b'7' => Kind::File,
+            b'1' => Kind::HardLink,
+            b'2' => Kind::Symlink,
+            b'3' => Kind::CharDevice,
+            b'4' => Kind::BlockDevice,
+            b'5' => Kind::Dir,
+            b'6' => Kind::Fifo,
+            _ => Kind::Other(flag),
+        }
+    }
+
+    fn flag(self) -> u8 {
+        match self {
+            Kind::File => b'0',
+            Kind::HardLink => b'1',
+            Kind::Symlink => b'2',
+            Kind::CharDevice => b'3',
+            Kind::BlockDevice => b'4',
+            Kind::Dir => b'5',
+            Kind::Fifo => b'6',
+            Kind::Other(flag) => flag,
+        }
+    }
+}
+
+/// The header of an archive member.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Header {
+    /// The path name. A directory's name ends with a slash.
+    pub name: Vec<u8>,
+    /// The permission bits, including the set-id and sticky bits.
+    pub mode: u32,
+    pub uid: u32,
+    pub gid: u32,
+    /// The size of the data that follows the header.
+    pub size: u64,
+    /// The modification time in seconds since the epoch.
+    pub mtime: i64,
+    pub kind: Kind,
+    /// The target of a link.
+    pub linkname: Vec<u8>,
+    pub uname: String,
+    pub gname: String,
+    pub devmajor: u32,
+    pub devminor: u32,
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+/// Returns a NUL-terminated field, or the whole field if there is no NUL.
+fn string_field(field: &[u8]) -> &[u8] {
+    let end = field.iter().position(|&c| c == 0).unwrap_or(field.len());
+    &field[..end]
+}
+
+/// Parses a numeric field: octal digits, optionally surrounded by blanks
+/// and NULs, or, if the high bit of the first byte is set, a big-endian
+/// base-256 number, as GNU tar writes for values too large for octal.
+fn parse_number(field: &[u8]) -> io::Result<u64> {
+    if field.first().is_some_and(|&c| c & 0x80 != 0) {
+        let mut n: u64 = (field[0] & 0x3f) as u64;
+        for &c in &field[1..] {
+            n = n.checked_mul(256).ok_or_else(|| invalid("numeric field overflow"))? + c as u64;
+        }
+        return Ok(n);
+    }
+    let text = string_field(field);
+    let text = std::str::from_utf8(text).map_err(|_| invalid("invalid numeric field"))?;
+    let text = text.trim_matches(|c| c == ' ' || c == '\0');
+    if text.is_empty() {
+        return Ok(0);
+    }
+    u64::from_str_radix(text, 8).map_err(|_| invalid("invalid numeric field"))
+}
+
+/// Formats a numeric field as zero-padded octal followed by a NUL, or in
+/// base-256 if the value is too large.
+fn format_number(field: &mut [u8], n: u64) {
+    let digits = field.len() - 1;
+    if digits >= 22 || n < 1 << (3 * digits) {
+        let text = format!("{:0width$o}", n, width = digits);
+        field[..digits].copy_from_slice(text.as_bytes());
+        field[digits] = 0;
+    } else {
+        let mut n = n;
+        for c in field.iter_mut().rev() {
+            *c = n as u8;
+            n >>= 8;
+        }
+        field[0] = 0x80;
+    }
+}
+
+/// Returns the checksum of a header block: the sum of its bytes, with the
+/// checksum field itself taken to be blanks.
+fn checksum(block: &[u8]) -> (u64, i64) {
+    let mut unsigned = 0u64;
+    let mut signed = 0i64;
+    for (i, &c) in block.iter().enumerate() {
+        let c = if (148..156).contains(&i) { b' ' } else { c };
+        unsigned += c as u64;
+        signed += c as i8 as i64;
+    }
+    (unsigned, signed)
+}
+
+impl Header {
+    /// Returns a header for a member of the given type, with the other
+    /// fields empty.
+    pub fn new(name: &[u8], kind: Kind) -> Header {
+        Header {
+            name: name.to_vec(),
+            mode: 0,
+            uid: 0,
+            gid: 0,
+            size: 0,
+            mtime: 0,
+            kind,
+            linkname: Vec::new(),
+            uname: String::new(),
+            gname: String::new(),
+            devmajor: 0,
+            devminor: 0,
+        }
+    }
+
+    /// Parses a header block. Returns None for a block of zeros, which
+    /// marks the end of the archive.
+    pub fn parse(block: &[u8]) -> io::Result<Option<Header>> {
+        if block.iter().all(|&c| c == 0) {
+            return Ok(None);
+        }
+        let sum = parse_number(&block[148..156])?;
+        let (unsigned, signed) = checksum(block);
+        if sum != unsigned && sum as i64 != signed {
+            return Err(invalid("header checksum error"));
+        }
+        let mut name = string_field(&block[0..100]).to_vec();
+        let ustar = &block[257..263] == b"ustar\0";
+        if ustar && block[345] != 0 {
+            let mut path = string_field(&block[345..500]).to_vec();
+            path.push(b'/');
+            path.extend_from_slice(&name);
+            name = path;
+        }
+        let kind = Kind::from_flag(block[156]);
+        // Old archives mark directories only by a trailing slash.
+        let kind = if kind == Kind::File && name.ends_with(b"/") { Kind::Dir } else { kind };
+        Ok(Some(Header {
+            name,
+            mode: parse_number(&block[100..108])? as u32 & 0o7777,
+            uid: parse_number(&block[108..116])? as u32,
+            gid: parse_number(&block[116..124])? as u32,
+            size: parse_number(&block[124..136])?,
+            mtime: parse_number(&block[136..148])? as i64,
+            kind,
+            linkname: string_field(&block[157..257]).to_vec(),
+            uname: String::from_utf8_lossy(string_field(&block[265..297])).into_owned(),
+            gname: String::from_utf8_lossy(string_field(&block[297..329])).into_owned(),
+            devmajor: parse_number(&block[329..337])? as u32,
+            devminor: parse_number(&block[337..345])? as u32,
+        }))
+    }
+
+    /// Formats the header as a block. A name longer than 100 bytes is
+    /// split at a slash between the prefix and name fields; if it can't
+    /// be, or the link name is longer than 100 bytes, an error is
+    /// returned.
+    pub fn to_block(&self) -> io::Result<[u8; BLOCK_SIZE]> {
+        let mut block = [0u8; BLOCK_SIZE];
+        let (prefix, name) = if self.name.len() <= 100 {
+            (&[][..], &self.name[..])
+        } else {
+            // Split at the first slash that leaves at most 100 bytes for
+            // the name, as long as the prefix fits in 155 bytes. A
+            // directory's trailing slash stays with the name.
+            let last = self.name.len() - 1;
+            let start = self.name.len() - 101;
+            let split = self.name[start..last].iter().position(|&c| c == b'/')
+                .map(|i| start + i)
+                .filter(|&i| i <= 155 && i > 0)
+                .ok_or_else(|| invalid("file name too long"))?;
+            (&self.name[..split], &self.name[split + 1..])
+        };
+        if self.linkname.len() > 100 {
+            return Err(invalid("link name too long"));
+        }
+        block[..name.len()].copy_from_slice(name);
+        format_number(&mut block[100..108], self.mode as u64);
+        format_number(&mut block[108..116], self.uid as u64);
+        format_number(&mut block[116..124], self.gid as u64);
+        format_number(&mut block[124..136], self.size);
+        format_number(&mut block[136..148], self.mtime.max(0) as u64);
+        block[156] = self.kind.flag();
+        block[157..157 + self.linkname.len()].copy_from_slice(&self.linkname);
+        block[257..263].copy_from_slice(b"ustar\0");
+        block[263..265].copy_from_slice(b"00");
+        let uname = &self.uname.as_bytes()[..self.uname.len().min(31)];
+        block[265..265 + uname.len()].copy_from_slice(uname);
+        let gname = &self.gname.as_bytes()[..self.gname.len().min(31)];
+        block[297..297 + gname.len()].copy_from_slice(gname);
+        format_number(&mut block[329..337], self.devmajor as u64);
+        format_number(&mut block[337..345], self.devminor as u64);
+        block[345..345 + prefix.len()].copy_from_slice(prefix);
+        let (sum, _) = checksum(&block);
+        let text = format!("{:06o}\0 ", sum);
+        block[148..156].copy_from_slice(text.as_bytes());
+        Ok(block)
+    }
+}
+
+/// Returns the number of bytes of padding that follow data of the given
+/// size.
+fn padding(size: u64) -> u64 {
+    (BLOCK_SIZE as u64 - size % BLOCK_SIZE as u64) % BLOCK_SIZE as u64
+}
+
+/// Reads the members of an archive.
+pub struct Reader<R: Read> {
+    inner: R,
+    /// The number of bytes of the current member's data, and its
+    /// padding, not yet read.
+    remaining: u64,
+    /// The number of bytes of the current member's data not yet read.
+    data: u64,
+}
+
+impl<R: Read> Reader<R> {
+    pub fn new(inner: R) -> Reader<R> {
+        Reader { inner, remaining: 0, data: 0 }
+    }
+
+    /// Reads a block, returning false at the end of the input.
+    fn read_block(&mut self, block: &mut [u8; BLOCK_SIZE]) -> io::Result<bool> {
+        let mut n = 0;
+        while n < BLOCK_SIZE {
+            match self.inner.read(&mut block[n..]) {
+                Ok(0) if n == 0 => return Ok(false),
+                Ok(0) => return Err(invalid("unexpected end of archive")),
+                Ok(m) => n += m,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(true)
+    }
+
+    /// Skips the rest of the current member.
+    fn skip(&mut self) -> io::Result<()> {
+        let n = io::copy(&mut (&mut self.inner).take(self.remaining), &mut io::sink())?;
+        if n < self.remaining {
+            return Err(invalid("unexpected end of archive"));
+        }
+        self.remaining = 0;
+        self.data = 0;
+        Ok(())
+    }
+
+    /// Reads the whole of the current member's data, which is expected to
+    /// be small.
+    fn read_small(&mut self, size: u64) -> io::Result<Vec<u8>> {
+        let mut data = Vec::new();
+        self.copy_data(&mut data)?;
+        data.truncate(size as usize);
+        self.skip()?;
+        Ok(data)
+    }
+
+    /// Returns the header of the next member, skipping any data of the
+    /// current one that hasn't been read. Returns None at the end of the
+    /// archive.
+    pub fn next_header(&mut self) -> io::Result<Option<Header>> {
+        self.skip()?;
+        let mut long_name: Option<Vec<u8>> = None;
+        let mut long_link: Option<Vec<u8>> = None;
+        let mut block = [0u8; BLOCK_SIZE];
+        loop {
+            if !self.read_block(&mut block)? {
+                return Ok(None);
+            }
+            let mut header = match Header::parse(&block)? {
+                Some(header) => header,
+                None => return Ok(None),
+            };
+            self.data = header.size;
+            self.remaining = header.size + padding(header.size);
+            match header.kind {
+                // GNU long names and link names.
+                Kind::Other(b'L') => long_name = Some(self.read_small(header.size)?),
+                Kind::Other(b'K') => long_link = Some(self.read_small(header.size)?),
+                // pax extended headers, of "length key=value\n" records.
+                Kind::Other(b'x') => {
+                    let records = self.read_small(header.size)?;
+                    for (key, value) in pax_records(&records) {
+                        match key {
+                            b"path" => long_name = Some(value.to_vec()),
+                            b"linkpath" => long_link = Some(value.to_vec()),
+                            _ => (),
+                        }
+                    }
+                },
+                Kind::Other(b'g') => self.skip()?,
+                _ => {
+                    if let Some(name) = long_name.take() {
+                        header.name = string_field(&name).to_vec();
+                    }
+                    if let Some(link) = long_link.take() {
+                        header.linkname = string_field(&link).to_vec();
+                    }
+                    // Only regular files have data in the archive.
+                    if matches!(header.kind, Kind::HardLink | Kind::Symlink | Kind::Dir) {
+                        self.data = 0;
+                    }
+                    return Ok(Some(header));
+                },
+            }
+        }
+    }
+
+    /// Copies the current member's data to the output.
+    pub fn copy_data(&mut self, out: &mut dyn Write) -> io::Result<u64> {
+        let n = io::copy(&mut (&mut self.inner).take(self.data), out)?;
+        if n < self.data {
+            return Err(invalid("unexpected end of archive"));
+        }
+        self.remaining -= n;
+        self.data = 0;
+        Ok(n)
+    }
+}
+
+/// Splits the contents of a pax extended header into key-value pairs.
+fn pax_records(data: &[u8]) -> Vec<(&[u8], &[u8])> {
+    let mut records = Vec::new();
+    let mut rest = data;
+    while let Some(space) = rest.iter().position(|&c| c == b' ') {
+        let len: usize = match std::str::from_utf8(&rest[..space]).ok()
+            .and_then(|s| s.parse().ok()) {
+            Some(len) if len > space + 1 && len <= rest.len() => len,
+            _ => break,
+        };
+        let record = &rest[space + 1..len - 1];
+        if let Some(eq) = record.iter().position(|&c| c == b'=') {
+            records.push((&record[..eq], &record[eq + 1..]));
+        }
+        rest = &rest[len..];
+    }
+    records
+}
+
+/// Writes the members of an archive.
+pub struct Writer<W: Write> {
+    inner: W,
+    /// The number of blocks written.
+    blocks: u64,
+}
+
+impl<W: Write> Writer<W> {
+    pub fn new(inner: W) -> Writer<W> {
+        Writer { inner, blocks: 0 }
+    }
+
+    /// Appends a member. If the header can't be formatted, nothing is
+    /// written. Otherwise exactly the header's size of data is written:
+    /// if the input is shorter, because a file shrank while being
+    /// archived, the data is padded with zeros.
+    pub fn append(&mut self, header: &Header, data: &mut dyn Read) -> io::Result<()> {
+        self.inner.write_all(&header.to_block()?)?;
+        let n = io::copy(&mut data.take(header.size), &mut self.inner)?;
+        let zeros = header.size - n + padding(header.size);
+        io::copy(&mut io::repeat(0).take(zeros), &mut self.inner)?;
+        self.blocks += 1 + header.size.div_ceil(BLOCK_SIZE as u64);
+        Ok(())
+    }
+
+    /// Writes the two zero blocks that end an archive, padded to a whole
+    /// record, returning the underlying writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        let blocks = self.blocks + 2;
+        let record = BLOCKING_FACTOR as u64;
+        let total = blocks.div_ceil(record) * record;
+        let zeros = (total - self.blocks) * BLOCK_SIZE as u64;
+        io::copy(&mut io::repeat(0).take(zeros), &mut self.inner)?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+#[test]
+fn test_round_trip() {
+    let mut file = Header::new(b"dir/file", Kind::File);
+    file.mode = 0o644;
+    file.size = 5;
+    file.mtime = 1614834367;
+    file.uname = "james".to_string();
+    let long: Vec<u8> = [&b"d".repeat(120)[..], b"/", &b"f".repeat(90)[..]].concat();
+    let mut dir = long.clone();
+    dir.push(b'/');
+    let dir = Header::new(&dir, Kind::Dir);
+    let mut link = Header::new(&long, Kind::Symlink);
+    link.linkname = b"dir/file".to_vec();
+
+    let mut writer = Writer::new(Vec::new());
+    writer.append(&file, &mut &b"hello"[..]).unwrap();
+    writer.append(&link, &mut io::empty()).unwrap();
+    writer.append(&dir, &mut io::empty()).unwrap();
+    let archive = writer.finish().unwrap();
+    assert_eq!(archive.len(), BLOCK_SIZE * BLOCKING_FACTOR);
+
+    let mut reader = Reader::new(&archive[..]);
+    assert_eq!(reader.next_header().unwrap(), Some(file));
+    let mut data = Vec::new();
+    reader.copy_data(&mut data).unwrap();
+    assert_eq!(data, b"hello");
+    assert_eq!(reader.next_header().unwrap(), Some(link));
+    assert_eq!(reader.next_header().unwrap(), Some(dir));
+    assert_eq!(reader.next_header().unwrap(), None);
+
+    assert!(Header::new(&b"x".repeat(101), Kind::File).to_block().is_err());
+    let mut big = [0u8; 12];
+    format_number(&mut big, 1 << 40);
+    assert_eq!(parse_number(&big).unwrap(), 1 << 40);
+}
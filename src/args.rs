@@ -0,0 +1,183 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+//! Command line arguments. The commands are given their arguments as
+//! OsStrings, so that file names that aren't valid UTF-8 can be passed to
+//! them.
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::process;
+
+use crate::err;
+use crate::status;
+
+/// Returns the arguments as Strings. If any isn't valid UTF-8, says so
+/// and exits.
+pub fn strings(args: Vec<OsString>) -> Vec<String> {
+    args.into_iter().map(|arg| arg.into_string().unwrap_or_else(|arg| {
+        err!("{}: argument is not valid UTF-8", arg.to_string_lossy());
+        process::exit(status::USAGE);
+    })).collect()
+}
+
+/// Returns the argument as a str. If it isn't valid UTF-8, says so and
+/// exits.
+pub fn str(arg: &OsStr) -> &str {
+    arg.to_str().unwrap_or_else(|| {
+        err!("{}: argument is not valid UTF-8", arg.to_string_lossy());
+        process::exit(status::USAGE);
+    })
+}
+
+/// Removes the long option, such as --json, from among the options that
+/// precede the first operand or "--", and returns whether it was there.
+/// The first argument is the command's name.
+pub fn long_flag<S: AsRef<OsStr>>(args: &mut Vec<S>, name: &str) -> bool {
+    for i in 1..args.len() {
+        let arg = args[i].as_ref().as_encoded_bytes();
+        if arg.strip_prefix(b"--") == Some(name.as_bytes()) {
+            args.remove(i);
+            return true;
+        }
+        if arg == b"--" || arg.len() < 2 || arg[0] != b'-' {
+            break;
+        }
+    }
+    false
+}
+
+/// Removes the long option, given as --name or --name=value, from among
+/// the options that precede the first operand or "--", and returns it:
+/// None if it wasn't there, otherwise its value, if it had one. The first
+/// argument is the command's name.
+pub fn long_option(args: &mut Vec<OsString>, name: &str) -> Option<Option<OsString>> {
+    for i in 1..args.len() {
+        let arg = args[i].as_encoded_bytes();
+        if let Some(rest) = arg.strip_prefix(b"--").and_then(|rest| rest.strip_prefix(name.as_bytes())) {
+            if rest.is_empty() {
+                args.remove(i);
+                return Some(None);
+            }
+            if let Some(value) = rest.strip_prefix(b"=") {
+                // SAFETY: the value follows the ASCII "=", so it is as
+                // valid as the argument it came from.
+                let value = unsafe { OsString::from_encoded_bytes_unchecked(value.to_vec()) };
+                args.remove(i);
+                return Some(Some(value));
+            }
+        }
+        if arg == b"--" || arg.len() < 2 || arg[0] != b'-' {
+            break;
+        }
+    }
+    None
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Arg {
+    Opt(char),
+    OptWithArg(char, OsString),
+    Arg(OsString),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    UnknownOpt(char),
+    MissingOptArg(char),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::UnknownOpt(c) => write!(f, "unknown option: -{}", c),
+            Error::MissingOptArg(c) => write!(f, "missing argument for option: -{}", c),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// A parser of options, as specified by a string of the option letters,
+/// each followed by a colon if it takes an argument. Options end at the
+/// first operand or at "--".
+pub struct GetOpt<'a, I: Iterator<Item = OsString>> {
+    spec: &'a str,
+    args: I,
+    /// The rest of a group of options, as in -rf.
+    group: Vec<u8>,
+    done: bool,
+}
+
+impl<'a, I: Iterator<Item = OsString>> GetOpt<'a, I> {
+    pub fn new(spec: &'a str, args: I) -> GetOpt<'a, I> {
+        GetOpt { spec, args, group: Vec::new(), done: false }
+    }
+
+    /// Returns whether the option takes an argument, or None if there is
+    /// no such option. Options are ASCII letters and digits.
+    fn takes_arg(&self, c: char) -> Option<bool> {
+        if !c.is_ascii_alphanumeric() {
+            return None;
+        }
+        let i = self.spec.find(c)?;
+        Some(self.spec[i + 1..].starts_with(':'))
+    }
+}
+
+impl<I: Iterator<Item = OsString>> Iterator for GetOpt<'_, I> {
+    type Item = Result<Arg, Error>;
+
+    fn next(&mut self) -> Option<Result<Arg, Error>> {
+        if self.group.is_empty() {
+            let arg = self.args.next()?;
+            let bytes = arg.as_encoded_bytes();
+            if self.done || bytes.len() < 2 || bytes[0] != b'-' {
+                self.done = true;
+                return Some(Ok(Arg::Arg(arg)));
+            }
+            if bytes == b"--" {
+                self.done = true;
+                return self.args.next().map(|arg| Ok(Arg::Arg(arg)));
+            }
+            self.group = bytes[1..].iter().rev().copied().collect();
+        }
+        let c = self.group.pop().unwrap() as char;
+        Some(match self.takes_arg(c) {
+            None => Err(Error::UnknownOpt(c)),
+            Some(false) => Ok(Arg::Opt(c)),
+            Some(true) if !self.group.is_empty() => {
+                let rest: Vec<u8> = self.group.drain(..).rev().collect();
+                // SAFETY: the bytes follow the valid UTF-8 "-" and an
+                // option letter, which takes_arg allows only in ASCII.
+                Ok(Arg::OptWithArg(c, unsafe { OsString::from_encoded_bytes_unchecked(rest) }))
+            },
+            Some(true) => match self.args.next() {
+                Some(arg) => Ok(Arg::OptWithArg(c, arg)),
+                None => Err(Error::MissingOptArg(c)),
+            },
+        })
+    }
+}
+
+#[test]
+fn test_getopt() {
+    use std::os::unix::ffi::OsStringExt;
+
+    let args = |s: &str| s.split(' ').map(OsString::from).collect::<Vec<_>>().into_iter();
+    let parsed: Vec<_> = GetOpt::new("ab:c", args("-ac -bx -b y -- -c z")).collect();
+    assert_eq!(parsed, [Ok(Arg::Opt('a')), Ok(Arg::Opt('c')),
+                        Ok(Arg::OptWithArg('b', OsString::from("x"))),
+                        Ok(Arg::OptWithArg('b', OsString::from("y"))),
+                        Ok(Arg::Arg(OsString::from("-c"))), Ok(Arg::Arg(OsString::from("z")))]);
+    let parsed: Vec<_> = GetOpt::new("a", args("-a - -a")).collect();
+    assert_eq!(parsed, [Ok(Arg::Opt('a')), Ok(Arg::Arg(OsString::from("-"))),
+                        Ok(Arg::Arg(OsString::from("-a")))]);
+    let parsed: Vec<_> = GetOpt::new("b:", args("-x -b")).collect();
+    assert_eq!(parsed, [Err(Error::UnknownOpt('x')), Err(Error::MissingOptArg('b'))]);
+    let parsed: Vec<_> = GetOpt::new("é:", args("-é")).collect();
+    assert_eq!(parsed, [Err(Error::UnknownOpt('Ã')), Err(Error::UnknownOpt('©'))]);
+
+    let name = OsString::from_vec(b"caf\xe9".to_vec());
+    let parsed: Vec<_> = GetOpt::new("", vec![name.clone()].into_iter()).collect();
+    assert_eq!(parsed, [Ok(Arg::Arg(name))]);
+}
@@ -0,0 +1,87 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+//! Atomic file replacement. Output is written to a temporary file in the
+//! same directory as the target, which is renamed over the target once
+//! complete so that readers never see a partially written file.
+use std::ffi::OsString;
+use std::fs;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use crate::trace;
+
+/// A file that replaces its target when committed. If it is dropped
+/// without being committed, the temporary file is removed and the target
+/// is left untouched.
+pub struct AtomicFile {
+    path: PathBuf,
+    tmp: PathBuf,
+    file: File,
+    committed: bool,
+}
+
+impl AtomicFile {
+    /// Creates a temporary file that will replace the file at `path`.
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<AtomicFile> {
+        let path = path.as_ref().to_path_buf();
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+        let name = match path.file_name() {
+            Some(name) => name.to_os_string(),
+            None => return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                              "invalid file name")),
+        };
+        let mut n = 0;
+        loop {
+            let mut tmp_name = OsString::from(".");
+            tmp_name.push(&name);
+            tmp_name.push(format!(".{}.{}", process::id(), n));
+            let tmp = dir.join(tmp_name);
+            let result = OpenOptions::new().write(true).create_new(true).open(&tmp);
+            match trace::op("create", format_args!("{:?}", tmp), result) {
+                Ok(file) => return Ok(AtomicFile { path, tmp, file, committed: false }),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists && n < 100 => n += 1,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Replaces the target with the data written so far, preserving the
+    /// permissions of the file being replaced.
+    pub fn commit(mut self) -> io::Result<()> {
+        self.file.flush()?;
+        self.file.sync_all()?;
+        if let Ok(md) = fs::metadata(&self.path) {
+            trace::op("chmod", format_args!("{:?}", self.tmp),
+                      fs::set_permissions(&self.tmp, md.permissions()))?;
+        }
+        trace::op("rename", format_args!("{:?}, {:?}", self.tmp, self.path),
+                  fs::rename(&self.tmp, &self.path))?;
+        self.committed = true;
+        Ok(())
+    }
+}
+
+impl Write for AtomicFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.file.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+impl Drop for AtomicFile {
+    fn drop(&mut self) {
+        if !self.committed {
+            let _ = trace::op("unlink", format_args!("{:?}", self.tmp), fs::remove_file(&self.tmp));
+        }
+    }
+}
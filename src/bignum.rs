@@ -0,0 +1,582 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+//! Arbitrary-precision decimal numbers, as used by dc(1) and bc(1). A
+//! number is an integer scaled down by a power of ten, its scale being the
+//! number of digits after the decimal point. The operations follow bc's
+//! rules for the scale of their results, truncating rather than rounding.
+use std::cmp::Ordering;
+use std::fmt;
+
+/// The base of the limbs of a magnitude, and the decimal digits in one.
+const BASE: u64 = 1_000_000_000;
+const BASE_DIGITS: usize = 9;
+
+/// The length of an output line, including the backslash that continues
+/// it.
+pub const LINE_LENGTH: usize = 70;
+
+/// A non-negative integer in base 10^9, least significant limb first and
+/// with no high zero limbs.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct Nat(Vec<u32>);
+
+impl Nat {
+    fn from_u64(mut n: u64) -> Nat {
+        let mut limbs = Vec::new();
+        while n > 0 {
+            limbs.push((n % BASE) as u32);
+            n /= BASE;
+        }
+        Nat(limbs)
+    }
+
+    fn trim(mut self) -> Nat {
+        while self.0.last() == Some(&0) {
+            self.0.pop();
+        }
+        self
+    }
+
+    fn is_zero(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    fn to_u64(&self) -> Option<u64> {
+        let mut n: u64 = 0;
+        for &limb in self.0.iter().rev() {
+            n = n.checked_mul(BASE)?.checked_add(limb as u64)?;
+        }
+        Some(n)
+    }
+
+    fn cmp(&self, other: &Nat) -> Ordering {
+        self.0.len().cmp(&other.0.len()).then_with(|| self.0.iter().rev().cmp(other.0.iter().rev()))
+    }
+
+    fn add(&self, other: &Nat) -> Nat {
+        let mut limbs = Vec::with_capacity(self.0.len().max(other.0.len()) + 1);
+        let mut carry = 0;
+        for i in 0..self.0.len().max(other.0.len()) {
+            let sum = *self.0.get(i).unwrap_or(&0) as u64 + *other.0.get(i).unwrap_or(&0) as u64 + carry;
+            limbs.push((sum % BASE) as u32);
+            carry = sum / BASE;
+        }
+        if carry > 0 {
+            limbs.push(carry as u32);
+        }
+        Nat(limbs)
+    }
+
+    /// Subtracts a number no greater than this one.
+    fn sub(&self, other: &Nat) -> Nat {
+        let mut limbs = Vec::with_capacity(self.0.len());
+        let mut borrow = 0;
+        for i in 0..self.0.len() {
+            let mut diff = self.0[i] as i64 - *other.0.get(i).unwrap_or(&0) as i64 - borrow;
+            borrow = (diff < 0) as i64;
+            if diff < 0 {
+                diff += BASE as i64;
+            }
+            limbs.push(diff as u32);
+        }
+        Nat(limbs).trim()
+    }
+
+    fn mul_small(&self, m: u32) -> Nat {
+        let mut limbs = Vec::with_capacity(self.0.len() + 1);
+        let mut carry = 0;
+        for &limb in &self.0 {
+            let product = limb as u64 * m as u64 + carry;
+            limbs.push((product % BASE) as u32);
+            carry = product / BASE;
+        }
+        if carry > 0 {
+            limbs.push(carry as u32);
+        }
+        Nat(limbs).trim()
+    }
+
+    fn mul(&self, other: &Nat) -> Nat {
+        if self.is_zero() || other.is_zero() {
+            return Nat::default();
+        }
+        let mut limbs = vec![0u64; self.0.len() + other.0.len()];
+        for (i, &a) in self.0.iter().enumerate() {
+            let mut carry = 0;
+            for (j, &b) in other.0.iter().enumerate() {
+                let t = limbs[i + j] + a as u64 * b as u64 + carry;
+                limbs[i + j] = t % BASE;
+                carry = t / BASE;
+            }
+            limbs[i + other.0.len()] += carry;
+        }
+        Nat(limbs.into_iter().map(|limb| limb as u32).collect()).trim()
+    }
+
+    fn divmod_small(&self, d: u32) -> (Nat, u32) {
+        let mut limbs = vec![0; self.0.len()];
+        let mut rem = 0;
+        for i in (0..self.0.len()).rev() {
+            let t = rem * BASE + self.0[i] as u64;
+            limbs[i] = (t / d as u64) as u32;
+            rem = t % d as u64;
+        }
+        (Nat(limbs).trim(), rem as u32)
+    }
+
+    /// Divides by a non-zero number, returning the quotient and remainder.
+    fn divmod(&self, d: &Nat) -> (Nat, Nat) {
+        if self.cmp(d) == Ordering::Less {
+            return (Nat::default(), self.clone());
+        }
+        if d.0.len() == 1 {
+            let (q, r) = self.divmod_small(d.0[0]);
+            return (q, Nat::from_u64(r as u64));
+        }
+        // Knuth's algorithm D, normalised so that the top limb of the
+        // divisor is at least half the base.
+        let f = (BASE / (*d.0.last().unwrap() as u64 + 1)) as u32;
+        let mut u = self.mul_small(f).0;
+        u.resize(self.0.len() + 1, 0);
+        let v = d.mul_small(f).0;
+        let n = v.len();
+        let mut q = vec![0; u.len() - n];
+        for j in (0..u.len() - n).rev() {
+            let top = u[j + n] as u64 * BASE + u[j + n - 1] as u64;
+            let mut qhat = top / v[n - 1] as u64;
+            let mut rhat = top % v[n - 1] as u64;
+            while qhat >= BASE || qhat * v[n - 2] as u64 > rhat * BASE + u[j + n - 2] as u64 {
+                qhat -= 1;
+                rhat += v[n - 1] as u64;
+                if rhat >= BASE {
+                    break;
+                }
+            }
+            let mut borrow = 0;
+            let mut carry = 0;
+            for i in 0..n {
+                let p = qhat * v[i] as u64 + carry;
+                carry = p / BASE;
+                let t = u[i + j] as i64 - (p % BASE) as i64 - borrow;
+                borrow = (t < 0) as i64;
+                u[i + j] = if t < 0 { (t + BASE as i64) as u32 } else { t as u32 };
+            }
+            let t = u[j + n] as i64 - carry as i64 - borrow;
+            if t < 0 {
+                // The estimate was one too many, so add the divisor back.
+                qhat -= 1;
+                let mut carry = 0;
+                for i in 0..n {
+                    let s = u[i + j] as u64 + v[i] as u64 + carry;
+                    u[i + j] = (s % BASE) as u32;
+                    carry = s / BASE;
+                }
+                u[j + n] = ((t + BASE as i64) as u64 + carry - BASE) as u32;
+            } else {
+                u[j + n] = t as u32;
+            }
+            q[j] = qhat as u32;
+        }
+        u.truncate(n);
+        let (r, _) = Nat(u).trim().divmod_small(f);
+        (Nat(q).trim(), r)
+    }
+
+    fn pow10(n: usize) -> Nat {
+        Nat::from_u64(1).mul_pow10(n)
+    }
+
+    fn mul_pow10(&self, n: usize) -> Nat {
+        if self.is_zero() {
+            return Nat::default();
+        }
+        let mut limbs = vec![0; n / BASE_DIGITS];
+        limbs.extend_from_slice(&self.0);
+        Nat(limbs).mul_small(10u32.pow((n % BASE_DIGITS) as u32))
+    }
+
+    /// Divides by a power of ten, truncating.
+    fn div_pow10(&self, n: usize) -> Nat {
+        let skip = n / BASE_DIGITS;
+        if skip >= self.0.len() {
+            return Nat::default();
+        }
+        Nat(self.0[skip..].to_vec()).divmod_small(10u32.pow((n % BASE_DIGITS) as u32)).0
+    }
+
+    /// Returns the number of decimal digits, which is 0 for 0.
+    fn digits(&self) -> usize {
+        match self.0.last() {
+            Some(top) => (self.0.len() - 1) * BASE_DIGITS + top.to_string().len(),
+            None => 0,
+        }
+    }
+
+    fn to_decimal(&self) -> String {
+        let mut s = match self.0.last() {
+            Some(top) => top.to_string(),
+            None => return "0".to_string(),
+        };
+        for limb in self.0.iter().rev().skip(1) {
+            s.push_str(&format!("{:09}", limb));
+        }
+        s
+    }
+
+    /// Returns the integer square root, the largest whose square is no
+    /// greater than this number.
+    fn sqrt(&self) -> Nat {
+        if self.is_zero() {
+            return Nat::default();
+        }
+        // Newton's method, from a power of ten no less than the root.
+        let mut x = Nat::pow10(self.digits().div_ceil(2));
+        loop {
+            let y = x.add(&self.divmod(&x).0).divmod_small(2).0;
+            if y.cmp(&x) != Ordering::Less {
+                return x;
+            }
+            x = y;
+        }
+    }
+}
+
+/// An arbitrary-precision decimal number.
+#[derive(Clone, Debug, Default)]
+pub struct Number {
+    negative: bool,
+    mag: Nat,
+    scale: usize,
+}
+
+impl Number {
+    fn new(negative: bool, mag: Nat, scale: usize) -> Number {
+        Number { negative: negative && !mag.is_zero(), mag, scale }
+    }
+
+    pub fn zero() -> Number {
+        Number::default()
+    }
+
+    pub fn from_i64(n: i64) -> Number {
+        Number::new(n < 0, Nat::from_u64(n.unsigned_abs()), 0)
+    }
+
+    /// Parses the digits of a number in the given base, with an optional
+    /// point. Digits may be 0-9 and A-F, whatever the base. The scale of
+    /// the number is the number of digits after the point.
+    pub fn parse(s: &str, ibase: u32) -> Option<Number> {
+        let (int, frac) = match s.split_once('.') {
+            Some((int, frac)) => (int, frac),
+            None => (s, ""),
+        };
+        let digit = |c: char| c.to_digit(16).filter(|_| !c.is_ascii_lowercase());
+        if int.is_empty() && frac.is_empty() {
+            return None;
+        }
+        let mut mag = Nat::default();
+        for c in int.chars() {
+            mag = mag.mul_small(ibase).add(&Nat::from_u64(digit(c)? as u64));
+        }
+        let scale = frac.len();
+        if ibase == 10 {
+            for c in frac.chars() {
+                mag = mag.mul_small(10).add(&Nat::from_u64(digit(c)? as u64));
+            }
+            return Some(Number::new(false, mag, scale));
+        }
+        // The fraction in another base is converted to as many decimal
+        // digits as it has.
+        let mut num = Nat::default();
+        let mut den = Nat::from_u64(1);
+        for c in frac.chars() {
+            num = num.mul_small(ibase).add(&Nat::from_u64(digit(c)? as u64));
+            den = den.mul_small(ibase);
+        }
+        let frac = num.mul_pow10(scale).divmod(&den).0;
+        Some(Number::new(false, mag.mul_pow10(scale).add(&frac), scale))
+    }
+
+    pub fn scale(&self) -> usize {
+        self.scale
+    }
+
+    /// Returns the number of significant decimal digits.
+    pub fn length(&self) -> usize {
+        self.mag.digits().max(self.scale).max(1)
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.mag.is_zero()
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    pub fn neg(&self) -> Number {
+        Number::new(!self.negative, self.mag.clone(), self.scale)
+    }
+
+    /// Returns the integer part, if it fits.
+    pub fn to_i64(&self) -> Option<i64> {
+        let n = i64::try_from(self.mag.div_pow10(self.scale).to_u64()?).ok()?;
+        Some(if self.negative { -n } else { n })
+    }
+
+    /// Returns the number with its scale reduced, truncating it, or
+    /// increased.
+    pub fn rescale(&self, scale: usize) -> Number {
+        let mag = match scale.cmp(&self.scale) {
+            Ordering::Less => self.mag.div_pow10(self.scale - scale),
+            Ordering::Equal => self.mag.clone(),
+            Ordering::Greater => self.mag.mul_pow10(scale - self.scale),
+        };
+        Number::new(self.negative, mag, scale)
+    }
+
+    fn truncate(self, scale: usize) -> Number {
+        if scale < self.scale { self.rescale(scale) } else { self }
+    }
+
+    pub fn add(&self, other: &Number) -> Number {
+        let scale = self.scale.max(other.scale);
+        let a = self.mag.mul_pow10(scale - self.scale);
+        let b = other.mag.mul_pow10(scale - other.scale);
+        if self.negative == other.negative {
+            return Number::new(self.negative, a.add(&b), scale);
+        }
+        match a.cmp(&b) {
+            Ordering::Less => Number::new(other.negative, b.sub(&a), scale),
+            _ => Number::new(self.negative, a.sub(&b), scale),
+        }
+    }
+
+    pub fn sub(&self, other: &Number) -> Number {
+        self.add(&other.neg())
+    }
+
+    fn mul_exact(&self, other: &Number) -> Number {
+        Number::new(self.negative != other.negative, self.mag.mul(&other.mag), self.scale + other.scale)
+    }
+
+    /// Multiplies, keeping no more digits after the point than the
+    /// operands have together, nor fewer than the greatest of the scale
+    /// and theirs.
+    pub fn mul(&self, other: &Number, scale: usize) -> Number {
+        let scale = (self.scale + other.scale).min(scale.max(self.scale).max(other.scale));
+        self.mul_exact(other).truncate(scale)
+    }
+
+    /// Divides to the given scale, or returns None if the divisor is zero.
+    pub fn div(&self, other: &Number, scale: usize) -> Option<Number> {
+        if other.is_zero() {
+            return None;
+        }
+        let shift = (other.scale + scale) as isize - self.scale as isize;
+        let q = if shift >= 0 {
+            self.mag.mul_pow10(shift as usize).divmod(&other.mag).0
+        } else {
+            self.mag.divmod(&other.mag.mul_pow10(-shift as usize)).0
+        };
+        Some(Number::new(self.negative != other.negative, q, scale))
+    }
+
+    /// Returns the remainder of dividing to the given scale, or None if the
+    /// divisor is zero.
+    pub fn rem(&self, other: &Number, scale: usize) -> Option<Number> {
+        let q = self.div(other, scale)?;
+        Some(self.sub(&q.mul_exact(other)))
+    }
+
+    /// Raises to an integer power, or returns None for a negative power of
+    /// zero.
+    pub fn pow(&self, exp: i64, scale: usize) -> Option<Number> {
+        let mut n = exp.unsigned_abs();
+        let mut result = Number::from_i64(1);
+        let mut base = self.clone();
+        while n > 0 {
+            if n & 1 == 1 {
+                result = result.mul_exact(&base);
+            }
+            n >>= 1;
+            if n > 0 {
+                base = base.mul_exact(&base);
+            }
+        }
+        if exp < 0 {
+            return Number::from_i64(1).div(&result, scale);
+        }
+        let max = self.scale.saturating_mul(exp as usize);
+        Some(result.truncate(max.min(scale.max(self.scale))))
+    }
+
+    /// Returns the square root to the greater of the given scale and the
+    /// number's, or None if it is negative.
+    pub fn sqrt(&self, scale: usize) -> Option<Number> {
+        if self.negative {
+            return None;
+        }
+        let scale = scale.max(self.scale);
+        Some(Number::new(false, self.mag.mul_pow10(2 * scale - self.scale).sqrt(), scale))
+    }
+
+    /// Formats the number in the given output base. Digits in bases over
+    /// 16 are written as decimal numbers, each preceded by a space. Zero is
+    /// written as 0, whatever its scale.
+    pub fn to_string_radix(&self, obase: u32) -> String {
+        let mut s = String::new();
+        if self.negative {
+            s.push('-');
+        }
+        let int = self.mag.div_pow10(self.scale);
+        let frac = self.mag.sub(&int.mul_pow10(self.scale));
+        if obase == 10 {
+            let digits = self.mag.to_decimal();
+            if int.is_zero() {
+                if self.scale == 0 || self.mag.is_zero() {
+                    return "0".to_string();
+                }
+                s.push('.');
+                s.push_str(&"0".repeat(self.scale.saturating_sub(digits.len())));
+                s.push_str(&digits);
+            } else {
+                let (int, frac) = digits.split_at(digits.len() - self.scale);
+                s.push_str(int);
+                if !frac.is_empty() {
+                    s.push('.');
+                    s.push_str(frac);
+                }
+            }
+            return s;
+        }
+        let width = (obase - 1).to_string().len();
+        let digit = |d: u32| {
+            if obase <= 16 {
+                char::from_digit(d, 16).unwrap().to_ascii_uppercase().to_string()
+            } else {
+                format!(" {:0width$}", d, width = width)
+            }
+        };
+        if int.is_zero() {
+            if self.scale == 0 || self.mag.is_zero() {
+                return digit(0);
+            }
+        } else {
+            let mut digits = Vec::new();
+            let mut n = int;
+            while !n.is_zero() {
+                let (q, r) = n.divmod_small(obase);
+                digits.push(digit(r));
+                n = q;
+            }
+            s.extend(digits.into_iter().rev());
+        }
+        if self.scale > 0 {
+            // As many digits as it takes to be as precise as the scale.
+            s.push('.');
+            let one = Nat::pow10(self.scale);
+            let mut precision = Nat::from_u64(1);
+            let mut frac = frac;
+            while precision.cmp(&one) == Ordering::Less {
+                let (d, r) = frac.mul_small(obase).divmod(&one);
+                s.push_str(&digit(d.to_u64().unwrap_or(0) as u32));
+                frac = r;
+                precision = precision.mul_small(obase);
+            }
+        }
+        s
+    }
+}
+
+impl PartialEq for Number {
+    fn eq(&self, other: &Number) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Number {}
+
+impl PartialOrd for Number {
+    fn partial_cmp(&self, other: &Number) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Number {
+    fn cmp(&self, other: &Number) -> Ordering {
+        let diff = self.sub(other);
+        if diff.is_zero() {
+            Ordering::Equal
+        } else if diff.negative {
+            Ordering::Less
+        } else {
+            Ordering::Greater
+        }
+    }
+}
+
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.to_string_radix(10))
+    }
+}
+
+/// Breaks a long line of output into lines of LINE_LENGTH, each but the
+/// last ending with a backslash.
+pub fn wrap(s: &str) -> String {
+    let chars: Vec<char> = s.chars().collect();
+    let mut out = String::new();
+    for (i, chunk) in chars.chunks(LINE_LENGTH - 1).enumerate() {
+        if i > 0 {
+            out.push_str("\\\n");
+        }
+        out.extend(chunk);
+    }
+    out
+}
+
+#[test]
+fn test_bignum() {
+    let n = |s: &str| {
+        let (negative, s) = match s.strip_prefix('-') {
+            Some(s) => (true, s),
+            None => (false, s),
+        };
+        let n = Number::parse(s, 10).unwrap();
+        if negative { n.neg() } else { n }
+    };
+    assert_eq!(n("1.50").add(&n("-3.2")).to_string(), "-1.70");
+    assert_eq!(n("0.05").to_string(), ".05");
+    assert_eq!(n("0.000").to_string(), "0");
+    assert_eq!(n("2.5").mul(&n("2.5"), 0).to_string(), "6.2");
+    assert_eq!(n("2.5").mul(&n("2.5"), 5).to_string(), "6.25");
+    assert_eq!(n("1").div(&n("3"), 20).unwrap().to_string(), ".33333333333333333333");
+    assert_eq!(n("-7").div(&n("2"), 0).unwrap().to_string(), "-3");
+    assert!(n("1").div(&n("0"), 0).is_none());
+    assert_eq!(n("7").rem(&n("3"), 0).unwrap().to_string(), "1");
+    assert_eq!(n("7").rem(&n("3"), 2).unwrap().to_string(), ".01");
+    assert_eq!(n("2").pow(100, 0).unwrap().to_string(), "1267650600228229401496703205376");
+    assert_eq!(n("2").pow(-2, 3).unwrap().to_string(), ".250");
+    assert_eq!(n("1.1").pow(2, 0).unwrap().to_string(), "1.2");
+    assert_eq!(n("2").sqrt(10).unwrap().to_string(), "1.4142135623");
+    assert_eq!(n("16").sqrt(0).unwrap().to_string(), "4");
+
+    let big = n("123456789012345678901234567890123456789");
+    let d = n("987654321987654321");
+    let q = big.div(&d, 0).unwrap();
+    let r = big.rem(&d, 0).unwrap();
+    assert_eq!(q.to_string(), "124999998748437501153");
+    assert_eq!(q.mul(&d, 0).add(&r), big);
+    assert!(r < d && !r.is_negative());
+
+    assert_eq!(n("255").to_string_radix(16), "FF");
+    assert_eq!(n("-10.5").to_string_radix(2), "-1010.1000");
+    assert_eq!(n("1000").to_string_radix(100), " 10 00");
+    assert_eq!(Number::parse("FF", 16).unwrap().to_string(), "255");
+    assert_eq!(Number::parse("0.1", 2).unwrap().to_string(), ".5");
+    assert_eq!(n("123.45").length(), 5);
+    assert_eq!(n("-12.7").to_i64(), Some(-12));
+    assert_eq!(wrap(&"1".repeat(100)), format!("{}\\\n{}", "1".repeat(69), "1".repeat(31)));
+}
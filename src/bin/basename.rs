@@ -1,42 +1,14 @@
 // Copyright 2021 James Bostock. See the LICENSE file at the top-level
 // directory of this distribution.
 
-// An implementation of the basename(1) command in Rust.
-// See http://man.cat-v.org/unix-7th/1/basename
+// The basename command; see src/cmd/basename.rs.
 use std::env;
-use std::path;
 use std::process;
 
-fn basename(path: &str) -> Option<&str> {
-    match path::Path::new(path).file_name() {
-	Some(file_name) => file_name.to_str(),
-	None => Some(path)
-    }
-}
+use rust_v7_cmd::cmd::{self, basename};
+use rust_v7_cmd::signal;
 
 fn main() {
-    let args: Vec<_> = env::args().collect();
-    match args.len() {
-	2 => {
-	    match basename(&args[1]) {
-		Some(s) => println!("{}", s),
-		None => eprintln!("basename failed")
-	    }
-	},
-	3 => {
-	    match basename(&args[1]) {
-		Some(s) => {
-		    match s.strip_suffix(&args[2]) {
-			Some(s) => println!("{}", s),
-			None => println!("{}", s)
-		    }
-		},
-		None => eprintln!("basename failed")
-	    }
-	},
-	_ => {
-	    eprintln!("usage: {} <path> [suffix]", args[0]);
-	    process::exit(1)
-	}
-    }
+    signal::default_sigpipe();
+    process::exit(cmd::exec(basename::run, &basename::SPEC, env::args_os().collect()));
 }
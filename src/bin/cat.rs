@@ -1,31 +1,14 @@
-// Copyright 2015-2021 James Bostock. See the LICENSE file at the top-level
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
 // directory of this distribution.
 
-// An implementation of the cat(1) command in Rust.
-// See http://man.cat-v.org/unix-7th/1/cat
+// The cat command; see src/cmd/cat.rs.
 use std::env;
-use std::io;
+use std::process;
 
-use rust_v7_lib as lib;
-
-fn cat(filename: &str) -> io::Result<u64> {
-    let mut reader = lib::Input::open(filename)?;
-    io::copy(&mut reader, &mut io::stdout())
-}
+use rust_v7_cmd::cmd::{self, cat};
+use rust_v7_cmd::signal;
 
 fn main() {
-    let mut args: Vec<_> = env::args().collect();
-
-    if args.len() == 1 {
-        args.push("-".to_string());
-    }
-
-    for arg in args.iter().skip(1) {
-        match cat(arg) {
-            Ok(_) => {}
-            Err(e) => {
-                eprintln!("{}: {}", arg, e);
-            }
-        };
-    }
+    signal::default_sigpipe();
+    process::exit(cmd::exec(cat::run, &cat::SPEC, env::args_os().collect()));
 }
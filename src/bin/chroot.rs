@@ -0,0 +1,21 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// The chroot command; see src/cmd/chroot.rs.
+use std::process;
+
+#[cfg(unix)]
+fn main() {
+    use std::env;
+    use rust_v7_cmd::cmd::{self, chroot};
+    use rust_v7_cmd::signal;
+
+    signal::default_sigpipe();
+    process::exit(cmd::exec(chroot::run, &chroot::SPEC, env::args_os().collect()));
+}
+
+#[cfg(not(unix))]
+fn main() {
+    rust_v7_cmd::err!("not supported on this system");
+    process::exit(rust_v7_cmd::status::FAILURE);
+}
@@ -0,0 +1,14 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// The join command; see src/cmd/join.rs.
+use std::env;
+use std::process;
+
+use rust_v7_cmd::cmd::{self, join};
+use rust_v7_cmd::signal;
+
+fn main() {
+    signal::default_sigpipe();
+    process::exit(cmd::exec(join::run, &join::SPEC, env::args_os().collect()));
+}
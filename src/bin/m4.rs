@@ -0,0 +1,14 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// The m4 command; see src/cmd/m4.rs.
+use std::env;
+use std::process;
+
+use rust_v7_cmd::cmd::{self, m4};
+use rust_v7_cmd::signal;
+
+fn main() {
+    signal::default_sigpipe();
+    process::exit(cmd::exec(m4::run, &m4::SPEC, env::args_os().collect()));
+}
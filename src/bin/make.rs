@@ -0,0 +1,14 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// The make command; see src/cmd/make.rs.
+use std::env;
+use std::process;
+
+use rust_v7_cmd::cmd::{self, make};
+use rust_v7_cmd::signal;
+
+fn main() {
+    signal::default_sigpipe();
+    process::exit(cmd::exec(make::run, &make::SPEC, env::args_os().collect()));
+}
@@ -0,0 +1,75 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// A multicall binary that contains all the commands. It runs the one it
+// is invoked as, through a link with the command's name, or else the one
+// named by its first argument, as in "v7 ls -l". Given --completion and
+// the name of a shell, it writes a completion script for the commands,
+// and given --man and a directory, their manual pages.
+use std::env;
+use std::ffi::OsString;
+use std::path::Path;
+use std::process;
+
+use rust_v7_cmd::cmd;
+use rust_v7_cmd::completion;
+use rust_v7_cmd::err;
+use rust_v7_cmd::man;
+use rust_v7_cmd::signal;
+use rust_v7_cmd::status;
+
+fn main() {
+    signal::default_sigpipe();
+    let mut args: Vec<OsString> = env::args_os().collect();
+    let prog = args.first().map(|arg| arg.to_string_lossy().into_owned()).unwrap_or_default();
+    let name = Path::new(&prog).file_name().and_then(|name| name.to_str()).unwrap_or_default();
+    if let Some((run, spec)) = cmd::find(name) {
+        process::exit(cmd::exec(run, spec, args));
+    }
+
+    args.remove(0);
+    if args.first().is_some_and(|arg| arg == "--completion") {
+        match args.get(1).and_then(|shell| shell.to_str()).and_then(completion::script) {
+            Some(script) => {
+                print!("{}", script);
+                process::exit(status::SUCCESS);
+            },
+            None => {
+                eprintln!("usage: {} --completion {}", prog, completion::SHELLS.join(" | "));
+                process::exit(status::USAGE);
+            }
+        }
+    }
+    if args.first().is_some_and(|arg| arg == "--man") {
+        match args.get(1) {
+            Some(dir) => match man::write_all(Path::new(dir)) {
+                Ok(()) => process::exit(status::SUCCESS),
+                Err(e) => {
+                    err!("{}: {}", dir.to_string_lossy(), e);
+                    process::exit(status::FAILURE);
+                }
+            },
+            None => {
+                eprintln!("usage: {} --man dir", prog);
+                process::exit(status::USAGE);
+            }
+        }
+    }
+    match args.first() {
+        Some(name) => match name.to_str().and_then(cmd::find) {
+            Some((run, spec)) => process::exit(cmd::exec(run, spec, args)),
+            None => {
+                err!("{}: no such command", name.to_string_lossy());
+                process::exit(status::NOT_FOUND);
+            }
+        },
+        None => {
+            let names: Vec<&str> = cmd::COMMANDS.iter().map(|(name, _, _)| *name).collect();
+            eprintln!("usage: {} command [arg ...]", prog);
+            eprintln!("       {} --completion {}", prog, completion::SHELLS.join(" | "));
+            eprintln!("       {} --man dir", prog);
+            eprintln!("commands: {}", names.join(" "));
+            process::exit(status::USAGE);
+        }
+    }
+}
@@ -0,0 +1,109 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+//! The units in which commands report disk usage, so that all of them
+//! agree: 512-byte blocks, as in the Seventh Edition, unless the
+//! BLOCKSIZE environment variable gives another size, such as "1k" or
+//! "4096", or the command is given -k, for 1024-byte blocks, or -h, for
+//! sizes in whatever unit suits them, such as "12K" or "1.5M".
+use std::env;
+
+use crate::size;
+use crate::warn;
+
+/// The size of the blocks in which st_blocks is counted, and usage is
+/// reported by default.
+pub const STAT_BLOCK: u64 = 512;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BlockSize {
+    /// The number of bytes in a block.
+    size: u64,
+    /// Report sizes for people to read rather than in blocks.
+    human: bool,
+}
+
+impl BlockSize {
+    /// Returns the block size given the command's -k and -h options, of
+    /// which -h takes precedence, and otherwise $BLOCKSIZE. An invalid
+    /// BLOCKSIZE draws a warning and is ignored.
+    pub fn new(kilo: bool, human: bool) -> BlockSize {
+        let size = if kilo {
+            1024
+        } else {
+            match env::var("BLOCKSIZE") {
+                Ok(val) => parse(&val).unwrap_or_else(|| {
+                    warn!("{}: invalid BLOCKSIZE", val);
+                    STAT_BLOCK
+                }),
+                Err(_) => STAT_BLOCK,
+            }
+        };
+        BlockSize { size, human }
+    }
+
+    /// Returns the number of blocks needed to hold the bytes.
+    pub fn blocks(self, bytes: u64) -> u64 {
+        bytes.div_ceil(self.size)
+    }
+
+    /// Returns the bytes as they are to be reported: as a number of
+    /// blocks or, with -h, for people to read.
+    pub fn format(self, bytes: u64) -> String {
+        if self.human {
+            human(bytes)
+        } else {
+            self.blocks(bytes).to_string()
+        }
+    }
+}
+
+/// Parses a block size as BLOCKSIZE gives it: a number with an optional
+/// suffix, as for size::parse, or a suffix alone, such as "K" for 1024.
+fn parse(s: &str) -> Option<u64> {
+    let size = if s.starts_with(|c: char| c.is_ascii_digit()) {
+        size::parse(s)?
+    } else {
+        size::multiplier(s)?
+    };
+    if size > 0 { Some(size) } else { None }
+}
+
+/// Returns a size in bytes for people to read: in bytes if it is less
+/// than a kibibyte, and otherwise in the largest unit in which it is at
+/// least one, rounded up, with a decimal place if that is less than ten.
+pub fn human(bytes: u64) -> String {
+    const UNITS: &[char] = &['K', 'M', 'G', 'T', 'P', 'E'];
+    if bytes < 1024 {
+        return bytes.to_string();
+    }
+    let mut unit = 0;
+    let mut scale: u64 = 1024;
+    while unit + 1 < UNITS.len() && bytes / scale >= 1024 {
+        unit += 1;
+        scale *= 1024;
+    }
+    // In tenths of the unit, rounded up.
+    let tenths = (u128::from(bytes) * 10).div_ceil(u128::from(scale));
+    if tenths < 100 {
+        format!("{}.{}{}", tenths / 10, tenths % 10, UNITS[unit])
+    } else {
+        format!("{}{}", tenths.div_ceil(10), UNITS[unit])
+    }
+}
+
+#[test]
+fn test_block_size() {
+    assert_eq!(parse("1k"), Some(1024));
+    assert_eq!(parse("K"), Some(1024));
+    assert_eq!(parse("0"), None);
+    assert_eq!(parse("x"), None);
+    let size = BlockSize { size: 1024, human: false };
+    assert_eq!(size.format(1025), "2");
+    assert_eq!(human(1023), "1023");
+    assert_eq!(human(1024), "1.0K");
+    assert_eq!(human(1536), "1.5K");
+    assert_eq!(human(10 * 1024 - 1), "10K");
+    assert_eq!(human(1 << 20), "1.0M");
+    assert_eq!(human(u64::MAX), "16E");
+}
@@ -0,0 +1,168 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+//! Checksums of files: the BSD and System V checksums computed by sum and
+//! the CRC computed by cksum. Each checksum is a writer, so that a file
+//! can be copied to it.
+use std::io;
+use std::io::Write;
+
+/// The BSD checksum, a 16-bit sum rotated right one bit before each byte
+/// is added.
+#[derive(Default)]
+pub struct Bsd {
+    sum: u16,
+    bytes: u64,
+}
+
+impl Bsd {
+    pub fn new() -> Bsd {
+        Bsd::default()
+    }
+
+    pub fn value(&self) -> u16 {
+        self.sum
+    }
+
+    /// Returns the number of bytes summed.
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+}
+
+impl Write for Bsd {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for &c in buf {
+            self.sum = self.sum.rotate_right(1).wrapping_add(c as u16);
+        }
+        self.bytes += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The System V checksum, the sum of the bytes folded to 16 bits.
+#[derive(Default)]
+pub struct SysV {
+    sum: u32,
+    bytes: u64,
+}
+
+impl SysV {
+    pub fn new() -> SysV {
+        SysV::default()
+    }
+
+    pub fn value(&self) -> u16 {
+        let r = (self.sum & 0xffff) + (self.sum >> 16);
+        ((r & 0xffff) + (r >> 16)) as u16
+    }
+
+    /// Returns the number of bytes summed.
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+}
+
+impl Write for SysV {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for &c in buf {
+            self.sum = self.sum.wrapping_add(c as u32);
+        }
+        self.bytes += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The generator polynomial of the POSIX CRC, as used by Ethernet.
+const POLYNOMIAL: u32 = 0x04c11db7;
+
+/// The CRCs of the bytes, most significant bit first.
+const CRC_TABLE: [u32; 256] = crc_table();
+
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = (i as u32) << 24;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 0x80000000 != 0 { (crc << 1) ^ POLYNOMIAL } else { crc << 1 };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+/// The CRC specified by POSIX for cksum, which covers the length of the
+/// data as well as the data itself.
+#[derive(Default)]
+pub struct Crc {
+    crc: u32,
+    bytes: u64,
+}
+
+impl Crc {
+    pub fn new() -> Crc {
+        Crc::default()
+    }
+
+    fn update(crc: u32, c: u8) -> u32 {
+        (crc << 8) ^ CRC_TABLE[((crc >> 24) ^ c as u32) as usize]
+    }
+
+    pub fn value(&self) -> u32 {
+        // The length follows the data, least significant byte first, in
+        // as few bytes as it takes.
+        let mut crc = self.crc;
+        let mut len = self.bytes;
+        while len != 0 {
+            crc = Crc::update(crc, len as u8);
+            len >>= 8;
+        }
+        !crc
+    }
+
+    /// Returns the number of bytes covered.
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+}
+
+impl Write for Crc {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for &c in buf {
+            self.crc = Crc::update(self.crc, c);
+        }
+        self.bytes += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_checksums() {
+    let mut bsd = Bsd::new();
+    let mut sysv = SysV::new();
+    let mut crc = Crc::new();
+    assert_eq!(crc.value(), 0xffffffff);
+    for sum in [&mut bsd as &mut dyn Write, &mut sysv, &mut crc] {
+        sum.write_all(b"hel").unwrap();
+        sum.write_all(b"lo\n").unwrap();
+    }
+    assert_eq!((bsd.value(), bsd.bytes()), (36979, 6));
+    assert_eq!((sysv.value(), sysv.bytes()), (542, 6));
+    assert_eq!((crc.value(), crc.bytes()), (3015617425, 6));
+}
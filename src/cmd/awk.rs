@@ -0,0 +1,2762 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// An implementation of the awk(1) command in Rust.
+// See http://man.cat-v.org/unix-7th/1/awk
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fs;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::process;
+use std::rc::Rc;
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::args;
+use crate::cmd;
+use crate::err;
+use crate::regex::{Regex, Syntax};
+use crate::status;
+
+/// The exit status after an error in the program or running it.
+const ERROR: i32 = status::TROUBLE;
+
+/// How deeply functions may call each other before it is taken to be
+/// runaway recursion.
+const MAX_DEPTH: usize = 5000;
+
+/// The size of the stack the program runs with.
+const STACK_SIZE: usize = 256 << 20;
+
+/// The slots of the special variables, which come first.
+const NR: usize = 0;
+const NF: usize = 1;
+const FS: usize = 2;
+const OFS: usize = 3;
+const ORS: usize = 4;
+const RS: usize = 5;
+const FILENAME: usize = 6;
+const FNR: usize = 7;
+const SUBSEP: usize = 8;
+const RSTART: usize = 9;
+const RLENGTH: usize = 10;
+const CONVFMT: usize = 11;
+const OFMT: usize = 12;
+const SPECIALS: &[&str] = &[
+    "NR", "NF", "FS", "OFS", "ORS", "RS", "FILENAME", "FNR", "SUBSEP", "RSTART", "RLENGTH", "CONVFMT", "OFMT",
+];
+
+const KEYWORDS: &[&str] = &[
+    "BEGIN", "END", "break", "continue", "delete", "do", "else", "exit", "for", "func", "function", "getline", "if",
+    "in", "next", "print", "printf", "return", "while",
+];
+
+/// The built-in functions, with the least and most arguments they take.
+const FUNCS: &[(&str, usize, usize)] = &[
+    ("atan2", 2, 2),
+    ("close", 1, 1),
+    ("cos", 1, 1),
+    ("exp", 1, 1),
+    ("fflush", 0, 1),
+    ("gsub", 2, 3),
+    ("index", 2, 2),
+    ("int", 1, 1),
+    ("length", 0, 1),
+    ("log", 1, 1),
+    ("match", 2, 2),
+    ("rand", 0, 0),
+    ("sin", 1, 1),
+    ("split", 2, 3),
+    ("sprintf", 1, usize::MAX),
+    ("sqrt", 1, 1),
+    ("srand", 0, 1),
+    ("sub", 2, 3),
+    ("substr", 2, 3),
+    ("system", 1, 1),
+    ("tolower", 1, 1),
+    ("toupper", 1, 1),
+];
+
+/// The operators, longest first so that, say, += isn't taken for +.
+const OPERATORS: &[&str] = &[
+    "+=", "-=", "*=", "/=", "%=", "^=", "==", "<=", ">=", "!=", "++", "--", "&&", "||", ">>", "!~",
+    "{", "}", "(", ")", "[", "]", ";", ",", "+", "-", "*", "/", "%", "^", "!", ">", "<", "|", "?",
+    ":", "~", "$", "=",
+];
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(f64),
+    Str(String),
+    Regex(String),
+    Name(String),
+    /// A name followed directly by a parenthesis, which calls a function.
+    Call(String),
+    Keyword(&'static str),
+    /// The name of a built-in function.
+    Func(&'static str),
+    Op(&'static str),
+    Newline,
+    Eof,
+}
+
+impl Token {
+    /// Describes the token for an error message.
+    fn describe(&self) -> String {
+        match self {
+            Token::Number(n) => n.to_string(),
+            Token::Str(s) => format!("\"{}\"", s),
+            Token::Regex(re) => format!("/{}/", re),
+            Token::Name(s) | Token::Call(s) => s.clone(),
+            Token::Keyword(s) | Token::Func(s) | Token::Op(s) => s.to_string(),
+            Token::Newline => "newline".to_string(),
+            Token::Eof => "end of file".to_string(),
+        }
+    }
+}
+
+/// Replaces the escape sequences in a string. A backslash before a
+/// character with no special meaning stands for itself.
+fn unescape(s: &str) -> String {
+    let mut out = String::new();
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('"') => out.push('"'),
+            Some('\\') => out.push('\\'),
+            Some('/') => out.push('/'),
+            Some('a') => out.push('\x07'),
+            Some('b') => out.push('\x08'),
+            Some('f') => out.push('\x0c'),
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some('t') => out.push('\t'),
+            Some('v') => out.push('\x0b'),
+            Some(c @ '0'..='7') => {
+                let mut n = c.to_digit(8).unwrap();
+                for _ in 0..2 {
+                    match chars.peek().and_then(|c| c.to_digit(8)) {
+                        Some(d) => {
+                            n = n * 8 + d;
+                            chars.next();
+                        },
+                        None => break,
+                    }
+                }
+                out.push(char::from_u32(n).unwrap_or('\0'));
+            },
+            Some(c) => {
+                out.push('\\');
+                out.push(c);
+            },
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+#[derive(Clone)]
+struct Lexer<'a> {
+    src: &'a [u8],
+    pos: usize,
+    line: usize,
+    /// Whether a / would start a regular expression rather than divide,
+    /// which depends on the token before it.
+    regex_ok: bool,
+}
+
+impl<'a> Lexer<'a> {
+    fn error<T>(&self, msg: &str) -> Result<T, String> {
+        Err(format!("syntax error at source line {}: {}", self.line, msg))
+    }
+
+    fn next(&mut self) -> Result<Token, String> {
+        let tok = self.scan()?;
+        self.regex_ok = !matches!(tok, Token::Number(_) | Token::Str(_) | Token::Name(_) | Token::Func(_)
+                                  | Token::Op(")" | "]" | "$" | "++" | "--"));
+        Ok(tok)
+    }
+
+    /// Reads up to the delimiter that ends a string or regular expression,
+    /// leaving escape sequences as they are.
+    fn delimited(&mut self, delim: u8, what: &str) -> Result<String, String> {
+        let start = self.pos;
+        loop {
+            match self.src.get(self.pos) {
+                Some(&c) if c == delim => break,
+                Some(b'\\') if self.pos + 1 < self.src.len() && self.src[self.pos + 1] != b'\n' => self.pos += 2,
+                Some(b'\n') | None => return self.error(&format!("non-terminated {}", what)),
+                Some(_) => self.pos += 1,
+            }
+        }
+        let text = String::from_utf8_lossy(&self.src[start..self.pos]).into_owned();
+        self.pos += 1;
+        Ok(text)
+    }
+
+    fn scan(&mut self) -> Result<Token, String> {
+        loop {
+            match self.src.get(self.pos) {
+                Some(b' ' | b'\t' | b'\r') => self.pos += 1,
+                // A backslash continues a line.
+                Some(b'\\') if self.src.get(self.pos + 1) == Some(&b'\n') => {
+                    self.pos += 2;
+                    self.line += 1;
+                },
+                Some(b'#') => {
+                    while self.src.get(self.pos).is_some_and(|&c| c != b'\n') {
+                        self.pos += 1;
+                    }
+                },
+                _ => break,
+            }
+        }
+        let c = match self.src.get(self.pos) {
+            Some(&c) => c,
+            None => return Ok(Token::Eof),
+        };
+        let digit_at = |i: usize| self.src.get(i).is_some_and(u8::is_ascii_digit);
+        if c == b'\n' {
+            self.pos += 1;
+            self.line += 1;
+            Ok(Token::Newline)
+        } else if c.is_ascii_digit() || (c == b'.' && digit_at(self.pos + 1)) {
+            let start = self.pos;
+            while digit_at(self.pos) {
+                self.pos += 1;
+            }
+            if self.src.get(self.pos) == Some(&b'.') {
+                self.pos += 1;
+                while digit_at(self.pos) {
+                    self.pos += 1;
+                }
+            }
+            if matches!(self.src.get(self.pos), Some(b'e' | b'E')) {
+                let sign = matches!(self.src.get(self.pos + 1), Some(b'+' | b'-')) as usize;
+                if digit_at(self.pos + 1 + sign) {
+                    self.pos += 1 + sign;
+                    while digit_at(self.pos) {
+                        self.pos += 1;
+                    }
+                }
+            }
+            let text = String::from_utf8_lossy(&self.src[start..self.pos]);
+            Ok(Token::Number(text.parse().unwrap_or(0.0)))
+        } else if c == b'"' {
+            self.pos += 1;
+            Ok(Token::Str(unescape(&self.delimited(b'"', "string")?)))
+        } else if c == b'/' && self.regex_ok {
+            self.pos += 1;
+            let text = self.delimited(b'/', "regular expression")?;
+            Ok(Token::Regex(text))
+        } else if c.is_ascii_alphabetic() || c == b'_' {
+            let start = self.pos;
+            while self.src.get(self.pos).is_some_and(|&c| c.is_ascii_alphanumeric() || c == b'_') {
+                self.pos += 1;
+            }
+            let name = String::from_utf8_lossy(&self.src[start..self.pos]).into_owned();
+            if let Some(kw) = KEYWORDS.iter().find(|&&kw| kw == name) {
+                Ok(Token::Keyword(kw))
+            } else if let Some((func, _, _)) = FUNCS.iter().find(|(func, _, _)| *func == name) {
+                Ok(Token::Func(func))
+            } else if self.src.get(self.pos) == Some(&b'(') {
+                Ok(Token::Call(name))
+            } else {
+                Ok(Token::Name(name))
+            }
+        } else {
+            match OPERATORS.iter().find(|op| self.src[self.pos..].starts_with(op.as_bytes())) {
+                Some(op) => {
+                    self.pos += op.len();
+                    Ok(Token::Op(op))
+                },
+                None => self.error(&format!("unexpected character {}", c as char)),
+            }
+        }
+    }
+}
+
+/// Converts the escape sequences of a regular expression literal that the
+/// regular expression engine doesn't know, leaving the rest to it.
+fn regex_source(text: &str) -> String {
+    let mut out = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('/') => out.push('/'),
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some('r') => out.push('\r'),
+            Some('f') => out.push('\x0c'),
+            Some('v') => out.push('\x0b'),
+            Some('a') => out.push('\x07'),
+            Some(c) => {
+                out.push('\\');
+                out.push(c);
+            },
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+fn compile(source: &str) -> Result<Regex, String> {
+    Regex::with_syntax(source.as_bytes(), Syntax::Extended, false)
+        .map_err(|e| format!("/{}/: {}", source, e))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Pow,
+    Concat,
+    Lt,
+    Le,
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+}
+
+impl BinOp {
+    /// Returns the arithmetic operator of an assignment such as +=.
+    fn assignment(op: &str) -> Option<Option<BinOp>> {
+        Some(match op {
+            "=" => None,
+            "+=" => Some(BinOp::Add),
+            "-=" => Some(BinOp::Sub),
+            "*=" => Some(BinOp::Mul),
+            "/=" => Some(BinOp::Div),
+            "%=" => Some(BinOp::Mod),
+            "^=" => Some(BinOp::Pow),
+            _ => return None,
+        })
+    }
+}
+
+/// A variable: a global, by its slot, or a parameter of the function
+/// being called.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Var {
+    Global(usize),
+    Local(usize),
+}
+
+/// Where getline reads from.
+#[derive(Debug)]
+enum Source {
+    /// The files named by the operands, or the standard input.
+    Main,
+    File(Box<Expr>),
+    /// The output of a command.
+    Command(Box<Expr>),
+}
+
+#[derive(Debug)]
+enum Expr {
+    Num(f64),
+    Str(String),
+    /// A regular expression on its own, which matches the record.
+    Regex(Rc<Regex>),
+    Var(Var),
+    /// An element of an array, whose subscripts are joined by SUBSEP.
+    Index(Var, Vec<Expr>),
+    /// Whether an array has an element.
+    In(Vec<Expr>, Var),
+    Field(Box<Expr>),
+    /// A parenthesised list of expressions, which may only be the
+    /// arguments of print or printf.
+    Group(Vec<Expr>),
+    /// An assignment, with the operator of, say, +=.
+    Assign(Option<BinOp>, Box<Expr>, Box<Expr>),
+    Cond(Box<Expr>, Box<Expr>, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    /// A match (~), or with true, a non-match (!~).
+    Match(bool, Box<Expr>, Box<Expr>),
+    Binary(BinOp, Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Neg(Box<Expr>),
+    Plus(Box<Expr>),
+    /// An increment or decrement, and whether it comes before the value.
+    Incr(Box<Expr>, f64, bool),
+    Call(&'static str, Vec<Expr>),
+    /// A call of a user-defined function, by its index.
+    User(usize, Vec<Expr>),
+    /// getline, and the variable it sets instead of the record.
+    Getline(Source, Option<Box<Expr>>),
+}
+
+impl Expr {
+    fn is_lvalue(&self) -> bool {
+        matches!(self, Expr::Var(_) | Expr::Index(..) | Expr::Field(_))
+    }
+}
+
+/// Where print and printf send their output, other than the standard
+/// output.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Dest {
+    /// > file: truncates the file when it is first written.
+    File,
+    /// >> file
+    Append,
+    /// | command
+    Pipe,
+}
+
+#[derive(Debug)]
+enum Stmt {
+    Expr(Expr),
+    /// print or, if format is true, printf.
+    Print { format: bool, args: Vec<Expr>, dest: Option<(Dest, Expr)> },
+    If(Expr, Box<Stmt>, Option<Box<Stmt>>),
+    While(Expr, Box<Stmt>),
+    Do(Box<Stmt>, Expr),
+    For(Option<Expr>, Option<Expr>, Option<Expr>, Box<Stmt>),
+    /// for (var in array)
+    ForIn(Var, Var, Box<Stmt>),
+    /// Deletes an element of an array or, without subscripts, all of them.
+    Delete(Var, Option<Vec<Expr>>),
+    Block(Vec<Stmt>),
+    Next,
+    Exit(Option<Expr>),
+    Return(Option<Expr>),
+    Break,
+    Continue,
+}
+
+#[derive(Debug)]
+enum Pattern {
+    Begin,
+    End,
+    /// No pattern: every record.
+    All,
+    Expr(Expr),
+    /// pattern, pattern: the records from one matching the first to the
+    /// next matching the second.
+    Range(Expr, Expr),
+}
+
+#[derive(Debug)]
+struct Item {
+    pattern: Pattern,
+    /// The action, which if missing prints the record.
+    action: Option<Vec<Stmt>>,
+}
+
+#[derive(Debug)]
+struct Function {
+    name: String,
+    params: usize,
+    body: Vec<Stmt>,
+}
+
+struct Program {
+    items: Vec<Item>,
+    /// The slots of the global variables, by name.
+    names: HashMap<String, usize>,
+    funcs: Rc<Vec<Function>>,
+}
+
+struct Parser<'a> {
+    lexer: Lexer<'a>,
+    tok: Token,
+    /// The line of the current token.
+    line: usize,
+    names: HashMap<String, usize>,
+    /// The parameters of the function being defined, if any.
+    params: Option<Vec<String>>,
+    /// The functions, which may be called before they are defined.
+    funcs: Vec<Option<Function>>,
+    func_names: HashMap<String, usize>,
+    /// The calls of functions, with the number of arguments and the line,
+    /// to check once all are defined.
+    calls: Vec<(usize, usize, usize)>,
+    /// Whether > is a redirection rather than a comparison, as it is in
+    /// the arguments of print and printf outside parentheses.
+    no_gt: bool,
+}
+
+impl<'a> Parser<'a> {
+    fn new(src: &'a [u8]) -> Result<Parser<'a>, String> {
+        let mut lexer = Lexer { src, pos: 0, line: 1, regex_ok: true };
+        let tok = lexer.next()?;
+        let names = SPECIALS.iter().enumerate().map(|(i, name)| (name.to_string(), i)).collect();
+        Ok(Parser {
+            line: lexer.line,
+            lexer,
+            tok,
+            names,
+            params: None,
+            funcs: Vec::new(),
+            func_names: HashMap::new(),
+            calls: Vec::new(),
+            no_gt: false,
+        })
+    }
+
+    fn error<T>(&self) -> Result<T, String> {
+        Err(format!("syntax error at source line {} near {}", self.line, self.tok.describe()))
+    }
+
+    fn advance(&mut self) -> Result<Token, String> {
+        let next = self.lexer.next()?;
+        self.line = self.lexer.line - (next == Token::Newline) as usize;
+        Ok(std::mem::replace(&mut self.tok, next))
+    }
+
+    /// Returns the token after the current one.
+    fn peek(&self) -> Result<Token, String> {
+        self.lexer.clone().next()
+    }
+
+    fn is_op(&self, op: &str) -> bool {
+        matches!(self.tok, Token::Op(o) if o == op)
+    }
+
+    fn eat_op(&mut self, op: &str) -> Result<bool, String> {
+        let found = self.is_op(op);
+        if found {
+            self.advance()?;
+        }
+        Ok(found)
+    }
+
+    fn expect_op(&mut self, op: &str) -> Result<(), String> {
+        if !self.eat_op(op)? {
+            return self.error();
+        }
+        Ok(())
+    }
+
+    fn is_keyword(&self, kw: &str) -> bool {
+        matches!(self.tok, Token::Keyword(k) if k == kw)
+    }
+
+    /// Skips newlines, which are allowed after some tokens.
+    fn newlines(&mut self) -> Result<(), String> {
+        while self.tok == Token::Newline {
+            self.advance()?;
+        }
+        Ok(())
+    }
+
+    /// Skips newlines and semicolons between statements and items.
+    fn terminators(&mut self) -> Result<(), String> {
+        while self.tok == Token::Newline || self.is_op(";") {
+            self.advance()?;
+        }
+        Ok(())
+    }
+
+    /// Resolves a name to a parameter of the function being defined or a
+    /// global variable.
+    fn var(&mut self, name: String) -> Var {
+        if let Some(i) = self.params.as_ref().and_then(|params| params.iter().position(|p| *p == name)) {
+            return Var::Local(i);
+        }
+        let n = self.names.len();
+        Var::Global(*self.names.entry(name).or_insert(n))
+    }
+
+    /// Parses the name of an array.
+    fn array(&mut self) -> Result<Var, String> {
+        match self.advance()? {
+            Token::Name(name) => Ok(self.var(name)),
+            tok => {
+                self.tok = tok;
+                self.error()
+            },
+        }
+    }
+
+    fn func(&mut self, name: String) -> usize {
+        if let Some(&i) = self.func_names.get(&name) {
+            return i;
+        }
+        self.funcs.push(None);
+        self.func_names.insert(name, self.funcs.len() - 1);
+        self.funcs.len() - 1
+    }
+
+    fn program(mut self) -> Result<Program, String> {
+        let mut items = Vec::new();
+        loop {
+            self.terminators()?;
+            match self.tok {
+                Token::Eof => break,
+                Token::Keyword("function" | "func") => self.function()?,
+                _ => items.push(self.item()?),
+            }
+        }
+        let mut funcs = Vec::new();
+        for (i, func) in self.funcs.into_iter().enumerate() {
+            match func {
+                Some(func) => funcs.push(func),
+                None => {
+                    let name = self.func_names.iter().find(|(_, &j)| i == j).unwrap().0;
+                    return Err(format!("calling undefined function {}", name));
+                }
+            }
+        }
+        for (i, args, line) in self.calls {
+            if args > funcs[i].params {
+                return Err(format!("function {} called with {} args, accepts only {} at source line {}",
+                                   funcs[i].name, args, funcs[i].params, line));
+            }
+        }
+        Ok(Program { items, names: self.names, funcs: Rc::new(funcs) })
+    }
+
+    fn function(&mut self) -> Result<(), String> {
+        self.advance()?;
+        let name = match self.advance()? {
+            Token::Name(name) | Token::Call(name) => name,
+            tok => {
+                self.tok = tok;
+                return self.error();
+            },
+        };
+        self.expect_op("(")?;
+        let mut params = Vec::new();
+        while !self.eat_op(")")? {
+            if !params.is_empty() {
+                self.expect_op(",")?;
+                self.newlines()?;
+            }
+            match self.advance()? {
+                Token::Name(param) => params.push(param),
+                tok => {
+                    self.tok = tok;
+                    return self.error();
+                },
+            }
+        }
+        self.newlines()?;
+        let i = self.func(name.clone());
+        if self.funcs[i].is_some() {
+            return Err(format!("function {} redefined at source line {}", name, self.line));
+        }
+        self.params = Some(params);
+        let body = self.block()?;
+        let params = self.params.take().unwrap().len();
+        self.funcs[i] = Some(Function { name, params, body });
+        Ok(())
+    }
+
+    fn item(&mut self) -> Result<Item, String> {
+        let pattern = match self.tok {
+            Token::Keyword("BEGIN") => {
+                self.advance()?;
+                return Ok(Item { pattern: Pattern::Begin, action: Some(self.block()?) });
+            },
+            Token::Keyword("END") => {
+                self.advance()?;
+                return Ok(Item { pattern: Pattern::End, action: Some(self.block()?) });
+            },
+            Token::Op("{") => Pattern::All,
+            _ => {
+                let first = self.expr()?;
+                if self.eat_op(",")? {
+                    self.newlines()?;
+                    Pattern::Range(first, self.expr()?)
+                } else {
+                    Pattern::Expr(first)
+                }
+            },
+        };
+        let action = if self.is_op("{") { Some(self.block()?) } else { None };
+        Ok(Item { pattern, action })
+    }
+
+    fn block(&mut self) -> Result<Vec<Stmt>, String> {
+        self.expect_op("{")?;
+        let mut stmts = Vec::new();
+        loop {
+            self.terminators()?;
+            if self.eat_op("}")? {
+                return Ok(stmts);
+            }
+            stmts.push(self.stmt()?);
+        }
+    }
+
+    /// Ends a simple statement, at a newline or semicolon, which it
+    /// consumes, or before a closing brace.
+    fn end_simple(&mut self) -> Result<(), String> {
+        match self.tok {
+            Token::Newline | Token::Op(";") => {
+                self.advance()?;
+            },
+            Token::Op("}") | Token::Eof => (),
+            _ => return self.error(),
+        }
+        Ok(())
+    }
+
+    fn at_terminator(&self) -> bool {
+        matches!(self.tok, Token::Newline | Token::Eof | Token::Op(";" | "}"))
+    }
+
+    /// Parses the parenthesised condition of if, while or do.
+    fn condition(&mut self) -> Result<Expr, String> {
+        self.expect_op("(")?;
+        let cond = self.expr()?;
+        self.expect_op(")")?;
+        Ok(cond)
+    }
+
+    fn stmt(&mut self) -> Result<Stmt, String> {
+        if self.is_op("{") {
+            return Ok(Stmt::Block(self.block()?));
+        }
+        if self.eat_op(";")? {
+            return Ok(Stmt::Block(Vec::new()));
+        }
+        let kw = match self.tok {
+            Token::Keyword(kw) if kw != "getline" => kw,
+            _ => {
+                let expr = self.expr()?;
+                self.end_simple()?;
+                return Ok(Stmt::Expr(expr));
+            },
+        };
+        self.advance()?;
+        let stmt = match kw {
+            "if" => {
+                let cond = self.condition()?;
+                self.newlines()?;
+                let then = Box::new(self.stmt()?);
+                self.newlines()?;
+                let otherwise = if self.is_keyword("else") {
+                    self.advance()?;
+                    self.newlines()?;
+                    Some(Box::new(self.stmt()?))
+                } else {
+                    None
+                };
+                return Ok(Stmt::If(cond, then, otherwise));
+            },
+            "while" => {
+                let cond = self.condition()?;
+                self.newlines()?;
+                return Ok(Stmt::While(cond, Box::new(self.stmt()?)));
+            },
+            "do" => {
+                self.newlines()?;
+                let body = Box::new(self.stmt()?);
+                self.newlines()?;
+                if !self.is_keyword("while") {
+                    return self.error();
+                }
+                self.advance()?;
+                Stmt::Do(body, self.condition()?)
+            },
+            "for" => {
+                self.expect_op("(")?;
+                if matches!(self.tok, Token::Name(_)) && self.peek()? == Token::Keyword("in") {
+                    let var = self.array()?;
+                    self.advance()?;
+                    let array = self.array()?;
+                    self.expect_op(")")?;
+                    self.newlines()?;
+                    return Ok(Stmt::ForIn(var, array, Box::new(self.stmt()?)));
+                }
+                let init = if self.is_op(";") { None } else { Some(self.expr()?) };
+                self.expect_op(";")?;
+                self.newlines()?;
+                let cond = if self.is_op(";") { None } else { Some(self.expr()?) };
+                self.expect_op(";")?;
+                self.newlines()?;
+                let step = if self.is_op(")") { None } else { Some(self.expr()?) };
+                self.expect_op(")")?;
+                self.newlines()?;
+                return Ok(Stmt::For(init, cond, step, Box::new(self.stmt()?)));
+            },
+            "next" => Stmt::Next,
+            "break" => Stmt::Break,
+            "continue" => Stmt::Continue,
+            "exit" => Stmt::Exit(if self.at_terminator() { None } else { Some(self.expr()?) }),
+            "return" if self.params.is_some() => {
+                Stmt::Return(if self.at_terminator() { None } else { Some(self.expr()?) })
+            },
+            "delete" => {
+                let array = self.array()?;
+                let subscripts = if self.eat_op("[")? { Some(self.subscripts()?) } else { None };
+                Stmt::Delete(array, subscripts)
+            },
+            "print" | "printf" => self.print(kw == "printf")?,
+            _ => return self.error(),
+        };
+        self.end_simple()?;
+        Ok(stmt)
+    }
+
+    fn print(&mut self, format: bool) -> Result<Stmt, String> {
+        let mut args = Vec::new();
+        if !self.at_terminator() && !self.is_op(">") && !self.is_op(">>") && !self.is_op("|") {
+            self.no_gt = true;
+            args = self.expr_list()?;
+            self.no_gt = false;
+            if let [Expr::Group(_)] = &args[..] {
+                if let Some(Expr::Group(list)) = args.pop() {
+                    args = list;
+                }
+            }
+        }
+        if format && args.is_empty() {
+            return self.error();
+        }
+        let dest = match self.tok {
+            Token::Op(">") => Some(Dest::File),
+            Token::Op(">>") => Some(Dest::Append),
+            Token::Op("|") => Some(Dest::Pipe),
+            _ => None,
+        };
+        let dest = match dest {
+            Some(dest) => {
+                self.advance()?;
+                self.no_gt = true;
+                let target = self.concat()?;
+                self.no_gt = false;
+                Some((dest, target))
+            },
+            None => None,
+        };
+        Ok(Stmt::Print { format, args, dest })
+    }
+
+    fn expr_list(&mut self) -> Result<Vec<Expr>, String> {
+        let mut list = vec![self.expr()?];
+        while self.eat_op(",")? {
+            self.newlines()?;
+            list.push(self.expr()?);
+        }
+        Ok(list)
+    }
+
+    fn expr(&mut self) -> Result<Expr, String> {
+        let lhs = self.ternary()?;
+        if let Token::Op(op) = self.tok {
+            if let Some(op) = BinOp::assignment(op) {
+                if !lhs.is_lvalue() {
+                    return self.error();
+                }
+                self.advance()?;
+                self.newlines()?;
+                let rhs = self.expr()?;
+                return Ok(Expr::Assign(op, Box::new(lhs), Box::new(rhs)));
+            }
+        }
+        Ok(lhs)
+    }
+
+    fn ternary(&mut self) -> Result<Expr, String> {
+        let cond = self.or()?;
+        if !self.eat_op("?")? {
+            return Ok(cond);
+        }
+        self.newlines()?;
+        let then = self.expr()?;
+        self.newlines()?;
+        self.expect_op(":")?;
+        self.newlines()?;
+        let otherwise = self.expr()?;
+        Ok(Expr::Cond(Box::new(cond), Box::new(then), Box::new(otherwise)))
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.and()?;
+        while self.eat_op("||")? {
+            self.newlines()?;
+            lhs = Expr::Or(Box::new(lhs), Box::new(self.and()?));
+        }
+        Ok(lhs)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.membership()?;
+        while self.eat_op("&&")? {
+            self.newlines()?;
+            lhs = Expr::And(Box::new(lhs), Box::new(self.membership()?));
+        }
+        Ok(lhs)
+    }
+
+    fn membership(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.matching()?;
+        while self.is_keyword("in") {
+            self.advance()?;
+            let array = self.array()?;
+            let subscripts = match lhs {
+                Expr::Group(list) => list,
+                expr => vec![expr],
+            };
+            lhs = Expr::In(subscripts, array);
+        }
+        Ok(lhs)
+    }
+
+    fn matching(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.comparison()?;
+        loop {
+            let negate = match self.tok {
+                Token::Op("~") => false,
+                Token::Op("!~") => true,
+                _ => return Ok(lhs),
+            };
+            self.advance()?;
+            lhs = Expr::Match(negate, Box::new(lhs), Box::new(self.comparison()?));
+        }
+    }
+
+    fn comparison(&mut self) -> Result<Expr, String> {
+        let lhs = self.concat()?;
+        let op = match self.tok {
+            Token::Op("<") => BinOp::Lt,
+            Token::Op("<=") => BinOp::Le,
+            Token::Op("==") => BinOp::Eq,
+            Token::Op("!=") => BinOp::Ne,
+            Token::Op(">") if !self.no_gt => BinOp::Gt,
+            Token::Op(">=") => BinOp::Ge,
+            _ => return Ok(lhs),
+        };
+        self.advance()?;
+        Ok(Expr::Binary(op, Box::new(lhs), Box::new(self.concat()?)))
+    }
+
+    /// Returns whether the current token can start an operand of
+    /// concatenation. An operator such as - can't, as it would be taken to
+    /// be binary.
+    fn starts_operand(&self) -> bool {
+        matches!(self.tok, Token::Number(_) | Token::Str(_) | Token::Regex(_) | Token::Name(_)
+                 | Token::Call(_) | Token::Func(_) | Token::Op("$" | "(" | "++" | "--"))
+    }
+
+    fn concat(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.additive()?;
+        loop {
+            if self.is_op("|") && self.peek()? == Token::Keyword("getline") {
+                self.advance()?;
+                self.advance()?;
+                let var = self.getline_var()?;
+                lhs = Expr::Getline(Source::Command(Box::new(lhs)), var);
+            } else if self.starts_operand() {
+                lhs = Expr::Binary(BinOp::Concat, Box::new(lhs), Box::new(self.additive()?));
+            } else {
+                return Ok(lhs);
+            }
+        }
+    }
+
+    /// Parses the variable that getline may set.
+    fn getline_var(&mut self) -> Result<Option<Box<Expr>>, String> {
+        if !matches!(self.tok, Token::Name(_) | Token::Op("$")) {
+            return Ok(None);
+        }
+        Ok(Some(Box::new(self.primary()?)))
+    }
+
+    /// Parses the subscripts of an array after the opening bracket.
+    fn subscripts(&mut self) -> Result<Vec<Expr>, String> {
+        let no_gt = std::mem::replace(&mut self.no_gt, false);
+        let subscripts = self.expr_list()?;
+        self.no_gt = no_gt;
+        self.expect_op("]")?;
+        Ok(subscripts)
+    }
+
+    /// Parses the arguments of a function after the opening parenthesis.
+    fn args(&mut self) -> Result<Vec<Expr>, String> {
+        let mut args = Vec::new();
+        if !self.is_op(")") {
+            let no_gt = std::mem::replace(&mut self.no_gt, false);
+            args = self.expr_list()?;
+            self.no_gt = no_gt;
+        }
+        self.expect_op(")")?;
+        Ok(args)
+    }
+
+    fn additive(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.multiplicative()?;
+        loop {
+            let op = match self.tok {
+                Token::Op("+") => BinOp::Add,
+                Token::Op("-") => BinOp::Sub,
+                _ => return Ok(lhs),
+            };
+            self.advance()?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(self.multiplicative()?));
+        }
+    }
+
+    fn multiplicative(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.unary()?;
+        loop {
+            let op = match self.tok {
+                Token::Op("*") => BinOp::Mul,
+                Token::Op("/") => BinOp::Div,
+                Token::Op("%") => BinOp::Mod,
+                _ => return Ok(lhs),
+            };
+            self.advance()?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        match self.tok {
+            Token::Op("!") => {
+                self.advance()?;
+                Ok(Expr::Not(Box::new(self.unary()?)))
+            },
+            Token::Op("-") => {
+                self.advance()?;
+                Ok(Expr::Neg(Box::new(self.unary()?)))
+            },
+            Token::Op("+") => {
+                self.advance()?;
+                Ok(Expr::Plus(Box::new(self.unary()?)))
+            },
+            _ => self.power(),
+        }
+    }
+
+    fn power(&mut self) -> Result<Expr, String> {
+        let base = self.postfix()?;
+        if !self.eat_op("^")? {
+            return Ok(base);
+        }
+        // Exponentiation groups to the right, and its exponent may have a
+        // sign.
+        Ok(Expr::Binary(BinOp::Pow, Box::new(base), Box::new(self.unary()?)))
+    }
+
+    fn postfix(&mut self) -> Result<Expr, String> {
+        if let Token::Op(op @ ("++" | "--")) = self.tok {
+            self.advance()?;
+            let lvalue = self.postfix()?;
+            if !lvalue.is_lvalue() {
+                return self.error();
+            }
+            return Ok(Expr::Incr(Box::new(lvalue), if op == "++" { 1.0 } else { -1.0 }, true));
+        }
+        let expr = self.primary()?;
+        if expr.is_lvalue() {
+            if let Token::Op(op @ ("++" | "--")) = self.tok {
+                self.advance()?;
+                return Ok(Expr::Incr(Box::new(expr), if op == "++" { 1.0 } else { -1.0 }, false));
+            }
+        }
+        Ok(expr)
+    }
+
+    fn primary(&mut self) -> Result<Expr, String> {
+        match self.advance()? {
+            Token::Number(n) => Ok(Expr::Num(n)),
+            Token::Str(s) => Ok(Expr::Str(s)),
+            Token::Regex(text) => Ok(Expr::Regex(Rc::new(compile(&regex_source(&text))?))),
+            Token::Op("$") => {
+                // A field number binds more tightly than anything but a
+                // prefix increment or minus sign.
+                let index = match self.tok {
+                    Token::Op("++" | "--") => self.postfix()?,
+                    Token::Op("-") => {
+                        self.advance()?;
+                        Expr::Neg(Box::new(self.primary()?))
+                    },
+                    _ => self.primary()?,
+                };
+                Ok(Expr::Field(Box::new(index)))
+            },
+            Token::Op("(") => {
+                let no_gt = std::mem::replace(&mut self.no_gt, false);
+                let mut list = self.expr_list()?;
+                self.no_gt = no_gt;
+                self.expect_op(")")?;
+                if list.len() == 1 {
+                    Ok(list.pop().unwrap())
+                } else if no_gt || self.is_keyword("in") {
+                    Ok(Expr::Group(list))
+                } else {
+                    self.error()
+                }
+            },
+            Token::Name(name) => {
+                let var = self.var(name);
+                if self.eat_op("[")? {
+                    return Ok(Expr::Index(var, self.subscripts()?));
+                }
+                Ok(Expr::Var(var))
+            },
+            Token::Call(name) => {
+                let i = self.func(name);
+                self.expect_op("(")?;
+                let args = self.args()?;
+                self.calls.push((i, args.len(), self.line));
+                Ok(Expr::User(i, args))
+            },
+            // length may be used without parentheses.
+            Token::Func("length") if !self.is_op("(") => Ok(Expr::Call("length", Vec::new())),
+            Token::Func(name) => {
+                let (_, min, max) = FUNCS.iter().find(|(func, _, _)| *func == name).unwrap();
+                self.expect_op("(")?;
+                let args = self.args()?;
+                if args.len() < *min || args.len() > *max {
+                    return Err(format!("{}: wrong number of arguments at source line {}", name, self.line));
+                }
+                let valid = match name {
+                    "split" => matches!(args[1], Expr::Var(_)),
+                    "sub" | "gsub" => args.get(2).is_none_or(Expr::is_lvalue),
+                    _ => true,
+                };
+                if !valid {
+                    return Err(format!("{}: invalid argument at source line {}", name, self.line));
+                }
+                Ok(Expr::Call(name, args))
+            },
+            Token::Keyword("getline") => {
+                let var = self.getline_var()?;
+                if !self.eat_op("<")? {
+                    return Ok(Expr::Getline(Source::Main, var));
+                }
+                let file = self.primary()?;
+                Ok(Expr::Getline(Source::File(Box::new(file)), var))
+            },
+            tok => {
+                self.tok = tok;
+                self.error()
+            },
+        }
+    }
+}
+
+/// A value: a number, a string or, for input, a string that is compared
+/// as a number if it looks like one.
+#[derive(Clone, Debug, PartialEq)]
+enum Value {
+    /// The value of a variable never assigned, which is both 0 and "".
+    Uninit,
+    Num(f64),
+    Str(String),
+    StrNum(String),
+}
+
+/// Returns the length of the longest prefix of the string that is a
+/// number, after any leading blanks.
+fn numeric_prefix(s: &str) -> usize {
+    let b = s.as_bytes();
+    let digit = |i: usize| b.get(i).is_some_and(u8::is_ascii_digit);
+    let mut i = b.iter().take_while(|&&c| matches!(c, b' ' | b'\t' | b'\n')).count();
+    if matches!(b.get(i), Some(b'+' | b'-')) {
+        i += 1;
+    }
+    let start = i;
+    while digit(i) {
+        i += 1;
+    }
+    let mut digits = i - start;
+    if b.get(i) == Some(&b'.') {
+        i += 1;
+        while digit(i) {
+            i += 1;
+            digits += 1;
+        }
+    }
+    if digits == 0 {
+        return 0;
+    }
+    if matches!(b.get(i), Some(b'e' | b'E')) {
+        let j = i + 1 + matches!(b.get(i + 1), Some(b'+' | b'-')) as usize;
+        if digit(j) {
+            i = j;
+            while digit(i) {
+                i += 1;
+            }
+        }
+    }
+    i
+}
+
+fn str_to_num(s: &str) -> f64 {
+    s[..numeric_prefix(s)].trim_start().parse().unwrap_or(0.0)
+}
+
+/// Returns whether a string from the input looks like a number, apart from
+/// blanks around it.
+fn looks_numeric(s: &str) -> bool {
+    let len = numeric_prefix(s);
+    len > 0 && s[len..].trim_matches(|c| matches!(c, ' ' | '\t' | '\n')).is_empty()
+}
+
+impl Value {
+    fn to_num(&self) -> f64 {
+        match self {
+            Value::Uninit => 0.0,
+            Value::Num(n) => *n,
+            Value::Str(s) | Value::StrNum(s) => str_to_num(s),
+        }
+    }
+
+    fn to_bool(&self) -> bool {
+        match self {
+            Value::Uninit => false,
+            Value::Num(n) => *n != 0.0,
+            Value::Str(s) => !s.is_empty(),
+            Value::StrNum(s) if looks_numeric(s) => str_to_num(s) != 0.0,
+            Value::StrNum(s) => !s.is_empty(),
+        }
+    }
+
+    /// Returns whether the value compares as a number.
+    fn is_numeric(&self) -> bool {
+        match self {
+            Value::Uninit | Value::Num(_) => true,
+            Value::Str(_) => false,
+            Value::StrNum(s) => looks_numeric(s),
+        }
+    }
+}
+
+/// The flags, width and precision of a printf conversion.
+#[derive(Default)]
+struct Spec {
+    left: bool,
+    plus: bool,
+    space: bool,
+    alt: bool,
+    zero: bool,
+    width: usize,
+    precision: Option<usize>,
+}
+
+impl Spec {
+    fn sign(&self, negative: bool) -> &'static str {
+        if negative {
+            "-"
+        } else if self.plus {
+            "+"
+        } else if self.space {
+            " "
+        } else {
+            ""
+        }
+    }
+
+    /// Pads a conversion to the width. The sign, or a prefix such as 0x,
+    /// goes before any zeros that pad it.
+    fn pad(&self, sign: &str, body: &str, zeros: bool) -> String {
+        let len = sign.chars().count() + body.chars().count();
+        let fill = self.width.saturating_sub(len);
+        if self.left {
+            format!("{}{}{}", sign, body, " ".repeat(fill))
+        } else if self.zero && zeros {
+            format!("{}{}{}", sign, "0".repeat(fill), body)
+        } else {
+            format!("{}{}{}", " ".repeat(fill), sign, body)
+        }
+    }
+}
+
+/// Formats a non-negative number in the style of %e.
+fn exponential(x: f64, precision: usize, alt: bool) -> String {
+    let s = format!("{:.*e}", precision, x);
+    let (mantissa, exp) = s.split_once('e').unwrap();
+    let exp: i32 = exp.parse().unwrap();
+    let point = if alt && precision == 0 { "." } else { "" };
+    format!("{}{}e{}{:02}", mantissa, point, if exp < 0 { '-' } else { '+' }, exp.abs())
+}
+
+/// Formats a non-negative number for a floating point conversion.
+fn float(x: f64, conv: char, precision: usize, alt: bool) -> String {
+    let s = if x.is_nan() {
+        "nan".to_string()
+    } else if x.is_infinite() {
+        "inf".to_string()
+    } else {
+        match conv.to_ascii_lowercase() {
+            'f' => {
+                let point = if alt && precision == 0 { "." } else { "" };
+                format!("{:.*}{}", precision, x, point)
+            },
+            'e' => exponential(x, precision, alt),
+            _ => {
+                let precision = precision.max(1);
+                let exp: i32 = if x == 0.0 {
+                    0
+                } else {
+                    let s = format!("{:.*e}", precision - 1, x);
+                    s.split_once('e').unwrap().1.parse().unwrap()
+                };
+                let mut s = if exp < -4 || exp >= precision as i32 {
+                    exponential(x, precision - 1, alt)
+                } else {
+                    format!("{:.*}", (precision as i32 - 1 - exp) as usize, x)
+                };
+                if !alt {
+                    let (mantissa, exp) = match s.find('e') {
+                        Some(i) => s.split_at(i),
+                        None => (s.as_str(), ""),
+                    };
+                    if mantissa.contains('.') {
+                        s = format!("{}{}", mantissa.trim_end_matches('0').trim_end_matches('.'), exp);
+                    }
+                }
+                s
+            },
+        }
+    };
+    if conv.is_ascii_uppercase() { s.to_uppercase() } else { s }
+}
+
+/// Formats values as printf does. Missing values are taken to be
+/// uninitialised.
+fn format(fmt: &str, args: &[Value], convfmt: &str) -> String {
+    let mut out = String::new();
+    let mut args = args.iter();
+    let mut next = || args.next().cloned().unwrap_or(Value::Uninit);
+    let mut chars = fmt.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+        let mut spec = Spec::default();
+        while let Some(&flag) = chars.peek() {
+            match flag {
+                '-' => spec.left = true,
+                '+' => spec.plus = true,
+                ' ' => spec.space = true,
+                '#' => spec.alt = true,
+                '0' => spec.zero = true,
+                _ => break,
+            }
+            chars.next();
+        }
+        if chars.peek() == Some(&'*') {
+            chars.next();
+            let width = next().to_num() as i64;
+            spec.left |= width < 0;
+            spec.width = width.unsigned_abs() as usize;
+        } else {
+            while let Some(d) = chars.peek().and_then(|c| c.to_digit(10)) {
+                spec.width = spec.width * 10 + d as usize;
+                chars.next();
+            }
+        }
+        if chars.peek() == Some(&'.') {
+            chars.next();
+            if chars.peek() == Some(&'*') {
+                chars.next();
+                let precision = next().to_num() as i64;
+                spec.precision = usize::try_from(precision).ok();
+            } else {
+                let mut precision = 0;
+                while let Some(d) = chars.peek().and_then(|c| c.to_digit(10)) {
+                    precision = precision * 10 + d as usize;
+                    chars.next();
+                }
+                spec.precision = Some(precision);
+            }
+        }
+        let conv = match chars.next() {
+            Some(conv) => conv,
+            None => {
+                out.push('%');
+                break;
+            }
+        };
+        let converted = match conv {
+            '%' => "%".to_string(),
+            'd' | 'i' => {
+                let n = next().to_num();
+                let n = if n.is_nan() { 0 } else { n as i64 };
+                let mut digits = n.unsigned_abs().to_string();
+                if let Some(precision) = spec.precision {
+                    if precision == 0 && n == 0 {
+                        digits.clear();
+                    }
+                    digits = format!("{}{}", "0".repeat(precision.saturating_sub(digits.len())), digits);
+                }
+                spec.pad(spec.sign(n < 0), &digits, spec.precision.is_none())
+            },
+            'o' | 'u' | 'x' | 'X' => {
+                let n = next().to_num();
+                let n = if n < 0.0 { n as i64 as u64 } else { n as u64 };
+                let mut digits = match conv {
+                    'o' => format!("{:o}", n),
+                    'x' => format!("{:x}", n),
+                    'X' => format!("{:X}", n),
+                    _ => n.to_string(),
+                };
+                if let Some(precision) = spec.precision {
+                    if precision == 0 && n == 0 {
+                        digits.clear();
+                    }
+                    digits = format!("{}{}", "0".repeat(precision.saturating_sub(digits.len())), digits);
+                }
+                let prefix = match conv {
+                    'o' if spec.alt && !digits.starts_with('0') => "0",
+                    'x' if spec.alt && n != 0 => "0x",
+                    'X' if spec.alt && n != 0 => "0X",
+                    _ => "",
+                };
+                spec.pad(prefix, &digits, spec.precision.is_none())
+            },
+            'c' => {
+                let s = match next() {
+                    Value::Num(n) => char::from_u32(n as u32).map(String::from).unwrap_or_default(),
+                    value => to_str(&value, convfmt).chars().take(1).collect(),
+                };
+                spec.pad("", &s, false)
+            },
+            's' => {
+                let s = to_str(&next(), convfmt);
+                let s = match spec.precision {
+                    Some(precision) => s.chars().take(precision).collect(),
+                    None => s,
+                };
+                spec.pad("", &s, false)
+            },
+            'e' | 'E' | 'f' | 'F' | 'g' | 'G' => {
+                let x = next().to_num();
+                let body = float(x.abs(), conv, spec.precision.unwrap_or(6), spec.alt);
+                spec.pad(spec.sign(x.is_sign_negative() && !x.is_nan()), &body, x.is_finite())
+            },
+            _ => format!("%{}", conv),
+        };
+        out.push_str(&converted);
+    }
+    out
+}
+
+/// Converts a number to a string: an integer as such, and anything else
+/// with the format.
+fn num_to_str(n: f64, fmt: &str) -> String {
+    if n == n.trunc() && n.abs() < 1e16 {
+        format!("{}", n as i64)
+    } else {
+        format(fmt, &[Value::Num(n)], "%.6g")
+    }
+}
+
+fn to_str(value: &Value, convfmt: &str) -> String {
+    match value {
+        Value::Uninit => String::new(),
+        Value::Num(n) => num_to_str(*n, convfmt),
+        Value::Str(s) | Value::StrNum(s) => s.clone(),
+    }
+}
+
+/// Reads a record from the input, ended by the first character of the
+/// record separator or, if that is empty, by one or more blank lines.
+fn read_record(reader: &mut dyn BufRead, rs: &str) -> io::Result<Option<String>> {
+    if rs.is_empty() {
+        let mut record = Vec::new();
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            if reader.read_until(b'\n', &mut line)? == 0 {
+                break;
+            }
+            let text = line.strip_suffix(b"\n").unwrap_or(&line);
+            if text.is_empty() {
+                if record.is_empty() {
+                    continue;
+                }
+                break;
+            }
+            if !record.is_empty() {
+                record.push(b'\n');
+            }
+            record.extend_from_slice(text);
+        }
+        return Ok(if record.is_empty() { None } else { Some(String::from_utf8_lossy(&record).into_owned()) });
+    }
+    let sep = rs.as_bytes()[0];
+    let mut buf = Vec::new();
+    if reader.read_until(sep, &mut buf)? == 0 {
+        return Ok(None);
+    }
+    if buf.last() == Some(&sep) {
+        buf.pop();
+    }
+    Ok(Some(String::from_utf8_lossy(&buf).into_owned()))
+}
+
+/// Splits a string at the matches of a regular expression.
+fn split_regex(s: &str, re: &Regex) -> Vec<String> {
+    if s.is_empty() {
+        return Vec::new();
+    }
+    let text = s.as_bytes();
+    let mut fields = Vec::new();
+    let mut start = 0;
+    let mut pos = 0;
+    while let Some(m) = re.find_at(text, pos) {
+        if m.end() == m.start() {
+            // An empty match separates nothing.
+            if m.end() >= text.len() {
+                break;
+            }
+            pos = m.end() + 1;
+            continue;
+        }
+        fields.push(String::from_utf8_lossy(&text[start..m.start()]).into_owned());
+        start = m.end();
+        pos = m.end();
+    }
+    fields.push(String::from_utf8_lossy(&text[start..]).into_owned());
+    fields
+}
+
+/// Replaces the first match of a regular expression or, if global, all of
+/// them, as sub and gsub do. In the replacement, & stands for the matched
+/// text and \& for a literal &. Returns the result and the number of
+/// replacements.
+fn substitute(re: &Regex, text: &[u8], repl: &str, global: bool) -> (String, usize) {
+    let mut out = Vec::new();
+    let mut count = 0;
+    let mut pos = 0;
+    // The end of the last non-empty match, where an empty match doesn't
+    // count.
+    let mut last_end = None;
+    while pos <= text.len() {
+        let m = match re.find_at(text, pos) {
+            Some(m) => m,
+            None => break,
+        };
+        let (start, end) = (m.start(), m.end());
+        out.extend_from_slice(&text[pos..start]);
+        if start != end || last_end != Some(start) {
+            let mut bytes = repl.bytes().peekable();
+            while let Some(c) = bytes.next() {
+                match c {
+                    b'\\' if matches!(bytes.peek(), Some(b'&' | b'\\')) => out.push(bytes.next().unwrap()),
+                    b'&' => out.extend_from_slice(&text[start..end]),
+                    _ => out.push(c),
+                }
+            }
+            count += 1;
+        }
+        if start == end {
+            if start < text.len() {
+                out.push(text[start]);
+            }
+            pos = start + 1;
+        } else {
+            pos = end;
+            last_end = Some(end);
+        }
+        if !global {
+            break;
+        }
+    }
+    if pos <= text.len() {
+        out.extend_from_slice(&text[pos..]);
+    }
+    (String::from_utf8_lossy(&out).into_owned(), count)
+}
+
+/// Why the normal flow of a program was interrupted.
+#[derive(Debug)]
+enum Flow {
+    Next,
+    Break,
+    Continue,
+    /// exit, with the exit status if one was given.
+    Exit(Option<i32>),
+    /// A return from a function, with its value.
+    Return(Value),
+    /// An error, which stops the program.
+    Error(String),
+}
+
+/// An output stream opened by print or printf.
+enum Output {
+    File(BufWriter<File>),
+    Pipe(BufWriter<process::ChildStdin>, process::Child),
+}
+
+impl Output {
+    fn writer(&mut self) -> &mut dyn Write {
+        match self {
+            Output::File(w) => w,
+            Output::Pipe(w, _) => w,
+        }
+    }
+
+    /// Flushes and closes the stream, returning the exit status of a
+    /// command.
+    fn close(self) -> i32 {
+        match self {
+            Output::File(mut w) => if w.flush().is_ok() { 0 } else { -1 },
+            Output::Pipe(w, mut child) => {
+                drop(w);
+                child.wait().ok().and_then(|status| status.code()).unwrap_or(-1)
+            },
+        }
+    }
+}
+
+/// An input stream opened by getline: a file or the output of a command.
+struct Input {
+    reader: Box<dyn BufRead>,
+    child: Option<process::Child>,
+}
+
+impl Input {
+    fn close(self) -> i32 {
+        drop(self.reader);
+        match self.child {
+            Some(mut child) => child.wait().ok().and_then(|status| status.code()).unwrap_or(-1),
+            None => 0,
+        }
+    }
+}
+
+type Array = Rc<RefCell<HashMap<String, Value>>>;
+
+/// The contents of a variable, which holds either a value or an array.
+/// Until it is used, it may become either.
+#[derive(Clone, Debug)]
+enum Cell {
+    Value(Value),
+    Array(Array),
+}
+
+/// Somewhere a value can be assigned, found before the value is computed
+/// so that subscripts and field numbers are only evaluated once.
+enum Place {
+    Var(Var),
+    Field(usize),
+    Elem(Array, String),
+}
+
+struct Interp {
+    globals: Vec<Cell>,
+    /// The parameters and locals of the functions being called.
+    frames: Vec<Vec<Cell>>,
+    names: HashMap<String, usize>,
+    funcs: Rc<Vec<Function>>,
+    record: String,
+    /// The fields of the record, $1 onwards.
+    fields: Vec<String>,
+    /// The operands, which name the input files, and the next to use.
+    operands: Vec<OsString>,
+    next_operand: usize,
+    /// The input file being read, if any.
+    input: Option<Box<dyn BufRead>>,
+    /// Whether any input file was opened; if not, the standard input is
+    /// read.
+    opened: bool,
+    out: Box<dyn Write>,
+    outputs: HashMap<String, Output>,
+    inputs: HashMap<String, Input>,
+    /// The regular expressions that strings have been used as.
+    regexes: HashMap<String, Rc<Regex>>,
+    /// Whether each range pattern has started matching.
+    in_range: Vec<bool>,
+    seed: f64,
+    rand_state: u64,
+}
+
+impl Interp {
+    fn new(program: &Program, out: Box<dyn Write>) -> Interp {
+        let mut globals = vec![Cell::Value(Value::Uninit); program.names.len().max(SPECIALS.len())];
+        let mut init = |var, value| globals[var] = Cell::Value(value);
+        init(NR, Value::Num(0.0));
+        init(NF, Value::Num(0.0));
+        init(FS, Value::Str(" ".to_string()));
+        init(OFS, Value::Str(" ".to_string()));
+        init(ORS, Value::Str("\n".to_string()));
+        init(RS, Value::Str("\n".to_string()));
+        init(FNR, Value::Num(0.0));
+        init(SUBSEP, Value::Str("\x1c".to_string()));
+        init(RSTART, Value::Num(0.0));
+        init(RLENGTH, Value::Num(-1.0));
+        init(CONVFMT, Value::Str("%.6g".to_string()));
+        init(OFMT, Value::Str("%.6g".to_string()));
+        Interp {
+            globals,
+            frames: Vec::new(),
+            names: program.names.clone(),
+            funcs: Rc::clone(&program.funcs),
+            record: String::new(),
+            fields: Vec::new(),
+            operands: Vec::new(),
+            next_operand: 0,
+            input: None,
+            opened: false,
+            out,
+            outputs: HashMap::new(),
+            inputs: HashMap::new(),
+            regexes: HashMap::new(),
+            in_range: Vec::new(),
+            seed: 0.0,
+            rand_state: 0,
+        }
+    }
+
+    fn to_str(&self, value: &Value) -> String {
+        to_str(value, &self.var_str(CONVFMT))
+    }
+
+    /// Converts a value to a string for print, which formats numbers with
+    /// OFMT.
+    fn output_str(&self, value: &Value) -> String {
+        to_str(value, &self.var_str(OFMT))
+    }
+
+    /// Returns the value of a special variable as a string.
+    fn var_str(&self, var: usize) -> String {
+        match &self.globals[var] {
+            Cell::Value(value) => to_str(value, "%.6g"),
+            Cell::Array(_) => String::new(),
+        }
+    }
+
+    /// Sets a special variable that counts something.
+    fn count(&mut self, var: usize, n: f64) {
+        self.globals[var] = Cell::Value(Value::Num(n));
+    }
+
+    fn counter(&self, var: usize) -> f64 {
+        match &self.globals[var] {
+            Cell::Value(value) => value.to_num(),
+            Cell::Array(_) => 0.0,
+        }
+    }
+
+    fn cell(&mut self, var: Var) -> &mut Cell {
+        match var {
+            Var::Global(i) => &mut self.globals[i],
+            Var::Local(i) => &mut self.frames.last_mut().unwrap()[i],
+        }
+    }
+
+    fn scalar(&mut self, var: Var) -> Result<Value, Flow> {
+        match self.cell(var) {
+            Cell::Value(value) => Ok(value.clone()),
+            Cell::Array(_) => Err(Flow::Error("can't use array as scalar".to_string())),
+        }
+    }
+
+    /// Returns the array a variable holds, making it one if it was unused.
+    fn array(&mut self, var: Var) -> Result<Array, Flow> {
+        let cell = self.cell(var);
+        match cell {
+            Cell::Array(array) => Ok(Rc::clone(array)),
+            Cell::Value(Value::Uninit) => {
+                let array = Array::default();
+                *cell = Cell::Array(Rc::clone(&array));
+                Ok(array)
+            },
+            Cell::Value(_) => Err(Flow::Error("can't use scalar as array".to_string())),
+        }
+    }
+
+    /// Joins the subscripts of an array element with SUBSEP.
+    fn subscript(&mut self, subscripts: &[Expr]) -> Result<String, Flow> {
+        let mut key = String::new();
+        for (i, subscript) in subscripts.iter().enumerate() {
+            if i > 0 {
+                key.push_str(&self.var_str(SUBSEP));
+            }
+            let value = self.eval(subscript)?;
+            key.push_str(&self.to_str(&value));
+        }
+        Ok(key)
+    }
+
+    /// Compiles a string as a regular expression, or finds it already
+    /// compiled.
+    fn regex(&mut self, source: &str) -> Result<Rc<Regex>, Flow> {
+        if let Some(re) = self.regexes.get(source) {
+            return Ok(Rc::clone(re));
+        }
+        let re = Rc::new(compile(source).map_err(Flow::Error)?);
+        self.regexes.insert(source.to_string(), Rc::clone(&re));
+        Ok(re)
+    }
+
+    /// Returns the regular expression an argument stands for: either a
+    /// literal one or a string to be compiled.
+    fn regex_arg(&mut self, expr: &Expr) -> Result<Rc<Regex>, Flow> {
+        match expr {
+            Expr::Regex(re) => Ok(Rc::clone(re)),
+            _ => {
+                let source = self.eval(expr)?;
+                let source = self.to_str(&source);
+                self.regex(&source)
+            },
+        }
+    }
+
+    /// Splits a string into fields by the field separator: runs of blanks
+    /// if it is a space, the character if it is any other single
+    /// character, or otherwise the matches of it as a regular expression.
+    fn split(&mut self, s: &str, fs: &str) -> Result<Vec<String>, Flow> {
+        if fs == " " {
+            return Ok(s.split([' ', '\t', '\n']).filter(|f| !f.is_empty()).map(String::from).collect());
+        }
+        if s.is_empty() {
+            return Ok(Vec::new());
+        }
+        if fs.chars().count() == 1 && fs != "\\" {
+            return Ok(s.split(fs).map(String::from).collect());
+        }
+        let re = self.regex(fs)?;
+        Ok(split_regex(s, &re))
+    }
+
+    /// Sets the record and splits it into fields.
+    fn set_record(&mut self, record: String) -> Result<(), Flow> {
+        let fs = self.var_str(FS);
+        self.fields = if self.var_str(RS).is_empty() && fs != " " {
+            // With blank lines separating records, a newline always
+            // separates fields.
+            let mut fields = Vec::new();
+            for line in record.split('\n') {
+                fields.extend(self.split(line, &fs)?);
+            }
+            fields
+        } else {
+            self.split(&record, &fs)?
+        };
+        self.record = record;
+        self.count(NF, self.fields.len() as f64);
+        Ok(())
+    }
+
+    /// Rebuilds the record from the fields, after one has changed.
+    fn rebuild(&mut self) {
+        self.record = self.fields.join(&self.var_str(OFS));
+        self.count(NF, self.fields.len() as f64);
+    }
+
+    fn field(&self, i: usize) -> Value {
+        if i == 0 {
+            return Value::StrNum(self.record.clone());
+        }
+        match self.fields.get(i - 1) {
+            Some(field) => Value::StrNum(field.clone()),
+            None => Value::Uninit,
+        }
+    }
+
+    fn set_field(&mut self, i: usize, value: String) -> Result<(), Flow> {
+        if i == 0 {
+            return self.set_record(value);
+        }
+        if self.fields.len() < i {
+            self.fields.resize(i, String::new());
+        }
+        self.fields[i - 1] = value;
+        self.rebuild();
+        Ok(())
+    }
+
+    fn field_index(&mut self, expr: &Expr) -> Result<usize, Flow> {
+        let n = self.eval(expr)?.to_num();
+        if n < 0.0 {
+            return Err(Flow::Error(format!("trying to access out of range field {}", n as i64)));
+        }
+        Ok(n as usize)
+    }
+
+    fn set_var(&mut self, var: Var, value: Value) -> Result<(), Flow> {
+        if var == Var::Global(NF) {
+            let n = value.to_num().max(0.0) as usize;
+            self.fields.resize(n, String::new());
+            self.rebuild();
+            return Ok(());
+        }
+        match self.cell(var) {
+            Cell::Array(_) => Err(Flow::Error("can't assign to an array".to_string())),
+            cell => {
+                *cell = Cell::Value(value);
+                Ok(())
+            },
+        }
+    }
+
+    fn place(&mut self, lvalue: &Expr) -> Result<Place, Flow> {
+        Ok(match lvalue {
+            Expr::Var(var) => Place::Var(*var),
+            Expr::Field(index) => Place::Field(self.field_index(index)?),
+            Expr::Index(var, subscripts) => {
+                let key = self.subscript(subscripts)?;
+                Place::Elem(self.array(*var)?, key)
+            },
+            _ => unreachable!("assignment to a value that isn't an lvalue"),
+        })
+    }
+
+    fn get(&mut self, place: &Place) -> Result<Value, Flow> {
+        match place {
+            Place::Var(var) => self.scalar(*var),
+            Place::Field(i) => Ok(self.field(*i)),
+            Place::Elem(array, key) => Ok(array.borrow_mut().entry(key.clone()).or_insert(Value::Uninit).clone()),
+        }
+    }
+
+    fn put(&mut self, place: Place, value: Value) -> Result<(), Flow> {
+        match place {
+            Place::Var(var) => self.set_var(var, value),
+            Place::Field(i) => {
+                let value = self.to_str(&value);
+                self.set_field(i, value)
+            },
+            Place::Elem(array, key) => {
+                array.borrow_mut().insert(key, value);
+                Ok(())
+            },
+        }
+    }
+
+    fn compare(&self, a: &Value, b: &Value) -> Ordering {
+        if a.is_numeric() && b.is_numeric() {
+            a.to_num().partial_cmp(&b.to_num()).unwrap_or(Ordering::Equal)
+        } else {
+            self.to_str(a).cmp(&self.to_str(b))
+        }
+    }
+
+    fn arith(&self, op: BinOp, a: f64, b: f64) -> Result<f64, Flow> {
+        Ok(match op {
+            BinOp::Add => a + b,
+            BinOp::Sub => a - b,
+            BinOp::Mul => a * b,
+            BinOp::Div | BinOp::Mod if b == 0.0 => return Err(Flow::Error("division by zero".to_string())),
+            BinOp::Div => a / b,
+            BinOp::Mod => a % b,
+            BinOp::Pow => a.powf(b),
+            _ => unreachable!("{:?} isn't arithmetic", op),
+        })
+    }
+
+    /// Returns a pseudo-random number in [0, 1).
+    fn rand(&mut self) -> f64 {
+        // xorshift64*, seeded so that the same seed gives the same
+        // sequence.
+        if self.rand_state == 0 {
+            self.rand_state = (self.seed.to_bits() ^ 0x9e37_79b9_7f4a_7c15) | 1;
+        }
+        let mut x = self.rand_state;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.rand_state = x;
+        (x.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Flushes all the output, before running a command that may write to
+    /// the same places.
+    fn flush(&mut self) {
+        let _ = self.out.flush();
+        for output in self.outputs.values_mut() {
+            let _ = output.writer().flush();
+        }
+    }
+
+    /// Closes a file or command opened for output or getline, returning
+    /// its status or -1 if it wasn't open.
+    fn close(&mut self, name: &str) -> i32 {
+        let mut status = -1;
+        if let Some(output) = self.outputs.remove(name) {
+            status = output.close();
+        }
+        if let Some(input) = self.inputs.remove(name) {
+            status = input.close();
+        }
+        status
+    }
+
+    /// Calls a built-in function. Those that take an array, regular
+    /// expression or variable to set look at the arguments themselves.
+    fn call(&mut self, name: &str, args: &[Expr]) -> Result<Value, Flow> {
+        match name {
+            "length" => {
+                let n = match args.first() {
+                    None => self.record.chars().count(),
+                    Some(Expr::Var(var)) if matches!(self.cell(*var), Cell::Array(_)) => {
+                        self.array(*var)?.borrow().len()
+                    },
+                    Some(arg) => {
+                        let value = self.eval(arg)?;
+                        self.to_str(&value).chars().count()
+                    },
+                };
+                return Ok(Value::Num(n as f64));
+            },
+            "split" => {
+                let s = self.eval(&args[0])?;
+                let s = self.to_str(&s);
+                let fields = match args.get(2) {
+                    None => {
+                        let fs = self.var_str(FS);
+                        self.split(&s, &fs)?
+                    },
+                    Some(Expr::Regex(re)) => split_regex(&s, re),
+                    Some(fs) => {
+                        let fs = self.eval(fs)?;
+                        let fs = self.to_str(&fs);
+                        self.split(&s, &fs)?
+                    },
+                };
+                let array = match &args[1] {
+                    Expr::Var(var) => self.array(*var)?,
+                    _ => unreachable!("split into something that isn't an array"),
+                };
+                let mut array = array.borrow_mut();
+                array.clear();
+                for (i, field) in fields.iter().enumerate() {
+                    array.insert((i + 1).to_string(), Value::StrNum(field.clone()));
+                }
+                return Ok(Value::Num(fields.len() as f64));
+            },
+            "sub" | "gsub" => {
+                let re = self.regex_arg(&args[0])?;
+                let repl = self.eval(&args[1])?;
+                let repl = self.to_str(&repl);
+                let place = match args.get(2) {
+                    Some(lvalue) => self.place(lvalue)?,
+                    None => Place::Field(0),
+                };
+                let text = self.get(&place)?;
+                let text = self.to_str(&text);
+                let (result, n) = substitute(&re, text.as_bytes(), &repl, name == "gsub");
+                if n > 0 {
+                    self.put(place, Value::Str(result))?;
+                }
+                return Ok(Value::Num(n as f64));
+            },
+            "match" => {
+                let s = self.eval(&args[0])?;
+                let s = self.to_str(&s);
+                let re = self.regex_arg(&args[1])?;
+                let text = s.as_bytes();
+                let chars = |bytes: &[u8]| String::from_utf8_lossy(bytes).chars().count() as f64;
+                let (start, len) = match re.find_at(text, 0) {
+                    Some(m) => (chars(&text[..m.start()]) + 1.0, chars(&text[m.start()..m.end()])),
+                    None => (0.0, -1.0),
+                };
+                self.count(RSTART, start);
+                self.count(RLENGTH, len);
+                return Ok(Value::Num(start));
+            },
+            _ => (),
+        }
+        let mut values = Vec::new();
+        for arg in args {
+            values.push(self.eval(arg)?);
+        }
+        let num = |i: usize| values[i].to_num();
+        Ok(Value::Num(match name {
+            "atan2" => num(0).atan2(num(1)),
+            "close" => {
+                let name = self.to_str(&values[0]);
+                self.close(&name) as f64
+            },
+            "cos" => num(0).cos(),
+            "exp" => num(0).exp(),
+            "fflush" => {
+                self.flush();
+                0.0
+            },
+            "index" => {
+                let s = self.to_str(&values[0]);
+                let t = self.to_str(&values[1]);
+                s.find(&t).map_or(0, |i| s[..i].chars().count() + 1) as f64
+            },
+            "int" => num(0).trunc(),
+            "log" => num(0).ln(),
+            "rand" => self.rand(),
+            "sin" => num(0).sin(),
+            "sprintf" => {
+                let fmt = self.to_str(&values[0]);
+                return Ok(Value::Str(format(&fmt, &values[1..], &self.var_str(CONVFMT))));
+            },
+            "sqrt" => num(0).sqrt(),
+            "srand" => {
+                let seed = match values.first() {
+                    Some(value) => value.to_num(),
+                    None => SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs()) as f64,
+                };
+                self.rand_state = 0;
+                std::mem::replace(&mut self.seed, seed)
+            },
+            "substr" => {
+                // The characters from m to m + n - 1, with the positions
+                // rounded and kept within the string.
+                let s = self.to_str(&values[0]);
+                let len = s.chars().count() as f64;
+                let m = num(1).round();
+                let end = if values.len() > 2 { m + num(2).round() } else { f64::INFINITY };
+                let (start, end) = (m.max(1.0), end.min(len + 1.0));
+                if start.is_nan() || end <= start {
+                    return Ok(Value::Str(String::new()));
+                }
+                let s = s.chars().skip(start as usize - 1).take((end - start) as usize).collect();
+                return Ok(Value::Str(s));
+            },
+            "system" => {
+                self.flush();
+                let command = self.to_str(&values[0]);
+                match process::Command::new("sh").arg("-c").arg(&command).status() {
+                    Ok(status) => status.code().unwrap_or(256) as f64,
+                    Err(e) => {
+                        err!("sh: {}", e);
+                        -1.0
+                    }
+                }
+            },
+            "tolower" => return Ok(Value::Str(self.to_str(&values[0]).to_lowercase())),
+            "toupper" => return Ok(Value::Str(self.to_str(&values[0]).to_uppercase())),
+            _ => unreachable!("no built-in function {}", name),
+        }))
+    }
+
+    /// Calls a user-defined function. Arrays are passed by reference, and
+    /// anything else by value.
+    fn call_user(&mut self, i: usize, args: &[Expr]) -> Result<Value, Flow> {
+        let funcs = Rc::clone(&self.funcs);
+        let func = &funcs[i];
+        if self.frames.len() >= MAX_DEPTH {
+            return Err(Flow::Error(format!("function {} nested too deeply", func.name)));
+        }
+        let mut frame = Vec::with_capacity(func.params);
+        for arg in args {
+            frame.push(match arg {
+                Expr::Var(var) => self.cell(*var).clone(),
+                _ => Cell::Value(self.eval(arg)?),
+            });
+        }
+        frame.resize(func.params, Cell::Value(Value::Uninit));
+        self.frames.push(frame);
+        let result = self.exec_all(&func.body);
+        let frame = self.frames.pop().unwrap();
+        // An unused variable passed to a parameter that the function used
+        // as an array becomes that array.
+        for (arg, cell) in args.iter().zip(frame) {
+            if let (Expr::Var(var), Cell::Array(array)) = (arg, cell) {
+                let cell = self.cell(*var);
+                if matches!(cell, Cell::Value(Value::Uninit)) {
+                    *cell = Cell::Array(array);
+                }
+            }
+        }
+        match result {
+            Ok(()) => Ok(Value::Uninit),
+            Err(Flow::Return(value)) => Ok(value),
+            Err(flow) => Err(flow),
+        }
+    }
+
+    /// Reads a record for getline from a file or command.
+    fn read_input(&mut self, name: &str, command: bool) -> io::Result<Option<String>> {
+        if !self.inputs.contains_key(name) {
+            let input = if command {
+                self.flush();
+                let mut child = process::Command::new("sh").arg("-c").arg(name)
+                    .stdout(process::Stdio::piped()).spawn()?;
+                let stdout = child.stdout.take().unwrap();
+                Input { reader: Box::new(BufReader::new(stdout)), child: Some(child) }
+            } else if name == "-" || name == "/dev/stdin" {
+                Input { reader: Box::new(BufReader::new(io::stdin())), child: None }
+            } else {
+                Input { reader: Box::new(BufReader::new(File::open(name)?)), child: None }
+            };
+            self.inputs.insert(name.to_string(), input);
+        }
+        let rs = self.var_str(RS);
+        read_record(&mut *self.inputs.get_mut(name).unwrap().reader, &rs)
+    }
+
+    fn getline(&mut self, source: &Source, var: &Option<Box<Expr>>) -> Result<Value, Flow> {
+        let record = match source {
+            Source::Main => self.read_main()?,
+            Source::File(file) | Source::Command(file) => {
+                let name = self.eval(file)?;
+                let name = self.to_str(&name);
+                match self.read_input(&name, matches!(source, Source::Command(_))) {
+                    Ok(record) => record,
+                    Err(_) => return Ok(Value::Num(-1.0)),
+                }
+            },
+        };
+        let record = match record {
+            Some(record) => record,
+            None => return Ok(Value::Num(0.0)),
+        };
+        if let Source::Command(_) = source {
+            self.count(NR, self.counter(NR) + 1.0);
+        }
+        match var {
+            Some(lvalue) => {
+                let place = self.place(lvalue)?;
+                self.put(place, Value::StrNum(record))?;
+            },
+            None => self.set_record(record)?,
+        }
+        Ok(Value::Num(1.0))
+    }
+
+    fn eval(&mut self, expr: &Expr) -> Result<Value, Flow> {
+        Ok(match expr {
+            Expr::Num(n) => Value::Num(*n),
+            Expr::Str(s) => Value::Str(s.clone()),
+            Expr::Regex(re) => Value::Num(re.is_match(self.record.as_bytes()) as i32 as f64),
+            Expr::Var(var) => self.scalar(*var)?,
+            Expr::Index(..) | Expr::Field(_) => {
+                let place = self.place(expr)?;
+                self.get(&place)?
+            },
+            Expr::In(subscripts, var) => {
+                let key = self.subscript(subscripts)?;
+                Value::Num(self.array(*var)?.borrow().contains_key(&key) as i32 as f64)
+            },
+            Expr::Group(_) => return Err(Flow::Error("parenthesised list out of context".to_string())),
+            Expr::Assign(op, lvalue, rhs) => {
+                let place = self.place(lvalue)?;
+                let value = self.eval(rhs)?;
+                let value = match op {
+                    None => value,
+                    Some(op) => {
+                        let current = self.get(&place)?.to_num();
+                        Value::Num(self.arith(*op, current, value.to_num())?)
+                    },
+                };
+                self.put(place, value.clone())?;
+                value
+            },
+            Expr::Cond(cond, then, otherwise) => {
+                if self.eval(cond)?.to_bool() {
+                    self.eval(then)?
+                } else {
+                    self.eval(otherwise)?
+                }
+            },
+            Expr::And(a, b) => Value::Num((self.eval(a)?.to_bool() && self.eval(b)?.to_bool()) as i32 as f64),
+            Expr::Or(a, b) => Value::Num((self.eval(a)?.to_bool() || self.eval(b)?.to_bool()) as i32 as f64),
+            Expr::Match(negate, text, re) => {
+                let text = self.eval(text)?;
+                let text = self.to_str(&text);
+                let re = self.regex_arg(re)?;
+                Value::Num((re.is_match(text.as_bytes()) != *negate) as i32 as f64)
+            },
+            Expr::Binary(op, a, b) => {
+                let a = self.eval(a)?;
+                let b = self.eval(b)?;
+                let ordering = || self.compare(&a, &b);
+                match op {
+                    BinOp::Concat => Value::Str(self.to_str(&a) + &self.to_str(&b)),
+                    BinOp::Lt => Value::Num((ordering() == Ordering::Less) as i32 as f64),
+                    BinOp::Le => Value::Num((ordering() != Ordering::Greater) as i32 as f64),
+                    BinOp::Eq => Value::Num((ordering() == Ordering::Equal) as i32 as f64),
+                    BinOp::Ne => Value::Num((ordering() != Ordering::Equal) as i32 as f64),
+                    BinOp::Gt => Value::Num((ordering() == Ordering::Greater) as i32 as f64),
+                    BinOp::Ge => Value::Num((ordering() != Ordering::Less) as i32 as f64),
+                    _ => Value::Num(self.arith(*op, a.to_num(), b.to_num())?),
+                }
+            },
+            Expr::Not(a) => Value::Num(!self.eval(a)?.to_bool() as i32 as f64),
+            Expr::Neg(a) => Value::Num(-self.eval(a)?.to_num()),
+            Expr::Plus(a) => Value::Num(self.eval(a)?.to_num()),
+            Expr::Incr(lvalue, delta, prefix) => {
+                let place = self.place(lvalue)?;
+                let old = self.get(&place)?.to_num();
+                self.put(place, Value::Num(old + delta))?;
+                Value::Num(if *prefix { old + delta } else { old })
+            },
+            Expr::Call(name, args) => self.call(name, args)?,
+            Expr::User(i, args) => self.call_user(*i, args)?,
+            Expr::Getline(source, var) => self.getline(source, var)?,
+        })
+    }
+
+    /// Writes the output of print or printf.
+    fn write(&mut self, dest: &Option<(Dest, Expr)>, text: &str) -> Result<(), Flow> {
+        let (dest, target) = match dest {
+            Some(dest) => dest,
+            None => {
+                return self.out.write_all(text.as_bytes()).map_err(|e| Flow::Error(format!("write error: {}", e)));
+            },
+        };
+        let name = self.eval(target)?;
+        let name = self.to_str(&name);
+        if name == "/dev/stdout" || name == "-" {
+            return self.out.write_all(text.as_bytes()).map_err(|e| Flow::Error(format!("write error: {}", e)));
+        }
+        if name == "/dev/stderr" {
+            let _ = io::stderr().write_all(text.as_bytes());
+            return Ok(());
+        }
+        if !self.outputs.contains_key(&name) {
+            let output = match dest {
+                Dest::Pipe => {
+                    self.flush();
+                    let mut child = process::Command::new("sh").arg("-c").arg(&name)
+                        .stdin(process::Stdio::piped()).spawn()
+                        .map_err(|e| Flow::Error(format!("{}: {}", name, e)))?;
+                    let stdin = child.stdin.take().unwrap();
+                    Output::Pipe(BufWriter::new(stdin), child)
+                },
+                _ => {
+                    let mut options = OpenOptions::new();
+                    if *dest == Dest::Append {
+                        options.append(true);
+                    } else {
+                        options.write(true).truncate(true);
+                    }
+                    let file = options.create(true).open(&name)
+                        .map_err(|e| Flow::Error(format!("can't redirect to {}: {}", name, e)))?;
+                    Output::File(BufWriter::new(file))
+                },
+            };
+            self.outputs.insert(name.clone(), output);
+        }
+        self.outputs.get_mut(&name).unwrap().writer().write_all(text.as_bytes())
+            .map_err(|e| Flow::Error(format!("{}: {}", name, e)))
+    }
+
+    fn exec_all(&mut self, stmts: &[Stmt]) -> Result<(), Flow> {
+        for stmt in stmts {
+            self.exec(stmt)?;
+        }
+        Ok(())
+    }
+
+    /// Runs the body of a loop, returning whether the loop should stop.
+    fn body(&mut self, body: &Stmt) -> Result<bool, Flow> {
+        match self.exec(body) {
+            Ok(()) | Err(Flow::Continue) => Ok(false),
+            Err(Flow::Break) => Ok(true),
+            Err(flow) => Err(flow),
+        }
+    }
+
+    fn exec(&mut self, stmt: &Stmt) -> Result<(), Flow> {
+        match stmt {
+            Stmt::Expr(expr) => {
+                self.eval(expr)?;
+            },
+            Stmt::Print { format: false, args, dest } => {
+                let mut text = if args.is_empty() { self.record.clone() } else { String::new() };
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        text.push_str(&self.var_str(OFS));
+                    }
+                    let value = self.eval(arg)?;
+                    text.push_str(&self.output_str(&value));
+                }
+                text.push_str(&self.var_str(ORS));
+                self.write(dest, &text)?;
+            },
+            Stmt::Print { format: true, args, dest } => {
+                let mut values = Vec::new();
+                for arg in args {
+                    values.push(self.eval(arg)?);
+                }
+                let fmt = self.to_str(&values[0]);
+                let text = format(&fmt, &values[1..], &self.var_str(CONVFMT));
+                self.write(dest, &text)?;
+            },
+            Stmt::If(cond, then, otherwise) => {
+                if self.eval(cond)?.to_bool() {
+                    self.exec(then)?;
+                } else if let Some(otherwise) = otherwise {
+                    self.exec(otherwise)?;
+                }
+            },
+            Stmt::While(cond, body) => {
+                while self.eval(cond)?.to_bool() {
+                    if self.body(body)? {
+                        break;
+                    }
+                }
+            },
+            Stmt::Do(body, cond) => loop {
+                if self.body(body)? || !self.eval(cond)?.to_bool() {
+                    break;
+                }
+            },
+            Stmt::For(init, cond, step, body) => {
+                if let Some(init) = init {
+                    self.eval(init)?;
+                }
+                loop {
+                    if let Some(cond) = cond {
+                        if !self.eval(cond)?.to_bool() {
+                            break;
+                        }
+                    }
+                    if self.body(body)? {
+                        break;
+                    }
+                    if let Some(step) = step {
+                        self.eval(step)?;
+                    }
+                }
+            },
+            Stmt::ForIn(var, array, body) => {
+                // The loop runs over the elements there were when it
+                // started, skipping any deleted since.
+                let array = self.array(*array)?;
+                let keys: Vec<String> = array.borrow().keys().cloned().collect();
+                for key in keys {
+                    if !array.borrow().contains_key(&key) {
+                        continue;
+                    }
+                    self.set_var(*var, Value::Str(key))?;
+                    if self.body(body)? {
+                        break;
+                    }
+                }
+            },
+            Stmt::Delete(array, subscripts) => {
+                let array = self.array(*array)?;
+                match subscripts {
+                    Some(subscripts) => {
+                        let key = self.subscript(subscripts)?;
+                        array.borrow_mut().remove(&key);
+                    },
+                    None => array.borrow_mut().clear(),
+                }
+            },
+            Stmt::Block(stmts) => self.exec_all(stmts)?,
+            Stmt::Next => return Err(Flow::Next),
+            Stmt::Break => return Err(Flow::Break),
+            Stmt::Continue => return Err(Flow::Continue),
+            Stmt::Exit(status) => {
+                let status = match status {
+                    Some(status) => Some(self.eval(status)?.to_num() as i32),
+                    None => None,
+                };
+                return Err(Flow::Exit(status));
+            },
+            Stmt::Return(value) => {
+                let value = match value {
+                    Some(value) => self.eval(value)?,
+                    None => Value::Uninit,
+                };
+                return Err(Flow::Return(value));
+            },
+        }
+        Ok(())
+    }
+
+    /// Returns whether an item's pattern selects the current record.
+    fn selects(&mut self, i: usize, pattern: &Pattern) -> Result<bool, Flow> {
+        Ok(match pattern {
+            Pattern::All => true,
+            Pattern::Expr(expr) => self.eval(expr)?.to_bool(),
+            Pattern::Range(start, end) => {
+                if !self.in_range[i] {
+                    if !self.eval(start)?.to_bool() {
+                        return Ok(false);
+                    }
+                    self.in_range[i] = true;
+                }
+                if self.eval(end)?.to_bool() {
+                    self.in_range[i] = false;
+                }
+                true
+            },
+            Pattern::Begin | Pattern::End => false,
+        })
+    }
+
+    /// Runs the items of the program for the current record.
+    fn record(&mut self, program: &Program) -> Result<(), Flow> {
+        for (i, item) in program.items.iter().enumerate() {
+            if !self.selects(i, &item.pattern)? {
+                continue;
+            }
+            let result = match &item.action {
+                Some(action) => self.exec_all(action),
+                None => self.exec(&Stmt::Print { format: false, args: Vec::new(), dest: None }),
+            };
+            match result {
+                Err(Flow::Next) => break,
+                result => result?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Assigns a value from the command line to a variable, if the
+    /// argument has the form name=value.
+    fn assignment(&mut self, arg: &str) -> Result<bool, Flow> {
+        match arg.split_once('=') {
+            Some((name, value)) if is_name(name) => {
+                if let Some(&var) = self.names.get(name) {
+                    self.set_var(Var::Global(var), Value::StrNum(unescape(value)))?;
+                }
+                Ok(true)
+            },
+            _ => Ok(false),
+        }
+    }
+
+    /// Opens the next input file named by the operands, making any
+    /// assignments before it, or the standard input if no file is named.
+    /// Returns false once there are no more.
+    fn next_input(&mut self) -> Result<bool, Flow> {
+        while self.next_operand < self.operands.len() {
+            let arg = self.operands[self.next_operand].clone();
+            self.next_operand += 1;
+            // An assignment is text, but a file's name need not be.
+            if arg.is_empty() || arg.to_str().map_or(Ok(false), |arg| self.assignment(arg))? {
+                continue;
+            }
+            self.input = Some(if arg == "-" {
+                Box::new(BufReader::new(io::stdin()))
+            } else {
+                match File::open(&arg) {
+                    Ok(f) => Box::new(BufReader::new(f)),
+                    Err(e) => return Err(Flow::Error(format!("can't open file {}: {}", arg.to_string_lossy(), e))),
+                }
+            });
+            self.globals[FILENAME] = Cell::Value(Value::Str(arg.to_string_lossy().into_owned()));
+            self.count(FNR, 0.0);
+            self.opened = true;
+            return Ok(true);
+        }
+        if self.opened {
+            return Ok(false);
+        }
+        self.input = Some(Box::new(BufReader::new(io::stdin())));
+        self.opened = true;
+        Ok(true)
+    }
+
+    /// Reads the next record of the main input, counting it in NR and FNR.
+    fn read_main(&mut self) -> Result<Option<String>, Flow> {
+        loop {
+            if self.input.is_none() && !self.next_input()? {
+                return Ok(None);
+            }
+            let rs = self.var_str(RS);
+            match read_record(&mut **self.input.as_mut().unwrap(), &rs) {
+                Ok(Some(record)) => {
+                    self.count(NR, self.counter(NR) + 1.0);
+                    self.count(FNR, self.counter(FNR) + 1.0);
+                    return Ok(Some(record));
+                },
+                Ok(None) => self.input = None,
+                Err(e) => return Err(Flow::Error(format!("read error: {}", e))),
+            }
+        }
+    }
+
+    /// Runs the actions of the items with the given pattern.
+    fn actions(&mut self, program: &Program, begin: bool) -> Result<(), Flow> {
+        for item in &program.items {
+            if matches!((&item.pattern, begin), (Pattern::Begin, true) | (Pattern::End, false)) {
+                if let Some(action) = &item.action {
+                    self.exec_all(action)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn main_loop(&mut self, program: &Program) -> Result<(), Flow> {
+        while let Some(record) = self.read_main()? {
+            self.set_record(record)?;
+            self.record(program)?;
+        }
+        Ok(())
+    }
+
+    /// Runs the program: the BEGIN actions, then the main items for each
+    /// record, then the END actions. Returns the exit status.
+    fn run(&mut self, program: &Program, operands: &[OsString]) -> i32 {
+        self.operands = operands.to_vec();
+        self.in_range = vec![false; program.items.len()];
+        let mut status = status::SUCCESS;
+        let mut result = self.actions(program, true);
+        let reads = program.items.iter().any(|item| !matches!(item.pattern, Pattern::Begin));
+        if result.is_ok() && reads {
+            result = self.main_loop(program);
+        }
+        // An exit runs the END actions, unless it was in one of them.
+        for end in [false, true] {
+            match result {
+                Err(Flow::Error(msg)) => {
+                    self.flush();
+                    err!("{}", msg);
+                    return ERROR;
+                },
+                Err(Flow::Exit(Some(code))) => status = code,
+                _ => (),
+            }
+            if end {
+                break;
+            }
+            result = self.actions(program, false);
+        }
+        self.flush();
+        for (_, output) in self.outputs.drain() {
+            output.close();
+        }
+        for (_, input) in self.inputs.drain() {
+            input.close();
+        }
+        status
+    }
+}
+
+/// Returns whether a string can be the name of a variable.
+fn is_name(s: &str) -> bool {
+    let mut chars = s.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !KEYWORDS.contains(&s)
+        && !FUNCS.iter().any(|(func, _, _)| *func == s)
+}
+
+pub const SPEC: cmd::Spec = cmd::Spec::new("pattern scanning and processing language", &[
+    cmd::Opt::arg('F', "fs", "separate fields with the regular expression fs"),
+    cmd::Opt::arg('f', "progfile", "read the program from progfile"),
+    cmd::Opt::arg('v', "var=value", "assign value to var before the program starts"),
+], "['program'] [file ...]");
+
+pub fn run(args: Vec<OsString>) -> i32 {
+    let parsed = SPEC.parse(args);
+    let mut fs: Option<String> = None;
+    let mut source = Vec::new();
+    let mut progfile = false;
+    let mut assignments = Vec::new();
+    let mut operands = parsed.operands.clone();
+
+    for (c, arg) in parsed.opts_os() {
+        match (c, arg) {
+            ('F', Some(arg)) => {
+                let arg = args::str(arg);
+                fs = Some(if arg == "t" { "\t".to_string() } else { unescape(arg) });
+            },
+            ('f', Some(arg)) => match fs::read(arg) {
+                Ok(text) => {
+                    // The program is the program files joined together.
+                    source.extend(text);
+                    source.push(b'\n');
+                    progfile = true;
+                },
+                Err(e) => {
+                    err!("{}: {}", arg.to_string_lossy(), e);
+                    process::exit(ERROR);
+                }
+            },
+            ('v', Some(arg)) => assignments.push(args::str(arg).to_string()),
+            _ => (),
+        }
+    }
+
+    if !progfile {
+        if operands.is_empty() {
+            SPEC.usage();
+        }
+        source = operands.remove(0).into_encoded_bytes();
+    }
+    // The interpreter recurses as the program does, so it runs on a
+    // thread with a stack big enough for deeply recursive functions.
+    let interpreter = thread::Builder::new().stack_size(STACK_SIZE).spawn(move || {
+        let program = match Parser::new(&source).and_then(Parser::program) {
+            Ok(program) => program,
+            Err(msg) => {
+                err!("{}", msg);
+                return ERROR;
+            }
+        };
+        let mut interp = Interp::new(&program, Box::new(BufWriter::new(io::stdout())));
+        if let Some(fs) = fs {
+            interp.globals[FS] = Cell::Value(Value::Str(fs));
+        }
+        for assignment in &assignments {
+            if !matches!(interp.assignment(assignment), Ok(true)) {
+                err!("{}: invalid assignment", assignment);
+                return ERROR;
+            }
+        }
+        interp.run(&program, &operands)
+    });
+    let status = match interpreter {
+        Ok(handle) => handle.join().unwrap_or(ERROR),
+        Err(e) => {
+            err!("{}", e);
+            ERROR
+        }
+    };
+    status
+}
+
+#[test]
+fn test_awk() {
+    assert_eq!(format("%5.2f|%-4d|%04d|%x|%c|%.3s|%e|%g|%g|%%", &[
+        Value::Num(1.23456), Value::Num(42.0), Value::Num(-7.0), Value::Num(255.0), Value::Str("hello".into()),
+        Value::Str("abcdef".into()), Value::Num(1234.5), Value::Num(0.0001), Value::Num(1e20),
+    ], "%.6g"), " 1.23|42  |-007|ff|h|abc|1.234500e+03|0.0001|1e+20|%");
+    assert_eq!(num_to_str(0.1 + 0.2, "%.6g"), "0.3");
+    assert!(looks_numeric(" 1e3 ") && !looks_numeric("1x") && !looks_numeric(""));
+
+    let source = b"BEGIN { FS = \":\" }\n\
+                   $2 ~ /^b/ { n++; s = s $1 }\n\
+                   NR == 2, NR == 3 { r = r NR }\n\
+                   END { x = 2 ^ 3 ^ 2; y = 10 % 4 \" \" (-3); $0 = \"a b c\"; $5 = \"e\"; z = $0 }\n\
+                   function fact(n) { return n <= 1 ? 1 : n * fact(n - 1) }\n\
+                   function fill(a, n,  i) { for (i = 1; i <= n; i++) a[i] = i * i }\n\
+                   END { f = fact(5); fill(sq, 3); for (k in sq) t += sq[k]; delete sq[2]; in2 = 2 in sq\n\
+                         c = split(\"a:b:c\", parts, \":\") parts[3]; g = \"hello world\"\n\
+                         ng = gsub(/o/, \"[&]\", g); m = match(\"foobar\", /ob+/) RSTART RLENGTH\n\
+                         u = substr(\"hello\", 2, 3) index(\"hello\", \"ll\") length(\"h\xc3\xa9llo\") }";
+    let program = Parser::new(source).and_then(Parser::program).unwrap();
+    let mut interp = Interp::new(&program, Box::new(io::sink()));
+    interp.in_range = vec![false; program.items.len()];
+    interp.actions(&program, true).unwrap();
+    for (nr, line) in ["1:bx", "2:y", "3:bz", "4:w"].iter().enumerate() {
+        interp.count(NR, nr as f64 + 1.0);
+        interp.set_record(line.to_string()).unwrap();
+        interp.record(&program).unwrap();
+    }
+    interp.actions(&program, false).unwrap();
+    let mut var = |name: &str| interp.scalar(Var::Global(program.names[name])).unwrap();
+    assert_eq!(var("n"), Value::Num(2.0));
+    assert_eq!(var("s"), Value::Str("13".into()));
+    assert_eq!(var("r"), Value::Str("23".into()));
+    assert_eq!(var("x"), Value::Num(512.0));
+    assert_eq!(var("y"), Value::Str("2 -3".into()));
+    assert_eq!(var("z"), Value::StrNum("a b c    e".into()));
+    assert_eq!(var("NF"), Value::Num(5.0));
+    assert_eq!(var("f"), Value::Num(120.0));
+    assert_eq!(var("t"), Value::Num(14.0));
+    assert_eq!(var("in2"), Value::Num(0.0));
+    assert_eq!(var("c"), Value::Str("3c".into()));
+    assert_eq!(var("g"), Value::Str("hell[o] w[o]rld".into()));
+    assert_eq!(var("ng"), Value::Num(2.0));
+    assert_eq!(var("m"), Value::Str("332".into()));
+    assert_eq!(var("u"), Value::Str("ell35".into()));
+
+    assert!(Parser::new(b"{ print (1 }").and_then(Parser::program).is_err());
+    assert!(Parser::new(b"{ f(1) }").and_then(Parser::program).is_err());
+}
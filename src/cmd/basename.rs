@@ -0,0 +1,64 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// An implementation of the basename(1) command in Rust.
+// See http://man.cat-v.org/unix-7th/1/basename
+use std::ffi::OsString;
+use std::io;
+use std::io::Write;
+use std::process;
+
+use crate::cmd::{Opt, Spec};
+use crate::err;
+use crate::status;
+
+/// Returns the last component of a path, ignoring any slashes it ends
+/// with, or "/" if it is nothing but slashes. A component such as . or ..
+/// is returned as it is.
+fn basename(path: &[u8]) -> &[u8] {
+    let path = match path.iter().rposition(|&c| c != b'/') {
+        Some(end) => &path[..=end],
+        None if path.is_empty() => path,
+        None => return &path[..1],
+    };
+    match path.iter().rposition(|&c| c == b'/') {
+        Some(slash) => &path[slash + 1..],
+        None => path,
+    }
+}
+
+pub const SPEC: Spec = Spec::new("strip directory and suffix from a file name", &[
+    Opt::flag('z', "end the output with NUL rather than newline"),
+], "string [suffix]");
+
+pub fn run(args: Vec<OsString>) -> i32 {
+    let mut args = args.into_iter();
+    let prog = args.next().unwrap().to_string_lossy().into_owned();
+    let mut operands: Vec<OsString> = args.collect();
+    // The string may begin with "-", so only -z and "--" are options.
+    let mut eol = b'\n';
+    if operands.len() > 1 && operands[0] == "-z" {
+	operands.remove(0);
+	eol = b'\0';
+    }
+    if operands.len() > 1 && operands[0] == "--" {
+	operands.remove(0);
+    }
+    if operands.is_empty() || operands.len() > 2 {
+	eprintln!("usage: {} [-z] string [suffix]", prog);
+	process::exit(status::USAGE)
+    }
+
+    let name = basename(operands[0].as_encoded_bytes());
+    // A suffix that is the whole name is left on it.
+    let name = match operands.get(1) {
+	Some(suffix) => name.strip_suffix(suffix.as_encoded_bytes()).filter(|rest| !rest.is_empty()).unwrap_or(name),
+	None => name
+    };
+    let mut out = io::stdout();
+    if let Err(e) = out.write_all(name).and_then(|_| out.write_all(&[eol])) {
+	err!("{}", e);
+	return status::FAILURE;
+    }
+    status::SUCCESS
+}
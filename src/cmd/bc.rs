@@ -0,0 +1,1453 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// An implementation of the bc(1) command in Rust.
+// See http://man.cat-v.org/unix-7th/1/bc
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fmt;
+use std::io;
+use std::io::{BufRead, BufReader, Write};
+use std::process;
+use std::rc::Rc;
+use std::thread;
+
+use crate::bignum;
+use crate::bignum::Number;
+use crate::cmd::{Opt, Spec};
+use crate::err;
+use crate::input::Input;
+use crate::status;
+use crate::warn;
+
+/// The largest array index, scale and output base allowed.
+const DIM_MAX: i64 = 65535;
+const SCALE_MAX: i64 = 99999;
+const BASE_MAX: i64 = 999;
+
+/// How deeply functions may call each other before it is taken to be
+/// runaway recursion.
+const MAX_DEPTH: usize = 5000;
+
+/// The size of the stack the program runs with.
+const STACK_SIZE: usize = 256 << 20;
+
+/// The math library loaded by -l, written in bc: the sine, cosine,
+/// arctangent, natural logarithm and exponential functions, and the Bessel
+/// function of integer order. Each works with extra digits and truncates
+/// its result to the scale it was called with.
+const LIBRARY: &str = r#"
+scale = 20
+
+define e(x) {
+	auto a, f, i, k, n, s, y
+	s = scale
+	n = 0
+	if (x < 0) {
+		n = 1
+		x = -x
+	}
+	/* Halve x until the series converges quickly, and square the sum as
+	   many times. */
+	scale = 0
+	y = x / 1
+	k = 1
+	while (y > 0) {
+		y = y / 2
+		k = k + 1
+	}
+	scale = s + 10 + k + x * 44 / 100
+	x = x / 2 ^ k
+	a = 1
+	f = 1
+	for (i = 1; 1; i++) {
+		f = f * x / i
+		if (f == 0) break
+		a = a + f
+	}
+	while (k--) a = a * a
+	scale = s
+	if (n) return (1 / a)
+	return (a / 1)
+}
+
+define l(x) {
+	auto a, f, i, k, s, t, u, v
+	if (x <= 0) return (1 - 10 ^ scale)
+	s = scale
+	scale = s + 20
+	/* Take square roots until x is near 1, where the series for
+	   2 atanh((x - 1) / (x + 1)) converges quickly. */
+	k = 0
+	while (x >= 1.5 || x <= 0.5) {
+		x = sqrt(x)
+		k = k + 1
+	}
+	u = (x - 1) / (x + 1)
+	v = u * u
+	a = u
+	f = u
+	for (i = 3; 1; i = i + 2) {
+		f = f * v
+		t = f / i
+		if (t == 0) break
+		a = a + t
+	}
+	a = a * 2 ^ (k + 1)
+	scale = s
+	return (a / 1)
+}
+
+define a(x) {
+	auto a, f, i, k, n, s, t, v
+	s = scale
+	n = 0
+	if (x < 0) {
+		n = 1
+		x = -x
+	}
+	scale = s + 10
+	/* Halve the angle until the series converges quickly. */
+	k = 0
+	while (x > 0.2) {
+		x = x / (1 + sqrt(1 + x * x))
+		k = k + 1
+	}
+	v = -x * x
+	a = x
+	f = x
+	for (i = 3; 1; i = i + 2) {
+		f = f * v
+		t = f / i
+		if (t == 0) break
+		a = a + t
+	}
+	a = a * 2 ^ k
+	scale = s
+	if (n) a = -a
+	return (a / 1)
+}
+
+define s(x) {
+	auto a, f, i, n, p, s, v
+	s = scale
+	/* Reduce x to between -pi and pi. */
+	scale = s + 10 + length(x)
+	p = 4 * a(1)
+	scale = 0
+	n = x / (2 * p)
+	scale = s + 10 + length(x)
+	x = x - n * 2 * p
+	if (x > p) x = x - 2 * p
+	if (x < -p) x = x + 2 * p
+	v = -x * x
+	a = x
+	f = x
+	for (i = 3; 1; i = i + 2) {
+		f = f * v / (i * (i - 1))
+		if (f == 0) break
+		a = a + f
+	}
+	scale = s
+	return (a / 1)
+}
+
+define c(x) {
+	auto a, f, i, n, p, s, v
+	s = scale
+	scale = s + 10 + length(x)
+	p = 4 * a(1)
+	scale = 0
+	n = x / (2 * p)
+	scale = s + 10 + length(x)
+	x = x - n * 2 * p
+	v = -x * x
+	a = 1
+	f = 1
+	for (i = 2; 1; i = i + 2) {
+		f = f * v / (i * (i - 1))
+		if (f == 0) break
+		a = a + f
+	}
+	scale = s
+	return (a / 1)
+}
+
+define j(n, x) {
+	auto a, f, i, m, s, v, y
+	s = scale
+	scale = 0
+	n = n / 1
+	m = 1
+	if (n < 0) {
+		n = -n
+		if (n % 2 == 1) m = -1
+	}
+	/* The terms grow before they shrink, so extra digits make up for
+	   their cancelling out. */
+	y = x
+	if (y < 0) y = -y
+	y = y * 44 / 100
+	scale = s + 10 + y
+	f = (x / 2) ^ n
+	for (i = 1; i <= n; i++) f = f / i
+	a = f
+	v = -x * x / 4
+	for (i = 1; 1; i++) {
+		f = f * v / (i * (i + n))
+		if (f == 0) break
+		a = a + f
+	}
+	scale = s
+	return (m * a / 1)
+}
+"#;
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    /// The digits of a number, converted when it is used, in the input
+    /// base of the time.
+    Number(String),
+    Str(String),
+    Name(String),
+    Keyword(&'static str),
+    Op(&'static str),
+    Newline,
+    Eof,
+}
+
+const KEYWORDS: &[&str] = &[
+    "auto", "break", "continue", "define", "else", "for", "halt", "ibase", "if", "last", "length", "obase",
+    "print", "quit", "return", "scale", "sqrt", "while",
+];
+
+/// The operators, longest first so that, say, += isn't taken for +.
+const OPERATORS: &[&str] = &[
+    "==", "<=", ">=", "!=", "&&", "||", "++", "--", "+=", "-=", "*=", "/=", "%=", "^=", "+", "-", "*", "/",
+    "%", "^", "=", "<", ">", "!", "(", ")", "[", "]", "{", "}", ",", ";",
+];
+
+/// Splits the input into tokens, reading lines only as they are needed so
+/// that each statement typed can be run before the next is read.
+struct Lexer {
+    inputs: Vec<Box<dyn BufRead>>,
+    buf: Vec<u8>,
+    pos: usize,
+    line: usize,
+}
+
+impl Lexer {
+    fn peek(&mut self) -> Option<u8> {
+        while self.pos >= self.buf.len() {
+            self.buf.clear();
+            self.pos = 0;
+            let input = self.inputs.first_mut()?;
+            match input.read_until(b'\n', &mut self.buf) {
+                Ok(0) | Err(_) => {
+                    self.inputs.remove(0);
+                },
+                Ok(_) => (),
+            }
+        }
+        Some(self.buf[self.pos])
+    }
+
+    fn bump(&mut self) -> Option<u8> {
+        let c = self.peek()?;
+        self.pos += 1;
+        if c == b'\n' {
+            self.line += 1;
+        }
+        Some(c)
+    }
+
+    /// Discards the rest of the line, after an error.
+    fn skip_line(&mut self) {
+        if self.pos > 0 && self.buf.get(self.pos - 1) == Some(&b'\n') {
+            return;
+        }
+        while self.bump().is_some_and(|c| c != b'\n') {}
+    }
+
+    /// Reads an empty pair of brackets, if they come next.
+    fn brackets(&mut self) -> bool {
+        let rest = &self.buf[self.pos..];
+        let mut chars = rest.iter().enumerate().filter(|(_, c)| !matches!(c, b' ' | b'\t'));
+        match (chars.next(), chars.next()) {
+            (Some((_, b'[')), Some((end, b']'))) => {
+                self.pos += end + 1;
+                true
+            },
+            _ => false,
+        }
+    }
+
+    fn error<T>(&self, msg: &str) -> Result<T, String> {
+        Err(format!("{} on line {}", msg, self.line))
+    }
+
+    fn next(&mut self) -> Result<Token, String> {
+        loop {
+            match self.peek() {
+                Some(b' ' | b'\t' | b'\r') => {
+                    self.bump();
+                },
+                Some(b'\\') => {
+                    self.bump();
+                    if self.bump() != Some(b'\n') {
+                        return self.error("illegal character: \\");
+                    }
+                },
+                Some(b'#') => while self.peek().is_some_and(|c| c != b'\n') {
+                    self.bump();
+                },
+                Some(b'/') => {
+                    self.bump();
+                    if self.peek() != Some(b'*') {
+                        return Ok(Token::Op(if self.peek() == Some(b'=') {
+                            self.bump();
+                            "/="
+                        } else {
+                            "/"
+                        }));
+                    }
+                    self.bump();
+                    let mut star = false;
+                    loop {
+                        match self.bump() {
+                            Some(b'/') if star => break,
+                            Some(c) => star = c == b'*',
+                            None => return self.error("end of file in comment"),
+                        }
+                    }
+                },
+                _ => break,
+            }
+        }
+        let c = match self.peek() {
+            Some(c) => c,
+            None => return Ok(Token::Eof),
+        };
+        if c == b'\n' {
+            self.bump();
+            Ok(Token::Newline)
+        } else if c.is_ascii_digit() || (b'A'..=b'F').contains(&c) || c == b'.' {
+            let mut digits = String::new();
+            loop {
+                match self.peek() {
+                    Some(c) if c.is_ascii_digit() || (b'A'..=b'F').contains(&c) => digits.push(c as char),
+                    Some(b'.') if !digits.contains('.') => digits.push('.'),
+                    // A number may be continued on the next line.
+                    Some(b'\\') if self.buf.get(self.pos + 1) == Some(&b'\n') => {
+                        self.bump();
+                    },
+                    _ => break,
+                }
+                self.bump();
+            }
+            if digits == "." {
+                return Ok(Token::Keyword("last"));
+            }
+            Ok(Token::Number(digits))
+        } else if c == b'"' {
+            self.bump();
+            let mut s = Vec::new();
+            loop {
+                match self.bump() {
+                    Some(b'"') => break,
+                    Some(c) => s.push(c),
+                    None => return self.error("end of file in string"),
+                }
+            }
+            Ok(Token::Str(String::from_utf8_lossy(&s).into_owned()))
+        } else if c.is_ascii_lowercase() {
+            let mut name = String::new();
+            while let Some(c) = self.peek().filter(|&c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == b'_') {
+                name.push(c as char);
+                self.bump();
+            }
+            match KEYWORDS.iter().find(|&&kw| kw == name) {
+                Some(kw) => Ok(Token::Keyword(kw)),
+                None => Ok(Token::Name(name)),
+            }
+        } else {
+            let rest = &self.buf[self.pos..];
+            match OPERATORS.iter().find(|op| rest.starts_with(op.as_bytes())) {
+                Some(op) => {
+                    self.pos += op.len();
+                    Ok(Token::Op(op))
+                },
+                None => {
+                    self.bump();
+                    self.error(&format!("illegal character: {}", c as char))
+                },
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Special {
+    Scale,
+    Ibase,
+    Obase,
+    Last,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Pow,
+}
+
+impl BinOp {
+    fn from_assignment(op: &str) -> Option<Option<BinOp>> {
+        Some(match op {
+            "=" => None,
+            "+=" => Some(BinOp::Add),
+            "-=" => Some(BinOp::Sub),
+            "*=" => Some(BinOp::Mul),
+            "/=" => Some(BinOp::Div),
+            "%=" => Some(BinOp::Mod),
+            "^=" => Some(BinOp::Pow),
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum RelOp {
+    Lt,
+    Le,
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+}
+
+/// An argument of a function: a value, or an array passed by name.
+#[derive(Debug)]
+enum Arg {
+    Value(Expr),
+    Array(String),
+}
+
+#[derive(Debug)]
+enum Expr {
+    Num(String),
+    Var(String),
+    Elem(String, Box<Expr>),
+    Special(Special),
+    /// An assignment, with the operator of, say, +=.
+    Assign(Option<BinOp>, Box<Expr>, Box<Expr>),
+    /// An increment or decrement, and whether it comes before the value.
+    Incr(Box<Expr>, bool, bool),
+    Neg(Box<Expr>),
+    Not(Box<Expr>),
+    Binary(BinOp, Box<Expr>, Box<Expr>),
+    Rel(RelOp, Box<Expr>, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Call(String, Vec<Arg>),
+    Length(Box<Expr>),
+    Scale(Box<Expr>),
+    Sqrt(Box<Expr>),
+}
+
+impl Expr {
+    fn is_lvalue(&self) -> bool {
+        matches!(self, Expr::Var(_) | Expr::Elem(..) | Expr::Special(_))
+    }
+}
+
+#[derive(Debug)]
+enum PrintItem {
+    Expr(Expr),
+    Str(String),
+}
+
+#[derive(Debug)]
+enum Stmt {
+    /// An expression, whose value is printed unless it is an assignment.
+    Expr(Expr),
+    /// A string, printed as it is.
+    Str(String),
+    Print(Vec<PrintItem>),
+    Block(Vec<Stmt>),
+    If(Expr, Box<Stmt>, Option<Box<Stmt>>),
+    While(Expr, Box<Stmt>),
+    For(Option<Expr>, Option<Expr>, Option<Expr>, Box<Stmt>),
+    Break,
+    Continue,
+    Return(Option<Expr>),
+    Halt,
+}
+
+/// A parameter or automatic variable of a function.
+#[derive(Debug)]
+struct Local {
+    name: String,
+    array: bool,
+}
+
+#[derive(Debug)]
+struct Function {
+    params: Vec<Local>,
+    autos: Vec<Local>,
+    body: Vec<Stmt>,
+}
+
+/// What the parser reads at the top level.
+enum Item {
+    Stmt(Stmt),
+    Define(String, Function),
+}
+
+struct Parser {
+    lexer: Lexer,
+    /// The next token, once it has been read.
+    tok: Option<Token>,
+    /// Whether quit has been read, which ends bc as soon as it is.
+    quit: bool,
+    /// How deeply the statement being parsed is nested, in braces and
+    /// loops, and whether it is in a function.
+    depth: usize,
+    loops: usize,
+    function: bool,
+    /// Whether the newlines ending the last statement have been read, in
+    /// looking for an else.
+    ended: bool,
+}
+
+impl Parser {
+    fn new(inputs: Vec<Box<dyn BufRead>>) -> Parser {
+        Parser {
+            lexer: Lexer { inputs, buf: Vec::new(), pos: 0, line: 1 },
+            tok: None,
+            quit: false,
+            depth: 0,
+            loops: 0,
+            function: false,
+            ended: false,
+        }
+    }
+
+    fn peek(&mut self) -> Result<&Token, String> {
+        if self.tok.is_none() {
+            self.tok = Some(self.lexer.next()?);
+        }
+        Ok(self.tok.as_ref().unwrap())
+    }
+
+    fn advance(&mut self) -> Result<Token, String> {
+        self.peek()?;
+        Ok(self.tok.take().unwrap())
+    }
+
+    fn error<T>(&mut self) -> Result<T, String> {
+        self.lexer.error("syntax error")
+    }
+
+    fn is_op(&mut self, op: &str) -> Result<bool, String> {
+        Ok(matches!(self.peek()?, Token::Op(o) if *o == op))
+    }
+
+    fn is_keyword(&mut self, kw: &str) -> Result<bool, String> {
+        Ok(matches!(self.peek()?, Token::Keyword(k) if *k == kw))
+    }
+
+    fn eat_op(&mut self, op: &str) -> Result<bool, String> {
+        let found = self.is_op(op)?;
+        if found {
+            self.advance()?;
+        }
+        Ok(found)
+    }
+
+    fn expect_op(&mut self, op: &str) -> Result<(), String> {
+        if !self.eat_op(op)? {
+            return self.error();
+        }
+        Ok(())
+    }
+
+    fn newlines(&mut self) -> Result<(), String> {
+        while *self.peek()? == Token::Newline {
+            self.advance()?;
+        }
+        Ok(())
+    }
+
+    /// Recovers from an error by discarding the rest of the line.
+    fn recover(&mut self) {
+        if self.tok.take() != Some(Token::Newline) {
+            self.lexer.skip_line();
+        }
+        self.depth = 0;
+        self.loops = 0;
+        self.function = false;
+        self.ended = false;
+    }
+
+    /// Reads the next statement or function definition at the top level,
+    /// or None at the end of the input.
+    fn item(&mut self) -> Result<Option<Item>, String> {
+        loop {
+            match self.peek()? {
+                Token::Eof => return Ok(None),
+                Token::Newline | Token::Op(";") => {
+                    self.advance()?;
+                },
+                Token::Keyword("define") => return Ok(Some(self.define()?)),
+                _ => {
+                    let stmt = self.stmt()?;
+                    self.end_stmt()?;
+                    return Ok(Some(Item::Stmt(stmt)));
+                },
+            }
+        }
+    }
+
+    /// Checks that a statement is followed by something that can end it.
+    fn end_stmt(&mut self) -> Result<(), String> {
+        if std::mem::take(&mut self.ended) {
+            return Ok(());
+        }
+        match self.peek()? {
+            Token::Newline | Token::Eof | Token::Op(";" | "}") => Ok(()),
+            _ => self.error(),
+        }
+    }
+
+    fn name(&mut self) -> Result<String, String> {
+        match self.advance()? {
+            Token::Name(name) => Ok(name),
+            _ => self.error(),
+        }
+    }
+
+    /// Parses a list of parameters or automatic variables, each a name
+    /// that may be followed by [] for an array.
+    fn locals(&mut self, end: &str) -> Result<Vec<Local>, String> {
+        let mut locals = Vec::new();
+        loop {
+            let name = self.name()?;
+            let array = self.eat_op("[")?;
+            if array {
+                self.expect_op("]")?;
+            }
+            locals.push(Local { name, array });
+            if !self.eat_op(",")? {
+                break;
+            }
+        }
+        if end == ")" {
+            self.expect_op(")")?;
+        } else if !matches!(self.peek()?, Token::Newline | Token::Op(";")) {
+            return self.error();
+        }
+        Ok(locals)
+    }
+
+    fn define(&mut self) -> Result<Item, String> {
+        self.advance()?;
+        let name = self.name()?;
+        self.expect_op("(")?;
+        let params = if self.eat_op(")")? { Vec::new() } else { self.locals(")")? };
+        self.expect_op("{")?;
+        self.function = true;
+        self.depth += 1;
+        self.newlines()?;
+        let mut autos = Vec::new();
+        if self.is_keyword("auto")? {
+            self.advance()?;
+            autos = self.locals("\n")?;
+        }
+        let body = self.stmts()?;
+        self.depth -= 1;
+        self.function = false;
+        Ok(Item::Define(name, Function { params, autos, body }))
+    }
+
+    /// Parses statements up to a closing brace.
+    fn stmts(&mut self) -> Result<Vec<Stmt>, String> {
+        let mut stmts = Vec::new();
+        loop {
+            match self.peek()? {
+                Token::Newline | Token::Op(";") => {
+                    self.advance()?;
+                },
+                Token::Op("}") => {
+                    self.advance()?;
+                    return Ok(stmts);
+                },
+                Token::Eof => return self.error(),
+                _ => {
+                    stmts.push(self.stmt()?);
+                    self.end_stmt()?;
+                },
+            }
+        }
+    }
+
+    /// Parses the body of a loop.
+    fn body(&mut self) -> Result<Box<Stmt>, String> {
+        self.newlines()?;
+        self.loops += 1;
+        let body = self.stmt();
+        self.loops -= 1;
+        Ok(Box::new(body?))
+    }
+
+    fn condition(&mut self) -> Result<Expr, String> {
+        self.expect_op("(")?;
+        let cond = self.expr()?;
+        self.expect_op(")")?;
+        Ok(cond)
+    }
+
+    fn stmt(&mut self) -> Result<Stmt, String> {
+        let kw = match self.advance()? {
+            Token::Op("{") => {
+                self.depth += 1;
+                let stmts = self.stmts();
+                self.depth -= 1;
+                return Ok(Stmt::Block(stmts?));
+            },
+            Token::Str(s) => return Ok(Stmt::Str(s)),
+            Token::Keyword(kw @ ("if" | "while" | "for" | "break" | "continue" | "return" | "quit" | "halt"
+                                | "print")) => kw,
+            tok => {
+                self.tok = Some(tok);
+                return Ok(Stmt::Expr(self.expr()?));
+            },
+        };
+        Ok(match kw {
+            "if" => {
+                let cond = self.condition()?;
+                self.newlines()?;
+                let then = Box::new(self.stmt()?);
+                // Inside braces, else may be on a later line; at the top
+                // level, looking for it there would wait for more input.
+                if self.depth > 0 && *self.peek()? == Token::Newline {
+                    self.newlines()?;
+                    self.ended = true;
+                }
+                let otherwise = if self.is_keyword("else")? {
+                    self.advance()?;
+                    self.newlines()?;
+                    Some(Box::new(self.stmt()?))
+                } else {
+                    None
+                };
+                Stmt::If(cond, then, otherwise)
+            },
+            "while" => {
+                let cond = self.condition()?;
+                Stmt::While(cond, self.body()?)
+            },
+            "for" => {
+                self.expect_op("(")?;
+                let init = if self.is_op(";")? { None } else { Some(self.expr()?) };
+                self.expect_op(";")?;
+                let cond = if self.is_op(";")? { None } else { Some(self.expr()?) };
+                self.expect_op(";")?;
+                let step = if self.is_op(")")? { None } else { Some(self.expr()?) };
+                self.expect_op(")")?;
+                Stmt::For(init, cond, step, self.body()?)
+            },
+            "break" if self.loops > 0 => Stmt::Break,
+            "continue" if self.loops > 0 => Stmt::Continue,
+            "return" if self.function => {
+                if matches!(self.peek()?, Token::Newline | Token::Eof | Token::Op(";" | "}")) {
+                    Stmt::Return(None)
+                } else {
+                    Stmt::Return(Some(self.expr()?))
+                }
+            },
+            "quit" => {
+                // quit takes effect when it is read, wherever it is.
+                self.quit = true;
+                Stmt::Block(Vec::new())
+            },
+            "halt" => Stmt::Halt,
+            "print" => {
+                let mut items = Vec::new();
+                loop {
+                    if let Token::Str(_) = self.peek()? {
+                        if let Token::Str(s) = self.advance()? {
+                            items.push(PrintItem::Str(unescape(&s)));
+                        }
+                    } else {
+                        items.push(PrintItem::Expr(self.expr()?));
+                    }
+                    if !self.eat_op(",")? {
+                        break;
+                    }
+                }
+                Stmt::Print(items)
+            },
+            _ => return self.error(),
+        })
+    }
+
+    fn expr(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.and()?;
+        while self.eat_op("||")? {
+            lhs = Expr::Or(Box::new(lhs), Box::new(self.and()?));
+        }
+        Ok(lhs)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.not()?;
+        while self.eat_op("&&")? {
+            lhs = Expr::And(Box::new(lhs), Box::new(self.not()?));
+        }
+        Ok(lhs)
+    }
+
+    fn not(&mut self) -> Result<Expr, String> {
+        if self.eat_op("!")? {
+            return Ok(Expr::Not(Box::new(self.not()?)));
+        }
+        self.relational()
+    }
+
+    fn relational(&mut self) -> Result<Expr, String> {
+        let lhs = self.assignment()?;
+        let op = match self.peek()? {
+            Token::Op("<") => RelOp::Lt,
+            Token::Op("<=") => RelOp::Le,
+            Token::Op("==") => RelOp::Eq,
+            Token::Op("!=") => RelOp::Ne,
+            Token::Op(">") => RelOp::Gt,
+            Token::Op(">=") => RelOp::Ge,
+            _ => return Ok(lhs),
+        };
+        self.advance()?;
+        Ok(Expr::Rel(op, Box::new(lhs), Box::new(self.assignment()?)))
+    }
+
+    fn assignment(&mut self) -> Result<Expr, String> {
+        let lhs = self.additive()?;
+        let op = match self.peek()? {
+            Token::Op(op) => BinOp::from_assignment(op),
+            _ => None,
+        };
+        match op {
+            Some(op) if lhs.is_lvalue() => {
+                self.advance()?;
+                Ok(Expr::Assign(op, Box::new(lhs), Box::new(self.assignment()?)))
+            },
+            _ => Ok(lhs),
+        }
+    }
+
+    fn additive(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.multiplicative()?;
+        loop {
+            let op = match self.peek()? {
+                Token::Op("+") => BinOp::Add,
+                Token::Op("-") => BinOp::Sub,
+                _ => return Ok(lhs),
+            };
+            self.advance()?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(self.multiplicative()?));
+        }
+    }
+
+    fn multiplicative(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.power()?;
+        loop {
+            let op = match self.peek()? {
+                Token::Op("*") => BinOp::Mul,
+                Token::Op("/") => BinOp::Div,
+                Token::Op("%") => BinOp::Mod,
+                _ => return Ok(lhs),
+            };
+            self.advance()?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(self.power()?));
+        }
+    }
+
+    fn power(&mut self) -> Result<Expr, String> {
+        let base = self.unary()?;
+        if !self.eat_op("^")? {
+            return Ok(base);
+        }
+        Ok(Expr::Binary(BinOp::Pow, Box::new(base), Box::new(self.power()?)))
+    }
+
+    /// Parses a unary minus, which binds more tightly than ^.
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.eat_op("-")? {
+            return Ok(Expr::Neg(Box::new(self.unary()?)));
+        }
+        self.increment()
+    }
+
+    fn increment(&mut self) -> Result<Expr, String> {
+        for (op, up) in [("++", true), ("--", false)] {
+            if self.eat_op(op)? {
+                let lvalue = self.primary()?;
+                if !lvalue.is_lvalue() {
+                    return self.error();
+                }
+                return Ok(Expr::Incr(Box::new(lvalue), up, true));
+            }
+        }
+        let expr = self.primary()?;
+        if expr.is_lvalue() {
+            for (op, up) in [("++", true), ("--", false)] {
+                if self.eat_op(op)? {
+                    return Ok(Expr::Incr(Box::new(expr), up, false));
+                }
+            }
+        }
+        Ok(expr)
+    }
+
+    /// Parses the parenthesised argument of length, scale or sqrt.
+    fn builtin(&mut self) -> Result<Box<Expr>, String> {
+        self.expect_op("(")?;
+        let expr = self.expr()?;
+        self.expect_op(")")?;
+        Ok(Box::new(expr))
+    }
+
+    fn primary(&mut self) -> Result<Expr, String> {
+        match self.advance()? {
+            Token::Number(digits) => Ok(Expr::Num(digits)),
+            Token::Op("(") => {
+                let expr = self.expr()?;
+                self.expect_op(")")?;
+                Ok(expr)
+            },
+            Token::Name(name) => {
+                if self.eat_op("[")? {
+                    let index = self.expr()?;
+                    self.expect_op("]")?;
+                    return Ok(Expr::Elem(name, Box::new(index)));
+                }
+                if !self.eat_op("(")? {
+                    return Ok(Expr::Var(name));
+                }
+                let mut args = Vec::new();
+                while !self.eat_op(")")? {
+                    if !args.is_empty() {
+                        self.expect_op(",")?;
+                    }
+                    // An array is passed as its name followed by [].
+                    if let Token::Name(array) = self.peek()? {
+                        let array = array.clone();
+                        if self.lexer.brackets() {
+                            self.advance()?;
+                            args.push(Arg::Array(array));
+                            continue;
+                        }
+                    }
+                    args.push(Arg::Value(self.expr()?));
+                }
+                Ok(Expr::Call(name, args))
+            },
+            Token::Keyword("scale") if self.is_op("(")? => Ok(Expr::Scale(self.builtin()?)),
+            Token::Keyword("scale") => Ok(Expr::Special(Special::Scale)),
+            Token::Keyword("ibase") => Ok(Expr::Special(Special::Ibase)),
+            Token::Keyword("obase") => Ok(Expr::Special(Special::Obase)),
+            Token::Keyword("last") => Ok(Expr::Special(Special::Last)),
+            Token::Keyword("length") => Ok(Expr::Length(self.builtin()?)),
+            Token::Keyword("sqrt") => Ok(Expr::Sqrt(self.builtin()?)),
+            _ => self.error(),
+        }
+    }
+}
+
+/// Replaces the escapes of a string in a print statement.
+fn unescape(s: &str) -> String {
+    let mut out = String::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('a') => out.push('\x07'),
+            Some('b') => out.push('\x08'),
+            Some('f') => out.push('\x0c'),
+            Some('n') => out.push('\n'),
+            Some('q') => out.push('"'),
+            Some('r') => out.push('\r'),
+            Some('t') => out.push('\t'),
+            Some(c) => out.push(c),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Error {
+    DivideByZero,
+    NegativeRoot,
+    Exponent,
+    Scale,
+    InputBase,
+    OutputBase,
+    Index,
+    Undefined(String),
+    Arguments(String),
+    Depth(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::DivideByZero => write!(f, "divide by zero"),
+            Error::NegativeRoot => write!(f, "square root of negative number"),
+            Error::Exponent => write!(f, "exponent too large"),
+            Error::Scale => write!(f, "scale must be between 0 and {}", SCALE_MAX),
+            Error::InputBase => write!(f, "input base must be between 2 and 16"),
+            Error::OutputBase => write!(f, "output base must be between 2 and {}", BASE_MAX),
+            Error::Index => write!(f, "array index must be between 0 and {}", DIM_MAX),
+            Error::Undefined(name) => write!(f, "function {} not defined", name),
+            Error::Arguments(name) => write!(f, "function {}: arguments do not match parameters", name),
+            Error::Depth(name) => write!(f, "function {} nested too deeply", name),
+        }
+    }
+}
+
+/// How a statement ends, other than by running on to the next.
+enum Flow {
+    Break,
+    Continue,
+    Return(Number),
+    Halt,
+    Error(Error),
+}
+
+impl From<Error> for Flow {
+    fn from(e: Error) -> Flow {
+        Flow::Error(e)
+    }
+}
+
+/// Something that can be assigned to, with any subscript evaluated.
+enum Place {
+    Var(String),
+    Elem(String, usize),
+    Special(Special),
+}
+
+/// What a parameter is bound to in a call.
+enum Binding {
+    Value(Number),
+    Array(HashMap<usize, Number>),
+}
+
+fn from_bool(b: bool) -> Number {
+    Number::from_i64(b as i64)
+}
+
+struct Bc<W: Write> {
+    out: W,
+    scale: usize,
+    ibase: u32,
+    obase: u32,
+    last: Number,
+    /// The variables and arrays, of which each name has a stack: calling
+    /// a function pushes its parameters and automatic variables, which
+    /// hide those of the same names until it returns.
+    vars: HashMap<String, Vec<Number>>,
+    arrays: HashMap<String, Vec<HashMap<usize, Number>>>,
+    funcs: HashMap<String, Rc<Function>>,
+    depth: usize,
+}
+
+impl<W: Write> Bc<W> {
+    fn new(out: W) -> Bc<W> {
+        Bc {
+            out,
+            scale: 0,
+            ibase: 10,
+            obase: 10,
+            last: Number::zero(),
+            vars: HashMap::new(),
+            arrays: HashMap::new(),
+            funcs: HashMap::new(),
+            depth: 0,
+        }
+    }
+
+    fn array(&mut self, name: &str) -> &mut HashMap<usize, Number> {
+        let stack = self.arrays.entry(name.to_string()).or_default();
+        if stack.is_empty() {
+            stack.push(HashMap::new());
+        }
+        stack.last_mut().unwrap()
+    }
+
+    fn index(&mut self, index: &Expr) -> Result<usize, Flow> {
+        match self.eval(index)?.to_i64() {
+            Some(i) if (0..=DIM_MAX).contains(&i) => Ok(i as usize),
+            _ => Err(Error::Index.into()),
+        }
+    }
+
+    fn place(&mut self, expr: &Expr) -> Result<Place, Flow> {
+        Ok(match expr {
+            Expr::Var(name) => Place::Var(name.clone()),
+            Expr::Elem(name, index) => Place::Elem(name.clone(), self.index(index)?),
+            Expr::Special(special) => Place::Special(*special),
+            _ => unreachable!("not an lvalue"),
+        })
+    }
+
+    fn get(&mut self, place: &Place) -> Number {
+        match place {
+            Place::Var(name) => self.vars.get(name).and_then(|stack| stack.last()).cloned().unwrap_or_default(),
+            Place::Elem(name, i) => self.array(name).get(i).cloned().unwrap_or_default(),
+            Place::Special(Special::Scale) => Number::from_i64(self.scale as i64),
+            Place::Special(Special::Ibase) => Number::from_i64(self.ibase as i64),
+            Place::Special(Special::Obase) => Number::from_i64(self.obase as i64),
+            Place::Special(Special::Last) => self.last.clone(),
+        }
+    }
+
+    fn put(&mut self, place: &Place, value: Number) -> Result<(), Error> {
+        let int = value.to_i64().unwrap_or(-1);
+        match place {
+            Place::Var(name) => {
+                let stack = self.vars.entry(name.clone()).or_default();
+                match stack.last_mut() {
+                    Some(var) => *var = value,
+                    None => stack.push(value),
+                }
+            },
+            Place::Elem(name, i) => {
+                self.array(name).insert(*i, value);
+            },
+            Place::Special(Special::Scale) if (0..=SCALE_MAX).contains(&int) => self.scale = int as usize,
+            Place::Special(Special::Scale) => return Err(Error::Scale),
+            Place::Special(Special::Ibase) if (2..=16).contains(&int) => self.ibase = int as u32,
+            Place::Special(Special::Ibase) => return Err(Error::InputBase),
+            Place::Special(Special::Obase) if (2..=BASE_MAX).contains(&int) => self.obase = int as u32,
+            Place::Special(Special::Obase) => return Err(Error::OutputBase),
+            Place::Special(Special::Last) => self.last = value,
+        }
+        Ok(())
+    }
+
+    fn binary(&mut self, op: BinOp, a: &Number, b: &Number) -> Result<Number, Error> {
+        match op {
+            BinOp::Add => Ok(a.add(b)),
+            BinOp::Sub => Ok(a.sub(b)),
+            BinOp::Mul => Ok(a.mul(b, self.scale)),
+            BinOp::Div => a.div(b, self.scale).ok_or(Error::DivideByZero),
+            BinOp::Mod => a.rem(b, self.scale).ok_or(Error::DivideByZero),
+            BinOp::Pow => {
+                if b.scale() > 0 && b.rescale(0) != *b {
+                    let _ = self.out.flush();
+                    warn!("warning: non-zero scale in exponent");
+                }
+                let exp = b.to_i64().filter(|exp| exp.unsigned_abs() <= u32::MAX as u64).ok_or(Error::Exponent)?;
+                a.pow(exp, self.scale).ok_or(Error::DivideByZero)
+            },
+        }
+    }
+
+    fn eval(&mut self, expr: &Expr) -> Result<Number, Flow> {
+        Ok(match expr {
+            Expr::Num(digits) => Number::parse(digits, self.ibase).unwrap_or_default(),
+            Expr::Var(_) | Expr::Elem(..) | Expr::Special(_) => {
+                let place = self.place(expr)?;
+                self.get(&place)
+            },
+            Expr::Assign(op, lvalue, rhs) => {
+                let place = self.place(lvalue)?;
+                let mut value = self.eval(rhs)?;
+                if let Some(op) = op {
+                    let old = self.get(&place);
+                    value = self.binary(*op, &old, &value)?;
+                }
+                self.put(&place, value.clone())?;
+                value
+            },
+            Expr::Incr(lvalue, up, prefix) => {
+                let place = self.place(lvalue)?;
+                let old = self.get(&place);
+                let one = Number::from_i64(1);
+                let new = if *up { old.add(&one) } else { old.sub(&one) };
+                self.put(&place, new.clone())?;
+                if *prefix { new } else { old }
+            },
+            Expr::Neg(expr) => self.eval(expr)?.neg(),
+            Expr::Not(expr) => from_bool(self.eval(expr)?.is_zero()),
+            Expr::Binary(op, a, b) => {
+                let a = self.eval(a)?;
+                let b = self.eval(b)?;
+                self.binary(*op, &a, &b)?
+            },
+            Expr::Rel(op, a, b) => {
+                let a = self.eval(a)?;
+                let b = self.eval(b)?;
+                from_bool(match op {
+                    RelOp::Lt => a < b,
+                    RelOp::Le => a <= b,
+                    RelOp::Eq => a == b,
+                    RelOp::Ne => a != b,
+                    RelOp::Gt => a > b,
+                    RelOp::Ge => a >= b,
+                })
+            },
+            Expr::And(a, b) => from_bool(!self.eval(a)?.is_zero() && !self.eval(b)?.is_zero()),
+            Expr::Or(a, b) => from_bool(!self.eval(a)?.is_zero() || !self.eval(b)?.is_zero()),
+            Expr::Call(name, args) => self.call(name, args)?,
+            Expr::Length(expr) => Number::from_i64(self.eval(expr)?.length() as i64),
+            Expr::Scale(expr) => Number::from_i64(self.eval(expr)?.scale() as i64),
+            Expr::Sqrt(expr) => self.eval(expr)?.sqrt(self.scale).ok_or(Error::NegativeRoot)?,
+        })
+    }
+
+    /// Calls a function. Its parameters and automatic variables hide any
+    /// of the same names, in the functions it calls as well as in itself.
+    fn call(&mut self, name: &str, args: &[Arg]) -> Result<Number, Flow> {
+        let func = match self.funcs.get(name) {
+            Some(func) => Rc::clone(func),
+            None => return Err(Error::Undefined(name.to_string()).into()),
+        };
+        if args.len() != func.params.len() {
+            return Err(Error::Arguments(name.to_string()).into());
+        }
+        if self.depth >= MAX_DEPTH {
+            return Err(Error::Depth(name.to_string()).into());
+        }
+        // The arguments are evaluated before any parameter hides a
+        // variable they use.
+        let mut bindings = Vec::with_capacity(args.len());
+        for (param, arg) in func.params.iter().zip(args) {
+            bindings.push(match (param.array, arg) {
+                (false, Arg::Value(expr)) => Binding::Value(self.eval(expr)?),
+                (true, Arg::Array(array)) => Binding::Array(self.array(array).clone()),
+                _ => return Err(Error::Arguments(name.to_string()).into()),
+            });
+        }
+        let autos = func.autos.iter().map(|auto| match auto.array {
+            false => Binding::Value(Number::zero()),
+            true => Binding::Array(HashMap::new()),
+        });
+        let locals: Vec<(&Local, Binding)> = func.params.iter().zip(bindings).chain(func.autos.iter().zip(autos)).collect();
+        for (local, binding) in locals.iter().map(|(local, binding)| (*local, binding)) {
+            match binding {
+                Binding::Value(value) => self.vars.entry(local.name.clone()).or_default().push(value.clone()),
+                Binding::Array(array) => self.arrays.entry(local.name.clone()).or_default().push(array.clone()),
+            }
+        }
+        self.depth += 1;
+        let result = self.exec_all(&func.body);
+        self.depth -= 1;
+        for (local, _) in &locals {
+            if local.array {
+                self.arrays.get_mut(&local.name).map(Vec::pop);
+            } else {
+                self.vars.get_mut(&local.name).map(Vec::pop);
+            }
+        }
+        match result {
+            Ok(()) => Ok(Number::zero()),
+            Err(Flow::Return(value)) => Ok(value),
+            Err(flow) => Err(flow),
+        }
+    }
+
+    fn print(&mut self, n: &Number) {
+        let _ = self.out.write_all(bignum::wrap(&n.to_string_radix(self.obase)).as_bytes());
+    }
+
+    fn exec_all(&mut self, stmts: &[Stmt]) -> Result<(), Flow> {
+        for stmt in stmts {
+            self.exec(stmt)?;
+        }
+        Ok(())
+    }
+
+    /// Runs a loop body, returning whether the loop should go on.
+    fn iterate(&mut self, body: &Stmt) -> Result<bool, Flow> {
+        match self.exec(body) {
+            Ok(()) | Err(Flow::Continue) => Ok(true),
+            Err(Flow::Break) => Ok(false),
+            Err(flow) => Err(flow),
+        }
+    }
+
+    fn exec(&mut self, stmt: &Stmt) -> Result<(), Flow> {
+        match stmt {
+            Stmt::Expr(Expr::Assign(..)) => {
+                if let Stmt::Expr(expr) = stmt {
+                    self.eval(expr)?;
+                }
+            },
+            Stmt::Expr(expr) => {
+                let value = self.eval(expr)?;
+                self.print(&value);
+                let _ = self.out.write_all(b"\n");
+                self.last = value;
+            },
+            Stmt::Str(s) => {
+                let _ = self.out.write_all(s.as_bytes());
+            },
+            Stmt::Print(items) => {
+                for item in items {
+                    match item {
+                        PrintItem::Expr(expr) => {
+                            let value = self.eval(expr)?;
+                            self.print(&value);
+                            self.last = value;
+                        },
+                        PrintItem::Str(s) => {
+                            let _ = self.out.write_all(s.as_bytes());
+                        },
+                    }
+                }
+            },
+            Stmt::Block(stmts) => self.exec_all(stmts)?,
+            Stmt::If(cond, then, otherwise) => {
+                if !self.eval(cond)?.is_zero() {
+                    self.exec(then)?;
+                } else if let Some(otherwise) = otherwise {
+                    self.exec(otherwise)?;
+                }
+            },
+            Stmt::While(cond, body) => {
+                while !self.eval(cond)?.is_zero() && self.iterate(body)? {}
+            },
+            Stmt::For(init, cond, step, body) => {
+                if let Some(init) = init {
+                    self.eval(init)?;
+                }
+                loop {
+                    if let Some(cond) = cond {
+                        if self.eval(cond)?.is_zero() {
+                            break;
+                        }
+                    }
+                    if !self.iterate(body)? {
+                        break;
+                    }
+                    if let Some(step) = step {
+                        self.eval(step)?;
+                    }
+                }
+            },
+            Stmt::Break => return Err(Flow::Break),
+            Stmt::Continue => return Err(Flow::Continue),
+            Stmt::Return(None) => return Err(Flow::Return(Number::zero())),
+            Stmt::Return(Some(expr)) => return Err(Flow::Return(self.eval(expr)?)),
+            Stmt::Halt => return Err(Flow::Halt),
+        }
+        Ok(())
+    }
+
+    /// Reads and runs statements until the end of the input, quit or halt.
+    /// Errors are reported and the statement that made them abandoned.
+    fn run(&mut self, parser: &mut Parser) {
+        loop {
+            let item = parser.item();
+            if parser.quit {
+                break;
+            }
+            match item {
+                Ok(None) => break,
+                Ok(Some(Item::Define(name, func))) => {
+                    self.funcs.insert(name, Rc::new(func));
+                },
+                Ok(Some(Item::Stmt(stmt))) => match self.exec(&stmt) {
+                    Err(Flow::Halt) => break,
+                    Err(Flow::Error(e)) => {
+                        let _ = self.out.flush();
+                        err!("{}", e);
+                    },
+                    _ => (),
+                },
+                Err(msg) => {
+                    let _ = self.out.flush();
+                    err!("{}", msg);
+                    parser.recover();
+                },
+            }
+            let _ = self.out.flush();
+        }
+        let _ = self.out.flush();
+    }
+}
+
+pub const SPEC: Spec = Spec::new("arbitrary-precision arithmetic language", &[
+    Opt::flag('l', "load the math library"),
+], "[file ...]");
+
+pub fn run(args: Vec<OsString>) -> i32 {
+    let parsed = SPEC.parse(args);
+    let library = parsed.flag('l');
+    let files = parsed.operands;
+
+    // Functions recurse as the interpreter does, so it runs on a thread
+    // with a stack big enough for deeply recursive ones.
+    let interpreter = thread::Builder::new().stack_size(STACK_SIZE).spawn(move || {
+        // The library and any files are read before the standard input.
+        let mut inputs: Vec<Box<dyn BufRead>> = Vec::new();
+        if library {
+            inputs.push(Box::new(io::Cursor::new(LIBRARY.as_bytes())));
+        }
+        for file in files {
+            match Input::open(&file) {
+                Ok(input) => inputs.push(Box::new(input)),
+                Err(e) => {
+                    err!("{}: {}", file.to_string_lossy(), e);
+                    process::exit(status::FAILURE);
+                }
+            }
+        }
+        inputs.push(Box::new(BufReader::new(io::stdin())));
+        Bc::new(io::BufWriter::new(io::stdout())).run(&mut Parser::new(inputs));
+    });
+    if let Err(e) = interpreter.map(|handle| handle.join()) {
+        err!("{}", e);
+        process::exit(status::FAILURE);
+    }
+    status::SUCCESS
+}
+
+#[test]
+fn test_bc() {
+    let run = |program: &str| {
+        let library: Box<dyn BufRead> = Box::new(io::Cursor::new(LIBRARY.as_bytes()));
+        let input: Box<dyn BufRead> = Box::new(io::Cursor::new(program.as_bytes().to_vec()));
+        let mut bc = Bc::new(Vec::new());
+        bc.run(&mut Parser::new(vec![library, input]));
+        String::from_utf8(bc.out).unwrap()
+    };
+    assert_eq!(run("scale = 0\n1 + 2 * 3; (1 + 2) * 3; 7 / 2; 7 % 3; -2 ^ 2; 2 ^ 3 ^ 2\n"), "7\n9\n3\n1\n4\n512\n");
+    assert_eq!(run("scale = 5; 1 / 3; 2.5 * 2.5; sqrt(2); length(123.45); scale(1.500)\n"),
+               ".33333\n6.25\n1.41421\n5\n3\n");
+    assert_eq!(run("obase = 16; 255; ibase = 16; obase = A; FF\n"), "FF\n255\n");
+    assert_eq!(run("a = 5; a += 2; a; a++; a; --a; b[3] = a * 2; b[3]; b[4]\n"), "7\n7\n8\n7\n14\n0\n");
+    // Assignment binds more tightly than comparison.
+    assert_eq!(run("x = 3 < 2; x; 3 == 3 && !(2 > 3); 0 || 0\n"), "0\n3\n1\n0\n");
+    assert_eq!(run("for (i = 0; i < 10; i++) { if (i == 3) continue; if (i > 5) break; i }\n"), "0\n1\n2\n4\n5\n");
+    assert_eq!(run("i = 3; while (i) i = i - 1\n\"done\n\"\n"), "done\n");
+    assert_eq!(run("if (0) 1 else 2\n{ if (1) {\n3\n}\nelse 4 }\n"), "2\n3\n");
+    assert_eq!(run(concat!(
+        "define f(n) {\n\tauto r\n\tif (n <= 1) return (1)\n\tr = n * f(n - 1)\n\treturn (r)\n}\n",
+        "f(20); r\n")), "2432902008176640000\n0\n");
+    // Automatic variables are seen by the functions a function calls.
+    assert_eq!(run("define g() { return (v) }\ndefine h(v) { return (g()) }\nv = 1; h(2); g()\n"), "2\n1\n");
+    assert_eq!(run("define s(a[]) { a[0] = 9; return (a[0] + a[1]) }\nx[1] = 1; s(x[]); x[0]\n"), "10\n0\n");
+    assert_eq!(run("print 1, \"\\t\", 2, \"\\n\"; 1 / 0; 5\nquit\n6\n"), "1\t2\n5\n");
+    assert_eq!(run("scale = 10; e(1); l(2); 4 * a(1); s(0); c(0); j(0, 0)\n"),
+               "2.7182818284\n.6931471805\n3.1415926532\n0\n1.0000000000\n1.0000000000\n");
+}
@@ -0,0 +1,175 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// An implementation of the cal(1) command in Rust.
+// See http://man.cat-v.org/unix-7th/1/cal
+use std::ffi::OsString;
+use std::io;
+use std::io::Write;
+use std::process;
+
+use crate::cmd::Spec;
+use crate::err;
+use crate::status;
+use crate::time;
+use crate::time::{Tm, LONG_MONTH_NAMES};
+
+const DAY_HEADINGS: &str = " S  M Tu  W Th  F  S";
+
+/// How far along a line of a year's calendar each of its three months
+/// starts.
+const MONTH_WIDTH: usize = 23;
+
+/// Returns the day of the week of 1 January of a year, with Sunday as 0.
+/// Years up to 1752 follow the Julian calendar and later ones the
+/// Gregorian, which Britain and its colonies adopted in September 1752.
+fn jan1(year: u32) -> u32 {
+    let mut d = 4 + year + year.div_ceil(4);
+    if year > 1800 {
+        d -= (year - 1701) / 100;
+        d += (year - 1601) / 400;
+    }
+    // The days dropped in the change.
+    if year > 1752 {
+        d += 3;
+    }
+    d % 7
+}
+
+fn is_leap(year: u32) -> bool {
+    year.is_multiple_of(4) && (year <= 1752 || !year.is_multiple_of(100) || year.is_multiple_of(400))
+}
+
+/// Returns the days of a month, which in September 1752 go from the 2nd
+/// straight to the 14th.
+fn days(month: u32, year: u32) -> Vec<u32> {
+    let len = match month {
+        2 if is_leap(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    };
+    (1..=len).filter(|&day| !(year == 1752 && month == 9 && (3..=13).contains(&day))).collect()
+}
+
+/// Lays out a month as six weeks of days at the given column of the
+/// lines.
+fn cal(month: u32, year: u32, lines: &mut [Vec<u8>], column: usize) {
+    let before: usize = (1..month).map(|m| days(m, year).len()).sum();
+    let mut wday = (jan1(year) as usize + before) % 7;
+    let mut week = 0;
+    for day in days(month, year) {
+        let pos = column + 3 * wday;
+        let line = &mut lines[week];
+        if line.len() < pos + 2 {
+            line.resize(pos + 2, b' ');
+        }
+        if day > 9 {
+            line[pos] = b'0' + (day / 10) as u8;
+        }
+        line[pos + 1] = b'0' + (day % 10) as u8;
+        wday += 1;
+        if wday == 7 {
+            wday = 0;
+            week += 1;
+        }
+    }
+}
+
+/// Writes a month's calendar.
+fn month(out: &mut dyn Write, month: u32, year: u32) -> io::Result<()> {
+    writeln!(out, "   {} {}", LONG_MONTH_NAMES[month as usize - 1], year)?;
+    writeln!(out, "{}", DAY_HEADINGS)?;
+    let mut lines = vec![Vec::new(); 6];
+    cal(month, year, &mut lines, 0);
+    for line in lines {
+        out.write_all(&line)?;
+        out.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+/// Writes a year's calendar, three months across.
+fn year(out: &mut dyn Write, year: u32) -> io::Result<()> {
+    write!(out, "\n\n\n\t\t\t\t{}\n\n", year)?;
+    for first in (1..=12).step_by(3) {
+        let name = |m: u32| &LONG_MONTH_NAMES[m as usize - 1][..3];
+        writeln!(out, "\t {}\t\t\t {}\t\t       {}", name(first), name(first + 1), name(first + 2))?;
+        writeln!(out, "{}   {}   {}", DAY_HEADINGS, DAY_HEADINGS, DAY_HEADINGS)?;
+        let mut lines = vec![Vec::new(); 6];
+        for i in 0..3 {
+            cal(first + i, year, &mut lines, MONTH_WIDTH * i as usize);
+        }
+        for line in lines {
+            out.write_all(&line)?;
+            out.write_all(b"\n")?;
+        }
+    }
+    write!(out, "\n\n\n")
+}
+
+pub const SPEC: Spec = Spec::new("print calendar", &[], "[[month] year]");
+
+pub fn run(args: Vec<OsString>) -> i32 {
+    let operands = SPEC.parse(args).strings();
+
+    let number = |arg: &str, max: u32| match arg.parse::<u32>() {
+        Ok(n) if (1..=max).contains(&n) && arg.bytes().all(|c| c.is_ascii_digit()) => n,
+        _ => {
+            err!("bad argument: {}", arg);
+            process::exit(status::USAGE);
+        }
+    };
+    let stdout = io::stdout();
+    let mut out = io::BufWriter::new(stdout.lock());
+    let result = match operands.as_slice() {
+        [] => {
+            let now = Tm::local(time::now().0);
+            month(&mut out, now.month, now.year as u32)
+        },
+        [y] => year(&mut out, number(y, 9999)),
+        [m, y] => month(&mut out, number(m, 12), number(y, 9999)),
+        _ => SPEC.usage(),
+    };
+    if let Err(e) = result.and_then(|_| out.flush()) {
+        err!("{}", e);
+        process::exit(status::FAILURE);
+    }
+    status::SUCCESS
+}
+
+#[test]
+fn test_cal() {
+    let month_of = |m, y| {
+        let mut out = Vec::new();
+        month(&mut out, m, y).unwrap();
+        String::from_utf8(out).unwrap()
+    };
+    assert_eq!(month_of(9, 1752), concat!(
+        "   September 1752\n",
+        " S  M Tu  W Th  F  S\n",
+        "       1  2 14 15 16\n",
+        "17 18 19 20 21 22 23\n",
+        "24 25 26 27 28 29 30\n",
+        "\n\n\n"));
+    assert_eq!(month_of(2, 2021), concat!(
+        "   February 2021\n",
+        " S  M Tu  W Th  F  S\n",
+        "    1  2  3  4  5  6\n",
+        " 7  8  9 10 11 12 13\n",
+        "14 15 16 17 18 19 20\n",
+        "21 22 23 24 25 26 27\n",
+        "28\n",
+        "\n"));
+    // Julian leap years include 1700; Gregorian ones not 1900.
+    assert_eq!(days(2, 1700).len(), 29);
+    assert_eq!(days(2, 1900).len(), 28);
+    assert_eq!(days(2, 2000).len(), 29);
+    assert_eq!(jan1(1), 6);
+    assert_eq!(jan1(1753), 1);
+    let mut out = Vec::new();
+    year(&mut out, 2021).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.starts_with("\n\n\n\t\t\t\t2021\n\n\t Jan\t\t\t Feb\t\t       Mar\n"));
+    assert!(out.contains("\n                1  2       1  2  3  4  5  6       1  2  3  4  5  6\n"));
+}
@@ -0,0 +1,51 @@
+// Copyright 2015-2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// An implementation of the cat(1) command in Rust.
+// See http://man.cat-v.org/unix-7th/1/cat
+use std::ffi::{OsStr, OsString};
+use std::io;
+
+use crate::cmd::Spec;
+#[cfg(unix)]
+use crate::copy;
+use crate::diag;
+use crate::err;
+use crate::input::Input;
+#[cfg(windows)]
+use crate::platform;
+use crate::sandbox;
+
+#[cfg(unix)]
+fn cat(filename: &OsStr) -> io::Result<u64> {
+    use std::os::fd::AsFd;
+
+    // The bytes go through unchanged, so the kernel can copy them.
+    let input = Input::open(filename)?;
+    copy::copy(input.as_fd(), io::stdout().as_fd())
+}
+
+#[cfg(windows)]
+fn cat(filename: &OsStr) -> io::Result<u64> {
+    let mut reader = Input::open(filename)?;
+    io::copy(&mut reader, &mut platform::stdout())
+}
+
+pub const SPEC: Spec = Spec::new("catenate and print", &[], "[file ...]");
+
+pub fn run(mut args: Vec<OsString>) -> i32 {
+    if args.len() == 1 {
+        args.push(OsString::from("-"));
+    }
+    if args[1..].iter().all(|arg| arg == "-") {
+        sandbox::filter();
+    }
+
+    for arg in args.iter().skip(1) {
+        match cat(arg) {
+            Ok(_) => {}
+            Err(e) => err!("{}: {}", arg.to_string_lossy(), e),
+        };
+    }
+    diag::status()
+}
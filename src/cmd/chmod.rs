@@ -0,0 +1,89 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// An implementation of the chmod(1) command in Rust.
+// See http://man.cat-v.org/unix-7th/1/chmod
+use std::ffi::OsString;
+use std::fs;
+use std::fs::{Metadata, Permissions};
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::process;
+
+use crate::cmd::{Opt, Spec};
+use crate::diag;
+use crate::dir::Dir;
+use crate::err;
+use crate::perm;
+use crate::status;
+use crate::trace;
+use crate::walk::{Follow, Walk};
+
+/// Gives a file, whose metadata are given, the mode. A file found in a
+/// directory being walked is changed by its name in the open directory.
+fn chmod(parent: Option<&Dir>, path: &Path, meta: &Metadata, mode: &perm::Mode, umask: u32) {
+    let new = mode.apply(meta.permissions().mode(), meta.is_dir(), umask);
+    let result = match (parent, path.file_name()) {
+        (Some(dir), Some(name)) => dir.chmod(name, new),
+        _ => fs::set_permissions(path, Permissions::from_mode(new)),
+    };
+    if let Err(e) = trace::op("chmod", format_args!("{:?}, {:04o}", path, new), result) {
+        err!("{}: {}", path.display(), e);
+    }
+}
+
+pub const SPEC: Spec = Spec::new("change the modes of files", &[
+    Opt::flag('R', "change directories and everything in them"),
+], "mode file ...");
+
+pub fn run(args: Vec<OsString>) -> i32 {
+    // A mode such as -w looks like an option, so it is taken out before
+    // the options are parsed: it is the first argument that isn't -R.
+    let mut args = args;
+    let mut i = 1;
+    while i < args.len() && args[i] == "-R" {
+        i += 1;
+    }
+    if i < args.len() && args[i] == "--" {
+        args.remove(i);
+    }
+    if i >= args.len() {
+        SPEC.usage();
+    }
+    let mode = args.remove(i);
+    let parsed = SPEC.parse(args);
+    let recursive = parsed.flag('R');
+    let mode = match perm::parse(&mode.to_string_lossy()) {
+        Ok(mode) => mode,
+        Err(e) => {
+            err!("{}", e);
+            process::exit(status::USAGE);
+        }
+    };
+    if parsed.operands.is_empty() {
+        SPEC.usage();
+    }
+    let umask = perm::umask();
+
+    for file in &parsed.operands {
+        let path = Path::new(file);
+        if !recursive {
+            match fs::metadata(path) {
+                Ok(meta) => chmod(None, path, &meta, &mode, umask),
+                Err(e) => err!("{}: {}", path.display(), e),
+            }
+            continue;
+        }
+        // The links found in the directories are left alone, since
+        // changing one's mode would change that of the file it refers to.
+        let mut walk = Walk::new([path]).follow(Follow::Roots);
+        while let Some(entry) = walk.next() {
+            match entry {
+                Ok(entry) if entry.metadata().file_type().is_symlink() => (),
+                Ok(entry) => chmod(walk.parent(), entry.path(), entry.metadata(), &mode, umask),
+                Err(e) => err!("{}", e),
+            }
+        }
+    }
+    diag::status()
+}
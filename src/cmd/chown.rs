@@ -0,0 +1,67 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// An implementation of the chown(1) command in Rust.
+// See http://man.cat-v.org/unix-7th/1/chown
+use std::ffi::OsString;
+use std::path::Path;
+use std::process;
+
+use crate::cmd::{Opt, Spec};
+use crate::diag;
+use crate::err;
+use crate::owner;
+use crate::status;
+use crate::users;
+use crate::walk::Follow;
+
+pub const SPEC: Spec = Spec::new("change the owners of files", &[
+    Opt::flag('R', "change directories and everything in them"),
+    Opt::flag('H', "with -R, follow symbolic links named as operands (the default)"),
+    Opt::flag('L', "with -R, follow all symbolic links"),
+    Opt::flag('P', "with -R, follow no symbolic links"),
+], "owner[:[group]] file ...");
+
+pub fn run(args: Vec<OsString>) -> i32 {
+    let parsed = SPEC.parse(args);
+    let recursive = parsed.flag('R');
+    let mut follow = Follow::Roots;
+    for (c, _) in parsed.opts() {
+        follow.set(c);
+    }
+    let [spec, files @ ..] = parsed.operands.as_slice() else {
+        SPEC.usage();
+    };
+    if files.is_empty() {
+        SPEC.usage();
+    }
+    let spec = spec.to_string_lossy();
+    let (user, group) = match spec.split_once(':') {
+        Some((user, group)) => (user, Some(group)),
+        None => (&*spec, None),
+    };
+    let uid = users::parse_uid(user).unwrap_or_else(|| {
+        err!("{}: unknown user", user);
+        process::exit(status::FAILURE);
+    });
+    // An empty group is the owner's login group.
+    let gid = match group {
+        Some("") => match users::user_name(uid).and_then(|name| users::login_gid(&name)) {
+            Some(gid) => Some(gid),
+            None => {
+                err!("{}: no login group", user);
+                return status::FAILURE;
+            }
+        },
+        Some(group) => Some(users::parse_gid(group).unwrap_or_else(|| {
+            err!("{}: unknown group", group);
+            process::exit(status::FAILURE);
+        })),
+        None => None,
+    };
+
+    for file in files {
+        owner::change(Path::new(file), Some(uid), gid, recursive, follow);
+    }
+    diag::status()
+}
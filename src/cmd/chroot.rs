@@ -0,0 +1,71 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// An implementation of the chroot(8) command in Rust. chroot is not a
+// Seventh Edition command; it first appeared in System III.
+// See https://man.freebsd.org/cgi/man.cgi?query=chroot&sektion=8
+use std::env;
+use std::ffi::OsString;
+use std::io;
+use std::os::unix::fs;
+use std::os::unix::process::CommandExt;
+use std::process;
+
+use crate::cmd::Spec;
+use crate::err;
+use crate::status;
+
+/// The exit status when chroot itself fails.
+const FAILED: i32 = 125;
+
+/// Returns the command to run: the one given or, by default, an
+/// interactive shell.
+fn command(args: &[OsString], shell: Option<OsString>) -> Vec<OsString> {
+    if !args.is_empty() {
+        return args.to_vec();
+    }
+    let shell = shell.filter(|s| !s.is_empty()).unwrap_or_else(|| OsString::from("/bin/sh"));
+    vec![shell, OsString::from("-i")]
+}
+
+pub const SPEC: Spec = Spec::new("run a command with another root directory", &[], "newroot [command [arg ...]]");
+
+pub fn run(args: Vec<OsString>) -> i32 {
+    let mut args = args.into_iter();
+    let prog = args.next().unwrap();
+    // The arguments aren't parsed as options beyond the first, so that
+    // those of the command are passed on as they are.
+    let mut operands: Vec<OsString> = args.collect();
+    if operands.first().is_some_and(|arg| arg == "--") {
+        operands.remove(0);
+    } else if operands.first().is_some_and(|arg| arg.len() > 1 && arg.as_encoded_bytes().starts_with(b"-")) {
+        operands.clear();
+    }
+    if operands.is_empty() {
+        eprintln!("usage: {} newroot [command [arg ...]]", prog.to_string_lossy());
+        process::exit(FAILED);
+    }
+    let root = operands.remove(0);
+
+    if let Err(e) = fs::chroot(&root).and_then(|_| env::set_current_dir("/")) {
+        if e.kind() == io::ErrorKind::PermissionDenied {
+            err!("{}: {}; only the super-user can change the root directory", root.to_string_lossy(), e);
+        } else {
+            err!("{}: {}", root.to_string_lossy(), e);
+        }
+        process::exit(FAILED);
+    }
+    let cmd = command(&operands, env::var_os("SHELL"));
+    let e = process::Command::new(&cmd[0]).args(&cmd[1..]).exec();
+    err!("{}: {}", cmd[0].to_string_lossy(), e);
+    if e.kind() == io::ErrorKind::NotFound { status::NOT_FOUND } else { status::NOT_EXECUTABLE }
+}
+
+#[test]
+fn test_command() {
+    let args = vec![OsString::from("ls"), OsString::from("-l")];
+    assert_eq!(command(&args, Some(OsString::from("/bin/ksh"))), args);
+    assert_eq!(command(&[], Some(OsString::from("/bin/ksh"))), vec!["/bin/ksh", "-i"]);
+    assert_eq!(command(&[], Some(OsString::new())), vec!["/bin/sh", "-i"]);
+    assert_eq!(command(&[], None), vec!["/bin/sh", "-i"]);
+}
@@ -0,0 +1,58 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// An implementation of the cksum(1) command in Rust. cksum is not a
+// Seventh Edition command; it first appeared in 4.4BSD.
+// See https://pubs.opengroup.org/onlinepubs/9699919799/utilities/cksum.html
+use std::ffi::{OsStr, OsString};
+use std::io;
+use std::io::Write;
+use std::process;
+
+use crate::checksum;
+use crate::cmd::Spec;
+use crate::diag;
+use crate::err;
+use crate::input::Input;
+use crate::status;
+
+/// Returns the CRC and size of the named file, or of the standard input
+/// if the name is "-".
+fn cksum(name: &OsStr) -> io::Result<(u32, u64)> {
+    let mut input = Input::open(name)?;
+    let mut crc = checksum::Crc::new();
+    io::copy(&mut input, &mut crc)?;
+    Ok((crc.value(), crc.bytes()))
+}
+
+pub const SPEC: Spec = Spec::new("write file checksums and sizes", &[], "[file ...]");
+
+pub fn run(args: Vec<OsString>) -> i32 {
+    let mut files = SPEC.parse(args).operands;
+
+    // The standard input is summed without a name.
+    let stdin = files.is_empty();
+    if stdin {
+        files.push(OsString::from("-"));
+    }
+
+    let mut out = io::stdout().lock();
+    for file in &files {
+        match cksum(file) {
+            Ok((crc, bytes)) => {
+                let mut line = format!("{} {}", crc, bytes).into_bytes();
+                if !stdin {
+                    line.push(b' ');
+                    line.extend_from_slice(file.as_encoded_bytes());
+                }
+                line.push(b'\n');
+                if let Err(e) = out.write_all(&line) {
+                    err!("{}", e);
+                    process::exit(status::FAILURE);
+                }
+            },
+            Err(e) => err!("{}: {}", file.to_string_lossy(), e),
+        }
+    }
+    diag::status()
+}
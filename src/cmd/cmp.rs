@@ -0,0 +1,152 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// An implementation of the cmp(1) command in Rust.
+// See http://man.cat-v.org/unix-7th/1/cmp
+use std::ffi::{OsStr, OsString};
+use std::io;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::process;
+
+use crate::cmd::{Opt, Spec};
+use crate::diag;
+use crate::err;
+use crate::input::Input;
+use crate::status;
+use crate::warn;
+
+/// The exit status when the files are the same.
+const SAME: i32 = status::TRUE;
+/// The exit status when the files differ.
+const DIFFERENT: i32 = status::FALSE;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    /// Report the first difference.
+    First,
+    /// List every differing byte.
+    List,
+    /// Report nothing; just set the exit status.
+    Silent,
+}
+
+/// Compares the two inputs, returning the exit status.
+fn cmp(names: [&OsStr; 2], readers: [&mut dyn BufRead; 2], mode: Mode,
+       out: &mut dyn Write) -> io::Result<i32> {
+    let [a, b] = readers;
+    let mut offset: u64 = 0;
+    let mut line: u64 = 1;
+    let mut status = SAME;
+    loop {
+        let abuf = a.fill_buf()?;
+        let bbuf = b.fill_buf()?;
+        if abuf.is_empty() || bbuf.is_empty() {
+            if abuf.len() == bbuf.len() {
+                return Ok(status);
+            }
+            if mode != Mode::Silent {
+                out.flush()?;
+                let name = if abuf.is_empty() { names[0] } else { names[1] };
+                warn!("EOF on {}", name.to_string_lossy());
+            }
+            return Ok(DIFFERENT);
+        }
+        let n = abuf.len().min(bbuf.len());
+        if abuf[..n] == bbuf[..n] {
+            // Only the first difference is reported with its line, and
+            // counting them is slow over the gigabytes of a large file.
+            if mode == Mode::First {
+                line += abuf[..n].iter().filter(|&&c| c == b'\n').count() as u64;
+            }
+        } else {
+            for (i, (&x, &y)) in abuf[..n].iter().zip(&bbuf[..n]).enumerate() {
+                if x == y {
+                    if x == b'\n' {
+                        line += 1;
+                    }
+                    continue;
+                }
+                let pos = offset + i as u64 + 1;
+                match mode {
+                    Mode::First => {
+                        out.write_all(&[names[0].as_encoded_bytes(), b" ",
+                                        names[1].as_encoded_bytes()].concat())?;
+                        writeln!(out, " differ: char {}, line {}", pos, line)?;
+                        return Ok(DIFFERENT);
+                    },
+                    Mode::Silent => return Ok(DIFFERENT),
+                    Mode::List => {
+                        writeln!(out, "{} {:o} {:o}", pos, x, y)?;
+                        status = DIFFERENT;
+                    },
+                }
+            }
+        }
+        offset += n as u64;
+        a.consume(n);
+        b.consume(n);
+    }
+}
+
+pub const SPEC: Spec = Spec::new("compare two files", &[
+    Opt::flag('l', "list the offset and the differing bytes of every difference"),
+    Opt::flag('s', "print nothing; only return the exit status"),
+], "file1 file2");
+
+pub fn run(args: Vec<OsString>) -> i32 {
+    // Trouble, rather than a difference, is the exit status cmp gives
+    // for bad usage.
+    let parsed = SPEC.try_parse(args).unwrap_or_else(|e| {
+        err!("{}", e);
+        process::exit(status::TROUBLE);
+    });
+    // The last of -l and -s wins.
+    let mode = match parsed.opts().last() {
+        Some(('l', _)) => Mode::List,
+        Some(('s', _)) => Mode::Silent,
+        _ => Mode::First,
+    };
+    let names = parsed.operands;
+
+    if names.len() != 2 {
+        eprintln!("usage: {}", SPEC.synopsis(diag::prog()));
+        process::exit(status::TROUBLE);
+    }
+    if names[0] == "-" && names[1] == "-" {
+        err!("standard input can only be compared with a file");
+        process::exit(status::TROUBLE);
+    }
+
+    let open = |name: &OsStr| match Input::open(name) {
+        Ok(reader) => reader,
+        Err(e) => {
+            if mode != Mode::Silent {
+                err!("{}: {}", name.to_string_lossy(), e);
+            }
+            process::exit(status::TROUBLE);
+        }
+    };
+    let inputs = [open(&names[0]), open(&names[1])];
+    // Regular files are compared in place, in memory, and anything else
+    // is read.
+    let maps = inputs.each_ref().map(Input::map);
+    let mut readers = inputs.map(|input| {
+        Box::new(BufReader::with_capacity(64 * 1024, input)) as Box<dyn BufRead + '_>
+    });
+    for (reader, map) in readers.iter_mut().zip(&maps) {
+        if let Some(map) = map {
+            *reader = Box::new(&map[..]);
+        }
+    }
+    let [a, b] = &mut readers;
+    let mut out = BufWriter::new(io::stdout());
+    let status = match cmp([&names[0], &names[1]], [a.as_mut(), b.as_mut()], mode, &mut out)
+        .and_then(|status| out.flush().map(|_| status)) {
+        Ok(status) => status,
+        Err(e) => {
+            err!("{}", e);
+            status::TROUBLE
+        }
+    };
+    status
+}
@@ -0,0 +1,102 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// An implementation of the cp(1) command in Rust.
+// See http://man.cat-v.org/unix-7th/1/cp
+use std::ffi::OsString;
+use std::fs;
+use std::fs::Metadata;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+use crate::cmd::{Opt, Spec};
+use crate::copy;
+use crate::diag;
+use crate::err;
+use crate::prompt;
+use crate::status;
+use crate::walk::Follow;
+
+/// Returns whether the metadata are of the same file.
+fn same_file(a: &Metadata, b: &Metadata) -> bool {
+    a.dev() == b.dev() && a.ino() == b.ino()
+}
+
+/// Copies a file or, if recursive, a directory to the path, following
+/// symbolic links as the policy says. If interactive, the user is asked
+/// before a file is replaced.
+fn cp(from: &Path, to: &Path, recursive: bool, follow: Follow, interactive: bool) {
+    // Without -R, a link named as an operand is always followed.
+    let meta = if !recursive || follow.follows(0) { fs::metadata(from) } else { fs::symlink_metadata(from) };
+    let meta = match meta {
+        Ok(meta) => meta,
+        Err(e) => {
+            err!("{}: {}", from.display(), e);
+            return;
+        }
+    };
+    if let Ok(dest) = fs::metadata(to) {
+        if same_file(&meta, &dest) {
+            err!("{} and {} are the same file", from.display(), to.display());
+            return;
+        }
+    }
+    if !recursive {
+        if meta.is_dir() {
+            err!("{}: is a directory", from.display());
+            return;
+        }
+        if interactive && fs::symlink_metadata(to).is_ok()
+            && !prompt::ask(&format!("overwrite '{}'?", to.display())) {
+            return;
+        }
+        if let Err(e) = copy::copy_file(from, to) {
+            err!("{}: {}", to.display(), e);
+        }
+        return;
+    }
+    // Copying a directory into itself would never end.
+    let parent = match to.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    if let (Ok(from), Ok(parent)) = (from.canonicalize(), parent.canonicalize()) {
+        if meta.is_dir() && parent.starts_with(from) {
+            err!("cannot copy {} into itself", to.display());
+            return;
+        }
+    }
+    copy::copy_tree(from, to, follow, interactive);
+}
+
+pub const SPEC: Spec = Spec::new("copy files", &[
+    Opt::flag('i', "ask before replacing each file"),
+    Opt::flag('R', "copy directories and their contents"),
+    Opt::flag('r', "the same as -R"),
+    Opt::flag('H', "with -R, follow symbolic links named as operands (the default)"),
+    Opt::flag('L', "with -R, follow all symbolic links"),
+    Opt::flag('P', "with -R, follow no symbolic links"),
+], "file1 file2 | file ... directory");
+
+pub fn run(args: Vec<OsString>) -> i32 {
+    let parsed = SPEC.parse(args);
+    let recursive = parsed.flag('R') || parsed.flag('r');
+    let interactive = parsed.flag('i');
+    let mut follow = Follow::Roots;
+    for (c, _) in parsed.opts() {
+        follow.set(c);
+    }
+    let mut files: Vec<PathBuf> = parsed.operands.into_iter().map(PathBuf::from).collect();
+    if files.len() < 2 {
+        SPEC.usage();
+    }
+    let target = files.pop().unwrap();
+    if files.len() > 1 && !target.is_dir() {
+        err!("{}: not a directory", target.display());
+        return status::FAILURE;
+    }
+    for file in &files {
+        cp(file, &copy::destination(file, &target), recursive, follow, interactive);
+    }
+    diag::status()
+}
@@ -0,0 +1,109 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// An implementation of the date(1) command in Rust.
+// See http://man.cat-v.org/unix-7th/1/date
+use std::ffi::OsString;
+use std::io;
+use std::process;
+
+use crate::cmd::{Opt, Spec};
+use crate::err;
+use crate::status;
+use crate::time;
+use crate::time::Tm;
+
+/// The format used when none is given: that of ctime(3), with the time
+/// zone before the year.
+const DEFAULT_FORMAT: &str = "%a %b %e %H:%M:%S %Z %Y";
+
+/// Parses a date given as MMDDhhmm[[CC]YY], in UTC or local time,
+/// returning seconds since the epoch. A two-digit year from 69 is in the
+/// twentieth century, and one before 69 in the twenty-first. Without a
+/// year, the current year is used.
+fn parse_date(s: &str, utc: bool) -> Option<i64> {
+    if !s.bytes().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let field = |i: usize| -> u32 { s[i..i + 2].parse().unwrap() };
+    let now = if utc { Tm::utc(time::now().0) } else { Tm::local(time::now().0) };
+    let year = match s.len() {
+        8 => now.year,
+        10 => {
+            let yy = field(8) as i32;
+            if yy >= 69 { 1900 + yy } else { 2000 + yy }
+        },
+        12 => s[8..].parse().unwrap(),
+        _ => return None,
+    };
+    let tm = Tm {
+        year,
+        month: field(0),
+        day: field(2),
+        hour: field(4),
+        min: field(6),
+        sec: 0,
+        ..now
+    };
+    if utc { tm.utc_secs() } else { tm.local_secs() }
+}
+
+/// Sets the system clock, which only the super-user may do.
+fn set_clock(secs: i64) -> io::Result<()> {
+    // SAFETY: geteuid has no preconditions.
+    if unsafe { libc::geteuid() } != 0 {
+        return Err(io::Error::new(io::ErrorKind::PermissionDenied,
+                                  "only the super-user may set the date"));
+    }
+    let ts = libc::timespec { tv_sec: secs as libc::time_t, tv_nsec: 0 };
+    // SAFETY: ts is a valid timespec.
+    if unsafe { libc::clock_settime(libc::CLOCK_REALTIME, &ts) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+pub const SPEC: Spec = Spec::new("print and set the date", &[
+    Opt::flag('u', "use UTC rather than the local time zone"),
+], "[+format | MMDDhhmm[[CC]YY]]");
+
+pub fn run(args: Vec<OsString>) -> i32 {
+    let parsed = SPEC.parse(args);
+    let utc = parsed.flag('u');
+    let operands = parsed.strings();
+
+    let format = match operands.as_slice() {
+        [] => DEFAULT_FORMAT,
+        [format] if format.starts_with('+') => &format[1..],
+        [date] => {
+            let secs = match parse_date(date, utc) {
+                Some(secs) => secs,
+                None => {
+                    err!("invalid date: {}", date);
+                    process::exit(status::FAILURE);
+                }
+            };
+            if let Err(e) = set_clock(secs) {
+                err!("cannot set the date: {}", e);
+                process::exit(status::FAILURE);
+            }
+            DEFAULT_FORMAT
+        },
+        _ => SPEC.usage(),
+    };
+    let secs = time::now().0;
+    let tm = if utc { Tm::utc(secs) } else { Tm::local(secs) };
+    println!("{}", tm.format(format));
+    status::SUCCESS
+}
+
+#[test]
+fn test_parse_date() {
+    let secs = parse_date("030405062021", true).unwrap();
+    assert_eq!(Tm::utc(secs).ctime(), "Thu Mar  4 05:06:00 2021");
+    assert_eq!(Tm::utc(parse_date("0304050621", true).unwrap()).year, 2021);
+    assert_eq!(Tm::utc(parse_date("0304050699", true).unwrap()).year, 1999);
+    assert_eq!(parse_date("02300506", true), None);
+    assert_eq!(parse_date("0304056", true), None);
+    assert_eq!(parse_date("+0304056", true), None);
+}
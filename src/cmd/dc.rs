@@ -0,0 +1,537 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// An implementation of the dc(1) command in Rust.
+// See http://man.cat-v.org/unix-7th/1/dc
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fmt;
+use std::io;
+use std::io::{BufRead, BufReader, Write};
+use std::process;
+use std::rc::Rc;
+
+use crate::bignum;
+use crate::bignum::Number;
+use crate::cmd::Spec;
+use crate::err;
+use crate::input::Input;
+use crate::status;
+
+#[derive(Clone, Debug)]
+enum Value {
+    Num(Number),
+    Str(Rc<[u8]>),
+}
+
+/// A register, of which each name has a stack.
+#[derive(Default)]
+struct Register {
+    value: Option<Value>,
+    array: HashMap<usize, Value>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Error {
+    StackEmpty,
+    NonNumeric,
+    DivideByZero,
+    NegativeRoot,
+    Scale,
+    InputBase,
+    OutputBase,
+    Index,
+    RegisterEmpty(u8),
+    Unimplemented(u8),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::StackEmpty => write!(f, "stack empty"),
+            Error::NonNumeric => write!(f, "non-numeric value"),
+            Error::DivideByZero => write!(f, "divide by zero"),
+            Error::NegativeRoot => write!(f, "square root of negative number"),
+            Error::Scale => write!(f, "scale must be a nonnegative number"),
+            Error::InputBase => write!(f, "input base must be a number between 2 and 16"),
+            Error::OutputBase => write!(f, "output base must be a number greater than 1"),
+            Error::Index => write!(f, "array index must be a nonnegative integer"),
+            Error::RegisterEmpty(r) => write!(f, "stack register '{}' (0{:o}) is empty", *r as char, r),
+            Error::Unimplemented(c) => write!(f, "'{}' (0{:o}) unimplemented", *c as char, c),
+        }
+    }
+}
+
+/// A string being executed, and the position in it.
+struct Frame {
+    code: Rc<[u8]>,
+    pos: usize,
+}
+
+struct Dc<W: Write> {
+    out: W,
+    stack: Vec<Value>,
+    registers: HashMap<u8, Vec<Register>>,
+    scale: usize,
+    ibase: u32,
+    obase: u32,
+    /// The strings being executed, innermost last.
+    frames: Vec<Frame>,
+    /// The sources of input, the one being read first.
+    inputs: Vec<Box<dyn BufRead>>,
+    /// The line of input being read, and the position in it.
+    line: Vec<u8>,
+    pos: usize,
+}
+
+impl<W: Write> Dc<W> {
+    fn new(out: W, inputs: Vec<Box<dyn BufRead>>) -> Dc<W> {
+        Dc {
+            out,
+            stack: Vec::new(),
+            registers: HashMap::new(),
+            scale: 0,
+            ibase: 10,
+            obase: 10,
+            frames: Vec::new(),
+            inputs,
+            line: Vec::new(),
+            pos: 0,
+        }
+    }
+
+    /// Reads the next line of input, moving on to the next source at the
+    /// end of one. Returns false at the end of the last.
+    fn read_line(&mut self) -> bool {
+        self.line.clear();
+        self.pos = 0;
+        while let Some(input) = self.inputs.first_mut() {
+            match input.read_until(b'\n', &mut self.line) {
+                Ok(0) | Err(_) => {
+                    self.inputs.remove(0);
+                },
+                Ok(_) => return true,
+            }
+        }
+        false
+    }
+
+    /// Returns the next byte of the string being executed, or of the input
+    /// if there is none. A string's end isn't crossed.
+    fn peek(&mut self) -> Option<u8> {
+        if let Some(frame) = self.frames.last() {
+            return frame.code.get(frame.pos).copied();
+        }
+        while self.pos >= self.line.len() {
+            if !self.read_line() {
+                return None;
+            }
+        }
+        Some(self.line[self.pos])
+    }
+
+    fn next(&mut self) -> Option<u8> {
+        let c = self.peek()?;
+        match self.frames.last_mut() {
+            Some(frame) => frame.pos += 1,
+            None => self.pos += 1,
+        }
+        Some(c)
+    }
+
+    /// Executes a string. If the current one has nothing left, it is
+    /// replaced rather than returned to, so that loops made of macros that
+    /// call themselves don't nest ever deeper.
+    fn execute(&mut self, code: Rc<[u8]>) {
+        if self.frames.last().is_some_and(|frame| frame.pos >= frame.code.len()) {
+            self.frames.pop();
+        }
+        self.frames.push(Frame { code, pos: 0 });
+    }
+
+    /// Executes a value: a string is executed and a number pushed.
+    fn execute_value(&mut self, value: Value) {
+        match value {
+            Value::Str(code) => self.execute(code),
+            value => self.stack.push(value),
+        }
+    }
+
+    fn top(&self) -> Result<&Value, Error> {
+        self.stack.last().ok_or(Error::StackEmpty)
+    }
+
+    fn pop(&mut self) -> Result<Value, Error> {
+        self.stack.pop().ok_or(Error::StackEmpty)
+    }
+
+    /// Pops a number, leaving a string where it is.
+    fn pop_num(&mut self) -> Result<Number, Error> {
+        match self.top()? {
+            Value::Num(n) => {
+                let n = n.clone();
+                self.stack.pop();
+                Ok(n)
+            },
+            Value::Str(_) => Err(Error::NonNumeric),
+        }
+    }
+
+    /// Replaces the top two numbers with the results of an operation on
+    /// them, leaving them if it fails.
+    fn binary<F>(&mut self, op: F) -> Result<(), Error>
+    where
+        F: Fn(&Number, &Number, usize) -> Result<Vec<Number>, Error>,
+    {
+        let len = self.stack.len();
+        if len < 2 {
+            return Err(Error::StackEmpty);
+        }
+        let results = match (&self.stack[len - 2], &self.stack[len - 1]) {
+            (Value::Num(a), Value::Num(b)) => op(a, b, self.scale)?,
+            _ => return Err(Error::NonNumeric),
+        };
+        self.stack.truncate(len - 2);
+        self.stack.extend(results.into_iter().map(Value::Num));
+        Ok(())
+    }
+
+    fn register(&mut self, name: u8) -> &mut Register {
+        let stack = self.registers.entry(name).or_default();
+        if stack.is_empty() {
+            stack.push(Register::default());
+        }
+        stack.last_mut().unwrap()
+    }
+
+    /// Reads the name of a register.
+    fn name(&mut self) -> Result<u8, Error> {
+        self.next().ok_or(Error::Unimplemented(b'\n'))
+    }
+
+    fn index(&mut self) -> Result<usize, Error> {
+        let n = self.pop_num()?;
+        n.to_i64().and_then(|i| usize::try_from(i).ok()).ok_or(Error::Index)
+    }
+
+    /// Writes a value as p does, without the newline.
+    fn write(&mut self, value: &Value) -> io::Result<()> {
+        match value {
+            Value::Num(n) => self.out.write_all(bignum::wrap(&n.to_string_radix(self.obase)).as_bytes()),
+            Value::Str(s) => self.out.write_all(s),
+        }
+    }
+
+    fn number(&mut self, first: u8) -> Number {
+        let mut digits = String::new();
+        let negative = first == b'_';
+        if !negative {
+            digits.push(first as char);
+        }
+        while let Some(c) = self.peek() {
+            if !(c.is_ascii_digit() || (b'A'..=b'F').contains(&c) || (c == b'.' && !digits.contains('.'))) {
+                break;
+            }
+            digits.push(c as char);
+            self.next();
+        }
+        let n = Number::parse(&digits, self.ibase).unwrap_or_else(Number::zero);
+        if negative { n.neg() } else { n }
+    }
+
+    /// Reads a string after its opening bracket. Brackets nest.
+    fn string(&mut self) -> Rc<[u8]> {
+        let mut s = Vec::new();
+        let mut depth = 0;
+        while let Some(c) = self.next() {
+            match c {
+                b'[' => depth += 1,
+                b']' if depth == 0 => break,
+                b']' => depth -= 1,
+                _ => (),
+            }
+            s.push(c);
+        }
+        s.into()
+    }
+
+    /// Runs a command. Returns false if it is to quit.
+    fn command(&mut self, c: u8) -> Result<bool, Error> {
+        match c {
+            b' ' | b'\t' | b'\r' | b'\n' => (),
+            b'0'..=b'9' | b'A'..=b'F' | b'.' | b'_' => {
+                let n = self.number(c);
+                self.stack.push(Value::Num(n));
+            },
+            b'[' => {
+                let s = self.string();
+                self.stack.push(Value::Str(s));
+            },
+            b'#' => while self.next().is_some_and(|c| c != b'\n') {},
+            b'+' => self.binary(|a, b, _| Ok(vec![a.add(b)]))?,
+            b'-' => self.binary(|a, b, _| Ok(vec![a.sub(b)]))?,
+            b'*' => self.binary(|a, b, scale| Ok(vec![a.mul(b, scale)]))?,
+            b'/' => self.binary(|a, b, scale| Ok(vec![a.div(b, scale).ok_or(Error::DivideByZero)?]))?,
+            b'%' => self.binary(|a, b, scale| Ok(vec![a.rem(b, scale).ok_or(Error::DivideByZero)?]))?,
+            b'~' => self.binary(|a, b, scale| {
+                let q = a.div(b, scale).ok_or(Error::DivideByZero)?;
+                Ok(vec![q, a.rem(b, scale).unwrap()])
+            })?,
+            b'^' => self.binary(|a, b, scale| {
+                let exp = b.to_i64().unwrap_or(0);
+                Ok(vec![a.pow(exp, scale).ok_or(Error::DivideByZero)?])
+            })?,
+            b'v' => {
+                let n = self.pop_num()?;
+                match n.sqrt(self.scale) {
+                    Some(root) => self.stack.push(Value::Num(root)),
+                    None => {
+                        self.stack.push(Value::Num(n));
+                        return Err(Error::NegativeRoot);
+                    }
+                }
+            },
+            b'p' => {
+                let value = self.top()?.clone();
+                let _ = self.write(&value).and_then(|_| self.out.write_all(b"\n"));
+            },
+            b'n' => {
+                let value = self.pop()?;
+                let _ = self.write(&value);
+            },
+            b'P' => {
+                let bytes = match self.pop()? {
+                    Value::Str(s) => s.to_vec(),
+                    Value::Num(n) => {
+                        // The integer part as a string of bytes.
+                        let base = Number::from_i64(256);
+                        let mut n = n.rescale(0);
+                        let mut bytes = Vec::new();
+                        while !n.is_zero() {
+                            bytes.push(n.rem(&base, 0).unwrap().to_i64().unwrap_or(0).unsigned_abs() as u8);
+                            n = n.div(&base, 0).unwrap();
+                        }
+                        bytes.reverse();
+                        bytes
+                    },
+                };
+                let _ = self.out.write_all(&bytes);
+            },
+            b'f' => {
+                for value in self.stack.clone().iter().rev() {
+                    let _ = self.write(value).and_then(|_| self.out.write_all(b"\n"));
+                }
+            },
+            b'c' => self.stack.clear(),
+            b'd' => {
+                let value = self.top()?.clone();
+                self.stack.push(value);
+            },
+            b'r' => {
+                let len = self.stack.len();
+                if len < 2 {
+                    return Err(Error::StackEmpty);
+                }
+                self.stack.swap(len - 1, len - 2);
+            },
+            b'z' => self.stack.push(Value::Num(Number::from_i64(self.stack.len() as i64))),
+            b'Z' => {
+                let len = match self.pop()? {
+                    Value::Num(n) => n.length(),
+                    Value::Str(s) => s.len(),
+                };
+                self.stack.push(Value::Num(Number::from_i64(len as i64)));
+            },
+            b'X' => {
+                let scale = match self.pop()? {
+                    Value::Num(n) => n.scale(),
+                    Value::Str(_) => 0,
+                };
+                self.stack.push(Value::Num(Number::from_i64(scale as i64)));
+            },
+            b'k' => {
+                let n = self.pop_num()?;
+                self.scale = n.to_i64().and_then(|n| usize::try_from(n).ok()).ok_or(Error::Scale)?;
+            },
+            b'K' => self.stack.push(Value::Num(Number::from_i64(self.scale as i64))),
+            b'i' => {
+                let n = self.pop_num()?;
+                self.ibase = n.to_i64().filter(|n| (2..=16).contains(n)).ok_or(Error::InputBase)? as u32;
+            },
+            b'I' => self.stack.push(Value::Num(Number::from_i64(self.ibase as i64))),
+            b'o' => {
+                let n = self.pop_num()?;
+                self.obase = n.to_i64().filter(|&n| n >= 2 && n <= u32::MAX as i64).ok_or(Error::OutputBase)? as u32;
+            },
+            b'O' => self.stack.push(Value::Num(Number::from_i64(self.obase as i64))),
+            b's' => {
+                let r = self.name()?;
+                let value = self.pop()?;
+                self.register(r).value = Some(value);
+            },
+            b'l' => {
+                let r = self.name()?;
+                let value = self.register(r).value.clone().unwrap_or(Value::Num(Number::zero()));
+                self.stack.push(value);
+            },
+            b'S' => {
+                let r = self.name()?;
+                let value = self.pop()?;
+                self.registers.entry(r).or_default().push(Register { value: Some(value), array: HashMap::new() });
+            },
+            b'L' => {
+                let r = self.name()?;
+                let register = self.registers.get_mut(&r).and_then(Vec::pop).ok_or(Error::RegisterEmpty(r))?;
+                self.stack.push(register.value.unwrap_or(Value::Num(Number::zero())));
+            },
+            b':' => {
+                let r = self.name()?;
+                let i = self.index()?;
+                let value = self.pop()?;
+                self.register(r).array.insert(i, value);
+            },
+            b';' => {
+                let r = self.name()?;
+                let i = self.index()?;
+                let value = self.register(r).array.get(&i).cloned().unwrap_or(Value::Num(Number::zero()));
+                self.stack.push(value);
+            },
+            b'x' => {
+                let value = self.pop()?;
+                self.execute_value(value);
+            },
+            b'<' | b'>' | b'=' => self.compare(c, false)?,
+            b'!' => match self.peek() {
+                Some(c @ (b'<' | b'>' | b'=')) => {
+                    self.next();
+                    self.compare(c, true)?;
+                },
+                _ => {
+                    let mut command = Vec::new();
+                    while let Some(c) = self.next() {
+                        if c == b'\n' {
+                            break;
+                        }
+                        command.push(c);
+                    }
+                    let _ = self.out.flush();
+                    let command = String::from_utf8_lossy(&command).into_owned();
+                    let _ = process::Command::new("sh").arg("-c").arg(command).status();
+                },
+            },
+            b'?' => {
+                if self.read_line() {
+                    let line = std::mem::take(&mut self.line);
+                    self.execute(line.into());
+                }
+            },
+            b'q' => {
+                if self.frames.len() <= 1 {
+                    return Ok(false);
+                }
+                self.frames.truncate(self.frames.len() - 2);
+            },
+            b'Q' => {
+                let n = self.index()?;
+                self.frames.truncate(self.frames.len().saturating_sub(n));
+            },
+            _ => return Err(Error::Unimplemented(c)),
+        }
+        Ok(true)
+    }
+
+    /// Compares the top two numbers, popping them, and executes a register
+    /// if the top one is less than, greater than or equal to the other (or,
+    /// if negated, isn't).
+    fn compare(&mut self, op: u8, negate: bool) -> Result<(), Error> {
+        let r = self.name()?;
+        let len = self.stack.len();
+        if len < 2 {
+            return Err(Error::StackEmpty);
+        }
+        let ordering = match (&self.stack[len - 1], &self.stack[len - 2]) {
+            (Value::Num(a), Value::Num(b)) => a.cmp(b),
+            _ => return Err(Error::NonNumeric),
+        };
+        self.stack.truncate(len - 2);
+        let holds = match op {
+            b'<' => ordering.is_lt(),
+            b'>' => ordering.is_gt(),
+            _ => ordering.is_eq(),
+        };
+        if holds != negate {
+            if let Some(value) = self.register(r).value.clone() {
+                self.execute_value(value);
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs commands until the input ends or one quits.
+    fn run(&mut self) {
+        loop {
+            while self.frames.last().is_some_and(|frame| frame.pos >= frame.code.len()) {
+                self.frames.pop();
+            }
+            let c = match self.next() {
+                Some(c) => c,
+                None => break,
+            };
+            match self.command(c) {
+                Ok(true) => (),
+                Ok(false) => break,
+                Err(e) => {
+                    let _ = self.out.flush();
+                    err!("{}", e);
+                },
+            }
+        }
+        let _ = self.out.flush();
+    }
+}
+
+pub const SPEC: Spec = Spec::new("desk calculator", &[], "[file]");
+
+pub fn run(args: Vec<OsString>) -> i32 {
+    let mut inputs: Vec<Box<dyn BufRead>> = Vec::new();
+    for file in SPEC.parse(args).operands {
+        match Input::open(&file) {
+            Ok(input) => inputs.push(Box::new(input)),
+            Err(e) => {
+                err!("{}: {}", file.to_string_lossy(), e);
+                process::exit(status::FAILURE);
+            }
+        }
+    }
+
+    // Any files are read before the standard input.
+    inputs.push(Box::new(BufReader::new(io::stdin())));
+    let mut dc = Dc::new(io::BufWriter::new(io::stdout()), inputs);
+    dc.run();
+    status::SUCCESS
+}
+
+#[test]
+fn test_dc() {
+    let run = |program: &str| {
+        let input: Box<dyn BufRead> = Box::new(io::Cursor::new(program.as_bytes().to_vec()));
+        let mut dc = Dc::new(Vec::new(), vec![input]);
+        dc.run();
+        String::from_utf8(dc.out).unwrap()
+    };
+    assert_eq!(run("2 3+p 10 4-p 6 7*p 7 2/p 7 2%p 2 10^p"), "5\n6\n42\n3\n1\n1024\n");
+    assert_eq!(run("5k 1 3/p _2 p 2v p c 3k 10 3~ f"), ".33333\n-2\n1.41421\n.001\n3.333\n");
+    assert_eq!(run("16o 255p 2o 5p 16i FF p"), "FF\n101\n11111111\n");
+    assert_eq!(run("[hello]p [[nested]] p sa la p lax"), "hello\n[nested]\n[nested]\n");
+    // A loop that counts down, which must not nest as it goes.
+    assert_eq!(run("[d p 1 - d 0 <a]sa 3 lax"), "3\n2\n1\n");
+    assert_eq!(run("[li 1+ si li 100000 >b]sb 0si lbx lip"), "100000\n");
+    assert_eq!(run("1 sa 2 Sa 3 Sa la p La p La p la p"), "3\n3\n2\n1\n");
+    assert_eq!(run("42 7:a 7;a p 8;a p z p"), "42\n0\n2\n");
+    assert_eq!(run("1 2 3 z p [q]x 4 p"), "3\n");
+    assert_eq!(run("[[1p 2Q 2p]x 3p]x 4p"), "1\n4\n");
+    assert_eq!(run("1 0/ f"), "0\n1\n");
+    assert_eq!(run("123.456 Z p X p [abc] Z p"), "6\n0\n3\n");
+    assert_eq!(run("65 P [b]P 10 n"), "Ab10");
+    assert_eq!(run("2 1 !<a [3p]sa 1 2 !<a"), "3\n");
+}
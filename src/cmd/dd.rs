@@ -0,0 +1,591 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// An implementation of the dd(1) command in Rust.
+// See http://man.cat-v.org/unix-7th/1/dd
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::os::fd::AsFd;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::cmd::Spec;
+use crate::copy;
+use crate::err;
+use crate::size;
+use crate::status;
+
+/// How often status=progress reports are made.
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Set by the signal handler when a status report has been requested.
+static REPORT: AtomicBool = AtomicBool::new(false);
+
+const EBCDIC_TO_ASCII: [u8; 256] = [
+    0o000, 0o001, 0o002, 0o003, 0o234, 0o011, 0o206, 0o177,
+    0o227, 0o215, 0o216, 0o013, 0o014, 0o015, 0o016, 0o017,
+    0o020, 0o021, 0o022, 0o023, 0o235, 0o205, 0o010, 0o207,
+    0o030, 0o031, 0o222, 0o217, 0o034, 0o035, 0o036, 0o037,
+    0o200, 0o201, 0o202, 0o203, 0o204, 0o012, 0o027, 0o033,
+    0o210, 0o211, 0o212, 0o213, 0o214, 0o005, 0o006, 0o007,
+    0o220, 0o221, 0o026, 0o223, 0o224, 0o225, 0o226, 0o004,
+    0o230, 0o231, 0o232, 0o233, 0o024, 0o025, 0o236, 0o032,
+    0o040, 0o240, 0o241, 0o242, 0o243, 0o244, 0o245, 0o246,
+    0o247, 0o250, 0o325, 0o056, 0o074, 0o050, 0o053, 0o174,
+    0o046, 0o251, 0o252, 0o253, 0o254, 0o255, 0o256, 0o257,
+    0o260, 0o261, 0o041, 0o044, 0o052, 0o051, 0o073, 0o176,
+    0o055, 0o057, 0o262, 0o263, 0o264, 0o265, 0o266, 0o267,
+    0o270, 0o271, 0o313, 0o054, 0o045, 0o137, 0o076, 0o077,
+    0o272, 0o273, 0o274, 0o275, 0o276, 0o277, 0o300, 0o301,
+    0o302, 0o140, 0o072, 0o043, 0o100, 0o047, 0o075, 0o042,
+    0o303, 0o141, 0o142, 0o143, 0o144, 0o145, 0o146, 0o147,
+    0o150, 0o151, 0o304, 0o305, 0o306, 0o307, 0o310, 0o311,
+    0o312, 0o152, 0o153, 0o154, 0o155, 0o156, 0o157, 0o160,
+    0o161, 0o162, 0o136, 0o314, 0o315, 0o316, 0o317, 0o320,
+    0o321, 0o345, 0o163, 0o164, 0o165, 0o166, 0o167, 0o170,
+    0o171, 0o172, 0o322, 0o323, 0o324, 0o133, 0o326, 0o327,
+    0o330, 0o331, 0o332, 0o333, 0o334, 0o335, 0o336, 0o337,
+    0o340, 0o341, 0o342, 0o343, 0o344, 0o135, 0o346, 0o347,
+    0o173, 0o101, 0o102, 0o103, 0o104, 0o105, 0o106, 0o107,
+    0o110, 0o111, 0o350, 0o351, 0o352, 0o353, 0o354, 0o355,
+    0o175, 0o112, 0o113, 0o114, 0o115, 0o116, 0o117, 0o120,
+    0o121, 0o122, 0o356, 0o357, 0o360, 0o361, 0o362, 0o363,
+    0o134, 0o237, 0o123, 0o124, 0o125, 0o126, 0o127, 0o130,
+    0o131, 0o132, 0o364, 0o365, 0o366, 0o367, 0o370, 0o371,
+    0o060, 0o061, 0o062, 0o063, 0o064, 0o065, 0o066, 0o067,
+    0o070, 0o071, 0o372, 0o373, 0o374, 0o375, 0o376, 0o377,
+];
+const ASCII_TO_EBCDIC: [u8; 256] = [
+    0o000, 0o001, 0o002, 0o003, 0o067, 0o055, 0o056, 0o057,
+    0o026, 0o005, 0o045, 0o013, 0o014, 0o015, 0o016, 0o017,
+    0o020, 0o021, 0o022, 0o023, 0o074, 0o075, 0o062, 0o046,
+    0o030, 0o031, 0o077, 0o047, 0o034, 0o035, 0o036, 0o037,
+    0o100, 0o132, 0o177, 0o173, 0o133, 0o154, 0o120, 0o175,
+    0o115, 0o135, 0o134, 0o116, 0o153, 0o140, 0o113, 0o141,
+    0o360, 0o361, 0o362, 0o363, 0o364, 0o365, 0o366, 0o367,
+    0o370, 0o371, 0o172, 0o136, 0o114, 0o176, 0o156, 0o157,
+    0o174, 0o301, 0o302, 0o303, 0o304, 0o305, 0o306, 0o307,
+    0o310, 0o311, 0o321, 0o322, 0o323, 0o324, 0o325, 0o326,
+    0o327, 0o330, 0o331, 0o342, 0o343, 0o344, 0o345, 0o346,
+    0o347, 0o350, 0o351, 0o255, 0o340, 0o275, 0o232, 0o155,
+    0o171, 0o201, 0o202, 0o203, 0o204, 0o205, 0o206, 0o207,
+    0o210, 0o211, 0o221, 0o222, 0o223, 0o224, 0o225, 0o226,
+    0o227, 0o230, 0o231, 0o242, 0o243, 0o244, 0o245, 0o246,
+    0o247, 0o250, 0o251, 0o300, 0o117, 0o320, 0o137, 0o007,
+    0o040, 0o041, 0o042, 0o043, 0o044, 0o025, 0o006, 0o027,
+    0o050, 0o051, 0o052, 0o053, 0o054, 0o011, 0o012, 0o033,
+    0o060, 0o061, 0o032, 0o063, 0o064, 0o065, 0o066, 0o010,
+    0o070, 0o071, 0o072, 0o073, 0o004, 0o024, 0o076, 0o341,
+    0o101, 0o102, 0o103, 0o104, 0o105, 0o106, 0o107, 0o110,
+    0o111, 0o121, 0o122, 0o123, 0o124, 0o125, 0o126, 0o127,
+    0o130, 0o131, 0o142, 0o143, 0o144, 0o145, 0o146, 0o147,
+    0o150, 0o151, 0o160, 0o161, 0o162, 0o163, 0o164, 0o165,
+    0o166, 0o167, 0o170, 0o200, 0o212, 0o213, 0o214, 0o215,
+    0o216, 0o217, 0o220, 0o152, 0o233, 0o234, 0o235, 0o236,
+    0o237, 0o240, 0o252, 0o253, 0o254, 0o112, 0o256, 0o257,
+    0o260, 0o261, 0o262, 0o263, 0o264, 0o265, 0o266, 0o267,
+    0o270, 0o271, 0o272, 0o273, 0o274, 0o241, 0o276, 0o277,
+    0o312, 0o313, 0o314, 0o315, 0o316, 0o317, 0o332, 0o333,
+    0o334, 0o335, 0o336, 0o337, 0o352, 0o353, 0o354, 0o355,
+    0o356, 0o357, 0o372, 0o373, 0o374, 0o375, 0o376, 0o377,
+];
+const ASCII_TO_IBM: [u8; 256] = [
+    0o000, 0o001, 0o002, 0o003, 0o067, 0o055, 0o056, 0o057,
+    0o026, 0o005, 0o045, 0o013, 0o014, 0o015, 0o016, 0o017,
+    0o020, 0o021, 0o022, 0o023, 0o074, 0o075, 0o062, 0o046,
+    0o030, 0o031, 0o077, 0o047, 0o034, 0o035, 0o036, 0o037,
+    0o100, 0o132, 0o177, 0o173, 0o133, 0o154, 0o120, 0o175,
+    0o115, 0o135, 0o134, 0o116, 0o153, 0o140, 0o113, 0o141,
+    0o360, 0o361, 0o362, 0o363, 0o364, 0o365, 0o366, 0o367,
+    0o370, 0o371, 0o172, 0o136, 0o114, 0o176, 0o156, 0o157,
+    0o174, 0o301, 0o302, 0o303, 0o304, 0o305, 0o306, 0o307,
+    0o310, 0o311, 0o321, 0o322, 0o323, 0o324, 0o325, 0o326,
+    0o327, 0o330, 0o331, 0o342, 0o343, 0o344, 0o345, 0o346,
+    0o347, 0o350, 0o351, 0o255, 0o340, 0o275, 0o137, 0o155,
+    0o171, 0o201, 0o202, 0o203, 0o204, 0o205, 0o206, 0o207,
+    0o210, 0o211, 0o221, 0o222, 0o223, 0o224, 0o225, 0o226,
+    0o227, 0o230, 0o231, 0o242, 0o243, 0o244, 0o245, 0o246,
+    0o247, 0o250, 0o251, 0o300, 0o117, 0o320, 0o241, 0o007,
+    0o040, 0o041, 0o042, 0o043, 0o044, 0o025, 0o006, 0o027,
+    0o050, 0o051, 0o052, 0o053, 0o054, 0o011, 0o012, 0o033,
+    0o060, 0o061, 0o032, 0o063, 0o064, 0o065, 0o066, 0o010,
+    0o070, 0o071, 0o072, 0o073, 0o004, 0o024, 0o076, 0o341,
+    0o101, 0o102, 0o103, 0o104, 0o105, 0o106, 0o107, 0o110,
+    0o111, 0o121, 0o122, 0o123, 0o124, 0o125, 0o126, 0o127,
+    0o130, 0o131, 0o142, 0o143, 0o144, 0o145, 0o146, 0o147,
+    0o150, 0o151, 0o160, 0o161, 0o162, 0o163, 0o164, 0o165,
+    0o166, 0o167, 0o170, 0o200, 0o212, 0o213, 0o214, 0o215,
+    0o216, 0o217, 0o220, 0o232, 0o233, 0o234, 0o235, 0o236,
+    0o237, 0o240, 0o252, 0o253, 0o254, 0o255, 0o256, 0o257,
+    0o260, 0o261, 0o262, 0o263, 0o264, 0o265, 0o266, 0o267,
+    0o270, 0o271, 0o272, 0o273, 0o274, 0o275, 0o276, 0o277,
+    0o312, 0o313, 0o314, 0o315, 0o316, 0o317, 0o332, 0o333,
+    0o334, 0o335, 0o336, 0o337, 0o352, 0o353, 0o354, 0o355,
+    0o356, 0o357, 0o372, 0o373, 0o374, 0o375, 0o376, 0o377,
+];
+
+/// The character set conversions.
+#[derive(Clone, Copy)]
+enum Charset {
+    /// EBCDIC to ASCII.
+    Ascii,
+    /// ASCII to EBCDIC.
+    Ebcdic,
+    /// ASCII to the IBM variant of EBCDIC.
+    Ibm,
+}
+
+/// The conversions selected by conv=.
+#[derive(Default)]
+struct Conv {
+    charset: Option<Charset>,
+    lcase: bool,
+    ucase: bool,
+    swab: bool,
+    sync: bool,
+    noerror: bool,
+    notrunc: bool,
+}
+
+impl Conv {
+    /// Returns the translation to apply to each byte, if any.
+    fn table(&self) -> Option<[u8; 256]> {
+        if self.charset.is_none() && !self.lcase && !self.ucase {
+            return None;
+        }
+        let case = |c: u8| {
+            if self.lcase {
+                c.to_ascii_lowercase()
+            } else if self.ucase {
+                c.to_ascii_uppercase()
+            } else {
+                c
+            }
+        };
+        // Case is converted in ASCII: after converting from EBCDIC, or
+        // before converting to it.
+        let mut table = [0; 256];
+        for (i, t) in table.iter_mut().enumerate() {
+            *t = match self.charset {
+                Some(Charset::Ascii) => case(EBCDIC_TO_ASCII[i]),
+                Some(Charset::Ebcdic) => ASCII_TO_EBCDIC[case(i as u8) as usize],
+                Some(Charset::Ibm) => ASCII_TO_IBM[case(i as u8) as usize],
+                None => case(i as u8),
+            };
+        }
+        Some(table)
+    }
+
+    /// Returns true if the data is changed, as opposed to how it is read
+    /// or written.
+    fn transforms(&self) -> bool {
+        self.charset.is_some() || self.lcase || self.ucase || self.swab
+    }
+}
+
+/// What is reported on standard error.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Status {
+    /// The record counts and transfer statistics.
+    Default,
+    /// Nothing but errors.
+    None,
+    /// The record counts only.
+    NoXfer,
+    /// The default, plus the transfer statistics every second.
+    Progress,
+}
+
+/// The operands controlling the copy.
+struct Options {
+    ifile: Option<OsString>,
+    ofile: Option<OsString>,
+    ibs: usize,
+    obs: usize,
+    /// Set by bs=, in which case each input block is written as it is
+    /// read, rather than being reblocked.
+    bs: Option<usize>,
+    count: Option<u64>,
+    skip: u64,
+    seek: u64,
+    conv: Conv,
+    status: Status,
+}
+
+/// The numbers of full and partial blocks read and written.
+#[derive(Default)]
+struct Stats {
+    in_full: u64,
+    in_partial: u64,
+    out_full: u64,
+    out_partial: u64,
+    /// The number of bytes written.
+    bytes: u64,
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}+{} records in", self.in_full, self.in_partial)?;
+        writeln!(f, "{}+{} records out", self.out_full, self.out_partial)
+    }
+}
+
+fn parse_size(key: &str, val: &str) -> Result<u64, String> {
+    size::parse_product(val).ok_or_else(|| format!("{}={}: invalid number", key, val))
+}
+
+fn parse_block_size(key: &str, val: &str) -> Result<usize, String> {
+    match parse_size(key, val)? {
+        0 => Err(format!("{}={}: invalid block size", key, val)),
+        n => usize::try_from(n).map_err(|_| format!("{}={}: block size too large", key, val)),
+    }
+}
+
+fn parse_conv(val: &str, conv: &mut Conv) -> Result<(), String> {
+    for name in val.split(',') {
+        match name {
+            "ascii" => conv.charset = Some(Charset::Ascii),
+            "ebcdic" => conv.charset = Some(Charset::Ebcdic),
+            "ibm" => conv.charset = Some(Charset::Ibm),
+            "lcase" => conv.lcase = true,
+            "ucase" => conv.ucase = true,
+            "swab" => conv.swab = true,
+            "sync" => conv.sync = true,
+            "noerror" => conv.noerror = true,
+            "notrunc" => conv.notrunc = true,
+            _ => return Err(format!("conv={}: unknown conversion", name)),
+        }
+    }
+    if conv.lcase && conv.ucase {
+        return Err("conv=lcase and conv=ucase are mutually exclusive".to_string());
+    }
+    Ok(())
+}
+
+fn parse_options<I: Iterator<Item = OsString>>(args: I) -> Result<Options, String> {
+    let mut opts = Options {
+        ifile: None,
+        ofile: None,
+        ibs: 512,
+        obs: 512,
+        bs: None,
+        count: None,
+        skip: 0,
+        seek: 0,
+        conv: Conv::default(),
+        status: Status::Default,
+    };
+    for arg in args {
+        // The names of the files, unlike the other values, needn't be
+        // valid UTF-8.
+        if let Some(name) = arg.as_bytes().strip_prefix(b"if=") {
+            opts.ifile = Some(OsString::from_vec(name.to_vec()));
+            continue;
+        }
+        if let Some(name) = arg.as_bytes().strip_prefix(b"of=") {
+            opts.ofile = Some(OsString::from_vec(name.to_vec()));
+            continue;
+        }
+        let arg = arg.into_string().map_err(|arg| format!("{}: unknown operand", arg.to_string_lossy()))?;
+        let (key, val) = arg.split_once('=').ok_or_else(|| format!("{}: unknown operand", arg))?;
+        match key {
+            "ibs" => opts.ibs = parse_block_size(key, val)?,
+            "obs" => opts.obs = parse_block_size(key, val)?,
+            "bs" => opts.bs = Some(parse_block_size(key, val)?),
+            "count" => opts.count = Some(parse_size(key, val)?),
+            "skip" => opts.skip = parse_size(key, val)?,
+            "seek" => opts.seek = parse_size(key, val)?,
+            "conv" => parse_conv(val, &mut opts.conv)?,
+            "status" => {
+                opts.status = match val {
+                    "none" => Status::None,
+                    "noxfer" => Status::NoXfer,
+                    "progress" => Status::Progress,
+                    _ => return Err(format!("{}: unknown status", val)),
+                }
+            },
+            _ => return Err(format!("{}: unknown operand", arg)),
+        }
+    }
+    if let Some(bs) = opts.bs {
+        opts.ibs = bs;
+        opts.obs = bs;
+        // Blocks are only written as read when the data isn't converted.
+        if opts.conv.transforms() {
+            opts.bs = None;
+        }
+    }
+    check_offset("skip", opts.skip, opts.ibs)?;
+    check_offset("seek", opts.seek, opts.obs)?;
+    Ok(opts)
+}
+
+/// Checks that the offset of the given number of blocks can be seeked to,
+/// rather than letting it wrap.
+fn check_offset(key: &str, blocks: u64, size: usize) -> Result<(), String> {
+    match blocks.checked_mul(size as u64).and_then(|n| i64::try_from(n).ok()) {
+        Some(_) => Ok(()),
+        None => Err(format!("{}={}: offset too large", key, blocks)),
+    }
+}
+
+/// Reads once into the buffer, retrying if interrupted.
+fn read_block(input: &mut File, buf: &mut [u8]) -> io::Result<usize> {
+    loop {
+        match input.read(buf) {
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            result => return result,
+        }
+    }
+}
+
+/// Adds the file name to an error opening a file.
+fn open_error(name: &OsStr, e: io::Error) -> io::Error {
+    io::Error::new(e.kind(), format!("{}: {}", name.to_string_lossy(), e))
+}
+
+extern "C" fn on_signal(_sig: libc::c_int) {
+    REPORT.store(true, Ordering::SeqCst);
+}
+
+/// Arranges for a status report when SIGUSR1 (or, where there is one,
+/// SIGINFO) is received.
+fn report_on_signal() {
+    let handler = on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    // SAFETY: the handler only stores to an atomic.
+    unsafe {
+        libc::signal(libc::SIGUSR1, handler);
+        #[cfg(any(target_os = "macos", target_os = "freebsd", target_os = "netbsd",
+                  target_os = "openbsd", target_os = "dragonfly"))]
+        libc::signal(libc::SIGINFO, handler);
+    }
+}
+
+struct Dd {
+    opts: Options,
+    stats: Stats,
+    start: Instant,
+    /// When progress was last reported.
+    progress: Instant,
+    /// Whether a progress report has been made, and so needs finishing
+    /// with a newline.
+    progress_shown: bool,
+    /// The translation selected by conv=, if any.
+    table: Option<[u8; 256]>,
+}
+
+impl Dd {
+    /// Returns the transfer statistics: bytes written, time taken and
+    /// the rate.
+    fn transfer(&self) -> String {
+        let secs = self.start.elapsed().as_secs_f64();
+        let rate = if secs > 0.0 { self.stats.bytes as f64 / secs } else { 0.0 };
+        format!("{} bytes transferred in {:.3} secs ({:.0} bytes/sec)",
+                self.stats.bytes, secs, rate)
+    }
+
+    /// Reports the records read and written so far, as is done on
+    /// completion and when requested by a signal.
+    fn report(&self) {
+        match self.opts.status {
+            Status::None => (),
+            Status::NoXfer => eprint!("{}", self.stats),
+            Status::Default | Status::Progress => {
+                eprint!("{}", self.stats);
+                eprintln!("{}", self.transfer());
+            },
+        }
+    }
+
+    /// Makes any reports that are due.
+    fn check_reports(&mut self) {
+        if REPORT.swap(false, Ordering::SeqCst) {
+            self.report();
+        }
+        if self.opts.status == Status::Progress && self.progress.elapsed() >= PROGRESS_INTERVAL {
+            eprint!("{}\r", self.transfer());
+            self.progress = Instant::now();
+            self.progress_shown = true;
+        }
+    }
+
+    fn open_input(&self) -> io::Result<File> {
+        match &self.opts.ifile {
+            Some(name) => File::open(name).map_err(|e| open_error(name, e)),
+            None => Ok(File::from(io::stdin().as_fd().try_clone_to_owned()?)),
+        }
+    }
+
+    /// Opens the output, truncating a regular file at the point where
+    /// output starts.
+    fn open_output(&self) -> io::Result<File> {
+        match &self.opts.ofile {
+            Some(name) => {
+                let file = OpenOptions::new().write(true).create(true).truncate(false)
+                    .open(name).map_err(|e| open_error(name, e))?;
+                if !self.opts.conv.notrunc && file.metadata()?.is_file() {
+                    file.set_len(self.opts.seek * self.opts.obs as u64)?;
+                }
+                Ok(file)
+            },
+            None => Ok(File::from(io::stdout().as_fd().try_clone_to_owned()?)),
+        }
+    }
+
+    /// Skips the given number of input blocks, by seeking if possible and
+    /// otherwise by reading them.
+    fn skip(&mut self, input: &mut File, buf: &mut [u8]) -> io::Result<()> {
+        let skip = self.opts.skip;
+        if skip == 0 || input.seek(SeekFrom::Current((skip * self.opts.ibs as u64) as i64)).is_ok() {
+            return Ok(());
+        }
+        for _ in 0..skip {
+            if read_block(input, buf)? == 0 {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_block(&mut self, output: &mut File, block: &[u8]) -> io::Result<()> {
+        output.write_all(block)?;
+        self.stats.bytes += block.len() as u64;
+        if block.len() == self.opts.obs {
+            self.stats.out_full += 1;
+        } else {
+            self.stats.out_partial += 1;
+        }
+        Ok(())
+    }
+
+    /// Returns whether the input can be copied as it is, by copy::copy,
+    /// rather than a block at a time: whether it is a regular file, which
+    /// reads in whole blocks until the last, to be copied to its end
+    /// without being changed, and without progress being reported. Its
+    /// records can then be counted from the bytes copied.
+    fn direct(&self, input: &File) -> io::Result<bool> {
+        let conv = &self.opts.conv;
+        Ok(self.table.is_none() && !conv.swab && !conv.sync && !conv.noerror
+           && self.opts.count.is_none() && self.opts.status != Status::Progress
+           && input.metadata()?.is_file())
+    }
+
+    /// Counts the records of a direct copy of the bytes.
+    fn count_direct(&mut self, bytes: u64) {
+        let (ibs, obs) = (self.opts.ibs as u64, self.opts.obs as u64);
+        self.stats.in_full += bytes / ibs;
+        self.stats.in_partial += u64::from(!bytes.is_multiple_of(ibs));
+        self.stats.out_full += bytes / obs;
+        self.stats.out_partial += u64::from(!bytes.is_multiple_of(obs));
+        self.stats.bytes += bytes;
+    }
+
+    fn copy(&mut self) -> io::Result<()> {
+        let mut input = self.open_input()?;
+        let mut output = self.open_output()?;
+        let mut ibuf = vec![0; self.opts.ibs];
+        self.skip(&mut input, &mut ibuf)?;
+        if self.opts.seek > 0 {
+            output.seek(SeekFrom::Start(self.opts.seek * self.opts.obs as u64))?;
+        }
+        if self.direct(&input)? {
+            let n = copy::copy(input.as_fd(), output.as_fd())?;
+            self.count_direct(n);
+            return output.flush();
+        }
+
+        let obs = self.opts.obs;
+        let mut obuf: Vec<u8> = Vec::with_capacity(obs);
+        while self.opts.count.is_none_or(|count| self.stats.in_full + self.stats.in_partial < count) {
+            self.check_reports();
+            let n = match read_block(&mut input, &mut ibuf) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(e) if self.opts.conv.noerror => {
+                    // Report the error and carry on after the bad block,
+                    // which sync replaces with NULs.
+                    err!("{}", e);
+                    self.report();
+                    let _ = input.seek(SeekFrom::Current(self.opts.ibs as i64));
+                    0
+                },
+                Err(e) => return Err(e),
+            };
+            if n == self.opts.ibs {
+                self.stats.in_full += 1;
+            } else {
+                self.stats.in_partial += 1;
+            }
+            let mut len = n;
+            if self.opts.conv.sync && n < self.opts.ibs {
+                ibuf[n..].fill(0);
+                len = self.opts.ibs;
+            }
+            let block = &mut ibuf[..len];
+            if self.opts.conv.swab {
+                for pair in block.chunks_exact_mut(2) {
+                    pair.swap(0, 1);
+                }
+            }
+            if let Some(table) = &self.table {
+                for c in block.iter_mut() {
+                    *c = table[*c as usize];
+                }
+            }
+            if self.opts.bs.is_some() {
+                self.write_block(&mut output, &ibuf[..len])?;
+                continue;
+            }
+            // Reblock: write out as many full output blocks as have been
+            // gathered, keeping the rest for later.
+            obuf.extend_from_slice(block);
+            let full = obuf.len() / obs * obs;
+            for i in (0..full).step_by(obs) {
+                self.write_block(&mut output, &obuf[i..i + obs])?;
+            }
+            obuf.drain(..full);
+        }
+        if !obuf.is_empty() {
+            self.write_block(&mut output, &obuf)?;
+        }
+        output.flush()
+    }
+}
+
+pub const SPEC: Spec = Spec::new("convert and copy a file", &[], "[operand=value ...]");
+
+pub fn run(args: Vec<OsString>) -> i32 {
+    let args = args.into_iter().skip(1);
+    let opts = match parse_options(args) {
+        Ok(opts) => opts,
+        Err(e) => {
+            err!("{}", e);
+            process::exit(status::FAILURE);
+        }
+    };
+
+    report_on_signal();
+    let table = opts.conv.table();
+    let mut dd = Dd {
+        opts,
+        stats: Stats::default(),
+        start: Instant::now(),
+        progress: Instant::now(),
+        progress_shown: false,
+        table,
+    };
+    let result = dd.copy();
+    if dd.progress_shown {
+        eprintln!();
+    }
+    if let Err(e) = &result {
+        err!("{}", e);
+    }
+    dd.report();
+    if result.is_ok() { status::SUCCESS } else { status::FAILURE }
+}
@@ -0,0 +1,711 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// An implementation of the diff(1) command in Rust.
+// See http://man.cat-v.org/unix-7th/1/diff
+use std::collections::{BTreeSet, HashMap};
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Read, Write};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use crate::cmd::{Opt, Spec};
+use crate::err;
+use crate::input::Input;
+use crate::time;
+use crate::status;
+use crate::time::Tm;
+
+/// The exit status when the files are the same.
+const SAME: i32 = status::TRUE;
+/// The exit status when the files differ.
+const DIFFERENT: i32 = status::FALSE;
+
+/// How much of the start of a file is checked for NULs when deciding
+/// whether it is binary.
+const BINARY_CHECK: usize = 8192;
+
+/// How whitespace is treated when comparing lines.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Blanks {
+    /// Whitespace is significant.
+    Exact,
+    /// Changes in the amount of whitespace are ignored (-b).
+    Amount,
+    /// All whitespace is ignored (-w).
+    Ignore,
+}
+
+/// Returns the line as it is to be compared. A missing newline at the
+/// end of the file only matters when whitespace is significant.
+fn canonical(line: &[u8], blanks: Blanks) -> Vec<u8> {
+    let text = line.strip_suffix(b"\n").unwrap_or(line);
+    match blanks {
+        Blanks::Exact => line.to_vec(),
+        Blanks::Ignore => text.iter().copied().filter(|c| !c.is_ascii_whitespace()).collect(),
+        Blanks::Amount => {
+            let mut key = Vec::with_capacity(line.len());
+            let mut space = false;
+            for &c in text {
+                if c.is_ascii_whitespace() {
+                    space = true;
+                } else {
+                    if space && !key.is_empty() {
+                        key.push(b' ');
+                    }
+                    space = false;
+                    key.push(c);
+                }
+            }
+            // A leading run of whitespace counts, a trailing one doesn't.
+            if text.first().is_some_and(|c| c.is_ascii_whitespace()) && !key.is_empty() {
+                key.insert(0, b' ');
+            }
+            key
+        },
+    }
+}
+
+/// Splits the data into lines, each including its newline (the last line
+/// may not have one).
+fn split_lines(data: &[u8]) -> Vec<&[u8]> {
+    data.split_inclusive(|&c| c == b'\n').collect()
+}
+
+/// Finds the lines of a that have to be deleted and those of b that have
+/// to be inserted to turn a into b, using Myers' O(ND) algorithm in its
+/// linear space, divide and conquer form.
+struct Myers<'a> {
+    a: &'a [usize],
+    b: &'a [usize],
+    /// Whether each line of a is deleted.
+    a_changed: Vec<bool>,
+    /// Whether each line of b is inserted.
+    b_changed: Vec<bool>,
+}
+
+impl<'a> Myers<'a> {
+    fn new(a: &'a [usize], b: &'a [usize]) -> Myers<'a> {
+        let mut myers = Myers {
+            a,
+            b,
+            a_changed: vec![false; a.len()],
+            b_changed: vec![false; b.len()],
+        };
+        myers.compare(0, a.len(), 0, b.len());
+        myers
+    }
+
+    /// Compares a[a0..a1] with b[b0..b1].
+    fn compare(&mut self, mut a0: usize, mut a1: usize, mut b0: usize, mut b1: usize) {
+        while a0 < a1 && b0 < b1 && self.a[a0] == self.b[b0] {
+            a0 += 1;
+            b0 += 1;
+        }
+        while a0 < a1 && b0 < b1 && self.a[a1 - 1] == self.b[b1 - 1] {
+            a1 -= 1;
+            b1 -= 1;
+        }
+        if a0 == a1 || b0 == b1 {
+            self.a_changed[a0..a1].fill(true);
+            self.b_changed[b0..b1].fill(true);
+            return;
+        }
+        match self.middle_snake(a0, a1, b0, b1) {
+            Some((x, y)) => {
+                self.compare(a0, x, b0, y);
+                self.compare(x, a1, y, b1);
+            },
+            None => {
+                self.a_changed[a0..a1].fill(true);
+                self.b_changed[b0..b1].fill(true);
+            },
+        }
+    }
+
+    /// Searches forwards from the start and backwards from the end at
+    /// the same time until the paths meet, returning the point at which
+    /// they do, which lies on an optimal path. Returns None if the ranges
+    /// have nothing in common.
+    fn middle_snake(&self, a0: usize, a1: usize, b0: usize, b1: usize) -> Option<(usize, usize)> {
+        let (a, b) = (&self.a[a0..a1], &self.b[b0..b1]);
+        let (n, m) = (a.len() as isize, b.len() as isize);
+        let max_d = (n + m + 1) / 2;
+        let offset = max_d;
+        let len = 2 * max_d + 2;
+        // The furthest x reached on each diagonal k (indexed by k +
+        // offset) going forwards, and going backwards from the end.
+        let mut vf = vec![-1isize; len as usize];
+        let mut vb = vec![-1isize; len as usize];
+        vf[offset as usize + 1] = 0;
+        vb[offset as usize + 1] = 0;
+        let delta = n - m;
+        // If delta is odd, the paths meet during a forward step.
+        let front = delta % 2 != 0;
+        // Diagonals that have run off the edges are no longer explored.
+        let (mut kf_start, mut kf_end, mut kb_start, mut kb_end) = (0, 0, 0, 0);
+        for d in 0..max_d {
+            let mut k = -d + kf_start;
+            while k <= d - kf_end {
+                let i = (offset + k) as usize;
+                let mut x = if k == -d || (k != d && vf[i - 1] < vf[i + 1]) {
+                    vf[i + 1]
+                } else {
+                    vf[i - 1] + 1
+                };
+                let mut y = x - k;
+                while x < n && y < m && a[x as usize] == b[y as usize] {
+                    x += 1;
+                    y += 1;
+                }
+                vf[i] = x;
+                if x > n {
+                    kf_end += 2;
+                } else if y > m {
+                    kf_start += 2;
+                } else if front {
+                    let j = offset + delta - k;
+                    if j >= 0 && j < len && vb[j as usize] != -1 && x >= n - vb[j as usize] {
+                        return Some((a0 + x as usize, b0 + y as usize));
+                    }
+                }
+                k += 2;
+            }
+            let mut k = -d + kb_start;
+            while k <= d - kb_end {
+                let i = (offset + k) as usize;
+                let mut x = if k == -d || (k != d && vb[i - 1] < vb[i + 1]) {
+                    vb[i + 1]
+                } else {
+                    vb[i - 1] + 1
+                };
+                let mut y = x - k;
+                while x < n && y < m && a[(n - x - 1) as usize] == b[(m - y - 1) as usize] {
+                    x += 1;
+                    y += 1;
+                }
+                vb[i] = x;
+                if x > n {
+                    kb_end += 2;
+                } else if y > m {
+                    kb_start += 2;
+                } else if !front {
+                    let j = offset + delta - k;
+                    if j >= 0 && j < len && vf[j as usize] != -1 {
+                        let xf = vf[j as usize];
+                        let yf = offset + xf - j;
+                        if xf >= n - x {
+                            return Some((a0 + xf as usize, b0 + yf as usize));
+                        }
+                    }
+                }
+                k += 2;
+            }
+        }
+        None
+    }
+}
+
+/// A run of deleted lines of the first file, a[a0..a1], and the lines of
+/// the second file, b[b0..b1], inserted in their place. Either may be
+/// empty.
+struct Hunk {
+    a0: usize,
+    a1: usize,
+    b0: usize,
+    b1: usize,
+}
+
+/// Groups the changed lines into hunks.
+fn hunks(a_changed: &[bool], b_changed: &[bool]) -> Vec<Hunk> {
+    let mut hunks = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a_changed.len() || j < b_changed.len() {
+        if i < a_changed.len() && j < b_changed.len() && !a_changed[i] && !b_changed[j] {
+            i += 1;
+            j += 1;
+            continue;
+        }
+        let (a0, b0) = (i, j);
+        while i < a_changed.len() && a_changed[i] {
+            i += 1;
+        }
+        while j < b_changed.len() && b_changed[j] {
+            j += 1;
+        }
+        hunks.push(Hunk { a0, a1: i, b0, b1: j });
+    }
+    hunks
+}
+
+/// Formats a range of lines, numbered from 1, as used by the normal
+/// format: "N" for a single line, otherwise "N,M". An empty range is
+/// given as the number of the line before it.
+fn range(from: usize, to: usize) -> String {
+    if to <= from + 1 {
+        to.to_string()
+    } else {
+        format!("{},{}", from + 1, to)
+    }
+}
+
+/// Writes a line preceded by the given prefix, noting if it lacks a
+/// newline.
+fn write_line(out: &mut dyn Write, prefix: &str, line: &[u8]) -> io::Result<()> {
+    out.write_all(prefix.as_bytes())?;
+    out.write_all(line)?;
+    if !line.ends_with(b"\n") {
+        out.write_all(b"\n\\ No newline at end of file\n")?;
+    }
+    Ok(())
+}
+
+/// Formats a range of lines for a unified diff hunk header: the first
+/// line and the number of lines, the latter omitted if it is 1. An empty
+/// range is given as the line before it and 0.
+fn unified_range(from: usize, to: usize) -> String {
+    match to - from {
+        0 => format!("{},0", from),
+        1 => format!("{}", to),
+        n => format!("{},{}", from + 1, n),
+    }
+}
+
+/// Splits the hunks into groups that are close enough together that
+/// their context overlaps or abuts.
+fn groups(hunks: &[Hunk], context: usize) -> Vec<&[Hunk]> {
+    let mut groups = Vec::new();
+    let mut start = 0;
+    for i in 1..=hunks.len() {
+        if i == hunks.len() || hunks[i].a0 - hunks[i - 1].a1 > 2 * context {
+            groups.push(&hunks[start..i]);
+            start = i;
+        }
+    }
+    groups
+}
+
+/// Reads the whole of the named file, "-" being standard input.
+fn read_file(name: &Path) -> io::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    let result = if name.as_os_str() == "-" {
+        Input::open("-").and_then(|mut reader| reader.read_to_end(&mut data)).map(|_| ())
+    } else {
+        File::open(name).and_then(|mut file| file.read_to_end(&mut data)).map(|_| ())
+    };
+    result.map(|_| data).map_err(|e| io::Error::new(e.kind(), format!("{}: {}", name.display(), e)))
+}
+
+/// Returns the modification time of the named file, or the current time
+/// for standard input, as seconds and nanoseconds since the epoch.
+fn modified(name: &Path) -> (i64, u32) {
+    if name.as_os_str() == "-" {
+        return time::now();
+    }
+    match fs::metadata(name) {
+        Ok(meta) => (meta.mtime(), meta.mtime_nsec() as u32),
+        Err(_) => time::now(),
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Normal,
+    /// The context format, with the given number of lines of context.
+    Context(usize),
+    /// The unified format, with the given number of lines of context.
+    Unified(usize),
+}
+
+struct Diff {
+    blanks: Blanks,
+    format: Format,
+    /// Only report whether files differ (-q).
+    brief: bool,
+    /// Compare subdirectories too (-r).
+    recursive: bool,
+    /// The options as given, for the line that introduces the differences
+    /// between each pair of files found when comparing directories.
+    options: Vec<String>,
+}
+
+impl Diff {
+    /// Compares the two files, writing the differences. Returns true if
+    /// they differ. When comparing directories, the differences are
+    /// introduced with a line naming the files.
+    fn diff_files(&self, names: [&Path; 2], in_dir: bool, out: &mut dyn Write)
+                  -> io::Result<bool> {
+        let data = [read_file(names[0])?, read_file(names[1])?];
+        if data[0] == data[1] {
+            return Ok(false);
+        }
+        let (x, y) = (names[0].display(), names[1].display());
+        if self.brief {
+            writeln!(out, "Files {} and {} differ", x, y)?;
+            return Ok(true);
+        }
+        let binary = |data: &[u8]| data[..data.len().min(BINARY_CHECK)].contains(&0);
+        if binary(&data[0]) || binary(&data[1]) {
+            writeln!(out, "Binary files {} and {} differ", x, y)?;
+            return Ok(true);
+        }
+
+        let a = split_lines(&data[0]);
+        let b = split_lines(&data[1]);
+        // Number the distinct lines, so that lines are compared as
+        // integers.
+        let mut ids: HashMap<Vec<u8>, usize> = HashMap::new();
+        let mut number = |lines: &[&[u8]]| -> Vec<usize> {
+            lines.iter().map(|line| {
+                let next = ids.len();
+                *ids.entry(canonical(line, self.blanks)).or_insert(next)
+            }).collect()
+        };
+        let a_ids = number(&a);
+        let b_ids = number(&b);
+        let myers = Myers::new(&a_ids, &b_ids);
+        let hunks = hunks(&myers.a_changed, &myers.b_changed);
+        if hunks.is_empty() {
+            return Ok(false);
+        }
+
+        if in_dir {
+            let mut line = vec!["diff".to_string()];
+            line.extend(self.options.iter().cloned());
+            writeln!(out, "{} {} {}", line.join(" "), x, y)?;
+        }
+        match self.format {
+            Format::Normal => {
+                for hunk in &hunks {
+                    self.normal(hunk, &a, &b, out)?;
+                }
+            },
+            Format::Context(context) => {
+                for (mark, name) in ["***", "---"].iter().zip(names) {
+                    let (secs, _) = modified(name);
+                    writeln!(out, "{} {}\t{}", mark, name.display(), Tm::local(secs).ctime())?;
+                }
+                for group in groups(&hunks, context) {
+                    self.context(group, context, &a, &b, out)?;
+                }
+            },
+            Format::Unified(context) => {
+                for (mark, name) in ["---", "+++"].iter().zip(names) {
+                    let (secs, nsecs) = modified(name);
+                    let tm = Tm::local(secs);
+                    writeln!(out, "{} {}\t{:04}-{:02}-{:02} {:02}:{:02}:{:02}.{:09} {}",
+                             mark, name.display(), tm.year, tm.month, tm.day, tm.hour,
+                             tm.min, tm.sec, nsecs, tm.numeric_zone())?;
+                }
+                for group in groups(&hunks, context) {
+                    self.unified(group, context, &a, &b, out)?;
+                }
+            },
+        }
+        Ok(true)
+    }
+
+    /// Writes a hunk in the normal format.
+    fn normal(&self, hunk: &Hunk, a: &[&[u8]], b: &[&[u8]], out: &mut dyn Write)
+              -> io::Result<()> {
+        let op = match (hunk.a0 < hunk.a1, hunk.b0 < hunk.b1) {
+            (true, true) => 'c',
+            (true, false) => 'd',
+            _ => 'a',
+        };
+        writeln!(out, "{}{}{}", range(hunk.a0, hunk.a1), op, range(hunk.b0, hunk.b1))?;
+        for line in &a[hunk.a0..hunk.a1] {
+            write_line(out, "< ", line)?;
+        }
+        if op == 'c' {
+            writeln!(out, "---")?;
+        }
+        for line in &b[hunk.b0..hunk.b1] {
+            write_line(out, "> ", line)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the ranges of lines of the two files covered by a group of
+    /// hunks and their context.
+    fn extent(group: &[Hunk], context: usize, a_len: usize) -> (usize, usize, usize, usize) {
+        let (first, last) = (&group[0], &group[group.len() - 1]);
+        let before = context.min(first.a0);
+        let after = context.min(a_len - last.a1);
+        (first.a0 - before, last.a1 + after, first.b0 - before, last.b1 + after)
+    }
+
+    /// Writes a group of hunks in the unified format.
+    fn unified(&self, group: &[Hunk], context: usize, a: &[&[u8]], b: &[&[u8]],
+               out: &mut dyn Write) -> io::Result<()> {
+        let (a0, a1, b0, b1) = Diff::extent(group, context, a.len());
+        writeln!(out, "@@ -{} +{} @@", unified_range(a0, a1), unified_range(b0, b1))?;
+        let mut i = a0;
+        for hunk in group {
+            for line in &a[i..hunk.a0] {
+                write_line(out, " ", line)?;
+            }
+            for line in &a[hunk.a0..hunk.a1] {
+                write_line(out, "-", line)?;
+            }
+            for line in &b[hunk.b0..hunk.b1] {
+                write_line(out, "+", line)?;
+            }
+            i = hunk.a1;
+        }
+        for line in &a[i..a1] {
+            write_line(out, " ", line)?;
+        }
+        Ok(())
+    }
+
+    /// Writes a group of hunks in the context format. Lines that are
+    /// changed are marked with !, those only deleted with - and those
+    /// only inserted with +. A file's lines are omitted if none of them
+    /// change.
+    fn context(&self, group: &[Hunk], context: usize, a: &[&[u8]], b: &[&[u8]],
+               out: &mut dyn Write) -> io::Result<()> {
+        let (a0, a1, b0, b1) = Diff::extent(group, context, a.len());
+        writeln!(out, "***************")?;
+        writeln!(out, "*** {} ****", range(a0, a1))?;
+        if group.iter().any(|hunk| hunk.a0 < hunk.a1) {
+            let mut i = a0;
+            for hunk in group {
+                for line in &a[i..hunk.a0] {
+                    write_line(out, "  ", line)?;
+                }
+                let mark = if hunk.b0 < hunk.b1 { "! " } else { "- " };
+                for line in &a[hunk.a0..hunk.a1] {
+                    write_line(out, mark, line)?;
+                }
+                i = hunk.a1;
+            }
+            for line in &a[i..a1] {
+                write_line(out, "  ", line)?;
+            }
+        }
+        writeln!(out, "--- {} ----", range(b0, b1))?;
+        if group.iter().any(|hunk| hunk.b0 < hunk.b1) {
+            let mut j = b0;
+            for hunk in group {
+                for line in &b[j..hunk.b0] {
+                    write_line(out, "  ", line)?;
+                }
+                let mark = if hunk.a0 < hunk.a1 { "! " } else { "+ " };
+                for line in &b[hunk.b0..hunk.b1] {
+                    write_line(out, mark, line)?;
+                }
+                j = hunk.b1;
+            }
+            for line in &b[j..b1] {
+                write_line(out, "  ", line)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Reports an error, returning the corresponding exit status.
+    fn trouble(&self, out: &mut dyn Write, e: &dyn fmt::Display) -> i32 {
+        let _ = out.flush();
+        err!("{}", e);
+        status::TROUBLE
+    }
+
+    /// Compares the two directories, returning the exit status.
+    fn diff_dirs(&self, dirs: [&Path; 2], out: &mut dyn Write) -> i32 {
+        let mut names: [BTreeSet<OsString>; 2] = [BTreeSet::new(), BTreeSet::new()];
+        for (dir, names) in dirs.iter().zip(names.iter_mut()) {
+            let entries = match fs::read_dir(dir) {
+                Ok(entries) => entries,
+                Err(e) => return self.trouble(out, &format!("{}: {}", dir.display(), e)),
+            };
+            for entry in entries {
+                match entry {
+                    Ok(entry) => {
+                        names.insert(entry.file_name());
+                    },
+                    Err(e) => return self.trouble(out, &format!("{}: {}", dir.display(), e)),
+                }
+            }
+        }
+
+        let mut status = SAME;
+        for name in names[0].union(&names[1]) {
+            let result = if !names[1].contains(name) {
+                writeln!(out, "Only in {}: {}", dirs[0].display(), name.to_string_lossy())
+                    .map(|_| DIFFERENT)
+            } else if !names[0].contains(name) {
+                writeln!(out, "Only in {}: {}", dirs[1].display(), name.to_string_lossy())
+                    .map(|_| DIFFERENT)
+            } else {
+                let paths = [dirs[0].join(name), dirs[1].join(name)];
+                self.diff_entries([&paths[0], &paths[1]], out)
+            };
+            status = status.max(match result {
+                Ok(status) => status,
+                Err(e) => self.trouble(out, &e),
+            });
+        }
+        status
+    }
+
+    /// Compares two files of the same name found in the directories being
+    /// compared, returning the exit status.
+    fn diff_entries(&self, paths: [&Path; 2], out: &mut dyn Write) -> io::Result<i32> {
+        let is_dir = [paths[0].is_dir(), paths[1].is_dir()];
+        let (x, y) = (paths[0].display(), paths[1].display());
+        match is_dir {
+            [true, true] if self.recursive => Ok(self.diff_dirs(paths, out)),
+            [true, true] => {
+                writeln!(out, "Common subdirectories: {} and {}", x, y)?;
+                Ok(SAME)
+            },
+            [true, false] => {
+                writeln!(out, "File {} is a directory while file {} is a regular file", x, y)?;
+                Ok(DIFFERENT)
+            },
+            [false, true] => {
+                writeln!(out, "File {} is a regular file while file {} is a directory", x, y)?;
+                Ok(DIFFERENT)
+            },
+            [false, false] => {
+                let differ = self.diff_files(paths, true, out)?;
+                Ok(if differ { DIFFERENT } else { SAME })
+            },
+        }
+    }
+
+    /// Compares the files or directories named on the command line,
+    /// returning the exit status. A file compared with a directory is
+    /// compared with the file of the same name in the directory.
+    fn diff(&self, names: [&OsStr; 2], out: &mut dyn Write) -> i32 {
+        let is_dir = |name: &OsStr| name != "-" && Path::new(name).is_dir();
+        let mut paths = [PathBuf::from(names[0]), PathBuf::from(names[1])];
+        match (is_dir(names[0]), is_dir(names[1])) {
+            (true, true) => return self.diff_dirs([&paths[0], &paths[1]], out),
+            (true, false) | (false, true) if names.iter().any(|&name| name == "-") => {
+                return self.trouble(out, &"cannot compare - to a directory");
+            },
+            (true, false) => {
+                let base = paths[1].file_name().unwrap_or_default().to_os_string();
+                paths[0].push(base);
+            },
+            (false, true) => {
+                let base = paths[0].file_name().unwrap_or_default().to_os_string();
+                paths[1].push(base);
+            },
+            (false, false) => (),
+        }
+        match self.diff_files([&paths[0], &paths[1]], false, out) {
+            Ok(true) => DIFFERENT,
+            Ok(false) => SAME,
+            Err(e) => self.trouble(out, &e),
+        }
+    }
+}
+
+fn parse_context(arg: &str) -> usize {
+    match arg.parse() {
+        Ok(n) => n,
+        Err(e) => {
+            err!("{}: {}", arg, e);
+            process::exit(status::TROUBLE);
+        }
+    }
+}
+
+pub const SPEC: Spec = Spec::new("differential file comparator", &[
+    Opt::flag('b', "ignore changes in the amount of white space"),
+    Opt::flag('c', "produce a context diff"),
+    Opt::flag('q', "report only whether the files differ"),
+    Opt::flag('r', "compare directories recursively"),
+    Opt::flag('u', "produce a unified diff"),
+    Opt::flag('w', "ignore all white space"),
+    Opt::arg('C', "n", "produce a context diff with n lines of context"),
+    Opt::arg('U', "n", "produce a unified diff with n lines of context"),
+], "file1 file2");
+
+pub fn run(args: Vec<OsString>) -> i32 {
+    let parsed = SPEC.parse(args);
+    let mut diff = Diff {
+        blanks: Blanks::Exact,
+        format: Format::Normal,
+        brief: false,
+        recursive: false,
+        options: Vec::new(),
+    };
+
+    for (c, arg) in parsed.opts() {
+        match (c, arg) {
+            ('b', _) => {
+                if diff.blanks == Blanks::Exact {
+                    diff.blanks = Blanks::Amount;
+                }
+                diff.options.push("-b".to_string());
+            },
+            ('w', _) => {
+                diff.blanks = Blanks::Ignore;
+                diff.options.push("-w".to_string());
+            },
+            ('c', _) => {
+                diff.format = Format::Context(3);
+                diff.options.push("-c".to_string());
+            },
+            ('u', _) => {
+                diff.format = Format::Unified(3);
+                diff.options.push("-u".to_string());
+            },
+            ('q', _) => {
+                diff.brief = true;
+                diff.options.push("-q".to_string());
+            },
+            ('r', _) => {
+                diff.recursive = true;
+                diff.options.push("-r".to_string());
+            },
+            ('C', Some(arg)) => {
+                diff.format = Format::Context(parse_context(arg));
+                diff.options.push(format!("-C {}", arg));
+            },
+            ('U', Some(arg)) => {
+                diff.format = Format::Unified(parse_context(arg));
+                diff.options.push(format!("-U {}", arg));
+            },
+            _ => (),
+        }
+    }
+
+    let names = parsed.operands;
+    if names.len() != 2 {
+        SPEC.usage();
+    }
+
+    let mut out = BufWriter::new(io::stdout());
+    let mut status = diff.diff([&names[0], &names[1]], &mut out);
+    if let Err(e) = out.flush() {
+        err!("{}", e);
+        status = status::TROUBLE;
+    }
+    status
+}
+
+#[test]
+fn test_myers() {
+    let lines = |s: &str| -> Vec<usize> { s.bytes().map(|c| c as usize).collect() };
+    for (a, b, d) in [("abcabba", "cbabac", 5), ("", "abc", 3), ("abc", "abc", 0),
+                      ("abcd", "xyz", 7), ("xaxbx", "ab", 3)] {
+        let (a, b) = (lines(a), lines(b));
+        let myers = Myers::new(&a, &b);
+        let changed = myers.a_changed.iter().chain(&myers.b_changed).filter(|&&c| c).count();
+        assert_eq!(changed, d);
+        // The unchanged lines must be the same in both.
+        let kept_a: Vec<usize> = a.iter().zip(&myers.a_changed).filter(|p| !p.1).map(|p| *p.0).collect();
+        let kept_b: Vec<usize> = b.iter().zip(&myers.b_changed).filter(|p| !p.1).map(|p| *p.0).collect();
+        assert_eq!(kept_a, kept_b);
+    }
+}
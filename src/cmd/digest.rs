@@ -0,0 +1,209 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// An implementation of a digest command in Rust, which when invoked as
+// md5sum, sha1sum, sha256sum or sha512sum computes that digest. None of
+// these are Seventh Edition commands; md5 first appeared in 4.4BSD, and
+// the *sum commands come from GNU coreutils.
+// See https://man.freebsd.org/cgi/man.cgi?query=md5&sektion=1
+use std::ffi::{OsStr, OsString};
+use std::io;
+use std::io::{BufRead, Write};
+use std::path::Path;
+use std::process;
+
+use crate::cmd::{Opt, Spec};
+use crate::digest;
+use crate::digest::Algorithm;
+use crate::err;
+use crate::input::Input;
+use crate::status;
+use crate::warn;
+
+/// The algorithm used when none is given.
+const DEFAULT: Algorithm = Algorithm::Sha256;
+
+/// Returns the algorithm named by the program name, as in sha1sum.
+fn algorithm_for(prog: &str) -> Option<Algorithm> {
+    let name = Path::new(prog).file_name()?.to_str()?;
+    Algorithm::from_name(name.strip_suffix("sum")?)
+}
+
+/// Returns the digest of the named file, or of the standard input if the
+/// name is "-".
+fn digest_file(name: &OsStr, alg: Algorithm) -> io::Result<Vec<u8>> {
+    let mut input = Input::open(name)?;
+    let mut digest = digest::Digest::new(alg);
+    io::copy(&mut input, &mut digest)?;
+    Ok(digest.value())
+}
+
+/// Parses a line of a list of digests: the digest in hexadecimal, a space
+/// and then a space, or a '*' for a file read in binary mode, before the
+/// name. Returns the digest, in lower case, and the name.
+fn parse_line(line: &str) -> Option<(String, &str)> {
+    let (hex, rest) = line.split_once(' ')?;
+    let name = rest.strip_prefix(' ').or_else(|| rest.strip_prefix('*'))?;
+    if hex.is_empty() || !hex.bytes().all(|c| c.is_ascii_hexdigit()) || name.is_empty() {
+        return None;
+    }
+    Some((hex.to_ascii_lowercase(), name))
+}
+
+/// What is reported when checking a list of digests.
+#[derive(Clone, Copy, PartialEq)]
+enum Report {
+    /// Every file, OK or not.
+    All,
+    /// Only the files that fail (-q).
+    Failures,
+    /// Nothing, only the exit status says whether all were OK (-s).
+    Nothing,
+}
+
+/// Checks the files in a list of digests, as produced by this command,
+/// against their digests. Without an algorithm, each line's is chosen by
+/// the length of its digest. Returns whether all the files were OK.
+fn check(list: &OsStr, alg: Option<Algorithm>, report: Report) -> io::Result<bool> {
+    let input = io::BufReader::new(Input::open(list)?);
+    let mut checked = 0;
+    let mut failed = 0;
+    let mut unreadable = 0;
+    let mut malformed = 0;
+    for line in input.lines() {
+        let line = line?;
+        let parsed = parse_line(&line).and_then(|(hex, name)| {
+            let found = alg.or_else(|| {
+                digest::ALGORITHMS.iter().map(|&(a, _)| a).find(|a| 2 * a.size() == hex.len())
+            })?;
+            (2 * found.size() == hex.len()).then_some((hex, name, found))
+        });
+        let Some((hex, name, found)) = parsed else {
+            malformed += 1;
+            continue;
+        };
+        checked += 1;
+        match digest_file(OsStr::new(name), found) {
+            Ok(value) if digest::hex(&value) == hex => {
+                if report == Report::All {
+                    println!("{}: OK", name);
+                }
+            },
+            Ok(_) => {
+                failed += 1;
+                if report != Report::Nothing {
+                    println!("{}: FAILED", name);
+                }
+            },
+            Err(e) => {
+                unreadable += 1;
+                if report != Report::Nothing {
+                    err!("{}: {}", name, e);
+                    println!("{}: FAILED open or read", name);
+                }
+            },
+        }
+    }
+
+    if checked == 0 {
+        err!("{}: no properly formatted digest lines found", list.to_string_lossy());
+        return Ok(false);
+    }
+    if report != Report::Nothing {
+        let plural = |n: i32, one: &'static str, many: &'static str| if n == 1 { one } else { many };
+        if malformed > 0 {
+            warn!("WARNING: {} {} improperly formatted", malformed,
+                 plural(malformed, "line is", "lines are"));
+        }
+        if unreadable > 0 {
+            warn!("WARNING: {} listed {} could not be read", unreadable,
+                 plural(unreadable, "file", "files"));
+        }
+        if failed > 0 {
+            warn!("WARNING: {} computed {} did NOT match", failed,
+                 plural(failed, "digest", "digests"));
+        }
+    }
+    Ok(failed == 0 && unreadable == 0)
+}
+
+pub const SPEC: Spec = Spec::new("compute and check message digests", &[
+    Opt::arg('a', "algorithm", "use the algorithm: md5, sha1, sha256 or sha512"),
+    Opt::flag('c', "check the digests listed in the files"),
+    Opt::flag('q', "when checking, report only failures"),
+    Opt::flag('s', "when checking, report nothing; only return the exit status"),
+], "[file ...]");
+
+pub fn run(args: Vec<OsString>) -> i32 {
+    // md5sum and the like are digest with the algorithm in the name.
+    let mut alg = algorithm_for(&args[0].to_string_lossy());
+    let parsed = SPEC.parse(args);
+    let checking = parsed.flag('c');
+    let mut report = Report::All;
+
+    for (c, arg) in parsed.opts() {
+        match (c, arg) {
+            ('a', Some(arg)) => match Algorithm::from_name(arg) {
+                Some(a) => alg = Some(a),
+                None => {
+                    let names: Vec<_> = digest::ALGORITHMS.iter().map(|(_, name)| *name).collect();
+                    err!("{}: unknown algorithm; use one of {}", arg, names.join(", "));
+                    process::exit(status::USAGE);
+                }
+            },
+            ('q', _) => report = Report::Failures,
+            ('s', _) => report = Report::Nothing,
+            _ => (),
+        }
+    }
+
+    if report != Report::All && !checking {
+        SPEC.usage();
+    }
+    let mut files = parsed.operands;
+    if files.is_empty() {
+        files.push(OsString::from("-"));
+    }
+
+    let mut status = status::SUCCESS;
+    for file in &files {
+        if checking {
+            match check(file, alg, report) {
+                Ok(true) => (),
+                Ok(false) => status = 1,
+                Err(e) => {
+                    err!("{}: {}", file.to_string_lossy(), e);
+                    status = status::FAILURE;
+                }
+            }
+        } else {
+            match digest_file(file, alg.unwrap_or(DEFAULT)) {
+                Ok(value) => {
+                    let line = [digest::hex(&value).as_bytes(), b"  ", file.as_encoded_bytes(), b"\n"].concat();
+                    if let Err(e) = io::stdout().write_all(&line) {
+                        err!("{}", e);
+                        process::exit(status::FAILURE);
+                    }
+                },
+                Err(e) => {
+                    err!("{}: {}", file.to_string_lossy(), e);
+                    status = status::FAILURE;
+                }
+            }
+        }
+    }
+    status
+}
+
+#[test]
+fn test_digest() {
+    assert_eq!(algorithm_for("/usr/bin/md5sum"), Some(Algorithm::Md5));
+    assert_eq!(algorithm_for("sha512sum"), Some(Algorithm::Sha512));
+    assert_eq!(algorithm_for("digest"), None);
+    assert_eq!(parse_line("D41D8cd98f00b204e9800998ecf8427e  a b"),
+               Some(("d41d8cd98f00b204e9800998ecf8427e".to_string(), "a b")));
+    assert_eq!(parse_line("0123 *bin"), Some(("0123".to_string(), "bin")));
+    assert_eq!(parse_line("0123 bin"), None);
+    assert_eq!(parse_line("xyz  file"), None);
+    assert_eq!(parse_line("0123  "), None);
+}
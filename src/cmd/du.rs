@@ -0,0 +1,163 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// An implementation of the du(1) command in Rust.
+// See http://man.cat-v.org/unix-7th/1/du
+use std::collections::HashSet;
+use std::ffi::{OsStr, OsString};
+use std::fs::Metadata;
+use std::io;
+use std::io::{BufWriter, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+use std::process;
+
+use crate::blocks::{BlockSize, STAT_BLOCK};
+use crate::cmd::{Opt, Spec};
+use crate::err;
+use crate::json::Record;
+use crate::status;
+use crate::walk::{Follow, Walk};
+
+struct Du {
+    /// Report on every file, not just directories.
+    all: bool,
+    /// Report only on the named files.
+    summary: bool,
+    /// Report files' apparent sizes, their lengths, rather than the space
+    /// they take, which for sparse files is less.
+    apparent: bool,
+    block_size: BlockSize,
+    follow: Follow,
+    /// Write JSON records instead of lines of text.
+    json: bool,
+    /// The byte that ends each line: a newline or, with -z, a NUL.
+    eol: u8,
+    /// The device and inode numbers of files with several links that have
+    /// already been counted.
+    seen: HashSet<(u64, u64)>,
+}
+
+impl Du {
+    /// Returns the size of a file in bytes: its length or the space it
+    /// takes.
+    fn size(&self, meta: &Metadata) -> u64 {
+        if self.apparent {
+            meta.len()
+        } else {
+            meta.blocks() * STAT_BLOCK
+        }
+    }
+
+    fn print(&self, out: &mut dyn Write, bytes: u64, path: &Path) -> io::Result<()> {
+        if self.json {
+            let record = Record::new().name("path", path)
+                .num("blocks", self.block_size.blocks(bytes)).num("bytes", bytes);
+            write!(out, "{}", record)?;
+        } else {
+            write!(out, "{}\t", self.block_size.format(bytes))?;
+            out.write_all(path.as_os_str().as_bytes())?;
+        }
+        out.write_all(&[self.eol])
+    }
+
+    /// Reports the usage of the named file, which, if it is a directory,
+    /// includes everything below it. Returns false if any errors occur.
+    fn du(&mut self, name: &OsStr, out: &mut dyn Write) -> io::Result<bool> {
+        let mut ok = true;
+        // The totals, in bytes, of the directories being traversed, indexed
+        // by depth.
+        let mut totals: Vec<u64> = vec![0];
+        for entry in Walk::new([name]).contents_first(true).follow(self.follow) {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    out.flush()?;
+                    err!("{}", e);
+                    ok = false;
+                    continue;
+                },
+            };
+            let meta = entry.metadata();
+            let depth = entry.depth();
+            if totals.len() < depth + 2 {
+                totals.resize(depth + 2, 0);
+            }
+            let bytes = if meta.is_dir() {
+                let bytes = self.size(meta) + totals[depth + 1];
+                totals[depth + 1] = 0;
+                if !self.summary || depth == 0 {
+                    self.print(out, bytes, entry.path())?;
+                }
+                bytes
+            } else {
+                // Files with several links are only counted, and only
+                // reported, the first time they are seen.
+                if meta.nlink() > 1 && !self.seen.insert((meta.dev(), meta.ino())) {
+                    continue;
+                }
+                if depth == 0 || (self.all && !self.summary) {
+                    self.print(out, self.size(meta), entry.path())?;
+                }
+                self.size(meta)
+            };
+            totals[depth] += bytes;
+        }
+        Ok(ok)
+    }
+}
+
+pub const SPEC: Spec = Spec::new("summarize disk usage", &[
+    Opt::flag('a', "report every file, not just directories"),
+    Opt::flag('A', "report apparent sizes rather than disk usage"),
+    Opt::flag('s', "report only the total for each operand"),
+    Opt::flag('k', "count in 1024-byte blocks"),
+    Opt::flag('h', "give sizes for people to read, such as 12K or 1.5M"),
+    Opt::flag('z', "end lines with NUL rather than newline"),
+    Opt::flag('H', "follow symbolic links named as operands"),
+    Opt::flag('L', "follow all symbolic links"),
+    Opt::flag('P', "follow no symbolic links (the default)"),
+], "[file ...]").json();
+
+pub fn run(args: Vec<OsString>) -> i32 {
+    let parsed = SPEC.parse(args);
+    let eol = if parsed.flag('z') { b'\0' } else { b'\n' };
+    let mut follow = Follow::Never;
+    for (c, _) in parsed.opts() {
+        follow.set(c);
+    }
+    let mut du = Du {
+        all: parsed.flag('a'),
+        summary: parsed.flag('s'),
+        apparent: parsed.flag('A'),
+        block_size: BlockSize::new(parsed.flag('k'), parsed.flag('h')),
+        follow,
+        json: parsed.json,
+        eol,
+        seen: HashSet::new(),
+    };
+    let mut names = parsed.operands;
+
+    if names.is_empty() {
+        names.push(OsString::from("."));
+    }
+
+    let mut out = BufWriter::new(io::stdout());
+    let mut status = status::SUCCESS;
+    for name in &names {
+        match du.du(name, &mut out) {
+            Ok(true) => (),
+            Ok(false) => status = 1,
+            Err(e) => {
+                err!("{}", e);
+                process::exit(status::FAILURE);
+            }
+        }
+    }
+    if let Err(e) = out.flush() {
+        err!("{}", e);
+        status = status::FAILURE;
+    }
+    status
+}
@@ -0,0 +1,92 @@
+// Copyright 2015-2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// An implementation of the echo(1) command in Rust.
+// See http://man.cat-v.org/unix-7th/1/echo
+use std::ffi::OsString;
+use std::io::Write;
+use std::os::unix::ffi::OsStrExt;
+
+use crate::cmd::{Opt, Spec};
+use crate::mode;
+use crate::platform;
+use crate::status;
+
+/// Appends the argument to the output with the escape sequences that
+/// POSIX echo recognises replaced. Returns false if the argument contains
+/// \c, which ends the output, newline and all.
+fn unescape(arg: &[u8], out: &mut Vec<u8>) -> bool {
+    let mut bytes = arg.iter().copied().peekable();
+    while let Some(b) = bytes.next() {
+        if b != b'\\' {
+            out.push(b);
+            continue;
+        }
+        match bytes.next() {
+            Some(b'a') => out.push(0x07),
+            Some(b'b') => out.push(0x08),
+            Some(b'c') => return false,
+            Some(b'f') => out.push(0x0c),
+            Some(b'n') => out.push(b'\n'),
+            Some(b'r') => out.push(b'\r'),
+            Some(b't') => out.push(b'\t'),
+            Some(b'v') => out.push(0x0b),
+            Some(b'\\') => out.push(b'\\'),
+            Some(b'0') => {
+                let mut n: u8 = 0;
+                for _ in 0..3 {
+                    match bytes.next_if(|d| (b'0'..=b'7').contains(d)) {
+                        Some(d) => n = n.wrapping_mul(8).wrapping_add(d - b'0'),
+                        None => break,
+                    }
+                }
+                out.push(n);
+            },
+            Some(b) => out.extend_from_slice(&[b'\\', b]),
+            None => out.push(b'\\'),
+        }
+    }
+    true
+}
+
+pub const SPEC: Spec = Spec::new("echo arguments", &[
+    Opt::flag('n', "print no newline"),
+], "[arg ...]").literal();
+
+pub fn run(args: Vec<OsString>) -> i32 {
+    // Only a first argument of -n is an option; any other argument,
+    // whether or not it starts with -, is echoed. POSIX echo also
+    // interprets escape sequences, as System V's did.
+    let mut args = args.into_iter().skip(1).peekable();
+    let mut newline = args.next_if(|arg| arg == "-n").is_none();
+    let posix = mode::posix();
+    let mut text = Vec::new();
+
+    for (i, arg) in args.enumerate() {
+	if i > 0 {
+	    text.push(b' ');
+	}
+	if !posix {
+	    text.extend_from_slice(arg.as_bytes());
+	} else if !unescape(arg.as_bytes(), &mut text) {
+	    newline = false;
+	    break;
+	}
+    }
+
+    if newline {
+	text.push(b'\n');
+    }
+    let _ = platform::stdout().write_all(&text);
+    status::SUCCESS
+}
+
+#[test]
+fn test_unescape() {
+    let mut out = Vec::new();
+    assert!(unescape(br"a\tb\\\0101\0\q", &mut out));
+    assert_eq!(out, b"a\tb\\A\0\\q");
+    out.clear();
+    assert!(!unescape(br"x\cy", &mut out));
+    assert_eq!(out, b"x");
+}
@@ -0,0 +1,1373 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// An implementation of the ed(1) command in Rust.
+// See http://man.cat-v.org/unix-7th/1/ed
+use std::collections::VecDeque;
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::fs::OpenOptions;
+use std::io;
+use std::io::{BufRead, BufWriter, Write};
+use std::mem;
+use std::os::unix::ffi::OsStringExt;
+use std::process;
+use std::process::Stdio;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::cmd::{Opt, Spec};
+use crate::err;
+use crate::regex::{Regex, Syntax};
+use crate::status;
+
+/// The width at which the l command folds long lines.
+const FOLD_WIDTH: usize = 72;
+
+/// Set when an interrupt is received while editing interactively.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn on_interrupt(_sig: libc::c_int) {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+}
+
+fn error<T>(msg: &str) -> Result<T, String> {
+    Err(msg.to_string())
+}
+
+/// A line of the buffer, without its newline. Lines are shared so that
+/// marks, and the lines selected by a global command, can find them
+/// wherever they move to.
+type Line = Rc<[u8]>;
+
+/// A change to the buffer: the lines removed from an index, and the
+/// number of lines inserted in their place.
+struct Change {
+    start: usize,
+    removed: Vec<Line>,
+    inserted: usize,
+}
+
+/// A piece of the replacement text of a substitution.
+enum Repl {
+    Literal(Vec<u8>),
+    Group(usize),
+}
+
+/// Parses the replacement text of a substitution, in which & stands for
+/// the matched text and \1 to \9 for the parenthesised subexpressions.
+fn replacement(raw: &[u8], groups: usize) -> Result<Vec<Repl>, String> {
+    let mut repl = Vec::new();
+    let mut lit = Vec::new();
+    let mut iter = raw.iter();
+    while let Some(&c) = iter.next() {
+        match c {
+            b'&' => {
+                if !lit.is_empty() {
+                    repl.push(Repl::Literal(mem::take(&mut lit)));
+                }
+                repl.push(Repl::Group(0));
+            },
+            b'\\' => match iter.next() {
+                Some(&d @ b'1'..=b'9') => {
+                    let n = (d - b'0') as usize;
+                    if n > groups {
+                        return error("invalid reference in replacement");
+                    }
+                    if !lit.is_empty() {
+                        repl.push(Repl::Literal(mem::take(&mut lit)));
+                    }
+                    repl.push(Repl::Group(n));
+                },
+                Some(&d) => lit.push(d),
+                None => lit.push(b'\\'),
+            },
+            _ => lit.push(c),
+        }
+    }
+    if !lit.is_empty() {
+        repl.push(Repl::Literal(lit));
+    }
+    Ok(repl)
+}
+
+/// Substitutes the replacement for the nth match in the line and, if
+/// global is true, for each match after it. Returns None if there is no
+/// such match.
+fn substitute(re: &Regex, repl: &[Repl], line: &[u8], global: bool, nth: usize)
+              -> Option<Vec<u8>> {
+    let mut result = Vec::new();
+    let mut copied = 0;
+    let mut pos = 0;
+    let mut count = 0;
+    let mut prev_end = None;
+    let mut replaced = false;
+    while pos <= line.len() {
+        let m = match re.find_at(line, pos) {
+            Some(m) => m,
+            None => break,
+        };
+        // An empty match adjacent to the previous match doesn't count.
+        if m.start() == m.end() && prev_end == Some(m.start()) {
+            pos = m.start() + 1;
+            continue;
+        }
+        count += 1;
+        if count >= nth {
+            result.extend_from_slice(&line[copied..m.start()]);
+            for piece in repl {
+                match piece {
+                    Repl::Literal(text) => result.extend_from_slice(text),
+                    Repl::Group(n) => if let Some((b, e)) = m.get(*n) {
+                        result.extend_from_slice(&line[b..e]);
+                    },
+                }
+            }
+            copied = m.end();
+            replaced = true;
+            if !global {
+                break;
+            }
+        }
+        prev_end = Some(m.end());
+        pos = if m.end() == m.start() { m.end() + 1 } else { m.end() };
+    }
+    if !replaced {
+        return None;
+    }
+    result.extend_from_slice(&line[copied..]);
+    Some(result)
+}
+
+/// Splits the command list of a global command into lines at the escaped
+/// newlines that join them.
+fn command_list(text: &[u8]) -> VecDeque<Vec<u8>> {
+    let mut list = VecDeque::from([Vec::new()]);
+    let mut iter = text.iter();
+    while let Some(&c) = iter.next() {
+        let last = list.back_mut().unwrap();
+        if c == b'\\' {
+            match iter.next() {
+                Some(b'\n') => list.push_back(Vec::new()),
+                Some(&d) => {
+                    last.push(c);
+                    last.push(d);
+                },
+                None => last.push(c),
+            }
+        } else {
+            last.push(c);
+        }
+    }
+    list
+}
+
+/// Returns the index of a mark given its letter.
+fn mark_index(c: Option<u8>) -> Result<usize, String> {
+    match c {
+        Some(c @ b'a'..=b'z') => Ok((c - b'a') as usize),
+        _ => error("invalid mark character"),
+    }
+}
+
+/// Runs a command with the shell.
+fn shell(cmd: &str) -> process::Command {
+    let mut command = process::Command::new("sh");
+    command.arg("-c").arg(cmd);
+    command
+}
+
+/// How to print a line: as it is, or unambiguously (l), and with or
+/// without its line number (n).
+#[derive(Clone, Copy, Default)]
+struct Style {
+    list: bool,
+    number: bool,
+}
+
+/// A command line being parsed.
+struct Parser<'a> {
+    text: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.text.get(self.pos).copied()
+    }
+
+    fn eat(&mut self, c: u8) -> bool {
+        let found = self.peek() == Some(c);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn skip_blanks(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t')) {
+            self.pos += 1;
+        }
+    }
+
+    fn number(&mut self) -> Result<Option<i64>, String> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        if self.pos == start {
+            return Ok(None);
+        }
+        match std::str::from_utf8(&self.text[start..self.pos]).unwrap().parse() {
+            Ok(n) => Ok(Some(n)),
+            Err(_) => error("number out of range"),
+        }
+    }
+
+    /// Returns the text up to the next unescaped delimiter, which may be
+    /// left out at the end of the line, and whether the delimiter was
+    /// found. An escaped delimiter is replaced by the delimiter itself;
+    /// all other escapes are kept. The delimiter may appear unescaped in a
+    /// bracket expression if the text is a regular expression.
+    fn delimited(&mut self, delim: u8, brackets: bool) -> (Vec<u8>, bool) {
+        let mut text = Vec::new();
+        while let Some(c) = self.peek() {
+            self.pos += 1;
+            if c == delim {
+                return (text, true);
+            } else if c == b'\\' {
+                match self.peek() {
+                    Some(d) if d == delim => text.push(d),
+                    Some(d) => {
+                        text.push(b'\\');
+                        text.push(d);
+                    },
+                    None => {
+                        text.push(b'\\');
+                        break;
+                    }
+                }
+                self.pos += 1;
+            } else if c == b'[' && brackets {
+                text.push(c);
+                self.bracket(&mut text);
+            } else {
+                text.push(c);
+            }
+        }
+        (text, false)
+    }
+
+    /// Copies the remainder of a bracket expression verbatim.
+    fn bracket(&mut self, text: &mut Vec<u8>) {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            self.pos += 1;
+            text.push(c);
+            let first = self.pos - start == 1
+                || (self.pos - start == 2 && self.text[start] == b'^');
+            if c == b']' && !first {
+                return;
+            }
+            if c == b'[' {
+                if let Some(k @ (b':' | b'.' | b'=')) = self.peek() {
+                    self.pos += 1;
+                    text.push(k);
+                    while let Some(d) = self.peek() {
+                        self.pos += 1;
+                        text.push(d);
+                        if d == k && self.peek() == Some(b']') {
+                            self.pos += 1;
+                            text.push(b']');
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Checks that nothing but blanks is left of the command.
+    fn end(&mut self) -> Result<(), String> {
+        self.skip_blanks();
+        if self.pos < self.text.len() {
+            return error("invalid command suffix");
+        }
+        Ok(())
+    }
+
+    /// Parses the suffixes p, l and n with which a command can ask for
+    /// the current line to be printed afterwards.
+    fn suffix(&mut self) -> Result<Option<Style>, String> {
+        let mut style: Option<Style> = None;
+        loop {
+            match self.peek() {
+                Some(b'p') => {
+                    style.get_or_insert_with(Style::default);
+                },
+                Some(b'l') => style.get_or_insert_with(Style::default).list = true,
+                Some(b'n') => style.get_or_insert_with(Style::default).number = true,
+                _ => break,
+            }
+            self.pos += 1;
+        }
+        self.end()?;
+        Ok(style)
+    }
+
+    /// Parses the file name given to e, f, r or w, which is separated
+    /// from the command by blanks.
+    fn filename(&mut self) -> Result<Option<OsString>, String> {
+        let start = self.pos;
+        self.skip_blanks();
+        if self.pos == self.text.len() {
+            return Ok(None);
+        }
+        if self.pos == start {
+            return error("invalid command suffix");
+        }
+        Ok(Some(OsString::from_vec(self.text[self.pos..].to_vec())))
+    }
+}
+
+struct Ed {
+    lines: Vec<Line>,
+    /// The number of the current line, which is 0 only when the buffer is
+    /// empty.
+    cur: usize,
+    /// The default file name.
+    file: Option<OsString>,
+    /// Whether the buffer has changed since it was last written.
+    modified: bool,
+    /// Whether the last command was refused because the buffer has been
+    /// modified, so that repeating it goes ahead.
+    warned: bool,
+    /// The lines marked with k, by letter.
+    marks: [Option<Line>; 26],
+    /// The changes made so far by the command being executed.
+    changes: Vec<Change>,
+    /// The changes made by the last command that changed the buffer, and
+    /// the line that was current before it, for the u command.
+    undo: Vec<Change>,
+    undo_cur: usize,
+    /// While a global command is being executed, the rest of its command
+    /// list for the current line, from which text is read too.
+    global: Option<VecDeque<Vec<u8>>>,
+    /// The last regular expression used.
+    last_re: Option<Rc<Regex>>,
+    /// The replacement text of the last substitution.
+    last_repl: Option<Vec<u8>>,
+    /// The last shell command run with !.
+    last_shell: Option<String>,
+    /// Whether to suppress byte counts.
+    silent: bool,
+    prompt: String,
+    prompting: bool,
+    /// Whether to explain errors as well as reporting them with "?".
+    explain: bool,
+    /// The explanation of the last error.
+    error: Option<String>,
+    input: Box<dyn BufRead>,
+    out: Box<dyn Write>,
+}
+
+impl Ed {
+    fn new(input: Box<dyn BufRead>, out: Box<dyn Write>) -> Ed {
+        Ed {
+            lines: Vec::new(),
+            cur: 0,
+            file: None,
+            modified: false,
+            warned: false,
+            marks: Default::default(),
+            changes: Vec::new(),
+            undo: Vec::new(),
+            undo_cur: 0,
+            global: None,
+            last_re: None,
+            last_repl: None,
+            last_shell: None,
+            silent: false,
+            prompt: "*".to_string(),
+            prompting: false,
+            explain: false,
+            error: None,
+            input,
+            out,
+        }
+    }
+
+    /// Reads a line of input, without its newline. Returns None at the end
+    /// of the input. An interrupt is reported as an error.
+    fn read_line(&mut self) -> io::Result<Option<Vec<u8>>> {
+        self.out.flush()?;
+        let mut line = Vec::new();
+        loop {
+            let buf = match self.input.fill_buf() {
+                Ok(buf) => buf,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {
+                    if INTERRUPTED.swap(false, Ordering::SeqCst) {
+                        return Err(e);
+                    }
+                    continue;
+                },
+                Err(e) => return Err(e),
+            };
+            if buf.is_empty() {
+                return Ok(if line.is_empty() { None } else { Some(line) });
+            }
+            match buf.iter().position(|&c| c == b'\n') {
+                Some(i) => {
+                    line.extend_from_slice(&buf[..i]);
+                    self.input.consume(i + 1);
+                    return Ok(Some(line));
+                },
+                None => {
+                    let n = buf.len();
+                    line.extend_from_slice(buf);
+                    self.input.consume(n);
+                }
+            }
+        }
+    }
+
+    /// Reads a command, which continues onto the next line if it ends
+    /// with an unescaped backslash. Returns None at the end of the input.
+    fn read_command(&mut self) -> io::Result<Option<Vec<u8>>> {
+        let mut line = match self.read_line()? {
+            Some(line) => line,
+            None => return Ok(None),
+        };
+        while line.iter().rev().take_while(|&&c| c == b'\\').count() % 2 == 1 {
+            match self.read_line()? {
+                Some(next) => {
+                    line.push(b'\n');
+                    line.extend(next);
+                },
+                None => break,
+            }
+        }
+        Ok(Some(line))
+    }
+
+    /// Reads lines of text, as for the a, c and i commands, up to a line
+    /// containing only a period. In a global command, the text is part of
+    /// the command list, and the period may be left out at its end.
+    fn read_text(&mut self) -> Result<Vec<Line>, String> {
+        let mut text = Vec::new();
+        loop {
+            let line = match &mut self.global {
+                Some(list) => Ok(list.pop_front()),
+                None => self.read_line(),
+            };
+            match line {
+                Ok(Some(line)) if line == b"." => break,
+                Ok(Some(line)) => text.push(Line::from(line)),
+                Ok(None) => break,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => return error("interrupt"),
+                Err(e) => return Err(e.to_string()),
+            }
+        }
+        Ok(text)
+    }
+
+    /// Compiles a regular expression, or reuses the last one if the text
+    /// is empty.
+    fn regex(&mut self, text: &[u8]) -> Result<Rc<Regex>, String> {
+        if text.is_empty() {
+            return match &self.last_re {
+                Some(re) => Ok(re.clone()),
+                None => error("no previous pattern"),
+            };
+        }
+        match Regex::with_syntax(text, Syntax::Basic, false) {
+            Ok(re) => {
+                let re = Rc::new(re);
+                self.last_re = Some(re.clone());
+                Ok(re)
+            },
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    /// Returns the number of the next line after (or, searching backwards,
+    /// before) the current one that matches, wrapping around the buffer.
+    fn search(&self, re: &Regex, forward: bool) -> Result<usize, String> {
+        let len = self.lines.len();
+        let mut n = self.cur;
+        for _ in 0..len {
+            n = if forward { n % len + 1 } else if n <= 1 { len } else { n - 1 };
+            if re.is_match(&self.lines[n - 1]) {
+                return Ok(n);
+            }
+        }
+        error("no match")
+    }
+
+    /// Returns the number of the line, if it is still in the buffer,
+    /// looking first at and after the index given.
+    fn find(&self, line: &Line, from: usize) -> Option<usize> {
+        let len = self.lines.len();
+        (0..len).map(|i| (from + i) % len)
+            .find(|&i| Rc::ptr_eq(&self.lines[i], line))
+            .map(|i| i + 1)
+    }
+
+    /// Parses an address: a line number, ., $, a mark or a search,
+    /// followed by any number of offsets. Returns None if there is no
+    /// address.
+    fn address(&mut self, p: &mut Parser) -> Result<Option<usize>, String> {
+        p.skip_blanks();
+        let mut addr = match p.peek() {
+            Some(b'.') => {
+                p.pos += 1;
+                Some(self.cur as i64)
+            },
+            Some(b'$') => {
+                p.pos += 1;
+                Some(self.lines.len() as i64)
+            },
+            Some(b'0'..=b'9') => p.number()?,
+            Some(b'\'') => {
+                p.pos += 1;
+                let mark = mark_index(p.peek())?;
+                p.pos += 1;
+                match self.marks[mark].as_ref().and_then(|line| self.find(line, 0)) {
+                    Some(n) => Some(n as i64),
+                    None => return error("invalid address"),
+                }
+            },
+            Some(delim @ (b'/' | b'?')) => {
+                p.pos += 1;
+                let (text, _) = p.delimited(delim, true);
+                let re = self.regex(&text)?;
+                Some(self.search(&re, delim == b'/')? as i64)
+            },
+            _ => None,
+        };
+        loop {
+            p.skip_blanks();
+            match p.peek() {
+                Some(c @ (b'+' | b'-' | b'^')) => {
+                    p.pos += 1;
+                    let n = p.number()?.unwrap_or(1);
+                    let base = addr.unwrap_or(self.cur as i64);
+                    addr = Some(if c == b'+' { base + n } else { base - n });
+                },
+                // A number following an address is added to it.
+                Some(b'0'..=b'9') if addr.is_some() => {
+                    addr = Some(addr.unwrap() + p.number()?.unwrap());
+                },
+                _ => break,
+            }
+        }
+        match addr {
+            Some(n) if n < 0 || n > self.lines.len() as i64 => error("invalid address"),
+            Some(n) => Ok(Some(n as usize)),
+            None => Ok(None),
+        }
+    }
+
+    /// Parses the addresses at the start of a command, returning at most
+    /// the last two of them. On its own, a comma or % stands for 1,$ and
+    /// a semicolon for .,$; a semicolon also makes the address before it
+    /// the current line.
+    fn addresses(&mut self, p: &mut Parser) -> Result<Vec<usize>, String> {
+        let mut addrs = Vec::new();
+        // The address that stands for one left out after a separator.
+        let mut missing = None;
+        loop {
+            let addr = self.address(p)?;
+            p.skip_blanks();
+            let sep = p.peek().filter(|&c| c == b',' || c == b';' || (c == b'%' && addr.is_none()));
+            match (addr, sep) {
+                (Some(n), None) => {
+                    addrs.push(n);
+                    break;
+                },
+                (None, None) => {
+                    addrs.extend(missing);
+                    break;
+                },
+                (Some(n), Some(c)) => {
+                    p.pos += 1;
+                    if c == b';' {
+                        self.cur = n;
+                    }
+                    addrs.push(n);
+                    missing = Some(n);
+                },
+                (None, Some(c)) => {
+                    p.pos += 1;
+                    addrs.push(if c == b';' { self.cur } else { 1 });
+                    missing = Some(self.lines.len());
+                }
+            }
+        }
+        let excess = addrs.len().saturating_sub(2);
+        addrs.drain(..excess);
+        Ok(addrs)
+    }
+
+    /// Returns the range of lines addressed, or the default range if there
+    /// are no addresses. The range must be of lines in the buffer.
+    fn range(&self, addrs: &[usize], default: (usize, usize)) -> Result<(usize, usize), String> {
+        let (first, second) = match *addrs {
+            [] => default,
+            [n] => (n, n),
+            [first, second] => (first, second),
+            _ => unreachable!(),
+        };
+        if first == 0 || first > second || second > self.lines.len() {
+            return error("invalid address");
+        }
+        Ok((first, second))
+    }
+
+    /// Returns the line addressed, or the default, which may be 0.
+    fn line(&self, addrs: &[usize], default: usize) -> usize {
+        addrs.last().copied().unwrap_or(default)
+    }
+
+    fn write_line(&mut self, n: usize, style: Style) -> io::Result<()> {
+        if style.number {
+            write!(self.out, "{}\t", n)?;
+        }
+        let line = &self.lines[n - 1];
+        if !style.list {
+            self.out.write_all(line)?;
+            return self.out.write_all(b"\n");
+        }
+        // Non-printing characters are escaped, long lines are folded and
+        // the end of the line is marked, so that nothing is hidden.
+        let mut col = 0;
+        for &c in line.iter() {
+            let escaped = match c {
+                b'\\' => "\\\\".to_string(),
+                b'\x07' => "\\a".to_string(),
+                b'\x08' => "\\b".to_string(),
+                b'\x0c' => "\\f".to_string(),
+                b'\r' => "\\r".to_string(),
+                b'\t' => "\\t".to_string(),
+                b'\x0b' => "\\v".to_string(),
+                b' '..=b'~' => (c as char).to_string(),
+                _ => format!("\\{:03o}", c),
+            };
+            if col + escaped.len() >= FOLD_WIDTH {
+                self.out.write_all(b"\\\n")?;
+                col = 0;
+            }
+            self.out.write_all(escaped.as_bytes())?;
+            col += escaped.len();
+        }
+        self.out.write_all(b"$\n")
+    }
+
+    /// Prints a range of lines, making the last of them current.
+    fn print(&mut self, first: usize, second: usize, style: Style) -> Result<(), String> {
+        for n in first..=second {
+            if INTERRUPTED.swap(false, Ordering::SeqCst) {
+                return error("interrupt");
+            }
+            self.write_line(n, style).map_err(|e| e.to_string())?;
+            self.cur = n;
+        }
+        Ok(())
+    }
+
+    /// Prints the current line if the command asked for it with a suffix.
+    fn print_current(&mut self, style: Option<Style>) -> Result<(), String> {
+        match style {
+            Some(style) if self.cur > 0 => self.print(self.cur, self.cur, style),
+            Some(_) => error("invalid address"),
+            None => Ok(()),
+        }
+    }
+
+    /// Prints a byte count, unless they have been suppressed.
+    fn print_count(&mut self, count: usize) -> Result<(), String> {
+        if !self.silent {
+            writeln!(self.out, "{}", count).map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+
+    /// Returns the file name given to a command, which becomes the default
+    /// if there isn't one already, or else the default. A name starting
+    /// with ! is a shell command to read from or write to instead.
+    fn filename(&mut self, p: &mut Parser) -> Result<OsString, String> {
+        match p.filename()? {
+            Some(name) => {
+                if self.file.is_none() && !name.as_encoded_bytes().starts_with(b"!") {
+                    self.file = Some(name.clone());
+                }
+                Ok(name)
+            },
+            None => match &self.file {
+                Some(name) => Ok(name.clone()),
+                None => error("no current filename"),
+            },
+        }
+    }
+
+    /// Reads the lines of a file, or the output of a command, returning
+    /// them and the number of bytes read. A missing newline at the end is
+    /// supplied.
+    fn read_file(&mut self, name: &OsStr) -> Result<(Vec<Line>, usize), String> {
+        let result = match name.as_encoded_bytes().strip_prefix(b"!") {
+            Some(cmd) => {
+                self.out.flush().map_err(|e| e.to_string())?;
+                shell(&String::from_utf8_lossy(cmd)).stderr(Stdio::inherit()).output().map(|output| output.stdout)
+            },
+            None => fs::read(name),
+        };
+        let data = match result {
+            Ok(data) => data,
+            Err(e) => {
+                err!("{}: {}", name.to_string_lossy(), e);
+                return error("cannot open input file");
+            }
+        };
+        let mut lines: Vec<Line> = data.split(|&c| c == b'\n').map(Line::from).collect();
+        if data.is_empty() || data.ends_with(b"\n") {
+            lines.pop();
+        }
+        Ok((lines, data.len()))
+    }
+
+    /// Writes a range of lines, returning the number of bytes written.
+    fn write_lines(&self, out: &mut dyn Write, first: usize, second: usize) -> io::Result<usize> {
+        let mut count = 0;
+        if first > 0 {
+            for line in &self.lines[first - 1..second] {
+                out.write_all(line)?;
+                out.write_all(b"\n")?;
+                count += line.len() + 1;
+            }
+        }
+        out.flush()?;
+        Ok(count)
+    }
+
+    /// Writes a range of lines to a file, replacing or appending to it, or
+    /// to a command, and returns the number of bytes written.
+    fn write_file(&mut self, name: &OsStr, first: usize, second: usize, append: bool)
+                  -> Result<usize, String> {
+        let result = match name.as_encoded_bytes().strip_prefix(b"!") {
+            Some(cmd) => {
+                self.out.flush().map_err(|e| e.to_string())?;
+                shell(&String::from_utf8_lossy(cmd)).stdin(Stdio::piped()).spawn().and_then(|mut child| {
+                    let mut stdin = BufWriter::new(child.stdin.take().unwrap());
+                    let count = self.write_lines(&mut stdin, first, second);
+                    drop(stdin);
+                    child.wait()?;
+                    count
+                })
+            },
+            None => OpenOptions::new().write(true).create(true)
+                .append(append).truncate(!append).open(name)
+                .and_then(|file| self.write_lines(&mut BufWriter::new(file), first, second)),
+        };
+        result.map_err(|e| {
+            err!("{}: {}", name.to_string_lossy(), e);
+            "cannot write file".to_string()
+        })
+    }
+
+    /// Replaces the lines from the index start up to end with others,
+    /// recording the change so that it can be undone.
+    fn splice(&mut self, start: usize, end: usize, lines: Vec<Line>) {
+        let inserted = lines.len();
+        let removed = self.lines.splice(start..end, lines).collect();
+        self.changes.push(Change { start, removed, inserted });
+        self.modified = true;
+    }
+
+    /// Replaces a range of lines, which may be empty, with the text. The
+    /// last line of the text becomes current or, if there is none, the
+    /// line after the range, if there is one, or else the last line.
+    fn replace(&mut self, first: usize, second: usize, text: Vec<Line>) {
+        let count = text.len();
+        self.splice(first - 1, second, text);
+        self.cur = if count > 0 {
+            first - 1 + count
+        } else {
+            first.min(self.lines.len())
+        };
+    }
+
+    /// Executes the command list of a global command with each of the
+    /// selected lines current in turn, passing over any that have been
+    /// deleted. Returns true if one of the commands was to quit.
+    fn global(&mut self, selected: &[Line], list: VecDeque<Vec<u8>>) -> Result<bool, String> {
+        let mut index = 0;
+        for line in selected {
+            if INTERRUPTED.swap(false, Ordering::SeqCst) {
+                return error("interrupt");
+            }
+            // The next line selected is usually at or after the last one.
+            let n = match self.find(line, index) {
+                Some(n) => n,
+                None => continue,
+            };
+            index = n - 1;
+            self.cur = n;
+            self.global = Some(list.clone());
+            while let Some(cmd) = self.global.as_mut().and_then(|list| list.pop_front()) {
+                if self.command(&cmd)? {
+                    return Ok(true);
+                }
+            }
+        }
+        Ok(false)
+    }
+
+    /// Returns the text of a shell command with unescaped ! replaced by
+    /// the last command and % by the default file name. If there were any
+    /// replacements, the command is printed.
+    fn shell_command(&mut self, text: &[u8]) -> Result<String, String> {
+        let mut cmd = Vec::new();
+        let mut replaced = false;
+        let mut iter = text.iter();
+        while let Some(&c) = iter.next() {
+            match c {
+                b'\\' => match iter.next() {
+                    Some(&d @ (b'!' | b'%')) => cmd.push(d),
+                    Some(&d) => {
+                        cmd.push(c);
+                        cmd.push(d);
+                    },
+                    None => cmd.push(c),
+                },
+                b'!' => match &self.last_shell {
+                    Some(last) => cmd.extend_from_slice(last.as_bytes()),
+                    None => return error("no previous command"),
+                },
+                b'%' => match &self.file {
+                    Some(name) => cmd.extend_from_slice(name.as_encoded_bytes()),
+                    None => return error("no current filename"),
+                },
+                _ => cmd.push(c),
+            }
+            replaced |= c == b'!' || c == b'%';
+        }
+        let cmd = String::from_utf8_lossy(&cmd).into_owned();
+        if replaced {
+            writeln!(self.out, "{}", cmd).map_err(|e| e.to_string())?;
+        }
+        self.last_shell = Some(cmd.clone());
+        Ok(cmd)
+    }
+
+    /// Executes a command line. Returns true if the command was to quit.
+    fn command(&mut self, line: &[u8]) -> Result<bool, String> {
+        let mut p = Parser { text: line, pos: 0 };
+        let addrs = self.addresses(&mut p)?;
+        p.skip_blanks();
+        let cmd = p.peek();
+        if cmd.is_some() {
+            p.pos += 1;
+        }
+        let warned = std::mem::take(&mut self.warned);
+        let no_addresses = |addrs: &[usize]| {
+            if addrs.is_empty() { Ok(()) } else { error("unexpected address") }
+        };
+
+        match cmd {
+            // An address on its own prints the line; a blank line prints
+            // the next one.
+            None => {
+                let n = addrs.last().copied().unwrap_or(self.cur + 1);
+                if n == 0 || n > self.lines.len() {
+                    return error("invalid address");
+                }
+                self.print(n, n, Style::default())?;
+            },
+            Some(b'a') => {
+                let n = self.line(&addrs, self.cur);
+                let style = p.suffix()?;
+                let text = self.read_text()?;
+                if text.is_empty() {
+                    self.cur = n;
+                } else {
+                    self.replace(n + 1, n, text);
+                }
+                self.print_current(style)?;
+            },
+            Some(b'c') => {
+                let (first, second) = self.range(&addrs, (self.cur, self.cur))?;
+                let style = p.suffix()?;
+                let text = self.read_text()?;
+                self.replace(first, second, text);
+                self.print_current(style)?;
+            },
+            Some(b'd') => {
+                let (first, second) = self.range(&addrs, (self.cur, self.cur))?;
+                let style = p.suffix()?;
+                self.replace(first, second, Vec::new());
+                self.print_current(style)?;
+            },
+            Some(c @ (b'e' | b'E')) => {
+                no_addresses(&addrs)?;
+                let name = self.filename(&mut p)?;
+                if c == b'e' && self.modified && !warned {
+                    self.warned = true;
+                    return error("warning: buffer modified");
+                }
+                let (lines, count) = self.read_file(&name)?;
+                // Editing a new file can't be undone.
+                self.lines = lines;
+                self.cur = self.lines.len();
+                self.modified = false;
+                self.marks = Default::default();
+                self.changes.clear();
+                self.undo.clear();
+                if !name.as_encoded_bytes().starts_with(b"!") {
+                    self.file = Some(name);
+                }
+                self.print_count(count)?;
+            },
+            Some(b'f') => {
+                no_addresses(&addrs)?;
+                if let Some(name) = p.filename()? {
+                    self.file = Some(name);
+                }
+                match &self.file {
+                    Some(name) => {
+                        self.out.write_all(name.as_encoded_bytes())
+                            .and_then(|_| self.out.write_all(b"\n"))
+                            .map_err(|e| e.to_string())?
+                    },
+                    None => return error("no current filename"),
+                }
+            },
+            Some(c @ (b'g' | b'v')) => {
+                if self.global.is_some() {
+                    return error("cannot nest global commands");
+                }
+                let (first, second) = self.range(&addrs, (1, self.lines.len()))?;
+                let delim = match p.peek() {
+                    Some(b' ') | None => return error("invalid pattern delimiter"),
+                    Some(c) => c,
+                };
+                p.pos += 1;
+                let (text, _) = p.delimited(delim, true);
+                let re = self.regex(&text)?;
+                let mut list = command_list(&line[p.pos..]);
+                if list.len() == 1 && list[0].is_empty() {
+                    list[0] = b"p".to_vec();
+                }
+                let selected: Vec<Line> = self.lines[first - 1..second].iter()
+                    .filter(|line| re.is_match(line) == (c == b'g'))
+                    .cloned().collect();
+                let result = self.global(&selected, list);
+                self.global = None;
+                if result? {
+                    return Ok(true);
+                }
+            },
+            Some(b'h') => {
+                no_addresses(&addrs)?;
+                p.end()?;
+                if let Some(msg) = &self.error {
+                    writeln!(self.out, "{}", msg).map_err(|e| e.to_string())?;
+                }
+            },
+            Some(b'H') => {
+                no_addresses(&addrs)?;
+                p.end()?;
+                self.explain = !self.explain;
+                if let (true, Some(msg)) = (self.explain, &self.error) {
+                    writeln!(self.out, "{}", msg).map_err(|e| e.to_string())?;
+                }
+            },
+            Some(b'i') => {
+                let n = self.line(&addrs, self.cur).max(1);
+                let style = p.suffix()?;
+                let text = self.read_text()?;
+                if text.is_empty() {
+                    self.cur = n.min(self.lines.len());
+                } else {
+                    self.replace(n, n - 1, text);
+                }
+                self.print_current(style)?;
+            },
+            Some(b'j') => {
+                let (first, second) = self.range(&addrs, (self.cur, self.cur + 1))?;
+                let style = p.suffix()?;
+                if first < second {
+                    let joined = self.lines[first - 1..second].concat();
+                    self.replace(first, second, vec![Line::from(joined)]);
+                }
+                self.print_current(style)?;
+            },
+            Some(b'k') => {
+                let n = self.line(&addrs, self.cur);
+                if n == 0 {
+                    return error("invalid address");
+                }
+                let mark = mark_index(p.peek())?;
+                p.pos += 1;
+                p.end()?;
+                self.marks[mark] = Some(self.lines[n - 1].clone());
+            },
+            Some(b'l') => {
+                let (first, second) = self.range(&addrs, (self.cur, self.cur))?;
+                let style = p.suffix()?.unwrap_or_default();
+                self.print(first, second, Style { list: true, ..style })?;
+            },
+            Some(b'm') => {
+                let (first, second) = self.range(&addrs, (self.cur, self.cur))?;
+                let dest = match self.address(&mut p)? {
+                    Some(dest) => dest,
+                    None => return error("destination expected"),
+                };
+                let style = p.suffix()?;
+                if dest >= first && dest < second {
+                    return error("invalid destination");
+                }
+                let moved = self.lines[first - 1..second].to_vec();
+                let count = moved.len();
+                self.splice(first - 1, second, Vec::new());
+                let dest = if dest >= second { dest - count } else { dest };
+                self.splice(dest, dest, moved);
+                self.cur = dest + count;
+                self.print_current(style)?;
+            },
+            Some(b'n') => {
+                let (first, second) = self.range(&addrs, (self.cur, self.cur))?;
+                let style = p.suffix()?.unwrap_or_default();
+                self.print(first, second, Style { number: true, ..style })?;
+            },
+            Some(b'p') => {
+                let (first, second) = self.range(&addrs, (self.cur, self.cur))?;
+                let style = p.suffix()?.unwrap_or_default();
+                self.print(first, second, style)?;
+            },
+            Some(b'P') => {
+                no_addresses(&addrs)?;
+                p.end()?;
+                self.prompting = !self.prompting;
+            },
+            Some(c @ (b'q' | b'Q')) => {
+                no_addresses(&addrs)?;
+                p.end()?;
+                if c == b'q' && self.modified && !warned {
+                    self.warned = true;
+                    return error("warning: buffer modified");
+                }
+                return Ok(true);
+            },
+            Some(b'r') => {
+                let n = self.line(&addrs, self.lines.len());
+                let name = self.filename(&mut p)?;
+                let (lines, count) = self.read_file(&name)?;
+                if !lines.is_empty() {
+                    self.replace(n + 1, n, lines);
+                }
+                self.print_count(count)?;
+            },
+            Some(b's') => {
+                let (first, second) = self.range(&addrs, (self.cur, self.cur))?;
+                let delim = match p.peek() {
+                    Some(b' ' | b'\n') | None => return error("invalid pattern delimiter"),
+                    Some(c) => c,
+                };
+                p.pos += 1;
+                let (text, terminated) = p.delimited(delim, true);
+                if !terminated {
+                    return error("missing pattern delimiter");
+                }
+                let re = self.regex(&text)?;
+                let (mut raw, terminated) = p.delimited(delim, false);
+                // A replacement of % alone repeats the last one.
+                if raw == b"%" {
+                    raw = match &self.last_repl {
+                        Some(raw) => raw.clone(),
+                        None => return error("no previous substitution"),
+                    };
+                }
+                let repl = replacement(&raw, re.groups())?;
+                self.last_repl = Some(raw);
+                let mut global = false;
+                let mut nth = None;
+                loop {
+                    match p.peek() {
+                        Some(b'g') if !global => {
+                            global = true;
+                            p.pos += 1;
+                        },
+                        Some(b'0'..=b'9') if nth.is_none() => match p.number()? {
+                            Some(0) => return error("invalid substitution count"),
+                            n => nth = n,
+                        },
+                        _ => break,
+                    }
+                }
+                // Leaving out the closing delimiter prints the result.
+                let style = if terminated {
+                    p.suffix()?
+                } else {
+                    p.end()?;
+                    Some(Style::default())
+                };
+
+                let nth = usize::try_from(nth.unwrap_or(1)).map_err(|e| e.to_string())?;
+                let mut n = first;
+                let mut last = second;
+                let mut found = false;
+                while n <= last {
+                    if INTERRUPTED.swap(false, Ordering::SeqCst) {
+                        return error("interrupt");
+                    }
+                    match substitute(&re, &repl, &self.lines[n - 1], global, nth) {
+                        Some(text) => {
+                            // Newlines in the replacement split the line.
+                            let lines: Vec<Line> = text.split(|&c| c == b'\n').map(Line::from).collect();
+                            let count = lines.len();
+                            self.splice(n - 1, n, lines);
+                            last += count - 1;
+                            n += count;
+                            self.cur = n - 1;
+                            found = true;
+                        },
+                        None => n += 1,
+                    }
+                }
+                // In a global command, lines that don't match are passed
+                // over.
+                if !found {
+                    return if self.global.is_some() { Ok(false) } else { error("no match") };
+                }
+                self.print_current(style)?;
+            },
+            Some(b't') => {
+                let (first, second) = self.range(&addrs, (self.cur, self.cur))?;
+                let dest = match self.address(&mut p)? {
+                    Some(dest) => dest,
+                    None => return error("destination expected"),
+                };
+                let style = p.suffix()?;
+                // The copies are new lines, which marks don't refer to.
+                let copy = self.lines[first - 1..second].iter().map(|line| Line::from(&line[..])).collect();
+                self.replace(dest + 1, dest, copy);
+                self.print_current(style)?;
+            },
+            Some(b'u') => {
+                no_addresses(&addrs)?;
+                p.end()?;
+                if self.undo.is_empty() {
+                    return error("nothing to undo");
+                }
+                // Undoing is itself a change, so a second u redoes.
+                let undo = mem::take(&mut self.undo);
+                for change in undo.into_iter().rev() {
+                    self.splice(change.start, change.start + change.inserted, change.removed);
+                }
+                self.cur = self.undo_cur.min(self.lines.len());
+            },
+            Some(c @ (b'w' | b'W')) => {
+                let quit = c == b'w' && p.eat(b'q');
+                let (first, second) = if addrs.is_empty() && self.lines.is_empty() {
+                    (0, 0)
+                } else {
+                    self.range(&addrs, (1, self.lines.len()))?
+                };
+                let name = self.filename(&mut p)?;
+                let count = self.write_file(&name, first, second, c == b'W')?;
+                if c == b'w' && first <= 1 && second == self.lines.len() && !name.as_encoded_bytes().starts_with(b"!") {
+                    self.modified = false;
+                }
+                self.print_count(count)?;
+                if quit {
+                    return self.command(b"q");
+                }
+            },
+            Some(b'=') => {
+                let n = self.line(&addrs, self.lines.len());
+                p.end()?;
+                writeln!(self.out, "{}", n).map_err(|e| e.to_string())?;
+            },
+            Some(b'!') => {
+                no_addresses(&addrs)?;
+                let cmd = self.shell_command(&line[p.pos..])?;
+                self.out.flush().map_err(|e| e.to_string())?;
+                if let Err(e) = shell(&cmd).status() {
+                    err!("sh: {}", e);
+                    return error("cannot run command");
+                }
+                if !self.silent {
+                    writeln!(self.out, "!").map_err(|e| e.to_string())?;
+                }
+            },
+            Some(_) => return error("unknown command"),
+        }
+        Ok(false)
+    }
+
+    /// Executes a command that has been read, keeping the changes it makes
+    /// to the buffer, if any, for the u command.
+    fn execute(&mut self, line: &[u8]) -> Result<bool, String> {
+        let cur = self.cur;
+        let result = self.command(line);
+        if !self.changes.is_empty() {
+            self.undo = mem::take(&mut self.changes);
+            self.undo_cur = cur;
+        }
+        result
+    }
+
+    /// Reports an error with "?", followed by an explanation if they have
+    /// been asked for.
+    fn report(&mut self, msg: String) -> io::Result<()> {
+        writeln!(self.out, "?")?;
+        if self.explain {
+            writeln!(self.out, "{}", msg)?;
+        }
+        self.error = Some(msg);
+        Ok(())
+    }
+
+    /// Reads and executes commands until told to quit, or until the end
+    /// of the input. Errors end the session unless the commands are being
+    /// typed at a terminal. Returns the exit status.
+    fn run(&mut self, interactive: bool) -> io::Result<i32> {
+        loop {
+            if self.prompting {
+                self.out.write_all(self.prompt.as_bytes())?;
+            }
+            let result = match self.read_command() {
+                Ok(Some(line)) => self.execute(&line),
+                // The end of the input is taken as a q command.
+                Ok(None) => self.execute(b"q"),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {
+                    writeln!(self.out)?;
+                    error("interrupt")
+                },
+                Err(e) => return Err(e),
+            };
+            match result {
+                Ok(true) => return Ok(0),
+                Ok(false) => (),
+                Err(msg) => {
+                    self.report(msg)?;
+                    if !interactive {
+                        self.out.flush()?;
+                        return Ok(1);
+                    }
+                }
+            }
+        }
+    }
+}
+
+pub const SPEC: Spec = Spec::new("text editor", &[
+    Opt::flag('s', "suppress byte counts and diagnostics"),
+    Opt::arg('p', "string", "prompt with string for commands"),
+], "[file]");
+
+pub fn run(args: Vec<OsString>) -> i32 {
+    let parsed = SPEC.parse(args);
+    let prompt = parsed.value('p').map(str::to_string);
+    let mut silent = parsed.flag('s');
+    let mut files = parsed.operands;
+    // "-" is the Seventh Edition spelling of -s.
+    let given = files.len();
+    files.retain(|file| file != "-");
+    silent |= files.len() < given;
+
+    if files.len() > 1 {
+        SPEC.usage();
+    }
+
+    // SAFETY: isatty accepts any file descriptor.
+    let interactive = unsafe { libc::isatty(0) } == 1;
+    if interactive {
+        // An interrupt abandons the current command rather than the
+        // whole session, and must interrupt reading a command too, so
+        // the handler is installed without SA_RESTART.
+        let handler = on_interrupt as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        // SAFETY: the handler only stores to an atomic, and sigaction is
+        // given a zeroed, then filled in, struct sigaction.
+        unsafe {
+            let mut action: libc::sigaction = std::mem::zeroed();
+            action.sa_sigaction = handler;
+            action.sa_flags = 0;
+            libc::sigemptyset(&mut action.sa_mask);
+            libc::sigaction(libc::SIGINT, &action, std::ptr::null_mut());
+            libc::signal(libc::SIGQUIT, libc::SIG_IGN);
+        }
+    }
+
+    let input = Box::new(io::stdin().lock());
+    let mut ed = Ed::new(input, Box::new(BufWriter::new(io::stdout())));
+    ed.silent = silent;
+    if let Some(prompt) = prompt {
+        ed.prompt = prompt;
+        ed.prompting = true;
+    }
+    if let Some(file) = files.pop() {
+        // A file that can't be read still becomes the default file name.
+        match ed.read_file(&file) {
+            Ok((lines, count)) => {
+                ed.lines = lines;
+                ed.cur = ed.lines.len();
+                if let Err(msg) = ed.print_count(count) {
+                    ed.error = Some(msg);
+                }
+            },
+            Err(msg) => ed.error = Some(msg),
+        }
+        ed.file = Some(file);
+    }
+
+    match ed.run(interactive) {
+        Ok(status) => {
+            if let Err(e) = ed.out.flush() {
+                err!("{}", e);
+                process::exit(status::FAILURE);
+            }
+            process::exit(status);
+        },
+        Err(e) => {
+            err!("{}", e);
+            process::exit(status::FAILURE);
+        }
+    }
+}
+
+#[test]
+fn test_commands() {
+    let input = b"one\ntwo\nthree\n.\nzero\n.\n";
+    let mut ed = Ed::new(Box::new(&input[..]), Box::new(io::sink()));
+    let lines = |ed: &Ed| ed.lines.iter().map(|l| String::from_utf8_lossy(l).into_owned())
+        .collect::<Vec<String>>();
+    ed.execute(b"a").unwrap();
+    assert_eq!((lines(&ed), ed.cur), (vec!["one".into(), "two".into(), "three".into()], 3));
+    ed.execute(b"0a").unwrap();
+    assert_eq!(ed.cur, 1);
+    assert_eq!(ed.addresses(&mut Parser { text: b"/t/;+1", pos: 0 }), Ok(vec![3, 4]));
+    assert_eq!(ed.addresses(&mut Parser { text: b",", pos: 0 }), Ok(vec![1, 4]));
+    assert_eq!(ed.addresses(&mut Parser { text: b"?o?,$-1", pos: 0 }), Ok(vec![2, 3]));
+    assert_eq!(ed.addresses(&mut Parser { text: b"$+1", pos: 0 }), error("invalid address"));
+    ed.execute(b"2,3m0").unwrap();
+    assert_eq!(lines(&ed), ["one", "two", "zero", "three"]);
+    assert_eq!(ed.cur, 2);
+    ed.execute(b"1t$").unwrap();
+    ed.execute(b"3,4j").unwrap();
+    assert_eq!(lines(&ed), ["one", "two", "zerothree", "one"]);
+    ed.execute(b"$d").unwrap();
+    assert_eq!((ed.cur, ed.modified), (3, true));
+    ed.execute(b"1ka").unwrap();
+    ed.execute(b"g/o/s/o\\(.\\)/<\\1>/g").unwrap();
+    assert_eq!(lines(&ed), ["<n>e", "two", "zer<t>hree"]);
+    assert_eq!(ed.execute(b"'a"), error("invalid address"));
+    ed.execute(b"u").unwrap();
+    assert_eq!(lines(&ed), ["one", "two", "zerothree"]);
+    assert_eq!(ed.execute(b"'a="), Ok(false));
+    ed.execute(b"2s/w/\\\n/").unwrap();
+    assert_eq!(lines(&ed), ["one", "t", "o", "zerothree"]);
+    assert_eq!(ed.execute(b"s/x/y/"), error("no match"));
+    assert_eq!(ed.execute(b"q"), error("warning: buffer modified"));
+    assert_eq!(ed.execute(b"q"), Ok(true));
+}
@@ -0,0 +1,74 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// An implementation of the file(1) command in Rust.
+// See http://man.cat-v.org/unix-7th/1/file
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::{Read, Write};
+use std::os::unix::fs::FileTypeExt;
+use std::process;
+
+use crate::cmd::Spec;
+use crate::diag;
+use crate::err;
+use crate::magic;
+use crate::status;
+
+/// Describes the named file: special files by their type and regular
+/// files by their contents. The standard input, named "-", is described
+/// by its contents.
+fn file(name: &OsStr) -> io::Result<String> {
+    if name == "-" {
+        let mut head = Vec::new();
+        io::stdin().take(magic::HEAD_SIZE as u64).read_to_end(&mut head)?;
+        return Ok(magic::describe(&head));
+    }
+    let meta = fs::symlink_metadata(name)?;
+    let file_type = meta.file_type();
+    if file_type.is_symlink() {
+        return Ok(format!("symbolic link to {}", fs::read_link(name)?.display()));
+    }
+    let description = if file_type.is_dir() {
+        "directory"
+    } else if file_type.is_fifo() {
+        "fifo (named pipe)"
+    } else if file_type.is_socket() {
+        "socket"
+    } else if file_type.is_char_device() {
+        "character special"
+    } else if file_type.is_block_device() {
+        "block special"
+    } else {
+        let mut head = Vec::new();
+        File::open(name)?.take(magic::HEAD_SIZE as u64).read_to_end(&mut head)?;
+        return Ok(magic::describe(&head));
+    };
+    Ok(description.to_string())
+}
+
+pub const SPEC: Spec = Spec::new("determine file type", &[], "file ...");
+
+pub fn run(args: Vec<OsString>) -> i32 {
+    let files = SPEC.parse(args).operands;
+    if files.is_empty() {
+        SPEC.usage();
+    }
+
+    let mut out = io::stdout().lock();
+    for name in &files {
+        match file(name) {
+            Ok(description) => {
+                let line = [name.as_encoded_bytes(), b":\t", description.as_bytes(), b"\n"].concat();
+                if let Err(e) = out.write_all(&line) {
+                    err!("{}", e);
+                    process::exit(status::FAILURE);
+                }
+            },
+            Err(e) => err!("{}: {}", name.to_string_lossy(), e),
+        }
+    }
+    diag::status()
+}
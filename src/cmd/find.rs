@@ -0,0 +1,542 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// An implementation of the find(1) command in Rust.
+// See http://man.cat-v.org/unix-7th/1/find
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::io;
+use std::io::{BufWriter, Write};
+use std::iter::Peekable;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::os::unix::fs::{FileTypeExt, MetadataExt};
+use std::path::PathBuf;
+use std::process;
+use std::process::Command;
+use std::time::SystemTime;
+
+use crate::args;
+use crate::blocks::STAT_BLOCK;
+use crate::cmd::{Opt, Spec};
+use crate::err;
+use crate::glob;
+use crate::json::Record;
+use crate::prompt;
+use crate::size;
+use crate::status;
+use crate::users;
+use crate::walk::{Entry, Follow, Walk};
+
+/// The number of bytes of paths that -exec ... {} + collects before
+/// running the command, kept well below ARG_MAX.
+const BATCH_SIZE: usize = 64 * 1024;
+
+/// A numeric operand: +N means more than N, -N less than N and N exactly
+/// N.
+#[derive(Clone, Copy)]
+enum Num {
+    Less(u64),
+    Equal(u64),
+    Greater(u64),
+}
+
+impl Num {
+    fn parse(arg: &str) -> Option<Num> {
+        if let Some(n) = arg.strip_prefix('+') {
+            n.parse().ok().map(Num::Greater)
+        } else if let Some(n) = arg.strip_prefix('-') {
+            n.parse().ok().map(Num::Less)
+        } else {
+            arg.parse().ok().map(Num::Equal)
+        }
+    }
+
+    fn matches(self, val: u64) -> bool {
+        match self {
+            Num::Less(n) => val < n,
+            Num::Equal(n) => val == n,
+            Num::Greater(n) => val > n,
+        }
+    }
+}
+
+/// The command and paths collected by -exec ... {} +.
+struct Batch {
+    argv: Vec<OsString>,
+    paths: Vec<PathBuf>,
+    /// The total length of the paths.
+    size: usize,
+    /// Whether every run of the command has succeeded.
+    ok: bool,
+}
+
+impl Batch {
+    fn run(&mut self) {
+        if !self.paths.is_empty() {
+            self.ok &= execute(&self.argv, &self.paths);
+            self.paths.clear();
+            self.size = 0;
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Time {
+    Access,
+    Change,
+    Modify,
+}
+
+/// An expression to be evaluated for each file.
+enum Expr {
+    /// True if the last component of the path matches the pattern.
+    Name(Vec<u8>),
+    /// True if the file is of the given type.
+    Type(char),
+    /// True if the file was last accessed, changed or modified the given
+    /// number of days before find started.
+    Time(Time, Num, i64),
+    /// True if the file was modified more recently than the given time.
+    Newer(i64, i64),
+    /// True if the size of the file, in units of the given number of
+    /// bytes, rounded up, matches.
+    Size(Num, u64),
+    User(u32),
+    Group(u32),
+    /// True if the permission bits equal the mode or, if the flag is set,
+    /// include all of its bits.
+    Perm(u32, bool),
+    Links(Num),
+    /// Always true. -depth and -xdev, which affect the traversal rather
+    /// than testing anything, evaluate to this.
+    True,
+    /// Prevents descent into the directory; always true.
+    Prune,
+    /// Prints the path; always true.
+    Print,
+    /// Prints the path followed by a NUL; always true.
+    Print0,
+    /// Prints a JSON record describing the file; always true. This is
+    /// what -print does given --json.
+    PrintJson,
+    /// Runs the command with {} replaced by the path, after asking for
+    /// confirmation if the flag is set. True if the command succeeds.
+    Exec(Vec<OsString>, bool),
+    /// Runs the command with as many paths as fit appended; always true.
+    ExecBatch(Batch),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+}
+
+/// Returns the letter used by -type for the file's type.
+fn type_letter(entry: &Entry) -> char {
+    let ft = entry.metadata().file_type();
+    if ft.is_dir() {
+        'd'
+    } else if ft.is_symlink() {
+        'l'
+    } else if ft.is_block_device() {
+        'b'
+    } else if ft.is_char_device() {
+        'c'
+    } else if ft.is_fifo() {
+        'p'
+    } else if ft.is_socket() {
+        's'
+    } else {
+        'f'
+    }
+}
+
+/// Asks whether to run a command.
+fn confirm(argv: &[OsString]) -> bool {
+    let argv: Vec<_> = argv.iter().map(|arg| arg.to_string_lossy()).collect();
+    prompt::ask(&format!("< {} >?", argv.join(" ")))
+}
+
+/// Returns the argument with each {} replaced by the path.
+fn replace(arg: &OsStr, path: &OsStr) -> OsString {
+    let mut bytes = Vec::new();
+    let mut rest = arg.as_bytes();
+    while let Some(i) = rest.windows(2).position(|w| w == b"{}") {
+        bytes.extend_from_slice(&rest[..i]);
+        bytes.extend_from_slice(path.as_bytes());
+        rest = &rest[i + 2..];
+    }
+    bytes.extend_from_slice(rest);
+    OsString::from_vec(bytes)
+}
+
+/// Runs a command, returning true if it exits successfully.
+fn execute(argv: &[OsString], paths: &[PathBuf]) -> bool {
+    match Command::new(&argv[0]).args(&argv[1..]).args(paths).status() {
+        Ok(status) => status.success(),
+        Err(e) => {
+            err!("{}: {}", argv[0].to_string_lossy(), e);
+            false
+        }
+    }
+}
+
+impl Expr {
+    /// Evaluates the expression for the file, setting prune if the
+    /// directory's contents are to be skipped.
+    fn eval(&mut self, entry: &Entry, out: &mut dyn Write, prune: &mut bool)
+            -> io::Result<bool> {
+        let meta = entry.metadata();
+        Ok(match self {
+            Expr::Name(pat) => glob::matches(pat, entry.name().as_bytes()),
+            Expr::Type(c) => type_letter(entry) == *c,
+            Expr::Time(field, n, now) => {
+                let t = match field {
+                    Time::Access => meta.atime(),
+                    Time::Change => meta.ctime(),
+                    Time::Modify => meta.mtime(),
+                };
+                let days = (*now - t).div_euclid(86400);
+                days >= 0 && n.matches(days as u64)
+            },
+            Expr::Newer(secs, nsecs) => (meta.mtime(), meta.mtime_nsec()) > (*secs, *nsecs),
+            Expr::Size(n, unit) => n.matches(meta.size().div_ceil(*unit)),
+            Expr::User(uid) => meta.uid() == *uid,
+            Expr::Group(gid) => meta.gid() == *gid,
+            Expr::Perm(mode, all) => {
+                let perm = meta.mode() & 0o7777;
+                if *all { perm & *mode == *mode } else { perm == *mode }
+            },
+            Expr::Links(n) => n.matches(meta.nlink()),
+            Expr::True => true,
+            Expr::Prune => {
+                *prune = true;
+                true
+            },
+            Expr::Print | Expr::Print0 => {
+                out.write_all(entry.path().as_os_str().as_bytes())?;
+                out.write_all(if matches!(self, Expr::Print) { b"\n" } else { b"\0" })?;
+                true
+            },
+            Expr::PrintJson => {
+                let record = Record::new()
+                    .name("path", entry.path())
+                    .str("type", &type_letter(entry).to_string())
+                    .num("size", meta.size())
+                    .num("mode", meta.mode() & 0o7777)
+                    .num("links", meta.nlink())
+                    .num("uid", meta.uid())
+                    .num("gid", meta.gid())
+                    .num("mtime", meta.mtime());
+                writeln!(out, "{}", record)?;
+                true
+            },
+            Expr::Exec(argv, prompt) => {
+                let path = entry.path().as_os_str();
+                let argv: Vec<OsString> = argv.iter().map(|arg| replace(arg, path)).collect();
+                out.flush()?;
+                if *prompt && !confirm(&argv) {
+                    return Ok(false);
+                }
+                execute(&argv, &[])
+            },
+            Expr::ExecBatch(batch) => {
+                batch.size += entry.path().as_os_str().len() + 1;
+                batch.paths.push(entry.path().to_path_buf());
+                if batch.size >= BATCH_SIZE {
+                    out.flush()?;
+                    batch.run();
+                }
+                true
+            },
+            Expr::Not(expr) => !expr.eval(entry, out, prune)?,
+            Expr::And(lhs, rhs) => lhs.eval(entry, out, prune)? && rhs.eval(entry, out, prune)?,
+            Expr::Or(lhs, rhs) => lhs.eval(entry, out, prune)? || rhs.eval(entry, out, prune)?,
+        })
+    }
+
+    /// Runs any commands with paths still waiting to be passed to them.
+    /// Returns false if any of them fail.
+    fn finish(&mut self) -> bool {
+        match self {
+            Expr::ExecBatch(batch) => {
+                batch.run();
+                batch.ok
+            },
+            Expr::Not(expr) => expr.finish(),
+            Expr::And(lhs, rhs) | Expr::Or(lhs, rhs) => lhs.finish() & rhs.finish(),
+            _ => true,
+        }
+    }
+
+    /// Returns true if the expression prints anything or runs commands,
+    /// in which case the implicit -print is not added.
+    fn has_action(&self) -> bool {
+        match self {
+            Expr::Print | Expr::Print0 | Expr::PrintJson | Expr::Exec(..) | Expr::ExecBatch(..) => true,
+            Expr::Not(expr) => expr.has_action(),
+            Expr::And(lhs, rhs) | Expr::Or(lhs, rhs) => lhs.has_action() || rhs.has_action(),
+            _ => false,
+        }
+    }
+}
+
+struct Parser<I: Iterator<Item = OsString>> {
+    args: Peekable<I>,
+    /// The time at which find started, in seconds since the epoch.
+    now: i64,
+    /// Set by -depth.
+    depth: bool,
+    /// Set by -xdev.
+    xdev: bool,
+    /// Set by --json, which makes -print write JSON records.
+    json: bool,
+}
+
+impl<I: Iterator<Item = OsString>> Parser<I> {
+    /// Returns the operand of a primary.
+    fn operand(&mut self, primary: &str) -> Result<OsString, String> {
+        self.args.next().ok_or_else(|| format!("{}: missing operand", primary))
+    }
+
+    /// Returns the operand of a primary that must be valid UTF-8.
+    fn str_operand(&mut self, primary: &str) -> Result<String, String> {
+        self.operand(primary)?.into_string().map_err(|arg| {
+            format!("{}: {}: not valid UTF-8", primary, arg.to_string_lossy())
+        })
+    }
+
+    fn num(&mut self, primary: &str) -> Result<Num, String> {
+        let arg = self.str_operand(primary)?;
+        Num::parse(&arg).ok_or_else(|| format!("{}: {}: invalid number", primary, arg))
+    }
+
+    /// Parses the command following -exec or -ok, up to the terminating
+    /// ; or {} +.
+    fn command(&mut self, primary: &str) -> Result<Expr, String> {
+        let mut argv: Vec<OsString> = Vec::new();
+        loop {
+            let arg = self.args.next().ok_or_else(|| format!("{}: missing ;", primary))?;
+            if arg == ";" {
+                break;
+            }
+            if arg == "+" && primary == "-exec" && argv.last().is_some_and(|a| a == "{}") {
+                argv.pop();
+                if argv.is_empty() {
+                    return Err(format!("{}: missing command", primary));
+                }
+                return Ok(Expr::ExecBatch(Batch { argv, paths: Vec::new(), size: 0, ok: true }));
+            }
+            argv.push(arg);
+        }
+        if argv.is_empty() {
+            return Err(format!("{}: missing command", primary));
+        }
+        Ok(Expr::Exec(argv, primary == "-ok"))
+    }
+
+    /// Parses the whole expression.
+    fn parse(&mut self) -> Result<Expr, String> {
+        let expr = self.parse_or()?;
+        match self.args.next() {
+            Some(arg) => Err(format!("{}: unexpected", arg.to_string_lossy())),
+            None => Ok(expr),
+        }
+    }
+
+    /// or: and [-o and]...
+    fn parse_or(&mut self) -> Result<Expr, String> {
+        let mut expr = self.parse_and()?;
+        while self.args.next_if(|arg| arg == "-o").is_some() {
+            let rhs = self.parse_and()?;
+            expr = Expr::Or(Box::new(expr), Box::new(rhs));
+        }
+        Ok(expr)
+    }
+
+    /// and: not [[-a] not]...
+    fn parse_and(&mut self) -> Result<Expr, String> {
+        let mut expr = self.parse_not()?;
+        while self.args.peek().is_some_and(|arg| arg != "-o" && arg != ")") {
+            self.args.next_if(|arg| arg == "-a");
+            let rhs = self.parse_not()?;
+            expr = Expr::And(Box::new(expr), Box::new(rhs));
+        }
+        Ok(expr)
+    }
+
+    /// not: [!]... primary
+    fn parse_not(&mut self) -> Result<Expr, String> {
+        if self.args.next_if(|arg| arg == "!").is_some() {
+            Ok(Expr::Not(Box::new(self.parse_not()?)))
+        } else {
+            self.parse_primary()
+        }
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, String> {
+        let arg = self.args.next().ok_or("missing expression")?;
+        let arg = arg.to_string_lossy().into_owned();
+        match arg.as_str() {
+            "(" => {
+                let expr = self.parse_or()?;
+                match self.args.next() {
+                    Some(arg) if arg == ")" => Ok(expr),
+                    _ => Err("(: missing )".to_string()),
+                }
+            },
+            "-name" => Ok(Expr::Name(self.operand(&arg)?.into_vec())),
+            "-type" => {
+                let t = self.str_operand(&arg)?;
+                match t.as_str() {
+                    "f" | "d" | "l" | "b" | "c" | "p" | "s" => {
+                        Ok(Expr::Type(t.chars().next().unwrap()))
+                    },
+                    _ => Err(format!("-type: {}: unknown type", t)),
+                }
+            },
+            "-atime" => Ok(Expr::Time(Time::Access, self.num(&arg)?, self.now)),
+            "-ctime" => Ok(Expr::Time(Time::Change, self.num(&arg)?, self.now)),
+            "-mtime" => Ok(Expr::Time(Time::Modify, self.num(&arg)?, self.now)),
+            "-newer" => {
+                let file = self.operand(&arg)?;
+                let meta = fs::metadata(&file).map_err(|e| format!("{}: {}", file.to_string_lossy(), e))?;
+                Ok(Expr::Newer(meta.mtime(), meta.mtime_nsec()))
+            },
+            "-size" => {
+                // The size is in 512-byte blocks, or bytes with a c after
+                // it, or another unit with the suffix of one, such as k.
+                let size = self.str_operand(&arg)?;
+                let n = size.find(|c: char| c.is_ascii_alphabetic()).unwrap_or(size.len());
+                let unit = match &size[n..] {
+                    "" => Some(STAT_BLOCK),
+                    "c" => Some(1),
+                    suffix => size::multiplier(suffix),
+                };
+                match (Num::parse(&size[..n]), unit) {
+                    (Some(num), Some(unit)) => Ok(Expr::Size(num, unit)),
+                    _ => Err(format!("{}: {}: invalid size", arg, size)),
+                }
+            },
+            "-user" => {
+                let user = self.str_operand(&arg)?;
+                match users::uid(&user).or_else(|| user.parse().ok()) {
+                    Some(uid) => Ok(Expr::User(uid)),
+                    None => Err(format!("{}: {}: no such user", arg, user)),
+                }
+            },
+            "-group" => {
+                let group = self.str_operand(&arg)?;
+                match users::gid(&group).or_else(|| group.parse().ok()) {
+                    Some(gid) => Ok(Expr::Group(gid)),
+                    None => Err(format!("{}: {}: no such group", arg, group)),
+                }
+            },
+            "-perm" => {
+                let mode = self.str_operand(&arg)?;
+                let (digits, all) = match mode.strip_prefix('-') {
+                    Some(digits) => (digits, true),
+                    None => (mode.as_str(), false),
+                };
+                match u32::from_str_radix(digits, 8) {
+                    Ok(bits) if bits <= 0o7777 => Ok(Expr::Perm(bits, all)),
+                    _ => Err(format!("{}: {}: invalid mode", arg, mode)),
+                }
+            },
+            "-links" => Ok(Expr::Links(self.num(&arg)?)),
+            "-print" if self.json => Ok(Expr::PrintJson),
+            "-print" => Ok(Expr::Print),
+            "-print0" => Ok(Expr::Print0),
+            "-prune" => Ok(Expr::Prune),
+            "-depth" => {
+                self.depth = true;
+                Ok(Expr::True)
+            },
+            "-xdev" => {
+                self.xdev = true;
+                Ok(Expr::True)
+            },
+            "-exec" | "-ok" => self.command(&arg),
+            _ => Err(format!("{}: unknown primary", arg)),
+        }
+    }
+}
+
+pub const SPEC: Spec = Spec::new("find files", &[
+    Opt::flag('H', "follow symbolic links named as paths"),
+    Opt::flag('L', "follow all symbolic links"),
+    Opt::flag('P', "follow no symbolic links (the default)"),
+], "[path ...] [expression]").json();
+
+pub fn run(mut args: Vec<OsString>) -> i32 {
+    let json = args::long_flag(&mut args, "json");
+    let mut args = args.into_iter().skip(1).peekable();
+
+    // The options, which come before the paths, only choose which links
+    // are followed.
+    let mut follow = Follow::Never;
+    while let Some(opt) = args.next_if(|arg| matches!(arg.to_str(), Some("-H" | "-L" | "-P"))) {
+        follow.set(opt.as_bytes()[1] as char);
+    }
+
+    let mut paths = Vec::new();
+    while let Some(path) = args.next_if(|arg| !arg.as_bytes().starts_with(b"-") && arg != "!" && arg != "(") {
+        paths.push(path);
+    }
+    if paths.is_empty() {
+        paths.push(OsString::from("."));
+    }
+
+    let now = match SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(d) => d.as_secs() as i64,
+        Err(_) => 0,
+    };
+    let mut parser = Parser { args, now, depth: false, xdev: false, json };
+    let print = || if json { Expr::PrintJson } else { Expr::Print };
+    let mut expr = if parser.args.peek().is_some() {
+        match parser.parse() {
+            Ok(expr) => expr,
+            Err(e) => {
+                err!("{}", e);
+                process::exit(status::FAILURE);
+            }
+        }
+    } else {
+        print()
+    };
+    if !expr.has_action() {
+        expr = Expr::And(Box::new(expr), Box::new(print()));
+    }
+
+    let mut out = BufWriter::new(io::stdout());
+    let mut status = status::SUCCESS;
+    let mut walk = Walk::new(&paths)
+        .contents_first(parser.depth)
+        .same_file_system(parser.xdev)
+        .follow(follow);
+    while let Some(entry) = walk.next() {
+        let mut prune = false;
+        let result = match entry {
+            Ok(entry) => expr.eval(&entry, &mut out, &mut prune).map(|_| ()),
+            Err(e) => {
+                let _ = out.flush();
+                err!("{}", e);
+                status = status::FAILURE;
+                continue;
+            },
+        };
+        if let Err(e) = result {
+            err!("{}", e);
+            process::exit(status::FAILURE);
+        }
+        if prune {
+            walk.skip_current_dir();
+        }
+    }
+    if let Err(e) = out.flush() {
+        err!("{}", e);
+        status = status::FAILURE;
+    }
+    if !expr.finish() {
+        status = status::FAILURE;
+    }
+    status
+}
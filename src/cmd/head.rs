@@ -0,0 +1,106 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// An implementation of the head(1) command in Rust. head is not a Seventh
+// Edition command; it first appeared in 3BSD.
+// See https://pubs.opengroup.org/onlinepubs/9699919799/utilities/head.html
+use std::ffi::OsString;
+use std::io;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::process;
+
+use crate::cmd::{Opt, Spec};
+use crate::diag;
+use crate::err;
+use crate::input::Input;
+use crate::status;
+
+/// How much of each file to print.
+#[derive(Clone, Copy)]
+enum Count {
+    Lines(u64),
+    Bytes(u64),
+}
+
+fn head(reader: Input, count: Count, out: &mut dyn Write) -> io::Result<()> {
+    match count {
+        Count::Bytes(n) => {
+            io::copy(&mut reader.take(n), out)?;
+        },
+        Count::Lines(n) => {
+            let mut reader = BufReader::new(reader);
+            let mut line = Vec::new();
+            for _ in 0..n {
+                line.clear();
+                if reader.read_until(b'\n', &mut line)? == 0 {
+                    break;
+                }
+                out.write_all(&line)?;
+            }
+        },
+    }
+    Ok(())
+}
+
+fn parse_count(arg: &str) -> u64 {
+    match arg.parse() {
+        Ok(n) => n,
+        Err(e) => {
+            err!("{}: {}", arg, e);
+            process::exit(status::FAILURE);
+        }
+    }
+}
+
+pub const SPEC: Spec = Spec::new("copy the first part of files", &[
+    Opt::arg('n', "count", "copy the first count lines"),
+    Opt::arg('c', "count", "copy the first count bytes"),
+], "[file ...]");
+
+pub fn run(args: Vec<OsString>) -> i32 {
+    // Rewrite the historical -NUM form as -n NUM.
+    let args = args.into_iter().enumerate().flat_map(|(i, arg)| {
+        let bytes = arg.as_encoded_bytes();
+        if i > 0 && bytes.len() > 1 && bytes[0] == b'-' && bytes[1..].iter().all(u8::is_ascii_digit) {
+            let n = arg.to_string_lossy()[1..].to_string();
+            vec![OsString::from("-n"), OsString::from(n)]
+        } else {
+            vec![arg]
+        }
+    }).collect();
+    let parsed = SPEC.parse(args);
+    let mut count = Count::Lines(10);
+    for (c, arg) in parsed.opts() {
+        match (c, arg) {
+            ('n', Some(arg)) => count = Count::Lines(parse_count(arg)),
+            ('c', Some(arg)) => count = Count::Bytes(parse_count(arg)),
+            _ => (),
+        }
+    }
+    let mut files = parsed.operands;
+
+    if files.is_empty() {
+        files.push(OsString::from("-"));
+    }
+
+    let mut out = BufWriter::new(io::stdout());
+    for (i, file) in files.iter().enumerate() {
+        let result = Input::open(file).and_then(|reader| {
+            if files.len() > 1 {
+                let sep = if i > 0 { "\n" } else { "" };
+                write!(out, "{}==> ", sep)?;
+                out.write_all(file.as_encoded_bytes())?;
+                writeln!(out, " <==")?;
+            }
+            head(reader, count, &mut out)
+        });
+        if let Err(e) = result {
+            let _ = out.flush();
+            err!("{}: {}", file.to_string_lossy(), e);
+        }
+    }
+    if let Err(e) = out.flush() {
+        err!("{}", e);
+    }
+    diag::status()
+}
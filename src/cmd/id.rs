@@ -0,0 +1,164 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// An implementation of the id(1) command in Rust. id is not a Seventh
+// Edition command; it comes from System V.
+// See https://pubs.opengroup.org/onlinepubs/9699919799/utilities/id.html
+use std::ffi::OsString;
+use std::process;
+
+use crate::cmd::{Opt, Spec};
+use crate::err;
+use crate::status;
+use crate::users;
+
+/// The user and group ids of a process or user.
+struct Ids {
+    uid: u32,
+    gid: u32,
+    euid: u32,
+    egid: u32,
+    /// The supplementary groups.
+    groups: Vec<u32>,
+}
+
+impl Ids {
+    /// Returns the ids of this process.
+    fn process() -> Ids {
+        // SAFETY: getuid and the like have no preconditions, and groups
+        // has room for the number of groups getgroups says there are.
+        unsafe {
+            let mut groups = vec![0; libc::getgroups(0, std::ptr::null_mut()).max(0) as usize];
+            let n = libc::getgroups(groups.len() as libc::c_int, groups.as_mut_ptr());
+            groups.truncate(n.max(0) as usize);
+            Ids {
+                uid: libc::getuid(),
+                gid: libc::getgid(),
+                euid: libc::geteuid(),
+                egid: libc::getegid(),
+                groups,
+            }
+        }
+    }
+
+    /// Returns the ids a user, given by name or number, logs in with.
+    fn user(user: &str) -> Option<Ids> {
+        let (name, uid) = match users::uid(user) {
+            Some(uid) => (user.to_string(), uid),
+            None => {
+                let uid = user.parse().ok()?;
+                (users::user_name(uid)?, uid)
+            },
+        };
+        let gid = users::login_gid(&name)?;
+        Some(Ids { uid, gid, euid: uid, egid: gid, groups: users::member_gids(&name) })
+    }
+
+    /// Returns the effective, or if real is true the real, group id
+    /// followed by the supplementary groups that are different.
+    fn group_list(&self, real: bool) -> Vec<u32> {
+        let mut list = vec![if real { self.gid } else { self.egid }];
+        for &gid in &self.groups {
+            if !list.contains(&gid) {
+                list.push(gid);
+            }
+        }
+        list
+    }
+}
+
+/// Returns an id followed by its name, if it has one, in parentheses.
+fn with_name(id: u32, name: Option<String>) -> String {
+    match name {
+        Some(name) => format!("{}({})", id, name),
+        None => id.to_string(),
+    }
+}
+
+/// Describes the ids: all of them, or, if which is 'u', 'g' or 'G', the
+/// user id, group id or groups, as names if names is true, and the real
+/// rather than the effective ones if real is true.
+fn describe(ids: &Ids, which: Option<char>, names: bool, real: bool) -> String {
+    let show = |id: u32, name: Option<String>| {
+        if names { name.unwrap_or_else(|| id.to_string()) } else { id.to_string() }
+    };
+    match which {
+        Some('u') => {
+            let uid = if real { ids.uid } else { ids.euid };
+            show(uid, users::user_name(uid))
+        },
+        Some('g') => {
+            let gid = if real { ids.gid } else { ids.egid };
+            show(gid, users::group_name(gid))
+        },
+        Some(_) => {
+            let list = ids.group_list(real);
+            list.iter().map(|&gid| show(gid, users::group_name(gid))).collect::<Vec<_>>().join(" ")
+        },
+        None => {
+            let mut s = format!("uid={} gid={}", with_name(ids.uid, users::user_name(ids.uid)),
+                                with_name(ids.gid, users::group_name(ids.gid)));
+            if ids.euid != ids.uid {
+                s += &format!(" euid={}", with_name(ids.euid, users::user_name(ids.euid)));
+            }
+            if ids.egid != ids.gid {
+                s += &format!(" egid={}", with_name(ids.egid, users::group_name(ids.egid)));
+            }
+            let groups: Vec<String> = ids.group_list(false).iter()
+                .map(|&gid| with_name(gid, users::group_name(gid)))
+                .collect();
+            s + " groups=" + &groups.join(",")
+        },
+    }
+}
+
+pub const SPEC: Spec = Spec::new("print user and group ids and names", &[
+    Opt::flag('G', "print all the group ids"),
+    Opt::flag('g', "print the group id"),
+    Opt::flag('n', "print names rather than numbers"),
+    Opt::flag('r', "print the real rather than the effective id"),
+    Opt::flag('u', "print the user id"),
+], "[user]");
+
+pub fn run(args: Vec<OsString>) -> i32 {
+    let parsed = SPEC.parse(args);
+    let mut which = None;
+    for (c, _) in parsed.opts() {
+        if matches!(c, 'G' | 'g' | 'u') {
+            if which.is_some_and(|w| w != c) {
+                SPEC.usage();
+            }
+            which = Some(c);
+        }
+    }
+    let names = parsed.flag('n');
+    let real = parsed.flag('r');
+    let operands = parsed.strings();
+
+    if operands.len() > 1 || ((names || real) && which.is_none()) {
+        SPEC.usage();
+    }
+    let ids = match operands.first() {
+        Some(user) => Ids::user(user).unwrap_or_else(|| {
+            err!("{}: no such user", user);
+            process::exit(status::FAILURE);
+        }),
+        None => Ids::process(),
+    };
+    println!("{}", describe(&ids, which, names, real));
+    status::SUCCESS
+}
+
+#[test]
+fn test_id() {
+    // Ids that nobody will have named.
+    let ids = Ids { uid: 4000000001, gid: 4000000002, euid: 4000000003, egid: 4000000002,
+                    groups: vec![4000000004, 4000000002] };
+    assert_eq!(describe(&ids, None, false, false),
+               "uid=4000000001 gid=4000000002 euid=4000000003 groups=4000000002,4000000004");
+    assert_eq!(describe(&ids, Some('u'), false, false), "4000000003");
+    assert_eq!(describe(&ids, Some('u'), true, true), "4000000001");
+    assert_eq!(describe(&ids, Some('g'), false, false), "4000000002");
+    assert_eq!(describe(&ids, Some('G'), false, false), "4000000002 4000000004");
+    assert_eq!(with_name(0, Some("root".to_string())), "0(root)");
+}
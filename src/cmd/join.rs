@@ -0,0 +1,393 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// An implementation of the join(1) command in Rust.
+// See http://man.cat-v.org/unix-7th/1/join
+use std::cmp::Ordering;
+use std::ffi::{OsStr, OsString};
+use std::io;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::process;
+
+use crate::cmd;
+use crate::collate::Collation;
+use crate::err;
+use crate::input::Input;
+use crate::status;
+
+/// A line split into fields.
+type Line = Vec<Vec<u8>>;
+
+/// An output field.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Spec {
+    /// The join field.
+    Join,
+    /// A field, numbered from 0, of one of the files, numbered from 0.
+    Field(usize, usize),
+}
+
+/// Parses an output field: 0 for the join field or file.field, where file
+/// is 1 or 2.
+fn parse_spec(s: &str) -> Option<Spec> {
+    if s == "0" {
+        return Some(Spec::Join);
+    }
+    let (file, field) = s.split_once('.')?;
+    let file = match file {
+        "1" => 0,
+        "2" => 1,
+        _ => return None,
+    };
+    match field.parse::<usize>() {
+        Ok(n) if n > 0 => Some(Spec::Field(file, n - 1)),
+        _ => None,
+    }
+}
+
+/// Parses a list of output fields separated by commas or blanks.
+fn parse_specs(s: &str) -> Option<Vec<Spec>> {
+    s.split([',', ' ', '\t']).filter(|s| !s.is_empty()).map(parse_spec).collect()
+}
+
+/// Parses a field number, returning it numbered from 0.
+fn parse_field(s: &str) -> Option<usize> {
+    s.parse::<usize>().ok().filter(|&n| n > 0).map(|n| n - 1)
+}
+
+/// The lines of an input, read a group of lines with the same join field
+/// at a time.
+struct Lines {
+    input: Box<dyn BufRead>,
+    /// The join field.
+    field: usize,
+    collation: Collation,
+    /// The next line, already read.
+    next: Option<Line>,
+}
+
+impl Lines {
+    fn new(input: Box<dyn BufRead>, field: usize, collation: Collation) -> Lines {
+        Lines { input, field, collation, next: None }
+    }
+
+    fn read_line(&mut self, sep: Option<u8>) -> io::Result<Option<Line>> {
+        let mut line = Vec::new();
+        if self.input.read_until(b'\n', &mut line)? == 0 {
+            return Ok(None);
+        }
+        if line.last() == Some(&b'\n') {
+            line.pop();
+        }
+        Ok(Some(split(&line, sep)))
+    }
+
+    /// Returns the next group of lines with the same join field, which is
+    /// empty at the end of the input.
+    fn group(&mut self, sep: Option<u8>) -> io::Result<Vec<Line>> {
+        let first = match self.next.take() {
+            Some(line) => line,
+            None => match self.read_line(sep)? {
+                Some(line) => line,
+                None => return Ok(Vec::new()),
+            },
+        };
+        let mut group = vec![first];
+        while let Some(line) = self.read_line(sep)? {
+            if self.collation.compare(key(&line, self.field), key(&group[0], self.field)) != Ordering::Equal {
+                self.next = Some(line);
+                break;
+            }
+            group.push(line);
+        }
+        Ok(group)
+    }
+}
+
+/// Splits a line into fields separated by the separator or, if there is
+/// none, by runs of blanks, ignoring leading blanks.
+fn split(line: &[u8], sep: Option<u8>) -> Line {
+    match sep {
+        Some(sep) => line.split(|&c| c == sep).map(|f| f.to_vec()).collect(),
+        None => line.split(|&c| c == b' ' || c == b'\t')
+            .filter(|f| !f.is_empty())
+            .map(|f| f.to_vec())
+            .collect(),
+    }
+}
+
+/// Returns the join field of a line, which is empty if it is missing.
+fn key(line: &Line, field: usize) -> &[u8] {
+    line.get(field).map_or(&[], |f| f.as_slice())
+}
+
+struct Join {
+    /// The field separator, or None for blanks.
+    sep: Option<u8>,
+    /// The join field of each file.
+    fields: [usize; 2],
+    /// Whether to write the lines of each file that have no match.
+    unpaired: [bool; 2],
+    /// Whether to write only the lines with no match.
+    only_unpaired: bool,
+    /// The output fields, or None for the join field followed by the
+    /// other fields of each file.
+    specs: Option<Vec<Spec>>,
+    /// The replacement for missing or empty output fields.
+    empty: Vec<u8>,
+    /// The order in which the inputs are sorted on their join fields.
+    collation: Collation,
+}
+
+impl Join {
+    /// Writes an output line made from a line of each file, either of
+    /// which may be missing if the other has no match.
+    fn write(&self, lines: [Option<&Line>; 2], out: &mut dyn Write) -> io::Result<()> {
+        let key = match lines {
+            [Some(line), _] => key(line, self.fields[0]),
+            [None, Some(line)] => key(line, self.fields[1]),
+            [None, None] => &[],
+        };
+        let mut fields: Vec<&[u8]> = Vec::new();
+        match &self.specs {
+            // Missing and empty fields are replaced.
+            Some(specs) => for spec in specs {
+                let field = match *spec {
+                    Spec::Join => key,
+                    Spec::Field(file, n) => lines[file].and_then(|line| line.get(n))
+                        .map_or(&[][..], |f| f.as_slice()),
+                };
+                fields.push(if field.is_empty() { &self.empty } else { field });
+            },
+            None => {
+                fields.push(key);
+                for (file, line) in lines.iter().enumerate() {
+                    if let Some(line) = line {
+                        fields.extend(line.iter().enumerate()
+                                      .filter(|&(n, _)| n != self.fields[file])
+                                      .map(|(_, f)| f.as_slice()));
+                    }
+                }
+            },
+        }
+        let sep = [self.sep.unwrap_or(b' ')];
+        out.write_all(&fields.join(&sep[..]))?;
+        out.write_all(b"\n")
+    }
+
+    /// Joins the two inputs, which must be sorted on their join fields.
+    fn join(&self, inputs: [Box<dyn BufRead>; 2], out: &mut dyn Write) -> io::Result<()> {
+        let [input1, input2] = inputs;
+        let mut lines = [Lines::new(input1, self.fields[0], self.collation),
+                         Lines::new(input2, self.fields[1], self.collation)];
+        let mut groups = [lines[0].group(self.sep)?, lines[1].group(self.sep)?];
+        while !groups[0].is_empty() && !groups[1].is_empty() {
+            let order = self.collation.compare(key(&groups[0][0], self.fields[0]),
+                                               key(&groups[1][0], self.fields[1]));
+            let file = match order {
+                Ordering::Less => 0,
+                Ordering::Greater => 1,
+                Ordering::Equal => {
+                    if !self.only_unpaired {
+                        for line1 in &groups[0] {
+                            for line2 in &groups[1] {
+                                self.write([Some(line1), Some(line2)], out)?;
+                            }
+                        }
+                    }
+                    groups = [lines[0].group(self.sep)?, lines[1].group(self.sep)?];
+                    continue;
+                },
+            };
+            self.write_unpaired(file, &groups[file], out)?;
+            groups[file] = lines[file].group(self.sep)?;
+        }
+        for file in 0..2 {
+            while !groups[file].is_empty() {
+                self.write_unpaired(file, &groups[file], out)?;
+                groups[file] = lines[file].group(self.sep)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_unpaired(&self, file: usize, group: &[Line], out: &mut dyn Write) -> io::Result<()> {
+        if self.unpaired[file] {
+            for line in group {
+                let mut lines = [None, None];
+                lines[file] = Some(line);
+                self.write(lines, out)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+pub const SPEC: cmd::Spec = cmd::Spec::new("relational database operator", &[
+    cmd::Opt::arg('a', "n", "also print the unpairable lines of file n"),
+    cmd::Opt::arg('e', "s", "replace empty output fields with s"),
+    cmd::Opt::arg('j', "field", "join on the field of both files"),
+    cmd::Opt::arg('o', "list", "print the fields in the list, such as 1.1,2.3"),
+    cmd::Opt::arg('t', "c", "separate fields with the character c"),
+    cmd::Opt::arg('v', "n", "print only the unpairable lines of file n"),
+    cmd::Opt::arg('1', "field", "join on the field of file 1"),
+    cmd::Opt::arg('2', "field", "join on the field of file 2"),
+    cmd::Opt::arg('L', "order", "collate text in order: bytes, codepoint, fold or locale"),
+], "file1 file2");
+
+pub fn run(args: Vec<OsString>) -> i32 {
+    let mut args = args.into_iter();
+    let prog = args.next().unwrap();
+
+    // The obsolete forms -j1 field and -j2 field are rewritten as -1 and
+    // -2, and the fields of an obsolete -o list given as several
+    // arguments are gathered into one.
+    let args: Vec<OsString> = args.collect();
+    let mut rewritten = vec![prog];
+    let mut i = 0;
+    while i < args.len() {
+        let arg = &args[i];
+        let next_is_field = args.get(i + 1).and_then(|s| s.to_str()).and_then(parse_field).is_some();
+        if (arg == "-j1" || arg == "-j2") && next_is_field {
+            rewritten.push(OsString::from(if arg == "-j1" { "-1" } else { "-2" }));
+        } else if arg == "-o" && i + 1 < args.len() {
+            let mut list = args[i + 1].clone();
+            i += 1;
+            // At least the two file operands must remain.
+            while i + 3 < args.len() && args[i + 1].to_str().and_then(parse_spec).is_some() {
+                list.push(",");
+                list.push(&args[i + 1]);
+                i += 1;
+            }
+            rewritten.push(arg.clone());
+            rewritten.push(list);
+        } else if arg == "--" {
+            rewritten.extend(args[i..].iter().cloned());
+            break;
+        } else {
+            rewritten.push(arg.clone());
+        }
+        i += 1;
+    }
+
+    let parsed = SPEC.parse(rewritten);
+    let mut join = Join {
+        sep: None,
+        fields: [0, 0],
+        unpaired: [false, false],
+        only_unpaired: false,
+        specs: None,
+        empty: Vec::new(),
+        collation: Collation::from_env(),
+    };
+    let file_number = |s: &str| match s {
+        "1" => 0,
+        "2" => 1,
+        _ => SPEC.usage(),
+    };
+
+    for (c, arg) in parsed.opts() {
+        match (c, arg.unwrap_or_default()) {
+            ('a', arg) => join.unpaired[file_number(arg)] = true,
+            ('v', arg) => {
+                join.unpaired[file_number(arg)] = true;
+                join.only_unpaired = true;
+            },
+            ('e', arg) => join.empty = arg.as_bytes().to_vec(),
+            ('o', arg) => match parse_specs(arg) {
+                Some(specs) if !specs.is_empty() => join.specs = Some(specs),
+                _ => {
+                    err!("{}: invalid field list", arg);
+                    process::exit(status::USAGE);
+                }
+            },
+            ('t', arg) => match arg.as_bytes() {
+                [c] => join.sep = Some(*c),
+                _ => {
+                    err!("{}: the separator must be a single character", arg);
+                    process::exit(status::USAGE);
+                }
+            },
+            ('j', arg) => {
+                let field = parse_field(arg).unwrap_or_else(|| SPEC.usage());
+                join.fields = [field, field];
+            },
+            ('1', arg) => {
+                join.fields[0] = parse_field(arg).unwrap_or_else(|| SPEC.usage());
+            },
+            ('2', arg) => {
+                join.fields[1] = parse_field(arg).unwrap_or_else(|| SPEC.usage());
+            },
+            ('L', arg) => match Collation::parse(arg) {
+                Some(collation) => join.collation = collation,
+                None => {
+                    err!("{}: unknown collation", arg);
+                    process::exit(status::USAGE);
+                }
+            },
+            _ => (),
+        }
+    }
+
+    let files = parsed.operands;
+    if files.len() != 2 {
+        SPEC.usage();
+    }
+    if files[0] == "-" && files[1] == "-" {
+        err!("only one file may be the standard input");
+        process::exit(status::FAILURE);
+    }
+
+    let open = |name: &OsStr| -> Box<dyn BufRead> {
+        match Input::open(name) {
+            Ok(input) => Box::new(BufReader::new(input)),
+            Err(e) => {
+                err!("{}: {}", name.to_string_lossy(), e);
+                process::exit(status::FAILURE);
+            }
+        }
+    };
+    let inputs = [open(&files[0]), open(&files[1])];
+    let mut out = BufWriter::new(io::stdout());
+    if let Err(e) = join.join(inputs, &mut out).and_then(|_| out.flush()) {
+        err!("{}", e);
+        process::exit(status::FAILURE);
+    }
+    status::SUCCESS
+}
+
+#[test]
+fn test_join() {
+    let run = |join: &Join, a: &'static [u8], b: &'static [u8]| {
+        let mut out = Vec::new();
+        join.join([Box::new(a), Box::new(b)], &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    };
+    let mut join = Join {
+        sep: None,
+        fields: [0, 0],
+        unpaired: [false, false],
+        only_unpaired: false,
+        specs: None,
+        empty: Vec::new(),
+        collation: Collation::Bytes,
+    };
+    let a = b"a 1\nb 2\nb 3\nd 4\n";
+    let b = b"b x\nb y\nc z\nd w\n";
+    assert_eq!(run(&join, a, b), "b 2 x\nb 2 y\nb 3 x\nb 3 y\nd 4 w\n");
+    join.unpaired = [true, true];
+    join.only_unpaired = true;
+    assert_eq!(run(&join, a, b), "a 1\nc z\n");
+    join.only_unpaired = false;
+    join.specs = parse_specs("0,2.2,1.2");
+    join.empty = b"-".to_vec();
+    join.sep = Some(b':');
+    assert_eq!(run(&join, b"a:1\nc:\n", b"b:x\nc:y\n"), "a:-:1\nb:x:-\nc:y:-\n");
+    join.specs = None;
+    join.sep = None;
+    join.unpaired = [false, false];
+    join.collation = Collation::Fold;
+    assert_eq!(run(&join, b"a 1\nB 2\n", b"A x\nb y\nb z\n"), "a 1 x\nB 2 y\nB 2 z\n");
+    assert_eq!(parse_specs("1.1 2.3"), Some(vec![Spec::Field(0, 0), Spec::Field(1, 2)]));
+    assert_eq!(parse_specs("3.1"), None);
+}
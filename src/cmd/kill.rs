@@ -0,0 +1,93 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// An implementation of the kill(1) command in Rust.
+// See http://man.cat-v.org/unix-7th/1/kill
+use std::ffi::OsString;
+use std::io;
+use std::process;
+
+use crate::args;
+use crate::cmd::{Opt, Spec};
+use crate::diag;
+use crate::err;
+use crate::signal;
+use crate::status;
+
+/// Lists the signal names or, given operands, translates each signal
+/// number (or exit status of a process killed by a signal) to its name
+/// and each name to its number.
+fn list(operands: &[String]) -> i32 {
+    if operands.is_empty() {
+        let names: Vec<&str> = signal::SIGNALS.iter().map(|&(name, _)| name).collect();
+        println!("{}", names.join(" "));
+        return 0;
+    }
+    for operand in operands {
+        let translated = match operand.parse::<libc::c_int>() {
+            Ok(n) => signal::name(if n > 128 { n - 128 } else { n }).map(String::from),
+            Err(_) => signal::number(operand).filter(|&n| n != 0).map(|n| n.to_string()),
+        };
+        match translated {
+            Some(s) => println!("{}", s),
+            None => err!("{}: unknown signal", operand),
+        }
+    }
+    diag::status()
+}
+
+pub const SPEC: Spec = Spec::new("terminate a process", &[
+    Opt::arg('s', "signal", "send the signal rather than TERM"),
+    Opt::flag('l', "list the signal names"),
+], "pid ...");
+
+pub fn run(args: Vec<OsString>) -> i32 {
+    let mut args = args::strings(args);
+    args.remove(0);
+    let bad_signal = |name: &str| -> ! {
+        err!("{}: unknown signal", name);
+        process::exit(status::FAILURE);
+    };
+
+    // GetOpt can't parse -SIGNAME, -N or negative process ids, so the
+    // (single) option is picked out by hand.
+    let mut sig = libc::SIGTERM;
+    let mut pids = &args[..];
+    match args.first().map(String::as_str) {
+        Some("-l") => process::exit(list(&args[1..])),
+        Some("-s") => match args.get(1) {
+            Some(name) => {
+                sig = signal::number(name).unwrap_or_else(|| bad_signal(name));
+                pids = &args[2..];
+            },
+            None => SPEC.usage(),
+        },
+        Some("--") => pids = &args[1..],
+        Some(arg) if arg.starts_with('-') => {
+            sig = signal::number(&arg[1..]).unwrap_or_else(|| bad_signal(&arg[1..]));
+            pids = &args[1..];
+        },
+        _ => (),
+    }
+    if pids.first().map(String::as_str) == Some("--") {
+        pids = &pids[1..];
+    }
+    if pids.is_empty() {
+        SPEC.usage();
+    }
+
+    for pid in pids {
+        let result = match pid.parse::<libc::pid_t>() {
+            // SAFETY: kill has no memory safety preconditions.
+            Ok(n) => match unsafe { libc::kill(n, sig) } {
+                0 => Ok(()),
+                _ => Err(io::Error::last_os_error().to_string()),
+            },
+            Err(_) => Err("invalid process id".to_string()),
+        };
+        if let Err(e) = result {
+            err!("{}: {}", pid, e);
+        }
+    }
+    diag::status()
+}
@@ -0,0 +1,76 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// An implementation of the line(1) command in Rust. line is not a Seventh
+// Edition command; it comes from System V.
+// See https://man.freebsd.org/cgi/man.cgi?query=line&sektion=1
+use std::ffi::OsString;
+use std::fs::File;
+use std::io;
+use std::io::{Read, Write};
+use std::mem::ManuallyDrop;
+use std::os::unix::io::FromRawFd;
+use std::process;
+
+use crate::cmd::Spec;
+use crate::err;
+use crate::status;
+
+/// Copies a line from the input to the output, a byte at a time so as
+/// not to take any more of the input than that. The output always ends
+/// with a newline. Returns false if the input ended before a newline.
+fn line(input: &mut dyn Read, out: &mut dyn Write) -> io::Result<bool> {
+    let mut line = Vec::new();
+    let mut c = [0u8];
+    let found = loop {
+        match input.read(&mut c) {
+            Ok(0) => break false,
+            Ok(_) if c[0] == b'\n' => break true,
+            Ok(_) => line.push(c[0]),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+            Err(e) => return Err(e),
+        }
+    };
+    line.push(b'\n');
+    out.write_all(&line)?;
+    Ok(found)
+}
+
+pub const SPEC: Spec = Spec::new("read one line", &[], "");
+
+pub fn run(args: Vec<OsString>) -> i32 {
+    if !SPEC.parse(args).operands.is_empty() {
+        SPEC.usage();
+    }
+
+    // The standard input is read directly, rather than through the
+    // buffered io::stdin(), so that the rest of it is left for whatever
+    // reads it next. It isn't closed when done with.
+    // SAFETY: file descriptor 0 is open for the life of the process.
+    let stdin = ManuallyDrop::new(unsafe { File::from_raw_fd(0) });
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let result = line(&mut &*stdin, &mut out).and_then(|found| out.flush().map(|_| found));
+    match result {
+        Ok(true) => (),
+        Ok(false) => process::exit(status::FAILURE),
+        Err(e) => {
+            err!("{}", e);
+            process::exit(status::FAILURE);
+        }
+    }
+    status::SUCCESS
+}
+
+#[test]
+fn test_line() {
+    let mut input = io::Cursor::new(b"first\nsecond\nlast".to_vec());
+    let mut out = Vec::new();
+    assert!(line(&mut input, &mut out).unwrap());
+    assert_eq!(out, b"first\n");
+    assert_eq!(input.position(), 6);
+    assert!(line(&mut input, &mut out).unwrap());
+    assert!(!line(&mut input, &mut out).unwrap());
+    assert!(!line(&mut input, &mut out).unwrap());
+    assert_eq!(out, b"first\nsecond\nlast\n\n");
+}
@@ -0,0 +1,72 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// An implementation of the ln(1) command in Rust.
+// See http://man.cat-v.org/unix-7th/1/ln
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+use crate::cmd::{Opt, Spec};
+use crate::copy;
+use crate::diag;
+use crate::err;
+use crate::platform;
+use crate::status;
+use crate::trace;
+
+/// Makes a link to a file at the path: a hard link or, if symbolic, a
+/// symbolic link, whose contents are the file's path as given. With force,
+/// anything but a directory already at the path is replaced.
+fn ln(from: &Path, to: &Path, symbolic: bool, force: bool) -> io::Result<()> {
+    if force {
+        if let Ok(old) = fs::symlink_metadata(to) {
+            if old.is_dir() {
+                return Err(io::Error::other("is a directory"));
+            }
+            // Replacing a file with a hard link to itself would remove it.
+            let same = |meta: fs::Metadata| meta.dev() == old.dev() && meta.ino() == old.ino();
+            if !symbolic && fs::metadata(from).is_ok_and(same) {
+                return Err(io::Error::other(format!("same file as {}", from.display())));
+            }
+            platform::remove_file(to)?;
+        }
+    }
+    if symbolic {
+        trace::op("symlink", format_args!("{:?}, {:?}", from, to), std::os::unix::fs::symlink(from, to))
+    } else {
+        trace::op("link", format_args!("{:?}, {:?}", from, to), fs::hard_link(from, to))
+    }
+}
+
+pub const SPEC: Spec = Spec::new("make links", &[
+    Opt::flag('s', "make symbolic links"),
+    Opt::flag('f', "replace files already at the links' paths"),
+], "file1 [file2] | file ... directory");
+
+pub fn run(args: Vec<OsString>) -> i32 {
+    let parsed = SPEC.parse(args);
+    let symbolic = parsed.flag('s');
+    let force = parsed.flag('f');
+    let mut files: Vec<PathBuf> = parsed.operands.into_iter().map(PathBuf::from).collect();
+    // A lone file is linked to under its own name in the current
+    // directory.
+    let target = match files.len() {
+        0 => SPEC.usage(),
+        1 => PathBuf::from("."),
+        _ => files.pop().unwrap(),
+    };
+    if files.len() > 1 && !target.is_dir() {
+        err!("{}: not a directory", target.display());
+        return status::FAILURE;
+    }
+    for file in &files {
+        let to = copy::destination(file, &target);
+        if let Err(e) = ln(file, &to, symbolic, force) {
+            err!("{}: {}", to.display(), e);
+        }
+    }
+    diag::status()
+}
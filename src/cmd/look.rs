@@ -0,0 +1,173 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// An implementation of the look(1) command in Rust.
+// See http://man.cat-v.org/unix-7th/1/look
+use std::cmp::Ordering;
+use std::ffi::{OsStr, OsString};
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Write};
+use std::process;
+
+use crate::cmd::{Opt, Spec};
+use crate::collate::Collation;
+use crate::err;
+use crate::mmap::Mmap;
+use crate::status;
+
+/// The word list searched if no file is named.
+const WORDS: &str = "/usr/share/dict/words";
+
+/// The exit statuses when a line is found, and when none is.
+const FOUND: i32 = status::TRUE;
+const NOT_FOUND: i32 = status::FALSE;
+
+struct Look {
+    /// Compare only letters, digits and blanks.
+    dictionary: bool,
+    /// Ignore the case of letters.
+    fold: bool,
+    /// The order in which the lines are sorted.
+    collation: Collation,
+}
+
+impl Look {
+    /// Returns the characters of a line, or of the prefix, that take part
+    /// in comparisons, transformed as they are compared.
+    fn chars<'a>(&'a self, s: &'a [u8]) -> impl Iterator<Item = u8> + 'a {
+        s.iter()
+            .take_while(|&&c| c != b'\n')
+            .filter(move |&&c| !self.dictionary || c.is_ascii_alphanumeric() || c == b' ' || c == b'\t')
+            .map(move |&c| if self.fold { c.to_ascii_lowercase() } else { c })
+    }
+
+    /// Compares the start of a line with the prefix: the line is Equal if
+    /// it begins with the prefix.
+    fn compare(&self, line: &[u8], prefix: &[u8]) -> Ordering {
+        if self.collation != Collation::Bytes {
+            let line: Vec<u8> = self.chars(line).collect();
+            let prefix: Vec<u8> = self.chars(prefix).collect();
+            return self.collation.compare_prefix(&line, &prefix);
+        }
+        let mut line = self.chars(line);
+        for p in self.chars(prefix) {
+            match line.next() {
+                Some(c) if c == p => (),
+                Some(c) => return c.cmp(&p),
+                None => return Ordering::Less,
+            }
+        }
+        Ordering::Equal
+    }
+
+    /// Writes the lines of the sorted data that begin with the prefix.
+    /// Returns whether there were any.
+    fn look(&self, data: &[u8], prefix: &[u8], out: &mut dyn Write) -> io::Result<bool> {
+        let line_end = |start: usize| {
+            data[start..].iter().position(|&c| c == b'\n').map_or(data.len(), |i| start + i + 1)
+        };
+        // A binary search for the first line that doesn't sort before the
+        // prefix. The lines before lo sort before it and those from hi on
+        // don't; both are always at the start of a line.
+        let mut lo = 0;
+        let mut hi = data.len();
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            let start = data[..mid].iter().rposition(|&c| c == b'\n').map_or(0, |i| i + 1);
+            if self.compare(&data[start..], prefix) == Ordering::Less {
+                lo = line_end(start);
+            } else {
+                hi = start;
+            }
+        }
+        let mut found = false;
+        while lo < data.len() && self.compare(&data[lo..], prefix) == Ordering::Equal {
+            let end = line_end(lo);
+            out.write_all(&data[lo..end])?;
+            if data[end - 1] != b'\n' {
+                out.write_all(b"\n")?;
+            }
+            found = true;
+            lo = end;
+        }
+        Ok(found)
+    }
+}
+
+pub const SPEC: Spec = Spec::new("find lines in a sorted list", &[
+    Opt::flag('d', "compare only letters, digits and blanks"),
+    Opt::flag('f', "fold upper case to lower case when comparing"),
+    Opt::arg('L', "order", "collate text in order: bytes, codepoint, fold or locale"),
+], "string [file]");
+
+pub fn run(args: Vec<OsString>) -> i32 {
+    let parsed = SPEC.parse(args);
+    let mut look = Look {
+        dictionary: parsed.flag('d'),
+        fold: parsed.flag('f'),
+        collation: Collation::from_env(),
+    };
+    for arg in parsed.values('L') {
+        match Collation::parse(arg) {
+            Some(collation) => look.collation = collation,
+            None => {
+                err!("{}: unknown collation", arg);
+                process::exit(status::TROUBLE);
+            }
+        }
+    }
+    let operands = parsed.operands;
+
+    // The word list is sorted in dictionary order, ignoring case.
+    let (prefix, name) = match operands.as_slice() {
+        [prefix] => {
+            look.dictionary = true;
+            look.fold = true;
+            (prefix, OsStr::new(WORDS))
+        },
+        [prefix, name] => (prefix, name.as_os_str()),
+        _ => SPEC.usage(),
+    };
+
+    let data = match File::open(name).and_then(|file| Mmap::map(&file)) {
+        Ok(data) => data,
+        Err(e) => {
+            err!("{}: {}", name.to_string_lossy(), e);
+            process::exit(status::TROUBLE);
+        }
+    };
+    let mut out = BufWriter::new(io::stdout());
+    match look.look(&data, prefix.as_encoded_bytes(), &mut out).and_then(|found| out.flush().map(|_| found)) {
+        Ok(true) => process::exit(FOUND),
+        Ok(false) => process::exit(NOT_FOUND),
+        Err(e) => {
+            err!("{}", e);
+            process::exit(status::TROUBLE);
+        }
+    }
+}
+
+#[test]
+fn test_look() {
+    let run = |look: &Look, data: &[u8], prefix: &[u8]| {
+        let mut out = Vec::new();
+        let found = look.look(data, prefix, &mut out).unwrap();
+        assert_eq!(found, !out.is_empty());
+        String::from_utf8(out).unwrap()
+    };
+    let plain = Look { dictionary: false, fold: false, collation: Collation::Bytes };
+    let data = b"apple\nbanana\nband\nbandana\ncherry";
+    assert_eq!(run(&plain, data, b"band"), "band\nbandana\n");
+    assert_eq!(run(&plain, data, b"a"), "apple\n");
+    assert_eq!(run(&plain, data, b"ch"), "cherry\n");
+    assert_eq!(run(&plain, data, b"bb"), "");
+    assert_eq!(run(&plain, data, b"z"), "");
+    assert_eq!(run(&plain, b"", b"a"), "");
+    let dict = Look { dictionary: true, fold: true, collation: Collation::Bytes };
+    let data = b"Aaron\nab-c\nAbc\nabd\n";
+    assert_eq!(run(&dict, data, b"ab"), "ab-c\nAbc\nabd\n");
+    assert_eq!(run(&dict, data, b"a.b.c"), "ab-c\nAbc\n");
+    let locale = Look { dictionary: false, fold: false, collation: Collation::Locale };
+    assert_eq!(run(&locale, data, b"ab"), "ab-c\nAbc\nabd\n");
+}
@@ -0,0 +1,473 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// An implementation of the ls(1) command in Rust.
+// See http://man.cat-v.org/unix-7th/1/ls
+use std::collections::HashMap;
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::fs::Metadata;
+use std::io;
+use std::io::{BufWriter, IsTerminal, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{FileTypeExt, MetadataExt};
+use std::path::{Path, PathBuf};
+
+use crate::args;
+use crate::blocks::{BlockSize, STAT_BLOCK};
+use crate::cmd::{Opt, Spec};
+use crate::collate::Collation;
+use crate::color::{Color, Painter, Style, When};
+use crate::dev;
+use crate::diag;
+use crate::err;
+use crate::json::Record;
+use crate::status;
+use crate::time::{self, Tm};
+use crate::tty;
+use crate::users;
+use crate::walk::Follow;
+
+/// How long ago, in seconds, a file can have been modified for ls -l to
+/// give the time of day rather than the year: about six months.
+const RECENT: i64 = 183 * 24 * 60 * 60;
+
+/// The width assumed for a terminal that doesn't say.
+const DEFAULT_WIDTH: usize = 80;
+
+/// A file to be listed: its name, as listed, its path and its metadata.
+struct File {
+    name: OsString,
+    path: PathBuf,
+    meta: Metadata,
+}
+
+struct Ls {
+    /// List in the long format.
+    long: bool,
+    /// List the files whose names begin with a dot, . and .. included.
+    all: bool,
+    /// List directories named as operands, rather than what they contain.
+    dirs: bool,
+    /// Sort by the time of last modification, newest first.
+    time: bool,
+    reverse: bool,
+    /// Give each file's size in blocks.
+    size: bool,
+    /// Give each file's inode number.
+    inode: bool,
+    block_size: BlockSize,
+    /// Which symbolic links are listed as what they refer to.
+    follow: Follow,
+    /// The order in which names are sorted.
+    collation: Collation,
+    /// Write JSON records instead of names.
+    json: bool,
+    /// Lay the names out in columns of this width, if writing to a
+    /// terminal.
+    width: Option<usize>,
+    /// Colours the names by the types of the files.
+    painter: Painter,
+    now: i64,
+    /// The names of the users and groups found so far, by id.
+    user_names: HashMap<u32, String>,
+    group_names: HashMap<u32, String>,
+}
+
+/// Returns the letter that gives a file's type in the form used by ls -l.
+fn type_letter(meta: &Metadata) -> char {
+    let kind = meta.file_type();
+    if kind.is_dir() {
+        'd'
+    } else if kind.is_symlink() {
+        'l'
+    } else if kind.is_char_device() {
+        'c'
+    } else if kind.is_block_device() {
+        'b'
+    } else if kind.is_fifo() {
+        'p'
+    } else if kind.is_socket() {
+        's'
+    } else {
+        '-'
+    }
+}
+
+/// Returns the mode of a file in the form used by ls -l.
+fn mode_string(meta: &Metadata) -> String {
+    let mut s = String::with_capacity(10);
+    s.push(type_letter(meta));
+    let mode = meta.mode();
+    for (shift, special, set) in [(6, 0o4000, 's'), (3, 0o2000, 's'), (0, 0o1000, 't')] {
+        let bits = (mode >> shift) & 7;
+        s.push(if bits & 4 != 0 { 'r' } else { '-' });
+        s.push(if bits & 2 != 0 { 'w' } else { '-' });
+        s.push(match (bits & 1 != 0, mode & special != 0) {
+            (true, true) => set,
+            (false, true) => set.to_ascii_uppercase(),
+            (true, false) => 'x',
+            (false, false) => '-',
+        });
+    }
+    s
+}
+
+/// Returns the style a file's name is coloured in, given by its type, or
+/// None for a regular file that isn't executable.
+fn style(meta: &Metadata) -> Option<Style> {
+    let kind = meta.file_type();
+    if kind.is_dir() {
+        Some(Style::bold(Color::Blue))
+    } else if kind.is_symlink() {
+        Some(Style::bold(Color::Cyan))
+    } else if kind.is_char_device() || kind.is_block_device() {
+        Some(Style::bold(Color::Yellow))
+    } else if kind.is_fifo() {
+        Some(Style::plain(Color::Yellow))
+    } else if kind.is_socket() {
+        Some(Style::bold(Color::Magenta))
+    } else if meta.mode() & 0o111 != 0 {
+        Some(Style::bold(Color::Green))
+    } else {
+        None
+    }
+}
+
+/// Returns the number of columns an entry takes on a terminal: the number
+/// of its characters, less the escape sequences that colour it.
+fn visible_width(entry: &[u8]) -> usize {
+    let mut text = Vec::with_capacity(entry.len());
+    let mut escape = false;
+    for &c in entry {
+        match c {
+            0x1b => escape = true,
+            b'm' if escape => escape = false,
+            _ if escape => (),
+            _ => text.push(c),
+        }
+    }
+    String::from_utf8_lossy(&text).chars().count()
+}
+
+/// Writes the entries in as many columns as fit in the width, ordered
+/// down the columns, as ls does on a terminal.
+fn write_columns(out: &mut dyn Write, entries: &[Vec<u8>], width: usize) -> io::Result<()> {
+    let widest = entries.iter().map(|entry| visible_width(entry)).max();
+    let Some(widest) = widest else {
+        return Ok(());
+    };
+    let column = widest + 2;
+    let columns = (width / column).max(1);
+    let rows = entries.len().div_ceil(columns);
+    for row in 0..rows {
+        let mut line: Vec<&Vec<u8>> = (row..entries.len()).step_by(rows).map(|i| &entries[i]).collect();
+        let last = line.pop().unwrap();
+        for entry in line {
+            out.write_all(entry)?;
+            let pad = column - visible_width(entry);
+            write!(out, "{:pad$}", "", pad = pad)?;
+        }
+        out.write_all(last)?;
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+impl Ls {
+    fn user(&mut self, uid: u32) -> &str {
+        self.user_names.entry(uid).or_insert_with(|| users::user_name(uid).unwrap_or_else(|| uid.to_string()))
+    }
+
+    fn group(&mut self, gid: u32) -> &str {
+        self.group_names.entry(gid).or_insert_with(|| users::group_name(gid).unwrap_or_else(|| gid.to_string()))
+    }
+
+    /// Writes a file's name, coloured by its type if names are being
+    /// coloured.
+    fn write_name(&self, out: &mut dyn Write, file: &File) -> io::Result<()> {
+        match style(&file.meta) {
+            Some(style) => self.painter.write(out, style, file.name.as_bytes()),
+            None => out.write_all(file.name.as_bytes()),
+        }
+    }
+
+    /// Returns the metadata of a file, or of what it refers to if it is a
+    /// symbolic link that is followed at the depth. A link to nothing is
+    /// listed as itself.
+    fn metadata(&self, path: &Path, depth: usize) -> io::Result<Metadata> {
+        if self.follow.follows(depth) {
+            fs::metadata(path).or_else(|_| fs::symlink_metadata(path))
+        } else {
+            fs::symlink_metadata(path)
+        }
+    }
+
+    /// Sorts the files by name, in the order of the collation, or, with -t,
+    /// by time, and reverses them with -r.
+    fn sort(&self, files: &mut [File]) {
+        files.sort_by(|a, b| self.collation.compare(a.name.as_bytes(), b.name.as_bytes()));
+        if self.time {
+            files.sort_by_key(|file| std::cmp::Reverse((file.meta.mtime(), file.meta.mtime_nsec())));
+        }
+        if self.reverse {
+            files.reverse();
+        }
+    }
+
+    /// Returns the files in a directory that are to be listed.
+    fn read_dir(&self, dir: &Path) -> io::Result<Vec<File>> {
+        let mut names = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            names.push(entry?.file_name());
+        }
+        if self.all {
+            names.push(OsString::from("."));
+            names.push(OsString::from(".."));
+        } else {
+            names.retain(|name| !name.as_bytes().starts_with(b"."));
+        }
+        let mut files = Vec::new();
+        for name in names {
+            let path = dir.join(&name);
+            match self.metadata(&path, 1) {
+                Ok(meta) => files.push(File { name, path, meta }),
+                Err(e) => err!("{}: {}", path.display(), e),
+            }
+        }
+        Ok(files)
+    }
+
+    /// Returns the inode number and size that precede a file's name, as
+    /// -i and -s ask.
+    fn prefix(&self, meta: &Metadata) -> String {
+        let mut prefix = String::new();
+        if self.inode {
+            prefix += &format!("{:5} ", meta.ino());
+        }
+        if self.size {
+            prefix += &format!("{:>4} ", self.block_size.format(meta.blocks() * STAT_BLOCK));
+        }
+        prefix
+    }
+
+    /// Writes the files in the long format, with the columns as wide as
+    /// the widest of their entries.
+    fn write_long(&mut self, out: &mut dyn Write, files: &[File]) -> io::Result<()> {
+        let mut rows = Vec::new();
+        for file in files {
+            let meta = &file.meta;
+            let kind = meta.file_type();
+            let size = if kind.is_char_device() || kind.is_block_device() {
+                format!("{}, {}", dev::major(meta.rdev()), dev::minor(meta.rdev()))
+            } else {
+                meta.len().to_string()
+            };
+            let age = self.now - meta.mtime();
+            let format = if (0..RECENT).contains(&age) { "%b %e %H:%M" } else { "%b %e  %Y" };
+            rows.push([meta.nlink().to_string(), self.user(meta.uid()).to_string(),
+                       self.group(meta.gid()).to_string(), size, Tm::local(meta.mtime()).format(format)]);
+        }
+        let width = |i: usize| rows.iter().map(|row: &[String; 5]| row[i].len()).max().unwrap_or(0);
+        let (links, user, group, size) = (width(0), width(1), width(2), width(3));
+        for (file, row) in files.iter().zip(&rows) {
+            write!(out, "{}{} {:>links$} {:<user$} {:<group$} {:>size$} {} ",
+                   self.prefix(&file.meta), mode_string(&file.meta), row[0], row[1], row[2], row[3], row[4])?;
+            self.write_name(out, file)?;
+            if file.meta.file_type().is_symlink() {
+                if let Ok(target) = fs::read_link(&file.path) {
+                    out.write_all(b" -> ")?;
+                    out.write_all(target.as_os_str().as_bytes())?;
+                }
+            }
+            writeln!(out)?;
+        }
+        Ok(())
+    }
+
+    /// Writes a JSON record for each of the files, with the members find's
+    /// -print gives with --json.
+    fn write_json(&self, out: &mut dyn Write, files: &[File]) -> io::Result<()> {
+        for file in files {
+            let meta = &file.meta;
+            let kind = match type_letter(meta) {
+                '-' => 'f',
+                c => c,
+            };
+            let record = Record::new()
+                .name("path", &file.path)
+                .str("type", &kind.to_string())
+                .num("size", meta.size())
+                .num("mode", meta.mode() & 0o7777)
+                .num("links", meta.nlink())
+                .num("uid", meta.uid())
+                .num("gid", meta.gid())
+                .num("mtime", meta.mtime());
+            writeln!(out, "{}", record)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the files, one to a line or in columns.
+    fn write_files(&mut self, out: &mut dyn Write, files: &[File]) -> io::Result<()> {
+        if self.json {
+            return self.write_json(out, files);
+        }
+        if self.long {
+            return self.write_long(out, files);
+        }
+        let mut entries = Vec::with_capacity(files.len());
+        for file in files {
+            let mut entry = self.prefix(&file.meta).into_bytes();
+            self.write_name(&mut entry, file)?;
+            entries.push(entry);
+        }
+        match self.width {
+            Some(width) => write_columns(out, &entries, width),
+            None => entries.iter().try_for_each(|entry| {
+                out.write_all(entry)?;
+                writeln!(out)
+            }),
+        }
+    }
+
+    /// Lists what a directory contains, preceded by its total size in
+    /// blocks if sizes are being given.
+    fn list_dir(&mut self, out: &mut dyn Write, dir: &Path) -> io::Result<()> {
+        let mut files = self.read_dir(dir)?;
+        self.sort(&mut files);
+        if (self.long || self.size) && !self.json {
+            let total: u64 = files.iter().map(|file| file.meta.blocks() * STAT_BLOCK).sum();
+            writeln!(out, "total {}", self.block_size.format(total))?;
+        }
+        self.write_files(out, &files)
+    }
+
+    /// Lists the operands: first those that aren't directories, or all of
+    /// them with -d, and then what each directory contains, under its
+    /// name if there is more than one operand.
+    fn ls(&mut self, out: &mut dyn Write, names: &[OsString]) -> io::Result<()> {
+        let mut files = Vec::new();
+        let mut dirs = Vec::new();
+        for name in names {
+            let path = PathBuf::from(name);
+            match self.metadata(&path, 0) {
+                Ok(meta) if meta.is_dir() && !self.dirs => dirs.push(File { name: name.clone(), path, meta }),
+                Ok(meta) => files.push(File { name: name.clone(), path, meta }),
+                Err(e) => err!("{}: {}", name.to_string_lossy(), e),
+            }
+        }
+        self.sort(&mut files);
+        self.sort(&mut dirs);
+        self.write_files(out, &files)?;
+        let headed = names.len() > 1 && !self.json;
+        for (i, dir) in dirs.iter().enumerate() {
+            if (i > 0 || !files.is_empty()) && !self.json {
+                writeln!(out)?;
+            }
+            if headed {
+                out.write_all(dir.name.as_bytes())?;
+                writeln!(out, ":")?;
+            }
+            if let Err(e) = self.list_dir(out, &dir.path) {
+                err!("{}: {}", dir.name.to_string_lossy(), e);
+            }
+        }
+        Ok(())
+    }
+}
+
+pub const SPEC: Spec = Spec::new("list the contents of directories", &[
+    Opt::flag('l', "list in the long format"),
+    Opt::flag('a', "list all files, including those whose names begin with a dot"),
+    Opt::flag('d', "list directories themselves rather than their contents"),
+    Opt::flag('t', "sort by time of last modification, newest first"),
+    Opt::flag('r', "reverse the order"),
+    Opt::flag('s', "give the size of each file in blocks"),
+    Opt::flag('i', "give the inode number of each file"),
+    Opt::flag('k', "count in 1024-byte blocks"),
+    Opt::flag('h', "give sizes for people to read, such as 12K or 1.5M"),
+    Opt::flag('H', "follow symbolic links named as operands"),
+    Opt::flag('L', "follow all symbolic links"),
+    Opt::flag('P', "follow no symbolic links"),
+], "[--color[=always|auto|never]] [file ...]").json();
+
+pub fn run(mut args: Vec<OsString>) -> i32 {
+    // Names are coloured as --color asks or, without it, as on the BSDs:
+    // as the output decides if CLICOLOR or CLICOLOR_FORCE is set, and
+    // otherwise not at all.
+    let when = match args::long_option(&mut args, "color") {
+        Some(None) => When::Auto,
+        Some(Some(when)) => match when.to_str() {
+            Some("always") => When::Always,
+            Some("auto") => When::Auto,
+            Some("never") => When::Never,
+            _ => {
+                err!("{}: invalid --color", when.to_string_lossy());
+                SPEC.usage();
+            }
+        },
+        None if env::var_os("CLICOLOR").is_some() || env::var_os("CLICOLOR_FORCE").is_some() => When::Auto,
+        None => When::Never,
+    };
+    let parsed = SPEC.parse(args);
+    let mut names = parsed.operands.clone();
+    if names.is_empty() {
+        names.push(OsString::from("."));
+    }
+
+    // A symbolic link named as an operand is listed as what it refers
+    // to, unless the link itself is asked about.
+    let mut follow = if parsed.flag('l') || parsed.flag('d') { Follow::Never } else { Follow::Roots };
+    for (c, _) in parsed.opts() {
+        follow.set(c);
+    }
+    let width = io::stdout().is_terminal().then(|| tty::size(1).map_or(DEFAULT_WIDTH, |(_, cols)| cols));
+    let mut ls = Ls {
+        long: parsed.flag('l'),
+        all: parsed.flag('a'),
+        dirs: parsed.flag('d'),
+        time: parsed.flag('t'),
+        reverse: parsed.flag('r'),
+        size: parsed.flag('s'),
+        inode: parsed.flag('i'),
+        block_size: BlockSize::new(parsed.flag('k'), parsed.flag('h')),
+        follow,
+        collation: Collation::from_env(),
+        json: parsed.json,
+        width,
+        painter: Painter::new(when, &io::stdout()),
+        now: time::now().0,
+        user_names: HashMap::new(),
+        group_names: HashMap::new(),
+    };
+    let mut out = BufWriter::new(io::stdout());
+    if let Err(e) = ls.ls(&mut out, &names).and_then(|_| out.flush()) {
+        err!("{}", e);
+        return status::FAILURE;
+    }
+    diag::status()
+}
+
+#[test]
+fn test_write_columns() {
+    let entries: Vec<Vec<u8>> = ["a", "bb", "c", "dddd", "e"].iter().map(|s| s.as_bytes().to_vec()).collect();
+    let mut out = Vec::new();
+    write_columns(&mut out, &entries, 18).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "a     c     e\nbb    dddd\n");
+    let mut out = Vec::new();
+    write_columns(&mut out, &entries, 4).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "a\nbb\nc\ndddd\ne\n");
+    let entries = [b"\x1b[1;34md\x1b[0m".to_vec(), b"ee".to_vec()];
+    let mut out = Vec::new();
+    write_columns(&mut out, &entries, 18).unwrap();
+    assert_eq!(out, b"\x1b[1;34md\x1b[0m   ee\n");
+}
+
+#[test]
+fn test_mode_string() {
+    let meta = fs::symlink_metadata("/").unwrap();
+    assert!(mode_string(&meta).starts_with('d'));
+    assert_eq!(mode_string(&meta).len(), 10);
+}
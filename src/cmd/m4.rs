@@ -0,0 +1,828 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// An implementation of the m4(1) command in Rust.
+// See http://man.cat-v.org/unix-7th/1/m4
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::OsString;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader, Write};
+use std::process;
+
+use crate::cmd::{Opt, Spec};
+use crate::err;
+use crate::status;
+
+const LQUOTE: &[u8] = b"`";
+const RQUOTE: &[u8] = b"'";
+const LCOMMENT: &[u8] = b"#";
+const RCOMMENT: &[u8] = b"\n";
+
+/// The builtin macros, and whether each is only recognised when it is
+/// followed by arguments, so that the words can appear in ordinary text.
+const BUILTINS: &[(&str, bool)] = &[
+    ("changecom", false), ("changequote", false), ("decr", true), ("define", true), ("defn", true),
+    ("divert", false), ("divnum", false), ("dnl", false), ("dumpdef", false), ("errprint", true),
+    ("esyscmd", true), ("eval", true), ("ifdef", true), ("ifelse", true), ("include", true), ("incr", true),
+    ("index", true), ("len", true), ("m4exit", false), ("m4wrap", true), ("maketemp", true),
+    ("popdef", true), ("pushdef", true), ("shift", true), ("sinclude", true), ("substr", true),
+    ("syscmd", true), ("sysval", false), ("translit", true), ("undefine", true), ("undivert", false),
+];
+
+/// The definition of a macro.
+#[derive(Clone, Debug)]
+enum Def {
+    Builtin(&'static str, bool),
+    Text(Vec<u8>),
+}
+
+enum Token {
+    Name(Vec<u8>),
+    /// A quoted string, without its outermost quotes.
+    Quoted(Vec<u8>),
+    Comment(Vec<u8>),
+    Char(u8),
+}
+
+/// A file being read.
+struct Input {
+    name: String,
+    reader: Box<dyn BufRead>,
+    line: usize,
+}
+
+struct M4<W: Write> {
+    out: W,
+    /// The definitions of each macro, the current one last.
+    defs: HashMap<Vec<u8>, Vec<Def>>,
+    /// Text to be read before any more input, in reverse order.
+    pushback: Vec<u8>,
+    inputs: Vec<Input>,
+    lquote: Vec<u8>,
+    rquote: Vec<u8>,
+    lcomment: Vec<u8>,
+    rcomment: Vec<u8>,
+    /// The diversion output goes to: 0 is the standard output, others
+    /// are kept until they are undiverted, and negative ones discarded.
+    diversion: i64,
+    diversions: BTreeMap<i64, Vec<u8>>,
+    /// Text saved by m4wrap, to be read at the end of the input.
+    wrap: Vec<u8>,
+    sysval: i32,
+    status: i32,
+}
+
+/// Parses an integer argument.
+fn int(s: &[u8]) -> Option<i64> {
+    String::from_utf8_lossy(s).trim().parse().ok()
+}
+
+/// Expands the ranges, such as a-z, in an argument of translit.
+fn ranges(s: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < s.len() {
+        if i + 2 < s.len() && s[i + 1] == b'-' {
+            let (from, to) = (s[i], s[i + 2]);
+            if from <= to {
+                out.extend(from..=to);
+            } else {
+                out.extend((to..=from).rev());
+            }
+            i += 3;
+        } else {
+            out.push(s[i]);
+            i += 1;
+        }
+    }
+    out
+}
+
+/// Evaluates an integer expression, with the operators of C and **.
+struct Eval<'a> {
+    s: &'a [u8],
+    pos: usize,
+}
+
+/// The binary operators, from the loosest binding to the tightest.
+const BINARY: &[&[&str]] = &[
+    &["||"], &["&&"], &["|"], &["^"], &["&"], &["==", "!="], &["<=", ">=", "<", ">"], &["<<", ">>"],
+    &["+", "-"], &["*", "/", "%"],
+];
+
+impl Eval<'_> {
+    fn skip(&mut self) {
+        while self.s.get(self.pos).is_some_and(|c| c.is_ascii_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    fn eat(&mut self, op: &str) -> bool {
+        self.skip();
+        let rest = &self.s[self.pos..];
+        // Don't take the start of a longer operator, such as | of ||.
+        let longer = rest.len() > op.len() && matches!((op, rest[op.len()]),
+            ("|", b'|') | ("&", b'&') | ("<", b'<' | b'=') | (">", b'>' | b'=') | ("*", b'*'));
+        if rest.starts_with(op.as_bytes()) && !longer {
+            self.pos += op.len();
+            return true;
+        }
+        false
+    }
+
+    fn binary(&mut self, level: usize) -> Result<i64, String> {
+        if level == BINARY.len() {
+            return self.power();
+        }
+        let mut lhs = self.binary(level + 1)?;
+        'outer: loop {
+            for op in BINARY[level] {
+                if self.eat(op) {
+                    let rhs = self.binary(level + 1)?;
+                    lhs = match *op {
+                        "||" => (lhs != 0 || rhs != 0) as i64,
+                        "&&" => (lhs != 0 && rhs != 0) as i64,
+                        "|" => lhs | rhs,
+                        "^" => lhs ^ rhs,
+                        "&" => lhs & rhs,
+                        "==" => (lhs == rhs) as i64,
+                        "!=" => (lhs != rhs) as i64,
+                        "<=" => (lhs <= rhs) as i64,
+                        ">=" => (lhs >= rhs) as i64,
+                        "<" => (lhs < rhs) as i64,
+                        ">" => (lhs > rhs) as i64,
+                        "<<" => lhs.wrapping_shl(rhs as u32),
+                        ">>" => lhs.wrapping_shr(rhs as u32),
+                        "+" => lhs.wrapping_add(rhs),
+                        "-" => lhs.wrapping_sub(rhs),
+                        "*" => lhs.wrapping_mul(rhs),
+                        _ if rhs == 0 => return Err("divide by zero".to_string()),
+                        "/" => lhs.wrapping_div(rhs),
+                        _ => lhs.wrapping_rem(rhs),
+                    };
+                    continue 'outer;
+                }
+            }
+            return Ok(lhs);
+        }
+    }
+
+    fn power(&mut self) -> Result<i64, String> {
+        let base = self.unary()?;
+        if !self.eat("**") {
+            return Ok(base);
+        }
+        let exp = self.power()?;
+        if exp < 0 {
+            return Err("negative exponent".to_string());
+        }
+        Ok(base.wrapping_pow(exp.min(u32::MAX as i64) as u32))
+    }
+
+    fn unary(&mut self) -> Result<i64, String> {
+        if self.eat("-") {
+            Ok(self.unary()?.wrapping_neg())
+        } else if self.eat("+") {
+            self.unary()
+        } else if self.eat("~") {
+            Ok(!self.unary()?)
+        } else if self.eat("!") {
+            Ok((self.unary()? == 0) as i64)
+        } else if self.eat("(") {
+            let n = self.binary(0)?;
+            if !self.eat(")") {
+                return Err("missing )".to_string());
+            }
+            Ok(n)
+        } else {
+            let start = self.pos;
+            while self.s.get(self.pos).is_some_and(|c| c.is_ascii_alphanumeric()) {
+                self.pos += 1;
+            }
+            let digits = String::from_utf8_lossy(&self.s[start..self.pos]).to_lowercase();
+            let n = if let Some(hex) = digits.strip_prefix("0x") {
+                i64::from_str_radix(hex, 16)
+            } else if let Some(bin) = digits.strip_prefix("0b") {
+                i64::from_str_radix(bin, 2)
+            } else if digits.len() > 1 && digits.starts_with('0') {
+                i64::from_str_radix(&digits[1..], 8)
+            } else {
+                digits.parse()
+            };
+            n.map_err(|_| "bad expression".to_string())
+        }
+    }
+}
+
+fn eval(s: &[u8]) -> Result<i64, String> {
+    let mut e = Eval { s, pos: 0 };
+    let n = e.binary(0)?;
+    e.skip();
+    if e.pos < s.len() {
+        return Err("bad expression".to_string());
+    }
+    Ok(n)
+}
+
+/// Formats a number in a radix, with at least the given number of digits.
+fn radix(n: i64, radix: u32, width: usize) -> String {
+    let mut digits = Vec::new();
+    let mut m = n.unsigned_abs();
+    loop {
+        digits.push(char::from_digit((m % radix as u64) as u32, radix).unwrap());
+        m /= radix as u64;
+        if m == 0 {
+            break;
+        }
+    }
+    while digits.len() < width {
+        digits.push('0');
+    }
+    if n < 0 {
+        digits.push('-');
+    }
+    digits.iter().rev().collect()
+}
+
+impl<W: Write> M4<W> {
+    fn new(out: W) -> M4<W> {
+        let mut defs = HashMap::new();
+        for &(name, blind) in BUILTINS {
+            defs.insert(name.as_bytes().to_vec(), vec![Def::Builtin(name, blind)]);
+        }
+        M4 {
+            out,
+            defs,
+            pushback: Vec::new(),
+            inputs: Vec::new(),
+            lquote: LQUOTE.to_vec(),
+            rquote: RQUOTE.to_vec(),
+            lcomment: LCOMMENT.to_vec(),
+            rcomment: RCOMMENT.to_vec(),
+            diversion: 0,
+            diversions: BTreeMap::new(),
+            wrap: Vec::new(),
+            sysval: 0,
+            status: 0,
+        }
+    }
+
+    /// Reports an error, at the place in the input it was found.
+    fn error(&mut self, msg: &str) {
+        let _ = self.out.flush();
+        match self.inputs.first() {
+            Some(input) => err!("{}: {}: {}", input.name, input.line, msg),
+            None => err!("{}", msg),
+        }
+        self.status = status::FAILURE;
+    }
+
+    fn push_back(&mut self, s: &[u8]) {
+        self.pushback.extend(s.iter().rev());
+    }
+
+    fn next_char(&mut self) -> Option<u8> {
+        if let Some(c) = self.pushback.pop() {
+            return Some(c);
+        }
+        while let Some(input) = self.inputs.first_mut() {
+            match input.reader.fill_buf() {
+                Ok([c, ..]) => {
+                    let c = *c;
+                    input.reader.consume(1);
+                    if c == b'\n' {
+                        input.line += 1;
+                    }
+                    return Some(c);
+                },
+                Ok([]) => {
+                    self.inputs.remove(0);
+                },
+                Err(e) => {
+                    let msg = format!("{}: {}", self.inputs[0].name, e);
+                    self.inputs.remove(0);
+                    self.error(&msg);
+                },
+            }
+        }
+        None
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        let c = self.next_char()?;
+        self.pushback.push(c);
+        Some(c)
+    }
+
+    /// Reads a string if it comes next in the input.
+    fn eat(&mut self, s: &[u8]) -> bool {
+        if s.is_empty() {
+            return false;
+        }
+        let mut read = Vec::with_capacity(s.len());
+        for &expected in s {
+            match self.next_char() {
+                Some(c) => {
+                    read.push(c);
+                    if c != expected {
+                        break;
+                    }
+                },
+                None => break,
+            }
+        }
+        if read == s {
+            return true;
+        }
+        self.push_back(&read);
+        false
+    }
+
+    fn next_token(&mut self) -> Result<Option<Token>, String> {
+        let lquote = self.lquote.clone();
+        if self.eat(&lquote) {
+            let rquote = self.rquote.clone();
+            let mut s = Vec::new();
+            let mut depth = 1;
+            loop {
+                if self.eat(&rquote) {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(Some(Token::Quoted(s)));
+                    }
+                    s.extend(&rquote);
+                } else if self.eat(&lquote) {
+                    depth += 1;
+                    s.extend(&lquote);
+                } else {
+                    match self.next_char() {
+                        Some(c) => s.push(c),
+                        None => return Err("end of file in string".to_string()),
+                    }
+                }
+            }
+        }
+        let lcomment = self.lcomment.clone();
+        if self.eat(&lcomment) {
+            let rcomment = self.rcomment.clone();
+            let mut s = lcomment;
+            loop {
+                if self.eat(&rcomment) {
+                    s.extend(&rcomment);
+                    return Ok(Some(Token::Comment(s)));
+                }
+                match self.next_char() {
+                    Some(c) => s.push(c),
+                    None => return Ok(Some(Token::Comment(s))),
+                }
+            }
+        }
+        let c = match self.next_char() {
+            Some(c) => c,
+            None => return Ok(None),
+        };
+        if !(c.is_ascii_alphabetic() || c == b'_') {
+            return Ok(Some(Token::Char(c)));
+        }
+        let mut name = vec![c];
+        while let Some(c) = self.peek().filter(|&c| c.is_ascii_alphanumeric() || c == b'_') {
+            name.push(c);
+            self.next_char();
+        }
+        Ok(Some(Token::Name(name)))
+    }
+
+    /// Returns whether a name is to be expanded as a macro where it is.
+    fn is_call(&mut self, name: &[u8]) -> bool {
+        match self.defs.get(name).and_then(|defs| defs.last()) {
+            Some(Def::Builtin(_, true)) => self.peek() == Some(b'('),
+            Some(_) => true,
+            None => false,
+        }
+    }
+
+    /// Collects the arguments of a macro call, after its opening
+    /// parenthesis, expanding the macros in them.
+    fn collect_args(&mut self) -> Result<Vec<Vec<u8>>, String> {
+        let mut args = Vec::new();
+        let mut arg = Vec::new();
+        let mut depth = 0;
+        self.skip_space();
+        loop {
+            match self.next_token()? {
+                None => return Err("end of file in argument list".to_string()),
+                Some(Token::Quoted(s) | Token::Comment(s)) => arg.extend(s),
+                Some(Token::Name(name)) => {
+                    if self.is_call(&name) {
+                        self.expand(name)?;
+                    } else {
+                        arg.extend(name);
+                    }
+                },
+                Some(Token::Char(b'(')) => {
+                    depth += 1;
+                    arg.push(b'(');
+                },
+                Some(Token::Char(b')')) if depth == 0 => {
+                    args.push(arg);
+                    return Ok(args);
+                },
+                Some(Token::Char(b')')) => {
+                    depth -= 1;
+                    arg.push(b')');
+                },
+                Some(Token::Char(b',')) if depth == 0 => {
+                    args.push(std::mem::take(&mut arg));
+                    self.skip_space();
+                },
+                Some(Token::Char(c)) => arg.push(c),
+            }
+        }
+    }
+
+    fn skip_space(&mut self) {
+        while let Some(c) = self.peek() {
+            if !c.is_ascii_whitespace() {
+                break;
+            }
+            self.next_char();
+        }
+    }
+
+    /// Expands a macro call, pushing back its result to be read again.
+    fn expand(&mut self, name: Vec<u8>) -> Result<(), String> {
+        let def = self.defs[&name].last().unwrap().clone();
+        let mut args = vec![name];
+        if self.peek() == Some(b'(') {
+            self.next_char();
+            args.extend(self.collect_args()?);
+        }
+        let result = match def {
+            Def::Builtin(builtin, _) => self.builtin(builtin, &args)?,
+            Def::Text(text) => self.substitute(&text, &args),
+        };
+        self.push_back(&result);
+        Ok(())
+    }
+
+    fn quote(&self, s: &[u8]) -> Vec<u8> {
+        [&self.lquote[..], s, &self.rquote[..]].concat()
+    }
+
+    /// Replaces the references to arguments in a macro's definition: $0 is
+    /// its name, $1 to $9 its arguments, $# their number, $* them all and
+    /// $@ them all quoted.
+    fn substitute(&self, text: &[u8], args: &[Vec<u8>]) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut i = 0;
+        while i < text.len() {
+            if text[i] != b'$' || i + 1 == text.len() {
+                out.push(text[i]);
+                i += 1;
+                continue;
+            }
+            match text[i + 1] {
+                c @ b'0'..=b'9' => {
+                    if let Some(arg) = args.get((c - b'0') as usize) {
+                        out.extend(arg);
+                    }
+                },
+                b'#' => out.extend((args.len() - 1).to_string().bytes()),
+                b'*' => out.extend(args[1..].join(&b","[..])),
+                b'@' => out.extend(args[1..].iter().map(|arg| self.quote(arg)).collect::<Vec<_>>().join(&b","[..])),
+                c => {
+                    out.push(b'$');
+                    out.push(c);
+                },
+            }
+            i += 2;
+        }
+        out
+    }
+
+    /// Writes text to the current diversion.
+    fn emit(&mut self, s: &[u8]) {
+        match self.diversion {
+            0 => {
+                if let Err(e) = self.out.write_all(s) {
+                    err!("{}", e);
+                    process::exit(status::FAILURE);
+                }
+            },
+            n if n > 0 => self.diversions.entry(n).or_default().extend(s),
+            _ => (),
+        }
+    }
+
+    /// Writes out a diversion, into the current one.
+    fn undivert(&mut self, n: i64) {
+        if n != self.diversion {
+            if let Some(text) = self.diversions.remove(&n) {
+                self.emit(&text);
+            }
+        }
+    }
+
+    /// Runs a command, returning its output if it is to be captured.
+    fn system(&mut self, command: &[u8], capture: bool) -> Vec<u8> {
+        let _ = self.out.flush();
+        let mut cmd = process::Command::new("sh");
+        cmd.arg("-c").arg(String::from_utf8_lossy(command).as_ref());
+        let result = if capture {
+            cmd.output().map(|output| (output.status, output.stdout))
+        } else {
+            cmd.status().map(|status| (status, Vec::new()))
+        };
+        match result {
+            Ok((status, stdout)) => {
+                self.sysval = status.code().unwrap_or(127);
+                stdout
+            },
+            Err(e) => {
+                self.error(&format!("sh: {}", e));
+                self.sysval = 127;
+                Vec::new()
+            },
+        }
+    }
+
+    fn builtin(&mut self, name: &str, args: &[Vec<u8>]) -> Result<Vec<u8>, String> {
+        let arg = |i: usize| args.get(i).map_or(&[][..], |arg| &arg[..]);
+        let n = args.len() - 1;
+        let mut result = Vec::new();
+        match name {
+            "define" | "pushdef" if !arg(1).is_empty() => {
+                let stack = self.defs.entry(arg(1).to_vec()).or_default();
+                if name == "define" {
+                    stack.pop();
+                }
+                stack.push(Def::Text(arg(2).to_vec()));
+            },
+            "undefine" => for name in &args[1..] {
+                self.defs.remove(name);
+            },
+            "popdef" => for name in &args[1..] {
+                if let Some(stack) = self.defs.get_mut(name) {
+                    stack.pop();
+                    if stack.is_empty() {
+                        self.defs.remove(name);
+                    }
+                }
+            },
+            "defn" => for name in &args[1..] {
+                if let Some(Def::Text(text)) = self.defs.get(name).and_then(|defs| defs.last()) {
+                    result.extend(self.quote(text));
+                }
+            },
+            "ifdef" => {
+                let i = if self.defs.contains_key(arg(1)) { 2 } else { 3 };
+                result.extend(arg(i));
+            },
+            "ifelse" => {
+                // Pairs are compared in turn, and a last odd one is the
+                // result if none match.
+                let mut rest = &args[1..];
+                while rest.len() >= 3 {
+                    if rest[0] == rest[1] {
+                        result.extend(&rest[2]);
+                        return Ok(result);
+                    }
+                    rest = &rest[3..];
+                }
+                if let [otherwise] = rest {
+                    result.extend(otherwise);
+                }
+            },
+            "include" | "sinclude" => {
+                let file = String::from_utf8_lossy(arg(1)).into_owned();
+                match fs::read(&file) {
+                    Ok(text) => result = text,
+                    Err(e) if name == "include" => self.error(&format!("{}: {}", file, e)),
+                    Err(_) => (),
+                }
+            },
+            "divert" => self.diversion = if n == 0 { 0 } else { int(arg(1)).unwrap_or(0) },
+            "undivert" if n == 0 => {
+                let all: Vec<i64> = self.diversions.keys().copied().collect();
+                for n in all {
+                    self.undivert(n);
+                }
+            },
+            "undivert" => for arg in &args[1..] {
+                match int(arg) {
+                    Some(n) => self.undivert(n),
+                    None => self.error(&format!("undivert: {}: bad diversion", String::from_utf8_lossy(arg))),
+                }
+            },
+            "divnum" => result.extend(self.diversion.to_string().bytes()),
+            "dnl" => while self.next_char().is_some_and(|c| c != b'\n') {},
+            "changequote" => {
+                if n == 0 {
+                    (self.lquote, self.rquote) = (LQUOTE.to_vec(), RQUOTE.to_vec());
+                } else {
+                    self.lquote = arg(1).to_vec();
+                    self.rquote = if n > 1 { arg(2).to_vec() } else { RQUOTE.to_vec() };
+                }
+            },
+            "changecom" => {
+                self.lcomment = arg(1).to_vec();
+                self.rcomment = if n > 1 { arg(2).to_vec() } else { RCOMMENT.to_vec() };
+            },
+            "eval" => {
+                let base = if n > 1 { int(arg(2)).unwrap_or(0) } else { 10 };
+                let width = if n > 2 { int(arg(3)).unwrap_or(0) } else { 1 };
+                if !(2..=36).contains(&base) {
+                    self.error("eval: bad radix");
+                } else {
+                    match eval(arg(1)) {
+                        Ok(value) => result.extend(radix(value, base as u32, width.max(0) as usize).bytes()),
+                        Err(msg) => self.error(&format!("eval: {}: {}", msg, String::from_utf8_lossy(arg(1)))),
+                    }
+                }
+            },
+            "incr" | "decr" => match int(arg(1)) {
+                Some(value) => {
+                    let value = if name == "incr" { value.wrapping_add(1) } else { value.wrapping_sub(1) };
+                    result.extend(value.to_string().bytes());
+                },
+                None => self.error(&format!("{}: bad number: {}", name, String::from_utf8_lossy(arg(1)))),
+            },
+            "len" => result.extend(arg(1).len().to_string().bytes()),
+            "index" => {
+                let (s, sub) = (arg(1), arg(2));
+                let i = (0..=s.len().saturating_sub(sub.len())).find(|&i| s[i..].starts_with(sub));
+                result.extend(i.map_or(-1, |i| i as i64).to_string().bytes());
+            },
+            "substr" => {
+                let s = arg(1);
+                let from = int(arg(2)).unwrap_or(0).max(0) as usize;
+                let len = if n > 2 { int(arg(3)).unwrap_or(0).max(0) as usize } else { s.len() };
+                if from < s.len() {
+                    result.extend(&s[from..s.len().min(from.saturating_add(len))]);
+                }
+            },
+            "translit" => {
+                let (from, to) = (ranges(arg(2)), ranges(arg(3)));
+                for &c in arg(1) {
+                    match from.iter().position(|&f| f == c) {
+                        Some(i) => result.extend(to.get(i)),
+                        None => result.push(c),
+                    }
+                }
+            },
+            "errprint" => {
+                let _ = self.out.flush();
+                eprint!("{}", String::from_utf8_lossy(&args[1..].join(&b" "[..])));
+            },
+            "dumpdef" => {
+                let mut names: Vec<Vec<u8>> = if n == 0 { self.defs.keys().cloned().collect() } else { args[1..].to_vec() };
+                names.sort();
+                let _ = self.out.flush();
+                for name in names {
+                    match self.defs.get(&name).and_then(|defs| defs.last()) {
+                        Some(Def::Text(text)) => eprintln!("{}:\t{}", String::from_utf8_lossy(&name), String::from_utf8_lossy(text)),
+                        Some(Def::Builtin(builtin, _)) => eprintln!("{}:\t<{}>", String::from_utf8_lossy(&name), builtin),
+                        None => self.error(&format!("dumpdef: {}: undefined", String::from_utf8_lossy(&name))),
+                    }
+                }
+            },
+            "syscmd" => {
+                self.system(arg(1), false);
+            },
+            "esyscmd" => result = self.system(arg(1), true),
+            "sysval" => result.extend(self.sysval.to_string().bytes()),
+            "maketemp" => {
+                // The Xs at the end of the template become the process id.
+                let template = arg(1);
+                let xs = template.iter().rev().take_while(|&&c| c == b'X').count();
+                let pid = format!("{:0width$}", process::id(), width = xs);
+                result.extend(&template[..template.len() - xs]);
+                result.extend(&pid.as_bytes()[pid.len().saturating_sub(xs)..]);
+            },
+            "m4exit" => {
+                let _ = self.out.flush();
+                process::exit(if n == 0 { 0 } else { int(arg(1)).unwrap_or(1) as i32 });
+            },
+            "m4wrap" => self.wrap.extend(arg(1)),
+            "shift" if n > 1 => {
+                result = args[2..].iter().map(|arg| self.quote(arg)).collect::<Vec<_>>().join(&b","[..]);
+            },
+            _ => (),
+        }
+        Ok(result)
+    }
+
+    /// Reads and expands the input, then writes out the diversions.
+    fn run(&mut self) {
+        loop {
+            let token = match self.next_token() {
+                Ok(token) => token,
+                Err(msg) => {
+                    self.error(&msg);
+                    break;
+                },
+            };
+            match token {
+                None if !self.wrap.is_empty() => {
+                    let wrap = std::mem::take(&mut self.wrap);
+                    self.push_back(&wrap);
+                },
+                None => break,
+                Some(Token::Name(name)) if self.is_call(&name) => {
+                    if let Err(msg) = self.expand(name) {
+                        self.error(&msg);
+                        break;
+                    }
+                },
+                Some(Token::Name(s) | Token::Quoted(s) | Token::Comment(s)) => self.emit(&s),
+                Some(Token::Char(c)) => self.emit(&[c]),
+            }
+        }
+        self.diversion = 0;
+        let all: Vec<i64> = self.diversions.keys().copied().collect();
+        for n in all {
+            self.undivert(n);
+        }
+        if let Err(e) = self.out.flush() {
+            err!("{}", e);
+            self.status = status::FAILURE;
+        }
+    }
+}
+
+pub const SPEC: Spec = Spec::new("macro processor", &[
+    Opt::arg('D', "name[=value]", "define name"),
+    Opt::arg('U', "name", "undefine name"),
+], "[file ...]");
+
+pub fn run(args: Vec<OsString>) -> i32 {
+    let parsed = SPEC.parse(args);
+    let stdout = io::stdout();
+    let mut m4 = M4::new(io::BufWriter::new(stdout.lock()));
+
+    for (c, arg) in parsed.opts() {
+        match (c, arg) {
+            ('D', Some(arg)) => {
+                let (name, value) = arg.split_once('=').unwrap_or((arg, ""));
+                m4.defs.insert(name.as_bytes().to_vec(), vec![Def::Text(value.as_bytes().to_vec())]);
+            },
+            ('U', Some(arg)) => {
+                m4.defs.remove(arg.as_bytes());
+            },
+            _ => (),
+        }
+    }
+    let mut files = parsed.operands;
+
+    if files.is_empty() {
+        files.push(OsString::from("-"));
+    }
+    for file in files {
+        let reader: Box<dyn BufRead> = if file == "-" {
+            Box::new(BufReader::new(io::stdin()))
+        } else {
+            match File::open(&file) {
+                Ok(f) => Box::new(BufReader::new(f)),
+                Err(e) => {
+                    err!("{}: {}", file.to_string_lossy(), e);
+                    process::exit(status::FAILURE);
+                }
+            }
+        };
+        // The name is only ever shown in messages.
+        m4.inputs.push(Input { name: file.to_string_lossy().into_owned(), reader, line: 1 });
+    }
+    m4.run();
+    m4.status
+}
+
+#[test]
+fn test_m4() {
+    let run = |input: &str| {
+        let mut m4 = M4::new(Vec::new());
+        let reader = Box::new(io::Cursor::new(input.as_bytes().to_vec()));
+        m4.inputs.push(Input { name: "-".to_string(), reader, line: 1 });
+        m4.run();
+        String::from_utf8(m4.out).unwrap()
+    };
+    assert_eq!(run("define(`x', `hello')x, `x' # x\ndefine it\n"), "hello, x # x\ndefine it\n");
+    assert_eq!(run("define(`add', `eval($1 + $2)')add(2, 3) add(10, -3)\n"), "5 7\n");
+    assert_eq!(run("define(`f', `$# `$0' $2')f(a, (b, c), `d')\n"), "3 f (b, c)\n");
+    assert_eq!(run("define(`n', `$#')define(`s', `n($*)')define(`q', `n($@)')s(a, `b,c') q(a, `b,c')\n"), "3 2\n");
+    assert_eq!(run(concat!(
+        "define(`fact', `ifelse($1, 0, 1, `eval($1 * fact(decr($1)))')')dnl\n",
+        "fact(10)\n")), "3628800\n");
+    assert_eq!(run("ifdef(`x', yes, no) ifelse(a, b, 1, a, a, 2, 3) ifelse(a, b, 1, 4)\n"), "no 2 4\n");
+    assert_eq!(run("divert(2)two\ndivert(1)one\ndivert(-1)gone\ndivert`'zero\n"), "zero\none\ntwo\n");
+    assert_eq!(run("divert(1)a\ndivert(2)b\ndivert(0)undivert(2)c\n"), "b\nc\na\n");
+    assert_eq!(run("changequote([, ])define([x], [`y'])x [x]\n"), "`y' x\n");
+    assert_eq!(run("eval(2 ** 10 - 7 % 4 << 1) eval(1 < 2 && !0) eval(255, 16) eval(5, 2, 8)\n"),
+               "2042 1 ff 00000101\n");
+    assert_eq!(run("len(`abc') index(`hello', `ll') substr(`hello', 1, 3) translit(`hello', `a-z', `A-Z')\n"),
+               "3 2 ell HELLO\n");
+    assert_eq!(run("shift(a, b, c) translit(`hello', `lo')\n"), "b,c he\n");
+    assert_eq!(run("pushdef(`x', 1)pushdef(`x', 2)x popdef(`x')x undefine(`x')x\n"), "2 1 x\n");
+    assert_eq!(run("define(`y', defn(`x'))define(`x', `X')y x m4wrap(`wrapped\n')\n"), " X \nwrapped\n");
+}
@@ -0,0 +1,671 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// An implementation of the make(1) command in Rust.
+// See http://man.cat-v.org/unix-7th/1/make
+use std::collections::HashMap;
+use std::env;
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::io;
+use std::io::{Read, Write};
+use std::process;
+use std::time::SystemTime;
+
+use crate::cmd::{Opt, Spec};
+use crate::err;
+use crate::status;
+use crate::warn;
+
+/// How deeply macros may refer to other macros before it is taken to be
+/// a macro that refers to itself.
+const MAX_DEPTH: usize = 100;
+
+/// How many suffix rules may be chained to make a target.
+const MAX_CHAIN: usize = 3;
+
+/// The rules and macros make knows without a makefile, which use them.
+const BUILTIN: &str = "\
+.SUFFIXES: .o .c .y .l .s .sh
+MAKE = make
+AR = ar
+ARFLAGS = -rv
+AS = as
+CC = cc
+CFLAGS = -O
+LDFLAGS =
+LEX = lex
+LFLAGS =
+YACC = yacc
+YFLAGS =
+.c:
+\t$(CC) $(CFLAGS) $(LDFLAGS) -o $@ $<
+.sh:
+\tcp $< $@
+\tchmod a+x $@
+.c.o:
+\t$(CC) $(CFLAGS) -c $<
+.s.o:
+\t$(AS) -o $@ $<
+.y.o:
+\t$(YACC) $(YFLAGS) $<
+\t$(CC) $(CFLAGS) -c y.tab.c
+\trm -f y.tab.c
+\tmv y.tab.o $@
+.l.o:
+\t$(LEX) $(LFLAGS) $<
+\t$(CC) $(CFLAGS) -c lex.yy.c
+\trm -f lex.yy.c
+\tmv lex.yy.o $@
+.y.c:
+\t$(YACC) $(YFLAGS) $<
+\tmv y.tab.c $@
+.l.c:
+\t$(LEX) $(LFLAGS) $<
+\tmv lex.yy.c $@
+";
+
+/// Where a macro was defined. Definitions from later in the list take
+/// precedence over earlier ones, whatever order they are made in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Origin {
+    Builtin,
+    Environment,
+    Makefile,
+    CommandLine,
+}
+
+#[derive(Debug)]
+struct Macro {
+    value: String,
+    origin: Origin,
+}
+
+/// A target and how to make it.
+#[derive(Debug, Default)]
+struct Target {
+    prereqs: Vec<String>,
+    commands: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+struct Makefile {
+    macros: HashMap<String, Macro>,
+    targets: HashMap<String, Target>,
+    /// The first target defined, which is made if none is named.
+    default: Option<String>,
+    /// The suffixes of .SUFFIXES, in the order suffix rules are tried.
+    suffixes: Vec<String>,
+}
+
+/// The macros make defines for the commands of each target.
+#[derive(Debug, Default)]
+struct Automatic {
+    /// $@, the target.
+    target: String,
+    /// $<, the prerequisite a suffix rule was chosen for or, for other
+    /// rules, the first.
+    implied: String,
+    /// $*, the target without its suffix.
+    stem: String,
+    /// $?, the prerequisites newer than the target.
+    newer: Vec<String>,
+}
+
+impl Makefile {
+    fn define(&mut self, name: &str, value: &str, origin: Origin) {
+        if self.macros.get(name).is_some_and(|m| m.origin > origin) {
+            return;
+        }
+        self.macros.insert(name.to_string(), Macro { value: value.to_string(), origin });
+    }
+
+    /// Replaces the macros in a string with their values, which are
+    /// themselves expanded.
+    fn expand(&self, s: &str) -> String {
+        self.expand_depth(s, None, 0)
+    }
+
+    /// Expands the macros in a command, including those for its target.
+    fn expand_for(&self, s: &str, auto: &Automatic) -> String {
+        self.expand_depth(s, Some(auto), 0)
+    }
+
+    fn expand_depth(&self, s: &str, auto: Option<&Automatic>, depth: usize) -> String {
+        let mut out = String::new();
+        let mut chars = s.char_indices();
+        while let Some((_, c)) = chars.next() {
+            if c != '$' {
+                out.push(c);
+                continue;
+            }
+            let reference = match chars.next() {
+                None => {
+                    out.push('$');
+                    break;
+                },
+                Some((_, '$')) => {
+                    out.push('$');
+                    continue;
+                },
+                Some((start, open @ ('(' | '{'))) => {
+                    let close = if open == '(' { ')' } else { '}' };
+                    let mut nesting = 0;
+                    let mut end = s.len();
+                    for (i, c) in chars.by_ref() {
+                        if c == open {
+                            nesting += 1;
+                        } else if c == close {
+                            if nesting == 0 {
+                                end = i;
+                                break;
+                            }
+                            nesting -= 1;
+                        }
+                    }
+                    // The name may itself use macros.
+                    self.expand_depth(&s[start + 1..end], auto, depth)
+                },
+                Some((_, c)) => c.to_string(),
+            };
+            out.push_str(&self.reference(&reference, auto, depth));
+        }
+        out
+    }
+
+    /// Returns the value of a macro reference, which may substitute one
+    /// suffix for another in each word, as in $(OBJS:.o=.c).
+    fn reference(&self, reference: &str, auto: Option<&Automatic>, depth: usize) -> String {
+        let (name, subst) = match reference.split_once(':') {
+            Some((name, subst)) => (name, subst.split_once('=')),
+            None => (reference, None),
+        };
+        let value = match (name, auto) {
+            ("@", Some(auto)) => auto.target.clone(),
+            ("<", Some(auto)) => auto.implied.clone(),
+            ("*", Some(auto)) => auto.stem.clone(),
+            ("?", Some(auto)) => auto.newer.join(" "),
+            _ => match self.macros.get(name) {
+                Some(m) if depth < MAX_DEPTH => self.expand_depth(&m.value, auto, depth + 1),
+                _ => String::new(),
+            },
+        };
+        match subst {
+            Some((from, to)) => value.split_whitespace().map(|word| match word.strip_suffix(from) {
+                Some(stem) => format!("{}{}", stem, to),
+                None => word.to_string(),
+            }).collect::<Vec<_>>().join(" "),
+            None => value,
+        }
+    }
+
+    /// Finds a suffix rule for a target, returning the prerequisite it
+    /// makes the target from, the target's stem and the rule.
+    fn infer(&self, name: &str) -> Option<(String, String, &Target)> {
+        self.infer_depth(name, 0)
+    }
+
+    /// Finds a suffix rule whose prerequisite exists, is a target or can
+    /// itself be made by a suffix rule, as a .c file can from a .y.
+    fn infer_depth(&self, name: &str, depth: usize) -> Option<(String, String, &Target)> {
+        let exists = |name: &str| {
+            self.targets.contains_key(name) || fs::metadata(name).is_ok()
+                || (depth < MAX_CHAIN && self.infer_depth(name, depth + 1).is_some())
+        };
+        // A rule such as .c.o makes a file with the second suffix from one
+        // with the first.
+        for to in self.suffixes.iter().filter(|to| name.len() > to.len() && name.ends_with(to.as_str())) {
+            let stem = &name[..name.len() - to.len()];
+            for from in &self.suffixes {
+                if let Some(rule) = self.targets.get(&format!("{}{}", from, to)) {
+                    let implied = format!("{}{}", stem, from);
+                    if exists(&implied) {
+                        return Some((implied, stem.to_string(), rule));
+                    }
+                }
+            }
+        }
+        // A rule such as .c makes a file from one with the suffix added.
+        for from in &self.suffixes {
+            if let Some(rule) = self.targets.get(from) {
+                let implied = format!("{}{}", name, from);
+                if exists(&implied) {
+                    return Some((implied, name.to_string(), rule));
+                }
+            }
+        }
+        None
+    }
+
+    /// Reads the rules and macro definitions of a makefile.
+    fn parse(&mut self, text: &str, file: &str, origin: Origin) -> Result<(), String> {
+        let lines: Vec<&str> = text.lines().collect();
+        // The targets of the rule that command lines belong to, and
+        // whether it has any yet.
+        let mut rule: Option<(Vec<String>, bool)> = None;
+        let mut i = 0;
+        while i < lines.len() {
+            let lineno = i + 1;
+            let error = |msg: &str| Err(format!("{}: {}: {}", file, lineno, msg));
+            // A line ending in a backslash goes on on the next.
+            let mut line = lines[i].to_string();
+            let command = line.starts_with('\t');
+            while line.ends_with('\\') && i + 1 < lines.len() {
+                i += 1;
+                if command {
+                    // The shell sees the backslash and newline.
+                    line.push('\n');
+                    line.push_str(lines[i].strip_prefix('\t').unwrap_or(lines[i]));
+                } else {
+                    line.pop();
+                    line.push(' ');
+                    line.push_str(lines[i].trim_start());
+                }
+            }
+            i += 1;
+
+            if command && !line.trim().is_empty() {
+                match &mut rule {
+                    Some((targets, started)) => {
+                        for target in targets.iter() {
+                            let commands = &mut self.targets.get_mut(target).unwrap().commands;
+                            // The commands of a later rule for a target
+                            // replace those of an earlier one.
+                            if !*started {
+                                commands.clear();
+                            }
+                            commands.push(line[1..].to_string());
+                        }
+                        *started = true;
+                    },
+                    None => return error("commands commence before first target"),
+                }
+                continue;
+            }
+            let line = match line.find('#') {
+                Some(hash) => &line[..hash],
+                None => &line,
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            rule = None;
+
+            if let Some(files) = line.strip_prefix("include ") {
+                for name in self.expand(files).split_whitespace() {
+                    let text = fs::read_to_string(name).map_err(|e| format!("{}: {}", name, e))?;
+                    self.parse(&text, name, origin)?;
+                }
+                continue;
+            }
+
+            let colon = line.find(':');
+            match line.find('=') {
+                Some(eq) if colon.is_none_or(|colon| eq < colon) => {
+                    let name = line[..eq].trim();
+                    if name.is_empty() || name.contains(char::is_whitespace) {
+                        return error("invalid macro name");
+                    }
+                    self.define(name, line[eq + 1..].trim(), origin);
+                    continue;
+                },
+                _ => (),
+            }
+
+            let line = self.expand(line);
+            let (targets, rest) = match line.split_once(':') {
+                Some((targets, rest)) => (targets, rest.strip_prefix(':').unwrap_or(rest)),
+                None => return error("missing separator"),
+            };
+            let (prereqs, command) = match rest.split_once(';') {
+                Some((prereqs, command)) => (prereqs, Some(command.trim_start())),
+                None => (rest, None),
+            };
+            let targets: Vec<String> = targets.split_whitespace().map(str::to_string).collect();
+            if targets.is_empty() {
+                return error("missing target");
+            }
+            // .SUFFIXES adds to the suffixes or, with none, clears them.
+            if targets == [".SUFFIXES"] {
+                match prereqs.split_whitespace().map(str::to_string).collect::<Vec<_>>() {
+                    suffixes if suffixes.is_empty() => self.suffixes.clear(),
+                    suffixes => self.suffixes.extend(suffixes),
+                }
+                continue;
+            }
+            for name in &targets {
+                let target = self.targets.entry(name.clone()).or_default();
+                target.prereqs.extend(prereqs.split_whitespace().map(str::to_string));
+                if let Some(command) = command {
+                    target.commands = vec![command.to_string()];
+                }
+                if self.default.is_none() && !name.starts_with('.') {
+                    self.default = Some(name.clone());
+                }
+            }
+            rule = Some((targets, command.is_some()));
+        }
+        Ok(())
+    }
+}
+
+/// The state of a target that has been considered.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Status {
+    /// Its prerequisites are being made.
+    Building,
+    /// It was remade, or would have been had commands been run.
+    Remade,
+    /// It was up to date, having been modified at the given time.
+    Current(SystemTime),
+    Failed,
+}
+
+/// Returns a command without the prefixes that say how to run it, and
+/// whether they say not to echo it, to ignore its failure and to run it
+/// even with -n.
+fn prefixes(mut command: &str) -> (&str, bool, bool, bool) {
+    let (mut silent, mut ignore, mut always) = (false, false, false);
+    loop {
+        match command.chars().next() {
+            Some('@') => silent = true,
+            Some('-') => ignore = true,
+            Some('+') => always = true,
+            Some(' ' | '\t') => (),
+            _ => return (command, silent, ignore, always),
+        }
+        command = &command[1..];
+    }
+}
+
+struct Make<'a, W: Write> {
+    makefile: &'a Makefile,
+    out: W,
+    dry_run: bool,
+    keep_going: bool,
+    silent: bool,
+    ignore: bool,
+    status: HashMap<String, Status>,
+}
+
+impl<W: Write> Make<'_, W> {
+    /// Makes a target after its prerequisites, returning its status,
+    /// which is Failed if it could not be made.
+    fn make(&mut self, name: &str) -> Status {
+        match self.status.get(name) {
+            Some(Status::Building) => {
+                err!("{}: circular dependency", name);
+                return Status::Failed;
+            },
+            Some(&status) => return status,
+            None => (),
+        }
+        self.status.insert(name.to_string(), Status::Building);
+        let status = self.remake(name);
+        self.status.insert(name.to_string(), status);
+        status
+    }
+
+    /// Returns whether a special target such as .SILENT applies to a
+    /// target, which it does to all if it has no prerequisites.
+    fn special(&self, special: &str, name: &str) -> bool {
+        self.makefile.targets.get(special).is_some_and(|t| t.prereqs.is_empty() || t.prereqs.iter().any(|p| p == name))
+    }
+
+    fn remake(&mut self, name: &str) -> Status {
+        let makefile = self.makefile;
+        let mtime = fs::metadata(name).and_then(|m| m.modified()).ok();
+        let target = makefile.targets.get(name);
+        // A target without commands of its own may be made by a suffix
+        // rule.
+        let inferred = match target {
+            Some(target) if !target.commands.is_empty() => None,
+            _ => makefile.infer(name),
+        };
+        if target.is_none() && inferred.is_none() {
+            return match mtime {
+                Some(mtime) => Status::Current(mtime),
+                None => {
+                    err!("don't know how to make {}", name);
+                    Status::Failed
+                },
+            };
+        }
+
+        let mut prereqs: Vec<&str> = target.map_or(Vec::new(), |t| t.prereqs.iter().map(String::as_str).collect());
+        if let Some((implied, _, _)) = &inferred {
+            prereqs.insert(0, implied);
+        }
+        let mut newer = Vec::new();
+        let mut failed = false;
+        for prereq in &prereqs {
+            match self.make(prereq) {
+                Status::Remade => newer.push(prereq.to_string()),
+                Status::Current(t) if mtime.is_none_or(|mtime| t > mtime) => newer.push(prereq.to_string()),
+                Status::Current(_) => (),
+                _ => {
+                    failed = true;
+                    if !self.keep_going {
+                        return Status::Failed;
+                    }
+                },
+            }
+        }
+        if failed {
+            err!("{} not remade because of errors", name);
+            return Status::Failed;
+        }
+        if let Some(mtime) = mtime.filter(|_| newer.is_empty()) {
+            return Status::Current(mtime);
+        }
+
+        let (commands, auto) = match inferred {
+            Some((implied, stem, rule)) => (&rule.commands, Automatic { target: name.to_string(), implied, stem, newer }),
+            None => {
+                let stem = makefile.suffixes.iter().find_map(|s| name.strip_suffix(s.as_str())).unwrap_or(name);
+                (&target.unwrap().commands, Automatic {
+                    target: name.to_string(),
+                    implied: prereqs.first().map_or(String::new(), |p| p.to_string()),
+                    stem: stem.to_string(),
+                    newer,
+                })
+            },
+        };
+        for command in commands {
+            let (command, silent, ignore, always) = prefixes(command);
+            let silent = silent || self.silent || self.special(".SILENT", name);
+            let ignore = ignore || self.ignore || self.special(".IGNORE", name);
+            let command = makefile.expand_for(command, &auto);
+            if !silent || self.dry_run {
+                let _ = writeln!(self.out, "{}", command);
+                let _ = self.out.flush();
+            }
+            if self.dry_run && !always {
+                continue;
+            }
+            let error = match process::Command::new("/bin/sh").arg("-c").arg(&command).status() {
+                Ok(status) if status.success() => continue,
+                Ok(status) => match status.code() {
+                    Some(code) => format!("{}: error code {}", name, code),
+                    None => format!("{}: command terminated by a signal", name),
+                },
+                Err(e) => format!("sh: {}", e),
+            };
+            if ignore {
+                warn!("{} (ignored)", error);
+            } else {
+                err!("{}", error);
+                return Status::Failed;
+            }
+        }
+        Status::Remade
+    }
+}
+
+pub const SPEC: Spec = Spec::new("maintain program groups", &[
+    Opt::arg('f', "makefile", "read makefile rather than makefile or Makefile"),
+    Opt::flag('i', "ignore errors from commands"),
+    Opt::flag('k', "keep going with unrelated targets after an error"),
+    Opt::flag('n', "print commands rather than running them"),
+    Opt::flag('r', "use no built-in rules"),
+    Opt::flag('s', "do not print commands before running them"),
+], "[name=value ...] [target ...]");
+
+pub fn run(args: Vec<OsString>) -> i32 {
+    let parsed = SPEC.parse(args);
+    let mut files: Vec<OsString> = parsed.values_os('f').map(OsStr::to_os_string).collect();
+    let dry_run = parsed.flag('n');
+    let keep_going = parsed.flag('k');
+    let ignore = parsed.flag('i');
+    let builtin = !parsed.flag('r');
+    let silent = parsed.flag('s');
+    let mut goals = Vec::new();
+    let mut makefile = Makefile::default();
+
+    for (name, value) in env::vars() {
+        // The shell that runs commands isn't the user's.
+        if name != "SHELL" {
+            makefile.define(&name, &value, Origin::Environment);
+        }
+    }
+
+    for arg in &parsed.operands {
+        // A makefile is text, so a target that isn't can't be made.
+        let Some(arg) = arg.to_str() else {
+            err!("don't know how to make {}", arg.to_string_lossy());
+            process::exit(status::FAILURE);
+        };
+        match arg.split_once('=') {
+            Some((name, value)) => makefile.define(name.trim(), value, Origin::CommandLine),
+            None => goals.push(arg.to_string()),
+        }
+    }
+
+    if builtin {
+        makefile.parse(BUILTIN, "builtin rules", Origin::Builtin).unwrap();
+    }
+    if files.is_empty() {
+        if let Some(name) = ["makefile", "Makefile"].iter().find(|name| fs::metadata(name).is_ok()) {
+            files.push(OsString::from(name));
+        }
+    }
+    for file in &files {
+        let text = if file == "-" {
+            let mut text = String::new();
+            io::stdin().read_to_string(&mut text).map(|_| text)
+        } else {
+            fs::read_to_string(file)
+        };
+        let file = file.to_string_lossy();
+        let result = match text {
+            Ok(text) => makefile.parse(&text, &file, Origin::Makefile),
+            Err(e) => Err(format!("{}: {}", file, e)),
+        };
+        if let Err(msg) = result {
+            err!("{}", msg);
+            process::exit(status::FAILURE);
+        }
+    }
+
+    if goals.is_empty() {
+        match &makefile.default {
+            Some(default) => goals.push(default.clone()),
+            None => {
+                err!("no targets");
+                process::exit(status::FAILURE);
+            }
+        }
+    }
+    let stdout = io::stdout();
+    let mut make = Make {
+        makefile: &makefile,
+        out: stdout.lock(),
+        dry_run,
+        keep_going,
+        silent,
+        ignore,
+        status: HashMap::new(),
+    };
+    let mut status = status::SUCCESS;
+    for goal in &goals {
+        match make.make(goal) {
+            Status::Current(_) => println!("`{}' is up to date.", goal),
+            Status::Failed if keep_going => status = 1,
+            Status::Failed => process::exit(status::FAILURE),
+            _ => (),
+        }
+    }
+    status
+}
+
+#[test]
+fn test_make() {
+    let mut makefile = Makefile::default();
+    makefile.define("CC", "gcc", Origin::CommandLine);
+    makefile.parse(concat!(
+        "# A comment\n",
+        "CC = cc\n",
+        "SRCS = a.c \\\n",
+        "\tb.c\n",
+        "OBJS = $(SRCS:.c=.o)\n",
+        "P = prog$$\n",
+        "\n",
+        "$(P): $(OBJS) make-test-missing.h\n",
+        "\t$(CC) -o $P ${OBJS}\n",
+        "\n",
+        "a.o: a.c ; $(CC) -c a.c\n",
+        "b.o: b.c\n",
+        "\t$(CC) -c \\\n",
+        "\tb.c\n",
+        "a.c b.c make-test-missing.h:\n",
+        ".PHONY: all\n",
+    ), "makefile", Origin::Makefile).unwrap();
+    assert_eq!(makefile.default.as_deref(), Some("prog$"));
+    assert_eq!(makefile.expand("$(OBJS) $(CC) $(NONE)x $$"), "a.o b.o gcc x $");
+    assert_eq!(makefile.targets["prog$"].prereqs, ["a.o", "b.o", "make-test-missing.h"]);
+    assert_eq!(makefile.targets["b.o"].commands, ["$(CC) -c \\\nb.c"]);
+    assert!(Makefile::default().parse("\techo\n", "makefile", Origin::Makefile).is_err());
+    assert!(Makefile::default().parse("nothing\n", "makefile", Origin::Makefile).is_err());
+
+    let mut make = Make {
+        makefile: &makefile,
+        out: Vec::new(),
+        dry_run: true,
+        keep_going: false,
+        silent: false,
+        ignore: false,
+        status: HashMap::new(),
+    };
+    assert_eq!(make.make("prog$"), Status::Remade);
+    assert_eq!(String::from_utf8(make.out).unwrap(), "gcc -c a.c\ngcc -c \\\nb.c\ngcc -o prog$ a.o b.o\n");
+
+    // Suffix rules, built in and not, and the macros for their commands.
+    let mut makefile = Makefile::default();
+    makefile.parse(BUILTIN, "builtin rules", Origin::Builtin).unwrap();
+    makefile.parse(concat!(
+        "CFLAGS = -g\n",
+        ".SUFFIXES: .x\n",
+        ".x.c:\n",
+        "\t@gen $< > $@ # $*\n",
+        "prog: main.o util.o\n",
+        "\t-$(CC) -o $@ main.o util.o\n",
+        "main.x util.c:\n",
+    ), "makefile", Origin::Makefile).unwrap();
+    let mut make = Make {
+        makefile: &makefile,
+        out: Vec::new(),
+        dry_run: true,
+        keep_going: false,
+        silent: false,
+        ignore: false,
+        status: HashMap::new(),
+    };
+    assert_eq!(make.make("prog"), Status::Remade);
+    assert_eq!(String::from_utf8(make.out).unwrap(), concat!(
+        "gen main.x > main.c # main\n",
+        "cc -g -c main.c\n",
+        "cc -g -c util.c\n",
+        "cc -o prog main.o util.o\n"));
+    assert_eq!(prefixes("@- echo"), ("echo", true, true, false));
+    assert_eq!(prefixes("+make"), ("make", false, false, true));
+}
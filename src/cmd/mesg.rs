@@ -0,0 +1,68 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// An implementation of the mesg(1) command in Rust.
+// See http://man.cat-v.org/unix-7th/1/mesg
+use std::ffi::OsString;
+use std::fs;
+use std::fs::Permissions;
+use std::os::unix::fs::PermissionsExt;
+use std::process;
+
+use crate::cmd::Spec;
+use crate::err;
+use crate::status;
+use crate::tty;
+
+/// The exit statuses when messages are allowed, and when they are not.
+const ALLOWED: i32 = status::TRUE;
+const NOT_ALLOWED: i32 = status::FALSE;
+
+/// The permission that lets others write to the terminal. The group of a
+/// terminal is the one that write and wall run as.
+const GROUP_WRITE: u32 = 0o020;
+
+pub const SPEC: Spec = Spec::new("permit or deny messages", &[], "[y | n]");
+
+pub fn run(args: Vec<OsString>) -> i32 {
+    let operands = SPEC.parse(args).strings();
+    let allow = match operands.as_slice() {
+        [] => None,
+        [arg] if arg == "y" => Some(true),
+        [arg] if arg == "n" => Some(false),
+        _ => SPEC.usage(),
+    };
+
+    let tty = match tty::controlling() {
+        Some(tty) => tty,
+        None => {
+            err!("not a terminal");
+            process::exit(status::TROUBLE);
+        }
+    };
+    let mode = match fs::metadata(&tty) {
+        Ok(meta) => meta.permissions().mode(),
+        Err(e) => {
+            err!("{}: {}", tty, e);
+            process::exit(status::TROUBLE);
+        }
+    };
+    let allowed = match allow {
+        None => {
+            let allowed = mode & GROUP_WRITE != 0;
+            println!("is {}", if allowed { "y" } else { "n" });
+            allowed
+        },
+        Some(allow) => {
+            // Refusing messages also takes away any permission for all
+            // users to write to the terminal.
+            let mode = if allow { mode | GROUP_WRITE } else { mode & !(GROUP_WRITE | 0o002) };
+            if let Err(e) = fs::set_permissions(&tty, Permissions::from_mode(mode & 0o7777)) {
+                err!("{}: {}", tty, e);
+                process::exit(status::TROUBLE);
+            }
+            allow
+        },
+    };
+    if allowed { ALLOWED } else { NOT_ALLOWED }
+}
@@ -0,0 +1,75 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// An implementation of the mkdir(1) command in Rust.
+// See http://man.cat-v.org/unix-7th/1/mkdir
+use std::ffi::OsString;
+use std::fs;
+use std::fs::Permissions;
+use std::io;
+use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+use std::path::Path;
+use std::process;
+
+use crate::cmd::{Opt, Spec};
+use crate::diag;
+use crate::err;
+use crate::perm;
+use crate::status;
+use crate::trace;
+
+/// Makes a directory with the mode, as far as the umask allows.
+fn make(path: &Path, mode: u32) -> io::Result<()> {
+    trace::op("mkdir", format_args!("{:?}", path), fs::DirBuilder::new().mode(mode).create(path))
+}
+
+/// Makes the directory and, with -p, any of those leading to it that
+/// don't exist, and gives it the mode, if one is given. An existing
+/// directory is an error only without -p.
+fn mkdir(path: &Path, parents: bool, mode: Option<u32>) -> io::Result<()> {
+    if parents {
+        if path.is_dir() {
+            return Ok(());
+        }
+        // Those leading to it must at least let it be made.
+        let umask = perm::umask();
+        if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            mkdir(parent, true, Some((0o777 & !umask) | 0o300))?;
+        }
+    }
+    make(path, 0o777)?;
+    // The mode given, unlike the default, isn't limited by the umask.
+    if let Some(mode) = mode {
+        let result = fs::set_permissions(path, Permissions::from_mode(mode));
+        trace::op("chmod", format_args!("{:?}, {:04o}", path, mode), result)?;
+    }
+    Ok(())
+}
+
+pub const SPEC: Spec = Spec::new("make directories", &[
+    Opt::flag('p', "make the directories leading to each, and ignore those that exist"),
+    Opt::arg('m', "mode", "give the directories the mode, in octal or symbolically"),
+], "dir ...");
+
+pub fn run(args: Vec<OsString>) -> i32 {
+    let parsed = SPEC.parse(args);
+    let parents = parsed.flag('p');
+    // A symbolic mode is relative to a=rwx.
+    let mode = parsed.value('m').map(|mode| match perm::parse(mode) {
+        Ok(mode) => mode.apply(0o777, true, 0),
+        Err(e) => {
+            err!("{}", e);
+            process::exit(status::USAGE);
+        }
+    });
+    if parsed.operands.is_empty() {
+        SPEC.usage();
+    }
+    for dir in &parsed.operands {
+        let path = Path::new(dir);
+        if let Err(e) = mkdir(path, parents, mode) {
+            err!("{}: {}", path.display(), e);
+        }
+    }
+    diag::status()
+}
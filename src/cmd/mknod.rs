@@ -0,0 +1,92 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// An implementation of the mknod(8) command in Rust.
+// See http://man.cat-v.org/unix-7th/8/mknod
+use std::ffi::{CString, OsStr, OsString};
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::process;
+
+use crate::cmd::Spec;
+use crate::dev::makedev;
+use crate::err;
+use crate::status;
+
+/// The kind of special file to make.
+#[derive(Debug, PartialEq)]
+enum Node {
+    Block(u32, u32),
+    Char(u32, u32),
+    Fifo,
+}
+
+/// Parses the operands following the name: b or c and the major and
+/// minor numbers of a device, or p for a FIFO. Returns None if they are
+/// neither.
+fn parse(operands: &[OsString]) -> Result<Option<Node>, String> {
+    let number = |s: &OsString| s.to_str().and_then(|n| n.parse().ok())
+        .ok_or_else(|| format!("{}: bad number", s.to_string_lossy()));
+    match operands {
+        [kind, major, minor] if kind == "b" => Ok(Some(Node::Block(number(major)?, number(minor)?))),
+        [kind, major, minor] if kind == "c" || kind == "u" => Ok(Some(Node::Char(number(major)?, number(minor)?))),
+        [kind] if kind == "p" => Ok(Some(Node::Fifo)),
+        _ => Ok(None),
+    }
+}
+
+/// Makes the special file, with mode 666 less the umask.
+fn mknod(name: &OsStr, node: &Node) -> io::Result<()> {
+    let path = CString::new(name.as_bytes())?;
+    // SAFETY: path is a valid C string.
+    let ret = unsafe {
+        match *node {
+            Node::Block(major, minor) => libc::mknod(path.as_ptr(), libc::S_IFBLK | 0o666, makedev(major, minor)),
+            Node::Char(major, minor) => libc::mknod(path.as_ptr(), libc::S_IFCHR | 0o666, makedev(major, minor)),
+            Node::Fifo => libc::mkfifo(path.as_ptr(), 0o666),
+        }
+    };
+    match ret {
+        0 => Ok(()),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+pub const SPEC: Spec = Spec::new("build special file", &[], "name b|c major minor | name p");
+
+pub fn run(args: Vec<OsString>) -> i32 {
+    let mut operands = SPEC.parse(args).operands;
+    if operands.is_empty() {
+        SPEC.usage();
+    }
+    let name = operands.remove(0);
+    let node = match parse(&operands) {
+        Ok(Some(node)) => node,
+        Err(e) => {
+            err!("{}", e);
+            process::exit(status::FAILURE);
+        },
+        Ok(None) => SPEC.usage(),
+    };
+    if let Err(e) = mknod(&name, &node) {
+        if e.kind() == io::ErrorKind::PermissionDenied && node != Node::Fifo {
+            err!("{}: {}; only the super-user can make devices", name.to_string_lossy(), e);
+        } else {
+            err!("{}: {}", name.to_string_lossy(), e);
+        }
+        process::exit(status::FAILURE);
+    }
+    status::SUCCESS
+}
+
+#[test]
+fn test_parse() {
+    let parse_str = |s: &str| parse(&s.split(' ').map(OsString::from).collect::<Vec<_>>());
+    assert_eq!(parse_str("b 8 1"), Ok(Some(Node::Block(8, 1))));
+    assert_eq!(parse_str("c 1 3"), Ok(Some(Node::Char(1, 3))));
+    assert_eq!(parse_str("u 1 3"), Ok(Some(Node::Char(1, 3))));
+    assert_eq!(parse_str("p"), Ok(Some(Node::Fifo)));
+    assert_eq!(parse_str("c x 3"), Err("x: bad number".to_string()));
+    assert_eq!(parse_str("p 1 2"), Ok(None));
+    assert_eq!(parse_str("b 1"), Ok(None));
+}
@@ -0,0 +1,165 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// An implementation of the mktemp(1) command in Rust. mktemp is not a
+// Seventh Edition command; it first appeared in OpenBSD 2.1.
+// See https://man.openbsd.org/mktemp.1
+use std::ffi::OsString;
+use std::fs;
+use std::fs::{DirBuilder, File, OpenOptions};
+use std::io;
+use std::io::{Read, Write};
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::SystemTime;
+
+use crate::cmd::{Opt, Spec};
+use crate::err;
+use crate::status;
+use crate::tempfile;
+use crate::trace;
+
+/// The template used when none is given.
+const DEFAULT_TEMPLATE: &str = "tmp.XXXXXXXXXX";
+
+/// The fewest Xs a template may end with.
+const MIN_XS: usize = 3;
+
+/// How many names are tried before giving up.
+const ATTEMPTS: usize = 10000;
+
+const CHARS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+
+/// A generator of the characters that replace the Xs: xorshift64*, seeded
+/// from /dev/urandom or, failing that, the time and process id.
+struct Random(u64);
+
+impl Random {
+    fn new() -> Random {
+        let mut seed = [0u8; 8];
+        let ok = File::open("/dev/urandom").and_then(|mut f| f.read_exact(&mut seed)).is_ok();
+        let seed = if ok {
+            u64::from_ne_bytes(seed)
+        } else {
+            let nanos = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH)
+                .map_or(0, |d| d.as_nanos() as u64);
+            nanos ^ ((process::id() as u64) << 32)
+        };
+        Random(seed | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545f4914f6cdd1d)
+    }
+}
+
+/// Returns the template with its trailing Xs replaced by random letters
+/// and digits.
+fn fill(template: &[u8], random: &mut Random) -> Vec<u8> {
+    let xs = template.iter().rev().take_while(|&&c| c == b'X').count();
+    let mut name = template[..template.len() - xs].to_vec();
+    for _ in 0..xs {
+        name.push(CHARS[(random.next() % CHARS.len() as u64) as usize]);
+    }
+    name
+}
+
+/// Creates a file, or if dir is true a directory, accessible only by its
+/// owner, with a name made from the template. If dry_run is true nothing
+/// is created, and the name is only checked not to exist.
+fn mktemp(template: &Path, dir: bool, dry_run: bool) -> io::Result<PathBuf> {
+    let template = template.as_os_str().as_bytes();
+    if !template.ends_with(&[b'X'; MIN_XS]) {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "too few X's in template"));
+    }
+    let mut random = Random::new();
+    for _ in 0..ATTEMPTS {
+        let path = PathBuf::from(OsString::from_vec(fill(template, &mut random)));
+        let result = if dry_run {
+            match fs::symlink_metadata(&path) {
+                Ok(_) => Err(io::Error::from(io::ErrorKind::AlreadyExists)),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+                Err(e) => Err(e),
+            }
+        } else if dir {
+            trace::op("mkdir", format_args!("{:?}", path), DirBuilder::new().mode(0o700).create(&path))
+        } else {
+            let file = OpenOptions::new().write(true).create_new(true).mode(0o600).open(&path);
+            trace::op("create", format_args!("{:?}", path), file.map(|_| ()))
+        };
+        match result {
+            Ok(()) => return Ok(path),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Err(io::Error::other("too many names in use"))
+}
+
+pub const SPEC: Spec = Spec::new("make a temporary file or directory", &[
+    Opt::flag('d', "make a directory"),
+    Opt::flag('q', "fail silently"),
+    Opt::flag('u', "make nothing; only print a name"),
+    Opt::arg('p', "dir", "make it in dir"),
+], "[template]");
+
+pub fn run(args: Vec<OsString>) -> i32 {
+    let parsed = SPEC.parse(args);
+    let parent = parsed.value_os('p').map(PathBuf::from);
+    let templates = &parsed.operands;
+
+    // Without a template, one is made in the directory for temporary
+    // files. A template given is relative to the directory given by -p.
+    let template = match templates.as_slice() {
+        [] => parent.unwrap_or_else(tempfile::temp_dir).join(DEFAULT_TEMPLATE),
+        [template] => match parent {
+            Some(parent) => parent.join(template),
+            None => PathBuf::from(template),
+        },
+        _ => SPEC.usage(),
+    };
+    match mktemp(&template, parsed.flag('d'), parsed.flag('u')) {
+        Ok(path) => {
+            let mut out = io::stdout();
+            if let Err(e) = out.write_all(path.as_os_str().as_bytes()).and_then(|_| out.write_all(b"\n")) {
+                err!("{}", e);
+                process::exit(status::FAILURE);
+            }
+        },
+        Err(e) => {
+            if !parsed.flag('q') {
+                err!("{}: {}", template.display(), e);
+            }
+            process::exit(status::FAILURE);
+        }
+    }
+    status::SUCCESS
+}
+
+#[test]
+fn test_mktemp() {
+    let mut random = Random(1);
+    let name = fill(b"a.XXXXXX", &mut random);
+    assert_eq!(name.len(), 8);
+    assert!(name.starts_with(b"a.") && name[2..].iter().all(|c| CHARS.contains(c)));
+    assert_ne!(fill(b"XXXXXX", &mut random), fill(b"XXXXXX", &mut random));
+    assert_eq!(fill(b"none", &mut random), b"none");
+    assert!(mktemp(Path::new("tmp.XX"), false, false).is_err());
+
+    let dir = tempfile::temp_dir();
+    let file = mktemp(&dir.join("mktemp.XXXXXX"), false, false).unwrap();
+    let meta = fs::metadata(&file).unwrap();
+    assert!(meta.is_file());
+    assert_eq!(std::os::unix::fs::PermissionsExt::mode(&meta.permissions()) & 0o777, 0o600);
+    fs::remove_file(&file).unwrap();
+    let sub = mktemp(&dir.join("mktemp.XXXXXX"), true, false).unwrap();
+    assert!(sub.is_dir());
+    fs::remove_dir(&sub).unwrap();
+    let unused = mktemp(&dir.join("mktemp.XXXXXX"), false, true).unwrap();
+    assert!(!unused.exists());
+}
@@ -0,0 +1,532 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+//! The commands. Each is a function that takes the command's arguments,
+//! the first of which is the name it was invoked by, and returns its exit
+//! status, so that it can be run by its own binary or by the v7 multicall
+//! binary. Those that depend on Unix facilities with no counterpart
+//! elsewhere, such as utmp and device numbers, exist only on Unix.
+//!
+//! The commands are in groups, each selected by a Cargo feature of the
+//! same name, so that a smaller build can leave some out: core, the files
+//! and the shell; text, the filters, editors and calculators; sysadmin,
+//! users, processes and devices; and devtools, for building programs. All
+//! of them are built by default.
+//!
+//! Each command also declares its Spec: what it does, its options and its
+//! operands. The --help and --version options are answered from that, by
+//! exec, before the command is run, and most commands parse their
+//! arguments with it too, so that the options they take are the ones
+//! their help, manual page and completions describe, and a bad option is
+//! reported the same way by all of them.
+#[cfg(feature = "text")]
+pub mod awk;
+#[cfg(feature = "core")]
+pub mod basename;
+#[cfg(feature = "text")]
+pub mod bc;
+#[cfg(feature = "text")]
+pub mod cal;
+#[cfg(feature = "core")]
+pub mod cat;
+#[cfg(feature = "core")]
+pub mod chgrp;
+#[cfg(feature = "core")]
+pub mod chmod;
+#[cfg(feature = "core")]
+pub mod chown;
+#[cfg(all(unix, feature = "sysadmin"))]
+pub mod chroot;
+#[cfg(feature = "core")]
+pub mod cksum;
+#[cfg(feature = "core")]
+pub mod cmp;
+#[cfg(feature = "core")]
+pub mod cp;
+#[cfg(feature = "core")]
+pub mod date;
+#[cfg(feature = "text")]
+pub mod dc;
+#[cfg(feature = "core")]
+pub mod dd;
+#[cfg(feature = "text")]
+pub mod diff;
+#[cfg(feature = "core")]
+pub mod digest;
+#[cfg(feature = "core")]
+pub mod du;
+#[cfg(feature = "core")]
+pub mod echo;
+#[cfg(feature = "text")]
+pub mod ed;
+#[cfg(feature = "core")]
+pub mod file;
+#[cfg(feature = "core")]
+pub mod find;
+#[cfg(feature = "core")]
+pub mod head;
+#[cfg(all(unix, feature = "sysadmin"))]
+pub mod id;
+#[cfg(feature = "text")]
+pub mod join;
+#[cfg(all(unix, feature = "sysadmin"))]
+pub mod kill;
+#[cfg(feature = "core")]
+pub mod line;
+#[cfg(feature = "core")]
+pub mod ln;
+#[cfg(feature = "text")]
+pub mod look;
+#[cfg(feature = "core")]
+pub mod ls;
+#[cfg(feature = "devtools")]
+pub mod m4;
+#[cfg(feature = "devtools")]
+pub mod make;
+#[cfg(all(unix, feature = "sysadmin"))]
+pub mod mesg;
+#[cfg(feature = "core")]
+pub mod mkdir;
+#[cfg(all(unix, feature = "sysadmin"))]
+pub mod mknod;
+#[cfg(feature = "core")]
+pub mod mktemp;
+#[cfg(feature = "text")]
+pub mod more;
+#[cfg(feature = "core")]
+pub mod mv;
+#[cfg(feature = "devtools")]
+pub mod nm;
+#[cfg(feature = "text")]
+pub mod od;
+#[cfg(feature = "text")]
+pub mod pr;
+#[cfg(feature = "text")]
+pub mod primes;
+#[cfg(feature = "core")]
+pub mod rm;
+#[cfg(feature = "core")]
+pub mod rmdir;
+#[cfg(feature = "text")]
+pub mod sed;
+#[cfg(feature = "core")]
+pub mod sh;
+#[cfg(feature = "core")]
+pub mod sleep;
+#[cfg(feature = "text")]
+pub mod sort;
+#[cfg(feature = "devtools")]
+pub mod strings;
+#[cfg(feature = "core")]
+pub mod sum;
+#[cfg(feature = "core")]
+pub mod tail;
+#[cfg(feature = "core")]
+pub mod tar;
+#[cfg(feature = "core")]
+pub mod tee;
+#[cfg(feature = "core")]
+pub mod test;
+#[cfg(feature = "core")]
+pub mod touch;
+#[cfg(feature = "core")]
+pub mod tp;
+#[cfg(feature = "text")]
+pub mod tr;
+#[cfg(all(unix, feature = "sysadmin"))]
+pub mod wall;
+#[cfg(feature = "text")]
+pub mod wc;
+#[cfg(all(unix, feature = "sysadmin"))]
+pub mod who;
+
+use std::ffi::{OsStr, OsString};
+use std::path::Path;
+use std::process;
+
+use crate::args;
+use crate::diag;
+use crate::err;
+use crate::status;
+
+/// The function that runs a command.
+pub type Run = fn(Vec<OsString>) -> i32;
+
+/// An option: its letter, the name of its argument if it takes one, and
+/// what it does.
+pub struct Opt {
+    pub letter: char,
+    pub arg: Option<&'static str>,
+    pub about: &'static str,
+}
+
+impl Opt {
+    /// An option that takes no argument.
+    pub const fn flag(letter: char, about: &'static str) -> Opt {
+        Opt { letter, arg: None, about }
+    }
+
+    /// An option that takes an argument.
+    pub const fn arg(letter: char, arg: &'static str, about: &'static str) -> Opt {
+        Opt { letter, arg: Some(arg), about }
+    }
+}
+
+/// What a command does and how it is invoked.
+pub struct Spec {
+    /// What the command does, in a line.
+    pub about: &'static str,
+    pub options: &'static [Opt],
+    /// The operands, as they appear in the synopsis.
+    pub operands: &'static str,
+    /// Set if --help and --version are operands like any other, as for
+    /// echo and test, whose operands may be any string.
+    pub literal: bool,
+    /// Set if the command takes --json, to write what it reports as JSON
+    /// records instead of columns.
+    pub json: bool,
+}
+
+impl Spec {
+    pub const fn new(about: &'static str, options: &'static [Opt], operands: &'static str) -> Spec {
+        Spec { about, options, operands, literal: false, json: false }
+    }
+
+    pub const fn literal(mut self) -> Spec {
+        self.literal = true;
+        self
+    }
+
+    pub const fn json(mut self) -> Spec {
+        self.json = true;
+        self
+    }
+
+    /// Returns the synopsis of the command: the options that take no
+    /// argument grouped together, then the others, then the operands.
+    pub fn synopsis(&self, name: &str) -> String {
+        let mut synopsis = name.to_string();
+        let flags: String = self.options.iter().filter(|opt| opt.arg.is_none()).map(|opt| opt.letter).collect();
+        if !flags.is_empty() {
+            synopsis += &format!(" [-{}]", flags);
+        }
+        if self.json {
+            synopsis += " [--json]";
+        }
+        for opt in self.options {
+            if let Some(arg) = opt.arg {
+                synopsis += &format!(" [-{} {}]", opt.letter, arg);
+            }
+        }
+        if !self.operands.is_empty() {
+            synopsis += " ";
+            synopsis += self.operands;
+        }
+        synopsis
+    }
+
+    /// Returns the option letters in the form GetOpt takes them, each
+    /// followed by a colon if it takes an argument.
+    fn getopt(&self) -> String {
+        self.options.iter().map(|opt| match opt.arg {
+            Some(_) => format!("{}:", opt.letter),
+            None => opt.letter.to_string(),
+        }).collect()
+    }
+
+    /// Parses the arguments, the first of which is the command's name, as
+    /// the options describe, returning an error for an option that isn't
+    /// among them or that lacks its argument.
+    pub fn try_parse(&self, args: Vec<OsString>) -> Result<Parsed, args::Error> {
+        let mut args = args;
+        let json = self.json && args::long_flag(&mut args, "json");
+        let getopt = self.getopt();
+        let mut parsed = Parsed { opts: Vec::new(), operands: Vec::new(), json };
+        for optarg in args::GetOpt::new(&getopt, args.into_iter().skip(1)) {
+            match optarg? {
+                args::Arg::Opt(c) => parsed.opts.push((c, None)),
+                args::Arg::OptWithArg(c, arg) => parsed.opts.push((c, Some(arg))),
+                args::Arg::Arg(arg) => parsed.operands.push(arg),
+            }
+        }
+        Ok(parsed)
+    }
+
+    /// Parses the arguments as try_parse does, but reports an error, with
+    /// the synopsis, and exits.
+    pub fn parse(&self, args: Vec<OsString>) -> Parsed {
+        self.try_parse(args).unwrap_or_else(|e| {
+            err!("{}", e);
+            self.usage()
+        })
+    }
+
+    /// Reports the synopsis, as for arguments the command can't make sense
+    /// of, and exits.
+    pub fn usage(&self) -> ! {
+        eprintln!("usage: {}", self.synopsis(diag::prog()));
+        process::exit(status::USAGE);
+    }
+
+    /// Returns the text printed by --help.
+    pub fn help(&self, name: &str) -> String {
+        let mut help = format!("{} - {}\n\nusage: {}\n", name, self.about, self.synopsis(name));
+        let width = self.options.iter().map(|opt| opt.arg.map_or(0, |arg| arg.len() + 1)).max();
+        if let Some(width) = width {
+            help += "\n";
+            for opt in self.options {
+                let arg = opt.arg.map_or(String::new(), |arg| format!(" {}", arg));
+                help += &format!("  -{}{:<w$}  {}\n", opt.letter, arg, opt.about, w = width);
+            }
+        }
+        if self.json {
+            help += if width.is_some() { "" } else { "\n" };
+            help += "  --json  write a JSON record for each entry\n";
+        }
+        help
+    }
+}
+
+/// The options a command was given, in order, and its operands, as
+/// Spec::parse finds them.
+#[derive(Debug)]
+pub struct Parsed {
+    opts: Vec<(char, Option<OsString>)>,
+    pub operands: Vec<OsString>,
+    /// Set if the command takes --json and was given it.
+    pub json: bool,
+}
+
+impl Parsed {
+    /// Returns the options in the order they were given, each with its
+    /// argument, if it takes one, as it was given, so that an argument
+    /// that names a file need not be valid UTF-8.
+    pub fn opts_os(&self) -> impl Iterator<Item = (char, Option<&OsStr>)> {
+        self.opts.iter().map(|(c, arg)| (*c, arg.as_deref()))
+    }
+
+    /// Returns the options as opts_os does, with their arguments as strs.
+    /// If an argument isn't valid UTF-8, says so and exits when it is
+    /// reached.
+    pub fn opts(&self) -> impl Iterator<Item = (char, Option<&str>)> {
+        self.opts_os().map(|(c, arg)| (c, arg.map(args::str)))
+    }
+
+    /// Returns whether the option was given.
+    pub fn flag(&self, letter: char) -> bool {
+        self.opts_os().any(|(c, _)| c == letter)
+    }
+
+    /// Returns the arguments of each time the option was given.
+    pub fn values_os(&self, letter: char) -> impl Iterator<Item = &OsStr> {
+        self.opts_os().filter(move |(c, _)| *c == letter).filter_map(|(_, arg)| arg)
+    }
+
+    /// Returns the arguments as values_os does, as strs. If one isn't
+    /// valid UTF-8, says so and exits.
+    pub fn values(&self, letter: char) -> impl Iterator<Item = &str> {
+        self.values_os(letter).map(args::str)
+    }
+
+    /// Returns the argument of the last time the option was given.
+    pub fn value_os(&self, letter: char) -> Option<&OsStr> {
+        self.values_os(letter).last()
+    }
+
+    /// Returns the argument as value_os does, as a str. If it isn't valid
+    /// UTF-8, says so and exits.
+    pub fn value(&self, letter: char) -> Option<&str> {
+        self.value_os(letter).map(args::str)
+    }
+
+    /// Returns the operands as Strings. If any isn't valid UTF-8, says so
+    /// and exits.
+    pub fn strings(&self) -> Vec<String> {
+        args::strings(self.operands.clone())
+    }
+}
+
+/// Runs a command or, if its only argument is --help or --version, prints
+/// its description or version.
+pub fn exec(run: Run, spec: &Spec, args: Vec<OsString>) -> i32 {
+    if let Some(arg0) = args.first() {
+        diag::set_prog(arg0);
+    }
+    if args.len() == 2 && !spec.literal {
+        let prog = Path::new(&args[0]).file_name().unwrap_or_default().to_string_lossy();
+        if args[1] == "--help" {
+            print!("{}", spec.help(&prog));
+            return status::SUCCESS;
+        }
+        if args[1] == "--version" {
+            println!("{} ({}) {}", prog, env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+            return status::SUCCESS;
+        }
+    }
+    run(args)
+}
+
+/// The commands by name, including the other names some answer to.
+pub const COMMANDS: &[(&str, Run, &Spec)] = &[
+    #[cfg(feature = "text")]
+    ("awk", awk::run, &awk::SPEC),
+    #[cfg(feature = "core")]
+    ("basename", basename::run, &basename::SPEC),
+    #[cfg(feature = "text")]
+    ("bc", bc::run, &bc::SPEC),
+    #[cfg(feature = "text")]
+    ("cal", cal::run, &cal::SPEC),
+    #[cfg(feature = "core")]
+    ("cat", cat::run, &cat::SPEC),
+    #[cfg(feature = "core")]
+    ("chgrp", chgrp::run, &chgrp::SPEC),
+    #[cfg(feature = "core")]
+    ("chmod", chmod::run, &chmod::SPEC),
+    #[cfg(feature = "core")]
+    ("chown", chown::run, &chown::SPEC),
+    #[cfg(all(unix, feature = "sysadmin"))]
+    ("chroot", chroot::run, &chroot::SPEC),
+    #[cfg(feature = "core")]
+    ("cksum", cksum::run, &cksum::SPEC),
+    #[cfg(feature = "core")]
+    ("cmp", cmp::run, &cmp::SPEC),
+    #[cfg(feature = "core")]
+    ("cp", cp::run, &cp::SPEC),
+    #[cfg(feature = "core")]
+    ("date", date::run, &date::SPEC),
+    #[cfg(feature = "text")]
+    ("dc", dc::run, &dc::SPEC),
+    #[cfg(feature = "core")]
+    ("dd", dd::run, &dd::SPEC),
+    #[cfg(feature = "text")]
+    ("diff", diff::run, &diff::SPEC),
+    #[cfg(feature = "core")]
+    ("digest", digest::run, &digest::SPEC),
+    #[cfg(feature = "core")]
+    ("md5sum", digest::run, &digest::SPEC),
+    #[cfg(feature = "core")]
+    ("sha1sum", digest::run, &digest::SPEC),
+    #[cfg(feature = "core")]
+    ("sha256sum", digest::run, &digest::SPEC),
+    #[cfg(feature = "core")]
+    ("sha512sum", digest::run, &digest::SPEC),
+    #[cfg(feature = "core")]
+    ("du", du::run, &du::SPEC),
+    #[cfg(feature = "core")]
+    ("echo", echo::run, &echo::SPEC),
+    #[cfg(feature = "text")]
+    ("ed", ed::run, &ed::SPEC),
+    #[cfg(feature = "core")]
+    ("file", file::run, &file::SPEC),
+    #[cfg(feature = "core")]
+    ("find", find::run, &find::SPEC),
+    #[cfg(feature = "core")]
+    ("head", head::run, &head::SPEC),
+    #[cfg(all(unix, feature = "sysadmin"))]
+    ("id", id::run, &id::SPEC),
+    #[cfg(feature = "text")]
+    ("join", join::run, &join::SPEC),
+    #[cfg(all(unix, feature = "sysadmin"))]
+    ("kill", kill::run, &kill::SPEC),
+    #[cfg(feature = "core")]
+    ("line", line::run, &line::SPEC),
+    #[cfg(feature = "core")]
+    ("ln", ln::run, &ln::SPEC),
+    #[cfg(feature = "text")]
+    ("look", look::run, &look::SPEC),
+    #[cfg(feature = "core")]
+    ("ls", ls::run, &ls::SPEC),
+    #[cfg(feature = "devtools")]
+    ("m4", m4::run, &m4::SPEC),
+    #[cfg(feature = "devtools")]
+    ("make", make::run, &make::SPEC),
+    #[cfg(all(unix, feature = "sysadmin"))]
+    ("mesg", mesg::run, &mesg::SPEC),
+    #[cfg(feature = "core")]
+    ("mkdir", mkdir::run, &mkdir::SPEC),
+    #[cfg(all(unix, feature = "sysadmin"))]
+    ("mknod", mknod::run, &mknod::SPEC),
+    #[cfg(feature = "core")]
+    ("mktemp", mktemp::run, &mktemp::SPEC),
+    #[cfg(feature = "text")]
+    ("more", more::run, &more::SPEC),
+    #[cfg(feature = "core")]
+    ("mv", mv::run, &mv::SPEC),
+    #[cfg(feature = "devtools")]
+    ("nm", nm::run, &nm::SPEC),
+    #[cfg(feature = "text")]
+    ("od", od::run, &od::SPEC),
+    #[cfg(feature = "text")]
+    ("pr", pr::run, &pr::SPEC),
+    #[cfg(feature = "text")]
+    ("primes", primes::run, &primes::SPEC),
+    #[cfg(feature = "core")]
+    ("rm", rm::run, &rm::SPEC),
+    #[cfg(feature = "core")]
+    ("rmdir", rmdir::run, &rmdir::SPEC),
+    #[cfg(feature = "text")]
+    ("sed", sed::run, &sed::SPEC),
+    #[cfg(feature = "core")]
+    ("sh", sh::run, &sh::SPEC),
+    #[cfg(feature = "core")]
+    ("sleep", sleep::run, &sleep::SPEC),
+    #[cfg(feature = "text")]
+    ("sort", sort::run, &sort::SPEC),
+    #[cfg(feature = "devtools")]
+    ("strings", strings::run, &strings::SPEC),
+    #[cfg(feature = "core")]
+    ("sum", sum::run, &sum::SPEC),
+    #[cfg(feature = "core")]
+    ("tail", tail::run, &tail::SPEC),
+    #[cfg(feature = "core")]
+    ("tar", tar::run, &tar::SPEC),
+    #[cfg(feature = "core")]
+    ("tee", tee::run, &tee::SPEC),
+    #[cfg(feature = "core")]
+    ("test", test::run, &test::SPEC),
+    #[cfg(feature = "core")]
+    ("[", test::run, &test::SPEC),
+    #[cfg(feature = "core")]
+    ("touch", touch::run, &touch::SPEC),
+    #[cfg(feature = "core")]
+    ("tp", tp::run, &tp::SPEC),
+    #[cfg(feature = "text")]
+    ("tr", tr::run, &tr::SPEC),
+    #[cfg(all(unix, feature = "sysadmin"))]
+    ("wall", wall::run, &wall::SPEC),
+    #[cfg(feature = "text")]
+    ("wc", wc::run, &wc::SPEC),
+    #[cfg(all(unix, feature = "sysadmin"))]
+    ("who", who::run, &who::SPEC),
+];
+
+/// Returns the command with the given name.
+pub fn find(name: &str) -> Option<(Run, &'static Spec)> {
+    COMMANDS.iter().find(|(n, _, _)| *n == name).map(|&(_, run, spec)| (run, spec))
+}
+
+#[test]
+fn test_parse() {
+    const SPEC: Spec = Spec::new("test", &[
+        Opt::flag('a', "a"),
+        Opt::arg('n', "number", "n"),
+    ], "[file ...]").json();
+    let args = |s: &str| s.split(' ').map(OsString::from).collect::<Vec<_>>();
+
+    let parsed = SPEC.try_parse(args("x --json -an 1 -n2 -- -a")).unwrap();
+    assert!(parsed.json);
+    assert!(parsed.flag('a'));
+    assert!(!parsed.flag('b'));
+    assert_eq!(parsed.values('n').collect::<Vec<_>>(), ["1", "2"]);
+    assert_eq!(parsed.value('n'), Some("2"));
+    assert_eq!(parsed.opts().next(), Some(('a', None)));
+    assert_eq!(parsed.strings(), ["-a"]);
+    // An option's argument, like an operand, need not be valid UTF-8.
+    let mut with_name = args("x -n");
+    with_name.push(std::os::unix::ffi::OsStringExt::from_vec(b"\xff".to_vec()));
+    let parsed = SPEC.try_parse(with_name).unwrap();
+    assert_eq!(parsed.value_os('n').map(OsStr::as_encoded_bytes), Some(&b"\xff"[..]));
+    assert!(parsed.flag('n'));
+    assert_eq!(SPEC.try_parse(args("x -b")).unwrap_err(), args::Error::UnknownOpt('b'));
+    assert_eq!(SPEC.try_parse(args("x -n")).unwrap_err(), args::Error::MissingOptArg('n'));
+}
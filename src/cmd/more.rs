@@ -0,0 +1,377 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// An implementation of the more(1) command in Rust. more is not a Seventh
+// Edition command; it first appeared in 3.0BSD.
+// See https://pubs.opengroup.org/onlinepubs/9699919799/utilities/more.html
+use std::collections::VecDeque;
+use std::ffi::{OsStr, OsString};
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::os::unix::io::AsRawFd;
+use std::process;
+
+use crate::cmd::Spec;
+use crate::err;
+use crate::regex::Regex;
+use crate::status;
+use crate::tty;
+
+/// The size of the screen when the terminal doesn't know it.
+const DEFAULT_ROWS: usize = 24;
+const DEFAULT_COLS: usize = 80;
+
+const HELP: &str = "space: next page, return: next line, d: half a page, /re: search, n: search again, q: quit";
+
+/// Returns how many rows of the screen a line takes, with tabs expanded
+/// and the bytes that continue UTF-8 characters taking no room.
+fn rows_for(line: &[u8], cols: usize) -> usize {
+    let mut width: usize = 0;
+    for &c in line.strip_suffix(b"\n").unwrap_or(line) {
+        if c == b'\t' {
+            width = (width / 8 + 1) * 8;
+        } else if !(0x80..0xc0).contains(&c) {
+            width += 1;
+        }
+    }
+    width.div_ceil(cols).max(1)
+}
+
+/// A file being paged through, with the lines a search has read ahead.
+struct Input {
+    reader: Box<dyn BufRead>,
+    ahead: VecDeque<Vec<u8>>,
+    /// How much of the file has been shown or skipped, and its size, if
+    /// it is known.
+    done: u64,
+    size: Option<u64>,
+}
+
+impl Input {
+    fn new(reader: Box<dyn BufRead>, size: Option<u64>) -> Input {
+        Input { reader, ahead: VecDeque::new(), done: 0, size }
+    }
+
+    /// Reads another line into the lines ahead, returning false at the end
+    /// of the file.
+    fn read_ahead(&mut self) -> io::Result<bool> {
+        let mut line = Vec::new();
+        if self.reader.read_until(b'\n', &mut line)? == 0 {
+            return Ok(false);
+        }
+        self.ahead.push_back(line);
+        Ok(true)
+    }
+
+    fn next_line(&mut self) -> io::Result<Option<Vec<u8>>> {
+        if self.ahead.is_empty() && !self.read_ahead()? {
+            return Ok(None);
+        }
+        let line = self.ahead.pop_front().unwrap();
+        self.done += line.len() as u64;
+        Ok(Some(line))
+    }
+
+    fn at_end(&mut self) -> io::Result<bool> {
+        Ok(self.ahead.is_empty() && !self.read_ahead()?)
+    }
+
+    /// Skips to the next line matching the expression, returning false,
+    /// having skipped nothing, if there is none.
+    fn search(&mut self, re: &Regex) -> io::Result<bool> {
+        let mut i = 0;
+        loop {
+            if i == self.ahead.len() && !self.read_ahead()? {
+                return Ok(false);
+            }
+            let line = &self.ahead[i];
+            if re.is_match(line.strip_suffix(b"\n").unwrap_or(line)) {
+                for line in self.ahead.drain(..i) {
+                    self.done += line.len() as u64;
+                }
+                return Ok(true);
+            }
+            i += 1;
+        }
+    }
+}
+
+struct Pager<W: Write> {
+    out: W,
+    /// Where the keys typed are read from.
+    keys: Box<dyn Read>,
+    rows: usize,
+    cols: usize,
+    /// The expression last searched for.
+    pattern: Option<Regex>,
+}
+
+impl<W: Write> Pager<W> {
+    fn key(&mut self) -> io::Result<Option<u8>> {
+        let mut c = [0];
+        loop {
+            match self.keys.read(&mut c) {
+                Ok(0) => return Ok(None),
+                Ok(_) => return Ok(Some(c[0])),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Shows a prompt, in reverse video, and waits for a key, which is
+    /// returned once the prompt has been erased.
+    fn prompt(&mut self, prompt: &str) -> io::Result<Option<u8>> {
+        write!(self.out, "\x1b[7m{}\x1b[m", prompt)?;
+        self.out.flush()?;
+        let key = self.key()?;
+        write!(self.out, "\r\x1b[K")?;
+        Ok(key)
+    }
+
+    /// Reads the expression to search for, echoing it, up to a return.
+    /// Returns None if it is abandoned with escape or interrupt.
+    fn read_pattern(&mut self) -> io::Result<Option<String>> {
+        let mut pattern = Vec::new();
+        write!(self.out, "/")?;
+        loop {
+            self.out.flush()?;
+            match self.key()? {
+                Some(b'\r' | b'\n') => break,
+                None | Some(0x1b | 0x03) => {
+                    write!(self.out, "\r\x1b[K")?;
+                    return Ok(None);
+                },
+                Some(0x7f | 0x08) => {
+                    if pattern.pop().is_some() {
+                        write!(self.out, "\x08 \x08")?;
+                    }
+                },
+                Some(c) => {
+                    pattern.push(c);
+                    self.out.write_all(&[c])?;
+                },
+            }
+        }
+        write!(self.out, "\r\x1b[K")?;
+        Ok(Some(String::from_utf8_lossy(&pattern).into_owned()))
+    }
+
+    /// Waits for a command, returning how many rows to show next, or None
+    /// to quit.
+    fn command(&mut self, input: &mut Input) -> io::Result<Option<usize>> {
+        let page = self.rows - 1;
+        let mut prompt = match input.size {
+            Some(size) if size > 0 => format!("--More--({}%)", input.done * 100 / size),
+            _ => "--More--".to_string(),
+        };
+        loop {
+            let key = match self.prompt(&prompt)? {
+                Some(key) => key,
+                None => return Ok(None),
+            };
+            let re = match key {
+                b' ' | b'f' => return Ok(Some(page)),
+                b'\r' | b'\n' | b'j' => return Ok(Some(1)),
+                b'd' | 0x04 => return Ok(Some(page.div_ceil(2))),
+                b'q' | b'Q' | 0x03 => return Ok(None),
+                b'h' | b'?' => {
+                    prompt = HELP.to_string();
+                    continue;
+                },
+                b'/' => match self.read_pattern()? {
+                    None => continue,
+                    Some(pattern) if pattern.is_empty() => self.pattern.take(),
+                    Some(pattern) => match Regex::new(&pattern) {
+                        Ok(re) => Some(re),
+                        Err(e) => {
+                            prompt = e.to_string();
+                            continue;
+                        },
+                    },
+                },
+                b'n' => self.pattern.take(),
+                _ => {
+                    write!(self.out, "\x07")?;
+                    continue;
+                },
+            };
+            let re = match re {
+                Some(re) => re,
+                None => {
+                    prompt = "No previous regular expression".to_string();
+                    continue;
+                },
+            };
+            let found = input.search(&re)?;
+            self.pattern = Some(re);
+            if found {
+                writeln!(self.out, "...skipping")?;
+                return Ok(Some((page - 1).max(1)));
+            }
+            prompt = "Pattern not found".to_string();
+        }
+    }
+
+    /// Pages through a file, starting with the given number of rows.
+    /// Returns false if the user quit.
+    fn page(&mut self, input: &mut Input, first: usize) -> io::Result<bool> {
+        let mut rows = first;
+        loop {
+            while rows > 0 {
+                let line = match input.next_line()? {
+                    Some(line) => line,
+                    None => return Ok(true),
+                };
+                rows = rows.saturating_sub(rows_for(&line, self.cols));
+                self.out.write_all(&line)?;
+                if !line.ends_with(b"\n") {
+                    writeln!(self.out)?;
+                }
+            }
+            if input.at_end()? {
+                return Ok(true);
+            }
+            match self.command(input)? {
+                Some(n) => rows = n,
+                None => return Ok(false),
+            }
+        }
+    }
+
+    /// Pages through the files, each headed by its name if there are
+    /// several. Returns false if any couldn't be read.
+    fn more(&mut self, files: &[OsString]) -> io::Result<bool> {
+        let mut ok = true;
+        for (i, file) in files.iter().enumerate() {
+            if i > 0 {
+                let prompt = format!("--More--(Next file: {})", file.to_string_lossy());
+                if matches!(self.prompt(&prompt)?, None | Some(b'q' | b'Q' | 0x03)) {
+                    break;
+                }
+            }
+            let mut input = match open(file) {
+                Ok(input) => input,
+                Err(e) => {
+                    self.out.flush()?;
+                    err!("{}: {}", file.to_string_lossy(), e);
+                    ok = false;
+                    continue;
+                },
+            };
+            let mut rows = self.rows - 1;
+            if files.len() > 1 {
+                self.out.write_all(b"::::::::::::::\n")?;
+                self.out.write_all(file.as_encoded_bytes())?;
+                self.out.write_all(b"\n::::::::::::::\n")?;
+                rows = rows.saturating_sub(3).max(1);
+            }
+            if !self.page(&mut input, rows)? {
+                break;
+            }
+        }
+        self.out.flush()?;
+        Ok(ok)
+    }
+}
+
+/// Opens a file, or the standard input for "-".
+fn open(file: &OsStr) -> io::Result<Input> {
+    if file == "-" {
+        return Ok(Input::new(Box::new(BufReader::new(io::stdin())), None));
+    }
+    let f = File::open(file)?;
+    let meta = f.metadata()?;
+    if meta.is_dir() {
+        return Err(io::Error::other("is a directory"));
+    }
+    let size = if meta.is_file() { Some(meta.len()) } else { None };
+    Ok(Input::new(Box::new(BufReader::new(f)), size))
+}
+
+/// Copies the files to the standard output, as cat does, for when it
+/// isn't a terminal.
+fn cat(files: &[OsString]) -> bool {
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let mut ok = true;
+    for file in files {
+        let result = open(file).and_then(|mut input| io::copy(&mut input.reader, &mut out));
+        if let Err(e) = result {
+            err!("{}: {}", file.to_string_lossy(), e);
+            ok = false;
+        }
+    }
+    ok
+}
+
+pub const SPEC: Spec = Spec::new("file perusal filter for crt viewing", &[], "[file ...]");
+
+pub fn run(args: Vec<OsString>) -> i32 {
+    let mut files = SPEC.parse(args).operands;
+
+    if files.is_empty() {
+        files.push(OsString::from("-"));
+    }
+    // Keys are read from the terminal, as the standard input may be what
+    // is being paged through.
+    let stdout = io::stdout();
+    let tty = File::open("/dev/tty");
+    let (tty, (rows, cols)) = match (tty, tty::size(stdout.as_raw_fd())) {
+        (Ok(tty), Some(size)) => (tty, size),
+        (Ok(tty), None) if tty::name(stdout.as_raw_fd()).is_some() => (tty, (DEFAULT_ROWS, DEFAULT_COLS)),
+        _ => process::exit(if cat(&files) { status::SUCCESS } else { status::FAILURE }),
+    };
+    let raw = tty::Raw::new(tty.as_raw_fd()).unwrap_or_else(|e| {
+        err!("/dev/tty: {}", e);
+        process::exit(status::FAILURE);
+    });
+    let mut pager = Pager {
+        out: io::BufWriter::new(stdout.lock()),
+        keys: Box::new(tty),
+        rows: rows.max(2),
+        cols,
+        pattern: None,
+    };
+    let result = pager.more(&files);
+    drop(raw);
+    match result {
+        Ok(true) => (),
+        Ok(false) => process::exit(status::FAILURE),
+        Err(e) => {
+            err!("{}", e);
+            process::exit(status::FAILURE);
+        }
+    }
+    status::SUCCESS
+}
+
+#[test]
+fn test_more() {
+    let run = |text: &str, keys: &str| {
+        let mut pager = Pager {
+            out: Vec::new(),
+            keys: Box::new(io::Cursor::new(keys.as_bytes().to_vec())),
+            rows: 4,
+            cols: 10,
+            pattern: None,
+        };
+        let mut input = Input::new(Box::new(io::Cursor::new(text.as_bytes().to_vec())), Some(text.len() as u64));
+        let finished = pager.page(&mut input, 3).unwrap();
+        let out = String::from_utf8(pager.out).unwrap();
+        (finished, out.replace("\x1b[7m", "<").replace("\x1b[m", ">").replace("\r\x1b[K", "|"))
+    };
+    let text = "1\n2\n3\n4\n5\n6\n7\n8\n9\n";
+    assert_eq!(run(text, "  "), (true, "1\n2\n3\n<--More--(33%)>|4\n5\n6\n<--More--(66%)>|7\n8\n9\n".to_string()));
+    assert_eq!(run(text, "\nq"), (false, "1\n2\n3\n<--More--(33%)>|4\n<--More--(44%)>|".to_string()));
+    // The prompt comes up again after a search that fails.
+    assert_eq!(run(text, "/7\n/x\n "), (true, concat!(
+        "1\n2\n3\n<--More--(33%)>|/7|...skipping\n7\n8\n<--More--(88%)>|/x|<Pattern not found>|",
+        "9\n").to_string()));
+    // A long line takes more than one row.
+    assert_eq!(run("0123456789abcdef\nx\ny\n", " ").1, "0123456789abcdef\nx\n<--More--(90%)>|y\n");
+    assert_eq!(rows_for(b"\t\t\n", 10), 2);
+    assert_eq!(rows_for("\u{e9}\u{e9}\u{e9}".as_bytes(), 3), 1);
+    assert_eq!(rows_for(b"\n", 80), 1);
+}
@@ -0,0 +1,185 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// An implementation of the nm(1) command in Rust.
+// See http://man.cat-v.org/unix-7th/1/nm
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::io;
+use std::io::{BufWriter, Write};
+use std::process;
+
+use crate::ar;
+use crate::cmd::{Opt, Spec};
+use crate::elf;
+use crate::elf::{Elf, Section, Symbol};
+use crate::err;
+use crate::status;
+
+/// Returns the letter for a symbol defined in a section, in lower case.
+fn section_letter(section: &Section) -> char {
+    if section.flags & elf::SHF_ALLOC == 0 {
+        if section.name.starts_with(".debug") { 'N' } else { 'n' }
+    } else if section.flags & elf::SHF_EXECINSTR != 0 {
+        't'
+    } else if section.kind == elf::SHT_NOBITS {
+        'b'
+    } else if section.flags & elf::SHF_WRITE != 0 {
+        'd'
+    } else {
+        'r'
+    }
+}
+
+/// Returns the letter describing a symbol's type: U for undefined, A for
+/// absolute, T for text, D for data, B for bss, R for read-only data, C
+/// for common, W or V for weak and so on. Local symbols have lower case
+/// letters.
+fn type_letter(file: &Elf, symbol: &Symbol) -> char {
+    let weak = symbol.bind == elf::STB_WEAK;
+    let object = symbol.kind == elf::STT_OBJECT;
+    let letter = match symbol.section {
+        elf::SHN_UNDEF if weak => return if object { 'v' } else { 'w' },
+        elf::SHN_UNDEF => return 'U',
+        elf::SHN_COMMON => return 'C',
+        elf::SHN_ABS => 'a',
+        n => file.sections.get(n as usize).map_or('?', section_letter),
+    };
+    if symbol.kind == elf::STT_GNU_IFUNC {
+        'i'
+    } else if weak {
+        if object { 'V' } else { 'W' }
+    } else if symbol.bind == elf::STB_GNU_UNIQUE {
+        'u'
+    } else if symbol.bind == elf::STB_LOCAL {
+        letter
+    } else {
+        letter.to_ascii_uppercase()
+    }
+}
+
+struct Nm {
+    /// List only external symbols.
+    external: bool,
+    /// Sort by value rather than by name.
+    numeric: bool,
+    /// List only undefined symbols.
+    undefined: bool,
+}
+
+impl Nm {
+    /// Lists the symbols of an object file. Returns false if it has none.
+    fn list(&self, data: &[u8], out: &mut dyn Write) -> io::Result<bool> {
+        let file = Elf::parse(data)?;
+        let symbols = match file.symbols(elf::SHT_SYMTAB)? {
+            Some(symbols) => symbols,
+            None => return Ok(false),
+        };
+        // Section and file name symbols are only of interest to debuggers.
+        let mut symbols: Vec<Symbol> = symbols.into_iter()
+            .filter(|sym| sym.kind != elf::STT_SECTION && sym.kind != elf::STT_FILE)
+            .filter(|sym| !self.external || sym.bind != elf::STB_LOCAL)
+            .filter(|sym| !self.undefined || sym.section == elf::SHN_UNDEF)
+            .collect();
+        if self.numeric {
+            // Undefined symbols have no value and come first.
+            symbols.sort_by(|a, b| {
+                let key = |sym: &Symbol| (sym.section != elf::SHN_UNDEF,
+                                          if sym.section == elf::SHN_UNDEF { 0 } else { sym.value });
+                key(a).cmp(&key(b)).then_with(|| a.name.cmp(&b.name))
+            });
+        } else {
+            symbols.sort_by(|a, b| a.name.cmp(&b.name));
+        }
+        let width = if file.is_64 { 16 } else { 8 };
+        for sym in &symbols {
+            let letter = type_letter(&file, sym);
+            if sym.section == elf::SHN_UNDEF {
+                writeln!(out, "{:width$} {} {}", "", letter, sym.name, width = width)?;
+            } else {
+                writeln!(out, "{:0width$x} {} {}", sym.value, letter, sym.name, width = width)?;
+            }
+        }
+        Ok(true)
+    }
+
+    /// Lists the symbols of the named file or, if it is an archive, of
+    /// each of its members, headed by the file's name if heading is true.
+    /// Problems with the file are reported, returning false.
+    fn nm(&self, path: &OsStr, heading: bool, out: &mut dyn Write) -> io::Result<bool> {
+        let report = |out: &mut dyn Write, name: &str, result: io::Result<bool>| {
+            out.flush()?;
+            match result {
+                Ok(true) => Ok(true),
+                // Having no symbols isn't an error.
+                Ok(false) => {
+                    err!("{}: no symbols", name);
+                    Ok(true)
+                },
+                Err(e) => {
+                    err!("{}: {}", name, e);
+                    Ok(false)
+                }
+            }
+        };
+        let name = &path.to_string_lossy();
+        let data = match fs::read(path) {
+            Ok(data) => data,
+            Err(e) => return report(out, name, Err(e)),
+        };
+        if heading {
+            out.write_all(b"\n")?;
+            out.write_all(path.as_encoded_bytes())?;
+            out.write_all(b":\n")?;
+        }
+        if !ar::is_archive(&data) {
+            let result = self.list(&data, out);
+            return report(out, name, result);
+        }
+        let members = match ar::members(&data) {
+            Ok(members) => members,
+            Err(e) => return report(out, name, Err(e)),
+        };
+        let mut ok = true;
+        for member in members {
+            writeln!(out, "\n{}:", member.name)?;
+            let result = self.list(member.data, out);
+            ok &= report(out, &member.name, result)?;
+        }
+        Ok(ok)
+    }
+}
+
+pub const SPEC: Spec = Spec::new("print name list", &[
+    Opt::flag('g', "print only external symbols"),
+    Opt::flag('n', "sort numerically rather than by name"),
+    Opt::flag('u', "print only undefined symbols"),
+], "[file ...]");
+
+pub fn run(args: Vec<OsString>) -> i32 {
+    let parsed = SPEC.parse(args);
+    let nm = Nm { external: parsed.flag('g'), numeric: parsed.flag('n'), undefined: parsed.flag('u') };
+    let mut files = parsed.operands;
+
+    if files.is_empty() {
+        files.push(OsString::from("a.out"));
+    }
+
+    let mut out = BufWriter::new(io::stdout());
+    let mut status = status::SUCCESS;
+    for file in &files {
+        match nm.nm(file, files.len() > 1, &mut out) {
+            Ok(true) => (),
+            Ok(false) => status = 1,
+            Err(e) => {
+                err!("{}", e);
+                process::exit(status::FAILURE);
+            }
+        }
+    }
+    if let Err(e) = out.flush() {
+        err!("{}", e);
+        status = status::FAILURE;
+    }
+    status
+}
@@ -0,0 +1,311 @@
+// Copyright 2016-2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// An implementation of the od(1) command in Rust.
+// See http://man.cat-v.org/unix-7th/1/od
+use std::ffi::{OsStr, OsString};
+use std::io;
+use std::io::BufReader;
+use std::io::BufWriter;
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::io::Stdout;
+use std::io::Write;
+
+use crate::cmd::{Opt, Spec};
+use crate::err;
+use crate::input::Input;
+use crate::sandbox;
+use crate::status;
+
+type FmtFn = fn(&mut BufWriter<Stdout>, &[u8], usize) -> io::Result<usize>;
+
+/// Writes a chunk of output data as octal byte values.
+fn write_oct_bytes(out: &mut BufWriter<Stdout>, data: &[u8], _: usize)
+                   -> io::Result<usize> {
+    for word in data.chunks(2) {
+	if word.len() == 1 {
+	    write!(out, " {:03o}", word[0])?;
+	} else {
+	    write!(out, " {:03o} {:03o}", word[0], word[1])?;
+	}
+    }
+    writeln!(out)?;
+    Ok(data.len())
+}
+
+/// Writes a word using the supplied format specifier.
+macro_rules! write_word {
+    ($out:expr, $word:expr, $fmt:expr, $width:expr) => {
+	if $word.len() == 1 {
+	    write!($out, " {1:>0$}", $width, format!($fmt, u16::from($word[0])))?;
+	} else {
+            write!($out, " {1:>0$}", $width,
+		   format!($fmt, u16::from($word[1]) << 8 | u16::from($word[0])))?;
+	}
+    }
+}
+
+/// Writes a chunk of output data as octal (16 bit) word values. Words are
+/// assumed to be little endian.
+fn write_oct_words(out: &mut BufWriter<Stdout>, data: &[u8], width: usize)
+                   -> io::Result<usize> {
+    for word in data.chunks(2) {
+	write_word!(out, word, "{:06o}", width);
+    }
+    writeln!(out)?;
+    Ok(data.len())
+}
+
+/// Writes a chunk of output data as decimal (16 bit) word values. Words are
+/// assumed to be little endian.
+fn write_dec_words(out: &mut BufWriter<Stdout>, data: &[u8], width: usize)
+                   -> io::Result<usize> {
+    for word in data.chunks(2) {
+	write_word!(out, word, "{:5}", width);
+    }
+    writeln!(out)?;
+    Ok(data.len())
+}
+
+/// Writes a chunk of output data as hexadecimal (16 bit) word values. Words
+/// are assumed to be little endian.
+fn write_hex_words(out: &mut BufWriter<Stdout>, data: &[u8], width: usize)
+                   -> io::Result<usize> {
+    for word in data.chunks(2) {
+	write_word!(out, word, "{:04x}", width);
+    }
+    writeln!(out)?;
+    Ok(data.len())
+}
+
+/// Writes a chunk of data as ASCII, reverting to octal byte values for
+/// non-printable characters. Standard escape sequences are supported.
+fn write_ascii_chars(out: &mut BufWriter<Stdout>, data: &[u8], _: usize)
+                     -> io::Result<usize> {
+    for word in data.chunks(2) {
+	write_ascii_char(out, word[0])?;
+	if word.len() > 1 {
+	    write_ascii_char(out, word[1])?;
+	}
+    }
+    writeln!(out)?;
+    Ok(data.len())
+}
+
+/// Write a byte as ASCII, reverting to octal byte values for
+/// non-printable characters. Standard escape sequences are supported.
+fn write_ascii_char(out: &mut BufWriter<Stdout>, byte: u8) -> io::Result<()> {
+    match byte {
+        7u8 => write!(out, "  \\g")?,
+        8u8 => write!(out, "  \\b")?,
+        9u8 => write!(out, "  \\t")?,
+        10u8 => write!(out, "  \\n")?,
+        11u8 => write!(out, "  \\v")?,
+        12u8 => write!(out, "  \\f")?,
+        13u8 => write!(out, "  \\r")?,
+        _ => if !(32u8..=126u8).contains(&byte) {
+            write!(out, " {:03o}", byte)?
+        } else {
+            write!(out, "   {}", byte as char)?
+        }
+    }
+
+    Ok(())
+ }
+
+const CHUNK_SIZE: usize = 16;
+
+// The offset string is of the form [+]offset[.][b]
+// +100 => 0o100
+// +100. => 100
+// +100b => 0o100 * 512
+// +100.b => 100 * 512
+fn parse_offset(offstr: &str) -> Result<u64, String> {
+    let mut char_indices = offstr.char_indices().rev();
+    let mut mult = 1;
+    let (s, r) = match char_indices.next() {
+        Some((x, 'b')) => {
+            mult = 512;
+            match char_indices.next() {
+                Some((y, '.')) => (&offstr[0..y], 10),
+                Some((_, _)) => (&offstr[0..x], 8),
+                None => (&offstr[0..0], 8)
+            }
+        },
+        Some((x, '.')) => (&offstr[0..x], 10),
+        Some((_, _)) => (offstr, 8),
+        None => (&offstr[0..0], 8)
+    };
+
+    match u64::from_str_radix(s, r) {
+        Ok(n) => n.checked_mul(mult).ok_or_else(|| "offset too large".to_string()),
+        Err(e) => Err(e.to_string())
+    }
+}
+
+#[test]
+fn test_parse_offset() {
+    assert_eq!(parse_offset("100"), Ok(0o100));
+    assert_eq!(parse_offset("100."), Ok(100));
+    assert_eq!(parse_offset("100b"), Ok(0o100 * 512));
+    assert_eq!(parse_offset("100.b"), Ok(100 * 512));
+    assert!(parse_offset("1777777777777777777777b").is_err());
+}
+
+/// Reads until the buffer is full or the input ends, as a pipe may give
+/// less than a line at a time. Returns the number of bytes read.
+fn fill(reader: &mut dyn Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut n = 0;
+    while n < buf.len() {
+        match reader.read(&mut buf[n..]) {
+            Ok(0) => break,
+            Ok(len) => n += len,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(n)
+}
+
+/// Dumps the data read from the named input source to the standard output.
+fn od(filename: &OsStr, offset: u64,
+      fmt_fns: &[FmtFn], width: usize)
+      -> io::Result<u64> {
+    let input = Input::open(filename)?;
+    // A regular file is dumped from memory, and anything else is read.
+    let map = input.map();
+    let mut reader: Box<dyn Read> = match map {
+        Some(ref map) => {
+            let rest = usize::try_from(offset).ok().and_then(|offset| map.get(offset..));
+            Box::new(rest.unwrap_or_default())
+        },
+        None => {
+            let mut reader = BufReader::new(input);
+            if offset > 0 {
+                reader.seek(SeekFrom::Start(offset))?;
+            }
+            Box::new(reader)
+        },
+    };
+    let mut writer = BufWriter::new(io::stdout());
+    let mut offset = offset;
+
+    let mut chunk = [0; CHUNK_SIZE];
+    loop {
+        let n = fill(&mut reader, &mut chunk)?;
+        if n > 0 {
+            let mut first = true;
+            for fmt_fn in fmt_fns.iter() {
+                if first {
+                    write!(writer, "{:07o}", offset)?;
+                    first = false;
+                } else {
+                    write!(writer, "       ")?;
+                }
+                fmt_fn(&mut writer, &chunk[0..n], width)?;
+            }
+            offset += n as u64;
+        }
+
+        if n < CHUNK_SIZE {
+            break
+        }
+    }
+    writeln!(writer, "{:07o}", offset)?;
+    Ok(offset)
+}
+
+pub const SPEC: Spec = Spec::new("octal dump", &[
+    Opt::flag('b', "dump bytes in octal"),
+    Opt::flag('c', "dump bytes as characters"),
+    Opt::flag('d', "dump words in decimal"),
+    Opt::flag('o', "dump words in octal"),
+    Opt::flag('x', "dump words in hexadecimal"),
+], "[file] [[+]offset[.][b]]");
+
+pub fn run(args: Vec<OsString>) -> i32 {
+    let parsed = SPEC.parse(args);
+    let mut offstr = String::from("0");
+    let mut fmt_fns: Vec<FmtFn> = Vec::new();
+    let mut width : usize = 0;
+
+    // Default to reading from standard input.
+    let mut filename = OsString::from("-");
+
+    for (c, _) in parsed.opts() {
+	match c {
+	    'b' => {
+		fmt_fns.push(write_oct_bytes);
+		if width < 7 {
+		    width = 7;
+		}
+	    },
+	    'c' => {
+		fmt_fns.push(write_ascii_chars);
+		if width < 7 {
+		    width = 7;
+		}
+	    },
+	    'd' => {
+		fmt_fns.push(write_dec_words);
+		if width < 5 {
+		    width = 5;
+		}
+	    },
+	    'x' => {
+		fmt_fns.push(write_hex_words);
+		if width < 4 {
+		    width = 4;
+		}
+	    },
+	    'o' => {
+		fmt_fns.push(write_oct_words);
+		if width < 6 {
+		    width = 6;
+		}
+	    },
+	    _ => (),
+	}
+    }
+    for val in parsed.operands {
+	match val.to_str() {
+	    Some(s) if s.starts_with('+') => offstr = s.to_string(),
+	    _ => filename = val,
+	}
+    }
+
+    // If no output formats have been specified, default to octal words.
+    if fmt_fns.is_empty() {
+        fmt_fns.push(write_oct_words);
+	width = 6;
+    }
+
+    let offset = match parse_offset(&offstr) {
+        Ok(off) => off,
+        Err(e) => {
+            err!("{}: {}", offstr, e);
+            std::process::exit(status::USAGE);
+        }
+    };
+
+    if filename == "-" {
+        sandbox::filter();
+    }
+    match od(&filename, offset, &fmt_fns, width) {
+        Ok(_) => std::process::exit(status::SUCCESS),
+        Err(e) => {
+            err!("{}: {}", filename.to_string_lossy(), e);
+            std::process::exit(status::FAILURE)
+        }
+    }
+}
+
+/// Parses an offset, for the fuzz target of the same name.
+#[cfg(fuzzing)]
+pub fn fuzz(data: &[u8]) {
+    if let Ok(offstr) = std::str::from_utf8(data) {
+        let _ = parse_offset(offstr);
+    }
+}
@@ -0,0 +1,466 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// An implementation of the pr(1) command in Rust.
+// See http://man.cat-v.org/unix-7th/1/pr
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::io;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::os::unix::fs::MetadataExt;
+use std::process;
+
+use crate::cmd::{Opt, Spec};
+use crate::diag;
+use crate::err;
+use crate::input::Input;
+use crate::status;
+use crate::time;
+use crate::time::Tm;
+
+/// The number of lines taken by the header, and by the trailer.
+const MARGIN: usize = 5;
+
+/// The width of a tab stop.
+const TAB: usize = 8;
+
+/// Returns the position after writing the byte at the given position.
+fn advance(pos: usize, c: u8) -> usize {
+    match c {
+        b'\t' => (pos / TAB + 1) * TAB,
+        // UTF-8 continuation bytes take no space of their own.
+        0x80..=0xbf => pos,
+        _ => pos + 1,
+    }
+}
+
+/// Writes a line of columns, keeping track of the position. Blanks are
+/// held back until something follows them, or until the next column is
+/// reached, and are then written using tabs where possible, so that
+/// trailing blanks are dropped.
+struct RowWriter<'a> {
+    out: &'a mut dyn Write,
+    pos: usize,
+    /// The position from which blanks are pending.
+    blank: usize,
+}
+
+impl<'a> RowWriter<'a> {
+    fn new(out: &'a mut dyn Write) -> RowWriter<'a> {
+        RowWriter { out, pos: 0, blank: 0 }
+    }
+
+    /// Writes the pending blanks. A single blank is always a space.
+    fn flush_blanks(&mut self) -> io::Result<()> {
+        let mut pos = self.blank;
+        while self.pos - pos > 1 && (pos / TAB + 1) * TAB <= self.pos {
+            self.out.write_all(b"\t")?;
+            pos = (pos / TAB + 1) * TAB;
+        }
+        while pos < self.pos {
+            self.out.write_all(b" ")?;
+            pos += 1;
+        }
+        self.blank = self.pos;
+        Ok(())
+    }
+
+    /// Writes as much of the text as ends at or before the limit.
+    fn put(&mut self, text: &[u8], limit: Option<usize>) -> io::Result<()> {
+        for &c in text {
+            let next = advance(self.pos, c);
+            if limit.is_some_and(|limit| next > limit) {
+                break;
+            }
+            if c != b' ' && c != b'\t' {
+                self.flush_blanks()?;
+                self.out.write_all(&[c])?;
+                self.blank = next;
+            }
+            self.pos = next;
+        }
+        Ok(())
+    }
+
+    /// Writes a character as is, even if it is a blank.
+    fn put_literal(&mut self, c: u8) -> io::Result<()> {
+        self.flush_blanks()?;
+        self.out.write_all(&[c])?;
+        self.pos = advance(self.pos, c);
+        self.blank = self.pos;
+        Ok(())
+    }
+
+    /// Pads with blanks to the given position.
+    fn pad(&mut self, to: usize) -> io::Result<()> {
+        self.pos = self.pos.max(to);
+        self.flush_blanks()
+    }
+
+    fn end(self) -> io::Result<()> {
+        self.out.write_all(b"\n")
+    }
+}
+
+/// Reads a line, without its newline.
+fn read_line(input: &mut dyn BufRead) -> io::Result<Option<Vec<u8>>> {
+    let mut line = Vec::new();
+    if input.read_until(b'\n', &mut line)? == 0 {
+        return Ok(None);
+    }
+    if line.last() == Some(&b'\n') {
+        line.pop();
+    }
+    Ok(Some(line))
+}
+
+struct Pr {
+    /// The title to use in place of the file name (-h).
+    title: Option<String>,
+    /// Omit the header and trailer, and don't pad the last page (-t).
+    omit_margins: bool,
+    /// The page length in lines (-l).
+    length: usize,
+    /// The page width in characters (-w), which limits the width of
+    /// each column when printing more than one.
+    width: usize,
+    /// The first page to print (+N).
+    first_page: usize,
+    /// The number of columns (-N).
+    columns: usize,
+    /// Print each file in its own column (-m).
+    merge: bool,
+    /// Separate columns with this character, rather than aligning them
+    /// with blanks (-s).
+    separator: Option<u8>,
+    /// Number lines with this many digits followed by this character (-n).
+    number: Option<(usize, u8)>,
+}
+
+impl Pr {
+    /// Formats the header line: the date on the left, the title in the
+    /// middle and the page number on the right.
+    fn header(&self, date: &str, title: &[u8], page: usize) -> Vec<u8> {
+        let page = format!("Page {}", page);
+        let width = String::from_utf8_lossy(title).chars().count();
+        let used = date.chars().count() + width + page.len();
+        let blanks = self.width.saturating_sub(used);
+        let left = (blanks / 2).max(1);
+        let right = (blanks - blanks / 2).max(1);
+        let mut header = format!("{}{:left$}", date, "", left = left).into_bytes();
+        header.extend_from_slice(title);
+        header.extend_from_slice(format!("{:right$}{}", "", page, right = right).as_bytes());
+        header
+    }
+
+    /// Formats a line number.
+    fn line_number(&self, n: usize) -> Vec<u8> {
+        match self.number {
+            Some((digits, sep)) => {
+                let n = n % 10usize.saturating_pow(digits as u32);
+                let mut field = format!("{:>1$}", n, digits).into_bytes();
+                field.push(sep);
+                field
+            },
+            None => Vec::new(),
+        }
+    }
+
+    /// Reads a page's worth of lines from a single input, laid out in
+    /// rows of columns. Each column is filled before the next; the
+    /// columns of a short last page are balanced.
+    fn read_page(&self, input: &mut dyn BufRead, body: usize, numbered: &mut usize)
+                 -> io::Result<Vec<Vec<Option<Vec<u8>>>>> {
+        let mut lines = Vec::new();
+        while lines.len() < body * self.columns {
+            match read_line(input)? {
+                Some(line) => {
+                    *numbered += 1;
+                    let mut text = self.line_number(*numbered);
+                    text.extend_from_slice(&line);
+                    lines.push(text);
+                },
+                None => break,
+            }
+        }
+        let heights: Vec<usize> = if lines.len() == body * self.columns {
+            vec![body; self.columns]
+        } else {
+            (0..self.columns)
+                .map(|i| lines.len() / self.columns + usize::from(i < lines.len() % self.columns))
+                .collect()
+        };
+        let mut rows: Vec<Vec<Option<Vec<u8>>>> = vec![vec![None; self.columns]; heights[0]];
+        let mut lines = lines.into_iter();
+        for (column, &height) in heights.iter().enumerate() {
+            for row in rows.iter_mut().take(height) {
+                row[column] = lines.next();
+            }
+        }
+        Ok(rows)
+    }
+
+    /// Reads a page's worth of rows from several inputs, a line from each.
+    fn read_merged_page(&self, inputs: &mut [Box<dyn BufRead>], body: usize,
+                        numbered: &mut usize) -> io::Result<Vec<Vec<Option<Vec<u8>>>>> {
+        let mut rows = Vec::new();
+        while rows.len() < body {
+            let row = inputs.iter_mut()
+                .map(|input| read_line(input.as_mut()))
+                .collect::<io::Result<Vec<_>>>()?;
+            if row.iter().all(Option::is_none) {
+                break;
+            }
+            *numbered += 1;
+            rows.push(row);
+        }
+        Ok(rows)
+    }
+
+    /// Writes a row of columns. When merging, a row's line number comes
+    /// before its columns.
+    fn write_row(&self, row: &[Option<Vec<u8>>], number: usize, out: &mut dyn Write)
+                 -> io::Result<()> {
+        if row.len() == 1 && !self.merge {
+            if let Some(text) = &row[0] {
+                out.write_all(text)?;
+            }
+            return out.write_all(b"\n");
+        }
+        let mut writer = RowWriter::new(out);
+        if self.merge {
+            writer.put(&self.line_number(number), None)?;
+        }
+        let start = writer.pos;
+        // When merging, files that have ended still have their columns.
+        let used = if self.merge {
+            row.len()
+        } else {
+            row.iter().rposition(Option::is_some).map_or(0, |i| i + 1)
+        };
+        let width = self.width.saturating_sub(start + row.len() - 1) / row.len();
+        for (i, cell) in row[..used].iter().enumerate() {
+            match self.separator {
+                Some(sep) if i > 0 => writer.put_literal(sep)?,
+                Some(_) => (),
+                None => writer.pad(start + i * (width + 1))?,
+            }
+            if let Some(text) = cell {
+                let limit = writer.pos + width;
+                writer.put(text, Some(limit))?;
+            }
+        }
+        writer.end()
+    }
+
+    /// Paginates the inputs: one, or several side by side when merging.
+    /// The title is the name of the file, unless merging.
+    fn print(&self, name: &OsStr, inputs: &mut [Box<dyn BufRead>], out: &mut dyn Write)
+             -> io::Result<()> {
+        let (secs, _) = if name == "-" || self.merge {
+            time::now()
+        } else {
+            fs::metadata(name).map(|meta| (meta.mtime(), 0)).unwrap_or_else(|_| time::now())
+        };
+        let tm = Tm::local(secs);
+        let date = format!("{:04}-{:02}-{:02} {:02}:{:02}",
+                           tm.year, tm.month, tm.day, tm.hour, tm.min);
+        let title = match &self.title {
+            Some(title) => title.as_bytes(),
+            None if name == "-" || self.merge => b"",
+            None => name.as_encoded_bytes(),
+        };
+        // Pages too short for the header and trailer are printed without
+        // them.
+        let margins = !self.omit_margins && self.length > 2 * MARGIN;
+        let body = if margins { self.length - 2 * MARGIN } else { self.length };
+
+        let mut numbered = 0;
+        let mut page = 1;
+        loop {
+            let first = numbered + 1;
+            let rows = if self.merge {
+                self.read_merged_page(inputs, body, &mut numbered)?
+            } else {
+                self.read_page(inputs[0].as_mut(), body, &mut numbered)?
+            };
+            if rows.is_empty() {
+                return Ok(());
+            }
+            if page >= self.first_page {
+                if margins {
+                    out.write_all(b"\n\n")?;
+                    out.write_all(&self.header(&date, title, page))?;
+                    out.write_all(b"\n\n\n")?;
+                }
+                for (i, row) in rows.iter().enumerate() {
+                    self.write_row(row, first + i, out)?;
+                }
+                if margins {
+                    for _ in rows.len()..body + MARGIN {
+                        out.write_all(b"\n")?;
+                    }
+                }
+            }
+            if rows.len() < body {
+                return Ok(());
+            }
+            page += 1;
+        }
+    }
+}
+
+/// Parses a numeric option argument, which must be positive.
+fn parse_number(opt: &str, arg: &str) -> usize {
+    match arg.parse() {
+        Ok(n) if n > 0 => n,
+        _ => {
+            err!("{}: invalid number: {}", opt, arg);
+            process::exit(status::USAGE);
+        }
+    }
+}
+
+pub const SPEC: Spec = Spec::new("print file", &[
+    Opt::flag('m', "print the files side by side"),
+    Opt::flag('t', "print no header or trailer"),
+    Opt::flag('s', "separate columns with a tab, or the character that follows"),
+    Opt::flag('n', "number the lines"),
+    Opt::arg('h', "header", "use header in place of the file name"),
+    Opt::arg('l', "length", "make pages length lines long"),
+    Opt::arg('w', "width", "make lines width characters wide"),
+], "[+page] [-columns] [file ...]");
+
+pub fn run(args: Vec<OsString>) -> i32 {
+    let mut args = args.into_iter();
+    let mut pr = Pr {
+        title: None,
+        omit_margins: false,
+        length: 66,
+        width: 72,
+        first_page: 1,
+        columns: 1,
+        merge: false,
+        separator: None,
+        number: None,
+    };
+
+    // Pick out +N, -N and the optional arguments of -s and -n, which
+    // GetOpt can't parse.
+    let mut rest = vec![args.next().unwrap()];
+    let mut options = true;
+    let mut optarg = false;
+    for arg in args {
+        if let Some(arg) = arg.to_str().filter(|_| options && !optarg) {
+            if let Some(n) = arg.strip_prefix('+').filter(|n| !n.is_empty()) {
+                pr.first_page = parse_number("+", n);
+                continue;
+            }
+            if let Some(n) = arg.strip_prefix('-').filter(|n| n.starts_with(|c: char| c.is_ascii_digit())) {
+                pr.columns = parse_number("-", n);
+                continue;
+            }
+            if let Some(sep) = arg.strip_prefix("-s") {
+                pr.separator = Some(sep.bytes().next().unwrap_or(b'\t'));
+                continue;
+            }
+            if let Some(spec) = arg.strip_prefix("-n") {
+                let (sep, digits) = match spec.chars().next() {
+                    Some(c) if !c.is_ascii_digit() => (c, &spec[c.len_utf8()..]),
+                    _ => ('\t', spec),
+                };
+                let digits = if digits.is_empty() { 5 } else { parse_number("-n", digits) };
+                pr.number = Some((digits, sep as u8));
+                continue;
+            }
+        }
+        if optarg {
+            optarg = false;
+        } else if arg == "-h" || arg == "-l" || arg == "-w" {
+            optarg = true;
+        } else if arg == "--" || !arg.as_encoded_bytes().starts_with(b"-") {
+            options = false;
+        }
+        rest.push(arg);
+    }
+
+    let parsed = SPEC.parse(rest);
+    for (c, arg) in parsed.opts() {
+        match (c, arg) {
+            ('h', Some(arg)) => pr.title = Some(arg.to_string()),
+            ('l', Some(arg)) => pr.length = parse_number("-l", arg),
+            ('m', _) => pr.merge = true,
+            ('t', _) => pr.omit_margins = true,
+            ('w', Some(arg)) => pr.width = parse_number("-w", arg),
+            _ => (),
+        }
+    }
+    let mut files = parsed.operands;
+
+    if files.is_empty() {
+        files.push(OsString::from("-"));
+    }
+
+    let mut out = BufWriter::new(io::stdout());
+    let open = |file: &OsStr, out: &mut BufWriter<io::Stdout>| -> Option<Box<dyn BufRead>> {
+        match Input::open(file) {
+            Ok(input) => Some(Box::new(BufReader::new(input))),
+            Err(e) => {
+                let _ = out.flush();
+                err!("{}: {}", file.to_string_lossy(), e);
+                None
+            }
+        }
+    };
+    let mut result = Ok(());
+    if pr.merge {
+        let mut inputs: Vec<Box<dyn BufRead>> = files.iter()
+            .filter_map(|file| open(file, &mut out))
+            .collect();
+        pr.columns = inputs.len();
+        if !inputs.is_empty() {
+            result = pr.print(OsStr::new(""), &mut inputs, &mut out);
+        }
+    } else {
+        for file in &files {
+            if let Some(input) = open(file, &mut out) {
+                result = pr.print(file, &mut [input], &mut out);
+                if result.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+    if let Err(e) = result.and_then(|_| out.flush()) {
+        err!("{}", e);
+    }
+    diag::status()
+}
+
+#[test]
+fn test_header() {
+    let pr = Pr {
+        title: None, omit_margins: false, length: 66, width: 40, first_page: 1,
+        columns: 1, merge: false, separator: None, number: None,
+    };
+    assert_eq!(pr.header("2021-03-04 05:06", b"title", 1),
+               b"2021-03-04 05:06      title       Page 1");
+    assert_eq!(pr.header("2021-03-04 05:06", b"a very long title", 12),
+               b"2021-03-04 05:06 a very long title Page 12");
+}
+
+#[test]
+fn test_write_row() {
+    let mut pr = Pr {
+        title: None, omit_margins: true, length: 66, width: 30, first_page: 1,
+        columns: 3, merge: false, separator: None, number: None,
+    };
+    let row = [Some(b"one".to_vec()), None, Some(b"a rather long line".to_vec())];
+    let mut out = Vec::new();
+    pr.write_row(&row, 1, &mut out).unwrap();
+    assert_eq!(out, b"one\t  \t    a rather\n");
+    pr.separator = Some(b':');
+    out.clear();
+    pr.write_row(&row, 1, &mut out).unwrap();
+    assert_eq!(out, b"one::a rather\n");
+}
@@ -0,0 +1,179 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// An implementation of the primes(6) command in Rust. primes is not a
+// Seventh Edition command; it comes from the BSD games.
+// See https://man.freebsd.org/cgi/man.cgi?query=primes&sektion=6
+use std::ffi::OsString;
+use std::io;
+use std::io::{BufRead, Write};
+use std::process;
+
+use crate::cmd::Spec;
+use crate::err;
+use crate::status;
+
+/// How many numbers the sieve covers at a time.
+const SEGMENT: u64 = 1 << 18;
+
+/// The primes below this are the ones the sieve strikes out multiples of,
+/// which is enough to find every prime below its square. Beyond that,
+/// the numbers the sieve leaves are tested one by one.
+const SIEVE_LIMIT: u64 = 1 << 16;
+
+/// Returns the primes below a limit, by the sieve of Eratosthenes.
+fn small_primes(limit: u64) -> Vec<u64> {
+    let mut composite = vec![false; limit as usize];
+    let mut primes = Vec::new();
+    for n in 2..limit {
+        if composite[n as usize] {
+            continue;
+        }
+        primes.push(n);
+        for m in (n * n..limit).step_by(n as usize) {
+            composite[m as usize] = true;
+        }
+    }
+    primes
+}
+
+fn mul_mod(a: u64, b: u64, m: u64) -> u64 {
+    (a as u128 * b as u128 % m as u128) as u64
+}
+
+fn pow_mod(mut base: u64, mut exp: u64, m: u64) -> u64 {
+    let mut result = 1;
+    while exp > 0 {
+        if exp & 1 == 1 {
+            result = mul_mod(result, base, m);
+        }
+        base = mul_mod(base, base, m);
+        exp >>= 1;
+    }
+    result
+}
+
+/// Returns whether an odd number with no small factors is prime, by the
+/// Miller-Rabin test, which with these bases is certain for any u64.
+fn is_prime(n: u64) -> bool {
+    let s = (n - 1).trailing_zeros();
+    let d = (n - 1) >> s;
+    [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37].iter().all(|&a| {
+        let mut x = pow_mod(a, d, n);
+        if x == 1 || x == n - 1 {
+            return true;
+        }
+        for _ in 1..s {
+            x = mul_mod(x, x, n);
+            if x == n - 1 {
+                return true;
+            }
+        }
+        false
+    })
+}
+
+/// Writes the primes from start up to, but not including, stop.
+fn primes(out: &mut dyn Write, start: u64, stop: u64) -> io::Result<()> {
+    let base = small_primes(SIEVE_LIMIT);
+    let mut composite = Vec::with_capacity(SEGMENT as usize);
+    let mut lo = start.max(2);
+    while lo < stop {
+        let hi = lo.saturating_add(SEGMENT).min(stop);
+        composite.clear();
+        composite.resize((hi - lo) as usize, false);
+        for &p in base.iter().take_while(|&&p| p * p < hi) {
+            let first = match lo.div_ceil(p).checked_mul(p) {
+                Some(first) => first.max(p * p),
+                None => continue,
+            };
+            if first < hi {
+                for m in (first - lo..hi - lo).step_by(p as usize) {
+                    composite[m as usize] = true;
+                }
+            }
+        }
+        for (i, &c) in composite.iter().enumerate() {
+            let n = lo + i as u64;
+            if !c && (n < SIEVE_LIMIT * SIEVE_LIMIT || is_prime(n)) {
+                writeln!(out, "{}", n)?;
+            }
+        }
+        lo = hi;
+    }
+    Ok(())
+}
+
+/// Parses a number, in decimal.
+fn number(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    if s.starts_with('-') {
+        return Err("negative numbers aren't permitted".to_string());
+    }
+    let digits = s.strip_prefix('+').unwrap_or(s);
+    if digits.is_empty() || !digits.bytes().all(|c| c.is_ascii_digit()) {
+        return Err(format!("{}: illegal numeric format", s));
+    }
+    digits.parse().map_err(|_| format!("{}: result too large", s))
+}
+
+pub const SPEC: Spec = Spec::new("generate primes", &[], "[start [stop]]");
+
+pub fn run(args: Vec<OsString>) -> i32 {
+    let operands = SPEC.parse(args).strings();
+
+    let parse = |s: &str| number(s).unwrap_or_else(|e| {
+        err!("{}", e);
+        process::exit(status::FAILURE);
+    });
+    let (start, stop) = match operands.as_slice() {
+        // Without operands, the start is read from the standard input.
+        [] => {
+            let mut line = String::new();
+            if let Err(e) = io::stdin().lock().read_line(&mut line) {
+                err!("{}", e);
+                process::exit(status::FAILURE);
+            }
+            (parse(&line), u64::MAX)
+        },
+        [start] => (parse(start), u64::MAX),
+        [start, stop] => (parse(start), parse(stop)),
+        _ => SPEC.usage(),
+    };
+    if start > stop {
+        err!("start value must be less than stop value");
+        process::exit(status::FAILURE);
+    }
+
+    let stdout = io::stdout();
+    let mut out = io::BufWriter::new(stdout.lock());
+    if let Err(e) = primes(&mut out, start, stop).and_then(|_| out.flush()) {
+        err!("{}", e);
+        process::exit(status::FAILURE);
+    }
+    status::SUCCESS
+}
+
+#[test]
+fn test_primes() {
+    let list = |start, stop| {
+        let mut out = Vec::new();
+        primes(&mut out, start, stop).unwrap();
+        String::from_utf8(out).unwrap().lines().map(|l| l.parse().unwrap()).collect::<Vec<u64>>()
+    };
+    assert_eq!(list(0, 30), vec![2, 3, 5, 7, 11, 13, 17, 19, 23, 29]);
+    assert_eq!(list(7, 7), vec![]);
+    assert_eq!(list(7, 8), vec![7]);
+    // Across a segment and beyond the square of the sieve limit.
+    let trial = |n: u64| n > 1 && (2..).take_while(|d| d * d <= n).all(|d| !n.is_multiple_of(d));
+    let (lo, hi) = (SEGMENT - 100, SEGMENT + 100);
+    assert_eq!(list(lo, hi), (lo..hi).filter(|&n| trial(n)).collect::<Vec<_>>());
+    let (lo, hi) = (SIEVE_LIMIT * SIEVE_LIMIT - 200, SIEVE_LIMIT * SIEVE_LIMIT + 200);
+    assert_eq!(list(lo, hi), (lo..hi).filter(|&n| trial(n)).collect::<Vec<_>>());
+    // The largest primes there are.
+    assert_eq!(list(u64::MAX - 99, u64::MAX), vec![u64::MAX - 94, u64::MAX - 82, u64::MAX - 58]);
+    assert_eq!(number("+12"), Ok(12));
+    assert!(number("-1").is_err());
+    assert!(number("1x").is_err());
+    assert!(number("18446744073709551616").is_err());
+}
@@ -0,0 +1,102 @@
+// Copyright 2016-2021 James Bostock. See the LICENSE file at the
+// top-level directory of this distribution.
+
+// An implementation of the rm(1) command in Rust.
+// See http://man.cat-v.org/unix-7th/1/rm
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::io;
+use std::io::IsTerminal;
+use std::path::Path;
+
+use crate::cmd::{Opt, Spec};
+use crate::err;
+use crate::mode;
+use crate::platform;
+use crate::prompt;
+use crate::status;
+
+/// Removes a file or directory. Returns Ok(false) if it is refused, and
+/// an error if one of the filesystem operations fails.
+fn rm(name: &OsStr, force: bool, recursive: bool,
+      interactive: bool) -> io::Result<bool> {
+    let md = fs::symlink_metadata(name)?;
+    let readonly = md.permissions().readonly();
+
+    if name == "." || name == ".." {
+	err!("cannot remove directory '{}'", name.to_string_lossy());
+	return Ok(false)
+    }
+
+    if md.is_dir() && !recursive {
+	err!("cannot remove '{}': it is a directory", name.to_string_lossy());
+	return Ok(false)
+    }
+
+    // POSIX asks about read-only files only if the answer can come from a
+    // terminal; the Seventh Edition always asked.
+    let ask = !force && readonly && (!mode::posix() || io::stdin().is_terminal());
+    let go = if ask || interactive {
+	let msg = format!("remove {}{} '{}'?",
+			  if readonly {
+			      "readonly "
+			  } else {
+			      ""
+			  },
+			  if md.is_dir() {
+			      "directory"
+			  } else {
+			      "file"
+			  },
+			  name.to_string_lossy()
+	);
+        prompt::ask(&msg)
+    } else {
+        true
+    };
+
+    if go {
+        if md.is_dir() {
+            platform::remove_all(Path::new(name))?
+        } else {
+	    platform::remove_file(Path::new(name))?
+        }
+    }
+    Ok(true)
+}
+
+pub const SPEC: Spec = Spec::new("remove files", &[
+    Opt::flag('f', "remove without asking, even read-only files"),
+    Opt::flag('r', "remove directories and their contents"),
+    Opt::flag('i', "ask before removing each file"),
+], "file ...");
+
+pub fn run(args: Vec<OsString>) -> i32 {
+    let parsed = SPEC.parse(args);
+    let force = parsed.flag('f');
+    let recursive = parsed.flag('r');
+    let interactive = parsed.flag('i');
+    let mut status = status::SUCCESS;
+
+    if parsed.operands.is_empty() && !(force && mode::posix()) {
+        SPEC.usage();
+    }
+    for arg in &parsed.operands {
+        match rm(arg, force, recursive, interactive) {
+            Ok(true) => (),
+            Ok(false) => status = status::FAILURE,
+            // With -f, a file that doesn't exist goes unmentioned and, in
+            // POSIX, doesn't count as a failure.
+            Err(e) if force && e.kind() == io::ErrorKind::NotFound => {
+                if !mode::posix() {
+                    status = status::FAILURE;
+                }
+            },
+            Err(e) => {
+                err!("{}: {}", arg.to_string_lossy(), e);
+                status = status::FAILURE;
+            }
+        }
+    }
+    status
+}
@@ -0,0 +1,46 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// An implementation of the rmdir(1) command in Rust.
+// See http://man.cat-v.org/unix-7th/1/rmdir
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::cmd::{Opt, Spec};
+use crate::diag;
+use crate::err;
+use crate::trace;
+
+/// Removes an empty directory.
+fn rmdir(path: &Path) -> io::Result<()> {
+    trace::op("rmdir", format_args!("{:?}", path), fs::remove_dir(path))
+}
+
+pub const SPEC: Spec = Spec::new("remove empty directories", &[
+    Opt::flag('p', "remove each directory in the path too, from the last up"),
+], "dir ...");
+
+pub fn run(args: Vec<OsString>) -> i32 {
+    let parsed = SPEC.parse(args);
+    let parents = parsed.flag('p');
+    if parsed.operands.is_empty() {
+        SPEC.usage();
+    }
+    for dir in &parsed.operands {
+        let mut path = Path::new(dir);
+        loop {
+            if let Err(e) = rmdir(path) {
+                err!("{}: {}", path.display(), e);
+                break;
+            }
+            // With -p, a/b/c is followed by a/b and then a.
+            match path.parent() {
+                Some(parent) if parents && !parent.as_os_str().is_empty() => path = parent,
+                _ => break,
+            }
+        }
+    }
+    diag::status()
+}
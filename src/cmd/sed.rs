@@ -0,0 +1,1317 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// An implementation of the sed(1) command in Rust.
+// See http://man.cat-v.org/unix-7th/1/sed
+use std::collections::HashMap;
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::process;
+use std::rc::Rc;
+
+use crate::atomic::AtomicFile;
+use crate::cmd::{Opt, Spec};
+use crate::err;
+use crate::input::Input;
+use crate::regex::{Regex, Syntax};
+use crate::status;
+
+/// A line address.
+enum Addr {
+    Line(usize),
+    Last,
+    /// A regular expression; None means the last one used.
+    Re(Option<Rc<Regex>>),
+}
+
+/// A piece of the replacement text of a substitution.
+enum Repl {
+    Literal(Vec<u8>),
+    Group(usize),
+}
+
+struct Subst {
+    re: Option<Rc<Regex>>,
+    repl: Vec<Repl>,
+    global: bool,
+    nth: usize,
+    print: bool,
+    wfile: Option<usize>,
+}
+
+enum Cmd {
+    /// The start of a block; holds the index of the command following
+    /// the matching '}'.
+    Block(usize),
+    EndBlock,
+    Subst(Box<Subst>),
+    Delete,
+    Print,
+    Quit,
+    LineNum,
+    Read(String),
+    Write(usize),
+    Hold,
+    HoldAppend,
+    Get,
+    GetAppend,
+    Exchange,
+    Label,
+    /// Branches hold the index of the command to continue at.
+    Branch(usize),
+    Test(usize),
+    Next,
+    NextAppend,
+    DeleteFirst,
+    PrintFirst,
+    Append(Vec<u8>),
+    Insert(Vec<u8>),
+    Change(Vec<u8>),
+    Translate(Box<[u8; 256]>),
+}
+
+struct Command {
+    addr1: Option<Addr>,
+    addr2: Option<Addr>,
+    negate: bool,
+    cmd: Cmd,
+}
+
+/// Parses a sed script into a list of commands.
+struct Parser<'a> {
+    script: &'a [u8],
+    pos: usize,
+    /// The files named by w commands and s///w flags, opened when parsed.
+    wfiles: Vec<File>,
+    wnames: HashMap<String, usize>,
+    labels: HashMap<String, usize>,
+    /// Branch commands whose labels are resolved once parsing completes.
+    branches: Vec<(usize, String)>,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.script.get(self.pos).copied()
+    }
+
+    fn skip_blanks(&mut self) {
+        while let Some(b' ') | Some(b'\t') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    fn error<T>(&self, msg: &str) -> Result<T, String> {
+        Err(format!("{} at char {}", msg, self.pos))
+    }
+
+    fn parse(&mut self) -> Result<Vec<Command>, String> {
+        let mut prog: Vec<Command> = Vec::new();
+        let mut blocks: Vec<usize> = Vec::new();
+        loop {
+            while let Some(b' ') | Some(b'\t') | Some(b'\n') | Some(b';') = self.peek() {
+                self.pos += 1;
+            }
+            match self.peek() {
+                None => break,
+                Some(b'#') => {
+                    self.rest_of_line();
+                    continue;
+                },
+                _ => {}
+            }
+            let addr1 = self.parse_addr()?;
+            let addr2 = if addr1.is_some() && self.peek() == Some(b',') {
+                self.pos += 1;
+                match self.parse_addr()? {
+                    Some(a) => Some(a),
+                    None => return self.error("expected address"),
+                }
+            } else {
+                None
+            };
+            self.skip_blanks();
+            let mut negate = false;
+            while self.peek() == Some(b'!') {
+                negate = true;
+                self.pos += 1;
+                self.skip_blanks();
+            }
+            let c = match self.peek() {
+                Some(c) => c,
+                None => return self.error("missing command"),
+            };
+            self.pos += 1;
+            if c == b':' && addr1.is_some() {
+                return self.error(": doesn't want any addresses");
+            }
+            let cmd = match c {
+                b'{' => {
+                    blocks.push(prog.len());
+                    Cmd::Block(0)
+                },
+                b'}' => {
+                    if addr1.is_some() || negate {
+                        return self.error("} doesn't want any addresses");
+                    }
+                    match blocks.pop() {
+                        Some(start) => prog[start].cmd = Cmd::Block(prog.len() + 1),
+                        None => return self.error("unexpected }"),
+                    }
+                    Cmd::EndBlock
+                },
+                b's' => Cmd::Subst(Box::new(self.parse_subst()?)),
+                b'd' => Cmd::Delete,
+                b'p' => Cmd::Print,
+                b'q' => Cmd::Quit,
+                b'=' => Cmd::LineNum,
+                b'r' => Cmd::Read(self.filename()?),
+                b'w' => {
+                    let name = self.filename()?;
+                    Cmd::Write(self.wfile(name)?)
+                },
+                b'h' => Cmd::Hold,
+                b'H' => Cmd::HoldAppend,
+                b'g' => Cmd::Get,
+                b'G' => Cmd::GetAppend,
+                b'x' => Cmd::Exchange,
+                b':' => {
+                    let label = self.label();
+                    if label.is_empty() {
+                        return self.error("\":\" lacks a label");
+                    }
+                    if self.labels.insert(label, prog.len()).is_some() {
+                        return self.error("duplicate label");
+                    }
+                    Cmd::Label
+                },
+                b'b' | b't' => {
+                    let label = self.label();
+                    self.branches.push((prog.len(), label));
+                    if c == b'b' {
+                        Cmd::Branch(0)
+                    } else {
+                        Cmd::Test(0)
+                    }
+                },
+                b'n' => Cmd::Next,
+                b'N' => Cmd::NextAppend,
+                b'D' => Cmd::DeleteFirst,
+                b'P' => Cmd::PrintFirst,
+                b'a' => Cmd::Append(self.text()),
+                b'i' => Cmd::Insert(self.text()),
+                b'c' => Cmd::Change(self.text()),
+                b'y' => Cmd::Translate(self.parse_translate()?),
+                _ => return self.error(&format!("unknown command: '{}'", c as char)),
+            };
+            if matches!(cmd, Cmd::Quit | Cmd::Append(_) | Cmd::Insert(_) | Cmd::Read(_) | Cmd::LineNum)
+                && addr2.is_some() {
+                return self.error("command only uses one address");
+            }
+            // Blocks and text arguments end the command themselves.
+            let ended = matches!(cmd, Cmd::Block(_) | Cmd::Append(_) | Cmd::Insert(_) | Cmd::Change(_));
+            prog.push(Command { addr1, addr2, negate, cmd });
+            if !ended {
+                self.end_of_command()?;
+            }
+        }
+        if !blocks.is_empty() {
+            return self.error("unmatched {");
+        }
+        for (i, label) in std::mem::take(&mut self.branches) {
+            let target = if label.is_empty() {
+                prog.len()
+            } else {
+                match self.labels.get(&label) {
+                    Some(&target) => target,
+                    None => return Err(format!("can't find label for jump to '{}'", label)),
+                }
+            };
+            prog[i].cmd = match prog[i].cmd {
+                Cmd::Branch(_) => Cmd::Branch(target),
+                _ => Cmd::Test(target),
+            };
+        }
+        Ok(prog)
+    }
+
+    /// Reads a label, which ends at a newline or semicolon.
+    fn label(&mut self) -> String {
+        self.skip_blanks();
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if c == b'\n' || c == b';' {
+                break;
+            }
+            self.pos += 1;
+        }
+        String::from_utf8_lossy(&self.script[start..self.pos]).trim_end().to_string()
+    }
+
+    /// Reads the text argument of an a, i or c command. The text may
+    /// follow the command on the same line or, traditionally, start on the
+    /// next line after a backslash. Lines ending in a backslash continue
+    /// the text.
+    fn text(&mut self) -> Vec<u8> {
+        self.skip_blanks();
+        if self.peek() == Some(b'\\') {
+            self.pos += 1;
+            self.skip_blanks();
+            if self.peek() == Some(b'\n') {
+                self.pos += 1;
+            }
+        }
+        let mut text = Vec::new();
+        while let Some(c) = self.peek() {
+            self.pos += 1;
+            match c {
+                b'\n' => break,
+                b'\\' => if let Some(d) = self.peek() {
+                    self.pos += 1;
+                    text.push(d);
+                },
+                _ => text.push(c),
+            }
+        }
+        text.push(b'\n');
+        text
+    }
+
+    fn parse_translate(&mut self) -> Result<Box<[u8; 256]>, String> {
+        let delim = match self.peek() {
+            Some(b'\n') | Some(b'\\') | None => {
+                return self.error("invalid transliteration delimiter")
+            },
+            Some(c) => c,
+        };
+        self.pos += 1;
+        let src = unescape(&self.delimited(delim, false)?);
+        let dst = unescape(&self.delimited(delim, false)?);
+        if src.len() != dst.len() {
+            return self.error("strings for y command are different lengths");
+        }
+        let mut table = Box::new([0u8; 256]);
+        for (i, b) in table.iter_mut().enumerate() {
+            *b = i as u8;
+        }
+        for (s, d) in src.iter().zip(dst.iter()) {
+            table[*s as usize] = *d;
+        }
+        Ok(table)
+    }
+
+    /// Checks that nothing but a separator follows a command.
+    fn end_of_command(&mut self) -> Result<(), String> {
+        self.skip_blanks();
+        match self.peek() {
+            None | Some(b'\n') | Some(b';') | Some(b'}') | Some(b'#') => Ok(()),
+            Some(_) => self.error("extra characters after command"),
+        }
+    }
+
+    fn rest_of_line(&mut self) -> &'a [u8] {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if c == b'\n' {
+                break;
+            }
+            self.pos += 1;
+        }
+        &self.script[start..self.pos]
+    }
+
+    fn filename(&mut self) -> Result<String, String> {
+        self.skip_blanks();
+        let name = String::from_utf8_lossy(self.rest_of_line()).into_owned();
+        if name.is_empty() {
+            return self.error("missing filename");
+        }
+        Ok(name)
+    }
+
+    /// Returns the index of the (truncated) output file with the given
+    /// name, opening it if this is its first use.
+    fn wfile(&mut self, name: String) -> Result<usize, String> {
+        if let Some(&i) = self.wnames.get(&name) {
+            return Ok(i);
+        }
+        match File::create(&name) {
+            Ok(f) => {
+                self.wfiles.push(f);
+                self.wnames.insert(name, self.wfiles.len() - 1);
+                Ok(self.wfiles.len() - 1)
+            },
+            Err(e) => Err(format!("{}: {}", name, e)),
+        }
+    }
+
+    fn parse_addr(&mut self) -> Result<Option<Addr>, String> {
+        match self.peek() {
+            Some(b'0'..=b'9') => {
+                let mut n = 0usize;
+                while let Some(c @ b'0'..=b'9') = self.peek() {
+                    n = n * 10 + (c - b'0') as usize;
+                    self.pos += 1;
+                }
+                Ok(Some(Addr::Line(n)))
+            },
+            Some(b'$') => {
+                self.pos += 1;
+                Ok(Some(Addr::Last))
+            },
+            Some(b'/') => {
+                self.pos += 1;
+                Ok(Some(Addr::Re(self.regex(b'/')?)))
+            },
+            Some(b'\\') => {
+                self.pos += 1;
+                match self.peek() {
+                    Some(delim) if delim != b'\n' && delim != b'\\' => {
+                        self.pos += 1;
+                        Ok(Some(Addr::Re(self.regex(delim)?)))
+                    },
+                    _ => self.error("invalid regular expression delimiter"),
+                }
+            },
+            _ => Ok(None),
+        }
+    }
+
+    /// Collects text up to an unescaped delimiter. Escaped delimiters are
+    /// replaced by the delimiter itself; all other escapes are kept. The
+    /// delimiter may appear unescaped in a bracket expression if the text
+    /// is a regular expression.
+    fn delimited(&mut self, delim: u8, brackets: bool) -> Result<Vec<u8>, String> {
+        let mut text = Vec::new();
+        loop {
+            let c = match self.peek() {
+                Some(b'\n') | None => return self.error("unterminated expression"),
+                Some(c) => c,
+            };
+            self.pos += 1;
+            if c == delim {
+                return Ok(text);
+            } else if c == b'\\' {
+                match self.peek() {
+                    Some(d) if d == delim => text.push(d),
+                    Some(b'\n') => text.push(b'\n'),
+                    Some(d) => {
+                        text.push(b'\\');
+                        text.push(d);
+                    },
+                    None => return self.error("unterminated expression"),
+                }
+                self.pos += 1;
+            } else if c == b'[' && brackets {
+                text.push(c);
+                self.bracket(&mut text)?;
+            } else {
+                text.push(c);
+            }
+        }
+    }
+
+    /// Copies the remainder of a bracket expression verbatim.
+    fn bracket(&mut self, text: &mut Vec<u8>) -> Result<(), String> {
+        let start = self.pos;
+        loop {
+            let c = match self.peek() {
+                Some(b'\n') | None => return self.error("unterminated bracket expression"),
+                Some(c) => c,
+            };
+            self.pos += 1;
+            text.push(c);
+            let first = self.pos - start == 1
+                || (self.pos - start == 2 && self.script[start] == b'^');
+            if c == b']' && !first {
+                return Ok(());
+            }
+            if c == b'[' {
+                if let Some(k @ (b':' | b'.' | b'=')) = self.peek() {
+                    self.pos += 1;
+                    text.push(k);
+                    while let Some(d) = self.peek() {
+                        self.pos += 1;
+                        text.push(d);
+                        if d == k && self.peek() == Some(b']') {
+                            self.pos += 1;
+                            text.push(b']');
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn regex(&mut self, delim: u8) -> Result<Option<Rc<Regex>>, String> {
+        let text = self.delimited(delim, true)?;
+        if text.is_empty() {
+            return Ok(None);
+        }
+        match Regex::with_syntax(&text, Syntax::Basic, false) {
+            Ok(re) => Ok(Some(Rc::new(re))),
+            Err(e) => self.error(&e.to_string()),
+        }
+    }
+
+    fn parse_subst(&mut self) -> Result<Subst, String> {
+        let delim = match self.peek() {
+            Some(b'\n') | Some(b'\\') | None => {
+                return self.error("invalid substitution delimiter")
+            },
+            Some(c) => c,
+        };
+        self.pos += 1;
+        let re_text = self.delimited(delim, true)?;
+        let raw = self.delimited(delim, false)?;
+
+        let mut repl = Vec::new();
+        let mut lit = Vec::new();
+        let mut iter = raw.iter();
+        while let Some(&c) = iter.next() {
+            match c {
+                b'&' => {
+                    if !lit.is_empty() {
+                        repl.push(Repl::Literal(std::mem::take(&mut lit)));
+                    }
+                    repl.push(Repl::Group(0));
+                },
+                b'\\' => match iter.next() {
+                    Some(&d @ b'0'..=b'9') => {
+                        if !lit.is_empty() {
+                            repl.push(Repl::Literal(std::mem::take(&mut lit)));
+                        }
+                        repl.push(Repl::Group((d - b'0') as usize));
+                    },
+                    Some(b'n') => lit.push(b'\n'),
+                    Some(&d) => lit.push(d),
+                    None => lit.push(b'\\'),
+                },
+                _ => lit.push(c),
+            }
+        }
+        if !lit.is_empty() {
+            repl.push(Repl::Literal(lit));
+        }
+
+        let mut subst = Subst {
+            re: None,
+            repl,
+            global: false,
+            nth: 1,
+            print: false,
+            wfile: None,
+        };
+        let mut icase = false;
+        let mut nth = None;
+        loop {
+            match self.peek() {
+                Some(b'g') => subst.global = true,
+                Some(b'p') => subst.print = true,
+                Some(b'I') => icase = true,
+                Some(b'0'..=b'9') => {
+                    let mut n = 0usize;
+                    while let Some(c @ b'0'..=b'9') = self.peek() {
+                        n = n * 10 + (c - b'0') as usize;
+                        self.pos += 1;
+                    }
+                    if n == 0 || nth.is_some() {
+                        return self.error("invalid substitution count");
+                    }
+                    nth = Some(n);
+                    continue;
+                },
+                Some(b'w') => {
+                    self.pos += 1;
+                    let name = self.filename()?;
+                    subst.wfile = Some(self.wfile(name)?);
+                    break;
+                },
+                _ => break,
+            }
+            self.pos += 1;
+        }
+        subst.nth = nth.unwrap_or(1);
+
+        if !re_text.is_empty() {
+            match Regex::with_syntax(&re_text, Syntax::Basic, icase) {
+                Ok(re) => {
+                    let max = subst.repl.iter().map(|r| match r {
+                        Repl::Group(n) => *n,
+                        _ => 0,
+                    }).max().unwrap_or(0);
+                    if max > re.groups() {
+                        return self.error("invalid reference in replacement");
+                    }
+                    subst.re = Some(Rc::new(re));
+                },
+                Err(e) => return self.error(&e.to_string()),
+            }
+        }
+        Ok(subst)
+    }
+}
+
+/// Replaces the escape sequences in the text of a y command.
+fn unescape(text: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut iter = text.iter();
+    while let Some(&c) = iter.next() {
+        if c == b'\\' {
+            match iter.next() {
+                Some(b'n') => out.push(b'\n'),
+                Some(&d) => out.push(d),
+                None => out.push(c),
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Reads lines from a sequence of files as if they were a single stream,
+/// looking one line ahead so that the last line can be recognised.
+struct Lines {
+    files: std::vec::IntoIter<OsString>,
+    reader: Option<BufReader<Input>>,
+    next: Option<Vec<u8>>,
+    failed: bool,
+}
+
+impl Lines {
+    fn new(files: Vec<OsString>) -> Lines {
+        Lines {
+            files: files.into_iter(),
+            reader: None,
+            next: None,
+            failed: false,
+        }
+    }
+
+    fn fill(&mut self) -> io::Result<()> {
+        while self.next.is_none() {
+            let reader = match self.reader {
+                Some(ref mut reader) => reader,
+                None => match self.files.next() {
+                    Some(name) => match Input::open(&name) {
+                        Ok(input) => self.reader.insert(BufReader::new(input)),
+                        Err(e) => {
+                            err!("{}: {}", name.to_string_lossy(), e);
+                            self.failed = true;
+                            continue;
+                        }
+                    },
+                    None => return Ok(()),
+                },
+            };
+            let mut buf = Vec::new();
+            if reader.read_until(b'\n', &mut buf)? == 0 {
+                self.reader = None;
+            } else {
+                self.next = Some(buf);
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the next line (without its newline) and whether the line
+    /// was terminated by a newline.
+    fn next_line(&mut self) -> io::Result<Option<(Vec<u8>, bool)>> {
+        self.fill()?;
+        Ok(self.next.take().map(|mut line| {
+            let newline = line.last() == Some(&b'\n');
+            if newline {
+                line.pop();
+            }
+            (line, newline)
+        }))
+    }
+
+    fn is_last(&mut self) -> io::Result<bool> {
+        self.fill()?;
+        Ok(self.next.is_none())
+    }
+}
+
+/// What to do once a script has finished executing on a line.
+enum Action {
+    Next,
+    Delete,
+    Quit,
+    /// Start the next cycle without reading a new line.
+    Restart,
+}
+
+/// Text to be written at the end of the cycle.
+enum Append {
+    Text(Vec<u8>),
+    File(String),
+}
+
+/// Where the output of the script goes.
+enum Output {
+    Stdout(BufWriter<io::Stdout>),
+    InPlace(BufWriter<AtomicFile>),
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Output::Stdout(w) => w.write(buf),
+            Output::InPlace(w) => w.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Output::Stdout(w) => w.flush(),
+            Output::InPlace(w) => w.flush(),
+        }
+    }
+}
+
+struct Sed {
+    prog: Rc<[Command]>,
+    /// Whether each command's address range is currently active.
+    in_range: Vec<bool>,
+    quiet: bool,
+    input: Lines,
+    out: Output,
+    /// Set when the last line written lacked a newline, which must be
+    /// supplied if anything else is written.
+    missing_newline: bool,
+    /// True if the current line was not terminated by a newline.
+    unterminated: bool,
+    wfiles: Vec<File>,
+    line: usize,
+    last_re: Option<Rc<Regex>>,
+    hold: Vec<u8>,
+    /// Set when a substitution has been made since the last line was read
+    /// or t command executed.
+    replaced: bool,
+    append: Vec<Append>,
+}
+
+impl Sed {
+    fn write(&mut self, data: &[u8]) -> io::Result<()> {
+        if self.missing_newline {
+            self.out.write_all(b"\n")?;
+            self.missing_newline = false;
+        }
+        self.out.write_all(data)
+    }
+
+    /// Writes the pattern space, reproducing a missing final newline.
+    fn write_line(&mut self, ps: &[u8]) -> io::Result<()> {
+        self.write(ps)?;
+        if self.unterminated && self.input.is_last()? {
+            self.missing_newline = true;
+            Ok(())
+        } else {
+            self.out.write_all(b"\n")
+        }
+    }
+
+    /// Reads the next input line, if there is one.
+    fn read_line(&mut self) -> io::Result<Option<Vec<u8>>> {
+        Ok(self.input.next_line()?.map(|(line, newline)| {
+            self.line += 1;
+            self.unterminated = !newline;
+            self.replaced = false;
+            line
+        }))
+    }
+
+    fn regex(&mut self, re: &Option<Rc<Regex>>) -> io::Result<Rc<Regex>> {
+        if let Some(re) = re {
+            self.last_re = Some(Rc::clone(re));
+        }
+        match self.last_re {
+            Some(ref re) => Ok(Rc::clone(re)),
+            None => Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                       "no previous regular expression")),
+        }
+    }
+
+    fn addr_matches(&mut self, addr: &Addr, ps: &[u8]) -> io::Result<bool> {
+        Ok(match addr {
+            Addr::Line(n) => self.line == *n,
+            Addr::Last => self.input.is_last()?,
+            Addr::Re(re) => self.regex(re)?.is_match(ps),
+        })
+    }
+
+    /// Determines whether a command applies to the pattern space, updating
+    /// the state of its range if it has one.
+    fn selected(&mut self, i: usize, cmd: &Command, ps: &[u8]) -> io::Result<bool> {
+        let matched = match (&cmd.addr1, &cmd.addr2) {
+            (None, _) => true,
+            (Some(addr1), None) => self.addr_matches(addr1, ps)?,
+            (Some(addr1), Some(addr2)) => {
+                if self.in_range[i] {
+                    let end = match addr2 {
+                        Addr::Line(n) => self.line >= *n,
+                        _ => self.addr_matches(addr2, ps)?,
+                    };
+                    if end {
+                        self.in_range[i] = false;
+                    }
+                    true
+                } else if self.addr_matches(addr1, ps)? {
+                    // A line number that has already been passed ends the
+                    // range immediately.
+                    self.in_range[i] = match addr2 {
+                        Addr::Line(n) => *n > self.line,
+                        _ => true,
+                    };
+                    true
+                } else {
+                    false
+                }
+            },
+        };
+        Ok(matched != cmd.negate)
+    }
+
+    fn subst(&mut self, s: &Subst, ps: &mut Vec<u8>) -> io::Result<bool> {
+        let re = self.regex(&s.re)?;
+        let mut result = Vec::new();
+        let mut copied = 0;
+        let mut pos = 0;
+        let mut count = 0;
+        let mut prev_end = None;
+        let mut replaced = false;
+        while pos <= ps.len() {
+            let m = match re.find_at(ps, pos) {
+                Some(m) => m,
+                None => break,
+            };
+            // An empty match adjacent to the previous match doesn't count.
+            if m.start() == m.end() && prev_end == Some(m.start()) {
+                pos = m.start() + 1;
+                continue;
+            }
+            count += 1;
+            if count >= s.nth {
+                result.extend_from_slice(&ps[copied..m.start()]);
+                for piece in &s.repl {
+                    match piece {
+                        Repl::Literal(text) => result.extend_from_slice(text),
+                        Repl::Group(n) => if let Some((b, e)) = m.get(*n) {
+                            result.extend_from_slice(&ps[b..e]);
+                        },
+                    }
+                }
+                copied = m.end();
+                replaced = true;
+                if !s.global {
+                    break;
+                }
+            }
+            prev_end = Some(m.end());
+            pos = if m.end() == m.start() { m.end() + 1 } else { m.end() };
+        }
+        if replaced {
+            result.extend_from_slice(&ps[copied..]);
+            *ps = result;
+        }
+        Ok(replaced)
+    }
+
+    /// Runs the script over the pattern space.
+    fn execute(&mut self, ps: &mut Vec<u8>) -> io::Result<Action> {
+        let prog = Rc::clone(&self.prog);
+        let mut pc = 0;
+        while pc < prog.len() {
+            if !self.selected(pc, &prog[pc], ps)? {
+                pc = match prog[pc].cmd {
+                    Cmd::Block(end) => end,
+                    _ => pc + 1,
+                };
+                continue;
+            }
+            match &prog[pc].cmd {
+                Cmd::Block(_) | Cmd::EndBlock | Cmd::Label => {},
+                Cmd::Subst(s) => {
+                    if self.subst(s, ps)? {
+                        self.replaced = true;
+                        if s.print {
+                            self.write_line(ps)?;
+                        }
+                        if let Some(w) = s.wfile {
+                            self.wfiles[w].write_all(ps)?;
+                            self.wfiles[w].write_all(b"\n")?;
+                        }
+                    }
+                },
+                Cmd::Delete => return Ok(Action::Delete),
+                Cmd::Print => self.write_line(ps)?,
+                Cmd::Quit => return Ok(Action::Quit),
+                Cmd::LineNum => {
+                    let num = format!("{}\n", self.line);
+                    self.write(num.as_bytes())?;
+                },
+                Cmd::Read(name) => self.append.push(Append::File(name.clone())),
+                Cmd::Write(w) => {
+                    self.wfiles[*w].write_all(ps)?;
+                    self.wfiles[*w].write_all(b"\n")?;
+                },
+                Cmd::Hold => self.hold.clone_from(ps),
+                Cmd::HoldAppend => {
+                    self.hold.push(b'\n');
+                    self.hold.extend_from_slice(ps);
+                },
+                Cmd::Get => ps.clone_from(&self.hold),
+                Cmd::GetAppend => {
+                    ps.push(b'\n');
+                    ps.extend_from_slice(&self.hold);
+                },
+                Cmd::Exchange => std::mem::swap(ps, &mut self.hold),
+                Cmd::Branch(target) => {
+                    pc = *target;
+                    continue;
+                },
+                Cmd::Test(target) => {
+                    if self.replaced {
+                        self.replaced = false;
+                        pc = *target;
+                        continue;
+                    }
+                },
+                Cmd::Next => {
+                    // Without a next line, quit as if the script had ended.
+                    if self.input.is_last()? {
+                        return Ok(Action::Quit);
+                    }
+                    if !self.quiet {
+                        self.write_line(ps)?;
+                    }
+                    self.flush_append()?;
+                    if let Some(line) = self.read_line()? {
+                        *ps = line;
+                    }
+                },
+                Cmd::NextAppend => {
+                    if self.input.is_last()? {
+                        return Ok(Action::Quit);
+                    }
+                    self.flush_append()?;
+                    if let Some(line) = self.read_line()? {
+                        ps.push(b'\n');
+                        ps.extend_from_slice(&line);
+                    }
+                },
+                Cmd::DeleteFirst => {
+                    return Ok(match ps.iter().position(|&c| c == b'\n') {
+                        Some(i) => {
+                            ps.drain(..=i);
+                            Action::Restart
+                        },
+                        None => Action::Delete,
+                    });
+                },
+                Cmd::PrintFirst => {
+                    let end = ps.iter().position(|&c| c == b'\n').unwrap_or(ps.len());
+                    self.write(&ps[..end])?;
+                    self.write(b"\n")?;
+                },
+                Cmd::Append(text) => self.append.push(Append::Text(text.clone())),
+                Cmd::Insert(text) => self.write(text)?,
+                Cmd::Change(text) => {
+                    // For a range, the text replaces the whole range.
+                    if prog[pc].addr2.is_none() || !self.in_range[pc] || prog[pc].negate {
+                        self.write(text)?;
+                    }
+                    return Ok(Action::Delete);
+                },
+                Cmd::Translate(table) => {
+                    for c in ps.iter_mut() {
+                        *c = table[*c as usize];
+                    }
+                },
+            }
+            pc += 1;
+        }
+        Ok(Action::Next)
+    }
+
+    /// Writes out the text queued by a and r commands.
+    fn flush_append(&mut self) -> io::Result<()> {
+        for append in std::mem::take(&mut self.append) {
+            match append {
+                Append::Text(text) => self.write(&text)?,
+                // Unreadable files are silently ignored.
+                Append::File(name) => if let Ok(data) = fs::read(&name) {
+                    self.write(&data)?;
+                },
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs the script over the input, returning true if it quit early.
+    fn run(&mut self) -> io::Result<bool> {
+        let mut ps = Vec::new();
+        let mut restart = false;
+        loop {
+            if !restart {
+                match self.read_line()? {
+                    Some(line) => ps = line,
+                    None => break,
+                }
+            }
+            let action = self.execute(&mut ps)?;
+            if matches!(action, Action::Next | Action::Quit) && !self.quiet {
+                self.write_line(&ps)?;
+            }
+            self.flush_append()?;
+            match action {
+                Action::Quit => {
+                    self.out.flush()?;
+                    return Ok(true);
+                },
+                Action::Restart => restart = true,
+                _ => restart = false,
+            }
+        }
+        self.out.flush()?;
+        Ok(false)
+    }
+
+    /// Edits a file in place, optionally keeping a backup copy of the
+    /// original with the given suffix. Returns true if the script quit.
+    fn edit(&mut self, file: &OsStr, suffix: &str) -> io::Result<bool> {
+        if !fs::metadata(file)?.is_file() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "not a regular file"));
+        }
+        if !suffix.is_empty() {
+            let mut backup = file.to_os_string();
+            backup.push(suffix);
+            fs::copy(file, backup)?;
+        }
+        let tmp = AtomicFile::create(file)?;
+        self.input = Lines::new(vec![file.to_os_string()]);
+        self.out = Output::InPlace(BufWriter::new(tmp));
+        self.line = 0;
+        self.missing_newline = false;
+        self.in_range.iter_mut().for_each(|r| *r = false);
+        let quit = self.run()?;
+        let out = std::mem::replace(&mut self.out, Output::Stdout(BufWriter::new(io::stdout())));
+        if let Output::InPlace(w) = out {
+            if !self.input.failed {
+                w.into_inner().map_err(|e| e.into_error())?.commit()?;
+            }
+        }
+        Ok(quit)
+    }
+}
+
+pub const SPEC: Spec = Spec::new("stream editor", &[
+    Opt::flag('n', "print only what the script prints"),
+    Opt::flag('i', "edit the files in place, keeping copies with the suffix that follows, if any"),
+    Opt::arg('e', "script", "add the commands in script"),
+    Opt::arg('f', "script_file", "add the commands in script_file"),
+], "[file ...]");
+
+/// Takes the -i option, whose suffix is optional and so must be attached
+/// to it, out of the arguments, returning the suffix if it was given. The
+/// options end at the first operand or "--", and an i among a group of
+/// them, as in -ni.bak, takes the rest of the group as its suffix.
+fn take_in_place(args: &mut Vec<OsString>) -> Option<String> {
+    let mut in_place = None;
+    let mut i = 1;
+    while i < args.len() {
+        let arg = args[i].to_string_lossy().into_owned();
+        if arg == "--" || arg.len() < 2 || !arg.starts_with('-') {
+            break;
+        }
+        // The first e, f or i in the group ends it.
+        let group = &arg[1..];
+        match group.find(['e', 'f', 'i']) {
+            Some(n) if group[n..].starts_with('i') => {
+                in_place = Some(group[n + 1..].to_string());
+                if n == 0 {
+                    args.remove(i);
+                    continue;
+                }
+                args[i] = OsString::from(format!("-{}", &group[..n]));
+            },
+            // The script follows as the next argument.
+            Some(n) if n + 1 == group.len() => i += 1,
+            _ => (),
+        }
+        i += 1;
+    }
+    in_place
+}
+
+pub fn run(mut args: Vec<OsString>) -> i32 {
+    let in_place = take_in_place(&mut args);
+    let parsed = SPEC.parse(args);
+    let mut quiet = parsed.flag('n');
+    let mut script: Option<Vec<u8>> = None;
+
+    let add_script = |text: &[u8], script: &mut Option<Vec<u8>>| {
+        let s = script.get_or_insert_with(Vec::new);
+        if !s.is_empty() {
+            s.push(b'\n');
+        }
+        s.extend_from_slice(text);
+    };
+
+    for (c, arg) in parsed.opts_os() {
+        match (c, arg) {
+            ('e', Some(arg)) => add_script(arg.as_encoded_bytes(), &mut script),
+            ('f', Some(arg)) => match fs::read(arg) {
+                Ok(text) => add_script(&text, &mut script),
+                Err(e) => {
+                    err!("{}: {}", arg.to_string_lossy(), e);
+                    process::exit(status::FAILURE);
+                }
+            },
+            _ => (),
+        }
+    }
+    let mut files = parsed.operands;
+    if script.is_none() && !files.is_empty() {
+        script = Some(files.remove(0).into_encoded_bytes());
+    }
+
+    let Some(script) = script else {
+        SPEC.usage();
+    };
+    if script.starts_with(b"#n\n") || script == b"#n" {
+        quiet = true;
+    }
+
+    let mut parser = Parser {
+        script: &script,
+        pos: 0,
+        wfiles: Vec::new(),
+        wnames: HashMap::new(),
+        labels: HashMap::new(),
+        branches: Vec::new(),
+    };
+    let commands = match parser.parse() {
+        Ok(commands) => commands,
+        Err(e) => {
+            err!("{}", e);
+            process::exit(status::FAILURE);
+        }
+    };
+
+    if files.is_empty() {
+        if in_place.is_some() {
+            err!("no input files");
+            process::exit(status::FAILURE);
+        }
+        files.push(OsString::from("-"));
+    }
+
+    let mut sed = Sed {
+        in_range: vec![false; commands.len()],
+        prog: commands.into(),
+        quiet,
+        input: Lines::new(Vec::new()),
+        out: Output::Stdout(BufWriter::new(io::stdout())),
+        missing_newline: false,
+        unterminated: false,
+        wfiles: parser.wfiles,
+        line: 0,
+        last_re: None,
+        hold: Vec::new(),
+        replaced: false,
+        append: Vec::new(),
+    };
+
+    let mut failed = false;
+    match in_place {
+        Some(suffix) => {
+            // Each file is edited separately, with its own line numbers.
+            for file in &files {
+                match sed.edit(file, &suffix) {
+                    Ok(quit) => {
+                        failed |= sed.input.failed;
+                        if quit {
+                            break;
+                        }
+                    },
+                    Err(e) => {
+                        err!("{}: {}", file.to_string_lossy(), e);
+                        failed = true;
+                    }
+                }
+            }
+        },
+        None => {
+            sed.input = Lines::new(files);
+            if let Err(e) = sed.run() {
+                err!("{}", e);
+                process::exit(status::FAILURE);
+            }
+            failed = sed.input.failed;
+        },
+    }
+    if failed {
+        process::exit(status::FAILURE);
+    }
+    status::SUCCESS
+}
+
+/// Parses a script, for the fuzz target of the same name. Scripts with a
+/// w in them are skipped, since w commands create their files as they are
+/// parsed.
+#[cfg(fuzzing)]
+pub fn fuzz(script: &[u8]) {
+    if script.contains(&b'w') {
+        return;
+    }
+    let mut parser = Parser {
+        script,
+        pos: 0,
+        wfiles: Vec::new(),
+        wnames: HashMap::new(),
+        labels: HashMap::new(),
+        branches: Vec::new(),
+    };
+    let _ = parser.parse();
+}
+
+#[test]
+fn test_parse() {
+    let parse = |script: &str| Parser {
+        script: script.as_bytes(),
+        pos: 0,
+        wfiles: Vec::new(),
+        wnames: HashMap::new(),
+        labels: HashMap::new(),
+        branches: Vec::new(),
+    }.parse();
+
+    let prog = parse("1,/x/!{s/\\(a\\)\\/b/[\\1&]\\n/2gp;b end\n}\n:end\n$d # comment").unwrap();
+    assert_eq!(prog.len(), 6);
+    assert!(matches!(prog[0], Command {
+        addr1: Some(Addr::Line(1)), addr2: Some(Addr::Re(Some(_))), negate: true, cmd: Cmd::Block(4),
+    }));
+    let Cmd::Subst(s) = &prog[1].cmd else { panic!("not a substitution") };
+    assert!(s.re.as_ref().unwrap().is_match(b"xa/b") && !s.re.as_ref().unwrap().is_match(b"ab"));
+    assert!(matches!(s.repl.as_slice(), [Repl::Literal(a), Repl::Group(1), Repl::Group(0), Repl::Literal(b)]
+                     if a == b"[" && b == b"]\n"));
+    assert!(s.global && s.print && s.nth == 2 && s.wfile.is_none());
+    assert!(matches!(prog[2].cmd, Cmd::Branch(4)));
+    assert!(matches!(prog[3].cmd, Cmd::EndBlock));
+    assert!(matches!(prog[4].cmd, Cmd::Label));
+    assert!(matches!(prog[5], Command { addr1: Some(Addr::Last), addr2: None, negate: false, cmd: Cmd::Delete }));
+
+    // An empty regular expression stands for the last one used, and a
+    // branch without a label goes to the end of the script.
+    let prog = parse("\\,x,s//y/I;t\na\\\nfirst\\\nsecond\ni third\ny/abc/x\\ny/").unwrap();
+    assert!(matches!(prog[0].addr1, Some(Addr::Re(Some(_)))));
+    let Cmd::Subst(s) = &prog[0].cmd else { panic!("not a substitution") };
+    assert!(s.re.is_none() && !s.global && s.nth == 1);
+    assert!(matches!(prog[1].cmd, Cmd::Test(5)));
+    assert!(matches!(&prog[2].cmd, Cmd::Append(text) if text == b"first\nsecond\n"));
+    assert!(matches!(&prog[3].cmd, Cmd::Insert(text) if text == b"third\n"));
+    let Cmd::Translate(table) = &prog[4].cmd else { panic!("not a transliteration") };
+    assert_eq!([table[b'a' as usize], table[b'b' as usize], table[b'c' as usize], table[b'd' as usize]],
+               [b'x', b'\n', b'y', b'd']);
+    let Cmd::Subst(s) = &parse("s/A/b/I3").unwrap()[0].cmd else { panic!("not a substitution") };
+    assert!(s.re.as_ref().unwrap().is_match(b"a") && s.nth == 3);
+
+    for (script, error) in [
+        ("k", "unknown command: 'k' at char 1"),
+        ("1,p", "expected address at char 2"),
+        ("{p", "unmatched { at char 2"),
+        ("}", "unexpected } at char 1"),
+        ("1}", "} doesn't want any addresses at char 2"),
+        ("b x", "can't find label for jump to 'x'"),
+        (":a\n:a", "duplicate label at char 5"),
+        ("1:a", ": doesn't want any addresses at char 2"),
+        ("1,2q", "command only uses one address at char 4"),
+        ("pq", "extra characters after command at char 1"),
+        ("s/a/\\1/", "invalid reference in replacement at char 7"),
+        ("s/a/b/0", "invalid substitution count at char 7"),
+        ("s/a/b", "unterminated expression at char 5"),
+        ("/[a/p", "unterminated bracket expression at char 5"),
+        ("y/ab/c/", "strings for y command are different lengths at char 7"),
+    ] {
+        assert_eq!(parse(script).err().as_deref(), Some(error), "script: {}", script);
+    }
+}
+
+#[test]
+fn test_execute() {
+    // Runs a script over the input, editing a temporary file in place to
+    // capture the output.
+    let sed = |script: &str, quiet: bool, input: &str| {
+        let mut tmp = crate::tempfile::TempFile::new("sed").unwrap();
+        tmp.write_all(input.as_bytes()).unwrap();
+        let mut parser = Parser {
+            script: script.as_bytes(),
+            pos: 0,
+            wfiles: Vec::new(),
+            wnames: HashMap::new(),
+            labels: HashMap::new(),
+            branches: Vec::new(),
+        };
+        let prog = parser.parse().unwrap();
+        let mut sed = Sed {
+            in_range: vec![false; prog.len()],
+            prog: prog.into(),
+            quiet,
+            input: Lines::new(Vec::new()),
+            out: Output::Stdout(BufWriter::new(io::stdout())),
+            missing_newline: false,
+            unterminated: false,
+            wfiles: parser.wfiles,
+            line: 0,
+            last_re: None,
+            hold: Vec::new(),
+            replaced: false,
+            append: Vec::new(),
+        };
+        sed.edit(tmp.path().as_os_str(), "").unwrap();
+        fs::read_to_string(tmp.path()).unwrap()
+    };
+    let abc = "a\nb\nc\n";
+
+    // Addresses and ranges.
+    assert_eq!(sed("2p", true, abc), "b\n");
+    assert_eq!(sed("$p", true, abc), "c\n");
+    assert_eq!(sed("2!d", false, abc), "b\n");
+    assert_eq!(sed("/b/,$d", false, abc), "a\n");
+    assert_eq!(sed("2,1p", true, abc), "b\n");
+    assert_eq!(sed("/a/,/b/!p", true, abc), "c\n");
+    assert_eq!(sed("/x/,/y/d", false, "x\n1\ny\n2\nx\n3\n"), "2\n");
+    assert_eq!(sed("/b/s//B/", false, abc), "a\nB\nc\n");
+    assert_eq!(sed("p", false, "a\nb"), "a\na\nb\nb");
+
+    // The flags of the s command, and its replacement text.
+    assert_eq!(sed("s/a/x/2", false, "aaa\n"), "axa\n");
+    assert_eq!(sed("s/a/x/2g", false, "aaa\n"), "axx\n");
+    assert_eq!(sed("s/a*/x/g", false, "baaac\n"), "xbxcx\n");
+    assert_eq!(sed("s/A/x/Ig", false, "aAb\n"), "xxb\n");
+    assert_eq!(sed("s/\\(b\\)\\(c\\)/\\2\\1[&]\\n/", false, "abcd\n"), "acb[bc]\nd\n");
+    assert_eq!(sed("s/b/B/p", true, abc), "B\n");
+    assert_eq!(sed(":a\ns/^.\\{1,3\\}$/ &/\nta", false, "ab\n"), "  ab\n");
+
+    // The hold space.
+    assert_eq!(sed("1!G;h;$!d", false, abc), "c\nb\na\n");
+    assert_eq!(sed("H;$!d;x;s/\\n/,/g", false, abc), ",a,b,c\n");
+    assert_eq!(sed("1h;3g", false, abc), "a\nb\na\n");
+    assert_eq!(sed("1h;2x;3G", false, abc), "a\na\nc\nb\n");
+
+    // Multiline pattern spaces.
+    assert_eq!(sed("$!N;/^\\(.*\\)\\n\\1$/!P;D", false, "a\na\nb\n"), "a\nb\n");
+    assert_eq!(sed("n;d", false, abc), "a\nc\n");
+
+    // Text, line numbers, transliteration and quitting.
+    assert_eq!(sed("1i before\n1a after\n2c changed\n$=", false, abc), "before\na\nafter\nchanged\n3\nc\n");
+    assert_eq!(sed("2,3c gone", false, abc), "a\ngone\n");
+    assert_eq!(sed("y/abc/ABC/", false, abc), "A\nB\nC\n");
+    assert_eq!(sed("2q", false, abc), "a\nb\n");
+}
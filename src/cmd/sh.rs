@@ -0,0 +1,2371 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// An implementation of the sh(1) command in Rust.
+// See http://man.cat-v.org/unix-7th/1/sh
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::io::{AsRawFd, FromRawFd, IntoRawFd};
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::path::PathBuf;
+use std::process;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::args;
+use crate::cmd::{Opt, Spec};
+use crate::err;
+use crate::glob;
+use crate::platform;
+use crate::signal;
+use crate::status;
+use crate::status::{NOT_EXECUTABLE, NOT_FOUND};
+use crate::tempfile::TempFile;
+
+/// The exit status after a syntax error.
+const SYNTAX_ERROR: i32 = 2;
+
+/// The search path used when PATH isn't set.
+const DEFAULT_PATH: &str = "/bin:/usr/bin";
+
+/// The characters that separate the fields produced by expansions when
+/// IFS isn't set.
+const DEFAULT_IFS: &str = " \t\n";
+
+/// The lowest file descriptor used to save one replaced by a redirection,
+/// out of the way of those that commands name.
+const SAVED_FD: libc::c_int = 10;
+
+/// The words that are reserved at the start of a command.
+const RESERVED: &[&str] = &[
+    "if", "then", "else", "elif", "fi", "case", "esac", "for", "while", "until", "do", "done", "{", "}",
+];
+
+/// The commands built into the shell.
+const BUILTINS: &[&str] = &[
+    ":", ".", "break", "cd", "continue", "eval", "exec", "exit", "export", "read", "readonly", "set",
+    "shift", "trap", "umask", "wait",
+];
+
+/// The signals caught for traps and not yet acted on, a bit for each.
+static CAUGHT: AtomicU64 = AtomicU64::new(0);
+
+/// The characters of the commands being read, a line at a time, as they
+/// are needed.
+struct Input {
+    reader: Box<dyn BufRead>,
+    line: Vec<char>,
+    pos: usize,
+    /// Whether to prompt for each line.
+    interactive: bool,
+    /// Whether the next line read is the start of a command, rather than
+    /// the continuation of one.
+    primary: bool,
+    /// The prompt for the start of a command.
+    prompt: String,
+    /// An error reading the input, which ends it.
+    error: Option<io::Error>,
+}
+
+impl Input {
+    fn new(reader: Box<dyn BufRead>, interactive: bool) -> Input {
+        Input {
+            reader,
+            line: Vec::new(),
+            pos: 0,
+            interactive,
+            primary: true,
+            prompt: String::new(),
+            error: None,
+        }
+    }
+
+    /// Reads the next line if the current one has been used up. At the end
+    /// of the input, the line is left empty.
+    fn fill(&mut self) {
+        if self.pos < self.line.len() {
+            return;
+        }
+        if self.interactive {
+            eprint!("{}", if self.primary { &self.prompt } else { "> " });
+        }
+        self.primary = false;
+        let mut buf = Vec::new();
+        if let Err(e) = self.reader.read_until(b'\n', &mut buf) {
+            self.error = Some(e);
+            buf.clear();
+        }
+        self.line = String::from_utf8_lossy(&buf).chars().collect();
+        self.pos = 0;
+    }
+
+    /// Returns the rest of the current line. Operators and reserved words
+    /// don't span lines, so this is as far ahead as the parser looks.
+    fn upcoming(&mut self) -> &[char] {
+        self.fill();
+        &self.line[self.pos..]
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.upcoming().first().copied()
+    }
+
+    /// Returns the character after the next one.
+    fn peek2(&mut self) -> Option<char> {
+        self.upcoming().get(1).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek();
+        if c.is_some() {
+            self.pos += 1;
+        }
+        c
+    }
+
+    /// Discards the rest of the current line, after an error.
+    fn discard_line(&mut self) {
+        self.pos = self.line.len();
+    }
+
+    /// Reads the line after the current one whole, as the body of a
+    /// here-document is read, or returns None at the end of the input.
+    fn read_line(&mut self) -> Option<String> {
+        if self.interactive {
+            eprint!("> ");
+        }
+        let mut buf = Vec::new();
+        match self.reader.read_until(b'\n', &mut buf) {
+            Ok(0) => None,
+            Ok(_) => Some(String::from_utf8_lossy(&buf).into_owned()),
+            Err(e) => {
+                self.error = Some(e);
+                None
+            }
+        }
+    }
+}
+
+/// A parameter substitution: $name or ${name}, possibly with an operator
+/// giving a word to use in place of it, or as well as it, depending on
+/// whether it is set.
+#[derive(Debug, PartialEq, Eq)]
+struct Param {
+    name: String,
+    /// The operator (-, =, ? or +), whether it was preceded by a colon,
+    /// which makes an empty value count as unset, and the word.
+    op: Option<(char, bool, Word)>,
+}
+
+/// A piece of a word, which is expanded according to how it was quoted.
+#[derive(Debug, PartialEq, Eq)]
+enum Piece {
+    Text(String),
+    Quoted(String),
+    /// A parameter, and whether it was within double quotes.
+    Param(Param, bool),
+    /// A command whose output is substituted, and whether it was within
+    /// double quotes.
+    Command(String, bool),
+}
+
+/// A word of a command: one or more pieces with nothing between them.
+#[derive(Debug, PartialEq, Eq)]
+struct Word(Vec<Piece>);
+
+impl Word {
+    /// Splits a word of the form name=value into the name and the value,
+    /// or gives it back if it isn't an assignment.
+    fn assignment(self) -> Result<(String, Word), Word> {
+        let (name, value) = match self.0.first() {
+            Some(Piece::Text(s)) => match s.split_once('=') {
+                Some((name, value)) if is_name(name) => (name.to_string(), value.to_string()),
+                _ => return Err(self),
+            },
+            _ => return Err(self),
+        };
+        let mut pieces = self.0;
+        if value.is_empty() {
+            pieces.remove(0);
+        } else {
+            pieces[0] = Piece::Text(value);
+        }
+        Ok((name, Word(pieces)))
+    }
+}
+
+/// Returns whether a string can be the name of a variable.
+fn is_name(s: &str) -> bool {
+    let mut chars = s.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// How a redirection changes a file descriptor.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Redirection {
+    /// <file: opens the file for reading.
+    Input,
+    /// >file: creates or truncates the file.
+    Output,
+    /// >>file: opens the file for appending, creating it if need be.
+    Append,
+    /// <&n or >&n: makes the descriptor a copy of n, or closes it if the
+    /// target is -.
+    Dup,
+    /// <<word or <<-word: opens the here-document that follows the
+    /// command for reading. The target is its text.
+    Here,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Redirect {
+    fd: libc::c_int,
+    kind: Redirection,
+    target: Word,
+}
+
+/// A command name, its arguments and its redirections, any of which may
+/// be missing.
+#[derive(Debug, PartialEq, Eq)]
+struct Simple {
+    /// The variables assigned for the command, or in the shell if there
+    /// is no command.
+    assignments: Vec<(String, Word)>,
+    words: Vec<Word>,
+    redirects: Vec<Redirect>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Compound {
+    /// { list }
+    Group(List),
+    /// ( list ), which runs in a subshell.
+    Subshell(List),
+    /// if list then list [elif list then list]... [else list] fi
+    If { branches: Vec<(List, List)>, otherwise: Option<List> },
+    /// while list do list done, or until, which stops when the condition
+    /// succeeds rather than fails.
+    While { condition: List, body: List, until: bool },
+    /// for name [in word...] do list done, which without the words runs
+    /// over the positional parameters.
+    For { name: String, words: Option<Vec<Word>>, body: List },
+    /// case word in [pattern [| pattern]...) list ;;]... esac
+    Case { word: Word, arms: Vec<(Vec<Word>, List)> },
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Command {
+    Simple(Simple),
+    /// A compound command and the redirections that apply to the whole
+    /// of it.
+    Compound(Compound, Vec<Redirect>),
+}
+
+/// Commands whose standard outputs are joined to the standard inputs of
+/// the commands after them.
+type Pipeline = Vec<Command>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Connector {
+    /// &&: runs the next pipeline if the last succeeded.
+    And,
+    /// ||: runs the next pipeline if the last failed.
+    Or,
+}
+
+/// Pipelines joined by && and ||.
+#[derive(Debug, PartialEq, Eq)]
+struct AndOr {
+    first: Pipeline,
+    rest: Vec<(Connector, Pipeline)>,
+}
+
+/// The lists in a line, or in the body of a compound command, separated
+/// by ;, & or newlines, each paired with whether it is to run in the
+/// background.
+type List = Vec<(AndOr, bool)>;
+
+/// Returns whether a character ends an unquoted word.
+fn is_break(c: char) -> bool {
+    matches!(c, ' ' | '\t' | '\n' | '|' | '&' | ';' | '<' | '>' | '(' | ')')
+}
+
+struct Parser<'a> {
+    input: &'a mut Input,
+}
+
+impl<'a> Parser<'a> {
+    /// Skips blanks and any comment after them, which runs to the end of
+    /// the line.
+    fn skip_blanks(&mut self) {
+        while matches!(self.input.peek(), Some(' ' | '\t')) {
+            self.input.next();
+        }
+        if self.input.peek() == Some('#') {
+            while self.input.peek().is_some_and(|c| c != '\n') {
+                self.input.next();
+            }
+        }
+    }
+
+    /// Skips blank lines, which may follow an operator that needs
+    /// something after it.
+    fn skip_newlines(&mut self) {
+        self.skip_blanks();
+        while self.input.peek() == Some('\n') {
+            self.input.next();
+            self.skip_blanks();
+        }
+    }
+
+    /// Returns whether the input continues with the word, followed by a
+    /// blank or an operator.
+    fn at_word(&mut self, word: &str) -> bool {
+        let rest = self.input.upcoming();
+        let len = word.chars().count();
+        rest.len() >= len && rest[..len].iter().copied().eq(word.chars())
+            && rest.get(len).is_none_or(|&c| is_break(c))
+    }
+
+    /// Returns the reserved word that the input continues with, if any.
+    fn reserved(&mut self) -> Option<&'static str> {
+        RESERVED.iter().copied().find(|word| self.at_word(word))
+    }
+
+    /// Consumes a word that the grammar requires.
+    fn keyword(&mut self, word: &str) -> Result<(), String> {
+        self.skip_blanks();
+        if !self.at_word(word) {
+            return Err(self.unexpected());
+        }
+        for _ in word.chars() {
+            self.input.next();
+        }
+        Ok(())
+    }
+
+    /// Returns the error for an unexpected word or operator, or the end of
+    /// the input.
+    fn unexpected(&mut self) -> String {
+        let rest = self.input.upcoming();
+        let what = match rest.first() {
+            None => "end of file".to_string(),
+            Some('\n') => "newline".to_string(),
+            Some(';') if rest.get(1) == Some(&';') => ";;".to_string(),
+            Some(&c) if is_break(c) => c.to_string(),
+            Some(_) => rest.iter().take_while(|&&c| !is_break(c)).collect(),
+        };
+        format!("unexpected {}", what)
+    }
+
+    /// Parses what follows a $: a parameter, or None if the $ stands for
+    /// itself.
+    fn dollar(&mut self, quoted: bool) -> Result<Option<Piece>, String> {
+        let name = match self.input.peek() {
+            Some('{') => {
+                self.input.next();
+                let mut name = String::new();
+                while let Some(c) = self.input.peek().filter(|&c| c.is_ascii_alphanumeric() || c == '_') {
+                    name.push(c);
+                    self.input.next();
+                }
+                if name.is_empty() {
+                    match self.input.next() {
+                        Some(c @ ('#' | '?' | '$' | '!' | '*' | '@')) => name.push(c),
+                        _ => return Err("bad substitution".to_string()),
+                    }
+                }
+                let colon = self.input.peek() == Some(':');
+                if colon {
+                    self.input.next();
+                }
+                let op = match self.input.next() {
+                    Some('}') if !colon => None,
+                    Some(op @ ('-' | '=' | '?' | '+')) => {
+                        let word = Word(self.pieces(true)?);
+                        self.input.next();
+                        Some((op, colon, word))
+                    },
+                    _ => return Err("bad substitution".to_string()),
+                };
+                return Ok(Some(Piece::Param(Param { name, op }, quoted)));
+            },
+            Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+                let mut name = String::new();
+                while let Some(c) = self.input.peek().filter(|&c| c.is_ascii_alphanumeric() || c == '_') {
+                    name.push(c);
+                    self.input.next();
+                }
+                name
+            },
+            Some(c @ ('0'..='9' | '#' | '?' | '$' | '!' | '*' | '@')) => {
+                self.input.next();
+                c.to_string()
+            },
+            _ => return Ok(None),
+        };
+        Ok(Some(Piece::Param(Param { name, op: None }, quoted)))
+    }
+
+    /// Parses the command between backquotes, after the opening one. A
+    /// backslash only escapes a backquote, a backslash or a $, or, within
+    /// double quotes, a double quote.
+    fn backquote(&mut self, quoted: bool) -> Result<String, String> {
+        let mut command = String::new();
+        loop {
+            match self.input.next() {
+                Some('`') => return Ok(command),
+                Some('\\') => match self.input.next() {
+                    Some(c @ ('`' | '\\' | '$')) => command.push(c),
+                    Some('"') if quoted => command.push('"'),
+                    Some(c) => {
+                        command.push('\\');
+                        command.push(c);
+                    },
+                    None => return Err("unterminated command substitution".to_string()),
+                },
+                Some(c) => command.push(c),
+                None => return Err("unterminated command substitution".to_string()),
+            }
+        }
+    }
+
+    /// Parses the pieces of a word. Within the braces of a parameter
+    /// substitution, the word ends at the closing brace; otherwise, it
+    /// ends at a blank or an operator.
+    fn pieces(&mut self, braced: bool) -> Result<Vec<Piece>, String> {
+        let mut pieces = Vec::new();
+        let mut text = String::new();
+        let flush = |text: &mut String, pieces: &mut Vec<Piece>| {
+            if !text.is_empty() {
+                pieces.push(Piece::Text(std::mem::take(text)));
+            }
+        };
+        loop {
+            let c = match self.input.peek() {
+                Some('}') if braced => break,
+                Some(c) if braced || !is_break(c) => c,
+                None if braced => return Err("bad substitution".to_string()),
+                _ => break,
+            };
+            self.input.next();
+            match c {
+                '\'' => {
+                    flush(&mut text, &mut pieces);
+                    let mut quoted = String::new();
+                    loop {
+                        match self.input.next() {
+                            Some('\'') => break,
+                            Some(c) => quoted.push(c),
+                            None => return Err("unterminated quoted string".to_string()),
+                        }
+                    }
+                    pieces.push(Piece::Quoted(quoted));
+                },
+                '"' => {
+                    flush(&mut text, &mut pieces);
+                    let mut quoted = String::new();
+                    loop {
+                        match self.input.next() {
+                            Some('"') => break,
+                            // Within double quotes, a backslash only
+                            // escapes the characters that are special
+                            // there.
+                            Some('\\') => match self.input.next() {
+                                Some('\n') => (),
+                                Some(c @ ('$' | '`' | '"' | '\\')) => quoted.push(c),
+                                Some(c) => {
+                                    quoted.push('\\');
+                                    quoted.push(c);
+                                },
+                                None => return Err("unterminated quoted string".to_string()),
+                            },
+                            Some('$') => match self.dollar(true)? {
+                                Some(piece) => {
+                                    flush_quoted(&mut quoted, &mut pieces);
+                                    pieces.push(piece);
+                                },
+                                None => quoted.push('$'),
+                            },
+                            Some('`') => {
+                                flush_quoted(&mut quoted, &mut pieces);
+                                pieces.push(Piece::Command(self.backquote(true)?, true));
+                            },
+                            Some(c) => quoted.push(c),
+                            None => return Err("unterminated quoted string".to_string()),
+                        }
+                    }
+                    // Quotes with nothing between them still make a word.
+                    if quoted.is_empty() && !matches!(pieces.last(), Some(Piece::Param(_, true) | Piece::Command(_, true))) {
+                        pieces.push(Piece::Quoted(quoted));
+                    } else {
+                        flush_quoted(&mut quoted, &mut pieces);
+                    }
+                },
+                '`' => {
+                    flush(&mut text, &mut pieces);
+                    pieces.push(Piece::Command(self.backquote(false)?, false));
+                },
+                '$' => match self.dollar(false)? {
+                    Some(piece) => {
+                        flush(&mut text, &mut pieces);
+                        pieces.push(piece);
+                    },
+                    None => text.push('$'),
+                },
+                '\\' => match self.input.next() {
+                    // An escaped newline joins the lines.
+                    Some('\n') => (),
+                    Some(c) => {
+                        flush(&mut text, &mut pieces);
+                        pieces.push(Piece::Quoted(c.to_string()));
+                    },
+                    None => text.push('\\'),
+                },
+                _ => text.push(c),
+            }
+        }
+        flush(&mut text, &mut pieces);
+        Ok(pieces)
+    }
+
+    /// Parses a word, or returns None if the next character can't start
+    /// one.
+    fn word(&mut self) -> Result<Option<Word>, String> {
+        let pieces = self.pieces(false)?;
+        Ok(if pieces.is_empty() { None } else { Some(Word(pieces)) })
+    }
+
+    /// Parses a redirection, if the input continues with one: an optional
+    /// file descriptor, then < or >.
+    fn redirection(&mut self) -> Result<Option<Redirect>, String> {
+        let rest = self.input.upcoming();
+        let digits = rest.iter().take_while(|c| c.is_ascii_digit()).count();
+        if !matches!(rest.get(digits), Some('<' | '>')) {
+            return Ok(None);
+        }
+        let fd = match digits {
+            0 => None,
+            _ => match rest[..digits].iter().collect::<String>().parse() {
+                Ok(fd) => Some(fd),
+                Err(_) => return Err("bad file descriptor".to_string()),
+            },
+        };
+        for _ in 0..digits {
+            self.input.next();
+        }
+        self.redirect(fd).map(Some)
+    }
+
+    /// Parses a redirection, at its < or >, given the descriptor written
+    /// before it, if any.
+    fn redirect(&mut self, fd: Option<libc::c_int>) -> Result<Redirect, String> {
+        let c = self.input.next();
+        let kind = match (c, self.input.peek()) {
+            (Some('<'), Some('<')) => Redirection::Here,
+            (Some('>'), Some('>')) => Redirection::Append,
+            (_, Some('&')) => Redirection::Dup,
+            (Some('<'), _) => Redirection::Input,
+            _ => Redirection::Output,
+        };
+        if kind != Redirection::Input && kind != Redirection::Output {
+            self.input.next();
+        }
+        // With <<-, the tabs that start the lines of a here-document are
+        // removed, so that it can be indented.
+        let strip = kind == Redirection::Here && self.input.peek() == Some('-');
+        if strip {
+            self.input.next();
+        }
+        let fd = fd.unwrap_or(if c == Some('<') { 0 } else { 1 });
+        self.skip_blanks();
+        let target = match self.input.peek() {
+            Some(c) if !is_break(c) => match self.word()? {
+                Some(target) => target,
+                None => return Err("missing file name".to_string()),
+            },
+            _ => return Err(self.unexpected()),
+        };
+        if kind == Redirection::Here {
+            return self.here_document(fd, target, strip);
+        }
+        Ok(Redirect { fd, kind, target })
+    }
+
+    /// Reads the body of a here-document, from the lines after the one
+    /// being parsed up to a line that is only the delimiter. Unless any of
+    /// the delimiter was quoted, parameters and commands in the body are
+    /// substituted, as within double quotes.
+    fn here_document(&mut self, fd: libc::c_int, delimiter: Word, strip: bool) -> Result<Redirect, String> {
+        let mut end = String::new();
+        let mut quoted = false;
+        for piece in delimiter.0 {
+            match piece {
+                Piece::Text(s) => end += &s,
+                Piece::Quoted(s) => {
+                    end += &s;
+                    quoted = true;
+                },
+                _ => return Err("bad here-document delimiter".to_string()),
+            }
+        }
+        let mut body = String::new();
+        while let Some(line) = self.input.read_line() {
+            let line = line.strip_suffix('\n').unwrap_or(&line);
+            let line = if strip { line.trim_start_matches('\t') } else { line };
+            if line == end {
+                break;
+            }
+            body += line;
+            body.push('\n');
+        }
+        let target = if quoted {
+            Word(vec![Piece::Quoted(body)])
+        } else {
+            let mut input = Input::new(Box::new(io::Cursor::new(body.into_bytes())), false);
+            Word(Parser { input: &mut input }.here_pieces()?)
+        };
+        Ok(Redirect { fd, kind: Redirection::Here, target })
+    }
+
+    /// Parses the body of a here-document whose delimiter wasn't quoted.
+    /// A backslash only escapes a $, a backquote, a backslash or a
+    /// newline, and double quotes stand for themselves.
+    fn here_pieces(&mut self) -> Result<Vec<Piece>, String> {
+        let mut pieces = Vec::new();
+        let mut quoted = String::new();
+        while let Some(c) = self.input.next() {
+            match c {
+                '\\' => match self.input.next() {
+                    Some('\n') => (),
+                    Some(c @ ('$' | '`' | '\\')) => quoted.push(c),
+                    Some(c) => {
+                        quoted.push('\\');
+                        quoted.push(c);
+                    },
+                    None => quoted.push('\\'),
+                },
+                '$' => match self.dollar(true)? {
+                    Some(piece) => {
+                        flush_quoted(&mut quoted, &mut pieces);
+                        pieces.push(piece);
+                    },
+                    None => quoted.push('$'),
+                },
+                '`' => {
+                    flush_quoted(&mut quoted, &mut pieces);
+                    pieces.push(Piece::Command(self.backquote(true)?, true));
+                },
+                c => quoted.push(c),
+            }
+        }
+        flush_quoted(&mut quoted, &mut pieces);
+        Ok(pieces)
+    }
+
+    /// Parses a simple command, which must have at least an assignment, a
+    /// word or a redirection.
+    fn simple(&mut self) -> Result<Simple, String> {
+        let mut assignments = Vec::new();
+        let mut words = Vec::new();
+        let mut redirects = Vec::new();
+        loop {
+            self.skip_blanks();
+            if let Some(redirect) = self.redirection()? {
+                redirects.push(redirect);
+                continue;
+            }
+            match self.input.peek() {
+                Some(c) if !is_break(c) => {
+                    if let Some(word) = self.word()? {
+                        if !words.is_empty() {
+                            words.push(word);
+                            continue;
+                        }
+                        match word.assignment() {
+                            Ok(assignment) => assignments.push(assignment),
+                            Err(word) => words.push(word),
+                        }
+                    }
+                },
+                _ => break,
+            }
+        }
+        if assignments.is_empty() && words.is_empty() && redirects.is_empty() {
+            return Err(self.unexpected());
+        }
+        Ok(Simple { assignments, words, redirects })
+    }
+
+    /// Parses a list that must have at least one command in it.
+    fn body(&mut self) -> Result<List, String> {
+        let list = self.list()?;
+        if list.is_empty() {
+            return Err(self.unexpected());
+        }
+        Ok(list)
+    }
+
+    fn if_command(&mut self) -> Result<Compound, String> {
+        self.keyword("if")?;
+        let mut branches = Vec::new();
+        loop {
+            let condition = self.body()?;
+            self.keyword("then")?;
+            branches.push((condition, self.body()?));
+            match self.reserved() {
+                Some("elif") => self.keyword("elif")?,
+                Some("else") => {
+                    self.keyword("else")?;
+                    let otherwise = self.body()?;
+                    self.keyword("fi")?;
+                    return Ok(Compound::If { branches, otherwise: Some(otherwise) });
+                },
+                _ => {
+                    self.keyword("fi")?;
+                    return Ok(Compound::If { branches, otherwise: None });
+                },
+            }
+        }
+    }
+
+    fn while_command(&mut self) -> Result<Compound, String> {
+        let until = self.reserved() == Some("until");
+        self.keyword(if until { "until" } else { "while" })?;
+        let condition = self.body()?;
+        self.keyword("do")?;
+        let body = self.body()?;
+        self.keyword("done")?;
+        Ok(Compound::While { condition, body, until })
+    }
+
+    fn for_command(&mut self) -> Result<Compound, String> {
+        self.keyword("for")?;
+        self.skip_blanks();
+        let name = match self.word()? {
+            Some(Word(pieces)) => match &pieces[..] {
+                [Piece::Text(name)] if is_name(name) => name.clone(),
+                _ => return Err("bad for loop variable".to_string()),
+            },
+            None => return Err(self.unexpected()),
+        };
+        self.skip_blanks();
+        if self.input.peek() == Some(';') {
+            self.input.next();
+        }
+        self.skip_newlines();
+        let mut words = None;
+        if self.at_word("in") {
+            self.keyword("in")?;
+            let mut list = Vec::new();
+            loop {
+                self.skip_blanks();
+                match self.input.peek() {
+                    Some(c) if !is_break(c) => list.extend(self.word()?),
+                    Some(';' | '\n') => {
+                        self.input.next();
+                        break;
+                    },
+                    _ => return Err(self.unexpected()),
+                }
+            }
+            words = Some(list);
+            self.skip_newlines();
+        }
+        self.keyword("do")?;
+        let body = self.body()?;
+        self.keyword("done")?;
+        Ok(Compound::For { name, words, body })
+    }
+
+    fn case_command(&mut self) -> Result<Compound, String> {
+        self.keyword("case")?;
+        self.skip_blanks();
+        let word = match self.word()? {
+            Some(word) => word,
+            None => return Err(self.unexpected()),
+        };
+        self.skip_newlines();
+        self.keyword("in")?;
+        let mut arms = Vec::new();
+        loop {
+            self.skip_newlines();
+            if self.reserved() == Some("esac") {
+                break;
+            }
+            if self.input.peek() == Some('(') {
+                self.input.next();
+            }
+            let mut patterns = Vec::new();
+            loop {
+                self.skip_blanks();
+                match self.input.peek() {
+                    Some(c) if !is_break(c) => patterns.extend(self.word()?),
+                    _ => return Err(self.unexpected()),
+                }
+                self.skip_blanks();
+                match self.input.next() {
+                    Some('|') => (),
+                    Some(')') => break,
+                    _ => return Err(self.unexpected()),
+                }
+            }
+            arms.push((patterns, self.list()?));
+            if self.input.peek() == Some(';') && self.input.peek2() == Some(';') {
+                self.input.next();
+                self.input.next();
+            } else {
+                break;
+            }
+        }
+        self.keyword("esac")?;
+        Ok(Compound::Case { word, arms })
+    }
+
+    /// Parses a command: a simple command, or a compound command and its
+    /// redirections.
+    fn command(&mut self) -> Result<Command, String> {
+        self.skip_blanks();
+        let compound = match self.reserved() {
+            Some("if") => self.if_command()?,
+            Some("while" | "until") => self.while_command()?,
+            Some("for") => self.for_command()?,
+            Some("case") => self.case_command()?,
+            Some("{") => {
+                self.keyword("{")?;
+                let list = self.body()?;
+                self.keyword("}")?;
+                Compound::Group(list)
+            },
+            Some(_) => return Err(self.unexpected()),
+            None if self.input.peek() == Some('(') => {
+                self.input.next();
+                let list = self.body()?;
+                if self.input.peek() != Some(')') {
+                    return Err(self.unexpected());
+                }
+                self.input.next();
+                Compound::Subshell(list)
+            },
+            None => return Ok(Command::Simple(self.simple()?)),
+        };
+        let mut redirects = Vec::new();
+        loop {
+            self.skip_blanks();
+            match self.redirection()? {
+                Some(redirect) => redirects.push(redirect),
+                None => break,
+            }
+        }
+        Ok(Command::Compound(compound, redirects))
+    }
+
+    fn pipeline(&mut self) -> Result<Pipeline, String> {
+        let mut pipeline = vec![self.command()?];
+        self.skip_blanks();
+        while self.input.peek() == Some('|') && self.input.peek2() != Some('|') {
+            self.input.next();
+            self.skip_newlines();
+            pipeline.push(self.command()?);
+            self.skip_blanks();
+        }
+        Ok(pipeline)
+    }
+
+    fn and_or(&mut self) -> Result<AndOr, String> {
+        let first = self.pipeline()?;
+        let mut rest = Vec::new();
+        loop {
+            let connector = match (self.input.peek(), self.input.peek2()) {
+                (Some('&'), Some('&')) => Connector::And,
+                (Some('|'), Some('|')) => Connector::Or,
+                _ => break,
+            };
+            self.input.next();
+            self.input.next();
+            self.skip_newlines();
+            rest.push((connector, self.pipeline()?));
+        }
+        Ok(AndOr { first, rest })
+    }
+
+    /// Parses the list in the body of a compound command, which ends
+    /// before a reserved word, a ) or a ;; that can't start a command.
+    fn list(&mut self) -> Result<List, String> {
+        let mut list = Vec::new();
+        loop {
+            self.skip_newlines();
+            let end = match self.input.peek() {
+                None | Some(')') => true,
+                Some(';') => self.input.peek2() == Some(';'),
+                _ => matches!(self.reserved(), Some("then" | "else" | "elif" | "fi" | "do" | "done" | "esac" | "}")),
+            };
+            if end {
+                break;
+            }
+            let and_or = self.and_or()?;
+            let background = match (self.input.peek(), self.input.peek2()) {
+                (Some('&'), _) => true,
+                (Some(';'), Some(c)) if c != ';' => false,
+                (Some(';'), None) | (Some('\n'), _) => false,
+                _ => {
+                    list.push((and_or, false));
+                    break;
+                },
+            };
+            self.input.next();
+            list.push((and_or, background));
+        }
+        Ok(list)
+    }
+
+    /// Parses the next line of commands, which ends at a newline, unless
+    /// a compound command carries it on over several lines. Returns None
+    /// at the end of the input.
+    fn line(&mut self) -> Result<Option<List>, String> {
+        let mut list = Vec::new();
+        self.skip_blanks();
+        while !matches!(self.input.peek(), None | Some('\n')) {
+            let and_or = self.and_or()?;
+            let background = match self.input.peek() {
+                Some('&') => true,
+                Some(';') => false,
+                _ => {
+                    list.push((and_or, false));
+                    break;
+                }
+            };
+            self.input.next();
+            list.push((and_or, background));
+            self.skip_blanks();
+        }
+        match self.input.peek() {
+            None if list.is_empty() => Ok(None),
+            None => Ok(Some(list)),
+            Some('\n') => {
+                self.input.next();
+                Ok(Some(list))
+            },
+            Some(_) => Err(self.unexpected()),
+        }
+    }
+}
+
+/// Adds any text within double quotes to the pieces of a word.
+fn flush_quoted(quoted: &mut String, pieces: &mut Vec<Piece>) {
+    if !quoted.is_empty() {
+        pieces.push(Piece::Quoted(std::mem::take(quoted)));
+    }
+}
+
+/// Returns whether the path names an executable regular file.
+fn is_executable(path: &PathBuf) -> bool {
+    fs::metadata(path).is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+}
+
+/// Finds a file for which the test holds in the directories of a search
+/// path. An empty directory in the path stands for the current directory.
+fn search(name: &str, path: &str, test: fn(&PathBuf) -> bool) -> Option<PathBuf> {
+    if name.contains(platform::is_separator) {
+        return Some(PathBuf::from(name));
+    }
+    path.split(platform::PATH_LIST_SEPARATOR)
+        .map(|dir| PathBuf::from(if dir.is_empty() { "." } else { dir }).join(name))
+        .find(test)
+}
+
+/// Returns the exit status that a shell reports for a process: its exit
+/// code or, if it was killed by a signal, 128 plus the signal number.
+fn exit_status(status: process::ExitStatus) -> i32 {
+    match (status.code(), status.signal()) {
+        (Some(code), _) => code,
+        (None, Some(sig)) => 128 + sig,
+        (None, None) => 1,
+    }
+}
+
+/// Waits for a child process to finish, returning its exit status.
+fn wait(pid: libc::pid_t) -> i32 {
+    let mut status = 0;
+    loop {
+        // SAFETY: status is a valid place for waitpid to store the status.
+        if unsafe { libc::waitpid(pid, &mut status, 0) } == pid {
+            return exit_status(process::ExitStatus::from_raw(status));
+        }
+        if io::Error::last_os_error().kind() != io::ErrorKind::Interrupted {
+            return NOT_FOUND;
+        }
+    }
+}
+
+/// The signals that an interactive shell ignores, so that typing the
+/// interrupt or quit character stops the command it is running but not
+/// the shell itself.
+const SHELL_SIGNALS: [libc::c_int; 3] = [libc::SIGINT, libc::SIGQUIT, libc::SIGTERM];
+
+/// A field being built by the expansion of a word: its text and, for file
+/// name generation, the same text as a pattern, in which the characters
+/// that were quoted are escaped.
+#[derive(Default)]
+struct Field {
+    text: String,
+    pattern: String,
+    /// Whether the pattern has unquoted special characters.
+    glob: bool,
+}
+
+impl Field {
+    fn push(&mut self, c: char, quoted: bool) {
+        self.text.push(c);
+        if quoted && matches!(c, '*' | '?' | '[' | '\\') {
+            self.pattern.push('\\');
+        } else if !quoted && matches!(c, '*' | '?' | '[') {
+            self.glob = true;
+        }
+        self.pattern.push(c);
+    }
+}
+
+/// A break or continue on its way out of the given number of loops.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Jump {
+    Break(usize),
+    Continue(usize),
+    /// An interrupt from the terminal, which abandons the whole of the
+    /// command line in an interactive shell.
+    Interrupt,
+}
+
+struct Shell {
+    interactive: bool,
+    /// The exit status of the last command.
+    status: i32,
+    /// The process ids of the background jobs not yet waited for.
+    jobs: Vec<libc::pid_t>,
+    /// The process id of the last background job, $!.
+    last_job: Option<libc::pid_t>,
+    /// The process id of the shell, $$, which subshells share.
+    pid: libc::pid_t,
+    /// The shell's variables, of which those exported are also kept in
+    /// its environment, to be passed on to commands.
+    vars: HashMap<String, String>,
+    exported: HashSet<String>,
+    readonly: HashSet<String>,
+    /// The name of the shell or script, $0.
+    name: String,
+    /// The positional parameters, $1 onwards.
+    args: Vec<String>,
+    /// The number of loops being executed.
+    loops: usize,
+    jump: Option<Jump>,
+    /// The exit status of the last command substitution in the command
+    /// being expanded, which a command with no name returns.
+    substituted: Option<i32>,
+    /// The commands to run on signals, by number, or on exit, as 0. An
+    /// empty command means the signal is ignored.
+    traps: BTreeMap<libc::c_int, String>,
+}
+
+impl Shell {
+    fn new(prog: String, interactive: bool) -> Shell {
+        let mut vars: HashMap<String, String> = env::vars().collect();
+        let exported = vars.keys().cloned().collect();
+        // A value of IFS from the environment could make the shell split
+        // a script's words in ways its author didn't intend.
+        vars.insert("IFS".to_string(), DEFAULT_IFS.to_string());
+        vars.entry("PATH".to_string()).or_insert_with(|| DEFAULT_PATH.to_string());
+        // SAFETY: geteuid has no preconditions.
+        let ps1 = if unsafe { libc::geteuid() } == 0 { "# " } else { "$ " };
+        vars.entry("PS1".to_string()).or_insert_with(|| ps1.to_string());
+        Shell {
+            name: prog,
+            interactive,
+            status: 0,
+            jobs: Vec::new(),
+            last_job: None,
+            pid: process::id() as libc::pid_t,
+            vars,
+            exported,
+            readonly: HashSet::new(),
+            args: Vec::new(),
+            loops: 0,
+            jump: None,
+            substituted: None,
+            traps: BTreeMap::new(),
+        }
+    }
+
+    /// Reports an error running a command, returning the exit status
+    /// for it.
+    fn command_error(&self, name: &str, e: &io::Error) -> i32 {
+        match e.kind() {
+            io::ErrorKind::NotFound => {
+                err!("{}: not found", name);
+                NOT_FOUND
+            },
+            _ => {
+                err!("{}: {}", name, e);
+                NOT_EXECUTABLE
+            }
+        }
+    }
+
+    /// Sets a variable, unless it is read-only. Returns whether it was
+    /// set.
+    fn assign(&mut self, name: &str, value: String) -> bool {
+        if self.readonly.contains(name) {
+            err!("{}: is read only", name);
+            return false;
+        }
+        if self.exported.contains(name) {
+            env::set_var(name, &value);
+        }
+        self.vars.insert(name.to_string(), value);
+        true
+    }
+
+    fn var(&self, name: &str) -> Option<&str> {
+        self.vars.get(name).map(String::as_str)
+    }
+
+    /// Returns the values of a parameter. This is one value for all but
+    /// $@ and $*, which have one for each positional parameter.
+    fn param(&mut self, param: &Param) -> Vec<String> {
+        let name = param.name.as_str();
+        let value = match name {
+            "@" | "*" if self.args.is_empty() => None,
+            "@" | "*" => Some(self.args.clone()),
+            "#" => Some(vec![self.args.len().to_string()]),
+            "?" => Some(vec![self.status.to_string()]),
+            "$" => Some(vec![self.pid.to_string()]),
+            "!" => self.last_job.map(|pid| vec![pid.to_string()]),
+            "0" => Some(vec![self.name.clone()]),
+            _ if name.starts_with(|c: char| c.is_ascii_digit()) => name.parse::<usize>().ok()
+                .and_then(|n| self.args.get(n.wrapping_sub(1)).cloned()).map(|arg| vec![arg]),
+            _ => self.vars.get(name).cloned().map(|value| vec![value]),
+        };
+        let unset = || if matches!(name, "@" | "*") { Vec::new() } else { vec![String::new()] };
+        let (op, colon, word) = match &param.op {
+            Some((op, colon, word)) => (*op, *colon, word),
+            None => return value.unwrap_or_else(unset),
+        };
+        let value = value.filter(|value| !colon || value.iter().any(|s| !s.is_empty()));
+        match (op, value) {
+            ('+', Some(_)) => vec![self.string(word)],
+            ('+', None) => unset(),
+            (_, Some(value)) => value,
+            ('-', None) => vec![self.string(word)],
+            ('=', None) => {
+                let value = self.string(word);
+                if !is_name(name) {
+                    err!("{}: cannot assign", name);
+                } else {
+                    self.assign(name, value.clone());
+                }
+                vec![value]
+            },
+            (_, None) => {
+                let msg = self.string(word);
+                let msg = if msg.is_empty() { "parameter null or not set" } else { &msg };
+                err!("{}: {}", name, msg);
+                if !self.interactive {
+                    process::exit(1);
+                }
+                unset()
+            },
+        }
+    }
+
+    /// Runs a command, returning what it writes to its standard output,
+    /// less any trailing newlines.
+    fn substitute(&mut self, command: &str) -> String {
+        let mut fds = [-1; 2];
+        // SAFETY: fds has room for the two descriptors.
+        if unsafe { libc::pipe(fds.as_mut_ptr()) } == -1 {
+            err!("pipe: {}", io::Error::last_os_error());
+            self.substituted = Some(1);
+            return String::new();
+        }
+        let result = self.fork(false, |shell| {
+            // SAFETY: the descriptors are the ends of the pipe.
+            unsafe {
+                libc::close(fds[0]);
+                libc::dup2(fds[1], 1);
+                libc::close(fds[1]);
+            }
+            let mut input = Input::new(Box::new(io::Cursor::new(command.as_bytes().to_vec())), false);
+            shell.run(&mut input)
+        });
+        // SAFETY: the write end of the pipe is the child's now, and the
+        // File takes over the read end.
+        let mut pipe = unsafe {
+            libc::close(fds[1]);
+            File::from_raw_fd(fds[0])
+        };
+        let mut output = Vec::new();
+        let _ = pipe.read_to_end(&mut output);
+        drop(pipe);
+        self.substituted = Some(match result {
+            Ok(pid) => wait(pid),
+            Err(e) => {
+                err!("fork: {}", e);
+                1
+            }
+        });
+        let mut output = String::from_utf8_lossy(&output).into_owned();
+        while output.ends_with('\n') {
+            output.pop();
+        }
+        output
+    }
+
+    /// Expands a word into fields. If split is true, the results of
+    /// unquoted substitutions are split into fields at the characters of
+    /// IFS; otherwise, there is at most one field.
+    fn expand(&mut self, word: &Word, split: bool) -> Vec<Field> {
+        let mut fields = Vec::new();
+        let mut field: Option<Field> = None;
+        for piece in &word.0 {
+            let (values, quoted) = match piece {
+                Piece::Text(s) | Piece::Quoted(s) => {
+                    let quoted = matches!(piece, Piece::Quoted(_));
+                    let field = field.get_or_insert_with(Field::default);
+                    for c in s.chars() {
+                        field.push(c, quoted);
+                    }
+                    continue;
+                },
+                Piece::Param(param, quoted) => (self.param(param), *quoted),
+                Piece::Command(command, quoted) => (vec![self.substitute(command)], *quoted),
+            };
+            let values = if split { values } else { vec![values.join(" ")] };
+            let ifs = self.var("IFS").unwrap_or(DEFAULT_IFS).to_string();
+            for (i, value) in values.iter().enumerate() {
+                // Each of the values of "$@" is a field of its own.
+                if i > 0 {
+                    fields.extend(field.take());
+                }
+                if quoted || !split {
+                    let field = field.get_or_insert_with(Field::default);
+                    for c in value.chars() {
+                        field.push(c, quoted);
+                    }
+                    continue;
+                }
+                for c in value.chars() {
+                    if ifs.contains(c) {
+                        fields.extend(field.take());
+                    } else {
+                        field.get_or_insert_with(Field::default).push(c, false);
+                    }
+                }
+            }
+        }
+        fields.extend(field);
+        fields
+    }
+
+    /// Expands words into the arguments of a command, generating file
+    /// names from those fields that are patterns that match any.
+    fn fields(&mut self, words: &[Word]) -> Vec<String> {
+        let mut args = Vec::new();
+        for word in words {
+            for field in self.expand(word, true) {
+                if field.glob {
+                    let names = glob::expand(field.pattern.as_bytes());
+                    if !names.is_empty() {
+                        args.extend(names.iter().map(|name| String::from_utf8_lossy(name).into_owned()));
+                        continue;
+                    }
+                }
+                args.push(field.text);
+            }
+        }
+        args
+    }
+
+    /// Expands a word into a single string, without splitting it or
+    /// generating file names.
+    fn string(&mut self, word: &Word) -> String {
+        self.expand(word, false).pop().map(|field| field.text).unwrap_or_default()
+    }
+
+    /// Expands a word into a pattern for case.
+    fn pattern(&mut self, word: &Word) -> String {
+        self.expand(word, false).pop().map(|field| field.pattern).unwrap_or_default()
+    }
+
+    /// Forks a child process that runs the function and exits with the
+    /// status it returns. Returns the process id of the child.
+    fn fork(&mut self, background: bool, f: impl FnOnce(&mut Shell) -> i32) -> io::Result<libc::pid_t> {
+        let _ = io::stdout().flush();
+        // SAFETY: the shell has a single thread, so the child can carry on
+        // running it.
+        let pid = unsafe { libc::fork() };
+        if pid == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        if pid != 0 {
+            return Ok(pid);
+        }
+        // SAFETY: ignoring signals, or restoring their default actions,
+        // can't fail for these signals.
+        unsafe {
+            // A subshell keeps the signals that are ignored, but not the
+            // commands trapped.
+            for (&sig, action) in &self.traps {
+                if sig != 0 && !action.is_empty() {
+                    libc::signal(sig, libc::SIG_DFL);
+                }
+            }
+            if background {
+                // Without job control, a background job must be immune to
+                // the signals from the terminal.
+                libc::signal(libc::SIGINT, libc::SIG_IGN);
+                libc::signal(libc::SIGQUIT, libc::SIG_IGN);
+            } else if self.interactive {
+                for sig in SHELL_SIGNALS {
+                    libc::signal(sig, libc::SIG_DFL);
+                }
+            }
+        }
+        self.interactive = false;
+        self.jobs.clear();
+        self.traps.retain(|_, action| action.is_empty());
+        let status = f(self);
+        let _ = io::stdout().flush();
+        process::exit(status);
+    }
+
+    /// Performs the redirections. Returns copies of the file descriptors
+    /// that they replaced, or -1 for those that weren't open, so that they
+    /// can be restored. If a redirection fails, undoes those before it and
+    /// returns the exit status.
+    fn redirect(&mut self, redirects: &[Redirect]) -> Result<Vec<(libc::c_int, libc::c_int)>, i32> {
+        let _ = io::stdout().flush();
+        let mut saved: Vec<(libc::c_int, libc::c_int)> = Vec::new();
+        for redirect in redirects {
+            let target = self.string(&redirect.target);
+            let mut options = OpenOptions::new();
+            let result = match redirect.kind {
+                Redirection::Dup if target == "-" => Ok(None),
+                Redirection::Dup => match target.parse::<libc::c_int>() {
+                    Ok(fd) => Ok(Some((fd, false))),
+                    Err(_) => Err(io::Error::from_raw_os_error(libc::EBADF)),
+                },
+                Redirection::Input => options.read(true).open(&target).map(|f| Some((f.into_raw_fd(), true))),
+                Redirection::Output => options.write(true).create(true).truncate(true).open(&target)
+                    .map(|f| Some((f.into_raw_fd(), true))),
+                Redirection::Append => options.append(true).create(true).open(&target)
+                    .map(|f| Some((f.into_raw_fd(), true))),
+                Redirection::Here => here_document(&target).map(|f| Some((f.into_raw_fd(), true))),
+            };
+            if !saved.iter().any(|&(fd, _)| fd == redirect.fd) {
+                // SAFETY: fcntl accepts any file descriptor, failing if it
+                // isn't open.
+                saved.push((redirect.fd, unsafe { libc::fcntl(redirect.fd, libc::F_DUPFD_CLOEXEC, SAVED_FD) }));
+            }
+            // SAFETY: the descriptors are the shell's own, or ones that
+            // the command named, which dup2 checks.
+            let result = result.and_then(|fd| unsafe {
+                match fd {
+                    Some((fd, opened)) if fd != redirect.fd => {
+                        let r = libc::dup2(fd, redirect.fd);
+                        if opened {
+                            libc::close(fd);
+                        }
+                        if r == -1 { Err(io::Error::last_os_error()) } else { Ok(()) }
+                    },
+                    Some(_) => Ok(()),
+                    None => {
+                        libc::close(redirect.fd);
+                        Ok(())
+                    },
+                }
+            });
+            if let Err(e) = result {
+                if redirect.kind == Redirection::Here {
+                    err!("here-document: {}", e);
+                } else {
+                    err!("{}: {}", target, e);
+                }
+                self.restore(saved);
+                return Err(1);
+            }
+        }
+        Ok(saved)
+    }
+
+    /// Puts back the file descriptors replaced by redirections.
+    fn restore(&self, saved: Vec<(libc::c_int, libc::c_int)>) {
+        let _ = io::stdout().flush();
+        for (fd, copy) in saved.into_iter().rev() {
+            // SAFETY: the copies were made by redirect and belong to the
+            // shell.
+            unsafe {
+                if copy == -1 {
+                    libc::close(fd);
+                } else {
+                    libc::dup2(copy, fd);
+                    libc::close(copy);
+                }
+            }
+        }
+    }
+
+    /// Executes a program in place of the shell, with the variables
+    /// assigned for it added to its environment. Returns only if that
+    /// fails, with the exit status.
+    fn exec(&self, args: &[String], assignments: &[(String, String)]) -> i32 {
+        let path = match search(&args[0], self.var("PATH").unwrap_or(""), is_executable) {
+            Some(path) => path,
+            None => {
+                err!("{}: not found", args[0]);
+                return NOT_FOUND;
+            }
+        };
+        let e = process::Command::new(path).arg0(&args[0]).args(&args[1..])
+            .envs(assignments.iter().map(|(name, value)| (name, value))).exec();
+        self.command_error(&args[0], &e)
+    }
+
+    /// Runs the commands read from the input, as . and eval do.
+    fn source(&mut self, reader: Box<dyn BufRead>) -> i32 {
+        let mut input = Input::new(reader, false);
+        self.run(&mut input)
+    }
+
+    fn dot(&mut self, args: &[String]) -> i32 {
+        let name = match args {
+            [name] => name,
+            _ => {
+                eprintln!("usage: . file");
+                return SYNTAX_ERROR;
+            }
+        };
+        let path = search(name, self.var("PATH").unwrap_or(""), |path| path.is_file())
+            .unwrap_or_else(|| PathBuf::from(name));
+        match File::open(&path) {
+            Ok(f) => self.source(Box::new(BufReader::new(f))),
+            Err(e) => {
+                err!("{}: {}", name, e);
+                1
+            }
+        }
+    }
+
+    /// Starts a break or continue out of the given number of loops, or
+    /// all of them if there are fewer.
+    fn jump(&mut self, name: &str, args: &[String], jump: fn(usize) -> Jump) -> i32 {
+        let n = match args.first().map(|n| n.parse::<usize>()) {
+            None => 1,
+            Some(Ok(n)) if n > 0 => n,
+            _ => {
+                err!("{}: {}: bad number", name, args[0]);
+                return 1;
+            }
+        };
+        if self.loops > 0 {
+            self.jump = Some(jump(n.min(self.loops)));
+        }
+        0
+    }
+
+    fn cd(&self, args: &[String]) -> i32 {
+        let dir = match args {
+            [] => match self.var("HOME") {
+                Some(home) => home.to_string(),
+                None => {
+                    err!("cd: no home directory");
+                    return 1;
+                }
+            },
+            [dir] => dir.clone(),
+            _ => {
+                err!("cd: too many arguments");
+                return 1;
+            }
+        };
+        match env::set_current_dir(&dir) {
+            Ok(()) => 0,
+            Err(e) => {
+                err!("cd: {}: {}", dir, e);
+                1
+            }
+        }
+    }
+
+    /// Marks variables as exported or read-only, setting them first if
+    /// given a value. Without arguments, lists the variables so marked.
+    fn mark(&mut self, command: &str, args: &[String]) -> i32 {
+        if args.is_empty() {
+            let marked = if command == "export" { &self.exported } else { &self.readonly };
+            let mut names: Vec<&String> = marked.iter().collect();
+            names.sort();
+            for name in names {
+                println!("{} {}", command, name);
+            }
+            return 0;
+        }
+        let mut status = 0;
+        for arg in args {
+            let (name, value) = match arg.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_string())),
+                None => (arg.as_str(), None),
+            };
+            if !is_name(name) {
+                err!("{}: {}: bad variable name", command, name);
+                status = 1;
+                continue;
+            }
+            if let Some(value) = value {
+                if !self.assign(name, value) {
+                    status = 1;
+                    continue;
+                }
+            }
+            if command == "export" {
+                if let Some(value) = self.vars.get(name) {
+                    env::set_var(name, value);
+                }
+                self.exported.insert(name.to_string());
+            } else {
+                self.readonly.insert(name.to_string());
+            }
+        }
+        status
+    }
+
+    /// Reads a line from the standard input, a byte at a time so as not to
+    /// take any more of it than that, and assigns its fields to the
+    /// variables, the last taking the rest of the line.
+    fn read(&mut self, args: &[String]) -> i32 {
+        if args.is_empty() {
+            eprintln!("usage: read name ...");
+            return SYNTAX_ERROR;
+        }
+        let mut line = Vec::new();
+        let mut eof = true;
+        loop {
+            let mut c = 0u8;
+            // SAFETY: c has room for the byte read.
+            match unsafe { libc::read(0, &mut c as *mut u8 as *mut libc::c_void, 1) } {
+                1 if c == b'\n' => {
+                    eof = false;
+                    break;
+                },
+                1 => line.push(c),
+                -1 if io::Error::last_os_error().kind() == io::ErrorKind::Interrupted => (),
+                _ => break,
+            }
+        }
+        let line = String::from_utf8_lossy(&line).into_owned();
+        let ifs = self.var("IFS").unwrap_or(DEFAULT_IFS).to_string();
+        let mut rest = line.trim_matches(|c| ifs.contains(c));
+        let mut status = if eof && line.is_empty() { 1 } else { 0 };
+        for (i, name) in args.iter().enumerate() {
+            let value = if i + 1 == args.len() {
+                std::mem::take(&mut rest)
+            } else {
+                let end = rest.find(|c| ifs.contains(c)).unwrap_or(rest.len());
+                let (field, after) = rest.split_at(end);
+                rest = after.trim_start_matches(|c| ifs.contains(c));
+                field
+            };
+            if !is_name(name) {
+                err!("read: {}: bad variable name", name);
+                status = 1;
+            } else if !self.assign(name, value.to_string()) {
+                status = 1;
+            }
+        }
+        status
+    }
+
+    /// Sets the positional parameters or, without arguments, lists the
+    /// variables.
+    fn set(&mut self, args: &[String]) -> i32 {
+        match args.first().map(String::as_str) {
+            None => {
+                let mut vars: Vec<(&String, &String)> = self.vars.iter().collect();
+                vars.sort();
+                for (name, value) in vars {
+                    println!("{}={}", name, value);
+                }
+            },
+            Some("--") => self.args = args[1..].to_vec(),
+            Some(arg) if arg.starts_with('-') && arg != "-" => {
+                err!("set: {}: bad option", arg);
+                return 1;
+            },
+            Some(_) => self.args = args.to_vec(),
+        }
+        0
+    }
+
+    fn shift(&mut self, args: &[String]) -> i32 {
+        let n = match args.first().map(|n| n.parse::<usize>()) {
+            None => 1,
+            Some(Ok(n)) => n,
+            Some(Err(_)) => {
+                err!("shift: {}: bad number", args[0]);
+                return 1;
+            }
+        };
+        if n > self.args.len() {
+            err!("shift: cannot shift");
+            return 1;
+        }
+        self.args.drain(..n);
+        0
+    }
+
+    fn umask(&self, args: &[String]) -> i32 {
+        match args {
+            [] => {
+                // SAFETY: umask can't fail; the mask is put back at once.
+                let mask = unsafe {
+                    let mask = libc::umask(0);
+                    libc::umask(mask);
+                    mask
+                };
+                println!("{:04o}", mask);
+                0
+            },
+            [mask] => match libc::mode_t::from_str_radix(mask, 8) {
+                Ok(mask) if mask <= 0o777 => {
+                    // SAFETY: umask can't fail.
+                    unsafe {
+                        libc::umask(mask);
+                    }
+                    0
+                },
+                _ => {
+                    err!("umask: {}: bad number", mask);
+                    1
+                }
+            },
+            _ => {
+                err!("umask: too many arguments");
+                1
+            }
+        }
+    }
+
+    /// Waits for the given background jobs, or all of them, returning the
+    /// exit status of the last one given.
+    fn wait(&mut self, args: &[String]) -> i32 {
+        if args.is_empty() {
+            for pid in std::mem::take(&mut self.jobs) {
+                wait(pid);
+            }
+            return 0;
+        }
+        let mut status = 0;
+        for arg in args {
+            match arg.parse::<libc::pid_t>() {
+                Ok(pid) if pid > 0 => {
+                    self.jobs.retain(|&job| job != pid);
+                    status = wait(pid);
+                },
+                _ => {
+                    err!("wait: {}: bad process id", arg);
+                    status = 1;
+                }
+            }
+        }
+        status
+    }
+
+    /// Sets the commands to run when the shell receives the signals, or
+    /// when it exits for EXIT or 0, or lists them if there are no
+    /// arguments. An empty command ignores the signals, and a command of
+    /// - restores their default actions, as does giving only signals.
+    fn trap(&mut self, args: &[String]) -> i32 {
+        let args = match args {
+            [first, rest @ ..] if first == "--" => rest,
+            _ => args,
+        };
+        if args.is_empty() {
+            for (&sig, action) in &self.traps {
+                let name = if sig == 0 { "EXIT" } else { signal::name(sig).unwrap_or("?") };
+                println!("trap -- '{}' {}", action.replace('\'', "'\\''"), name);
+            }
+            return 0;
+        }
+        let (action, sigs) = if args.len() == 1 || args[0].parse::<u32>().is_ok() {
+            (None, args)
+        } else {
+            (Some(args[0].as_str()).filter(|&action| action != "-"), &args[1..])
+        };
+        let mut status = 0;
+        for name in sigs {
+            let sig = match name.as_str() {
+                "EXIT" => Some(0),
+                name => signal::number(name),
+            };
+            match sig {
+                Some(sig) if sig != libc::SIGKILL && sig != libc::SIGSTOP => self.set_trap(sig, action),
+                _ => {
+                    err!("trap: {}: bad trap", name);
+                    status = 1;
+                },
+            }
+        }
+        status
+    }
+
+    fn set_trap(&mut self, sig: libc::c_int, action: Option<&str>) {
+        if sig != 0 {
+            let handler = match action {
+                Some("") => libc::SIG_IGN,
+                Some(_) => on_trap as extern "C" fn(libc::c_int) as libc::sighandler_t,
+                // An interactive shell goes back to ignoring the signals
+                // it would otherwise be killed by.
+                None if self.interactive && SHELL_SIGNALS.contains(&sig) => libc::SIG_IGN,
+                None => libc::SIG_DFL,
+            };
+            // SAFETY: the handler only sets a bit in an atomic, and
+            // KILL and STOP, which can't be caught, were rejected.
+            unsafe {
+                libc::signal(sig, handler);
+            }
+        }
+        match action {
+            Some(action) => self.traps.insert(sig, action.to_string()),
+            None => self.traps.remove(&sig),
+        };
+    }
+
+    /// Runs the commands trapped for the signals caught since the last
+    /// time, keeping the exit status of the command they interrupted.
+    fn run_traps(&mut self) {
+        let caught = CAUGHT.swap(0, Ordering::SeqCst);
+        if caught == 0 {
+            return;
+        }
+        let status = self.status;
+        for (sig, action) in self.traps.clone() {
+            if sig != 0 && caught & 1 << sig != 0 {
+                self.source(Box::new(io::Cursor::new(action.into_bytes())));
+            }
+        }
+        self.status = status;
+    }
+
+    /// Runs the command trapped for the shell's exit, if any, and exits
+    /// with the status.
+    fn exit(&mut self, status: i32) -> ! {
+        if let Some(action) = self.traps.remove(&0) {
+            self.status = status;
+            self.source(Box::new(io::Cursor::new(action.into_bytes())));
+        }
+        let _ = io::stdout().flush();
+        process::exit(status);
+    }
+
+    /// Runs a built-in command, returning its exit status, or None if
+    /// there is no such built-in.
+    fn builtin(&mut self, name: &str, args: &[String]) -> Option<i32> {
+        Some(match name {
+            ":" => 0,
+            "." => self.dot(args),
+            "break" => self.jump(name, args, Jump::Break),
+            "cd" => self.cd(args),
+            "continue" => self.jump(name, args, Jump::Continue),
+            "eval" => self.source(Box::new(io::Cursor::new(args.join(" ").into_bytes()))),
+            "exit" => {
+                let status = match args.first() {
+                    Some(n) => n.parse().unwrap_or(SYNTAX_ERROR),
+                    None => self.status,
+                };
+                self.exit(status);
+            },
+            "export" | "readonly" => self.mark(name, args),
+            "read" => self.read(args),
+            "set" => self.set(args),
+            "shift" => self.shift(args),
+            "trap" => self.trap(args),
+            "umask" => self.umask(args),
+            "wait" => self.wait(args),
+            _ => return None,
+        })
+    }
+
+    /// Runs a simple command: a built-in or a program found on the search
+    /// path. If exec is true, a program is executed in place of the shell.
+    /// Returns the exit status.
+    fn simple(&mut self, simple: &Simple, exec: bool) -> i32 {
+        self.substituted = None;
+        let args = self.fields(&simple.words);
+        if args.is_empty() {
+            // Without a command, the assignments are to the shell's own
+            // variables, made in turn, and the redirections create or
+            // open the files but do no more.
+            for (name, value) in &simple.assignments {
+                let value = self.string(value);
+                if !self.assign(name, value) {
+                    return 1;
+                }
+            }
+            let status = self.substituted.unwrap_or(0);
+            return match self.redirect(&simple.redirects) {
+                Ok(saved) => {
+                    self.restore(saved);
+                    status
+                },
+                Err(status) => status,
+            };
+        }
+        let mut assignments = Vec::new();
+        for (name, value) in &simple.assignments {
+            assignments.push((name.clone(), self.string(value)));
+        }
+        let name = args[0].as_str();
+        if BUILTINS.contains(&name) {
+            for (name, value) in assignments {
+                if !self.assign(&name, value) {
+                    return 1;
+                }
+            }
+            let saved = match self.redirect(&simple.redirects) {
+                Ok(saved) => saved,
+                Err(status) => return status,
+            };
+            if name == "exec" {
+                // The redirections of exec apply to the shell from then
+                // on.
+                for (_, copy) in saved {
+                    if copy != -1 {
+                        // SAFETY: the copy belongs to the shell.
+                        unsafe {
+                            libc::close(copy);
+                        }
+                    }
+                }
+                if args.len() == 1 {
+                    return 0;
+                }
+                let _ = io::stdout().flush();
+                process::exit(self.exec(&args[1..], &[]));
+            }
+            let status = self.builtin(name, &args[1..]).unwrap_or(NOT_FOUND);
+            self.restore(saved);
+            return status;
+        }
+        if exec {
+            if let Err(status) = self.redirect(&simple.redirects) {
+                return status;
+            }
+            return self.exec(&args, &assignments);
+        }
+        match self.fork(false, |shell| match shell.redirect(&simple.redirects) {
+            Ok(_) => shell.exec(&args, &assignments),
+            Err(status) => status,
+        }) {
+            Ok(pid) => wait(pid),
+            Err(e) => {
+                err!("fork: {}", e);
+                1
+            }
+        }
+    }
+
+    /// Takes a break or continue at the end of the body of a loop,
+    /// returning whether the loop should stop.
+    fn end_of_body(&mut self) -> bool {
+        match self.jump.take() {
+            None => false,
+            Some(Jump::Interrupt) => {
+                self.jump = Some(Jump::Interrupt);
+                true
+            },
+            Some(Jump::Break(n)) => {
+                if n > 1 {
+                    self.jump = Some(Jump::Break(n - 1));
+                }
+                true
+            },
+            Some(Jump::Continue(n)) => {
+                if n > 1 {
+                    self.jump = Some(Jump::Continue(n - 1));
+                }
+                n > 1
+            },
+        }
+    }
+
+    fn compound(&mut self, compound: &Compound) -> i32 {
+        match compound {
+            Compound::Group(list) => self.execute(list),
+            Compound::Subshell(list) => match self.fork(false, |shell| shell.execute(list)) {
+                Ok(pid) => wait(pid),
+                Err(e) => {
+                    err!("fork: {}", e);
+                    1
+                }
+            },
+            Compound::If { branches, otherwise } => {
+                for (condition, body) in branches {
+                    let status = self.execute(condition);
+                    if self.jump.is_some() {
+                        return status;
+                    }
+                    if status == 0 {
+                        return self.execute(body);
+                    }
+                }
+                match otherwise {
+                    Some(body) => self.execute(body),
+                    None => 0,
+                }
+            },
+            Compound::While { condition, body, until } => {
+                let mut status = 0;
+                self.loops += 1;
+                loop {
+                    let result = self.execute(condition);
+                    if self.jump.is_some() {
+                        if self.end_of_body() {
+                            break;
+                        }
+                        continue;
+                    }
+                    if (result == 0) == *until {
+                        break;
+                    }
+                    status = self.execute(body);
+                    if self.end_of_body() {
+                        break;
+                    }
+                }
+                self.loops -= 1;
+                status
+            },
+            Compound::For { name, words, body } => {
+                let values = match words {
+                    Some(words) => self.fields(words),
+                    None => self.args.clone(),
+                };
+                let mut status = 0;
+                self.loops += 1;
+                for value in values {
+                    if !self.assign(name, value) {
+                        status = 1;
+                        break;
+                    }
+                    status = self.execute(body);
+                    if self.end_of_body() {
+                        break;
+                    }
+                }
+                self.loops -= 1;
+                status
+            },
+            Compound::Case { word, arms } => {
+                let value = self.string(word);
+                for (patterns, body) in arms {
+                    for pattern in patterns {
+                        if glob::matches(self.pattern(pattern).as_bytes(), value.as_bytes()) {
+                            return self.execute(body);
+                        }
+                    }
+                }
+                0
+            },
+        }
+    }
+
+    /// Runs a command. If exec is true, a simple command that runs a
+    /// program executes it in place of the shell.
+    fn command(&mut self, command: &Command, exec: bool) -> i32 {
+        match command {
+            Command::Simple(simple) => self.simple(simple, exec),
+            Command::Compound(compound, redirects) => {
+                let saved = match self.redirect(redirects) {
+                    Ok(saved) => saved,
+                    Err(status) => return status,
+                };
+                let status = self.compound(compound);
+                self.restore(saved);
+                status
+            },
+        }
+    }
+
+    /// Runs the commands of a pipeline, each in its own process, returning
+    /// the exit status of the last.
+    fn pipeline(&mut self, pipeline: &Pipeline) -> i32 {
+        if let [command] = &pipeline[..] {
+            return self.command(command, false);
+        }
+        let mut pids = Vec::new();
+        // The end of the last pipe from which the next command reads.
+        let mut input: Option<libc::c_int> = None;
+        for (i, command) in pipeline.iter().enumerate() {
+            let last = i + 1 == pipeline.len();
+            let mut fds = [-1; 2];
+            // SAFETY: fds has room for the two descriptors.
+            if !last && unsafe { libc::pipe(fds.as_mut_ptr()) } == -1 {
+                err!("pipe: {}", io::Error::last_os_error());
+                break;
+            }
+            let result = self.fork(false, |shell| {
+                // SAFETY: the descriptors are the ends of the pipes.
+                unsafe {
+                    if let Some(fd) = input {
+                        libc::dup2(fd, 0);
+                        libc::close(fd);
+                    }
+                    if !last {
+                        libc::dup2(fds[1], 1);
+                        libc::close(fds[1]);
+                        libc::close(fds[0]);
+                    }
+                }
+                shell.command(command, true)
+            });
+            // SAFETY: the children have their own copies of these.
+            unsafe {
+                if let Some(fd) = input {
+                    libc::close(fd);
+                }
+                if !last {
+                    libc::close(fds[1]);
+                }
+            }
+            input = if last { None } else { Some(fds[0]) };
+            match result {
+                Ok(pid) => pids.push(pid),
+                Err(e) => {
+                    err!("fork: {}", e);
+                    break;
+                }
+            }
+        }
+        if let Some(fd) = input {
+            // SAFETY: the pipe was left open when a command couldn't be
+            // started.
+            unsafe {
+                libc::close(fd);
+            }
+        }
+        let complete = pids.len() == pipeline.len();
+        let mut status = 1;
+        for pid in pids {
+            status = wait(pid);
+        }
+        if complete { status } else { 1 }
+    }
+
+    fn and_or(&mut self, and_or: &AndOr) -> i32 {
+        self.status = self.pipeline(&and_or.first);
+        for (connector, pipeline) in &and_or.rest {
+            if self.jump.is_some() {
+                break;
+            }
+            let run = match connector {
+                Connector::And => self.status == 0,
+                Connector::Or => self.status != 0,
+            };
+            if run {
+                self.status = self.pipeline(pipeline);
+            }
+        }
+        self.status
+    }
+
+    /// Starts a list running in the background, with its standard input
+    /// from /dev/null so that it doesn't compete with the shell for the
+    /// terminal.
+    fn background(&mut self, and_or: &AndOr) {
+        let result = self.fork(true, |shell| {
+            if let Ok(null) = File::open("/dev/null") {
+                // SAFETY: null is open.
+                unsafe {
+                    libc::dup2(null.as_raw_fd(), 0);
+                }
+            }
+            shell.and_or(and_or)
+        });
+        match result {
+            Ok(pid) => {
+                if self.interactive {
+                    eprintln!("{}", pid);
+                }
+                self.jobs.push(pid);
+                self.last_job = Some(pid);
+                self.status = 0;
+            },
+            Err(e) => {
+                err!("fork: {}", e);
+                self.status = 1;
+            }
+        }
+    }
+
+    fn execute(&mut self, list: &List) -> i32 {
+        if list.is_empty() {
+            self.status = 0;
+        }
+        for (and_or, background) in list {
+            if self.jump.is_some() {
+                break;
+            }
+            if *background {
+                self.background(and_or);
+            } else if self.and_or(and_or) == 128 + libc::SIGINT && self.interactive && self.jump.is_none() {
+                // Start the prompt on a new line after the ^C.
+                eprintln!();
+                self.jump = Some(Jump::Interrupt);
+            }
+            self.run_traps();
+        }
+        self.status
+    }
+
+    /// Reads and executes commands until the end of the input. Returns
+    /// the exit status of the last command.
+    fn run(&mut self, input: &mut Input) -> i32 {
+        loop {
+            input.primary = true;
+            if let Some(prompt) = self.var("PS1") {
+                input.prompt = prompt.to_string();
+            }
+            let mut parser = Parser { input: &mut *input };
+            match parser.line() {
+                Ok(Some(list)) => {
+                    if !list.is_empty() {
+                        self.execute(&list);
+                    }
+                    // A break or continue from eval or . carries on out
+                    // to the loops around it.
+                    if self.loops == 0 {
+                        self.jump = None;
+                    }
+                },
+                Ok(None) => {
+                    if let Some(e) = input.error.take() {
+                        err!("{}", e);
+                        return 1;
+                    }
+                    return self.status;
+                },
+                Err(msg) => {
+                    err!("syntax error: {}", msg);
+                    self.status = SYNTAX_ERROR;
+                    if !self.interactive {
+                        return self.status;
+                    }
+                    input.discard_line();
+                }
+            }
+        }
+    }
+}
+
+pub const SPEC: Spec = Spec::new("command language", &[
+    Opt::flag('i', "be interactive"),
+    Opt::flag('s', "read commands from the standard input"),
+    Opt::arg('c', "string", "run the commands in string"),
+], "[file] [arg ...]");
+
+pub fn run(args: Vec<OsString>) -> i32 {
+    let mut args = args;
+    let prog = args.remove(0).to_string_lossy().into_owned();
+    let usage = || -> ! {
+        eprintln!("usage: {} [-i] [-c string | -s | file] [arg ...]", prog);
+        process::exit(status::USAGE);
+    };
+
+    // Options end at the first operand, which, with the operands after
+    // it, belongs to the script rather than the shell, so they are picked
+    // out by hand.
+    let mut command: Option<OsString> = None;
+    let mut interactive = false;
+    let mut stdin = false;
+    let mut i = 0;
+    while i < args.len() && args[i].as_encoded_bytes().starts_with(b"-") && args[i] != "-" {
+        match args[i].to_str().unwrap_or_default() {
+            "--" => {
+                i += 1;
+                break;
+            },
+            "-c" => {
+                i += 1;
+                command = Some(args.get(i).cloned().unwrap_or_else(|| usage()));
+            },
+            "-i" => interactive = true,
+            "-s" => stdin = true,
+            _ => usage(),
+        }
+        i += 1;
+    }
+    let mut operands = args[i..].to_vec();
+
+    // The name of a script, or the first operand after a command string,
+    // is $0; the operands after it are the positional parameters. The
+    // script's name and the commands need not be valid UTF-8, but the
+    // parameters, being expanded as text, must be.
+    let mut name = None;
+    let reader: Box<dyn BufRead> = match command {
+        Some(command) => {
+            if !operands.is_empty() {
+                name = Some(operands.remove(0));
+            }
+            Box::new(io::Cursor::new(command.into_encoded_bytes()))
+        },
+        None if !stdin && !operands.is_empty() => {
+            let file = operands.remove(0);
+            let reader = match File::open(&file) {
+                Ok(f) => Box::new(BufReader::new(f)),
+                Err(e) => {
+                    err!("{}: {}", file.to_string_lossy(), e);
+                    process::exit(NOT_FOUND);
+                }
+            };
+            name = Some(file);
+            reader
+        },
+        None => {
+            // SAFETY: isatty accepts any file descriptor.
+            interactive |= unsafe { libc::isatty(0) == 1 && libc::isatty(2) == 1 };
+            Box::new(BufReader::new(io::stdin()))
+        },
+    };
+
+    if interactive {
+        for sig in SHELL_SIGNALS {
+            // SAFETY: ignoring these signals can't fail.
+            unsafe {
+                libc::signal(sig, libc::SIG_IGN);
+            }
+        }
+    }
+
+    let mut input = Input::new(reader, interactive);
+    let mut shell = Shell::new(prog, interactive);
+    if let Some(name) = name {
+        shell.name = name.to_string_lossy().into_owned();
+    }
+    shell.args = args::strings(operands);
+    let status = shell.run(&mut input);
+    shell.exit(status)
+}
+
+/// Notes that a signal for which a command is trapped was caught, for
+/// the shell to run the command once the current one has finished.
+extern "C" fn on_trap(sig: libc::c_int) {
+    CAUGHT.fetch_or(1 << sig, Ordering::SeqCst);
+}
+
+/// Returns a file from which the text of a here-document can be read.
+/// It is a temporary file, removed once opened, rather than a pipe, which
+/// could hold only so much before the shell blocked writing to it.
+fn here_document(text: &str) -> io::Result<File> {
+    let mut tmp = TempFile::new("sh")?;
+    tmp.write_all(text.as_bytes())?;
+    File::open(tmp.path())
+}
+
+#[test]
+fn test_parse() {
+    let text = "echo 'a b'  \"c\\\"d\\x\"e\\ f\\\ng # comment\n\n\
+                a 2>&1 <in|b >>out && c ||\nd & e;f\n\
+                x=1 y= echo $x\"${y-a b}\"`date`\n\
+                for i in 1 2\ndo\n  if test $i = 1; then :; else break; fi\ndone\n\
+                case $x in\n(a|b) ;;\n*) (c) ;;\nesac >out\n\
+                cat <<E\"O\"F 3<<-EOF\n$x\nEOF\n\t\"$x\\$\"\n\tEOF\n";
+    let mut input = Input::new(Box::new(text.as_bytes()), false);
+    let mut parser = Parser { input: &mut input };
+    let text = |s: &str| Piece::Text(s.to_string());
+    let quoted = |s: &str| Piece::Quoted(s.to_string());
+    let param = |s: &str, quoted| Piece::Param(Param { name: s.to_string(), op: None }, quoted);
+    let word = |s: &str| Word(vec![text(s)]);
+    let simple = |words: &[&str], redirects: Vec<Redirect>| Command::Simple(Simple {
+        assignments: Vec::new(),
+        words: words.iter().map(|s| word(s)).collect(),
+        redirects,
+    });
+    let and_or = |first: Pipeline| AndOr { first, rest: Vec::new() };
+    let list = |commands: Vec<Command>| commands.into_iter().map(|c| (and_or(vec![c]), false)).collect::<List>();
+    let redirect = |fd, kind, target| Redirect { fd, kind, target: word(target) };
+    assert_eq!(parser.line(), Ok(Some(vec![(and_or(vec![Command::Simple(Simple {
+        assignments: Vec::new(),
+        words: vec![
+            word("echo"),
+            Word(vec![quoted("a b")]),
+            Word(vec![quoted("c\"d\\x"), text("e"), quoted(" "), text("fg")]),
+        ],
+        redirects: Vec::new(),
+    })]), false)])));
+    assert_eq!(parser.line(), Ok(Some(vec![])));
+    assert_eq!(parser.line(), Ok(Some(vec![
+        (AndOr {
+            first: vec![
+                simple(&["a"], vec![
+                    redirect(2, Redirection::Dup, "1"),
+                    redirect(0, Redirection::Input, "in"),
+                ]),
+                simple(&["b"], vec![redirect(1, Redirection::Append, "out")]),
+            ],
+            rest: vec![
+                (Connector::And, vec![simple(&["c"], Vec::new())]),
+                (Connector::Or, vec![simple(&["d"], Vec::new())]),
+            ],
+        }, true),
+        (and_or(vec![simple(&["e"], Vec::new())]), false),
+        (and_or(vec![simple(&["f"], Vec::new())]), false),
+    ])));
+    assert_eq!(parser.line(), Ok(Some(list(vec![Command::Simple(Simple {
+        assignments: vec![("x".to_string(), word("1")), ("y".to_string(), Word(vec![]))],
+        words: vec![word("echo"), Word(vec![
+            param("x", false),
+            Piece::Param(Param { name: "y".to_string(), op: Some(('-', false, word("a b"))) }, true),
+            Piece::Command("date".to_string(), false),
+        ])],
+        redirects: Vec::new(),
+    })]))));
+    assert_eq!(parser.line(), Ok(Some(list(vec![Command::Compound(Compound::For {
+        name: "i".to_string(),
+        words: Some(vec![word("1"), word("2")]),
+        body: list(vec![Command::Compound(Compound::If {
+            branches: vec![(
+                list(vec![Command::Simple(Simple {
+                    assignments: Vec::new(),
+                    words: vec![word("test"), Word(vec![param("i", false)]), word("="), word("1")],
+                    redirects: Vec::new(),
+                })]),
+                list(vec![simple(&[":"], Vec::new())]),
+            )],
+            otherwise: Some(list(vec![simple(&["break"], Vec::new())])),
+        }, Vec::new())]),
+    }, Vec::new())]))));
+    assert_eq!(parser.line(), Ok(Some(list(vec![Command::Compound(Compound::Case {
+        word: Word(vec![param("x", false)]),
+        arms: vec![
+            (vec![word("a"), word("b")], Vec::new()),
+            (vec![word("*")], list(vec![Command::Compound(Compound::Subshell(list(vec![simple(&["c"], Vec::new())])), Vec::new())])),
+        ],
+    }, vec![redirect(1, Redirection::Output, "out")])]))));
+    assert_eq!(parser.line(), Ok(Some(list(vec![simple(&["cat"], vec![
+        Redirect { fd: 0, kind: Redirection::Here, target: Word(vec![quoted("$x\n")]) },
+        Redirect { fd: 3, kind: Redirection::Here, target: Word(vec![quoted("\""), param("x", true), quoted("$\"\n")]) },
+    ])]))));
+    assert_eq!(parser.line(), Ok(None));
+
+    for (text, error) in [("echo 'a\n", "unterminated quoted string"), ("a | ;\n", "unexpected ;"),
+                          ("a >\n", "unexpected newline"), ("a && \n", "unexpected end of file"),
+                          ("if a; then b; done\n", "unexpected done"), ("fi\n", "unexpected fi"),
+                          ("cat <<$x\n", "bad here-document delimiter")] {
+        let mut input = Input::new(Box::new(text.as_bytes()), false);
+        let mut parser = Parser { input: &mut input };
+        assert_eq!(parser.line(), Err(error.to_string()));
+    }
+}
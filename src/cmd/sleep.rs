@@ -0,0 +1,70 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// An implementation of the sleep(1) command in Rust.
+// See http://man.cat-v.org/unix-7th/1/sleep
+use std::ffi::OsString;
+use std::process;
+use std::thread;
+use std::time::Duration;
+
+use crate::cmd::Spec;
+use crate::err;
+use crate::status;
+
+/// Parses a duration: a decimal number of seconds, which may have a
+/// fraction, optionally followed by s for seconds, m for minutes, h for
+/// hours or d for days.
+fn parse_duration(s: &str) -> Option<Duration> {
+    let (number, multiplier) = match s.char_indices().last()? {
+        (i, 's') => (&s[..i], 1.0),
+        (i, 'm') => (&s[..i], 60.0),
+        (i, 'h') => (&s[..i], 60.0 * 60.0),
+        (i, 'd') => (&s[..i], 24.0 * 60.0 * 60.0),
+        _ => (s, 1.0),
+    };
+    let digits = number.bytes().filter(u8::is_ascii_digit).count();
+    let points = number.bytes().filter(|&c| c == b'.').count();
+    if digits == 0 || points > 1 || digits + points != number.len() {
+        return None;
+    }
+    let secs: f64 = number.parse().ok()?;
+    Duration::try_from_secs_f64(secs * multiplier).ok()
+}
+
+pub const SPEC: Spec = Spec::new("suspend execution for an interval", &[], "time[s|m|h|d] ...");
+
+pub fn run(args: Vec<OsString>) -> i32 {
+    let operands = SPEC.parse(args).strings();
+    if operands.is_empty() {
+        SPEC.usage();
+    }
+    let mut total = Duration::ZERO;
+    for arg in &operands {
+        match parse_duration(arg).and_then(|d| total.checked_add(d)) {
+            Some(sum) => total = sum,
+            None => {
+                err!("invalid time interval: {}", arg);
+                process::exit(status::USAGE);
+            }
+        }
+    }
+    thread::sleep(total);
+    status::SUCCESS
+}
+
+#[test]
+fn test_parse_duration() {
+    assert_eq!(parse_duration("2"), Some(Duration::from_secs(2)));
+    assert_eq!(parse_duration("0.5"), Some(Duration::from_millis(500)));
+    assert_eq!(parse_duration(".25s"), Some(Duration::from_millis(250)));
+    assert_eq!(parse_duration("1.5m"), Some(Duration::from_secs(90)));
+    assert_eq!(parse_duration("2h"), Some(Duration::from_secs(7200)));
+    assert_eq!(parse_duration("1d"), Some(Duration::from_secs(86400)));
+    assert_eq!(parse_duration("1x"), None);
+    assert_eq!(parse_duration("."), None);
+    assert_eq!(parse_duration("1.2.3"), None);
+    assert_eq!(parse_duration("s"), None);
+    assert_eq!(parse_duration(""), None);
+    assert_eq!(parse_duration("-1"), None);
+}
@@ -0,0 +1,794 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// An implementation of the sort(1) command in Rust.
+// See http://man.cat-v.org/unix-7th/1/sort
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::os::unix::fs::MetadataExt;
+use std::path::PathBuf;
+use std::process;
+
+use crate::args;
+use crate::atomic::AtomicFile;
+use crate::cmd::{Opt, Spec};
+use crate::collate::Collation;
+use crate::err;
+use crate::input::Input;
+use crate::mmap::Mmap;
+use crate::mode;
+use crate::status;
+use crate::tempfile;
+use crate::tempfile::TempFile;
+
+/// The amount of line data held in memory before a sorted run is written
+/// to a temporary file.
+const MEMORY_LIMIT: usize = 64 * 1024 * 1024;
+
+/// The number of runs of the same size that are merged together.
+const MERGE_ORDER: usize = 16;
+
+/// How lines are to be compared.
+#[derive(Clone, Copy, Default)]
+struct Order {
+    blanks: bool,
+    /// The order of text that -f and -n don't override.
+    collation: Collation,
+    fold: bool,
+    numeric: bool,
+    reverse: bool,
+}
+
+fn is_blank(c: &u8) -> bool {
+    *c == b' ' || *c == b'\t'
+}
+
+fn skip_blanks(s: &[u8]) -> &[u8] {
+    let n = s.iter().take_while(|c| is_blank(c)).count();
+    &s[n..]
+}
+
+/// Splits the leading number of a string into its sign, integer part
+/// (without leading zeros) and fraction (without trailing zeros).
+fn parse_num(s: &[u8]) -> (bool, &[u8], &[u8]) {
+    let s = skip_blanks(s);
+    let (neg, s) = match s.first() {
+        Some(b'-') => (true, &s[1..]),
+        _ => (false, s),
+    };
+    let int_len = s.iter().take_while(|c| c.is_ascii_digit()).count();
+    let int = &s[..int_len];
+    let int = &int[int.iter().take_while(|c| **c == b'0').count()..];
+    let frac = match s.get(int_len) {
+        Some(b'.') => {
+            let rest = &s[int_len + 1..];
+            let len = rest.iter().take_while(|c| c.is_ascii_digit()).count();
+            let frac = &rest[..len];
+            &frac[..frac.len() - frac.iter().rev().take_while(|c| **c == b'0').count()]
+        },
+        _ => &s[..0],
+    };
+    (neg, int, frac)
+}
+
+/// Compares the leading numbers of two strings arithmetically. Strings
+/// without a leading number compare as zero.
+fn numeric_cmp(a: &[u8], b: &[u8]) -> Ordering {
+    let (aneg, aint, afrac) = parse_num(a);
+    let (bneg, bint, bfrac) = parse_num(b);
+    let azero = aint.is_empty() && afrac.is_empty();
+    let bzero = bint.is_empty() && bfrac.is_empty();
+    let aneg = aneg && !azero;
+    let bneg = bneg && !bzero;
+    if aneg != bneg {
+        return if aneg { Ordering::Less } else { Ordering::Greater };
+    }
+    let ord = aint.len().cmp(&bint.len())
+        .then_with(|| aint.cmp(bint))
+        .then_with(|| afrac.cmp(bfrac));
+    if aneg {
+        ord.reverse()
+    } else {
+        ord
+    }
+}
+
+#[test]
+fn test_numeric_cmp() {
+    assert_eq!(numeric_cmp(b"10", b"9"), Ordering::Greater);
+    assert_eq!(numeric_cmp(b" -10", b"-9"), Ordering::Less);
+    assert_eq!(numeric_cmp(b"0.50", b".5"), Ordering::Equal);
+    assert_eq!(numeric_cmp(b"-0", b"abc"), Ordering::Equal);
+    assert_eq!(numeric_cmp(b"1.05", b"1.5"), Ordering::Less);
+}
+
+impl Order {
+    /// Compares two strings according to the ordering options, ignoring
+    /// reversal.
+    fn compare(&self, a: &[u8], b: &[u8]) -> Ordering {
+        let (a, b) = if self.blanks {
+            (skip_blanks(a), skip_blanks(b))
+        } else {
+            (a, b)
+        };
+        if self.numeric {
+            numeric_cmp(a, b)
+        } else if self.fold {
+            a.iter().map(u8::to_ascii_uppercase)
+                .cmp(b.iter().map(u8::to_ascii_uppercase))
+        } else {
+            self.collation.compare(a, b)
+        }
+    }
+}
+
+/// One end of a sort key.
+#[derive(Clone, Copy)]
+struct KeyPos {
+    /// The field number, counting from 1.
+    field: usize,
+    /// The character position within the field, counting from 1. For the
+    /// end of a key, 0 means the end of the field.
+    ch: usize,
+    /// Whether leading blanks in the field are skipped.
+    blanks: bool,
+}
+
+/// A sort key.
+struct Key {
+    start: KeyPos,
+    end: Option<KeyPos>,
+    order: Order,
+    /// True if the key has its own ordering options, overriding the
+    /// global ones.
+    has_flags: bool,
+}
+
+/// Parses key ordering flags, returning whether the b flag was present.
+fn parse_flags(flags: &str, order: &mut Order) -> Result<bool, String> {
+    let mut blanks = false;
+    for c in flags.chars() {
+        match c {
+            'b' => blanks = true,
+            'f' => order.fold = true,
+            'n' => order.numeric = true,
+            'r' => order.reverse = true,
+            _ => return Err(format!("invalid key flag: {}", c)),
+        }
+    }
+    Ok(blanks)
+}
+
+/// Parses a key position of the form field[.char][flags].
+fn parse_pos(spec: &str, order: &mut Order) -> Result<(KeyPos, bool), String> {
+    let digits = |s: &str| s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let n = digits(spec);
+    let field = spec[..n].parse::<usize>()
+        .map_err(|_| format!("invalid key position: {}", spec))?;
+    let mut rest = &spec[n..];
+    let mut ch = 0;
+    if let Some(r) = rest.strip_prefix('.') {
+        let n = digits(r);
+        ch = r[..n].parse::<usize>()
+            .map_err(|_| format!("invalid key position: {}", spec))?;
+        rest = &r[n..];
+    }
+    let has_flags = !rest.is_empty();
+    let blanks = parse_flags(rest, order)?;
+    Ok((KeyPos { field, ch, blanks }, has_flags))
+}
+
+impl Key {
+    /// Parses a key definition of the form start[,end] as given to -k.
+    fn parse(spec: &str) -> Result<Key, String> {
+        let mut order = Order::default();
+        let (start, end) = match spec.split_once(',') {
+            Some((start, end)) => (start, Some(end)),
+            None => (spec, None),
+        };
+        let (mut start, start_flags) = parse_pos(start, &mut order)?;
+        if start.field == 0 {
+            return Err(format!("invalid field number: {}", spec));
+        }
+        if start.ch == 0 {
+            start.ch = 1;
+        }
+        let (end, end_flags) = match end {
+            Some(end) => {
+                let (end, flags) = parse_pos(end, &mut order)?;
+                if end.field == 0 {
+                    return Err(format!("invalid field number: {}", spec));
+                }
+                (Some(end), flags)
+            },
+            None => (None, false),
+        };
+        Ok(Key { start, end, order, has_flags: start_flags || end_flags })
+    }
+
+    /// Returns the whole line as a key.
+    fn line(order: Order) -> Key {
+        Key {
+            start: KeyPos { field: 1, ch: 1, blanks: false },
+            end: None,
+            order,
+            has_flags: true,
+        }
+    }
+
+    /// Extracts the key from a line.
+    fn extract<'a>(&self, line: &'a [u8], sep: Option<u8>) -> &'a [u8] {
+        if self.start.field == 1 && self.start.ch == 1 && !self.start.blanks
+            && self.end.is_none() {
+            return line;
+        }
+        let fields = split_fields(line, sep);
+        let position = |pos: &KeyPos, is_end: bool| -> usize {
+            let (mut start, end) = match fields.get(pos.field - 1) {
+                Some(&range) => range,
+                None => return line.len(),
+            };
+            if pos.blanks {
+                while start < end && is_blank(&line[start]) {
+                    start += 1;
+                }
+            }
+            if is_end && pos.ch == 0 {
+                end
+            } else if is_end {
+                end.min(start + pos.ch)
+            } else {
+                end.min(start + pos.ch - 1)
+            }
+        };
+        let start = position(&self.start, false);
+        let end = match self.end {
+            Some(ref pos) => position(pos, true),
+            None => line.len(),
+        };
+        &line[start..end.max(start)]
+    }
+}
+
+/// Returns the ranges of the fields of a line. Without a separator, a
+/// field is a maximal sequence of non-blanks and its leading blanks.
+fn split_fields(line: &[u8], sep: Option<u8>) -> Vec<(usize, usize)> {
+    let mut fields = Vec::new();
+    match sep {
+        Some(sep) => {
+            let mut start = 0;
+            for (i, c) in line.iter().enumerate() {
+                if *c == sep {
+                    fields.push((start, i));
+                    start = i + 1;
+                }
+            }
+            fields.push((start, line.len()));
+        },
+        None => {
+            let mut i = 0;
+            while i < line.len() {
+                let start = i;
+                while i < line.len() && is_blank(&line[i]) {
+                    i += 1;
+                }
+                while i < line.len() && !is_blank(&line[i]) {
+                    i += 1;
+                }
+                fields.push((start, i));
+            }
+        },
+    }
+    fields
+}
+
+#[test]
+fn test_extract() {
+    let key = Key::parse("2").unwrap();
+    assert_eq!(key.extract(b"a  b c", None), b"  b c");
+    let key = Key::parse("2,2").unwrap();
+    assert_eq!(key.extract(b"a  b c", None), b"  b");
+    let key = Key::parse("2.2b,2").unwrap();
+    assert_eq!(key.extract(b"a  bcd e", None), b"cd");
+    let key = Key::parse("3,3").unwrap();
+    assert_eq!(key.extract(b"root:x:0:0", Some(b':')), b"0");
+    assert_eq!(key.extract(b"root", Some(b':')), b"");
+}
+
+struct Sort {
+    keys: Vec<Key>,
+    sep: Option<u8>,
+    /// Whether the last-resort comparison is reversed.
+    reverse: bool,
+    unique: bool,
+    /// The byte that ends each line: a newline or, with -z, a NUL.
+    eol: u8,
+}
+
+impl Sort {
+    /// Compares two lines by their keys, disregarding the last-resort
+    /// comparison.
+    fn compare_keys(&self, a: &[u8], b: &[u8]) -> Ordering {
+        for key in &self.keys {
+            let ord = key.order.compare(key.extract(a, self.sep), key.extract(b, self.sep));
+            let ord = if key.order.reverse { ord.reverse() } else { ord };
+            if ord != Ordering::Equal {
+                return ord;
+            }
+        }
+        Ordering::Equal
+    }
+
+    /// Compares two lines. Lines that compare equal are ordered by their
+    /// bytes unless only unique lines are wanted.
+    fn compare(&self, a: &[u8], b: &[u8]) -> Ordering {
+        let ord = self.compare_keys(a, b);
+        if ord != Ordering::Equal || self.unique {
+            return ord;
+        }
+        if self.reverse {
+            b.cmp(a)
+        } else {
+            a.cmp(b)
+        }
+    }
+}
+
+/// Reads a line, without its terminator.
+fn next_line(reader: &mut dyn BufRead, eol: u8) -> io::Result<Option<Vec<u8>>> {
+    let mut line = Vec::new();
+    if reader.read_until(eol, &mut line)? == 0 {
+        return Ok(None);
+    }
+    if line.last() == Some(&eol) {
+        line.pop();
+    }
+    Ok(Some(line))
+}
+
+fn write_line(out: &mut dyn Write, line: &[u8], eol: u8) -> io::Result<()> {
+    out.write_all(line)?;
+    out.write_all(&[eol])
+}
+
+impl Sort {
+    fn sort_lines<L: AsRef<[u8]>>(&self, lines: &mut Vec<L>) {
+        lines.sort_by(|a, b| self.compare(a.as_ref(), b.as_ref()));
+        if self.unique {
+            lines.dedup_by(|a, b| self.compare_keys(a.as_ref(), b.as_ref()) == Ordering::Equal);
+        }
+    }
+
+    /// Merges sorted sources. Equal lines are taken from the earliest
+    /// source first, so merging preserves the order of the input.
+    fn merge(&self, mut sources: Vec<Box<dyn BufRead>>, out: &mut dyn Write)
+             -> io::Result<()> {
+        let mut heads = Vec::with_capacity(sources.len());
+        for source in sources.iter_mut() {
+            heads.push(next_line(source.as_mut(), self.eol)?);
+        }
+        let mut last: Option<Vec<u8>> = None;
+        loop {
+            let mut best: Option<usize> = None;
+            for (i, head) in heads.iter().enumerate() {
+                if let Some(line) = head {
+                    let better = match best {
+                        Some(b) => self.compare(line, heads[b].as_ref().unwrap()) == Ordering::Less,
+                        None => true,
+                    };
+                    if better {
+                        best = Some(i);
+                    }
+                }
+            }
+            let i = match best {
+                Some(i) => i,
+                None => return Ok(()),
+            };
+            let line = heads[i].take().unwrap();
+            heads[i] = next_line(sources[i].as_mut(), self.eol)?;
+            if self.unique {
+                if let Some(ref prev) = last {
+                    if self.compare_keys(prev, &line) == Ordering::Equal {
+                        continue;
+                    }
+                }
+                write_line(out, &line, self.eol)?;
+                last = Some(line);
+            } else {
+                write_line(out, &line, self.eol)?;
+            }
+        }
+    }
+
+    /// Checks that the input is sorted, reporting the first line that is
+    /// out of order unless quiet.
+    fn check(&self, name: &OsStr, reader: &mut dyn BufRead, quiet: bool)
+             -> io::Result<bool> {
+        let mut prev: Option<Vec<u8>> = None;
+        let mut n = 0;
+        while let Some(line) = next_line(reader, self.eol)? {
+            n += 1;
+            if let Some(ref prev) = prev {
+                let ord = if self.unique {
+                    self.compare_keys(prev, &line)
+                } else {
+                    self.compare(prev, &line)
+                };
+                if ord == Ordering::Greater || (self.unique && ord == Ordering::Equal) {
+                    if !quiet {
+                        err!("{}:{}: disorder: {}", name.to_string_lossy(), n,
+                             String::from_utf8_lossy(&line));
+                    }
+                    return Ok(false);
+                }
+            }
+            prev = Some(line);
+        }
+        Ok(true)
+    }
+}
+
+/// Accumulates lines in memory, writing them out as sorted runs in
+/// temporary files whenever memory fills up. Lines of mapped files are
+/// borrowed rather than copied.
+struct Sorter<'a> {
+    sort: &'a Sort,
+    tmpdir: PathBuf,
+    lines: Vec<Cow<'a, [u8]>>,
+    size: usize,
+    /// Runs in input order, along with how many merges produced them.
+    runs: Vec<(usize, TempFile)>,
+}
+
+impl<'a> Sorter<'a> {
+    fn push(&mut self, line: Cow<'a, [u8]>) -> io::Result<()> {
+        if let Cow::Owned(ref line) = line {
+            self.size += line.len();
+        }
+        self.size += std::mem::size_of::<Cow<[u8]>>();
+        self.lines.push(line);
+        if self.size >= MEMORY_LIMIT {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    /// Sorts the lines in memory and writes them to a new run.
+    fn spill(&mut self) -> io::Result<()> {
+        let mut lines = std::mem::take(&mut self.lines);
+        self.size = 0;
+        self.sort.sort_lines(&mut lines);
+        let mut run = TempFile::new_in(&self.tmpdir, "sort")?;
+        {
+            let mut out = BufWriter::new(&mut run);
+            for line in &lines {
+                write_line(&mut out, line, self.sort.eol)?;
+            }
+            out.flush()?;
+        }
+        run.rewind()?;
+        self.runs.push((0, run));
+
+        // Merge runs of the same level as soon as there are enough of
+        // them, so that each line is only rewritten a few times.
+        loop {
+            let level = self.runs.last().map_or(0, |r| r.0);
+            let count = self.runs.iter().rev().take_while(|r| r.0 == level).count();
+            if count < MERGE_ORDER {
+                return Ok(());
+            }
+            let tail = self.runs.split_off(self.runs.len() - count);
+            let mut merged = TempFile::new_in(&self.tmpdir, "sort")?;
+            {
+                let mut out = BufWriter::new(&mut merged);
+                self.sort.merge(readers(tail), &mut out)?;
+                out.flush()?;
+            }
+            merged.rewind()?;
+            self.runs.push((level + 1, merged));
+        }
+    }
+
+    /// Writes all of the lines, in order, to the output.
+    fn finish(mut self, out: &mut dyn Write) -> io::Result<()> {
+        if self.runs.is_empty() {
+            let mut lines = std::mem::take(&mut self.lines);
+            self.sort.sort_lines(&mut lines);
+            for line in &lines {
+                write_line(out, line, self.sort.eol)?;
+            }
+            return Ok(());
+        }
+        if !self.lines.is_empty() {
+            self.spill()?;
+        }
+        let runs = std::mem::take(&mut self.runs);
+        self.sort.merge(readers(runs), out)
+    }
+}
+
+fn readers(runs: Vec<(usize, TempFile)>) -> Vec<Box<dyn BufRead>> {
+    runs.into_iter()
+        .map(|(_, run)| Box::new(BufReader::new(run)) as Box<dyn BufRead>)
+        .collect()
+}
+
+/// An input to be sorted: a regular file, mapped into memory, or anything
+/// else, to be read.
+enum Source {
+    Mapped(Mmap),
+    Read(Box<dyn BufRead>),
+}
+
+/// Returns the lines of a mapped file, without their terminators.
+fn lines(data: &[u8], eol: u8) -> impl Iterator<Item = &[u8]> {
+    data.strip_suffix(&[eol]).unwrap_or(data).split(move |&c| c == eol)
+}
+
+fn open(file: &OsStr) -> Result<Box<dyn BufRead>, String> {
+    match Input::open(file) {
+        Ok(input) => Ok(Box::new(BufReader::new(input))),
+        Err(e) => Err(format!("{}: {}", file.to_string_lossy(), e)),
+    }
+}
+
+/// Sorts (or, if merge is set, merges) the files to the output.
+fn sort_files(sort: &Sort, files: &[OsString], merge: bool, tmpdir: PathBuf,
+              out: &mut dyn Write) -> Result<(), String> {
+    if merge {
+        let sources = files.iter().map(|f| open(f)).collect::<Result<Vec<_>, _>>()?;
+        return sort.merge(sources, out).map_err(|e| e.to_string());
+    }
+    let mut sources = Vec::with_capacity(files.len());
+    for file in files {
+        let input = Input::open(file).map_err(|e| format!("{}: {}", file.to_string_lossy(), e))?;
+        sources.push(match input.map() {
+            Some(map) => Source::Mapped(map),
+            None => Source::Read(Box::new(BufReader::new(input))),
+        });
+    }
+    let mut sorter = Sorter {
+        sort,
+        tmpdir,
+        lines: Vec::new(),
+        size: 0,
+        runs: Vec::new(),
+    };
+    for (file, source) in files.iter().zip(&mut sources) {
+        match source {
+            Source::Mapped(map) => for line in lines(map, sort.eol) {
+                sorter.push(Cow::Borrowed(line)).map_err(|e| e.to_string())?;
+            },
+            Source::Read(reader) => loop {
+                match next_line(reader.as_mut(), sort.eol) {
+                    Ok(Some(line)) => sorter.push(Cow::Owned(line)).map_err(|e| e.to_string())?,
+                    Ok(None) => break,
+                    Err(e) => return Err(format!("{}: {}", file.to_string_lossy(), e)),
+                }
+            },
+        }
+    }
+    sorter.finish(out).map_err(|e| e.to_string())
+}
+
+/// Returns true if the two names refer to the same existing file.
+fn same_file(a: &OsStr, b: &OsStr) -> bool {
+    match (fs::metadata(a), fs::metadata(b)) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+        _ => false,
+    }
+}
+
+/// Rewrites the obsolescent +pos1 [-pos2] key syntax, in which fields and
+/// characters are counted from zero, into the equivalent -k options.
+fn convert_positions(args: Vec<OsString>) -> Vec<OsString> {
+    // Returns what follows the prefix, if the argument is a position.
+    let pos = |arg: &OsStr, prefix: char| {
+        arg.to_str()?.strip_prefix(prefix)
+            .filter(|s| s.starts_with(|c: char| c.is_ascii_digit()))
+            .map(str::to_string)
+    };
+    // Adds one to the field and character numbers of a position.
+    let convert = |pos: &str, is_end: bool| -> String {
+        let n = pos.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(pos.len());
+        let (num, flags) = pos.split_at(n);
+        let (field, ch) = match num.split_once('.') {
+            Some((f, c)) => (f.parse().unwrap_or(0), c.parse().unwrap_or(0)),
+            None => (num.parse().unwrap_or(0), 0),
+        };
+        if is_end {
+            // -F.C ends before character C of field F (from zero), which
+            // is the end of the previous field when C is zero.
+            if ch == 0 {
+                format!("{}{}", field, flags)
+            } else {
+                format!("{}.{}{}", field + 1, ch, flags)
+            }
+        } else {
+            format!("{}.{}{}", field + 1, ch + 1, flags)
+        }
+    };
+
+    let mut result = Vec::new();
+    let mut iter = args.into_iter().peekable();
+    while let Some(arg) = iter.next() {
+        if arg == "--" {
+            result.push(arg);
+            result.extend(iter);
+            break;
+        }
+        if let Some(start) = pos(&arg, '+') {
+            let mut spec = convert(&start, false);
+            if let Some(end) = iter.peek().and_then(|next| pos(next, '-')) {
+                iter.next();
+                spec.push(',');
+                spec.push_str(&convert(&end, true));
+            }
+            result.push(OsString::from(format!("-k{}", spec)));
+        } else {
+            result.push(arg);
+        }
+    }
+    result
+}
+
+pub const SPEC: Spec = Spec::new("sort or merge files", &[
+    Opt::flag('b', "ignore leading blanks in keys"),
+    Opt::flag('c', "check that the input is sorted"),
+    Opt::flag('C', "check that the input is sorted, reporting nothing"),
+    Opt::flag('f', "fold lower case to upper case"),
+    Opt::flag('m', "merge files that are already sorted"),
+    Opt::flag('n', "sort numerically"),
+    Opt::flag('r', "reverse the order"),
+    Opt::flag('u', "print only the first of equal lines"),
+    Opt::flag('z', "end lines with NUL rather than newline"),
+    Opt::arg('o', "output", "write to output, which may be one of the inputs"),
+    Opt::arg('t', "c", "separate fields with the character c"),
+    Opt::arg('k', "key", "sort on the key, such as 2,3n"),
+    Opt::arg('T', "dir", "put temporary files in dir"),
+    Opt::arg('L', "order", "collate text in order: bytes, codepoint, fold or locale"),
+], "[+pos1 [-pos2]] [file ...]");
+
+pub fn run(mut args: Vec<OsString>) -> i32 {
+    let prog = args.remove(0);
+    // POSIX no longer has the +pos syntax, so there such an argument is a
+    // file.
+    let mut args = if mode::posix() { args } else { convert_positions(args) };
+    args.insert(0, prog);
+    let parsed = SPEC.parse(args);
+    let mut order = Order { collation: Collation::from_env(), ..Order::default() };
+    let mut keys: Vec<Key> = Vec::new();
+    let mut sep: Option<u8> = None;
+    let mut unique = false;
+    let mut eol = b'\n';
+    let mut check = false;
+    let mut quiet = false;
+    let mut merge = false;
+    let mut tmpdir = tempfile::temp_dir();
+    let mut output: Option<OsString> = None;
+
+    for (c, arg) in parsed.opts_os() {
+        match (c, arg.unwrap_or_default()) {
+            ('b', _) => order.blanks = true,
+            ('c', _) => check = true,
+            ('C', _) => {
+                check = true;
+                quiet = true;
+            },
+            ('m', _) => merge = true,
+            ('f', _) => order.fold = true,
+            ('n', _) => order.numeric = true,
+            ('r', _) => order.reverse = true,
+            ('u', _) => unique = true,
+            ('z', _) => eol = b'\0',
+            ('o', arg) => output = Some(arg.to_os_string()),
+            ('T', arg) => tmpdir = PathBuf::from(arg),
+            ('L', arg) => match Collation::parse(args::str(arg)) {
+                Some(collation) => order.collation = collation,
+                None => {
+                    err!("unknown collation: {}", arg.to_string_lossy());
+                    process::exit(status::USAGE);
+                }
+            },
+            ('t', arg) => {
+                if arg.len() != 1 {
+                    err!("invalid field separator: {}", arg.to_string_lossy());
+                    process::exit(status::USAGE);
+                }
+                sep = Some(arg.as_encoded_bytes()[0]);
+            },
+            ('k', arg) => match Key::parse(args::str(arg)) {
+                Ok(key) => keys.push(key),
+                Err(e) => {
+                    err!("{}", e);
+                    process::exit(status::USAGE);
+                }
+            },
+            _ => (),
+        }
+    }
+    let mut files = parsed.operands;
+
+    if files.is_empty() {
+        files.push(OsString::from("-"));
+    }
+
+    // Keys without ordering options of their own use the global ones,
+    // and all use the collation.
+    for key in keys.iter_mut() {
+        key.order.collation = order.collation;
+        if !key.has_flags {
+            key.order = order;
+            key.start.blanks |= order.blanks;
+            if let Some(ref mut end) = key.end {
+                end.blanks |= order.blanks;
+            }
+        }
+    }
+    if keys.is_empty() {
+        keys.push(Key::line(order));
+    }
+
+    let sort = Sort { keys, sep, reverse: order.reverse, unique, eol };
+
+    if check {
+        if files.len() > 1 {
+            err!("only one file may be checked");
+            process::exit(status::USAGE);
+        }
+        let sorted = open(&files[0])
+            .and_then(|mut r| sort.check(&files[0], r.as_mut(), quiet)
+                      .map_err(|e| format!("{}: {}", files[0].to_string_lossy(), e)));
+        match sorted {
+            Ok(true) => process::exit(status::TRUE),
+            Ok(false) => process::exit(status::FALSE),
+            Err(e) => {
+                err!("{}", e);
+                process::exit(status::TROUBLE);
+            }
+        }
+    }
+
+    tempfile::remove_on_signal();
+
+    // If the output file is also an input, the output is written to a
+    // temporary file that replaces it once complete.
+    let result = match output {
+        Some(ref name) if files.iter().any(|f| same_file(f, name.as_ref())) => {
+            AtomicFile::create(name)
+                .map_err(|e| format!("{}: {}", name.to_string_lossy(), e))
+                .and_then(|f| {
+                    let mut out = BufWriter::new(f);
+                    sort_files(&sort, &files, merge, tmpdir, &mut out)?;
+                    out.into_inner().map_err(|e| e.into_error())
+                        .and_then(|f| f.commit())
+                        .map_err(|e| format!("{}: {}", name.to_string_lossy(), e))
+                })
+        },
+        Some(ref name) => {
+            File::create(name)
+                .map_err(|e| format!("{}: {}", name.to_string_lossy(), e))
+                .and_then(|f| {
+                    let mut out = BufWriter::new(f);
+                    sort_files(&sort, &files, merge, tmpdir, &mut out)?;
+                    out.flush().map_err(|e| format!("{}: {}", name.to_string_lossy(), e))
+                })
+        },
+        None => {
+            let mut out = BufWriter::new(io::stdout());
+            sort_files(&sort, &files, merge, tmpdir, &mut out)
+                .and_then(|_| out.flush().map_err(|e| e.to_string()))
+        },
+    };
+    if let Err(e) = result {
+        err!("{}", e);
+        process::exit(status::FAILURE);
+    }
+    status::SUCCESS
+}
@@ -0,0 +1,158 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// An implementation of the strings(1) command in Rust. strings is not a
+// Seventh Edition command; it first appeared in 3.0BSD.
+// See https://pubs.opengroup.org/onlinepubs/9699919799/utilities/strings.html
+use std::ffi::OsString;
+use std::io;
+use std::io::{Read, Write};
+use std::process;
+
+use crate::cmd::{Opt, Spec};
+use crate::diag;
+use crate::elf;
+use crate::elf::Elf;
+use crate::err;
+use crate::input::Input;
+use crate::status;
+
+struct Strings {
+    /// The fewest characters a string may have.
+    min: usize,
+    /// How to print each string's offset: 'o', 'd' or 'x', if at all.
+    radix: Option<char>,
+    /// Scan only the initialized data sections of ELF files.
+    data_only: bool,
+}
+
+fn printable(c: u8) -> bool {
+    c.is_ascii_graphic() || c == b' ' || c == b'\t'
+}
+
+impl Strings {
+    /// Writes the strings in some bytes, which are at the given offset in
+    /// the file.
+    fn scan(&self, data: &[u8], offset: u64, out: &mut dyn Write) -> io::Result<()> {
+        let mut i = 0;
+        while i < data.len() {
+            let len = data[i..].iter().take_while(|&&c| printable(c)).count();
+            if len >= self.min {
+                let pos = offset + i as u64;
+                match self.radix {
+                    Some('o') => write!(out, "{:7o} ", pos)?,
+                    Some('d') => write!(out, "{:7} ", pos)?,
+                    Some(_) => write!(out, "{:7x} ", pos)?,
+                    None => (),
+                }
+                out.write_all(&data[i..i + len])?;
+                out.write_all(b"\n")?;
+            }
+            i += len.max(1);
+        }
+        Ok(())
+    }
+
+    /// Writes the strings in a file: in the whole of it or, if data_only
+    /// is set and it is an ELF file, in the sections that hold the
+    /// initial values of its data.
+    fn strings(&self, data: &[u8], out: &mut dyn Write) -> io::Result<()> {
+        if !self.data_only || !elf::is_elf(data) {
+            return self.scan(data, 0, out);
+        }
+        let file = Elf::parse(data)?;
+        for section in &file.sections {
+            let loaded = section.flags & elf::SHF_ALLOC != 0;
+            let code = section.flags & elf::SHF_EXECINSTR != 0;
+            if loaded && !code && section.kind != elf::SHT_NOBITS {
+                self.scan(file.contents(section)?, section.offset, out)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+pub const SPEC: Spec = Spec::new("find printable strings in a file", &[
+    Opt::flag('a', "search the whole file"),
+    Opt::flag('d', "search only the data sections of object files"),
+    Opt::flag('o', "print the offset of each string in octal"),
+    Opt::arg('n', "number", "print strings at least number characters long"),
+    Opt::arg('t', "radix", "print the offset of each string in radix o, d or x"),
+], "[file ...]");
+
+pub fn run(args: Vec<OsString>) -> i32 {
+    let parsed = SPEC.parse(args);
+    let mut strings = Strings { min: 4, radix: None, data_only: false };
+
+    for (c, arg) in parsed.opts() {
+        match (c, arg) {
+            ('a', _) => strings.data_only = false,
+            ('d', _) => strings.data_only = true,
+            ('n', Some(arg)) => match arg.parse() {
+                Ok(n) if n > 0 => strings.min = n,
+                _ => {
+                    err!("invalid minimum string length: {}", arg);
+                    process::exit(status::USAGE);
+                }
+            },
+            ('o', _) => strings.radix = Some('o'),
+            ('t', Some(arg)) => match arg {
+                "o" | "d" | "x" => strings.radix = arg.chars().next(),
+                _ => {
+                    err!("invalid radix: {}", arg);
+                    process::exit(status::USAGE);
+                }
+            },
+            _ => (),
+        }
+    }
+    let mut files = parsed.operands;
+
+    if files.is_empty() {
+        files.push(OsString::from("-"));
+    }
+
+    let stdout = io::stdout();
+    let mut out = io::BufWriter::new(stdout.lock());
+    for file in &files {
+        // A regular file is scanned in memory, and anything else is read.
+        let scanned = Input::open(file).and_then(|mut input| match input.map() {
+            Some(map) => strings.strings(&map, &mut out),
+            None => {
+                let mut data = Vec::new();
+                input.read_to_end(&mut data)?;
+                strings.strings(&data, &mut out)
+            },
+        });
+        if let Err(e) = scanned {
+            let _ = out.flush();
+            err!("{}: {}", file.to_string_lossy(), e);
+        }
+    }
+    if let Err(e) = out.flush() {
+        err!("{}", e);
+    }
+    diag::status()
+}
+
+#[test]
+fn test_strings() {
+    let run = |strings: &Strings, data: &[u8]| {
+        let mut out = Vec::new();
+        strings.strings(data, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    };
+    let mut strings = Strings { min: 4, radix: None, data_only: false };
+    let data = b"\x01abc\x00hello world\x7f\x02tab\there\nxyz";
+    assert_eq!(run(&strings, data), "hello world\ntab\there\n");
+    strings.min = 3;
+    assert_eq!(run(&strings, data), "abc\nhello world\ntab\there\nxyz\n");
+    strings.radix = Some('x');
+    assert_eq!(run(&strings, data), "      1 abc\n      5 hello world\n     12 tab\there\n     1b xyz\n");
+    strings.radix = Some('o');
+    assert_eq!(run(&strings, b"\0\0\0\0\0\0\0\0\0abcd"), "     11 abcd\n");
+    // Files that aren't ELF are scanned whole, even for initialized data.
+    strings.data_only = true;
+    strings.radix = None;
+    assert_eq!(run(&strings, data), "abc\nhello world\ntab\there\nxyz\n");
+}
@@ -0,0 +1,73 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// An implementation of the sum(1) command in Rust.
+// See http://man.cat-v.org/unix-7th/1/sum
+use std::ffi::{OsStr, OsString};
+use std::io;
+use std::io::Write;
+use std::process;
+
+use crate::checksum;
+use crate::cmd::{Opt, Spec};
+use crate::diag;
+use crate::err;
+use crate::input::Input;
+use crate::status;
+
+/// Returns the checksum and size of the named file, or of the standard
+/// input if the name is "-". The BSD checksum is accompanied by the
+/// number of 1024-byte blocks and the System V one by the number of
+/// 512-byte blocks.
+fn sum(name: &OsStr, sysv: bool) -> io::Result<(u16, u64)> {
+    let mut input = Input::open(name)?;
+    if sysv {
+        let mut sum = checksum::SysV::new();
+        io::copy(&mut input, &mut sum)?;
+        Ok((sum.value(), sum.bytes().div_ceil(512)))
+    } else {
+        let mut sum = checksum::Bsd::new();
+        io::copy(&mut input, &mut sum)?;
+        Ok((sum.value(), sum.bytes().div_ceil(1024)))
+    }
+}
+
+pub const SPEC: Spec = Spec::new("sum and count blocks in a file", &[
+    Opt::flag('s', "use the System V algorithm"),
+], "[file ...]");
+
+pub fn run(args: Vec<OsString>) -> i32 {
+    let parsed = SPEC.parse(args);
+    let sysv = parsed.flag('s');
+    let mut files = parsed.operands;
+
+    // The standard input is summed without a name.
+    let stdin = files.is_empty();
+    if stdin {
+        files.push(OsString::from("-"));
+    }
+
+    let mut out = io::stdout().lock();
+    for file in &files {
+        match sum(file, sysv) {
+            Ok((value, blocks)) => {
+                let mut line = if sysv {
+                    format!("{} {}", value, blocks)
+                } else {
+                    format!("{:05} {:5}", value, blocks)
+                }.into_bytes();
+                if !stdin {
+                    line.push(b' ');
+                    line.extend_from_slice(file.as_encoded_bytes());
+                }
+                line.push(b'\n');
+                if let Err(e) = out.write_all(&line) {
+                    err!("{}", e);
+                    process::exit(status::FAILURE);
+                }
+            },
+            Err(e) => err!("{}: {}", file.to_string_lossy(), e),
+        }
+    }
+    diag::status()
+}
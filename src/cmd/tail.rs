@@ -0,0 +1,547 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// An implementation of the tail(1) command in Rust.
+// See http://man.cat-v.org/unix-7th/1/tail
+use std::collections::VecDeque;
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::os::unix::fs::MetadataExt;
+use std::process;
+use std::thread;
+use std::time::Duration;
+
+use crate::cmd;
+use crate::diag;
+use crate::err;
+use crate::input::Input;
+use crate::status;
+
+/// The size of the blocks read when scanning a file backwards.
+const BLOCK_SIZE: usize = 8192;
+
+/// How often followed files are checked for new data.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Unit {
+    Lines,
+    Bytes,
+}
+
+/// Which part of the input to print, and whether to keep printing.
+#[derive(Clone, Copy)]
+struct Spec {
+    /// Count from the start of the input rather than from the end.
+    from_start: bool,
+    count: u64,
+    unit: Unit,
+    reverse: bool,
+    /// Whether a count was given explicitly.
+    explicit: bool,
+    /// Keep reading as the file grows.
+    follow: bool,
+    /// Follow the file by name, reopening it if it is replaced.
+    retry: bool,
+}
+
+/// Parses a count of the form [+|-]number, where a leading + counts from
+/// the start of the input.
+fn parse_count(arg: &str, spec: &mut Spec) -> Result<(), String> {
+    let (from_start, digits) = match arg.strip_prefix('+') {
+        Some(digits) => (true, digits),
+        None => (false, arg.strip_prefix('-').unwrap_or(arg)),
+    };
+    spec.count = digits.parse().map_err(|_| format!("invalid count: {}", arg))?;
+    spec.from_start = from_start;
+    spec.explicit = true;
+    Ok(())
+}
+
+/// Parses the historical [+|-]number[lbc][r][f] option form.
+fn parse_historical(arg: &str, spec: &mut Spec) -> bool {
+    let sign = match arg.chars().next() {
+        Some(c @ ('+' | '-')) => c,
+        _ => return false,
+    };
+    let rest = &arg[1..];
+    let n = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+    if n == 0 {
+        return false;
+    }
+    let mut unit = Unit::Lines;
+    let mut mult = 1;
+    let mut reverse = spec.reverse;
+    let mut follow = spec.follow;
+    for c in rest[n..].chars() {
+        match c {
+            'l' => unit = Unit::Lines,
+            'c' => unit = Unit::Bytes,
+            'b' => {
+                unit = Unit::Bytes;
+                mult = 512;
+            },
+            'r' => reverse = true,
+            'f' => follow = true,
+            _ => return false,
+        }
+    }
+    let count: u64 = match rest[..n].parse() {
+        Ok(count) => count,
+        Err(_) => return false,
+    };
+    *spec = Spec {
+        from_start: sign == '+',
+        count: count * mult,
+        unit,
+        reverse,
+        explicit: true,
+        follow,
+        ..*spec
+    };
+    true
+}
+
+/// Returns the offset of the start of the last n lines of a seekable
+/// input of the given length. A final newline doesn't start a line.
+fn last_lines_offset<R: Read + Seek>(reader: &mut R, len: u64, n: u64) -> io::Result<u64> {
+    if n == 0 {
+        return Ok(len);
+    }
+    let mut buf = vec![0; BLOCK_SIZE];
+    let mut pos = len;
+    let mut count = 0;
+    while pos > 0 {
+        let size = BLOCK_SIZE.min(pos as usize);
+        pos -= size as u64;
+        reader.seek(SeekFrom::Start(pos))?;
+        reader.read_exact(&mut buf[..size])?;
+        for i in (0..size).rev() {
+            if buf[i] == b'\n' && pos + i as u64 + 1 != len {
+                count += 1;
+                if count == n {
+                    return Ok(pos + i as u64 + 1);
+                }
+            }
+        }
+    }
+    Ok(0)
+}
+
+/// Returns the offset just past the first n - 1 lines of the input.
+fn skip_lines<R: Read + Seek>(reader: &mut R, n: u64) -> io::Result<u64> {
+    let mut reader = BufReader::new(reader);
+    let mut offset = 0;
+    let mut line = Vec::new();
+    for _ in 1..n {
+        line.clear();
+        let len = reader.read_until(b'\n', &mut line)?;
+        if len == 0 {
+            break;
+        }
+        offset += len as u64;
+    }
+    Ok(offset)
+}
+
+/// Writes the lines between the two offsets in reverse order, reading
+/// the input backwards so that memory use doesn't depend on its size.
+fn write_reversed<R: Read + Seek>(reader: &mut R, start: u64, end: u64,
+                                  out: &mut dyn Write) -> io::Result<()> {
+    let mut buf = vec![0; BLOCK_SIZE];
+    // The part of the current line found in blocks already read.
+    let mut carry: Vec<u8> = Vec::new();
+    let mut pos = end;
+    while pos > start {
+        let size = BLOCK_SIZE.min((pos - start) as usize);
+        pos -= size as u64;
+        reader.seek(SeekFrom::Start(pos))?;
+        reader.read_exact(&mut buf[..size])?;
+        let mut line_end = size;
+        for i in (0..size).rev() {
+            if buf[i] == b'\n' {
+                if pos + i as u64 + 1 != end {
+                    out.write_all(&buf[i + 1..line_end])?;
+                    out.write_all(&carry)?;
+                    out.write_all(b"\n")?;
+                }
+                carry.clear();
+                line_end = i;
+            }
+        }
+        let mut line = buf[..line_end].to_vec();
+        line.append(&mut carry);
+        carry = line;
+    }
+    if !carry.is_empty() {
+        out.write_all(&carry)?;
+        out.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+/// Prints part of a regular file, seeking rather than reading all of it.
+fn tail_file<R: Read + Seek>(reader: &mut R, spec: &Spec, out: &mut dyn Write)
+                             -> io::Result<()> {
+    let len = reader.seek(SeekFrom::End(0))?;
+    let start = match (spec.from_start, spec.unit) {
+        (true, Unit::Bytes) => spec.count.saturating_sub(1).min(len),
+        (true, Unit::Lines) => {
+            reader.seek(SeekFrom::Start(0))?;
+            skip_lines(reader, spec.count)?
+        },
+        (false, Unit::Bytes) => len.saturating_sub(spec.count),
+        (false, Unit::Lines) => last_lines_offset(reader, len, spec.count)?,
+    };
+    if spec.reverse {
+        write_reversed(reader, start, len, out)
+    } else {
+        reader.seek(SeekFrom::Start(start))?;
+        io::copy(&mut reader.take(len - start), out).map(|_| ())
+    }
+}
+
+/// Prints part of a stream that can't be seeked, such as a pipe. Only as
+/// much of the input as will be printed is held in memory.
+fn tail_stream<R: Read>(reader: R, spec: &Spec, out: &mut dyn Write) -> io::Result<()> {
+    let mut reader = BufReader::new(reader);
+    if spec.unit == Unit::Bytes {
+        if spec.from_start {
+            io::copy(&mut (&mut reader).take(spec.count.saturating_sub(1)), &mut io::sink())?;
+            if !spec.reverse {
+                return io::copy(&mut reader, out).map(|_| ());
+            }
+            let mut data = Vec::new();
+            reader.read_to_end(&mut data)?;
+            return write_lines_reversed(&data, out);
+        }
+        let mut ring: VecDeque<u8> = VecDeque::new();
+        let mut buf = vec![0; BLOCK_SIZE];
+        loop {
+            let n = match reader.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            ring.extend(&buf[..n]);
+            let excess = ring.len().saturating_sub(spec.count as usize);
+            ring.drain(..excess);
+        }
+        let data: Vec<u8> = ring.into();
+        return if spec.reverse {
+            write_lines_reversed(&data, out)
+        } else {
+            out.write_all(&data)
+        };
+    }
+
+    let mut lines: VecDeque<Vec<u8>> = VecDeque::new();
+    let mut skip = if spec.from_start { spec.count.saturating_sub(1) } else { 0 };
+    loop {
+        let mut line = Vec::new();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            break;
+        }
+        if skip > 0 {
+            skip -= 1;
+            continue;
+        }
+        if spec.from_start && !spec.reverse {
+            out.write_all(&line)?;
+            continue;
+        }
+        lines.push_back(line);
+        if !spec.from_start && lines.len() as u64 > spec.count {
+            lines.pop_front();
+        }
+    }
+    if spec.reverse {
+        for line in lines.iter().rev() {
+            out.write_all(line)?;
+            if line.last() != Some(&b'\n') {
+                out.write_all(b"\n")?;
+            }
+        }
+    } else {
+        for line in &lines {
+            out.write_all(line)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_lines_reversed(data: &[u8], out: &mut dyn Write) -> io::Result<()> {
+    write_reversed(&mut io::Cursor::new(data), 0, data.len() as u64, out)
+}
+
+fn tail(mut reader: Input, regular: bool, spec: &Spec, out: &mut dyn Write)
+        -> io::Result<()> {
+    if regular {
+        tail_file(&mut reader, spec, out)
+    } else {
+        tail_stream(reader, spec, out)
+    }
+}
+
+/// A file being followed.
+struct Followed {
+    name: OsString,
+    /// The open file, if any. It is None when a file followed by name
+    /// could not be opened.
+    file: Option<File>,
+    /// The device and inode numbers of the open file.
+    id: (u64, u64),
+    /// How much of the file has been printed.
+    pos: u64,
+    /// Whether the file has been reported as inaccessible.
+    gone: bool,
+}
+
+impl Followed {
+    fn new(name: &OsStr, mut file: File) -> io::Result<Followed> {
+        let meta = file.metadata()?;
+        let pos = file.stream_position()?;
+        Ok(Followed {
+            name: name.to_owned(),
+            file: Some(file),
+            id: (meta.dev(), meta.ino()),
+            pos,
+            gone: false,
+        })
+    }
+
+    fn missing(name: &OsStr) -> Followed {
+        Followed { name: name.to_owned(), file: None, id: (0, 0), pos: 0, gone: true }
+    }
+
+    /// Prints whatever has been written to the file since it was last
+    /// read, preceded by a header if one is given. Returns whether
+    /// anything was printed.
+    fn read_new(&mut self, header: Option<&[u8]>, out: &mut dyn Write)
+                -> io::Result<bool> {
+        let file = match self.file.as_mut() {
+            Some(file) => file,
+            None => return Ok(false),
+        };
+        let len = file.metadata()?.len();
+        if len < self.pos {
+            err!("{}: file truncated", self.name.to_string_lossy());
+            self.pos = 0;
+        }
+        if len == self.pos {
+            return Ok(false);
+        }
+        if let Some(header) = header {
+            out.write_all(header)?;
+        }
+        file.seek(SeekFrom::Start(self.pos))?;
+        self.pos += io::copy(&mut file.take(len - self.pos), out)?;
+        Ok(true)
+    }
+
+    /// Checks whether the name now refers to a different file, as
+    /// happens when a log file is rotated, and if so opens the new file.
+    /// Returns whether the file was reopened.
+    fn reopen(&mut self) -> bool {
+        let meta = match fs::metadata(&self.name) {
+            Ok(meta) => meta,
+            Err(e) => {
+                if !self.gone {
+                    err!("{}: file has become inaccessible: {}", self.name.to_string_lossy(), e);
+                    self.gone = true;
+                }
+                return false;
+            },
+        };
+        if self.file.is_some() && (meta.dev(), meta.ino()) == self.id {
+            return false;
+        }
+        match File::open(&self.name) {
+            Ok(file) => {
+                if self.file.is_some() {
+                    err!("{}: file has been replaced; following new file", self.name.to_string_lossy());
+                } else {
+                    err!("{}: file has appeared; following new file", self.name.to_string_lossy());
+                }
+                self.file = Some(file);
+                self.id = (meta.dev(), meta.ino());
+                self.pos = 0;
+                self.gone = false;
+                true
+            },
+            Err(_) => false,
+        }
+    }
+}
+
+/// Prints data appended to the followed files, for ever. The last index
+/// is that of the file whose output was printed last, if it is followed.
+fn follow(files: &mut [Followed], retry: bool, headers: bool,
+          mut last: Option<usize>, out: &mut dyn Write) -> ! {
+    loop {
+        for (i, f) in files.iter_mut().enumerate() {
+            loop {
+                let header = if headers && last != Some(i) {
+                    Some([b"\n==> ", f.name.as_encoded_bytes(), b" <==\n"].concat())
+                } else {
+                    None
+                };
+                match f.read_new(header.as_deref(), out) {
+                    Ok(true) => last = Some(i),
+                    Ok(false) => (),
+                    Err(e) => err!("{}: {}", f.name.to_string_lossy(), e),
+                }
+                // Any data left in a rotated file has now been printed,
+                // so move on to its replacement.
+                if !retry || !f.reopen() {
+                    break;
+                }
+            }
+        }
+        if let Err(e) = out.flush() {
+            err!("{}", e);
+            process::exit(status::FAILURE);
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+pub const SPEC: cmd::Spec = cmd::Spec::new("deliver the last part of a file", &[
+    cmd::Opt::flag('r', "print the lines in reverse order"),
+    cmd::Opt::flag('f', "keep reading as the file grows"),
+    cmd::Opt::flag('F', "keep reading as the file grows, reopening it if it is replaced"),
+    cmd::Opt::arg('n', "count", "start count lines from the end, or from the start with +"),
+    cmd::Opt::arg('c', "count", "start count bytes from the end, or from the start with +"),
+], "[file ...]");
+
+pub fn run(args: Vec<OsString>) -> i32 {
+    let mut args = args.into_iter();
+    let mut spec = Spec {
+        from_start: false,
+        count: 10,
+        unit: Unit::Lines,
+        reverse: false,
+        explicit: false,
+        follow: false,
+        retry: false,
+    };
+
+    // Pick out the historical forms, which GetOpt can't parse.
+    let mut rest = vec![args.next().unwrap()];
+    let mut options = true;
+    let mut optarg = false;
+    for arg in args {
+        if options && !optarg && arg.to_str().is_some_and(|arg| parse_historical(arg, &mut spec)) {
+            continue;
+        }
+        if optarg {
+            optarg = false;
+        } else if arg == "-n" || arg == "-c" {
+            optarg = true;
+        } else if arg == "--" || !arg.as_encoded_bytes().starts_with(b"-") {
+            options = false;
+        }
+        rest.push(arg);
+    }
+
+    let parsed = SPEC.parse(rest);
+    for (c, arg) in parsed.opts() {
+        let result = match (c, arg) {
+            ('n', Some(arg)) => {
+                spec.unit = Unit::Lines;
+                parse_count(arg, &mut spec)
+            },
+            ('c', Some(arg)) => {
+                spec.unit = Unit::Bytes;
+                parse_count(arg, &mut spec)
+            },
+            ('r', _) => {
+                spec.reverse = true;
+                Ok(())
+            },
+            ('f', _) => {
+                spec.follow = true;
+                Ok(())
+            },
+            ('F', _) => {
+                spec.follow = true;
+                spec.retry = true;
+                Ok(())
+            },
+            _ => Ok(()),
+        };
+        if let Err(e) = result {
+            err!("{}", e);
+            process::exit(status::USAGE);
+        }
+    }
+    let mut files = parsed.operands;
+
+    if spec.reverse && spec.follow {
+        SPEC.usage();
+    }
+
+    // With -r and no count, the whole input is reversed.
+    if spec.reverse && !spec.explicit {
+        spec.from_start = true;
+        spec.count = 1;
+    }
+    if files.is_empty() {
+        files.push(OsString::from("-"));
+    }
+
+    let mut out = BufWriter::new(io::stdout());
+    let mut followed = Vec::new();
+    let mut last = None;
+    for (i, file) in files.iter().enumerate() {
+        let header = |out: &mut dyn Write| -> io::Result<()> {
+            if files.len() > 1 {
+                let sep = if i > 0 { "\n" } else { "" };
+                write!(out, "{}==> ", sep)?;
+                out.write_all(file.as_encoded_bytes())?;
+                writeln!(out, " <==")?;
+            }
+            Ok(())
+        };
+        last = None;
+        // Standard input and anything other than a regular file can't be
+        // followed, and are simply printed.
+        let result = if spec.follow && file != "-" {
+            File::open(file).and_then(|mut f| {
+                header(&mut out)?;
+                if f.metadata()?.is_file() {
+                    tail_file(&mut f, &spec, &mut out)?;
+                    last = Some(followed.len());
+                    followed.push(Followed::new(file, f)?);
+                } else {
+                    tail_stream(f, &spec, &mut out)?;
+                }
+                Ok(())
+            })
+        } else {
+            Input::open(file).and_then(|reader| {
+                let regular = file != "-" && fs::metadata(file)?.is_file();
+                header(&mut out)?;
+                tail(reader, regular, &spec, &mut out)
+            })
+        };
+        if let Err(e) = result {
+            let _ = out.flush();
+            err!("{}: {}", file.to_string_lossy(), e);
+            if spec.retry {
+                followed.push(Followed::missing(file));
+            }
+        }
+    }
+    if !followed.is_empty() {
+        follow(&mut followed, spec.retry, files.len() > 1, last, &mut out);
+    }
+    if let Err(e) = out.flush() {
+        err!("{}", e);
+    }
+    diag::status()
+}
@@ -0,0 +1,638 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// An implementation of the tar(1) command in Rust.
+// See http://man.cat-v.org/unix-7th/1/tar
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::ffi::{CString, OsStr, OsString};
+use std::fs;
+use std::fs::{File, Metadata, OpenOptions, Permissions};
+use std::io;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{FileTypeExt, MetadataExt, OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::process::{Child, Command, Stdio};
+
+use crate::archive::{Header, Kind, Reader, Writer};
+use crate::cmd::Spec;
+use crate::dev::{major, makedev, minor};
+use crate::err;
+use crate::sparse;
+use crate::status;
+use crate::time::Tm;
+use crate::trace;
+use crate::users;
+use crate::walk::Walk;
+
+/// Returns the name with any trailing slashes removed.
+fn trim_slashes(name: &[u8]) -> &[u8] {
+    let end = name.iter().rposition(|&c| c != b'/').map_or(name.len().min(1), |i| i + 1);
+    &name[..end]
+}
+
+/// Returns the mode of a member in the form used by ls -l.
+fn mode_string(header: &Header) -> String {
+    let mut s = String::with_capacity(10);
+    s.push(match header.kind {
+        Kind::Dir => 'd',
+        Kind::Symlink => 'l',
+        Kind::HardLink => 'h',
+        Kind::CharDevice => 'c',
+        Kind::BlockDevice => 'b',
+        Kind::Fifo => 'p',
+        _ => '-',
+    });
+    let mode = header.mode;
+    for (shift, special, set, unset) in [(6, 0o4000, 's', 'S'), (3, 0o2000, 's', 'S'), (0, 0o1000, 't', 'T')] {
+        let bits = (mode >> shift) & 7;
+        s.push(if bits & 4 != 0 { 'r' } else { '-' });
+        s.push(if bits & 2 != 0 { 'w' } else { '-' });
+        s.push(match (bits & 1 != 0, mode & special != 0) {
+            (true, true) => set,
+            (false, true) => unset,
+            (true, false) => 'x',
+            (false, false) => '-',
+        });
+    }
+    s
+}
+
+/// Writes a member's name followed by a newline.
+fn write_name(out: &mut dyn Write, name: &[u8]) -> io::Result<()> {
+    out.write_all(name)?;
+    writeln!(out)
+}
+
+/// Writes a line describing a member in the style of ls -l.
+fn write_long(out: &mut dyn Write, header: &Header) -> io::Result<()> {
+    let user = match header.uname.as_str() {
+        "" => header.uid.to_string(),
+        name => name.to_string(),
+    };
+    let group = match header.gname.as_str() {
+        "" => header.gid.to_string(),
+        name => name.to_string(),
+    };
+    let owner = format!("{}/{}", user, group);
+    let size = match header.kind {
+        Kind::CharDevice | Kind::BlockDevice => format!("{},{}", header.devmajor, header.devminor),
+        _ => header.size.to_string(),
+    };
+    let width = 18usize.saturating_sub(owner.len());
+    let time = Tm::local(header.mtime).format("%Y-%m-%d %H:%M");
+    write!(out, "{} {} {:>width$} {} ", mode_string(header), owner, size, time, width = width)?;
+    out.write_all(&header.name)?;
+    match header.kind {
+        Kind::Symlink => out.write_all(b" -> ")?,
+        Kind::HardLink => out.write_all(b" link to ")?,
+        _ => return writeln!(out),
+    }
+    write_name(out, &header.linkname)
+}
+
+/// Returns the header describing a file, checking that it can be written.
+fn file_header(path: &Path, meta: &Metadata) -> io::Result<Header> {
+    let file_type = meta.file_type();
+    let kind = if file_type.is_dir() {
+        Kind::Dir
+    } else if file_type.is_symlink() {
+        Kind::Symlink
+    } else if file_type.is_char_device() {
+        Kind::CharDevice
+    } else if file_type.is_block_device() {
+        Kind::BlockDevice
+    } else if file_type.is_fifo() {
+        Kind::Fifo
+    } else if file_type.is_socket() {
+        return Err(io::Error::other("socket ignored"));
+    } else {
+        Kind::File
+    };
+    let mut name = path.as_os_str().as_bytes().to_vec();
+    if kind == Kind::Dir && !name.ends_with(b"/") {
+        name.push(b'/');
+    }
+    let mut header = Header::new(&name, kind);
+    header.mode = meta.mode() & 0o7777;
+    header.uid = meta.uid();
+    header.gid = meta.gid();
+    header.mtime = meta.mtime();
+    header.uname = users::user_name(header.uid).unwrap_or_default();
+    header.gname = users::group_name(header.gid).unwrap_or_default();
+    match kind {
+        Kind::File => header.size = meta.len(),
+        Kind::Symlink => header.linkname = fs::read_link(path)?.as_os_str().as_bytes().to_vec(),
+        Kind::CharDevice | Kind::BlockDevice => {
+            header.devmajor = major(meta.rdev());
+            header.devminor = minor(meta.rdev());
+        },
+        _ => (),
+    }
+    header.to_block()?;
+    Ok(header)
+}
+
+fn c_path(path: &Path) -> io::Result<CString> {
+    Ok(CString::new(path.as_os_str().as_bytes())?)
+}
+
+/// Sets the access and modification times of a file, or, if nofollow is
+/// true and the file is a symbolic link, of the link itself.
+fn set_mtime(path: &Path, mtime: i64, nofollow: bool) -> io::Result<()> {
+    let cpath = c_path(path)?;
+    let times = [libc::timespec { tv_sec: mtime, tv_nsec: 0 }; 2];
+    let flags = if nofollow { libc::AT_SYMLINK_NOFOLLOW } else { 0 };
+    // SAFETY: cpath is a valid C string and times points to two
+    // timespecs, as utimensat requires.
+    match unsafe { libc::utimensat(libc::AT_FDCWD, cpath.as_ptr(), times.as_ptr(), flags) } {
+        0 => Ok(()),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+/// Creates a FIFO or device special file.
+fn make_node(path: &Path, header: &Header) -> io::Result<()> {
+    let cpath = c_path(path)?;
+    // SAFETY: cpath is a valid C string.
+    let ret = unsafe {
+        match header.kind {
+            Kind::Fifo => libc::mkfifo(cpath.as_ptr(), header.mode),
+            Kind::CharDevice => libc::mknod(cpath.as_ptr(), libc::S_IFCHR | header.mode,
+                                            makedev(header.devmajor, header.devminor)),
+            _ => libc::mknod(cpath.as_ptr(), libc::S_IFBLK | header.mode,
+                             makedev(header.devmajor, header.devminor)),
+        }
+    };
+    match ret {
+        0 => Ok(()),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+/// Removes whatever, other than a directory, is in the way of extracting
+/// a member.
+fn remove(path: &Path) -> io::Result<()> {
+    match fs::symlink_metadata(path) {
+        Ok(meta) if meta.is_dir() => Ok(()),
+        Ok(_) => trace::op("unlink", format_args!("{:?}", path), fs::remove_file(path)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+struct Tar {
+    verbose: bool,
+    /// Extract members even if they could end up outside the current
+    /// directory.
+    force: bool,
+    /// The files to archive or the members to list or extract.
+    names: Vec<OsString>,
+    /// Whether each of the names has been found in the archive.
+    found: Vec<bool>,
+}
+
+impl Tar {
+    /// Returns whether the named member was asked for: either every
+    /// member was, or it is, or is below, one of the names.
+    fn selected(&mut self, name: &[u8]) -> bool {
+        if self.names.is_empty() {
+            return true;
+        }
+        let name = trim_slashes(name);
+        let mut selected = false;
+        for (operand, found) in self.names.iter().zip(self.found.iter_mut()) {
+            let operand = trim_slashes(operand.as_encoded_bytes());
+            if name == operand || (name.starts_with(operand) && name[operand.len()] == b'/') {
+                *found = true;
+                selected = true;
+            }
+        }
+        selected
+    }
+
+    /// Reports the names that weren't found in the archive. Returns false
+    /// if there were any.
+    fn check_found(&self) -> bool {
+        let mut ok = true;
+        for (name, &found) in self.names.iter().zip(&self.found) {
+            if !found {
+                err!("{}: not found in archive", name.to_string_lossy());
+                ok = false;
+            }
+        }
+        ok
+    }
+
+    /// Writes an archive of the named files, and everything below those
+    /// that are directories, logging their names if verbose. The archive
+    /// itself, identified by its device and inode numbers, is skipped.
+    /// Returns false if any files couldn't be archived.
+    fn create(&self, out: Box<dyn Write>, archive: Option<(u64, u64)>,
+              log: &mut dyn Write) -> io::Result<bool> {
+        let mut writer = Writer::new(out);
+        // The names of files with several links that have already been
+        // archived, by device and inode number.
+        let mut links: HashMap<(u64, u64), Vec<u8>> = HashMap::new();
+        let mut ok = true;
+        for entry in Walk::new(&self.names) {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    log.flush()?;
+                    err!("{}", e);
+                    ok = false;
+                    continue;
+                },
+            };
+            let path = entry.path();
+            let meta = entry.metadata();
+            let id = (meta.dev(), meta.ino());
+            if archive == Some(id) {
+                log.flush()?;
+                err!("{}: file is the archive; not dumped", path.display());
+                continue;
+            }
+            let mut header = match file_header(path, meta) {
+                Ok(header) => header,
+                Err(e) => {
+                    log.flush()?;
+                    err!("{}: {}", path.display(), e);
+                    ok = false;
+                    continue;
+                },
+            };
+            // A link whose target's name is too long for the header is
+            // archived as a copy instead.
+            if header.kind == Kind::File {
+                if let Some(target) = links.get(&id).filter(|target| target.len() <= 100) {
+                    header.kind = Kind::HardLink;
+                    header.linkname = target.clone();
+                    header.size = 0;
+                }
+            }
+            if header.kind == Kind::File {
+                // The file's holes are archived as the NULs they read as,
+                // but without being read.
+                let mut file = match File::open(path).and_then(sparse::Reader::new) {
+                    Ok(file) => file,
+                    Err(e) => {
+                        log.flush()?;
+                        err!("{}: {}", path.display(), e);
+                        ok = false;
+                        continue;
+                    },
+                };
+                writer.append(&header, &mut file)?;
+                if meta.nlink() > 1 {
+                    links.insert(id, header.name.clone());
+                }
+            } else {
+                writer.append(&header, &mut io::empty())?;
+            }
+            if self.verbose {
+                write_name(log, &header.name)?;
+            }
+        }
+        writer.finish()?;
+        Ok(ok)
+    }
+
+    /// Lists the members of the archive, in the style of ls -l if
+    /// verbose. Returns false if any of the names weren't found.
+    fn list(&mut self, input: &mut dyn Read, out: &mut dyn Write) -> io::Result<bool> {
+        let mut reader = Reader::new(input);
+        while let Some(header) = reader.next_header()? {
+            if !self.selected(&header.name) {
+                continue;
+            }
+            if self.verbose {
+                write_long(out, &header)?;
+            } else {
+                write_name(out, &header.name)?;
+            }
+        }
+        out.flush()?;
+        Ok(self.check_found())
+    }
+
+    /// Extracts the members of the archive, logging their names if
+    /// verbose. Returns false if any couldn't be extracted.
+    fn extract(&mut self, input: &mut dyn Read, log: &mut dyn Write) -> io::Result<bool> {
+        let mut reader = Reader::new(input);
+        // The directories extracted, with their modes and modification
+        // times.
+        let mut dirs: Vec<(PathBuf, u32, i64)> = Vec::new();
+        // The symbolic links extracted.
+        let mut symlinks: HashSet<PathBuf> = HashSet::new();
+        let mut ok = true;
+        while let Some(header) = reader.next_header()? {
+            if !self.selected(&header.name) {
+                continue;
+            }
+            let path = PathBuf::from(OsStr::from_bytes(trim_slashes(&header.name)));
+            if !self.force {
+                if let Some(reason) = unsafe_member(&header, &path, &symlinks) {
+                    log.flush()?;
+                    err!("{}: {}", path.display(), reason);
+                    ok = false;
+                    continue;
+                }
+            }
+            if self.verbose {
+                write_name(log, &header.name)?;
+            }
+            symlinks.remove(&path);
+            match extract_member(&header, &path, &mut reader, &mut dirs) {
+                Ok(()) if header.kind == Kind::Symlink => {
+                    symlinks.insert(path);
+                },
+                Ok(()) => (),
+                Err(e) => {
+                    log.flush()?;
+                    err!("{}: {}", path.display(), e);
+                    ok = false;
+                },
+            }
+        }
+        // The directories' modes and times are set last, deepest first, so
+        // that a read-only directory can be filled and extracting its
+        // contents doesn't change its modification time.
+        for (path, mode, mtime) in dirs.iter().rev() {
+            let result = chmod(path, *mode)
+                .and_then(|_| set_mtime(path, *mtime, false));
+            if let Err(e) = result {
+                log.flush()?;
+                err!("{}: {}", path.display(), e);
+                ok = false;
+            }
+        }
+        log.flush()?;
+        Ok(self.check_found() && ok)
+    }
+}
+
+/// Returns whether a name is absolute or has a .. component.
+fn escapes(name: &[u8]) -> bool {
+    name.starts_with(b"/") || name.split(|&c| c == b'/').any(|c| c == b"..")
+}
+
+/// Returns why extracting a member could write outside the current
+/// directory, or None if it couldn't. Either its name, or the name of the
+/// target of a hard link, is absolute or has a .. component, or it is
+/// below a symbolic link extracted earlier.
+fn unsafe_member(header: &Header, path: &Path, symlinks: &HashSet<PathBuf>) -> Option<&'static str> {
+    if header.name.starts_with(b"/") {
+        Some("absolute path name refused")
+    } else if escapes(&header.name) {
+        Some("path name containing .. refused")
+    } else if header.kind == Kind::HardLink && escapes(&header.linkname) {
+        Some("link to a file outside the current directory refused")
+    } else if path.ancestors().skip(1).any(|dir| symlinks.contains(dir)) {
+        Some("path name below a symbolic link refused")
+    } else {
+        None
+    }
+}
+
+/// Extracts a member to the given path. The modes and times of
+/// directories are left to be set later and are added to dirs instead.
+fn extract_member<R: Read>(header: &Header, path: &Path, reader: &mut Reader<R>,
+                           dirs: &mut Vec<(PathBuf, u32, i64)>) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        if !parent.as_os_str().is_empty() {
+            fs::create_dir_all(parent)?;
+        }
+    }
+    remove(path)?;
+    match header.kind {
+        Kind::Dir => {
+            if let Err(e) = trace::op("mkdir", format_args!("{:?}", path), fs::create_dir(path)) {
+                if e.kind() != io::ErrorKind::AlreadyExists || !path.is_dir() {
+                    return Err(e);
+                }
+            }
+            dirs.push((path.to_path_buf(), header.mode, header.mtime));
+            return Ok(());
+        },
+        Kind::Symlink => {
+            let target = OsStr::from_bytes(&header.linkname);
+            trace::op("symlink", format_args!("{:?}, {:?}", target, path),
+                      std::os::unix::fs::symlink(target, path))?;
+            return set_mtime(path, header.mtime, true);
+        },
+        Kind::HardLink => {
+            let target = OsStr::from_bytes(&header.linkname);
+            return trace::op("link", format_args!("{:?}, {:?}", target, path),
+                             fs::hard_link(target, path));
+        },
+        Kind::Fifo | Kind::CharDevice | Kind::BlockDevice => make_node(path, header)?,
+        // Members of unknown types are extracted as regular files. Blocks
+        // of NULs are left as holes, so that sparse files stay sparse.
+        _ => {
+            let file = OpenOptions::new().write(true).create_new(true)
+                .mode(0o600).open(path);
+            let file = trace::op("create", format_args!("{:?}", path), file)?;
+            let mut writer = sparse::Writer::new(file);
+            reader.copy_data(&mut writer)?;
+            writer.finish()?;
+        },
+    }
+    chmod(path, header.mode)?;
+    set_mtime(path, header.mtime, false)
+}
+
+fn chmod(path: &Path, mode: u32) -> io::Result<()> {
+    trace::op("chmod", format_args!("{:?}, {:04o}", path, mode),
+              fs::set_permissions(path, Permissions::from_mode(mode)))
+}
+
+/// Opens the archive for writing, through the compression program if
+/// there is one. Returns the output and the compression process.
+fn open_output(archive: &OsStr, compress: Option<&str>) -> io::Result<(Box<dyn Write>, Option<Child>)> {
+    let target = if archive == "-" { None } else { Some(File::create(archive)?) };
+    match (compress, target) {
+        (None, None) => Ok((Box::new(BufWriter::new(io::stdout())), None)),
+        (None, Some(file)) => Ok((Box::new(BufWriter::new(file)), None)),
+        (Some(program), target) => {
+            let mut child = Command::new(program).arg("-c")
+                .stdin(Stdio::piped())
+                .stdout(target.map_or_else(Stdio::inherit, Stdio::from))
+                .spawn()
+                .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", program, e)))?;
+            let stdin = child.stdin.take().unwrap();
+            Ok((Box::new(BufWriter::new(stdin)), Some(child)))
+        },
+    }
+}
+
+/// Opens the archive for reading, through the compression program if
+/// there is one. Returns the input and the compression process.
+fn open_input(archive: &OsStr, compress: Option<&str>) -> io::Result<(Box<dyn Read>, Option<Child>)> {
+    let source = if archive == "-" { None } else { Some(File::open(archive)?) };
+    match (compress, source) {
+        (None, None) => Ok((Box::new(BufReader::new(io::stdin())), None)),
+        (None, Some(file)) => Ok((Box::new(BufReader::new(file)), None)),
+        (Some(program), source) => {
+            let mut child = Command::new(program).arg("-dc")
+                .stdin(source.map_or_else(Stdio::inherit, Stdio::from))
+                .stdout(Stdio::piped())
+                .spawn()
+                .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", program, e)))?;
+            let stdout = child.stdout.take().unwrap();
+            Ok((Box::new(BufReader::new(stdout)), Some(child)))
+        },
+    }
+}
+
+pub const SPEC: Spec = Spec::new("tape archiver", &[], "[-]{c|t|x}[vfzjP] [archive] [-C dir] [file ...]");
+
+pub fn run(args: Vec<OsString>) -> i32 {
+    let mut args = args.into_iter();
+    let prog = args.next().unwrap();
+    let usage = || -> ! {
+        eprintln!("usage: {} [-]{{c|t|x}}[vfzjP] [archive] [-C dir] [file ...]", prog.to_string_lossy());
+        process::exit(status::USAGE);
+    };
+
+    // The first argument is a key of letters, whose leading - is
+    // optional; the archive named by f comes before the files.
+    let key = args.next().unwrap_or_else(|| usage());
+    let key = key.to_string_lossy();
+    let mut operands: Vec<OsString> = args.collect();
+    let mut function = None;
+    let mut verbose = false;
+    let mut force = false;
+    let mut compress = None;
+    let mut archive: Option<OsString> = None;
+    for c in key.strip_prefix('-').unwrap_or(&key).chars() {
+        match c {
+            'c' | 't' | 'x' if function.is_none() => function = Some(c),
+            'v' => verbose = true,
+            'P' => force = true,
+            'z' => compress = Some("gzip"),
+            'j' => compress = Some("bzip2"),
+            'f' if archive.is_none() && !operands.is_empty() => archive = Some(operands.remove(0)),
+            _ => usage(),
+        }
+    }
+    let function = function.unwrap_or_else(|| usage());
+    let archive = archive.unwrap_or_else(|| OsString::from("-"));
+
+    // The directories to change to, in turn, once the archive is open.
+    let mut dirs: Vec<OsString> = Vec::new();
+    let mut names: Vec<OsString> = Vec::new();
+    let mut operands = operands.into_iter();
+    while let Some(operand) = operands.next() {
+        if operand == "-C" {
+            dirs.push(operands.next().unwrap_or_else(|| usage()));
+        } else {
+            names.push(operand);
+        }
+    }
+    if function == 'c' && names.is_empty() {
+        usage();
+    }
+    let change_dir = || {
+        for dir in &dirs {
+            if let Err(e) = env::set_current_dir(dir) {
+                err!("{}: {}", dir.to_string_lossy(), e);
+                process::exit(status::FAILURE);
+            }
+        }
+    };
+
+    let mut tar = Tar {
+        verbose,
+        force,
+        found: vec![false; names.len()],
+        names,
+    };
+    let (result, child) = if function == 'c' {
+        let (out, child) = open_output(&archive, compress).unwrap_or_else(|e| {
+            err!("{}: {}", archive.to_string_lossy(), e);
+            process::exit(status::FAILURE);
+        });
+        let id = fs::metadata(&archive).ok()
+            .filter(|_| archive != "-")
+            .map(|meta| (meta.dev(), meta.ino()));
+        change_dir();
+        // The names of the files archived go to the standard error if the
+        // archive is going to the standard output.
+        let mut log: Box<dyn Write> = if archive == "-" {
+            Box::new(io::stderr())
+        } else {
+            Box::new(BufWriter::new(io::stdout()))
+        };
+        (tar.create(out, id, &mut log).and_then(|ok| log.flush().map(|_| ok)), child)
+    } else {
+        let (mut input, child) = open_input(&archive, compress).unwrap_or_else(|e| {
+            err!("{}: {}", archive.to_string_lossy(), e);
+            process::exit(status::FAILURE);
+        });
+        change_dir();
+        let mut out = BufWriter::new(io::stdout());
+        let result = if function == 't' {
+            tar.list(&mut input, &mut out)
+        } else {
+            tar.extract(&mut input, &mut out)
+        };
+        // Whatever follows the end of the archive is read, so that the
+        // compression program isn't killed by a broken pipe.
+        let result = result.and_then(|ok| io::copy(&mut input, &mut io::sink()).map(|_| ok));
+        (result, child)
+    };
+    let mut status = match result {
+        Ok(true) => 0,
+        Ok(false) => 1,
+        Err(e) => {
+            err!("{}", e);
+            1
+        }
+    };
+    if let (Some(mut child), Some(program)) = (child, compress) {
+        match child.wait() {
+            Ok(exit) if exit.success() => (),
+            Ok(exit) => {
+                err!("{}: {}", program, exit);
+                status = status::FAILURE;
+            },
+            Err(e) => {
+                err!("{}: {}", program, e);
+                status = status::FAILURE;
+            }
+        }
+    }
+    status
+}
+
+#[test]
+fn test_mode_string() {
+    let mut header = Header::new(b"dir/", Kind::Dir);
+    header.mode = 0o1777;
+    assert_eq!(mode_string(&header), "drwxrwxrwt");
+    header.kind = Kind::File;
+    header.mode = 0o6644;
+    assert_eq!(mode_string(&header), "-rwSr-Sr--");
+}
+
+#[test]
+fn test_unsafe_member() {
+    let mut symlinks = HashSet::new();
+    symlinks.insert(PathBuf::from("dir/link"));
+    let check = |name: &[u8], kind, linkname: &[u8]| {
+        let mut header = Header::new(name, kind);
+        header.linkname = linkname.to_vec();
+        let path = PathBuf::from(OsStr::from_bytes(trim_slashes(name)));
+        unsafe_member(&header, &path, &symlinks).is_some()
+    };
+    assert!(!check(b"dir/file", Kind::File, b""));
+    assert!(!check(b"dir/..file", Kind::File, b""));
+    assert!(!check(b"dir/link", Kind::Symlink, b"/etc"));
+    assert!(check(b"/etc/passwd", Kind::File, b""));
+    assert!(check(b"dir/../../file", Kind::File, b""));
+    assert!(check(b"dir/hard", Kind::HardLink, b"/etc/passwd"));
+    assert!(check(b"dir/link/passwd", Kind::File, b""));
+}
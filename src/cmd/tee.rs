@@ -0,0 +1,83 @@
+// Copyright 2017-2021 James Bostock. See the LICENSE file at the
+// top-level directory of this distribution.
+
+// An implementation of the tee(1) command in Rust.
+// See http://man.cat-v.org/unix-7th/1/tee
+use std::ffi::{OsStr, OsString};
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::{Result, Write};
+
+use crate::cmd::{Opt, Spec};
+use crate::copy;
+use crate::diag;
+use crate::err;
+use crate::trace;
+
+/// A multi-way writer.
+struct Tee {
+    writers: Vec<Box<dyn Write>>
+}
+
+impl Tee {
+    // Create a new Tee
+    fn new() -> Self {
+        Tee { writers: Vec::new() }
+    }
+
+    // Add a writer to a Tee
+    fn push(&mut self, w: Box<dyn Write>) {
+        self.writers.push(w);
+    }
+}
+
+impl Write for Tee {
+    /// Writes the whole buffer to each of the writers.
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        for w in &mut self.writers {
+            w.write_all(buf)?
+        }
+        Ok(buf.len())
+    }
+
+    /// Flushes each writer.
+    fn flush(&mut self) -> Result<()> {
+        for w in &mut self.writers {
+            w.flush()?
+        }
+        Ok(())
+    }
+}
+
+// Opens a file for either writing (i.e. truncating) or appending.
+fn open_helper(path: &OsStr, append: bool) -> io::Result<File> {
+    if append {
+	trace::op("append", format_args!("{:?}", path), OpenOptions::new().append(true).open(path))
+    } else {
+	trace::op("create", format_args!("{:?}", path), File::create(path))
+    }
+}
+
+pub const SPEC: Spec = Spec::new("pipe fitting", &[
+    Opt::flag('a', "append to the files"),
+], "[file ...]");
+
+pub fn run(args: Vec<OsString>) -> i32 {
+    let parsed = SPEC.parse(args);
+    let append = parsed.flag('a');
+    let mut tee: Tee = Tee::new();
+
+    tee.push(Box::new(io::stdout()));
+
+    for arg in parsed.operands {
+	match open_helper(&arg, append) {
+	    Ok(f) => { tee.writers.push(Box::new(f)); },
+	    Err(e) => err!("{}: {}", arg.to_string_lossy(), e),
+	}
+    }
+
+    if let Err(e) = copy::copy_buffered(&mut io::stdin().lock(), &mut tee) {
+        err!("{}", e);
+    }
+    diag::status()
+}
@@ -0,0 +1,239 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// An implementation of the test(1) command in Rust. When invoked as [, the
+// last argument must be ].
+// See http://man.cat-v.org/unix-7th/1/test
+use std::ffi::{CString, OsStr, OsString};
+use std::fs;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{FileTypeExt, MetadataExt, PermissionsExt};
+use std::path::Path;
+use std::process;
+
+use crate::cmd::Spec;
+use crate::err;
+use crate::status;
+
+/// Returns true if the file is accessible with the given mode, using the
+/// effective user and group ids.
+fn accessible(path: &OsStr, mode: libc::c_int) -> bool {
+    let cpath = match CString::new(path.as_bytes()) {
+        Ok(cpath) => cpath,
+        Err(_) => return false,
+    };
+    // SAFETY: cpath is a valid C string.
+    unsafe { libc::faccessat(libc::AT_FDCWD, cpath.as_ptr(), mode, libc::AT_EACCESS) == 0 }
+}
+
+fn is_unary(op: &OsStr) -> bool {
+    matches!(op.to_str(), Some("-b" | "-c" | "-d" | "-e" | "-f" | "-g" | "-h" | "-k" | "-L" | "-n"
+             | "-p" | "-r" | "-s" | "-S" | "-t" | "-u" | "-w" | "-x" | "-z"))
+}
+
+fn is_binary(op: &OsStr) -> bool {
+    matches!(op.to_str(), Some("=" | "!=" | "-eq" | "-ne" | "-lt" | "-le" | "-gt" | "-ge"
+             | "-nt" | "-ot" | "-ef" | "-a" | "-o"))
+}
+
+/// Evaluates a unary primary.
+fn unary(op: &OsStr, arg: &OsStr) -> Result<bool, String> {
+    let meta = || fs::metadata(arg);
+    Ok(match op.to_str().unwrap_or_default() {
+        "-n" => !arg.is_empty(),
+        "-z" => arg.is_empty(),
+        "-e" => meta().is_ok(),
+        "-f" => meta().is_ok_and(|m| m.is_file()),
+        "-d" => meta().is_ok_and(|m| m.is_dir()),
+        "-b" => meta().is_ok_and(|m| m.file_type().is_block_device()),
+        "-c" => meta().is_ok_and(|m| m.file_type().is_char_device()),
+        "-p" => meta().is_ok_and(|m| m.file_type().is_fifo()),
+        "-S" => meta().is_ok_and(|m| m.file_type().is_socket()),
+        "-h" | "-L" => fs::symlink_metadata(arg).is_ok_and(|m| m.file_type().is_symlink()),
+        "-s" => meta().is_ok_and(|m| m.len() > 0),
+        "-u" => meta().is_ok_and(|m| m.permissions().mode() & 0o4000 != 0),
+        "-g" => meta().is_ok_and(|m| m.permissions().mode() & 0o2000 != 0),
+        "-k" => meta().is_ok_and(|m| m.permissions().mode() & 0o1000 != 0),
+        "-r" => accessible(arg, libc::R_OK),
+        "-w" => accessible(arg, libc::W_OK),
+        "-x" => accessible(arg, libc::X_OK),
+        "-t" => {
+            let fd = integer(arg)?;
+            // SAFETY: isatty accepts any file descriptor.
+            i32::try_from(fd).is_ok_and(|fd| unsafe { libc::isatty(fd) } == 1)
+        },
+        _ => return Err(format!("{}: unknown operator", op.to_string_lossy())),
+    })
+}
+
+/// Parses an integer operand, allowing surrounding blanks.
+fn integer(s: &OsStr) -> Result<i64, String> {
+    s.to_str().and_then(|s| s.trim().parse().ok())
+        .ok_or_else(|| format!("{}: integer expression expected", s.to_string_lossy()))
+}
+
+/// Evaluates a binary primary.
+fn binary(left: &OsStr, op: &OsStr, right: &OsStr) -> Result<bool, String> {
+    let modified = |path: &OsStr| fs::metadata(path).and_then(|m| m.modified()).ok();
+    Ok(match op.to_str().unwrap_or_default() {
+        "=" => left == right,
+        "!=" => left != right,
+        "-eq" => integer(left)? == integer(right)?,
+        "-ne" => integer(left)? != integer(right)?,
+        "-lt" => integer(left)? < integer(right)?,
+        "-le" => integer(left)? <= integer(right)?,
+        "-gt" => integer(left)? > integer(right)?,
+        "-ge" => integer(left)? >= integer(right)?,
+        "-nt" => match (modified(left), modified(right)) {
+            (Some(l), Some(r)) => l > r,
+            (l, r) => l.is_some() && r.is_none(),
+        },
+        "-ot" => match (modified(left), modified(right)) {
+            (Some(l), Some(r)) => l < r,
+            (l, r) => l.is_none() && r.is_some(),
+        },
+        "-ef" => match (fs::metadata(left), fs::metadata(right)) {
+            (Ok(l), Ok(r)) => (l.dev(), l.ino()) == (r.dev(), r.ino()),
+            _ => false,
+        },
+        "-a" => !left.is_empty() && !right.is_empty(),
+        "-o" => !left.is_empty() || !right.is_empty(),
+        _ => return Err(format!("{}: unknown operator", op.to_string_lossy())),
+    })
+}
+
+/// A recursive descent parser for expressions of more than four
+/// arguments, where -a binds more tightly than -o.
+struct Parser<'a> {
+    args: &'a [OsString],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&'a OsStr> {
+        self.args.get(self.pos).map(OsString::as_os_str)
+    }
+
+    /// Returns whether the next argument is the given one.
+    fn at(&self, arg: &str) -> bool {
+        self.peek().is_some_and(|next| next == arg)
+    }
+
+    fn next(&mut self) -> Result<&'a OsStr, String> {
+        let arg = self.peek().ok_or("argument expected")?;
+        self.pos += 1;
+        Ok(arg)
+    }
+
+    fn or(&mut self) -> Result<bool, String> {
+        let mut value = self.and()?;
+        while self.at("-o") {
+            self.pos += 1;
+            value = self.and()? || value;
+        }
+        Ok(value)
+    }
+
+    fn and(&mut self) -> Result<bool, String> {
+        let mut value = self.not()?;
+        while self.at("-a") {
+            self.pos += 1;
+            value = self.not()? && value;
+        }
+        Ok(value)
+    }
+
+    fn not(&mut self) -> Result<bool, String> {
+        if self.at("!") {
+            self.pos += 1;
+            return Ok(!self.not()?);
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<bool, String> {
+        let arg = self.next()?;
+        if arg == "(" {
+            let value = self.or()?;
+            return match self.next()? {
+                arg if arg == ")" => Ok(value),
+                arg => Err(format!("{}: ')' expected", arg.to_string_lossy())),
+            };
+        }
+        if is_unary(arg) && self.pos < self.args.len() {
+            let operand = self.next()?;
+            return unary(arg, operand);
+        }
+        match self.peek() {
+            Some(op) if is_binary(op) && op != "-a" && op != "-o" => {
+                self.pos += 1;
+                let right = self.next()?;
+                binary(arg, op, right)
+            },
+            _ => Ok(!arg.is_empty()),
+        }
+    }
+}
+
+/// Evaluates the expression. Expressions of up to four arguments are
+/// evaluated as POSIX specifies, which resolves the ambiguities of, for
+/// example, "test ! = x" and "test -n".
+fn eval(args: &[OsString]) -> Result<bool, String> {
+    let arg = |i: usize| args[i].as_os_str();
+    match args.len() {
+        0 => Ok(false),
+        1 => Ok(!arg(0).is_empty()),
+        2 if arg(0) == "!" => eval(&args[1..]).map(|value| !value),
+        2 if is_unary(arg(0)) => unary(arg(0), arg(1)),
+        2 => Err(format!("{}: unary operator expected", arg(0).to_string_lossy())),
+        3 if is_binary(arg(1)) => binary(arg(0), arg(1), arg(2)),
+        3 if arg(0) == "!" => eval(&args[1..]).map(|value| !value),
+        3 if arg(0) == "(" && arg(2) == ")" => eval(&args[1..2]),
+        4 if arg(0) == "!" => eval(&args[1..]).map(|value| !value),
+        4 if arg(0) == "(" && arg(3) == ")" => eval(&args[1..3]),
+        _ => {
+            let mut parser = Parser { args, pos: 0 };
+            let value = parser.or()?;
+            match parser.peek() {
+                None => Ok(value),
+                Some(arg) => Err(format!("{}: unexpected argument", arg.to_string_lossy())),
+            }
+        },
+    }
+}
+
+pub const SPEC: Spec = Spec::new("condition command", &[], "expression").literal();
+
+pub fn run(mut args: Vec<OsString>) -> i32 {
+    let prog = args.remove(0);
+    let bracket = Path::new(&prog).file_name().is_some_and(|name| name == "[");
+    if bracket && args.pop().is_none_or(|arg| arg != "]") {
+        err!("missing ]");
+        process::exit(status::TROUBLE);
+    }
+    match eval(&args) {
+        Ok(true) => process::exit(status::TRUE),
+        Ok(false) => process::exit(status::FALSE),
+        Err(e) => {
+            err!("{}", e);
+            process::exit(status::TROUBLE);
+        }
+    }
+}
+
+#[test]
+fn test_eval() {
+    let eval = |s: &str| eval(&s.split(' ').filter(|a| !a.is_empty()).map(OsString::from)
+                              .collect::<Vec<_>>());
+    assert_eq!(eval(""), Ok(false));
+    assert_eq!(eval("-n"), Ok(true));
+    assert_eq!(eval("! = x"), Ok(false));
+    assert_eq!(eval("! -z x"), Ok(true));
+    assert_eq!(eval("( -z )"), Ok(true));
+    assert_eq!(eval("1 -lt 2"), Ok(true));
+    assert_eq!(eval("x = y -o 3 -ge 3"), Ok(true));
+    assert_eq!(eval("a -a ! ( b = b ) -o -z x"), Ok(false));
+    assert_eq!(eval("-d / -a -e / -a ! -f /"), Ok(true));
+    assert!(eval("1 -eq x").is_err());
+    assert!(eval("( a = a").is_err());
+}
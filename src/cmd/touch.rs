@@ -0,0 +1,153 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// An implementation of the touch(1) command in Rust.
+// See https://pubs.opengroup.org/onlinepubs/9699919799/utilities/touch.html
+use std::ffi::{CString, OsStr, OsString};
+use std::fs;
+use std::fs::OpenOptions;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::MetadataExt;
+use std::process;
+
+use crate::args;
+use crate::cmd::{Opt, Spec};
+use crate::diag;
+use crate::err;
+use crate::status;
+use crate::time;
+use crate::time::Tm;
+
+/// Parses a time given as [[CC]YY]MMDDhhmm[.ss] in local time, returning
+/// seconds since the epoch. A two-digit year from 69 is in the twentieth
+/// century, and one before 69 in the twenty-first. Without a year, the
+/// current year is used.
+fn parse_time(s: &str) -> Option<i64> {
+    let (digits, sec) = match s.split_once('.') {
+        Some((digits, sec)) if sec.len() == 2 => (digits, sec),
+        Some(_) => return None,
+        None => (s, "00"),
+    };
+    if !digits.bytes().chain(sec.bytes()).all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let field = |i: usize| -> u32 { digits[i..i + 2].parse().unwrap() };
+    let (year, rest) = match digits.len() {
+        8 => (Tm::local(time::now().0).year, 0),
+        10 => {
+            let yy = field(0) as i32;
+            (if yy >= 69 { 1900 + yy } else { 2000 + yy }, 2)
+        },
+        12 => (digits[..4].parse().unwrap(), 4),
+        _ => return None,
+    };
+    let tm = Tm {
+        year,
+        month: field(rest),
+        day: field(rest + 2),
+        hour: field(rest + 4),
+        min: field(rest + 6),
+        sec: sec.parse().unwrap(),
+        wday: 0,
+        yday: 0,
+        utc_offset: 0,
+        zone: String::new(),
+    };
+    tm.local_secs()
+}
+
+/// Sets the access and modification times of the file, creating it first
+/// if it doesn't exist and create is true.
+fn touch(path: &OsStr, times: &[libc::timespec; 2], create: bool) -> io::Result<()> {
+    let cpath = CString::new(path.as_bytes())?;
+    let set = || {
+        // SAFETY: cpath is a valid C string and times points to two
+        // timespecs, as utimensat requires.
+        if unsafe { libc::utimensat(libc::AT_FDCWD, cpath.as_ptr(), times.as_ptr(), 0) } == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    };
+    match set() {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            if !create {
+                return Ok(());
+            }
+            OpenOptions::new().write(true).create(true).truncate(false).open(path)?;
+            set()
+        },
+        result => result,
+    }
+}
+
+pub const SPEC: Spec = Spec::new("update the times of files", &[
+    Opt::flag('a', "change only the access time"),
+    Opt::flag('c', "create no files"),
+    Opt::flag('m', "change only the modification time"),
+    Opt::arg('r', "file", "use the times of file"),
+    Opt::arg('t', "time", "use the time [[CC]YY]MMDDhhmm[.ss]"),
+], "file ...");
+
+pub fn run(args: Vec<OsString>) -> i32 {
+    let parsed = SPEC.parse(args);
+    let create = !parsed.flag('c');
+    let access = parsed.flag('a');
+    let modification = parsed.flag('m');
+    let now = libc::timespec { tv_sec: 0, tv_nsec: libc::UTIME_NOW };
+    let mut times = [now, now];
+
+    for (c, arg) in parsed.opts_os() {
+        match (c, arg) {
+            ('r', Some(arg)) => match fs::metadata(arg) {
+                Ok(meta) => {
+                    times[0] = libc::timespec { tv_sec: meta.atime(), tv_nsec: meta.atime_nsec() };
+                    times[1] = libc::timespec { tv_sec: meta.mtime(), tv_nsec: meta.mtime_nsec() };
+                },
+                Err(e) => {
+                    err!("{}: {}", arg.to_string_lossy(), e);
+                    process::exit(status::FAILURE);
+                }
+            },
+            ('t', Some(arg)) => match parse_time(args::str(arg)) {
+                Some(secs) => times = [libc::timespec { tv_sec: secs, tv_nsec: 0 }; 2],
+                None => {
+                    err!("invalid date format: {}", arg.to_string_lossy());
+                    process::exit(status::USAGE);
+                }
+            },
+            _ => (),
+        }
+    }
+
+    let files = parsed.operands;
+    if files.is_empty() {
+        SPEC.usage();
+    }
+    // -a or -m alone leaves the other time alone.
+    if access != modification {
+        let omit = if access { 1 } else { 0 };
+        times[omit].tv_nsec = libc::UTIME_OMIT;
+    }
+
+    for file in &files {
+        if let Err(e) = touch(file, &times, create) {
+            err!("{}: {}", file.to_string_lossy(), e);
+        }
+    }
+    diag::status()
+}
+
+#[test]
+fn test_parse_time() {
+    let secs = parse_time("202103040506.07").unwrap();
+    let tm = Tm::local(secs);
+    assert_eq!((tm.year, tm.month, tm.day, tm.hour, tm.min, tm.sec), (2021, 3, 4, 5, 6, 7));
+    assert_eq!(Tm::local(parse_time("6903040506").unwrap()).year, 1969);
+    assert_eq!(Tm::local(parse_time("6803040506").unwrap()).year, 2068);
+    assert_eq!(parse_time("02300000"), None);
+    assert_eq!(parse_time("0304050"), None);
+    assert_eq!(parse_time("03040506.7"), None);
+    assert_eq!(parse_time("0304x506"), None);
+}
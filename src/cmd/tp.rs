@@ -0,0 +1,511 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// An implementation of the tp(1) command in Rust.
+// See http://man.cat-v.org/unix-7th/1/tp
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::fs::{File, OpenOptions, Permissions};
+use std::io;
+use std::io::{BufWriter, Read, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{MetadataExt, OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{Duration, UNIX_EPOCH};
+
+use crate::cmd::Spec;
+use crate::err;
+use crate::status;
+use crate::time::Tm;
+use crate::trace;
+use crate::walk::Walk;
+
+const BLOCK: usize = 512;
+
+/// The size of a directory entry: the name, mode, owner, size,
+/// modification time, tape address and checksum of a file.
+const ENTRY: usize = 64;
+
+/// The longest name an entry can hold is one less than this.
+const NAME_LEN: usize = 32;
+
+/// The number of entries in the directory on a DECtape and on a magtape.
+/// The directory follows the boot block and the files follow it.
+const DECTAPE_ENTRIES: usize = 192;
+const MAGTAPE_ENTRIES: usize = 496;
+
+/// The largest size the three bytes of an entry's size can hold.
+const MAX_SIZE: u64 = (1 << 24) - 1;
+
+const S_IFMT: u16 = 0o170000;
+const S_IFREG: u16 = 0o100000;
+
+/// A file on the tape. Only the low bytes of the owner's user and group
+/// ids are kept.
+#[derive(Clone, Debug, PartialEq)]
+struct Entry {
+    name: Vec<u8>,
+    mode: u16,
+    uid: u8,
+    gid: u8,
+    mtime: u32,
+    data: Vec<u8>,
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn word(raw: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([raw[offset], raw[offset + 1]])
+}
+
+/// Returns the sum of the words of a directory entry, which is 0 for a
+/// valid one.
+fn checksum(raw: &[u8]) -> u16 {
+    (0..ENTRY).step_by(2).fold(0u16, |sum, i| sum.wrapping_add(word(raw, i)))
+}
+
+/// Reads the files on a tape with the given number of directory entries.
+/// Entries with bad checksums are an error unless ignore is true, in
+/// which case they are skipped.
+fn read_tape(tape: &[u8], entries: usize, ignore: bool) -> io::Result<Vec<Entry>> {
+    if tape.len() < BLOCK + entries * ENTRY {
+        return Err(invalid("tape too short for its directory".to_string()));
+    }
+    let mut files = Vec::new();
+    for raw in tape[BLOCK..BLOCK + entries * ENTRY].chunks(ENTRY) {
+        if raw[0] == 0 {
+            continue;
+        }
+        if checksum(raw) != 0 {
+            if ignore {
+                continue;
+            }
+            return Err(invalid("directory checksum".to_string()));
+        }
+        let name = raw[..NAME_LEN].split(|&c| c == 0).next().unwrap().to_vec();
+        // The size is three bytes, the highest first, and the time two
+        // words, the high one first, as on the PDP-11.
+        let size = ((raw[37] as usize) << 16) | word(raw, 38) as usize;
+        let mtime = ((word(raw, 40) as u32) << 16) | word(raw, 42) as u32;
+        let start = word(raw, 44) as usize * BLOCK;
+        let data = match tape.get(start..start + size) {
+            Some(data) => data.to_vec(),
+            None if ignore => continue,
+            None => return Err(invalid(format!("{}: beyond the end of the tape",
+                                               String::from_utf8_lossy(&name)))),
+        };
+        files.push(Entry { name, mode: word(raw, 32), uid: raw[34], gid: raw[35], mtime, data });
+    }
+    Ok(files)
+}
+
+/// Writes the files to a tape with the given number of directory entries,
+/// placing them one after another following the directory.
+fn write_tape(files: &[Entry], entries: usize) -> io::Result<Vec<u8>> {
+    if files.len() > entries {
+        return Err(invalid("directory full".to_string()));
+    }
+    let mut tape = vec![0; BLOCK + entries * ENTRY];
+    for (i, file) in files.iter().enumerate() {
+        let tapea = u16::try_from(tape.len() / BLOCK).map_err(|_| invalid("tape full".to_string()))?;
+        let size = file.data.len();
+        let raw = &mut tape[BLOCK + i * ENTRY..BLOCK + (i + 1) * ENTRY];
+        raw[..file.name.len()].copy_from_slice(&file.name);
+        raw[32..34].copy_from_slice(&file.mode.to_le_bytes());
+        raw[34] = file.uid;
+        raw[35] = file.gid;
+        raw[37] = (size >> 16) as u8;
+        raw[38..40].copy_from_slice(&(size as u16).to_le_bytes());
+        raw[40..42].copy_from_slice(&((file.mtime >> 16) as u16).to_le_bytes());
+        raw[42..44].copy_from_slice(&(file.mtime as u16).to_le_bytes());
+        raw[44..46].copy_from_slice(&tapea.to_le_bytes());
+        let sum = checksum(raw);
+        raw[62..64].copy_from_slice(&sum.wrapping_neg().to_le_bytes());
+        tape.extend(&file.data);
+        tape.resize(tape.len().div_ceil(BLOCK) * BLOCK, 0);
+    }
+    Ok(tape)
+}
+
+/// Returns the mode of a file in the form used by ls -l.
+fn mode_string(mode: u16) -> String {
+    let mut s = String::with_capacity(10);
+    s.push(if mode & S_IFMT == 0o040000 { 'd' } else { '-' });
+    for (shift, special, set) in [(6, 0o4000, 's'), (3, 0o2000, 's'), (0, 0o1000, 't')] {
+        let bits = (mode >> shift) & 7;
+        s.push(if bits & 4 != 0 { 'r' } else { '-' });
+        s.push(if bits & 2 != 0 { 'w' } else { '-' });
+        s.push(match (bits & 1 != 0, mode & special != 0) {
+            (true, true) => set,
+            (false, true) => set.to_ascii_uppercase(),
+            (true, false) => 'x',
+            (false, false) => '-',
+        });
+    }
+    s
+}
+
+/// Returns whether a name is absolute or has a .. component.
+fn escapes(name: &[u8]) -> bool {
+    name.starts_with(b"/") || name.split(|&c| c == b'/').any(|c| c == b"..")
+}
+
+/// Returns the entry for a file to be written to the tape.
+fn file_entry(path: &Path, meta: &fs::Metadata) -> io::Result<Entry> {
+    let name = path.as_os_str().as_bytes().to_vec();
+    if name.len() >= NAME_LEN {
+        return Err(io::Error::other("name too long"));
+    }
+    if meta.len() > MAX_SIZE {
+        return Err(io::Error::other("file too large"));
+    }
+    Ok(Entry {
+        name,
+        mode: S_IFREG | (meta.mode() & 0o7777) as u16,
+        uid: meta.uid() as u8,
+        gid: meta.gid() as u8,
+        mtime: meta.mtime() as u32,
+        data: fs::read(path)?,
+    })
+}
+
+/// Extracts a file, replacing whatever is at its path. Its owner isn't
+/// restored: the ids on the tape are those of another system.
+fn extract_file(entry: &Entry, path: &Path) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        if !parent.as_os_str().is_empty() {
+            fs::create_dir_all(parent)?;
+        }
+    }
+    match fs::symlink_metadata(path) {
+        Ok(meta) if !meta.is_dir() => {
+            trace::op("unlink", format_args!("{:?}", path), fs::remove_file(path))?
+        },
+        _ => (),
+    }
+    let file = OpenOptions::new().write(true).create_new(true).mode(0o600).open(path);
+    let mut file = trace::op("create", format_args!("{:?}", path), file)?;
+    file.write_all(&entry.data)?;
+    let mode = (entry.mode & 0o7777) as u32;
+    trace::op("chmod", format_args!("{:?}, {:04o}", path, mode),
+              file.set_permissions(Permissions::from_mode(mode)))?;
+    file.set_modified(UNIX_EPOCH + Duration::from_secs(entry.mtime as u64))
+}
+
+struct Tp {
+    verbose: bool,
+    /// Extract files even if they would end up outside the current
+    /// directory.
+    force: bool,
+    names: Vec<OsString>,
+    /// Which of the names have matched a file on the tape.
+    found: Vec<bool>,
+}
+
+impl Tp {
+    /// Returns whether a file on the tape is one of those named, or is
+    /// below a named directory, noting the name as found. Every file is
+    /// selected if there are no names.
+    fn selected(&mut self, name: &[u8]) -> bool {
+        if self.names.is_empty() {
+            return true;
+        }
+        let mut selected = false;
+        for (i, n) in self.names.iter().enumerate() {
+            let n = n.as_bytes();
+            let n = &n[..n.iter().rposition(|&c| c != b'/').map_or(0, |i| i + 1)];
+            if name == n || (name.starts_with(n) && name.get(n.len()) == Some(&b'/')) {
+                self.found[i] = true;
+                selected = true;
+            }
+        }
+        selected
+    }
+
+    /// Reports the names that didn't match any file, returning false if
+    /// there were any.
+    fn check_found(&self) -> bool {
+        let mut ok = true;
+        for (name, &found) in self.names.iter().zip(&self.found) {
+            if !found {
+                err!("{}: not found", name.to_string_lossy());
+                ok = false;
+            }
+        }
+        ok
+    }
+
+    /// Lists the files on the tape, in the style of ls -l if verbose.
+    fn list(&mut self, files: &[Entry], out: &mut dyn Write) -> io::Result<bool> {
+        let mut blocks = 0;
+        for file in files {
+            blocks += file.data.len().div_ceil(BLOCK);
+            if !self.selected(&file.name) {
+                continue;
+            }
+            if self.verbose {
+                let time = Tm::local(file.mtime as i64).format("%Y-%m-%d %H:%M");
+                write!(out, "{} {:>3}/{:<3} {:>8} {} ", mode_string(file.mode), file.uid, file.gid,
+                       file.data.len(), time)?;
+            }
+            out.write_all(&file.name)?;
+            writeln!(out)?;
+        }
+        if self.verbose {
+            writeln!(out, "{:4} entries\n{:4} used", files.len(), blocks)?;
+        }
+        out.flush()?;
+        Ok(self.check_found())
+    }
+
+    /// Extracts the files on the tape, logging their names if verbose.
+    fn extract(&mut self, files: &[Entry], log: &mut dyn Write) -> io::Result<bool> {
+        let mut ok = true;
+        for file in files {
+            if !self.selected(&file.name) {
+                continue;
+            }
+            let path = PathBuf::from(OsStr::from_bytes(&file.name));
+            if !self.force && escapes(&file.name) {
+                log.flush()?;
+                err!("{}: path name outside the current directory refused",
+                     path.display());
+                ok = false;
+                continue;
+            }
+            if self.verbose {
+                write!(log, "x ")?;
+                log.write_all(&file.name)?;
+                writeln!(log)?;
+            }
+            if let Err(e) = extract_file(file, &path) {
+                log.flush()?;
+                err!("{}: {}", path.display(), e);
+                ok = false;
+            }
+        }
+        log.flush()?;
+        Ok(self.check_found() && ok)
+    }
+
+    /// Deletes the named files from the tape.
+    fn delete(&mut self, files: &mut Vec<Entry>, log: &mut dyn Write) -> io::Result<bool> {
+        let mut kept = Vec::new();
+        for file in files.drain(..) {
+            if !self.selected(&file.name) {
+                kept.push(file);
+            } else if self.verbose {
+                write!(log, "d ")?;
+                log.write_all(&file.name)?;
+                writeln!(log)?;
+            }
+        }
+        *files = kept;
+        Ok(self.check_found())
+    }
+
+    /// Writes the named files, and those below named directories, to the
+    /// tape, replacing files already there with the same names. If update
+    /// is true, a file is only replaced if it has been modified since.
+    fn replace(&mut self, files: &mut Vec<Entry>, update: bool, archive: Option<(u64, u64)>,
+               log: &mut dyn Write) -> io::Result<bool> {
+        let mut ok = true;
+        for entry in Walk::new(&self.names) {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    log.flush()?;
+                    err!("{}", e);
+                    ok = false;
+                    continue;
+                },
+            };
+            let (path, meta) = (entry.path(), entry.metadata());
+            if meta.is_dir() {
+                continue;
+            }
+            if !meta.is_file() {
+                log.flush()?;
+                err!("{}: not a regular file; not dumped", path.display());
+                continue;
+            }
+            if archive == Some((meta.dev(), meta.ino())) {
+                log.flush()?;
+                err!("{}: file is the archive; not dumped", path.display());
+                continue;
+            }
+            let name = path.as_os_str().as_bytes();
+            let old = files.iter().position(|file| file.name == name);
+            if update && old.is_some_and(|i| files[i].mtime as i64 >= meta.mtime()) {
+                continue;
+            }
+            let file = match file_entry(path, meta) {
+                Ok(file) => file,
+                Err(e) => {
+                    log.flush()?;
+                    err!("{}: {}", path.display(), e);
+                    ok = false;
+                    continue;
+                },
+            };
+            if self.verbose {
+                write!(log, "{} ", if old.is_some() { 'r' } else { 'a' })?;
+                log.write_all(name)?;
+                writeln!(log)?;
+            }
+            match old {
+                Some(i) => files[i] = file,
+                None => files.push(file),
+            }
+        }
+        Ok(ok)
+    }
+}
+
+pub const SPEC: Spec = Spec::new("manipulate tape archive", &[], "[-]{r|u|d|x|t}[mvcifP0-7] [archive] [name ...]");
+
+pub fn run(args: Vec<OsString>) -> i32 {
+    let mut args = args.into_iter();
+    let prog = args.next().unwrap();
+    let usage = || -> ! {
+        eprintln!("usage: {} [-]{{r|u|d|x|t}}[mvcifP0-7] [archive] [name ...]", prog.to_string_lossy());
+        process::exit(status::USAGE);
+    };
+
+    // The first argument is a key of letters, as for tar; the archive
+    // named by f comes before the names.
+    let key = args.next().unwrap_or_else(|| usage());
+    let key = key.to_string_lossy();
+    let mut names: Vec<OsString> = args.collect();
+    let mut function = None;
+    let mut magtape = false;
+    let mut drive = '0';
+    let mut verbose = false;
+    let mut fresh = false;
+    let mut ignore = false;
+    let mut force = false;
+    let mut archive = None;
+    for c in key.strip_prefix('-').unwrap_or(&key).chars() {
+        match c {
+            'r' | 'u' | 'd' | 'x' | 't' if function.is_none() => function = Some(c),
+            'm' => magtape = true,
+            '0'..='7' => drive = c,
+            'v' => verbose = true,
+            'c' => fresh = true,
+            'i' => ignore = true,
+            'P' => force = true,
+            'f' if archive.is_none() && !names.is_empty() => archive = Some(names.remove(0)),
+            _ => usage(),
+        }
+    }
+    let function = function.unwrap_or_else(|| usage());
+    let archive = archive.unwrap_or_else(|| {
+        OsString::from(format!("/dev/{}{}", if magtape { "mt" } else { "tap" }, drive))
+    });
+    let entries = if magtape { MAGTAPE_ENTRIES } else { DECTAPE_ENTRIES };
+    let writes = matches!(function, 'r' | 'u' | 'd');
+    if writes && names.is_empty() && !(function == 'r' && fresh) {
+        usage();
+    }
+
+    // A fresh tape, or a new archive file, starts with no files.
+    let mut tape = Vec::new();
+    let result = if writes && fresh {
+        Ok(())
+    } else if archive == "-" {
+        io::stdin().read_to_end(&mut tape).map(|_| ())
+    } else {
+        match File::open(&archive).and_then(|mut f| f.read_to_end(&mut tape)) {
+            Err(e) if writes && e.kind() == io::ErrorKind::NotFound => Ok(()),
+            result => result.map(|_| ()),
+        }
+    };
+    let files = result.and_then(|_| {
+        if tape.is_empty() && writes { Ok(Vec::new()) } else { read_tape(&tape, entries, ignore) }
+    });
+    let mut files = files.unwrap_or_else(|e| {
+        err!("{}: {}", archive.to_string_lossy(), e);
+        process::exit(status::FAILURE);
+    });
+
+    let mut tp = Tp {
+        verbose,
+        force,
+        found: vec![false; names.len()],
+        names,
+    };
+    // The names of the files go to the standard error if the archive is
+    // going to the standard output.
+    let mut log: Box<dyn Write> = if writes && archive == "-" {
+        Box::new(io::stderr())
+    } else {
+        Box::new(BufWriter::new(io::stdout()))
+    };
+    let result = match function {
+        't' => tp.list(&files, &mut log),
+        'x' => tp.extract(&files, &mut log),
+        'd' => tp.delete(&mut files, &mut log),
+        _ => {
+            let id = fs::metadata(&archive).ok()
+                .filter(|_| archive != "-")
+                .map(|meta| (meta.dev(), meta.ino()));
+            tp.replace(&mut files, function == 'u', id, &mut log)
+        },
+    };
+    let result = result.and_then(|ok| {
+        log.flush()?;
+        if writes {
+            let tape = write_tape(&files, entries)?;
+            if archive == "-" {
+                io::stdout().write_all(&tape)?;
+                io::stdout().flush()?;
+            } else {
+                OpenOptions::new().write(true).create(true).truncate(true).open(&archive)?
+                    .write_all(&tape)?;
+            }
+        }
+        Ok(ok)
+    });
+    match result {
+        Ok(true) => (),
+        Ok(false) => process::exit(status::FAILURE),
+        Err(e) => {
+            err!("{}: {}", archive.to_string_lossy(), e);
+            process::exit(status::FAILURE);
+        }
+    }
+    status::SUCCESS
+}
+
+#[test]
+fn test_tape() {
+    let file = |name: &[u8], data: &[u8]| Entry {
+        name: name.to_vec(),
+        mode: S_IFREG | 0o644,
+        uid: 3,
+        gid: 1,
+        mtime: 0x12345678,
+        data: data.to_vec(),
+    };
+    let files = vec![file(b"usr/src/a.c", b"main(){}\n"), file(b"empty", b""), file(b"big", &[7; 600])];
+    let tape = write_tape(&files, DECTAPE_ENTRIES).unwrap();
+    // The boot block, 24 blocks of directory, then 1, 0 and 2 blocks of
+    // data.
+    assert_eq!(tape.len(), (1 + 24 + 3) * BLOCK);
+    let raw = &tape[BLOCK..BLOCK + ENTRY];
+    assert_eq!(checksum(raw), 0);
+    assert_eq!(&raw[40..46], &[0x34, 0x12, 0x78, 0x56, 25, 0]);
+    assert_eq!(word(&tape[BLOCK + 2 * ENTRY..], 44), 26);
+    assert_eq!(read_tape(&tape, DECTAPE_ENTRIES, false).unwrap(), files);
+    let mut bad = tape.clone();
+    bad[BLOCK + 33] ^= 1;
+    assert!(read_tape(&bad, DECTAPE_ENTRIES, false).is_err());
+    assert_eq!(read_tape(&bad, DECTAPE_ENTRIES, true).unwrap(), files[1..]);
+    let tape = write_tape(&files, MAGTAPE_ENTRIES).unwrap();
+    assert_eq!(word(&tape[BLOCK..], 44), 63);
+    assert!(write_tape(&vec![file(b"x", b""); 193], DECTAPE_ENTRIES).is_err());
+    assert_eq!(mode_string(S_IFREG | 0o4755), "-rwsr-xr-x");
+}
@@ -0,0 +1,383 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// An implementation of the tr(1) command in Rust.
+// See http://man.cat-v.org/unix-7th/1/tr
+use std::ffi::OsString;
+use std::io;
+use std::io::{Read, Write};
+use std::process;
+
+use crate::cmd::{Opt, Spec};
+use crate::err;
+use crate::regex;
+use crate::sandbox;
+use crate::status;
+
+/// An element of a set, as given on the command line.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Elem {
+    Byte(u8),
+    /// A byte repeated the given number of times ([x*n]).
+    Repeat(u8, usize),
+    /// A byte repeated as often as is needed to make the second set as
+    /// long as the first ([x*] or [x*0]).
+    Fill(u8),
+}
+
+struct Parser<'a> {
+    set: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(set: &'a str) -> Parser<'a> {
+        Parser { set: set.as_bytes(), pos: 0 }
+    }
+
+    /// Reads a character, which may be an escape sequence: a backslash
+    /// followed by one to three octal digits, by one of the letters
+    /// abfnrtv, or by any other character, which stands for itself.
+    fn char(&mut self) -> u8 {
+        let c = self.set[self.pos];
+        self.pos += 1;
+        if c != b'\\' || self.pos == self.set.len() {
+            return c;
+        }
+        let c = self.set[self.pos];
+        self.pos += 1;
+        match c {
+            b'0'..=b'7' => {
+                let mut n = (c - b'0') as u32;
+                for _ in 0..2 {
+                    match self.set.get(self.pos) {
+                        Some(&d @ b'0'..=b'7') if n * 8 + ((d - b'0') as u32) < 256 => {
+                            n = n * 8 + (d - b'0') as u32;
+                            self.pos += 1;
+                        },
+                        _ => break,
+                    }
+                }
+                n as u8
+            },
+            b'a' => 7,
+            b'b' => 8,
+            b'f' => 12,
+            b'n' => b'\n',
+            b'r' => b'\r',
+            b't' => b'\t',
+            b'v' => 11,
+            _ => c,
+        }
+    }
+
+    /// Tries to read a repetition, [x*n]. The count is octal if it begins
+    /// with 0, and decimal otherwise. Returns None, without consuming
+    /// anything, if what follows is not a repetition.
+    fn repeat(&mut self) -> Option<Result<Elem, String>> {
+        let start = self.pos;
+        self.pos += 1;
+        if self.pos >= self.set.len() {
+            self.pos = start;
+            return None;
+        }
+        let c = self.char();
+        if self.set.get(self.pos) != Some(&b'*') {
+            self.pos = start;
+            return None;
+        }
+        let digits = &self.set[self.pos + 1..];
+        let end = match digits.iter().position(|&d| d == b']') {
+            Some(end) if digits[..end].iter().all(u8::is_ascii_digit) => end,
+            _ => {
+                self.pos = start;
+                return None;
+            }
+        };
+        let count = String::from_utf8_lossy(&digits[..end]).into_owned();
+        self.pos += end + 2;
+        let radix = if count.starts_with('0') { 8 } else { 10 };
+        Some(match usize::from_str_radix(&count, radix) {
+            _ if count.is_empty() => Ok(Elem::Fill(c)),
+            Ok(0) => Ok(Elem::Fill(c)),
+            Ok(n) => Ok(Elem::Repeat(c, n)),
+            Err(_) => Err(format!("invalid repeat count '{}' in [{}*]", count, c as char)),
+        })
+    }
+
+    /// Tries to read a character class, [:name:], or an equivalence
+    /// class, [=c=], returning its members in ascending order. In the
+    /// POSIX locale, an equivalence class contains only the character
+    /// itself. Returns None, without consuming anything, if what follows
+    /// is neither.
+    fn class(&mut self) -> Option<Result<Vec<u8>, String>> {
+        let rest = &self.set[self.pos..];
+        if rest.starts_with(b"[:") {
+            let end = rest[2..].windows(2).position(|w| w == b":]")?;
+            let name = String::from_utf8_lossy(&rest[2..2 + end]).into_owned();
+            self.pos += end + 4;
+            if regex::in_class(&name, 0).is_none() {
+                return Some(Err(format!("invalid character class '{}'", name)));
+            }
+            return Some(Ok((0..=255u8).filter(|&c| regex::in_class(&name, c) == Some(true))
+                           .collect()));
+        }
+        if rest.starts_with(b"[=") {
+            let start = self.pos;
+            self.pos += 2;
+            if self.pos < self.set.len() {
+                let c = self.char();
+                if self.set[self.pos..].starts_with(b"=]") {
+                    self.pos += 2;
+                    return Some(Ok(vec![c]));
+                }
+            }
+            self.pos = start;
+        }
+        None
+    }
+
+    /// Parses the whole set.
+    fn parse(&mut self) -> Result<Vec<Elem>, String> {
+        let mut elems = Vec::new();
+        while self.pos < self.set.len() {
+            if self.set[self.pos] == b'[' {
+                if let Some(members) = self.class() {
+                    elems.extend(members?.into_iter().map(Elem::Byte));
+                    continue;
+                }
+                if let Some(elem) = self.repeat() {
+                    elems.push(elem?);
+                    continue;
+                }
+            }
+            let c = self.char();
+            if self.set.get(self.pos) == Some(&b'-') && self.pos + 1 < self.set.len() {
+                self.pos += 1;
+                let end = self.char();
+                if end < c {
+                    return Err(format!("range {}-{} is in reverse order",
+                                       c as char, end as char));
+                }
+                elems.extend((c..=end).map(Elem::Byte));
+            } else {
+                elems.push(Elem::Byte(c));
+            }
+        }
+        Ok(elems)
+    }
+}
+
+/// Parses the first set, in which repetitions are not allowed.
+fn parse_set1(set: &str) -> Result<Vec<u8>, String> {
+    Parser::new(set).parse()?.into_iter().map(|elem| match elem {
+        Elem::Byte(c) => Ok(c),
+        _ => Err("repetitions are only allowed in the second set".to_string()),
+    }).collect()
+}
+
+/// Parses the second set, expanding it to the length of the first. The
+/// first [x*] fills out the set; if there is none, and the set is still
+/// too short, its last character is repeated, so that every byte in the
+/// first set is translated. A longer second set is truncated.
+fn parse_set2(set: &str, len: usize) -> Result<Vec<u8>, String> {
+    let elems = Parser::new(set).parse()?;
+    let fixed: usize = elems.iter().map(|elem| match elem {
+        Elem::Byte(_) => 1,
+        Elem::Repeat(_, n) => *n,
+        Elem::Fill(_) => 0,
+    }).fold(0, usize::saturating_add);
+    let mut fill = len.saturating_sub(fixed);
+    let mut bytes = Vec::with_capacity(len);
+    for elem in elems {
+        let (c, n) = match elem {
+            Elem::Byte(c) => (c, 1),
+            Elem::Repeat(c, n) => (c, n),
+            Elem::Fill(c) => (c, std::mem::take(&mut fill)),
+        };
+        let n = n.min(len.saturating_sub(bytes.len()));
+        bytes.extend(std::iter::repeat_n(c, n));
+    }
+    if let Some(&last) = bytes.last() {
+        bytes.resize(len, last);
+    }
+    Ok(bytes)
+}
+
+/// Parses a set that is only used to test membership, as when deleting
+/// or squeezing, so that repetitions need only be included once.
+fn parse_members(set: &str) -> Result<[bool; 256], String> {
+    let mut members = [false; 256];
+    for elem in Parser::new(set).parse()? {
+        let (Elem::Byte(c) | Elem::Repeat(c, _) | Elem::Fill(c)) = elem;
+        members[c as usize] = true;
+    }
+    Ok(members)
+}
+
+/// Returns the bytes not in the set, in ascending order.
+fn complement(set: &[u8]) -> Vec<u8> {
+    (0..=255u8).filter(|c| !set.contains(c)).collect()
+}
+
+/// Returns the set as a membership table.
+fn members(set: &[u8]) -> [bool; 256] {
+    let mut members = [false; 256];
+    for &c in set {
+        members[c as usize] = true;
+    }
+    members
+}
+
+struct Tr {
+    /// Maps each input byte to its output byte.
+    table: [u8; 256],
+    /// The input bytes to delete.
+    delete: [bool; 256],
+    /// The output bytes of which a run is squeezed to one.
+    squeeze: [bool; 256],
+}
+
+impl Tr {
+    fn new() -> Tr {
+        let mut table = [0u8; 256];
+        for (i, entry) in table.iter_mut().enumerate() {
+            *entry = i as u8;
+        }
+        Tr { table, delete: [false; 256], squeeze: [false; 256] }
+    }
+
+    /// Maps each byte in the first set to the corresponding byte in the
+    /// second. If a byte occurs more than once in the first set, the
+    /// last occurrence wins.
+    fn translate(&mut self, set1: &[u8], set2: &[u8]) {
+        for (&from, &to) in set1.iter().zip(set2) {
+            self.table[from as usize] = to;
+        }
+    }
+
+    /// Copies the input to the output, deleting, translating and
+    /// squeezing.
+    fn run(&self, input: &mut dyn Read, out: &mut dyn Write) -> io::Result<()> {
+        let simple = !self.delete.contains(&true) && !self.squeeze.contains(&true);
+        let mut buf = vec![0u8; 64 * 1024];
+        let mut obuf = Vec::with_capacity(buf.len());
+        let mut last: Option<u8> = None;
+        loop {
+            let n = match input.read(&mut buf) {
+                Ok(0) => return Ok(()),
+                Ok(n) => n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            if simple {
+                for c in &mut buf[..n] {
+                    *c = self.table[*c as usize];
+                }
+                out.write_all(&buf[..n])?;
+                continue;
+            }
+            obuf.clear();
+            for &c in &buf[..n] {
+                if self.delete[c as usize] {
+                    continue;
+                }
+                let c = self.table[c as usize];
+                if self.squeeze[c as usize] && last == Some(c) {
+                    continue;
+                }
+                obuf.push(c);
+                last = Some(c);
+            }
+            out.write_all(&obuf)?;
+        }
+    }
+}
+
+pub const SPEC: Spec = Spec::new("translate characters", &[
+    Opt::flag('c', "complement the characters of string1"),
+    Opt::flag('d', "delete the characters of string1"),
+    Opt::flag('s', "squeeze runs of the characters of string2 to one"),
+], "string1 [string2]");
+
+pub fn run(args: Vec<OsString>) -> i32 {
+    let parsed = SPEC.parse(args);
+    let complemented = parsed.flag('c');
+    let delete = parsed.flag('d');
+    let squeeze = parsed.flag('s');
+    let sets = parsed.strings();
+
+    // Deleting without squeezing, and squeezing without translating, take
+    // one set; everything else takes two.
+    let wanted = match (delete, squeeze) {
+        (true, false) => 1,
+        (false, true) if sets.len() == 1 => 1,
+        _ => 2,
+    };
+    if sets.len() != wanted {
+        SPEC.usage();
+    }
+
+    let fail = |e: String| -> ! {
+        err!("{}", e);
+        process::exit(status::FAILURE);
+    };
+    let mut set1 = parse_set1(&sets[0]).unwrap_or_else(|e| fail(e));
+    if complemented {
+        set1 = complement(&set1);
+    }
+    let mut tr = Tr::new();
+    if delete {
+        tr.delete = members(&set1);
+        if squeeze {
+            tr.squeeze = parse_members(&sets[1]).unwrap_or_else(|e| fail(e));
+        }
+    } else if wanted == 2 {
+        let set2 = parse_set2(&sets[1], set1.len()).unwrap_or_else(|e| fail(e));
+        if set2.is_empty() && !set1.is_empty() {
+            fail("string2 must not be empty".to_string());
+        }
+        tr.translate(&set1, &set2);
+        if squeeze {
+            tr.squeeze = members(&set2);
+        }
+    } else {
+        tr.squeeze = members(&set1);
+    }
+
+    sandbox::filter();
+    let mut out = io::BufWriter::new(io::stdout());
+    if let Err(e) = tr.run(&mut io::stdin().lock(), &mut out).and_then(|_| out.flush()) {
+        fail(e.to_string());
+    }
+    status::SUCCESS
+}
+
+#[test]
+fn test_sets() {
+    assert_eq!(parse_set1("a-e").unwrap(), b"abcde");
+    assert_eq!(parse_set1("\\101\\n\\\\-").unwrap(), b"A\n\\-");
+    assert_eq!(parse_set1("[a").unwrap(), b"[a");
+    assert!(parse_set1("z-a").is_err());
+    assert!(parse_set1("[a*2]").is_err());
+    assert_eq!(parse_set2("[x*2]y", 5).unwrap(), b"xxyyy");
+    assert_eq!(parse_set2("a[x*]z", 5).unwrap(), b"axxxz");
+    assert_eq!(parse_set2("[x*010]y", 10).unwrap(), b"xxxxxxxxyy");
+    assert_eq!(parse_set2("abc", 2).unwrap(), b"ab");
+    assert_eq!(parse_set1("[:digit:][=x=]").unwrap(), b"0123456789x");
+    assert!(parse_set1("[:bogus:]").is_err());
+    assert_eq!(parse_set1("[:x").unwrap(), b"[:x");
+    assert_eq!(complement(&parse_set1("\\000-\\375").unwrap()), b"\xfe\xff");
+}
+
+/// Expands a pair of sets, separated by a NUL, for the fuzz target of the
+/// same name.
+#[cfg(fuzzing)]
+pub fn fuzz(data: &[u8]) {
+    let text = String::from_utf8_lossy(data);
+    let (set1, set2) = text.split_once('\0').unwrap_or((&text, ""));
+    if let Ok(set) = parse_set1(set1) {
+        let _ = parse_set2(set2, set.len());
+    }
+    let _ = parse_members(set1);
+}
@@ -0,0 +1,132 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// An implementation of the wall(1) command in Rust.
+// See http://man.cat-v.org/unix-7th/1/wall
+use std::collections::HashSet;
+use std::ffi::{CStr, OsString};
+use std::fs;
+use std::fs::OpenOptions;
+use std::io;
+use std::io::{Read, Write};
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::process;
+
+use crate::cmd::Spec;
+use crate::diag;
+use crate::err;
+use crate::status;
+use crate::time;
+use crate::time::Tm;
+use crate::tty;
+use crate::users;
+use crate::utmp;
+
+/// Returns the name of this host.
+fn host_name() -> Option<String> {
+    let mut buf = [0 as libc::c_char; 256];
+    // SAFETY: buf is writable for its whole length, less the last byte,
+    // which stays NUL in case the name is truncated.
+    if unsafe { libc::gethostname(buf.as_mut_ptr(), buf.len() - 1) } != 0 {
+        return None;
+    }
+    // SAFETY: buf is NUL-terminated.
+    Some(unsafe { CStr::from_ptr(buf.as_ptr()) }.to_string_lossy().into_owned())
+}
+
+/// Returns the text of the message as it is sent to a terminal: lines end
+/// with a carriage return and newline, as a terminal in raw mode needs,
+/// and other control characters are made visible, so that a message
+/// can't take control of a terminal.
+fn sanitize(text: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(text.len());
+    for &c in text {
+        match c {
+            b'\n' => out.extend_from_slice(b"\r\n"),
+            b'\t' | b'\x07' => out.push(c),
+            0..=0x1f | 0x7f => out.extend_from_slice(&[b'^', c ^ 0x40]),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Writes the message to the terminal without waiting: a terminal that
+/// isn't being read mustn't hold up the others.
+fn send(path: &str, message: &[u8]) -> io::Result<()> {
+    let mut tty = OpenOptions::new()
+        .write(true)
+        .custom_flags(libc::O_NOCTTY | libc::O_NONBLOCK)
+        .open(path)?;
+    tty.write_all(message)
+}
+
+pub const SPEC: Spec = Spec::new("write to all users", &[], "[message ...]");
+
+pub fn run(args: Vec<OsString>) -> i32 {
+    let words = SPEC.parse(args).strings();
+
+    // The message is the arguments or, if there are none, the standard
+    // input.
+    let mut text = words.join(" ").into_bytes();
+    if words.is_empty() {
+        if let Err(e) = io::stdin().read_to_end(&mut text) {
+            err!("{}", e);
+            process::exit(status::FAILURE);
+        }
+    }
+    if !text.is_empty() && !text.ends_with(b"\n") {
+        text.push(b'\n');
+    }
+
+    // SAFETY: getuid and geteuid have no preconditions.
+    let (uid, root) = unsafe { (libc::getuid(), libc::geteuid() == 0) };
+    let user = users::user_name(uid).unwrap_or_else(|| uid.to_string());
+    let host = host_name().unwrap_or_else(|| "localhost".to_string());
+    let from = tty::controlling()
+        .map(|name| name.strip_prefix("/dev/").map(String::from).unwrap_or(name))
+        .unwrap_or_else(|| "somewhere".to_string());
+    let (now, _) = time::now();
+    let mut message = format!("\x07\x07\x07Broadcast Message from {}@{} ({}) at {} ...\r\n\r\n",
+                              user, host, from, Tm::local(now).format("%H:%M")).into_bytes();
+    message.extend(sanitize(&text));
+
+    let entries = match utmp::read(utmp::UTMP_FILE) {
+        Ok(entries) => entries,
+        Err(e) => {
+            err!("{}: {}", utmp::UTMP_FILE, e);
+            process::exit(status::FAILURE);
+        }
+    };
+    let mut sent: HashSet<&str> = HashSet::new();
+    for entry in &entries {
+        let line = entry.line.as_str();
+        if entry.kind != utmp::USER_PROCESS || entry.user.is_empty() || line.is_empty()
+            || line.split('/').any(|c| c == "..") || !sent.insert(line) {
+            continue;
+        }
+        let path = format!("/dev/{}", line);
+        // Only the super-user may write to a user who has refused
+        // messages with mesg.
+        if !root {
+            match fs::metadata(&path) {
+                Ok(meta) if meta.permissions().mode() & 0o020 != 0 => (),
+                _ => continue,
+            }
+        }
+        match send(&path, &message) {
+            Ok(()) => (),
+            // Terminals that accept messages may still only be writable by
+            // their group, which wall may not be running as.
+            Err(e) if !root && e.kind() == io::ErrorKind::PermissionDenied => (),
+            Err(e) => err!("{}: {}", path, e),
+        }
+    }
+    diag::status()
+}
+
+#[test]
+fn test_sanitize() {
+    assert_eq!(sanitize(b"hi\tthere\x07\n\x1b[2J\x7f\n"), b"hi\tthere\x07\r\n^[[2J^?\r\n");
+    assert_eq!(sanitize("caf\u{e9}".as_bytes()), "caf\u{e9}".as_bytes());
+}
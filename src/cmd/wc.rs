@@ -0,0 +1,282 @@
+// Copyright 2018-2021 James Bostock. See the LICENSE file at the
+// top-level directory of this distribution.
+
+// An implementation of the wc(1) command in Rust.
+// See http://man.cat-v.org/unix-7th/1/wc
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::io;
+use std::io::{Read, Write};
+use std::ops;
+
+use crate::cmd::{Opt, Spec};
+use crate::diag;
+use crate::err;
+use crate::input::Input;
+use crate::json::Record;
+use crate::sandbox;
+use crate::status;
+
+struct Format {
+    chars: bool, // Technically bytes.
+    mchars: bool, // Multibyte characters.
+    words: bool,
+    lines: bool
+}
+
+impl Format {
+    fn new() -> Format {
+        // By default, all values are printed.
+        Format {chars: false, mchars: false, words: false, lines: false}
+    }
+}
+
+struct Counts<'a, 'b> {
+    chars: usize,
+    mchars: usize,
+    words: usize,
+    lines: usize,
+    file: &'a OsStr,
+    in_word: bool,
+    // The number of continuation bytes still expected to complete the
+    // current UTF-8 sequence, and the number of bytes seen so far.
+    utf8_need: usize,
+    utf8_seen: usize,
+    format: &'b Format
+}
+
+impl<'a, 'b> Counts<'a, 'b> {
+    fn new(file: &'a OsStr, format: &'b Format) -> Counts<'a, 'b> {
+        Counts {chars: 0, mchars: 0, words: 0, lines: 0, file, in_word: false,
+                utf8_need: 0, utf8_seen: 0, format}
+    }
+
+    /// Counts the characters in a buffer, decoding UTF-8. Each byte of an
+    /// invalid or incomplete sequence counts as a character.
+    fn count_mchars(&mut self, buf: &[u8]) {
+        for &c in buf {
+            if self.utf8_need > 0 {
+                if (0x80..=0xbf).contains(&c) {
+                    self.utf8_need -= 1;
+                    self.utf8_seen += 1;
+                    if self.utf8_need == 0 {
+                        self.mchars += 1;
+                        self.utf8_seen = 0;
+                    }
+                    continue;
+                }
+                self.mchars += self.utf8_seen;
+                self.utf8_need = 0;
+                self.utf8_seen = 0;
+            }
+            self.utf8_need = match c {
+                0xc2..=0xdf => 1,
+                0xe0..=0xef => 2,
+                0xf0..=0xf4 => 3,
+                _ => 0,
+            };
+            if self.utf8_need == 0 {
+                self.mchars += 1;
+            } else {
+                self.utf8_seen = 1;
+            }
+        }
+    }
+
+    /// Returns the counts as a JSON record.
+    fn record(&self) -> Record {
+        let mut record = Record::new().name("file", self.file);
+        if self.format.lines {
+            record = record.num("lines", self.lines as u64);
+        }
+        if self.format.words {
+            record = record.num("words", self.words as u64);
+        }
+        if self.format.mchars {
+            record = record.num("chars", self.mchars as u64);
+        }
+        if self.format.chars {
+            record = record.num("bytes", self.chars as u64);
+        }
+        record
+    }
+
+    /// Accounts for an incomplete UTF-8 sequence at the end of the input.
+    fn finish(&mut self) {
+        self.mchars += self.utf8_seen;
+        self.utf8_need = 0;
+        self.utf8_seen = 0;
+    }
+}
+
+impl<'a, 'b> ops::AddAssign for Counts<'a, 'b> {
+
+    fn add_assign(&mut self, rhs: Counts) {
+        *self = Counts {
+            chars: self.chars + rhs.chars,
+            mchars: self.mchars + rhs.mchars,
+            words: self.words + rhs.words,
+            lines: self.lines + rhs.lines,
+            file: self.file,
+            in_word: self.in_word,
+            utf8_need: 0,
+            utf8_seen: 0,
+            format: self.format
+        };
+    }
+}
+
+impl<'a, 'b> Counts<'a, 'b> {
+    /// Writes the counts and the name of the file, as it is, if it has
+    /// one, in a line.
+    fn print(&self, out: &mut dyn Write) -> io::Result<()> {
+        let counts = [(self.format.lines, self.lines), (self.format.words, self.words),
+                      (self.format.mchars, self.mchars), (self.format.chars, self.chars)];
+        let counts: Vec<String> = counts.iter().filter(|(on, _)| *on).map(|(_, n)| format!("{:7}", n)).collect();
+        out.write_all(counts.join(" ").as_bytes())?;
+        if !self.file.is_empty() {
+            out.write_all(b" ")?;
+            out.write_all(self.file.as_encoded_bytes())?;
+        }
+        writeln!(out)
+    }
+}
+
+impl<'a, 'b> io::Write for Counts<'a, 'b> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // ASCII codes of the characters that we consider whitespace.
+        const CR: u8 = 13; // Carriage return
+        const HT: u8 = 9;  // Horizontal tab
+        const LF: u8 = 10; // Line feed
+        const SP: u8 = 32; // Space
+        const VT: u8 = 11; // Vertical tab
+
+        for c in buf {
+            if *c == CR || *c == HT || *c == LF || *c == SP || *c == VT {
+                if self.in_word {
+                    self.in_word = false
+                }
+                if *c == LF {
+                    self.lines += 1;
+                }
+            } else if !self.in_word {
+                self.in_word = true;
+                self.words += 1;
+            }
+        }
+        if self.format.mchars {
+            self.count_mchars(buf);
+        }
+        self.chars += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The size of the buffer used when only counting bytes and lines.
+const BUFFER_SIZE: usize = 128 * 1024;
+
+fn wc<'a, 'b>(filename: &'a OsStr, format: &'b Format) -> io::Result<Counts<'a, 'b>> {
+    let mut reader = Input::open(filename)?;
+    let mut wc = Counts::new(filename, format);
+    if format.words || format.mchars {
+        io::copy(&mut reader, &mut wc)?;
+        wc.finish();
+        return Ok(wc);
+    }
+
+    // Counting only bytes and lines needs no per-byte state. The size of
+    // a regular file can be taken from its metadata, although some
+    // special filesystems report a size of zero for files with content.
+    if !format.lines && filename != "-" {
+        if let Ok(md) = fs::metadata(filename) {
+            if md.is_file() && md.len() > 0 {
+                wc.chars = md.len() as usize;
+                return Ok(wc);
+            }
+        }
+    }
+    let mut buf = vec![0; BUFFER_SIZE];
+    loop {
+        let n = match reader.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        if format.lines {
+            wc.lines += buf[..n].iter().filter(|&&c| c == b'\n').count();
+        }
+        wc.chars += n;
+    }
+    Ok(wc)
+}
+
+pub const SPEC: Spec = Spec::new("word count", &[
+    Opt::flag('c', "count bytes"),
+    Opt::flag('l', "count lines"),
+    Opt::flag('m', "count characters"),
+    Opt::flag('w', "count words"),
+], "[file ...]").json();
+
+pub fn run(args: Vec<OsString>) -> i32 {
+    let parsed = SPEC.parse(args);
+    let json = parsed.json;
+
+    let mut format = Format::new();
+    format.chars = parsed.flag('c');
+    format.mchars = parsed.flag('m');
+    format.lines = parsed.flag('l');
+    format.words = parsed.flag('w');
+
+    if parsed.opts().next().is_none() {
+        format.chars = true;
+        format.lines = true;
+        format.words = true;
+    }
+
+    let mut total = Counts::new(OsStr::new("total"), &format);
+    let mut files = parsed.operands;
+    // The standard input read for want of any file has no name.
+    let named = !files.is_empty();
+    if files.is_empty() {
+        files.push(OsString::from("-"));
+    }
+    if files.iter().all(|file| file == "-") {
+        sandbox::filter();
+    }
+
+    let mut out = io::stdout().lock();
+    for arg in &files {
+        let result = match wc(arg, &format) {
+            Ok(wc) if json => writeln!(out, "{}", wc.record()),
+            Ok(mut wc) => {
+                if !named {
+                    wc.file = OsStr::new("");
+                }
+                let result = wc.print(&mut out);
+                total += wc;
+                result
+            }
+            Err(e) => {
+                err!("{}: {}", arg.to_string_lossy(), e);
+                Ok(())
+            }
+        };
+        if let Err(e) = result {
+            err!("{}", e);
+            return status::FAILURE;
+        }
+    }
+
+    // A JSON consumer can add up the records itself.
+    if files.len() > 1 && !json {
+        if let Err(e) = total.print(&mut out) {
+            err!("{}", e);
+        }
+    }
+    diag::status()
+}
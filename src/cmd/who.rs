@@ -0,0 +1,74 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// An implementation of the who(1) command in Rust.
+// See http://man.cat-v.org/unix-7th/1/who
+use std::ffi::{OsStr, OsString};
+use std::io;
+use std::io::{BufWriter, Write};
+use std::process;
+
+use crate::cmd::{Opt, Spec};
+use crate::err;
+use crate::status;
+use crate::time::Tm;
+use crate::tty;
+use crate::utmp;
+
+/// Writes an entry as user, terminal, login time and, for a remote login,
+/// host.
+fn write_entry(entry: &utmp::Entry, out: &mut dyn Write) -> io::Result<()> {
+    let time = Tm::local(entry.time).format("%b %e %H:%M");
+    write!(out, "{:<8} {:<12} {}", entry.user, entry.line, time)?;
+    if !entry.host.is_empty() {
+        write!(out, " ({})", entry.host)?;
+    }
+    writeln!(out)
+}
+
+pub const SPEC: Spec = Spec::new("who is on the system", &[
+    Opt::flag('q', "print only the names and their number"),
+], "[file | am i]");
+
+pub fn run(args: Vec<OsString>) -> i32 {
+    let parsed = SPEC.parse(args);
+    let quick = parsed.flag('q');
+    let operands = parsed.operands;
+
+    // "who am i" reports only the user on this terminal.
+    let (file, am_i) = match operands.as_slice() {
+        [] => (OsStr::new(utmp::UTMP_FILE), false),
+        [file] => (file.as_os_str(), false),
+        [am, i] if am == "am" && (i == "i" || i == "I") => (OsStr::new(utmp::UTMP_FILE), true),
+        _ => SPEC.usage(),
+    };
+
+    let entries = match utmp::read(file) {
+        Ok(entries) => entries,
+        Err(e) => {
+            err!("{}: {}", file.to_string_lossy(), e);
+            process::exit(status::FAILURE);
+        }
+    };
+    // The terminal on the standard input, without the /dev/ prefix.
+    let tty = tty::name(0).filter(|_| am_i)
+        .map(|name| name.strip_prefix("/dev/").map(String::from).unwrap_or(name));
+    let users: Vec<&utmp::Entry> = entries.iter()
+        .filter(|entry| entry.kind == utmp::USER_PROCESS && !entry.user.is_empty())
+        .filter(|entry| !am_i || Some(&entry.line) == tty.as_ref())
+        .collect();
+
+    let mut out = BufWriter::new(io::stdout());
+    let result = if quick {
+        let names: Vec<&str> = users.iter().map(|entry| entry.user.as_str()).collect();
+        writeln!(out, "{}", names.join(" "))
+            .and_then(|_| writeln!(out, "# users={}", names.len()))
+    } else {
+        users.iter().try_for_each(|entry| write_entry(entry, &mut out))
+    };
+    if let Err(e) = result.and_then(|_| out.flush()) {
+        err!("{}", e);
+        process::exit(status::FAILURE);
+    }
+    status::SUCCESS
+}
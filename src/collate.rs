@@ -0,0 +1,133 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+//! The orders in which sort, join, look and ls compare text. By default
+//! they compare bytes, as the Seventh Edition did, so that their output is
+//! the same wherever they run. Another order can be chosen with the -L
+//! option of all but ls, or by setting LC_ALL or LC_COLLATE to a locale
+//! other than C or POSIX; LANG alone is not enough, since it is set almost
+//! everywhere.
+//!
+//! Bytes that aren't part of valid UTF-8 are taken as the last characters
+//! in Unicode, so that they sort after all but those, in the order of
+//! their values.
+use std::cmp::Ordering;
+use std::env;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Collation {
+    /// By the values of the bytes.
+    #[default]
+    Bytes,
+    /// By Unicode code points. For valid UTF-8 this is the same as the
+    /// order of the bytes.
+    CodePoint,
+    /// By code points, ignoring case.
+    Fold,
+    /// As most locales have it, there being no locale tables here: by the
+    /// letters and digits, ignoring case, then with lower case before upper
+    /// case, and only then by the punctuation, spaces and the rest.
+    Locale,
+}
+
+/// Returns the characters of the text, with bytes that aren't valid UTF-8
+/// taken as the last code points of all, U+10FF80 to U+10FFFF.
+fn chars(s: &[u8]) -> impl Iterator<Item = char> + '_ {
+    s.utf8_chunks().flat_map(|chunk| {
+        chunk.valid().chars().chain(chunk.invalid().iter().map(|&b| {
+            char::from_u32(0x10ff00 + u32::from(b)).unwrap_or(char::MAX)
+        }))
+    })
+}
+
+/// Returns the characters that count first under the Locale order.
+fn primary(s: &[u8]) -> impl Iterator<Item = char> + '_ {
+    chars(s).filter(|c| c.is_alphanumeric()).flat_map(char::to_lowercase)
+}
+
+/// Compares a line with a prefix: Equal if the line begins with it, and
+/// otherwise as the line compares with the prefix.
+fn prefix_cmp<T: Ord>(mut line: impl Iterator<Item = T>, prefix: impl Iterator<Item = T>) -> Ordering {
+    for p in prefix {
+        match line.next() {
+            Some(c) if c == p => (),
+            Some(c) => return c.cmp(&p),
+            None => return Ordering::Less,
+        }
+    }
+    Ordering::Equal
+}
+
+impl Collation {
+    /// Returns the collation of the given name, as given to -L: bytes
+    /// (also C or POSIX), codepoint, fold or locale.
+    pub fn parse(name: &str) -> Option<Collation> {
+        match name {
+            "bytes" | "C" | "POSIX" => Some(Collation::Bytes),
+            "codepoint" => Some(Collation::CodePoint),
+            "fold" => Some(Collation::Fold),
+            "locale" => Some(Collation::Locale),
+            _ => None,
+        }
+    }
+
+    /// Returns the collation selected by the environment.
+    pub fn from_env() -> Collation {
+        let locale = ["LC_ALL", "LC_COLLATE"].iter()
+            .filter_map(env::var_os)
+            .find(|val| !val.is_empty());
+        match locale {
+            Some(val) if val != "C" && val != "POSIX" && !val.as_encoded_bytes().starts_with(b"C.") => {
+                Collation::Locale
+            },
+            _ => Collation::Bytes,
+        }
+    }
+
+    pub fn compare(self, a: &[u8], b: &[u8]) -> Ordering {
+        match self {
+            Collation::Bytes => a.cmp(b),
+            Collation::CodePoint => chars(a).cmp(chars(b)),
+            Collation::Fold => chars(a).flat_map(char::to_lowercase)
+                .cmp(chars(b).flat_map(char::to_lowercase)),
+            Collation::Locale => primary(a).cmp(primary(b))
+                .then_with(|| {
+                    let upper = |s| chars(s).filter(|c| c.is_alphanumeric()).map(char::is_uppercase);
+                    upper(a).cmp(upper(b))
+                })
+                .then_with(|| chars(a).cmp(chars(b))),
+        }
+    }
+
+    /// Compares the start of a line with a prefix: Equal if the line begins
+    /// with the prefix, in the sense of the collation, and otherwise as the
+    /// line sorts relative to the prefix. Under the Locale order only the
+    /// letters and digits count.
+    pub fn compare_prefix(self, line: &[u8], prefix: &[u8]) -> Ordering {
+        match self {
+            Collation::Bytes => prefix_cmp(line.iter(), prefix.iter()),
+            Collation::CodePoint => prefix_cmp(chars(line), chars(prefix)),
+            Collation::Fold => prefix_cmp(chars(line).flat_map(char::to_lowercase),
+                                          chars(prefix).flat_map(char::to_lowercase)),
+            Collation::Locale => prefix_cmp(primary(line), primary(prefix)),
+        }
+    }
+}
+
+#[test]
+fn test_collation() {
+    let sorted = |collation: Collation, words: &[&'static str]| {
+        let mut sorted = words.to_vec();
+        sorted.sort_by(|a, b| collation.compare(a.as_bytes(), b.as_bytes()));
+        sorted
+    };
+    let words = ["b", "B", "a-c", "ab", "A", "é", "\u{ff}"];
+    assert_eq!(sorted(Collation::Bytes, &words), ["A", "B", "a-c", "ab", "b", "é", "\u{ff}"]);
+    assert_eq!(sorted(Collation::Fold, &words), ["A", "a-c", "ab", "b", "B", "é", "\u{ff}"]);
+    assert_eq!(sorted(Collation::Locale, &words), ["A", "ab", "a-c", "b", "B", "é", "\u{ff}"]);
+    assert_eq!(Collation::CodePoint.compare(b"\xff", "\u{10ff7f}".as_bytes()), Ordering::Greater);
+    assert_eq!(Collation::Bytes.compare_prefix(b"abc", b"ab"), Ordering::Equal);
+    assert_eq!(Collation::Fold.compare_prefix(b"ABC", b"ab"), Ordering::Equal);
+    assert_eq!(Collation::Locale.compare_prefix(b"a-bc", b"ab"), Ordering::Equal);
+    assert_eq!(Collation::Locale.compare_prefix(b"a", b"ab"), Ordering::Less);
+}
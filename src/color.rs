@@ -0,0 +1,131 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+//! ANSI colour for output to terminals. Whether there is any colour is
+//! decided as the NO_COLOR and CLICOLOR conventions have it: not if
+//! NO_COLOR is set, always if CLICOLOR_FORCE is, not if CLICOLOR is 0, and
+//! otherwise only if the output is a terminal. A command's own option,
+//! such as ls's --color, can ask for it always or never instead. Output that
+//! isn't coloured is the same as if there were no such thing.
+use std::env;
+use std::ffi::OsString;
+use std::io;
+use std::io::{IsTerminal, Write};
+
+/// When to colour output, as chosen by a command's options.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum When {
+    Never,
+    /// As the environment and the output decide.
+    Auto,
+    Always,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Color {
+    Black,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+}
+
+/// A foreground colour, possibly bold.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Style {
+    pub color: Option<Color>,
+    pub bold: bool,
+}
+
+impl Style {
+    pub const fn plain(color: Color) -> Style {
+        Style { color: Some(color), bold: false }
+    }
+
+    pub const fn bold(color: Color) -> Style {
+        Style { color: Some(color), bold: true }
+    }
+
+    /// Returns the parameters of the SGR escape sequence that selects the
+    /// style.
+    fn sgr(self) -> String {
+        let mut params = Vec::new();
+        if self.bold {
+            params.push("1".to_string());
+        }
+        if let Some(color) = self.color {
+            params.push((30 + color as u8).to_string());
+        }
+        params.join(";")
+    }
+}
+
+/// Decides whether to colour, given the values of NO_COLOR, CLICOLOR and
+/// CLICOLOR_FORCE.
+fn decide(when: When, terminal: bool, no_color: Option<OsString>, clicolor: Option<OsString>,
+          force: Option<OsString>) -> bool {
+    let set = |var: &Option<OsString>| var.as_ref().is_some_and(|val| !val.is_empty() && val != "0");
+    match when {
+        When::Never => false,
+        When::Always => true,
+        When::Auto if no_color.is_some_and(|val| !val.is_empty()) => false,
+        When::Auto if set(&force) => true,
+        When::Auto if clicolor.is_some_and(|val| val == "0") => false,
+        When::Auto => terminal,
+    }
+}
+
+/// Writes text in colour, or, if colour is off, as it is.
+#[derive(Clone, Copy, Debug)]
+pub struct Painter {
+    enabled: bool,
+}
+
+impl Painter {
+    /// Returns a painter for output to the given stream, usually the
+    /// standard output.
+    pub fn new<T: IsTerminal>(when: When, out: &T) -> Painter {
+        let enabled = decide(when, out.is_terminal(), env::var_os("NO_COLOR"),
+                             env::var_os("CLICOLOR"), env::var_os("CLICOLOR_FORCE"));
+        Painter { enabled }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Writes the text in the style.
+    pub fn write(&self, out: &mut dyn Write, style: Style, text: &[u8]) -> io::Result<()> {
+        if !self.enabled || text.is_empty() {
+            return out.write_all(text);
+        }
+        write!(out, "\x1b[{}m", style.sgr())?;
+        out.write_all(text)?;
+        out.write_all(b"\x1b[0m")
+    }
+}
+
+#[test]
+fn test_color() {
+    let var = |s: &str| Some(OsString::from(s));
+    assert!(decide(When::Auto, true, None, None, None));
+    assert!(!decide(When::Auto, false, None, None, None));
+    assert!(!decide(When::Auto, true, var("1"), None, None));
+    assert!(decide(When::Auto, true, var(""), None, None));
+    assert!(decide(When::Auto, false, None, None, var("1")));
+    assert!(!decide(When::Auto, false, None, None, var("0")));
+    assert!(!decide(When::Auto, true, None, var("0"), None));
+    assert!(decide(When::Always, false, var("1"), None, None));
+    assert!(!decide(When::Never, true, None, None, var("1")));
+
+    let mut out = Vec::new();
+    let painter = Painter { enabled: true };
+    painter.write(&mut out, Style::bold(Color::Blue), b"dir").unwrap();
+    assert_eq!(out, b"\x1b[1;34mdir\x1b[0m");
+    out.clear();
+    Painter { enabled: false }.write(&mut out, Style::plain(Color::Red), b"x").unwrap();
+    assert_eq!(out, b"x");
+}
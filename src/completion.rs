@@ -0,0 +1,140 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+//! Completion scripts for bash and zsh, generated from the commands'
+//! Specs, as written by "v7 --completion bash" or "v7 --completion zsh".
+//! They complete the options of each command, and of the command named
+//! by v7's first argument, and leave the operands to the shell's own
+//! completion of file names.
+use std::fmt::Write;
+
+use crate::cmd::{Spec, COMMANDS};
+
+/// The shells for which there are scripts.
+pub const SHELLS: &[&str] = &["bash", "zsh"];
+
+/// Returns the script for the named shell.
+pub fn script(shell: &str) -> Option<String> {
+    match shell {
+        "bash" => Some(bash()),
+        "zsh" => Some(zsh()),
+        _ => None,
+    }
+}
+
+/// Returns the options of the command as they are typed, each on its own.
+fn options(spec: &Spec) -> Vec<String> {
+    let mut options: Vec<String> = spec.options.iter().map(|opt| format!("-{}", opt.letter)).collect();
+    if spec.json {
+        options.push("--json".to_string());
+    }
+    if !spec.literal {
+        options.extend(["--help".to_string(), "--version".to_string()]);
+    }
+    options
+}
+
+fn names() -> Vec<&'static str> {
+    COMMANDS.iter().map(|&(name, _, _)| name).collect()
+}
+
+/// Returns the script for bash, which offers the options when the word
+/// being completed starts with -, and file names otherwise.
+pub fn bash() -> String {
+    let mut script = String::from("# bash completion for the v7 commands, from v7 --completion bash.\n\n");
+    script += "_v7_options() {\n    case $1 in\n";
+    for &(name, _, spec) in COMMANDS {
+        let _ = writeln!(script, "        {}) echo \"{}\" ;;", name, options(spec).join(" "));
+    }
+    script += "    esac\n}\n\n";
+    script += "_v7() {\n";
+    script += "    local cur=${COMP_WORDS[COMP_CWORD]} cmd=${COMP_WORDS[0]##*/}\n";
+    script += "    if [[ $cmd == v7 ]]; then\n";
+    script += "        if ((COMP_CWORD == 1)); then\n";
+    let _ = writeln!(script, "            COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))", names().join(" "));
+    script += "            return\n";
+    script += "        fi\n";
+    script += "        cmd=${COMP_WORDS[1]}\n";
+    script += "    fi\n";
+    script += "    if [[ $cur == -* ]]; then\n";
+    script += "        COMPREPLY=($(compgen -W \"$(_v7_options \"$cmd\")\" -- \"$cur\"))\n";
+    script += "    fi\n";
+    script += "}\n\n";
+    let _ = writeln!(script, "complete -o default -F _v7 {} v7", names().join(" "));
+    script
+}
+
+/// Escapes an option's description for _arguments, which would otherwise
+/// take a bracket in it as the end of the description.
+fn zsh_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('[', "\\[").replace(']', "\\]")
+}
+
+/// Quotes a word for zsh.
+fn zsh_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+/// Returns the arguments to _arguments that describe the command.
+fn zsh_arguments(spec: &Spec) -> Vec<String> {
+    let mut args = Vec::new();
+    for opt in spec.options {
+        let about = zsh_escape(opt.about);
+        args.push(match opt.arg {
+            Some(arg) => zsh_quote(&format!("-{}+[{}]:{}:_default", opt.letter, about, arg)),
+            None => zsh_quote(&format!("-{}[{}]", opt.letter, about)),
+        });
+    }
+    if spec.json {
+        args.push(zsh_quote("--json[write a JSON record for each entry]"));
+    }
+    if !spec.literal {
+        args.push(zsh_quote("(- *)--help[describe the command]"));
+        args.push(zsh_quote("(- *)--version[print the version]"));
+    }
+    if !spec.operands.is_empty() {
+        args.push(zsh_quote("*:operand:_files"));
+    }
+    args
+}
+
+/// Returns the script for zsh, which describes each option.
+pub fn zsh() -> String {
+    let mut script = format!("#compdef {} v7\n\n", names().join(" "));
+    script += "# zsh completion for the v7 commands, from v7 --completion zsh.\n\n";
+    script += "_v7_command() {\n    case $1 in\n";
+    for &(name, _, spec) in COMMANDS {
+        let _ = writeln!(script, "        ({})\n            _arguments -s {} ;;", zsh_quote(name), zsh_arguments(spec).join(" "));
+    }
+    script += "        (*)\n            _files ;;\n";
+    script += "    esac\n}\n\n";
+    script += "_v7() {\n";
+    script += "    local cmd=${words[1]:t}\n";
+    script += "    if [[ $cmd == v7 ]]; then\n";
+    script += "        if ((CURRENT == 2)); then\n";
+    // Quoted, since [ would otherwise start a pattern.
+    let names: Vec<String> = names().into_iter().map(zsh_quote).collect();
+    let _ = writeln!(script, "            compadd -- {}", names.join(" "));
+    script += "            return\n";
+    script += "        fi\n";
+    script += "        shift words\n";
+    script += "        ((CURRENT--))\n";
+    script += "        cmd=$words[1]\n";
+    script += "    fi\n";
+    script += "    _v7_command $cmd\n";
+    script += "}\n\n";
+    script += "_v7 \"$@\"\n";
+    script
+}
+
+#[test]
+#[cfg(all(feature = "core", feature = "text"))]
+fn test_completion() {
+    let bash = bash();
+    assert!(bash.contains("        du) echo \"-a -A -s -k -h -z -H -L -P --json --help --version\" ;;\n"));
+    assert!(bash.contains("        echo) echo \"-n\" ;;\n"));
+    assert!(zsh().contains("'-L+[collate text in order: bytes, codepoint, fold or locale]:order:_default'"));
+    assert!(zsh().contains("'-t+[use the time \\[\\[CC\\]YY\\]MMDDhhmm\\[.ss\\]]:time:_default'"));
+    assert!(zsh().contains("        ('[')\n"));
+    assert_eq!(zsh_quote("it's"), "'it'\\''s'");
+}
@@ -0,0 +1,243 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+//! Copying in bulk, as cat, dd and tee do. On Linux, a copy from one
+//! descriptor to another is left to the kernel, so that the data never
+//! passes through the process: with copy_file_range between regular files
+//! and with splice where one end is a pipe. Anything the kernel won't copy
+//! that way, and everything elsewhere, is read and written through a
+//! buffer large enough that the copy is limited by the disks rather than
+//! by the number of system calls.
+//!
+//! Whole files are copied, as cp and mv copy them, with copy_file, which
+//! keeps the holes of a sparse file, and whole trees with copy_tree.
+use std::fs;
+use std::fs::{File, Metadata, OpenOptions, Permissions};
+use std::io::{self, Read, Write};
+use std::os::fd::{AsFd, BorrowedFd};
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+use crate::blocks::STAT_BLOCK;
+use crate::err;
+use crate::platform;
+use crate::prompt;
+use crate::sparse;
+use crate::trace;
+use crate::walk::{Follow, Walk};
+
+/// The size of the buffer data is copied through.
+pub const BUF_SIZE: usize = 128 * 1024;
+
+/// Copies everything from the reader to the writer through a buffer,
+/// returning the number of bytes copied.
+pub fn copy_buffered(from: &mut dyn Read, to: &mut dyn Write) -> io::Result<u64> {
+    let mut buf = vec![0; BUF_SIZE];
+    let mut total = 0;
+    loop {
+        let n = match from.read(&mut buf) {
+            Ok(0) => return Ok(total),
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        to.write_all(&buf[..n])?;
+        total += n as u64;
+    }
+}
+
+/// Copies everything from one descriptor, from its offset, to the other,
+/// at its offset, returning the number of bytes copied.
+pub fn copy(from: BorrowedFd, to: BorrowedFd) -> io::Result<u64> {
+    let mut total = 0;
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    if kernel::copy(from, to, &mut total)? {
+        return Ok(total);
+    }
+    let mut input = File::from(from.try_clone_to_owned()?);
+    let mut output = File::from(to.try_clone_to_owned()?);
+    Ok(total + copy_buffered(&mut input, &mut output)?)
+}
+
+/// Returns the path a file is copied, moved or linked to: the target or,
+/// if that is a directory, the file's name in it.
+pub fn destination(from: &Path, target: &Path) -> PathBuf {
+    match from.file_name() {
+        Some(name) if target.is_dir() => target.join(name),
+        _ => target.to_path_buf(),
+    }
+}
+
+/// Copies the regular file at one path to the other, which is created if
+/// it doesn't exist and truncated if it does, and given the permissions of
+/// the first. Returns the number of bytes copied.
+pub fn copy_file(from: &Path, to: &Path) -> io::Result<u64> {
+    let input = File::open(from)?;
+    let meta = input.metadata()?;
+    let output = trace::op("create", format_args!("{:?}", to), OpenOptions::new()
+        .write(true).create(true).truncate(true).mode(meta.mode() & 0o7777).open(to))?;
+    // A file that takes less space than its length has holes, which are
+    // copied as holes, rather than as the NULs they read as.
+    let (n, output) = if meta.blocks() * STAT_BLOCK < meta.len() {
+        let mut writer = sparse::Writer::new(output);
+        let n = copy_buffered(&mut sparse::Reader::new(input)?, &mut writer)?;
+        (n, writer.finish()?)
+    } else {
+        (copy(input.as_fd(), output.as_fd())?, output)
+    };
+    // The mode given to open is masked by the umask, and doesn't apply to
+    // a file that already exists.
+    trace::op("chmod", format_args!("{:?}, {:04o}", to, meta.mode() & 0o7777),
+              output.set_permissions(meta.permissions()))?;
+    Ok(n)
+}
+
+/// Copies a file that isn't a directory to the path, as a link if it is a
+/// symbolic link.
+fn copy_one(from: &Path, meta: &Metadata, to: &Path) -> io::Result<()> {
+    let kind = meta.file_type();
+    if kind.is_symlink() {
+        let target = fs::read_link(from)?;
+        // A link at the path, as a copy made before leaves, is kept if it
+        // already refers to the same file, and replaced, as a file would
+        // be, if it doesn't.
+        match fs::symlink_metadata(to) {
+            Ok(old) if old.file_type().is_symlink() && fs::read_link(to)? == target => return Ok(()),
+            Ok(old) if !old.is_dir() => platform::remove_file(to)?,
+            _ => (),
+        }
+        trace::op("symlink", format_args!("{:?}, {:?}", target, to),
+                  std::os::unix::fs::symlink(&target, to))
+    } else if kind.is_file() {
+        copy_file(from, to).map(|_| ())
+    } else {
+        Err(io::Error::other("not a regular file"))
+    }
+}
+
+/// Copies a file and, if it is a directory, everything in it to the path,
+/// making directories that don't exist. Symbolic links are copied as
+/// links, unless the policy follows them. If interactive, the user is
+/// asked before a file is replaced. Reports what couldn't be copied and
+/// returns false if anything couldn't.
+pub fn copy_tree(from: &Path, to: &Path, follow: Follow, interactive: bool) -> bool {
+    let mut ok = true;
+    // A directory is made writable, so that its contents can be copied
+    // into it, and given its proper mode only once they have been.
+    let mut modes: Vec<(PathBuf, Permissions)> = Vec::new();
+    for entry in Walk::new([from]).follow(follow) {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                err!("{}", e);
+                ok = false;
+                continue;
+            }
+        };
+        let rel = entry.path().strip_prefix(from).unwrap_or(Path::new(""));
+        let dest = if rel.as_os_str().is_empty() { to.to_path_buf() } else { to.join(rel) };
+        let meta = entry.metadata();
+        let result = if meta.is_dir() {
+            let result = match fs::DirBuilder::new().mode(meta.mode() | 0o700).create(&dest) {
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists && dest.is_dir() => Ok(()),
+                result => trace::op("mkdir", format_args!("{:?}", dest), result),
+            };
+            if result.is_ok() {
+                modes.push((dest.clone(), meta.permissions()));
+            }
+            result
+        } else if interactive && fs::symlink_metadata(&dest).is_ok()
+            && !prompt::ask(&format!("overwrite '{}'?", dest.display())) {
+            continue;
+        } else {
+            copy_one(entry.path(), meta, &dest)
+        };
+        if let Err(e) = result {
+            err!("{}: {}", dest.display(), e);
+            ok = false;
+        }
+    }
+    for (dir, perms) in modes.into_iter().rev() {
+        let mode = perms.mode() & 0o7777;
+        let result = fs::set_permissions(&dir, perms);
+        if let Err(e) = trace::op("chmod", format_args!("{:?}, {:04o}", dir, mode), result) {
+            err!("{}: {}", dir.display(), e);
+            ok = false;
+        }
+    }
+    ok
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod kernel {
+    use std::io;
+    use std::os::fd::{AsRawFd, BorrowedFd};
+    use std::ptr;
+
+    /// The most the kernel is asked to copy at once.
+    const CHUNK: usize = 1 << 30;
+
+    /// Copies with copy_file_range or, failing that, splice, adding what
+    /// is copied to the total. Returns false, having copied nothing, if
+    /// the kernel can copy between the descriptors in neither way.
+    pub fn copy(from: BorrowedFd, to: BorrowedFd, total: &mut u64) -> io::Result<bool> {
+        let (from, to) = (from.as_raw_fd(), to.as_raw_fd());
+        for splice in [false, true] {
+            loop {
+                // SAFETY: the descriptors are open, and the null offsets
+                // ask for their own offsets to be used and updated.
+                let n = unsafe {
+                    if splice {
+                        libc::splice(from, ptr::null_mut(), to, ptr::null_mut(), CHUNK, libc::SPLICE_F_MOVE)
+                    } else {
+                        libc::copy_file_range(from, ptr::null_mut(), to, ptr::null_mut(), CHUNK, 0)
+                    }
+                };
+                match n {
+                    // Files that claim to be empty, as those in /proc do,
+                    // may have something to read after all.
+                    0 if *total == 0 => break,
+                    0 => return Ok(true),
+                    n if n > 0 => *total += n as u64,
+                    _ => {
+                        let e = io::Error::last_os_error();
+                        match e.raw_os_error() {
+                            Some(libc::EINTR) => (),
+                            // Not between these descriptors: an output
+                            // opened for appending, say, or no pipe for
+                            // splice.
+                            Some(libc::EINVAL | libc::EXDEV | libc::ENOSYS | libc::EOPNOTSUPP
+                                 | libc::EBADF | libc::EPERM) if *total == 0 => break,
+                            _ => return Err(e),
+                        }
+                    },
+                }
+            }
+        }
+        Ok(false)
+    }
+}
+
+#[test]
+fn test_copy() {
+    use crate::tempfile::TempFile;
+
+    let data: Vec<u8> = (0..3 * BUF_SIZE).map(|i| (i % 251) as u8).collect();
+    let from = TempFile::new("copy").unwrap();
+    let to = TempFile::new("copy").unwrap();
+    std::fs::write(from.path(), &data).unwrap();
+    let input = File::open(from.path()).unwrap();
+    let output = File::create(to.path()).unwrap();
+    assert_eq!(copy(input.as_fd(), output.as_fd()).unwrap(), data.len() as u64);
+    assert_eq!(std::fs::read(to.path()).unwrap(), data);
+
+    let copied = TempFile::new("copy").unwrap();
+    std::fs::set_permissions(from.path(), Permissions::from_mode(0o640)).unwrap();
+    assert_eq!(copy_file(from.path(), copied.path()).unwrap(), data.len() as u64);
+    assert_eq!(std::fs::read(copied.path()).unwrap(), data);
+    assert_eq!(std::fs::metadata(copied.path()).unwrap().mode() & 0o777, 0o640);
+
+    let mut out = Vec::new();
+    assert_eq!(copy_buffered(&mut &data[..], &mut out).unwrap(), data.len() as u64);
+    assert_eq!(out, data);
+}
@@ -0,0 +1,29 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+//! Device numbers, in the encoding used by glibc, which splits the major
+//! and minor numbers each into a low and a high part.
+
+/// Returns the major number of a device.
+pub fn major(dev: u64) -> u32 {
+    (((dev >> 8) & 0xfff) | ((dev >> 32) & !0xfff)) as u32
+}
+
+/// Returns the minor number of a device.
+pub fn minor(dev: u64) -> u32 {
+    ((dev & 0xff) | ((dev >> 12) & !0xff)) as u32
+}
+
+/// Returns the device with the given major and minor numbers.
+pub fn makedev(major: u32, minor: u32) -> u64 {
+    let (major, minor) = (major as u64, minor as u64);
+    ((major & 0xfff) << 8) | ((major & !0xfff) << 32) | (minor & 0xff) | ((minor & !0xff) << 12)
+}
+
+#[test]
+fn test_makedev() {
+    assert_eq!(makedev(8, 1), 0x801);
+    assert_eq!(minor(makedev(259, 65536)), 65536);
+    assert_eq!(major(makedev(259, 65536)), 259);
+    assert_eq!(major(makedev(u32::MAX, 0)), u32::MAX);
+}
@@ -0,0 +1,74 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+//! Diagnostics. Every command reports what goes wrong on its standard
+//! error as "prog: message", where prog is the name it was invoked by,
+//! without any directories; cmd::exec records that name before running
+//! the command. A command reports a failure with [`err!`], which also
+//! notes that one occurred so that the command can exit with [`status`],
+//! and anything that doesn't make the command fail with [`warn!`].
+use std::env;
+use std::ffi::OsStr;
+use std::fmt;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::status;
+
+static PROG: OnceLock<String> = OnceLock::new();
+static FAILED: AtomicBool = AtomicBool::new(false);
+
+/// Records the name the command was invoked by, taken from its first
+/// argument. Only the first call has any effect.
+pub fn set_prog(arg0: &OsStr) {
+    let name = Path::new(arg0).file_name().unwrap_or(arg0);
+    let _ = PROG.set(name.to_string_lossy().into_owned());
+}
+
+/// Returns the name the command was invoked by, or, if that hasn't been
+/// recorded, the name of the running program.
+pub fn prog() -> &'static str {
+    PROG.get_or_init(|| {
+        let arg0 = env::args_os().next().unwrap_or_default();
+        Path::new(&arg0).file_name().unwrap_or_default().to_string_lossy().into_owned()
+    })
+}
+
+/// Returns true if a failure has been reported.
+pub fn failed() -> bool {
+    FAILED.load(Ordering::Relaxed)
+}
+
+/// Returns FAILURE if a failure has been reported, and SUCCESS otherwise.
+pub fn status() -> i32 {
+    if failed() { status::FAILURE } else { status::SUCCESS }
+}
+
+#[doc(hidden)]
+pub fn report(failure: bool, msg: fmt::Arguments) {
+    if failure {
+        FAILED.store(true, Ordering::Relaxed);
+    }
+    // There is nowhere to report a failure to write to the standard error.
+    let _ = writeln!(io::stderr().lock(), "{}: {}", prog(), msg);
+}
+
+/// Reports a failure on the standard error, prefixed by the program's
+/// name, and notes that one occurred.
+#[macro_export]
+macro_rules! err {
+    ($($arg:tt)*) => {
+        $crate::diag::report(true, format_args!($($arg)*))
+    };
+}
+
+/// Reports something on the standard error, prefixed by the program's
+/// name, without the command counting as having failed.
+#[macro_export]
+macro_rules! warn {
+    ($($arg:tt)*) => {
+        $crate::diag::report(false, format_args!($($arg)*))
+    };
+}
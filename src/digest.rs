@@ -0,0 +1,420 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+//! Message digests: MD5, SHA-1, SHA-256 and SHA-512. Like the checksums,
+//! each digest is a writer, so that a file can be copied to it.
+use std::io;
+use std::io::Write;
+
+/// A digest algorithm.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Algorithm {
+    Md5,
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+/// The algorithms, with their names.
+pub const ALGORITHMS: [(Algorithm, &str); 4] = [
+    (Algorithm::Md5, "md5"),
+    (Algorithm::Sha1, "sha1"),
+    (Algorithm::Sha256, "sha256"),
+    (Algorithm::Sha512, "sha512"),
+];
+
+impl Algorithm {
+    /// Returns the algorithm with the given name, such as "sha256".
+    pub fn from_name(name: &str) -> Option<Algorithm> {
+        ALGORITHMS.iter().find(|(_, n)| *n == name).map(|&(alg, _)| alg)
+    }
+
+    pub fn name(self) -> &'static str {
+        ALGORITHMS.iter().find(|(alg, _)| *alg == self).map(|&(_, n)| n).unwrap()
+    }
+
+    /// Returns the length of a digest in bytes.
+    pub fn size(self) -> usize {
+        match self {
+            Algorithm::Md5 => 16,
+            Algorithm::Sha1 => 20,
+            Algorithm::Sha256 => 32,
+            Algorithm::Sha512 => 64,
+        }
+    }
+
+    /// Returns the length of the blocks the data is digested in.
+    fn block_size(self) -> usize {
+        match self {
+            Algorithm::Sha512 => 128,
+            _ => 64,
+        }
+    }
+}
+
+/// The sines from which MD5 takes a constant for each step.
+const MD5_K: [u32; 64] = [
+    0xd76aa478, 0xe8c7b756, 0x242070db, 0xc1bdceee,
+    0xf57c0faf, 0x4787c62a, 0xa8304613, 0xfd469501,
+    0x698098d8, 0x8b44f7af, 0xffff5bb1, 0x895cd7be,
+    0x6b901122, 0xfd987193, 0xa679438e, 0x49b40821,
+    0xf61e2562, 0xc040b340, 0x265e5a51, 0xe9b6c7aa,
+    0xd62f105d, 0x02441453, 0xd8a1e681, 0xe7d3fbc8,
+    0x21e1cde6, 0xc33707d6, 0xf4d50d87, 0x455a14ed,
+    0xa9e3e905, 0xfcefa3f8, 0x676f02d9, 0x8d2a4c8a,
+    0xfffa3942, 0x8771f681, 0x6d9d6122, 0xfde5380c,
+    0xa4beea44, 0x4bdecfa9, 0xf6bb4b60, 0xbebfbc70,
+    0x289b7ec6, 0xeaa127fa, 0xd4ef3085, 0x04881d05,
+    0xd9d4d039, 0xe6db99e5, 0x1fa27cf8, 0xc4ac5665,
+    0xf4292244, 0x432aff97, 0xab9423a7, 0xfc93a039,
+    0x655b59c3, 0x8f0ccc92, 0xffeff47d, 0x85845dd1,
+    0x6fa87e4f, 0xfe2ce6e0, 0xa3014314, 0x4e0811a1,
+    0xf7537e82, 0xbd3af235, 0x2ad7d2bb, 0xeb86d391,
+];
+
+/// The amounts MD5 rotates by in each round.
+const MD5_S: [[u32; 4]; 4] = [[7, 12, 17, 22], [5, 9, 14, 20], [4, 11, 16, 23], [6, 10, 15, 21]];
+
+/// The fractional parts of the cube roots of the first 64 primes.
+const SHA256_K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5,
+    0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3,
+    0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc,
+    0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7,
+    0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13,
+    0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3,
+    0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5,
+    0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208,
+    0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+/// The fractional parts of the square roots of the first 8 primes.
+const SHA256_H: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a,
+    0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+/// The fractional parts of the cube roots of the first 80 primes.
+const SHA512_K: [u64; 80] = [
+    0x428a2f98d728ae22, 0x7137449123ef65cd,
+    0xb5c0fbcfec4d3b2f, 0xe9b5dba58189dbbc,
+    0x3956c25bf348b538, 0x59f111f1b605d019,
+    0x923f82a4af194f9b, 0xab1c5ed5da6d8118,
+    0xd807aa98a3030242, 0x12835b0145706fbe,
+    0x243185be4ee4b28c, 0x550c7dc3d5ffb4e2,
+    0x72be5d74f27b896f, 0x80deb1fe3b1696b1,
+    0x9bdc06a725c71235, 0xc19bf174cf692694,
+    0xe49b69c19ef14ad2, 0xefbe4786384f25e3,
+    0x0fc19dc68b8cd5b5, 0x240ca1cc77ac9c65,
+    0x2de92c6f592b0275, 0x4a7484aa6ea6e483,
+    0x5cb0a9dcbd41fbd4, 0x76f988da831153b5,
+    0x983e5152ee66dfab, 0xa831c66d2db43210,
+    0xb00327c898fb213f, 0xbf597fc7beef0ee4,
+    0xc6e00bf33da88fc2, 0xd5a79147930aa725,
+    0x06ca6351e003826f, 0x142929670a0e6e70,
+    0x27b70a8546d22ffc, 0x2e1b21385c26c926,
+    0x4d2c6dfc5ac42aed, 0x53380d139d95b3df,
+    0x650a73548baf63de, 0x766a0abb3c77b2a8,
+    0x81c2c92e47edaee6, 0x92722c851482353b,
+    0xa2bfe8a14cf10364, 0xa81a664bbc423001,
+    0xc24b8b70d0f89791, 0xc76c51a30654be30,
+    0xd192e819d6ef5218, 0xd69906245565a910,
+    0xf40e35855771202a, 0x106aa07032bbd1b8,
+    0x19a4c116b8d2d0c8, 0x1e376c085141ab53,
+    0x2748774cdf8eeb99, 0x34b0bcb5e19b48a8,
+    0x391c0cb3c5c95a63, 0x4ed8aa4ae3418acb,
+    0x5b9cca4f7763e373, 0x682e6ff3d6b2b8a3,
+    0x748f82ee5defb2fc, 0x78a5636f43172f60,
+    0x84c87814a1f0ab72, 0x8cc702081a6439ec,
+    0x90befffa23631e28, 0xa4506cebde82bde9,
+    0xbef9a3f7b2c67915, 0xc67178f2e372532b,
+    0xca273eceea26619c, 0xd186b8c721c0c207,
+    0xeada7dd6cde0eb1e, 0xf57d4f7fee6ed178,
+    0x06f067aa72176fba, 0x0a637dc5a2c898a6,
+    0x113f9804bef90dae, 0x1b710b35131c471b,
+    0x28db77f523047d84, 0x32caab7b40c72493,
+    0x3c9ebe0a15c9bebc, 0x431d67c49c100d4c,
+    0x4cc5d4becb3e42b6, 0x597f299cfc657e2a,
+    0x5fcb6fab3ad6faec, 0x6c44198c4a475817,
+];
+
+/// The fractional parts of the square roots of the first 8 primes.
+const SHA512_H: [u64; 8] = [
+    0x6a09e667f3bcc908, 0xbb67ae8584caa73b,
+    0x3c6ef372fe94f82b, 0xa54ff53a5f1d36f1,
+    0x510e527fade682d1, 0x9b05688c2b3e6c1f,
+    0x1f83d9abfb41bd6b, 0x5be0cd19137e2179,
+];
+
+/// The state of a digest between blocks.
+#[derive(Clone)]
+enum State {
+    Md5([u32; 4]),
+    Sha1([u32; 5]),
+    Sha256([u32; 8]),
+    Sha512([u64; 8]),
+}
+
+impl State {
+    fn new(alg: Algorithm) -> State {
+        match alg {
+            Algorithm::Md5 => State::Md5([0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476]),
+            Algorithm::Sha1 => State::Sha1([0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476, 0xc3d2e1f0]),
+            Algorithm::Sha256 => State::Sha256(SHA256_H),
+            Algorithm::Sha512 => State::Sha512(SHA512_H),
+        }
+    }
+
+    /// Digests a block.
+    fn compress(&mut self, block: &[u8]) {
+        match self {
+            State::Md5(h) => md5(h, block),
+            State::Sha1(h) => sha1(h, block),
+            State::Sha256(h) => sha256(h, block),
+            State::Sha512(h) => sha512(h, block),
+        }
+    }
+
+    fn bytes(&self) -> Vec<u8> {
+        match self {
+            State::Md5(h) => h.iter().flat_map(|w| w.to_le_bytes()).collect(),
+            State::Sha1(h) => h.iter().flat_map(|w| w.to_be_bytes()).collect(),
+            State::Sha256(h) => h.iter().flat_map(|w| w.to_be_bytes()).collect(),
+            State::Sha512(h) => h.iter().flat_map(|w| w.to_be_bytes()).collect(),
+        }
+    }
+}
+
+fn md5(h: &mut [u32; 4], block: &[u8]) {
+    let mut m = [0u32; 16];
+    for (w, b) in m.iter_mut().zip(block.chunks_exact(4)) {
+        *w = u32::from_le_bytes(b.try_into().unwrap());
+    }
+    let [mut a, mut b, mut c, mut d] = *h;
+    for i in 0..64 {
+        let (f, g) = match i / 16 {
+            0 => ((b & c) | (!b & d), i),
+            1 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+            2 => (b ^ c ^ d, (3 * i + 5) % 16),
+            _ => (c ^ (b | !d), (7 * i) % 16),
+        };
+        let f = f.wrapping_add(a).wrapping_add(MD5_K[i]).wrapping_add(m[g]);
+        a = d;
+        d = c;
+        c = b;
+        b = b.wrapping_add(f.rotate_left(MD5_S[i / 16][i % 4]));
+    }
+    for (x, y) in h.iter_mut().zip([a, b, c, d]) {
+        *x = x.wrapping_add(y);
+    }
+}
+
+fn sha1(h: &mut [u32; 5], block: &[u8]) {
+    let mut w = [0u32; 80];
+    for (w, b) in w.iter_mut().zip(block.chunks_exact(4)) {
+        *w = u32::from_be_bytes(b.try_into().unwrap());
+    }
+    for i in 16..80 {
+        w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+    }
+    let [mut a, mut b, mut c, mut d, mut e] = *h;
+    for (i, &w) in w.iter().enumerate() {
+        let (f, k) = match i / 20 {
+            0 => ((b & c) | (!b & d), 0x5a827999),
+            1 => (b ^ c ^ d, 0x6ed9eba1),
+            2 => ((b & c) | (b & d) | (c & d), 0x8f1bbcdc),
+            _ => (b ^ c ^ d, 0xca62c1d6),
+        };
+        let t = a.rotate_left(5).wrapping_add(f).wrapping_add(e).wrapping_add(k).wrapping_add(w);
+        e = d;
+        d = c;
+        c = b.rotate_left(30);
+        b = a;
+        a = t;
+    }
+    for (x, y) in h.iter_mut().zip([a, b, c, d, e]) {
+        *x = x.wrapping_add(y);
+    }
+}
+
+fn sha256(h: &mut [u32; 8], block: &[u8]) {
+    let mut w = [0u32; 64];
+    for (w, b) in w.iter_mut().zip(block.chunks_exact(4)) {
+        *w = u32::from_be_bytes(b.try_into().unwrap());
+    }
+    for i in 16..64 {
+        let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+        let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+        w[i] = w[i - 16].wrapping_add(s0).wrapping_add(w[i - 7]).wrapping_add(s1);
+    }
+    let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut hh] = *h;
+    for (&k, &w) in SHA256_K.iter().zip(w.iter()) {
+        let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+        let ch = (e & f) ^ (!e & g);
+        let t1 = hh.wrapping_add(s1).wrapping_add(ch).wrapping_add(k).wrapping_add(w);
+        let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+        let maj = (a & b) ^ (a & c) ^ (b & c);
+        hh = g;
+        g = f;
+        f = e;
+        e = d.wrapping_add(t1);
+        d = c;
+        c = b;
+        b = a;
+        a = t1.wrapping_add(s0.wrapping_add(maj));
+    }
+    for (x, y) in h.iter_mut().zip([a, b, c, d, e, f, g, hh]) {
+        *x = x.wrapping_add(y);
+    }
+}
+
+fn sha512(h: &mut [u64; 8], block: &[u8]) {
+    let mut w = [0u64; 80];
+    for (w, b) in w.iter_mut().zip(block.chunks_exact(8)) {
+        *w = u64::from_be_bytes(b.try_into().unwrap());
+    }
+    for i in 16..80 {
+        let s0 = w[i - 15].rotate_right(1) ^ w[i - 15].rotate_right(8) ^ (w[i - 15] >> 7);
+        let s1 = w[i - 2].rotate_right(19) ^ w[i - 2].rotate_right(61) ^ (w[i - 2] >> 6);
+        w[i] = w[i - 16].wrapping_add(s0).wrapping_add(w[i - 7]).wrapping_add(s1);
+    }
+    let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut hh] = *h;
+    for (&k, &w) in SHA512_K.iter().zip(w.iter()) {
+        let s1 = e.rotate_right(14) ^ e.rotate_right(18) ^ e.rotate_right(41);
+        let ch = (e & f) ^ (!e & g);
+        let t1 = hh.wrapping_add(s1).wrapping_add(ch).wrapping_add(k).wrapping_add(w);
+        let s0 = a.rotate_right(28) ^ a.rotate_right(34) ^ a.rotate_right(39);
+        let maj = (a & b) ^ (a & c) ^ (b & c);
+        hh = g;
+        g = f;
+        f = e;
+        e = d.wrapping_add(t1);
+        d = c;
+        c = b;
+        b = a;
+        a = t1.wrapping_add(s0.wrapping_add(maj));
+    }
+    for (x, y) in h.iter_mut().zip([a, b, c, d, e, f, g, hh]) {
+        *x = x.wrapping_add(y);
+    }
+}
+
+/// A message digest.
+#[derive(Clone)]
+pub struct Digest {
+    alg: Algorithm,
+    state: State,
+    /// The data written since the last complete block.
+    block: Vec<u8>,
+    bytes: u64,
+}
+
+impl Digest {
+    pub fn new(alg: Algorithm) -> Digest {
+        Digest { alg, state: State::new(alg), block: Vec::new(), bytes: 0 }
+    }
+
+    pub fn algorithm(&self) -> Algorithm {
+        self.alg
+    }
+
+    fn update(&mut self, mut buf: &[u8]) {
+        let size = self.alg.block_size();
+        if !self.block.is_empty() {
+            let n = buf.len().min(size - self.block.len());
+            self.block.extend_from_slice(&buf[..n]);
+            buf = &buf[n..];
+            if self.block.len() < size {
+                return;
+            }
+            self.state.compress(&self.block);
+            self.block.clear();
+        }
+        let mut blocks = buf.chunks_exact(size);
+        for block in &mut blocks {
+            self.state.compress(block);
+        }
+        self.block.extend_from_slice(blocks.remainder());
+    }
+
+    /// Returns the digest of the data written so far.
+    pub fn value(&self) -> Vec<u8> {
+        // The data is padded with a one bit and then zeros, leaving room
+        // at the end of the last block for its length in bits.
+        let mut digest = self.clone();
+        let size = self.alg.block_size();
+        let room = size / 8;
+        let mut pad = vec![0x80];
+        pad.resize((2 * size - room - 1 - self.block.len()) % size + 1, 0);
+        let bits = self.bytes.wrapping_mul(8);
+        match self.alg {
+            Algorithm::Md5 => pad.extend_from_slice(&bits.to_le_bytes()),
+            _ => {
+                pad.resize(pad.len() + room - 8, 0);
+                pad.extend_from_slice(&bits.to_be_bytes());
+            },
+        }
+        digest.update(&pad);
+        digest.state.bytes()
+    }
+
+    /// Returns the number of bytes digested.
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+}
+
+impl Write for Digest {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.update(buf);
+        self.bytes += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Returns bytes, such as a digest, as a string of hexadecimal digits.
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[test]
+fn test_digests() {
+    let cases = [
+        (Algorithm::Md5, "900150983cd24fb0d6963f7d28e17f72", "cabe45dcc9ae5b66ba86600cca6b8ba8"),
+        (Algorithm::Sha1, "a9993e364706816aba3e25717850c26c9cd0d89d",
+         "291e9a6c66994949b57ba5e650361e98fc36b1ba"),
+        (Algorithm::Sha256, "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+         "41edece42d63e8d9bf515a9ba6932e1c20cbc9f5a5d134645adb5db1b9737ea3"),
+        (Algorithm::Sha512,
+         "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a\
+          2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f",
+         "67ba5535a46e3f86dbfbed8cbbaf0125c76ed549ff8b0b9e03e0c88cf90fa634\
+          fa7b12b47d77b694de488ace8d9a65967dc96df599727d3292a8d9d447709c97"),
+    ];
+    for (alg, abc, a1000) in cases {
+        let mut digest = Digest::new(alg);
+        digest.write_all(b"ab").unwrap();
+        digest.write_all(b"c").unwrap();
+        assert_eq!(hex(&digest.value()), abc);
+        assert_eq!(digest.value().len(), alg.size());
+        // Written in pieces that straddle the blocks.
+        let mut digest = Digest::new(alg);
+        for n in [1, 63, 100, 300, 536] {
+            digest.write_all(&vec![b'a'; n]).unwrap();
+        }
+        assert_eq!((hex(&digest.value()), digest.bytes()), (a1000.to_string(), 1000));
+        assert_eq!(Algorithm::from_name(alg.name()), Some(alg));
+    }
+    assert_eq!(hex(&Digest::new(Algorithm::Md5).value()), "d41d8cd98f00b204e9800998ecf8427e");
+}
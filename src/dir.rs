@@ -0,0 +1,301 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+//! Open directories, in which files are found by name with openat,
+//! fstatat and unlinkat rather than by path. A path is looked up afresh
+//! each time it is used, so a directory in it could be swapped for a
+//! symbolic link between one use and the next, sending a recursive command
+//! somewhere else entirely; a name in an open directory can only refer to
+//! a file in that directory.
+use std::ffi::{CStr, CString, OsStr, OsString};
+use std::fs::{File, Metadata};
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, IntoRawFd, OwnedFd, RawFd};
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::path::Path;
+
+use crate::trace;
+
+fn c_name(name: &OsStr) -> io::Result<CString> {
+    Ok(CString::new(name.as_bytes())?)
+}
+
+fn check(ret: libc::c_int) -> io::Result<libc::c_int> {
+    match ret {
+        -1 => Err(io::Error::last_os_error()),
+        n => Ok(n),
+    }
+}
+
+/// Sets errno to zero, so that readdir's errors can be told from the end
+/// of the directory.
+fn clear_errno() {
+    // SAFETY: each of these returns the location of the calling thread's
+    // errno.
+    #[cfg(target_os = "linux")]
+    unsafe { *libc::__errno_location() = 0 };
+    #[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd",
+              target_os = "dragonfly"))]
+    unsafe { *libc::__error() = 0 };
+    #[cfg(any(target_os = "android", target_os = "openbsd", target_os = "netbsd"))]
+    unsafe { *libc::__errno() = 0 };
+}
+
+/// What fstatat says of a file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Stat {
+    pub dev: u64,
+    pub ino: u64,
+    pub mode: u32,
+}
+
+impl Stat {
+    // mode_t is u16 on some systems, such as macOS, so the casts are
+    // needed there.
+    #[allow(clippy::unnecessary_cast)]
+    pub fn is_dir(&self) -> bool {
+        self.mode & libc::S_IFMT as u32 == libc::S_IFDIR as u32
+    }
+
+    #[allow(clippy::unnecessary_cast)]
+    pub fn is_symlink(&self) -> bool {
+        self.mode & libc::S_IFMT as u32 == libc::S_IFLNK as u32
+    }
+}
+
+/// The number of fchmodat2, which Linux has had since 6.6, and which is
+/// the same on every architecture.
+#[cfg(target_os = "linux")]
+const SYS_FCHMODAT2: libc::c_long = 452;
+
+/// An open directory.
+#[derive(Debug)]
+pub struct Dir {
+    fd: OwnedFd,
+}
+
+impl Dir {
+    fn open_at(fd: RawFd, name: &OsStr, follow: bool) -> io::Result<Dir> {
+        let cname = c_name(name)?;
+        let mut flags = libc::O_RDONLY | libc::O_DIRECTORY | libc::O_CLOEXEC;
+        if !follow {
+            flags |= libc::O_NOFOLLOW;
+        }
+        // SAFETY: cname is a valid C string.
+        let fd = trace::op("opendir", format_args!("{:?}", name),
+                           check(unsafe { libc::openat(fd, cname.as_ptr(), flags) }))?;
+        // SAFETY: openat has just returned the descriptor, which nothing
+        // else owns.
+        Ok(Dir { fd: unsafe { OwnedFd::from_raw_fd(fd) } })
+    }
+
+    /// Opens the directory at the path, which may be a symbolic link to
+    /// one.
+    pub fn open(path: &Path) -> io::Result<Dir> {
+        Dir::open_at(libc::AT_FDCWD, path.as_os_str(), true)
+    }
+
+    /// Opens the named directory in this one. If it is a symbolic link, it
+    /// is followed only if follow is true, and is otherwise an error.
+    pub fn open_dir(&self, name: &OsStr, follow: bool) -> io::Result<Dir> {
+        Dir::open_at(self.fd.as_raw_fd(), name, follow)
+    }
+
+    /// Returns the metadata of the directory itself.
+    pub fn metadata(&self) -> io::Result<Metadata> {
+        File::from(self.fd.try_clone()?).metadata()
+    }
+
+    /// Returns what fstatat says of the named file, not following it if
+    /// it is a symbolic link.
+    pub fn stat(&self, name: &OsStr) -> io::Result<Stat> {
+        let cname = c_name(name)?;
+        // SAFETY: stat is plain data, which fstatat fills in.
+        let mut st: libc::stat = unsafe { std::mem::zeroed() };
+        // SAFETY: cname is a valid C string and st is a stat to fill in.
+        check(unsafe {
+            libc::fstatat(self.fd.as_raw_fd(), cname.as_ptr(), &mut st, libc::AT_SYMLINK_NOFOLLOW)
+        })?;
+        Ok(Stat { dev: st.st_dev as u64, ino: st.st_ino as u64, mode: st.st_mode as u32 })
+    }
+
+    /// Changes the mode of the named file. A symbolic link is never
+    /// followed, and its mode can't be changed.
+    pub fn chmod(&self, name: &OsStr, mode: u32) -> io::Result<()> {
+        let cname = c_name(name)?;
+        // Linux's fchmodat ignores AT_SYMLINK_NOFOLLOW, which glibc
+        // emulates through /proc and musl not at all, so fchmodat2 is
+        // tried first, and without it the file is checked not to be a
+        // link before fchmodat follows it.
+        #[cfg(target_os = "linux")]
+        {
+            // SAFETY: cname is a valid C string.
+            let ret = unsafe {
+                libc::syscall(SYS_FCHMODAT2, self.fd.as_raw_fd(), cname.as_ptr(), mode, libc::AT_SYMLINK_NOFOLLOW)
+            };
+            match check(ret as libc::c_int) {
+                Ok(_) => (),
+                Err(e) if e.raw_os_error() == Some(libc::ENOSYS) => self.chmod_unless_link(name, mode)?,
+                Err(e) => return Err(e),
+            }
+        }
+        #[cfg(not(target_os = "linux"))]
+        // SAFETY: cname is a valid C string.
+        check(unsafe {
+            libc::fchmodat(self.fd.as_raw_fd(), cname.as_ptr(), mode as _, libc::AT_SYMLINK_NOFOLLOW)
+        })?;
+        Ok(())
+    }
+
+    /// Changes the mode of the named file, as chmod does where there is no
+    /// fchmodat2.
+    #[cfg(target_os = "linux")]
+    fn chmod_unless_link(&self, name: &OsStr, mode: u32) -> io::Result<()> {
+        if self.stat(name)?.is_symlink() {
+            return Err(io::Error::from_raw_os_error(libc::EOPNOTSUPP));
+        }
+        let cname = c_name(name)?;
+        // SAFETY: cname is a valid C string.
+        check(unsafe { libc::fchmodat(self.fd.as_raw_fd(), cname.as_ptr(), mode, 0) })?;
+        Ok(())
+    }
+
+    /// Changes the owner and group of the named file, either of which may
+    /// be left as it is. A symbolic link is changed itself unless it is
+    /// to be followed.
+    pub fn chown(&self, name: &OsStr, uid: Option<u32>, gid: Option<u32>, follow: bool) -> io::Result<()> {
+        let cname = c_name(name)?;
+        // An id of -1 leaves it as it is.
+        let (uid, gid) = (uid.unwrap_or(u32::MAX), gid.unwrap_or(u32::MAX));
+        // SAFETY: cname is a valid C string.
+        check(unsafe {
+            libc::fchownat(self.fd.as_raw_fd(), cname.as_ptr(), uid, gid, if follow { 0 } else { libc::AT_SYMLINK_NOFOLLOW })
+        })?;
+        Ok(())
+    }
+
+    /// Returns the names of the files in the directory, other than . and
+    /// .., in the order the directory gives them.
+    pub fn names(&self) -> io::Result<Vec<OsString>> {
+        // The stream owns the descriptor it is given, so it is given a
+        // copy.
+        let fd = self.fd.try_clone()?.into_raw_fd();
+        // SAFETY: fd is an open directory, which the stream takes over.
+        let stream = unsafe { libc::fdopendir(fd) };
+        if stream.is_null() {
+            let e = io::Error::last_os_error();
+            // SAFETY: fd is still ours, the stream not having been made.
+            unsafe { libc::close(fd) };
+            return Err(e);
+        }
+        // The copy shares its offset with the original, which may already
+        // have been read.
+        // SAFETY: stream is an open directory stream.
+        unsafe { libc::rewinddir(stream) };
+        let mut names = Vec::new();
+        let result = loop {
+            clear_errno();
+            // SAFETY: stream is an open directory stream.
+            let ent = unsafe { libc::readdir(stream) };
+            if ent.is_null() {
+                let e = io::Error::last_os_error();
+                break if e.raw_os_error().unwrap_or(0) == 0 { Ok(()) } else { Err(e) };
+            }
+            // SAFETY: readdir has returned an entry, whose name is a C
+            // string valid until the next call.
+            let name = unsafe { CStr::from_ptr((*ent).d_name.as_ptr()) }.to_bytes();
+            if name != b"." && name != b".." {
+                names.push(OsString::from_vec(name.to_vec()));
+            }
+        };
+        // SAFETY: stream is an open directory stream, closed only here.
+        unsafe { libc::closedir(stream) };
+        result.map(|_| names)
+    }
+
+    /// Removes the named file or, if dir is true, the named empty
+    /// directory.
+    pub fn unlink(&self, name: &OsStr, dir: bool) -> io::Result<()> {
+        let cname = c_name(name)?;
+        let (op, flags) = if dir { ("rmdir", libc::AT_REMOVEDIR) } else { ("unlink", 0) };
+        // SAFETY: cname is a valid C string.
+        let result = check(unsafe { libc::unlinkat(self.fd.as_raw_fd(), cname.as_ptr(), flags) });
+        trace::op(op, format_args!("{:?}", name), result)?;
+        Ok(())
+    }
+
+    /// Removes the named file and, if it is a directory, everything in
+    /// it. Symbolic links are removed, not followed.
+    pub fn remove_all(&self, name: &OsStr) -> io::Result<()> {
+        if !self.stat(name)?.is_dir() {
+            return self.unlink(name, false);
+        }
+        // Should the directory have been swapped for a link since, opening
+        // it fails rather than following the link.
+        let dir = self.open_dir(name, false)?;
+        for entry in dir.names()? {
+            dir.remove_all(&entry)?;
+        }
+        self.unlink(name, true)
+    }
+}
+
+/// Removes the file at the path and, if it is a directory, everything in
+/// it, as rm -r does.
+pub fn remove_all(path: &Path) -> io::Result<()> {
+    let name = path.file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "invalid path"))?;
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    Dir::open(parent)?.remove_all(name)
+}
+
+#[test]
+fn test_remove_all() {
+    use std::fs;
+    use crate::tempfile;
+
+    let root = tempfile::temp_dir().join(format!("dir-test.{}", std::process::id()));
+    fs::create_dir_all(root.join("a/b")).unwrap();
+    fs::write(root.join("a/b/f"), "x").unwrap();
+    fs::write(root.join("kept"), "x").unwrap();
+    std::os::unix::fs::symlink(root.join("kept"), root.join("a/link")).unwrap();
+    let dir = Dir::open(&root).unwrap();
+    let mut names = dir.open_dir(OsStr::new("a"), false).unwrap().names().unwrap();
+    names.sort();
+    assert_eq!(names, ["b", "link"]);
+    assert!(dir.stat(OsStr::new("a")).unwrap().is_dir());
+    assert!(dir.open_dir(OsStr::new("kept"), false).is_err());
+    remove_all(&root.join("a")).unwrap();
+    assert!(!root.join("a").exists());
+    assert!(root.join("kept").exists());
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn test_chmod() {
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+    use crate::tempfile;
+
+    let root = tempfile::temp_dir().join(format!("dir-chmod.{}", std::process::id()));
+    fs::create_dir_all(&root).unwrap();
+    fs::write(root.join("f"), "x").unwrap();
+    std::os::unix::fs::symlink("f", root.join("l")).unwrap();
+    let mode = || fs::metadata(root.join("f")).unwrap().permissions().mode() & 0o7777;
+    let dir = Dir::open(&root).unwrap();
+    dir.chmod(OsStr::new("f"), 0o600).unwrap();
+    assert_eq!(mode(), 0o600);
+    assert!(dir.chmod(OsStr::new("l"), 0o644).is_err());
+    assert_eq!(mode(), 0o600);
+    #[cfg(target_os = "linux")]
+    {
+        dir.chmod_unless_link(OsStr::new("f"), 0o640).unwrap();
+        assert_eq!(mode(), 0o640);
+        assert!(dir.chmod_unless_link(OsStr::new("l"), 0o644).is_err());
+        assert_eq!(mode(), 0o640);
+    }
+    fs::remove_dir_all(&root).unwrap();
+}
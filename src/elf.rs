@@ -0,0 +1,292 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+//! A minimal reader of ELF object files: the section headers and the
+//! symbol table, in either class and byte order.
+use std::io;
+
+/// The section header type of a symbol table.
+pub const SHT_SYMTAB: u32 = 2;
+/// The section header type of a section that occupies no space in the file.
+pub const SHT_NOBITS: u32 = 8;
+/// The section header type of a dynamic symbol table.
+pub const SHT_DYNSYM: u32 = 11;
+
+/// The section flags.
+pub const SHF_WRITE: u64 = 0x1;
+pub const SHF_ALLOC: u64 = 0x2;
+pub const SHF_EXECINSTR: u64 = 0x4;
+
+/// The symbol bindings.
+pub const STB_LOCAL: u8 = 0;
+pub const STB_GLOBAL: u8 = 1;
+pub const STB_WEAK: u8 = 2;
+pub const STB_GNU_UNIQUE: u8 = 10;
+
+/// The symbol types.
+pub const STT_NOTYPE: u8 = 0;
+pub const STT_OBJECT: u8 = 1;
+pub const STT_FUNC: u8 = 2;
+pub const STT_SECTION: u8 = 3;
+pub const STT_FILE: u8 = 4;
+pub const STT_COMMON: u8 = 5;
+pub const STT_TLS: u8 = 6;
+pub const STT_GNU_IFUNC: u8 = 10;
+
+/// The special section indexes.
+pub const SHN_UNDEF: u16 = 0;
+pub const SHN_ABS: u16 = 0xfff1;
+pub const SHN_COMMON: u16 = 0xfff2;
+pub const SHN_XINDEX: u16 = 0xffff;
+
+const MAGIC: &[u8] = b"\x7fELF";
+
+/// Returns whether the data starts like an ELF file.
+pub fn is_elf(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+/// A section header.
+#[derive(Clone, Debug)]
+pub struct Section {
+    pub name: String,
+    pub kind: u32,
+    pub flags: u64,
+    pub addr: u64,
+    pub offset: u64,
+    pub size: u64,
+    pub link: u32,
+    pub entsize: u64,
+}
+
+/// A symbol table entry.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Symbol {
+    pub name: String,
+    pub value: u64,
+    pub size: u64,
+    /// The binding, such as STB_GLOBAL.
+    pub bind: u8,
+    /// The type, such as STT_FUNC.
+    pub kind: u8,
+    /// The index of the section in which the symbol is defined, or a
+    /// special index such as SHN_UNDEF.
+    pub section: u16,
+}
+
+/// An ELF file.
+pub struct Elf<'a> {
+    data: &'a [u8],
+    /// Whether this is a 64-bit file.
+    pub is_64: bool,
+    little_endian: bool,
+    /// The object file type, such as 1 for relocatable or 2 for
+    /// executable.
+    pub file_type: u16,
+    pub sections: Vec<Section>,
+}
+
+impl<'a> Elf<'a> {
+    /// Parses the header and section headers of an ELF file.
+    pub fn parse(data: &'a [u8]) -> io::Result<Elf<'a>> {
+        if !is_elf(data) || data.len() < 16 {
+            return Err(invalid("file format not recognized"));
+        }
+        let is_64 = match data[4] {
+            1 => false,
+            2 => true,
+            _ => return Err(invalid("unknown ELF class")),
+        };
+        let little_endian = match data[5] {
+            1 => true,
+            2 => false,
+            _ => return Err(invalid("unknown ELF byte order")),
+        };
+        let mut elf = Elf { data, is_64, little_endian, file_type: 0, sections: Vec::new() };
+        elf.file_type = elf.u16_at(16)?;
+        let (shoff, shentsize, mut shnum, mut shstrndx) = if is_64 {
+            (elf.u64_at(40)?, elf.u16_at(58)? as u64, elf.u16_at(60)? as u64, elf.u16_at(62)?)
+        } else {
+            (elf.u32_at(32)? as u64, elf.u16_at(46)? as u64, elf.u16_at(48)? as u64, elf.u16_at(50)?)
+        };
+        if shoff == 0 {
+            return Ok(elf);
+        }
+        // With many sections, the real count and string table index are
+        // kept in the first section header.
+        let (first, _) = elf.section_at(shoff)?;
+        if shnum == 0 {
+            shnum = first.size;
+        }
+        if shstrndx == SHN_XINDEX {
+            shstrndx = first.link as u16;
+        }
+        let mut sections = Vec::new();
+        let mut names = Vec::new();
+        for i in 0..shnum {
+            let offset = i.checked_mul(shentsize).and_then(|n| n.checked_add(shoff))
+                .ok_or_else(|| invalid("bad section header offset"))?;
+            let (section, name) = elf.section_at(offset)?;
+            sections.push(section);
+            names.push(name);
+        }
+        if let Some(strtab) = sections.get(shstrndx as usize) {
+            let strtab = elf.contents(strtab)?;
+            for (section, name) in sections.iter_mut().zip(names) {
+                section.name = string_at(strtab, name as usize);
+            }
+        }
+        elf.sections = sections;
+        Ok(elf)
+    }
+
+    fn bytes(&self, offset: u64, len: usize) -> io::Result<&'a [u8]> {
+        let start = usize::try_from(offset).map_err(|_| invalid("offset out of range"))?;
+        start.checked_add(len).and_then(|end| self.data.get(start..end))
+            .ok_or_else(|| invalid("truncated file"))
+    }
+
+    fn u16_at(&self, offset: u64) -> io::Result<u16> {
+        let b = self.bytes(offset, 2)?.try_into().unwrap();
+        Ok(if self.little_endian { u16::from_le_bytes(b) } else { u16::from_be_bytes(b) })
+    }
+
+    fn u32_at(&self, offset: u64) -> io::Result<u32> {
+        let b = self.bytes(offset, 4)?.try_into().unwrap();
+        Ok(if self.little_endian { u32::from_le_bytes(b) } else { u32::from_be_bytes(b) })
+    }
+
+    fn u64_at(&self, offset: u64) -> io::Result<u64> {
+        let b = self.bytes(offset, 8)?.try_into().unwrap();
+        Ok(if self.little_endian { u64::from_le_bytes(b) } else { u64::from_be_bytes(b) })
+    }
+
+    /// Reads a word: 8 bytes in a 64-bit file or 4 in a 32-bit one.
+    fn word_at(&self, offset: u64) -> io::Result<u64> {
+        if self.is_64 { self.u64_at(offset) } else { Ok(self.u32_at(offset)? as u64) }
+    }
+
+    /// Reads the section header at the offset. Returns the section, with
+    /// its name left empty, and the offset of its name in the section
+    /// header string table.
+    fn section_at(&self, offset: u64) -> io::Result<(Section, u32)> {
+        let w = if self.is_64 { 8 } else { 4 };
+        self.bytes(offset, 16 + 6 * w as usize)?;
+        let section = Section {
+            name: String::new(),
+            kind: self.u32_at(offset + 4)?,
+            flags: self.word_at(offset + 8)?,
+            addr: self.word_at(offset + 8 + w)?,
+            offset: self.word_at(offset + 8 + 2 * w)?,
+            size: self.word_at(offset + 8 + 3 * w)?,
+            link: self.u32_at(offset + 8 + 4 * w)?,
+            entsize: self.word_at(offset + 16 + 5 * w)?,
+        };
+        Ok((section, self.u32_at(offset)?))
+    }
+
+    /// Returns the contents of a section, which are empty for a section
+    /// that occupies no space in the file.
+    pub fn contents(&self, section: &Section) -> io::Result<&'a [u8]> {
+        if section.kind == SHT_NOBITS {
+            return Ok(&[]);
+        }
+        let len = usize::try_from(section.size).map_err(|_| invalid("section too large"))?;
+        self.bytes(section.offset, len)
+    }
+
+    /// Returns the symbols in the first section of the given type,
+    /// SHT_SYMTAB or SHT_DYNSYM, without the null symbol at index 0.
+    /// Returns None if there is no such section.
+    pub fn symbols(&self, kind: u32) -> io::Result<Option<Vec<Symbol>>> {
+        let table = match self.sections.iter().find(|section| section.kind == kind) {
+            Some(table) => table,
+            None => return Ok(None),
+        };
+        let strtab = match self.sections.get(table.link as usize) {
+            Some(section) => self.contents(section)?,
+            None => &[],
+        };
+        // Checking that the whole table is in the file means that the
+        // offsets of the entries can't overflow.
+        self.contents(table)?;
+        let entsize = if self.is_64 { 24 } else { 16 };
+        let count = table.size / entsize;
+        let mut symbols = Vec::new();
+        for i in 1..count {
+            let offset = table.offset + i * entsize;
+            let (value, size, info_offset) = if self.is_64 {
+                (self.u64_at(offset + 8)?, self.u64_at(offset + 16)?, offset + 4)
+            } else {
+                (self.u32_at(offset + 4)? as u64, self.u32_at(offset + 8)? as u64, offset + 12)
+            };
+            let info = self.bytes(info_offset, 1)?[0];
+            symbols.push(Symbol {
+                name: string_at(strtab, self.u32_at(offset)? as usize),
+                value,
+                size,
+                bind: info >> 4,
+                kind: info & 0xf,
+                section: self.u16_at(info_offset + 2)?,
+            });
+        }
+        Ok(Some(symbols))
+    }
+}
+
+/// Returns the NUL-terminated string at the offset in a string table, or
+/// an empty string if the offset is out of range.
+fn string_at(strtab: &[u8], offset: usize) -> String {
+    let s = strtab.get(offset..).unwrap_or(&[]);
+    let end = s.iter().position(|&c| c == 0).unwrap_or(s.len());
+    String::from_utf8_lossy(&s[..end]).into_owned()
+}
+
+#[test]
+fn test_parse() {
+    // A 32-bit big-endian file with a symbol table, its string table and
+    // the section header string table.
+    let mut data = vec![0u8; 280];
+    data[..7].copy_from_slice(b"\x7fELF\x01\x02\x01");
+    data[16..18].copy_from_slice(&1u16.to_be_bytes());
+    data[32..36].copy_from_slice(&120u32.to_be_bytes());
+    data[46..48].copy_from_slice(&40u16.to_be_bytes());
+    data[48..50].copy_from_slice(&4u16.to_be_bytes());
+    data[50..52].copy_from_slice(&3u16.to_be_bytes());
+    data[52..57].copy_from_slice(b"\0foo\0");
+    data[76..80].copy_from_slice(&1u32.to_be_bytes());
+    data[80..84].copy_from_slice(&0x1234u32.to_be_bytes());
+    data[84..88].copy_from_slice(&4u32.to_be_bytes());
+    data[88] = (STB_GLOBAL << 4) | STT_FUNC;
+    data[90..92].copy_from_slice(&SHN_ABS.to_be_bytes());
+    data[92..119].copy_from_slice(b"\0.symtab\0.strtab\0.shstrtab\0");
+    for (i, (name, kind, offset, size, link)) in
+        [(1u32, SHT_SYMTAB, 60u32, 32u32, 2u32), (9, 3, 52, 5, 0), (17, 3, 92, 27, 0)].iter().enumerate() {
+        let sh = 160 + i * 40;
+        data[sh..sh + 4].copy_from_slice(&name.to_be_bytes());
+        data[sh + 4..sh + 8].copy_from_slice(&kind.to_be_bytes());
+        data[sh + 16..sh + 20].copy_from_slice(&offset.to_be_bytes());
+        data[sh + 20..sh + 24].copy_from_slice(&size.to_be_bytes());
+        data[sh + 24..sh + 28].copy_from_slice(&link.to_be_bytes());
+    }
+
+    let elf = Elf::parse(&data).unwrap();
+    assert!(!elf.is_64);
+    assert_eq!(elf.file_type, 1);
+    let names: Vec<&str> = elf.sections.iter().map(|s| s.name.as_str()).collect();
+    assert_eq!(names, ["", ".symtab", ".strtab", ".shstrtab"]);
+    assert_eq!(elf.symbols(SHT_SYMTAB).unwrap(), Some(vec![Symbol {
+        name: "foo".to_string(),
+        value: 0x1234,
+        size: 4,
+        bind: STB_GLOBAL,
+        kind: STT_FUNC,
+        section: SHN_ABS,
+    }]));
+    assert_eq!(elf.symbols(SHT_DYNSYM).unwrap(), None);
+    assert!(Elf::parse(&data[..200]).is_err());
+}
@@ -0,0 +1,218 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+//! Shell-style pattern matching, as used by find -name and by the shell
+//! for file name generation. A pattern may contain `*` (any string), `?`
+//! (any byte), `[...]` (any byte in the set, `!` negating it, with ranges
+//! and `[:class:]` names) and `\` quoting the next byte.
+use std::ffi::OsStr;
+use std::fs;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+use crate::regex;
+
+/// Matches a bracket expression starting just after the `[` at the start
+/// of the pattern. Returns whether the byte matched and the length of the
+/// expression (excluding the `[`), or None if it isn't terminated, in
+/// which case the `[` matches itself.
+fn match_bracket(pat: &[u8], c: u8) -> Option<(bool, usize)> {
+    let mut i = 0;
+    let negate = matches!(pat.first(), Some(b'!') | Some(b'^'));
+    if negate {
+        i += 1;
+    }
+    let mut matched = false;
+    let mut first = true;
+    loop {
+        let lo = *pat.get(i)?;
+        if lo == b']' && !first {
+            return Some((matched != negate, i + 1));
+        }
+        first = false;
+        if lo == b'[' && pat.get(i + 1) == Some(&b':') {
+            if let Some(len) = pat[i + 2..].windows(2).position(|w| w == b":]") {
+                let name = String::from_utf8_lossy(&pat[i + 2..i + 2 + len]);
+                matched |= regex::in_class(&name, c).unwrap_or(false);
+                i += len + 4;
+                continue;
+            }
+        }
+        let lo = if lo == b'\\' && i + 1 < pat.len() {
+            i += 1;
+            pat[i]
+        } else {
+            lo
+        };
+        i += 1;
+        if pat.get(i) == Some(&b'-') && pat.get(i + 1).is_some_and(|&hi| hi != b']') {
+            let hi = pat[i + 1];
+            matched |= lo <= c && c <= hi;
+            i += 2;
+        } else {
+            matched |= lo == c;
+        }
+    }
+}
+
+/// Returns true if the whole of the name matches the pattern.
+pub fn matches(pat: &[u8], name: &[u8]) -> bool {
+    let (mut p, mut n) = (0, 0);
+    // Where to resume if what follows the last * fails to match: the
+    // pattern position after the * and the name position it consumed up
+    // to.
+    let mut resume: Option<(usize, usize)> = None;
+    loop {
+        if p < pat.len() {
+            let step = match pat[p] {
+                b'*' => {
+                    resume = Some((p + 1, n));
+                    p += 1;
+                    continue;
+                },
+                b'?' if n < name.len() => Some(1),
+                b'[' if n < name.len() => match match_bracket(&pat[p + 1..], name[n]) {
+                    Some((true, len)) => Some(len + 1),
+                    Some((false, _)) => None,
+                    None if name[n] == b'[' => Some(1),
+                    None => None,
+                },
+                b'\\' if p + 1 < pat.len() => {
+                    if n < name.len() && name[n] == pat[p + 1] { Some(2) } else { None }
+                },
+                c if n < name.len() && name[n] == c => Some(1),
+                _ => None,
+            };
+            if let Some(len) = step {
+                p += len;
+                n += 1;
+                continue;
+            }
+        } else if n == name.len() {
+            return true;
+        }
+        match resume {
+            Some((rp, rn)) if rn < name.len() => {
+                resume = Some((rp, rn + 1));
+                p = rp;
+                n = rn + 1;
+            },
+            _ => return false,
+        }
+    }
+}
+
+/// Returns whether the pattern has an unquoted `*`, `?` or `[`, and so
+/// can match names other than itself.
+pub fn is_pattern(pat: &[u8]) -> bool {
+    let mut i = 0;
+    while i < pat.len() {
+        match pat[i] {
+            b'*' | b'?' | b'[' => return true,
+            b'\\' => i += 2,
+            _ => i += 1,
+        }
+    }
+    false
+}
+
+/// Removes the quoting from a pattern.
+fn unquote(pat: &[u8]) -> Vec<u8> {
+    let mut name = Vec::new();
+    let mut i = 0;
+    while i < pat.len() {
+        if pat[i] == b'\\' && i + 1 < pat.len() {
+            i += 1;
+        }
+        name.push(pat[i]);
+        i += 1;
+    }
+    name
+}
+
+/// Returns the names of the files that match a pattern, sorted, as the
+/// shell generates them. A `/` in a name must be matched by one in the
+/// pattern, as must a `.` at the start of a name. There are no names if
+/// nothing matches.
+pub fn expand(pat: &[u8]) -> Vec<Vec<u8>> {
+    let (mut names, rest) = match pat.strip_prefix(b"/") {
+        Some(rest) => (vec![b"/".to_vec()], rest),
+        None => (vec![Vec::new()], pat),
+    };
+    let components: Vec<&[u8]> = rest.split(|&c| c == b'/').collect();
+    for (i, component) in components.iter().enumerate() {
+        let last = i + 1 == components.len();
+        let mut next = Vec::new();
+        for prefix in &names {
+            let mut push = |name: &[u8]| {
+                let mut path = prefix.clone();
+                path.extend_from_slice(name);
+                if !last {
+                    path.push(b'/');
+                }
+                next.push(path);
+            };
+            if !is_pattern(component) {
+                // Whether the file exists is checked once the whole name
+                // is known.
+                push(&unquote(component));
+                continue;
+            }
+            let dir = if prefix.is_empty() { Path::new(".") } else { Path::new(OsStr::from_bytes(prefix)) };
+            let entries = match fs::read_dir(dir) {
+                Ok(entries) => entries,
+                Err(_) => continue,
+            };
+            for entry in entries.flatten() {
+                let name = entry.file_name();
+                let name = name.as_bytes();
+                if (!name.starts_with(b".") || component.starts_with(b".")) && matches(component, name) {
+                    push(name);
+                }
+            }
+        }
+        names = next;
+    }
+    names.retain(|name| fs::symlink_metadata(OsStr::from_bytes(name)).is_ok());
+    names.sort();
+    names
+}
+
+#[test]
+fn test_matches() {
+    assert!(matches(b"*.rs", b"main.rs"));
+    assert!(matches(b"*.rs", b".rs"));
+    assert!(!matches(b"*.rs", b"main.rc"));
+    assert!(matches(b"a*b*c", b"aXbYbZc"));
+    assert!(!matches(b"a*b*c", b"aXbYbZ"));
+    assert!(matches(b"?", b"x"));
+    assert!(!matches(b"?", b""));
+    assert!(matches(b"[a-c]x", b"bx"));
+    assert!(!matches(b"[!a-c]x", b"bx"));
+    assert!(matches(b"[]]", b"]"));
+    assert!(matches(b"[[:digit:]]*", b"9lives"));
+    assert!(matches(b"\\*", b"*"));
+    assert!(!matches(b"\\*", b"x"));
+    assert!(matches(b"[", b"["));
+    assert!(matches(b"**", b""));
+    assert!(is_pattern(b"a[bc]"));
+    assert!(!is_pattern(b"a\\*b"));
+
+    let dir = std::env::temp_dir().join(format!("glob.{}", std::process::id()));
+    for name in ["a/x.c", "a/.y.c", "b/z.c", "b/z.h"] {
+        let path = dir.join(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, "").unwrap();
+    }
+    let prefix = dir.as_os_str().as_bytes();
+    let expand = |pat: &str| -> Vec<String> {
+        let mut full = prefix.to_vec();
+        full.extend_from_slice(pat.as_bytes());
+        expand(&full).iter().map(|name| String::from_utf8_lossy(&name[prefix.len()..]).into_owned()).collect()
+    };
+    assert_eq!(expand("/*/*.c"), ["/a/x.c", "/b/z.c"]);
+    assert_eq!(expand("/a/.*.c"), ["/a/.y.c"]);
+    assert_eq!(expand("/?/z.[ch]"), ["/b/z.c", "/b/z.h"]);
+    assert_eq!(expand("/*/w.c"), Vec::<String>::new());
+    fs::remove_dir_all(&dir).unwrap();
+}
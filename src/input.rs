@@ -0,0 +1,97 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+//! Input files. Every command that reads files takes "-" to mean the
+//! standard input, which they all open with Input. It accepts names that
+//! aren't valid UTF-8. Commands that scan their input as a whole can map a
+//! regular file into memory instead of reading it, falling back to reading
+//! for anything else.
+use std::ffi::OsStr;
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
+
+use crate::mmap::Mmap;
+
+/// A file or, if named "-", the standard input.
+pub enum Input {
+    Stdin(io::StdinLock<'static>),
+    File(BufReader<File>),
+}
+
+impl Input {
+    pub fn open<P: AsRef<OsStr>>(name: P) -> io::Result<Input> {
+        let name = name.as_ref();
+        if name == "-" {
+            Ok(Input::Stdin(io::stdin().lock()))
+        } else {
+            Ok(Input::File(BufReader::new(File::open(name)?)))
+        }
+    }
+
+    /// Maps the file into memory, if it is a non-empty regular file, so
+    /// that it can be scanned without being copied. Returns None for the
+    /// standard input, pipes, devices, files that claim to be empty, as
+    /// those in /proc do, and files that can't be mapped, all of which are
+    /// to be read instead. The input must not have been read from or
+    /// seeked.
+    pub fn map(&self) -> Option<Mmap> {
+        match self {
+            Input::Stdin(_) => None,
+            Input::File(file) => {
+                let meta = file.get_ref().metadata().ok()?;
+                if !meta.is_file() || meta.len() == 0 {
+                    return None;
+                }
+                Mmap::map(file.get_ref()).ok()
+            },
+        }
+    }
+}
+
+/// The input's descriptor, for copying from without reading. The input
+/// must not have been read from, or what has been read but not consumed
+/// would be skipped.
+#[cfg(unix)]
+impl std::os::fd::AsFd for Input {
+    fn as_fd(&self) -> std::os::fd::BorrowedFd<'_> {
+        match self {
+            Input::Stdin(stdin) => stdin.as_fd(),
+            Input::File(file) => file.get_ref().as_fd(),
+        }
+    }
+}
+
+impl Read for Input {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Input::Stdin(stdin) => stdin.read(buf),
+            Input::File(file) => file.read(buf),
+        }
+    }
+}
+
+impl BufRead for Input {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        match self {
+            Input::Stdin(stdin) => stdin.fill_buf(),
+            Input::File(file) => file.fill_buf(),
+        }
+    }
+
+    fn consume(&mut self, amt: usize) {
+        match self {
+            Input::Stdin(stdin) => stdin.consume(amt),
+            Input::File(file) => file.consume(amt),
+        }
+    }
+}
+
+impl Seek for Input {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match self {
+            Input::Stdin(_) => Err(io::Error::new(io::ErrorKind::Unsupported, "cannot seek the standard input")),
+            Input::File(file) => file.seek(pos),
+        }
+    }
+}
@@ -0,0 +1,87 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+//! Records in JSON, as written by the commands that report on files when
+//! given --json: one object per line, for each file or entry that the
+//! command would otherwise describe in a line of columns. Numbers are
+//! plain decimal integers. JSON strings are Unicode, so names that aren't
+//! valid UTF-8 are written with U+FFFD in place of the invalid bytes.
+use std::ffi::OsStr;
+use std::fmt;
+use std::fmt::Write;
+
+/// A JSON object, built a member at a time.
+pub struct Record {
+    buf: String,
+}
+
+impl Record {
+    pub fn new() -> Record {
+        Record { buf: String::new() }
+    }
+
+    fn key(&mut self, key: &str) {
+        self.buf.push(if self.buf.is_empty() { '{' } else { ',' });
+        quote(&mut self.buf, key);
+        self.buf.push(':');
+    }
+
+    pub fn str(mut self, key: &str, value: &str) -> Record {
+        self.key(key);
+        quote(&mut self.buf, value);
+        self
+    }
+
+    /// Adds a file name, which need not be valid UTF-8.
+    pub fn name<S: AsRef<OsStr>>(self, key: &str, value: S) -> Record {
+        self.str(key, &value.as_ref().to_string_lossy())
+    }
+
+    pub fn num<N: Into<i128>>(mut self, key: &str, value: N) -> Record {
+        self.key(key);
+        let _ = write!(self.buf, "{}", value.into());
+        self
+    }
+}
+
+impl Default for Record {
+    fn default() -> Record {
+        Record::new()
+    }
+}
+
+impl fmt::Display for Record {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.buf.is_empty() {
+            f.write_str("{}")
+        } else {
+            write!(f, "{}}}", self.buf)
+        }
+    }
+}
+
+/// Appends the string, quoted and escaped as JSON requires.
+fn quote(buf: &mut String, s: &str) {
+    buf.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => buf.push_str("\\\""),
+            '\\' => buf.push_str("\\\\"),
+            '\n' => buf.push_str("\\n"),
+            '\r' => buf.push_str("\\r"),
+            '\t' => buf.push_str("\\t"),
+            c if (c as u32) < 0x20 || c == '\u{7f}' => {
+                let _ = write!(buf, "\\u{:04x}", c as u32);
+            },
+            c => buf.push(c),
+        }
+    }
+    buf.push('"');
+}
+
+#[test]
+fn test_record() {
+    assert_eq!(Record::new().to_string(), "{}");
+    let record = Record::new().str("file", "a \"b\"\\\n\x01").num("lines", 3u64).num("n", -1);
+    assert_eq!(record.to_string(), r#"{"file":"a \"b\"\\\n\u0001","lines":3,"n":-1}"#);
+}
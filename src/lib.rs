@@ -0,0 +1,50 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+//! The commands and the code they share.
+
+// Much of the shared code uses Unix interfaces directly; see platform.
+#[cfg(not(unix))]
+compile_error!("the commands build only on Unix");
+
+pub mod archive;
+pub mod ar;
+pub mod args;
+pub mod atomic;
+pub mod bignum;
+pub mod blocks;
+pub mod checksum;
+pub mod cmd;
+pub mod collate;
+pub mod color;
+pub mod completion;
+pub mod copy;
+pub mod dev;
+pub mod dir;
+pub mod diag;
+pub mod digest;
+pub mod elf;
+pub mod glob;
+pub mod input;
+pub mod json;
+pub mod magic;
+pub mod man;
+pub mod mmap;
+pub mod mode;
+pub mod owner;
+pub mod perm;
+pub mod platform;
+pub mod prompt;
+pub mod regex;
+pub mod sandbox;
+pub mod signal;
+pub mod size;
+pub mod sparse;
+pub mod status;
+pub mod tempfile;
+pub mod time;
+pub mod trace;
+pub mod tty;
+pub mod users;
+pub mod utmp;
+pub mod walk;
@@ -0,0 +1,177 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+//! Identification of the type of a file from its first few bytes, by a
+//! table of magic numbers and, failing that, by whether it looks like
+//! text.
+use crate::archive;
+
+/// How much of a file should be read to identify it.
+pub const HEAD_SIZE: usize = 8192;
+
+/// How to describe a file whose magic number matches.
+enum Description {
+    Fixed(&'static str),
+    /// A function of the file's first bytes.
+    Computed(fn(&[u8]) -> String),
+}
+
+/// A magic number: the bytes found at an offset in files of a type.
+struct Magic {
+    offset: usize,
+    bytes: &'static [u8],
+    description: Description,
+}
+
+const fn fixed(offset: usize, bytes: &'static [u8], description: &'static str) -> Magic {
+    Magic { offset, bytes, description: Description::Fixed(description) }
+}
+
+const fn computed(offset: usize, bytes: &'static [u8], description: fn(&[u8]) -> String) -> Magic {
+    Magic { offset, bytes, description: Description::Computed(description) }
+}
+
+/// The magic numbers, in the order in which they are tried.
+const MAGIC: &[Magic] = &[
+    computed(0, b"\x7fELF", elf),
+    computed(0, b"#!", script),
+    fixed(0, b"!<arch>\n", "current ar archive"),
+    fixed(257, b"ustar\0", "POSIX tar archive"),
+    fixed(257, b"ustar  \0", "GNU tar archive"),
+    fixed(0, b"\x1f\x8b", "gzip compressed data"),
+    fixed(0, b"BZh", "bzip2 compressed data"),
+    fixed(0, b"\xfd7zXZ\0", "XZ compressed data"),
+    fixed(0, b"\x28\xb5\x2f\xfd", "Zstandard compressed data"),
+    fixed(0, b"\x1f\x9d", "compress'd data"),
+    fixed(0, b"PK\x03\x04", "Zip archive data"),
+    fixed(0, b"\x89PNG\r\n\x1a\n", "PNG image data"),
+    fixed(0, b"GIF87a", "GIF image data"),
+    fixed(0, b"GIF89a", "GIF image data"),
+    fixed(0, b"\xff\xd8\xff", "JPEG image data"),
+    fixed(0, b"%PDF-", "PDF document"),
+    fixed(0, b"%!PS", "PostScript document"),
+];
+
+/// Describes an ELF file by its class, byte order, type and machine.
+fn elf(data: &[u8]) -> String {
+    let class = match data.get(4) {
+        Some(1) => "32-bit",
+        Some(2) => "64-bit",
+        _ => return "ELF, invalid class".to_string(),
+    };
+    let (order, little) = match data.get(5) {
+        Some(1) => ("LSB", true),
+        Some(2) => ("MSB", false),
+        _ => return format!("ELF {}, invalid byte order", class),
+    };
+    let half = |offset: usize| {
+        data.get(offset..offset + 2).map(|b| {
+            let b = [b[0], b[1]];
+            if little { u16::from_le_bytes(b) } else { u16::from_be_bytes(b) }
+        })
+    };
+    let kind = match half(16) {
+        Some(1) => "relocatable",
+        Some(2) => "executable",
+        Some(3) => "shared object",
+        Some(4) => "core file",
+        _ => "unknown type",
+    };
+    let machine = match half(18) {
+        Some(2) => "SPARC",
+        Some(3) => "Intel 80386",
+        Some(8) => "MIPS",
+        Some(20) => "PowerPC",
+        Some(21) => "64-bit PowerPC",
+        Some(22) => "IBM S/390",
+        Some(40) => "ARM",
+        Some(43) => "SPARC V9",
+        Some(62) => "x86-64",
+        Some(183) => "ARM aarch64",
+        Some(243) => "RISC-V",
+        _ => "unknown machine",
+    };
+    format!("ELF {} {} {}, {}", class, order, kind, machine)
+}
+
+/// Describes a script by its interpreter and the kind of its text.
+fn script(data: &[u8]) -> String {
+    let line = data[2..].split(|&c| c == b'\n').next().unwrap_or(&[]);
+    let interpreter = String::from_utf8_lossy(line);
+    let interpreter = interpreter.trim();
+    format!("a {} script, {}", interpreter, text(data).unwrap_or("data"))
+}
+
+/// Returns whether a byte is found in text: a printable ASCII character
+/// or one of the usual control characters (backspace, tab, newline,
+/// form feed, carriage return and escape).
+fn is_text_byte(c: u8) -> bool {
+    matches!(c, 0x20..=0x7e | 0x08..=0x0a | 0x0c | 0x0d | 0x1b)
+}
+
+/// Describes the data as text, or returns None if it doesn't look like
+/// text. If the data is only the start of a file, it may end part way
+/// through a UTF-8 sequence.
+pub fn text(data: &[u8]) -> Option<&'static str> {
+    if data.iter().all(|&c| is_text_byte(c)) {
+        return Some("ASCII text");
+    }
+    let valid = match std::str::from_utf8(data) {
+        Ok(s) => s,
+        Err(e) if e.error_len().is_none() && data.len() >= HEAD_SIZE => {
+            std::str::from_utf8(&data[..e.valid_up_to()]).unwrap()
+        },
+        Err(_) => return None,
+    };
+    if valid.chars().all(|c| !c.is_ascii() || is_text_byte(c as u8)) {
+        Some("UTF-8 text")
+    } else {
+        None
+    }
+}
+
+/// Describes a file given its first bytes, up to HEAD_SIZE of them.
+pub fn describe(data: &[u8]) -> String {
+    if data.is_empty() {
+        return "empty".to_string();
+    }
+    for magic in MAGIC {
+        if data.get(magic.offset..).is_some_and(|d| d.starts_with(magic.bytes)) {
+            return match magic.description {
+                Description::Fixed(s) => s.to_string(),
+                Description::Computed(f) => f(data),
+            };
+        }
+    }
+    // Old tar archives have no magic number, but do have a checksum.
+    if data.len() >= archive::BLOCK_SIZE
+        && matches!(archive::Header::parse(&data[..archive::BLOCK_SIZE]), Ok(Some(_))) {
+        return "tar archive".to_string();
+    }
+    text(data).unwrap_or("data").to_string()
+}
+
+#[test]
+fn test_describe() {
+    assert_eq!(describe(b""), "empty");
+    assert_eq!(describe(b"hello\n"), "ASCII text");
+    assert_eq!(describe("h\u{e9}llo\n".as_bytes()), "UTF-8 text");
+    assert_eq!(describe(b"h\xe9llo\n"), "data");
+    assert_eq!(describe(b"\x00\x01\x02"), "data");
+    assert_eq!(describe(b"#!/bin/sh\necho hi\n"), "a /bin/sh script, ASCII text");
+    assert_eq!(describe(b"\x1f\x8b\x08\x00"), "gzip compressed data");
+    let mut elf = b"\x7fELF\x02\x01\x01\0\0\0\0\0\0\0\0\0\x01\0\x3e\0".to_vec();
+    elf.resize(64, 0);
+    assert_eq!(describe(&elf), "ELF 64-bit LSB relocatable, x86-64");
+    let header = archive::Header::new(b"file", archive::Kind::File);
+    let mut block = header.to_block().unwrap();
+    assert_eq!(describe(&block), "POSIX tar archive");
+    // Make it look like an old archive, without the magic number.
+    for c in &mut block[257..265] {
+        *c = 0;
+    }
+    let sum: u32 = block.iter().enumerate()
+        .map(|(i, &c)| if (148..156).contains(&i) { b' ' as u32 } else { c as u32 }).sum();
+    block[148..156].copy_from_slice(format!("{:06o}\0 ", sum).as_bytes());
+    assert_eq!(describe(&block), "tar archive");
+}
@@ -0,0 +1,74 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+//! Manual pages, in roff, generated from the commands' Specs, as written
+//! by "v7 --man dir". Each page has the command's name and what it does,
+//! its synopsis and its options, so that it says no more and no less than
+//! --help does.
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::cmd::{Spec, COMMANDS};
+
+/// Escapes text for roff: backslashes and minus signs, and a period or
+/// quote at the start of a line, which would otherwise make it a request.
+fn escape(s: &str) -> String {
+    let s = s.replace('\\', "\\e").replace('-', "\\-");
+    if s.starts_with('.') || s.starts_with('\'') {
+        format!("\\&{}", s)
+    } else {
+        s
+    }
+}
+
+/// Returns the page for the command of the given name.
+pub fn page(name: &str, spec: &Spec) -> String {
+    let mut page = format!(".TH {} 1 \"\" \"{} {}\"\n", name.to_uppercase(),
+                           env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+    page += &format!(".SH NAME\n{} \\- {}\n", escape(name), escape(spec.about));
+    page += &format!(".SH SYNOPSIS\n.B {}\n", escape(name));
+    let synopsis = spec.synopsis(name);
+    let rest = synopsis[name.len()..].trim_start();
+    if !rest.is_empty() {
+        page += &format!("{}\n", escape(rest));
+    }
+    if !spec.options.is_empty() || spec.json || !spec.literal {
+        page += ".SH OPTIONS\n";
+    }
+    for opt in spec.options {
+        match opt.arg {
+            Some(arg) => page += &format!(".TP\n.BI \\-{} \" {}\"\n", opt.letter, escape(arg)),
+            None => page += &format!(".TP\n.B \\-{}\n", opt.letter),
+        }
+        page += &escape(opt.about);
+        page += "\n";
+    }
+    if spec.json {
+        page += ".TP\n.B \\-\\-json\nwrite a JSON record for each entry\n";
+    }
+    if !spec.literal {
+        page += ".TP\n.B \\-\\-help\ndescribe the command and exit\n";
+        page += ".TP\n.B \\-\\-version\nprint the version and exit\n";
+    }
+    page
+}
+
+/// Writes the page of every command, as name.1, into the directory.
+pub fn write_all(dir: &Path) -> io::Result<()> {
+    for &(name, _, spec) in COMMANDS {
+        fs::write(dir.join(format!("{}.1", name)), page(name, spec))?;
+    }
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "core")]
+fn test_page() {
+    let page = page("du", &crate::cmd::du::SPEC);
+    assert!(page.starts_with(".TH DU 1 "));
+    assert!(page.contains(".SH NAME\ndu \\- summarize disk usage\n"));
+    assert!(page.contains(".SH SYNOPSIS\n.B du\n[\\-aAskhzHLP] [\\-\\-json] [file ...]\n"));
+    assert!(page.contains(".TP\n.B \\-k\ncount in 1024\\-byte blocks\n"));
+    assert_eq!(escape(".x \\n"), "\\&.x \\en");
+}
@@ -0,0 +1,75 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+//! Read-only memory mapping of files, so that large files can be
+//! searched without reading them into memory.
+use std::fs::File;
+use std::io;
+use std::ops::Deref;
+use std::os::unix::io::AsRawFd;
+use std::ptr;
+use std::slice;
+
+/// The contents of a file, mapped into memory.
+pub struct Mmap {
+    ptr: *mut libc::c_void,
+    len: usize,
+}
+
+impl Mmap {
+    /// Maps the whole of a file. The file should not be changed while it
+    /// is mapped.
+    pub fn map(file: &File) -> io::Result<Mmap> {
+        let len = usize::try_from(file.metadata()?.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "file too large to map"))?;
+        // An empty mapping isn't allowed.
+        if len == 0 {
+            return Ok(Mmap { ptr: ptr::null_mut(), len });
+        }
+        // SAFETY: the arguments describe a new read-only mapping of len
+        // bytes of an open file; the result is checked below.
+        let ptr = unsafe {
+            libc::mmap(ptr::null_mut(), len, libc::PROT_READ, libc::MAP_PRIVATE, file.as_raw_fd(), 0)
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Mmap { ptr, len })
+    }
+}
+
+impl Deref for Mmap {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        if self.len == 0 {
+            return &[];
+        }
+        // SAFETY: ptr points to a readable mapping of len bytes that
+        // lives as long as self.
+        unsafe { slice::from_raw_parts(self.ptr as *const u8, self.len) }
+    }
+}
+
+impl Drop for Mmap {
+    fn drop(&mut self) {
+        if self.len != 0 {
+            // SAFETY: ptr and len describe a mapping made by map, which
+            // is no longer referenced.
+            unsafe {
+                libc::munmap(self.ptr, self.len);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_map() {
+    use std::io::Write;
+
+    let mut file = crate::tempfile::TempFile::new("mmap").unwrap();
+    file.write_all(b"hello, world\n").unwrap();
+    file.flush().unwrap();
+    let map = Mmap::map(&File::open(file.path()).unwrap()).unwrap();
+    assert_eq!(&map[..], b"hello, world\n");
+}
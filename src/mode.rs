@@ -0,0 +1,28 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+//! Whether the commands behave as their Seventh Edition namesakes did,
+//! which is the default, or as POSIX specifies, which they do if the
+//! POSIXLY_CORRECT environment variable is set. Where the two agree, or
+//! one says nothing, the commands do the same either way.
+use std::env;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    V7,
+    Posix,
+}
+
+/// Returns the mode selected by the environment.
+pub fn mode() -> Mode {
+    if env::var_os("POSIXLY_CORRECT").is_some() {
+        Mode::Posix
+    } else {
+        Mode::V7
+    }
+}
+
+/// Returns true if the commands are to behave as POSIX specifies.
+pub fn posix() -> bool {
+    mode() == Mode::Posix
+}
@@ -0,0 +1,94 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+//! Changing the owners and groups of files, as chown and chgrp do, either
+//! of the files alone or, recursively, of directories and everything in
+//! them. The files found in a directory are changed by their names in the
+//! open directory, and the symbolic links among them are changed
+//! themselves, rather than the files they refer to, which may be anywhere,
+//! unless -L asks for them to be followed.
+use std::io;
+use std::os::unix::fs::{chown, lchown};
+use std::path::Path;
+
+use crate::dir::Dir;
+use crate::err;
+use crate::trace;
+use crate::walk::{Follow, Walk};
+
+/// Changes the owner and group of a file, either of which may be left as
+/// it is, following it if it is a symbolic link only if follow is set. A
+/// file found in the open directory parent is changed by its name there.
+fn change_one(parent: Option<&Dir>, path: &Path, uid: Option<u32>, gid: Option<u32>,
+              follow: bool) -> io::Result<()> {
+    let result = match (parent, path.file_name()) {
+        (Some(dir), Some(name)) => dir.chown(name, uid, gid, follow),
+        _ if follow => chown(path, uid, gid),
+        _ => lchown(path, uid, gid),
+    };
+    let id = |id: Option<u32>| id.map_or(-1, i64::from);
+    trace::op("chown", format_args!("{:?}, {}, {}", path, id(uid), id(gid)), result)
+}
+
+/// Changes the owner and group of the file and, if recursive, of
+/// everything in it, following the symbolic links the policy does. A file
+/// named alone is always followed. Reports what couldn't be changed.
+pub fn change(path: &Path, uid: Option<u32>, gid: Option<u32>, recursive: bool, follow: Follow) {
+    if !recursive {
+        if let Err(e) = change_one(None, path, uid, gid, true) {
+            err!("{}: {}", path.display(), e);
+        }
+        return;
+    }
+    let mut walk = Walk::new([path]).follow(follow);
+    while let Some(entry) = walk.next() {
+        match entry {
+            Ok(entry) => {
+                let follows = follow.follows(entry.depth());
+                if let Err(e) = change_one(walk.parent(), entry.path(), uid, gid, follows) {
+                    err!("{}: {}", entry.path().display(), e);
+                }
+            },
+            Err(e) => err!("{}", e),
+        }
+    }
+}
+
+#[test]
+fn test_change_swapped() {
+    use std::fs;
+    use std::os::unix::fs::{symlink, MetadataExt};
+    use std::time::Duration;
+    use crate::tempfile;
+
+    // A directory swapped for a symbolic link while it is being walked
+    // mustn't lead the change to the files the link refers to.
+    let root = tempfile::temp_dir().join(format!("owner-test.{}", std::process::id()));
+    fs::create_dir_all(root.join("tree/a")).unwrap();
+    fs::create_dir_all(root.join("outside")).unwrap();
+    fs::write(root.join("tree/a/f"), "").unwrap();
+    fs::write(root.join("outside/f"), "").unwrap();
+    let gid = fs::metadata(&root).unwrap().gid();
+    let ctime = |path: &str| {
+        let meta = fs::metadata(root.join(path)).unwrap();
+        (meta.ctime(), meta.ctime_nsec())
+    };
+    let (inside, outside) = (ctime("tree/a/f"), ctime("outside/f"));
+    std::thread::sleep(Duration::from_millis(20));
+
+    let mut walk = Walk::new([root.join("tree")]);
+    let mut changed = false;
+    while let Some(entry) = walk.next() {
+        let entry = entry.unwrap();
+        if entry.path().ends_with("a/f") {
+            fs::rename(root.join("tree/a"), root.join("tree/moved")).unwrap();
+            symlink(root.join("outside"), root.join("tree/a")).unwrap();
+            change_one(walk.parent(), entry.path(), None, Some(gid), false).unwrap();
+            changed = true;
+        }
+    }
+    assert!(changed);
+    assert_eq!(ctime("outside/f"), outside);
+    assert_ne!(ctime("tree/moved/f"), inside);
+    fs::remove_dir_all(&root).unwrap();
+}
@@ -0,0 +1,182 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+//! Permission modes, as chmod and mkdir -m take them: either an octal
+//! number, which gives the whole mode, or, as POSIX has it, symbolic
+//! clauses that change the mode a file already has, such as "u+rwx,go-w"
+//! or "a=rx". A clause is the classes of user it applies to, any of u, g,
+//! o and a, and then one or more actions: +, - or =, followed by any of
+//! the permissions r, w, x, X (x if the file is a directory or is already
+//! executable by someone), s and t, or by one of u, g and o for the
+//! permissions that class already has. A clause that names no class
+//! applies to all of them, except for the bits in the umask.
+use std::fmt;
+
+/// The bits of each class of user: its read, write and execute bits, and
+/// its set-id or sticky bit.
+const USER: u32 = 0o4700;
+const GROUP: u32 = 0o2070;
+const OTHER: u32 = 0o1007;
+const ALL: u32 = 0o7777;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Perm {
+    /// The permissions listed, and whether X was among them.
+    List(u32, bool),
+    /// The permissions of the class whose bits are at the shift.
+    Copy(u32),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Action {
+    op: char,
+    perm: Perm,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Clause {
+    /// The bits of the classes the clause applies to, or 0 if it names
+    /// none.
+    who: u32,
+    actions: Vec<Action>,
+}
+
+/// A mode, as parsed from its octal or symbolic form.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Mode {
+    Octal(u32),
+    Symbolic(Symbolic),
+}
+
+/// The clauses of a symbolic mode.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Symbolic(Vec<Clause>);
+
+/// An error in a mode.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Error(String);
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid mode: {}", self.0)
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Parses a mode in its octal or symbolic form.
+pub fn parse(s: &str) -> Result<Mode, Error> {
+    let invalid = || Error(s.to_string());
+    if s.starts_with(|c: char| c.is_ascii_digit()) {
+        return match u32::from_str_radix(s, 8) {
+            Ok(mode) if mode <= ALL => Ok(Mode::Octal(mode)),
+            _ => Err(invalid()),
+        };
+    }
+    let mut clauses = Vec::new();
+    for clause in s.split(',') {
+        let mut chars = clause.chars().peekable();
+        let mut who = 0;
+        while let Some(c) = chars.next_if(|c| "ugoa".contains(*c)) {
+            who |= match c {
+                'u' => USER,
+                'g' => GROUP,
+                'o' => OTHER,
+                _ => ALL,
+            };
+        }
+        let mut actions = Vec::new();
+        while let Some(op) = chars.next() {
+            if !"+-=".contains(op) {
+                return Err(invalid());
+            }
+            let perm = match chars.next_if(|c| "ugo".contains(*c)) {
+                Some('u') => Perm::Copy(6),
+                Some('g') => Perm::Copy(3),
+                Some(_) => Perm::Copy(0),
+                None => {
+                    let (mut bits, mut x) = (0, false);
+                    while let Some(c) = chars.next_if(|c| "rwxXst".contains(*c)) {
+                        match c {
+                            'r' => bits |= 0o444,
+                            'w' => bits |= 0o222,
+                            'x' => bits |= 0o111,
+                            'X' => x = true,
+                            's' => bits |= 0o6000,
+                            _ => bits |= 0o1000,
+                        }
+                    }
+                    Perm::List(bits, x)
+                },
+            };
+            actions.push(Action { op, perm });
+        }
+        if actions.is_empty() {
+            return Err(invalid());
+        }
+        clauses.push(Clause { who, actions });
+    }
+    Ok(Mode::Symbolic(Symbolic(clauses)))
+}
+
+impl Mode {
+    /// Returns the mode a file is to have, given the one it has, whether it
+    /// is a directory, and the umask, which limits the clauses that name
+    /// no class.
+    pub fn apply(&self, mode: u32, is_dir: bool, umask: u32) -> u32 {
+        let clauses = match self {
+            Mode::Octal(mode) => return *mode,
+            Mode::Symbolic(Symbolic(clauses)) => clauses,
+        };
+        let mut mode = mode & ALL;
+        for clause in clauses {
+            let (who, mask) = match clause.who {
+                0 => (ALL, ALL & !umask),
+                who => (who, who),
+            };
+            for action in &clause.actions {
+                let bits = match action.perm {
+                    Perm::List(bits, x) if x && (is_dir || mode & 0o111 != 0) => bits | 0o111,
+                    Perm::List(bits, _) => bits,
+                    Perm::Copy(shift) => ((mode >> shift) & 7) * 0o111,
+                } & mask;
+                mode = match action.op {
+                    '+' => mode | bits,
+                    '-' => mode & !bits,
+                    _ => (mode & !who) | bits,
+                };
+            }
+        }
+        mode
+    }
+}
+
+/// Returns the process's umask.
+pub fn umask() -> u32 {
+    // SAFETY: umask can't fail, and the mask is put back straight away.
+    let mask = unsafe { libc::umask(0) };
+    unsafe { libc::umask(mask) };
+    mask as u32
+}
+
+#[test]
+fn test_parse() {
+    let apply = |s: &str, mode: u32, is_dir: bool| parse(s).unwrap().apply(mode, is_dir, 0o022);
+    assert_eq!(apply("755", 0o600, false), 0o755);
+    assert_eq!(apply("u+rwx,go-w", 0o666, false), 0o744);
+    assert_eq!(apply("a=rx", 0o777, false), 0o555);
+    assert_eq!(apply("+w", 0o444, false), 0o644);
+    assert_eq!(apply("=r", 0o777, false), 0o444);
+    assert_eq!(apply("go=u", 0o640, false), 0o666);
+    assert_eq!(apply("u-w+x", 0o644, false), 0o544);
+    assert_eq!(apply("a+X", 0o644, false), 0o644);
+    assert_eq!(apply("a+X", 0o644, true), 0o755);
+    assert_eq!(apply("a+X", 0o744, false), 0o755);
+    assert_eq!(apply("u+s,g+s,+t", 0o755, false), 0o7755);
+    assert_eq!(apply("o+t", 0o777, true), 0o1777);
+    assert_eq!(apply("ug=", 0o4755, false), 0o005);
+    for s in ["", "8", "17777", "u", "u+rz", "a+r,", "x"] {
+        assert!(parse(s).is_err(), "{}", s);
+    }
+    assert_eq!(parse("9").unwrap_err().to_string(), "invalid mode: 9");
+}
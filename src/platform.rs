@@ -0,0 +1,89 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+//! The things that differ between Unix and Windows: path separators,
+//! removing read-only files and writing bytes to the console. These are
+//! only a start on a Windows port; the crate as a whole builds only on
+//! Unix.
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+
+use crate::trace;
+
+/// The separator of the directories in a search path such as PATH.
+#[cfg(unix)]
+pub const PATH_LIST_SEPARATOR: char = ':';
+#[cfg(windows)]
+pub const PATH_LIST_SEPARATOR: char = ';';
+
+/// Returns whether the character separates the components of a path.
+/// Windows accepts both slashes.
+pub fn is_separator(c: char) -> bool {
+    c == '/' || (cfg!(windows) && c == '\\')
+}
+
+/// Removes a file. On Unix, whether a file can be removed depends only on
+/// the directory it is in, but Windows refuses to remove a file with the
+/// read-only attribute set, so the attribute is cleared first. The caller
+/// is expected to have asked for confirmation.
+pub fn remove_file(path: &Path) -> io::Result<()> {
+    if cfg!(windows) {
+        let mut perms = fs::symlink_metadata(path)?.permissions();
+        if perms.readonly() {
+            #[allow(clippy::permissions_set_readonly_false)]
+            perms.set_readonly(false);
+            fs::set_permissions(path, perms)?;
+        }
+    }
+    trace::op("unlink", format_args!("{:?}", path), fs::remove_file(path))
+}
+
+/// Removes a file and, if it is a directory, everything in it. On Unix,
+/// the tree is removed through open directories, so that swapping one of
+/// them for a symbolic link while it is being removed can't redirect the
+/// removal elsewhere.
+pub fn remove_all(path: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    return crate::dir::remove_all(path);
+    #[cfg(windows)]
+    return trace::op("remove_all", format_args!("{:?}", path), fs::remove_dir_all(path));
+}
+
+/// The standard output, for commands that copy bytes through unchanged.
+/// A Windows console accepts only UTF-8, so there any other bytes are
+/// written as replacement characters rather than failing the write, as
+/// are characters split between two writes.
+pub struct Stdout {
+    out: io::StdoutLock<'static>,
+    console: bool,
+}
+
+pub fn stdout() -> Stdout {
+    let out = io::stdout().lock();
+    let console = cfg!(windows) && io::IsTerminal::is_terminal(&out);
+    Stdout { out, console }
+}
+
+impl Write for Stdout {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.console {
+            self.out.write_all(String::from_utf8_lossy(buf).as_bytes())?;
+            Ok(buf.len())
+        } else {
+            self.out.write(buf)
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+#[test]
+fn test_is_separator() {
+    assert!(is_separator('/'));
+    assert_eq!(is_separator('\\'), cfg!(windows));
+    assert!(!is_separator(':'));
+}
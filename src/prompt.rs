@@ -0,0 +1,61 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+//! Questions put to the user, such as rm -i and find -ok ask. They are
+//! written to, and answered from, the user's terminal rather than the
+//! standard input and output, which may be the files or pipes the command
+//! is working on. Where there is no terminal to ask, as under cron, the
+//! answer is taken to be no, so that nothing is done without consent.
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+
+use crate::diag;
+use crate::warn;
+
+#[cfg(unix)]
+const INPUT: &str = "/dev/tty";
+#[cfg(unix)]
+const OUTPUT: &str = "/dev/tty";
+#[cfg(windows)]
+const INPUT: &str = "CONIN$";
+#[cfg(windows)]
+const OUTPUT: &str = "CONOUT$";
+
+/// Returns whether an answer is yes: whether it starts, after any blanks,
+/// with y or Y.
+fn yes(answer: &str) -> bool {
+    answer.trim_start().starts_with(['y', 'Y'])
+}
+
+fn terminal() -> io::Result<(File, File)> {
+    Ok((File::open(INPUT)?, OpenOptions::new().write(true).open(OUTPUT)?))
+}
+
+/// Puts the question, which should end with "?", to the user, after the
+/// program's name as in diagnostics, and returns whether they answer yes.
+/// If there is no terminal, or it can't be read, the answer is no.
+pub fn ask(question: &str) -> bool {
+    let (input, mut output) = match terminal() {
+        Ok(tty) => tty,
+        Err(_) => {
+            warn!("{} no (no terminal)", question);
+            return false;
+        }
+    };
+    if write!(output, "{}: {} ", diag::prog(), question).and_then(|_| output.flush()).is_err() {
+        return false;
+    }
+    let mut answer = String::new();
+    match BufReader::new(input).read_line(&mut answer) {
+        Ok(_) => yes(&answer),
+        Err(_) => false,
+    }
+}
+
+#[test]
+fn test_yes() {
+    assert!(yes("y\n"));
+    assert!(yes(" Yes\n"));
+    assert!(!yes("n\n"));
+    assert!(!yes(""));
+}
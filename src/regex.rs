@@ -0,0 +1,692 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+//! A small backtracking regular expression engine. Both the basic syntax
+//! used by ed(1) and sed(1) and the extended syntax used by awk(1) are
+//! supported. Matching is performed on bytes so that the input need not be
+//! valid UTF-8.
+use std::error;
+use std::fmt;
+
+/// The regular expression dialect to compile.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Syntax {
+    /// Basic regular expressions, as used by ed and sed.
+    Basic,
+    /// Extended regular expressions, as used by awk and egrep.
+    Extended,
+}
+
+/// An error encountered while compiling a regular expression.
+#[derive(Debug)]
+pub struct Error {
+    msg: &'static str,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.msg)
+    }
+}
+
+impl error::Error for Error {}
+
+fn error<T>(msg: &'static str) -> Result<T, Error> {
+    Err(Error { msg })
+}
+
+type ByteSet = Box<[bool; 256]>;
+
+#[derive(Debug)]
+enum Node {
+    Byte(u8),
+    Any,
+    Set(ByteSet),
+    Bol,
+    Eol,
+    WordStart,
+    WordEnd,
+    /// A list of alternatives, optionally captured as the numbered group.
+    Group(Option<usize>, Vec<Vec<Node>>),
+    Repeat(Box<Node>, usize, Option<usize>),
+    Backref(usize),
+}
+
+impl Node {
+    /// Returns true if the node always matches exactly one byte.
+    fn is_single(&self) -> bool {
+        matches!(self, Node::Byte(_) | Node::Any | Node::Set(_))
+    }
+}
+
+/// A compiled regular expression.
+#[derive(Debug)]
+pub struct Regex {
+    nodes: Vec<Node>,
+    groups: usize,
+    longest: bool,
+}
+
+/// The locations of a match and of its parenthesised subexpressions.
+#[derive(Clone, Debug)]
+pub struct Captures {
+    locs: Vec<Option<(usize, usize)>>,
+}
+
+impl Captures {
+    /// Returns the start and end offsets of the given subexpression. Group
+    /// 0 is the whole match.
+    pub fn get(&self, i: usize) -> Option<(usize, usize)> {
+        self.locs.get(i).copied().flatten()
+    }
+
+    /// Returns the offset at which the match starts.
+    pub fn start(&self) -> usize {
+        self.locs[0].map_or(0, |(s, _)| s)
+    }
+
+    /// Returns the offset just past the end of the match.
+    pub fn end(&self) -> usize {
+        self.locs[0].map_or(0, |(_, e)| e)
+    }
+}
+
+fn is_word(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || c == b'_'
+}
+
+/// Returns true if the byte belongs to the named POSIX character class.
+pub fn in_class(class: &str, c: u8) -> Option<bool> {
+    Some(match class {
+        "alpha" => c.is_ascii_alphabetic(),
+        "digit" => c.is_ascii_digit(),
+        "alnum" => c.is_ascii_alphanumeric(),
+        "upper" => c.is_ascii_uppercase(),
+        "lower" => c.is_ascii_lowercase(),
+        "space" => c.is_ascii_whitespace() || c == 11,
+        "blank" => c == b' ' || c == b'\t',
+        "punct" => c.is_ascii_punctuation(),
+        "print" => (32..=126).contains(&c),
+        "graph" => c.is_ascii_graphic(),
+        "cntrl" => c.is_ascii_control(),
+        "xdigit" => c.is_ascii_hexdigit(),
+        _ => return None,
+    })
+}
+
+struct Parser<'a> {
+    pat: &'a [u8],
+    pos: usize,
+    syntax: Syntax,
+    icase: bool,
+    groups: usize,
+    has_alt: bool,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self, off: usize) -> Option<u8> {
+        self.pat.get(self.pos + off).copied()
+    }
+
+    fn ere(&self) -> bool {
+        self.syntax == Syntax::Extended
+    }
+
+    /// Returns the length of the alternation operator at the current
+    /// position, or 0 if there isn't one.
+    fn alt_len(&self) -> usize {
+        match (self.ere(), self.peek(0), self.peek(1)) {
+            (true, Some(b'|'), _) => 1,
+            (false, Some(b'\\'), Some(b'|')) => 2,
+            _ => 0,
+        }
+    }
+
+    /// Returns the length of the group closing operator at the current
+    /// position, or 0 if there isn't one.
+    fn close_len(&self) -> usize {
+        match (self.ere(), self.peek(0), self.peek(1)) {
+            (true, Some(b')'), _) => 1,
+            (false, Some(b'\\'), Some(b')')) => 2,
+            _ => 0,
+        }
+    }
+
+    fn parse_alts(&mut self, depth: usize) -> Result<Vec<Vec<Node>>, Error> {
+        let mut alts = vec![self.parse_seq(depth)?];
+        loop {
+            let n = self.alt_len();
+            if n == 0 {
+                break;
+            }
+            self.pos += n;
+            self.has_alt = true;
+            alts.push(self.parse_seq(depth)?);
+        }
+        Ok(alts)
+    }
+
+    fn parse_seq(&mut self, depth: usize) -> Result<Vec<Node>, Error> {
+        let mut seq: Vec<Node> = Vec::new();
+        while self.pos < self.pat.len() {
+            if self.alt_len() > 0 {
+                break;
+            }
+            if self.close_len() > 0 {
+                if depth > 0 {
+                    break;
+                } else if !self.ere() {
+                    return error("unmatched \\)");
+                }
+            }
+            let atom = self.parse_atom(&seq)?;
+            let atom = self.parse_quantifiers(atom)?;
+            seq.push(atom);
+        }
+        Ok(seq)
+    }
+
+    /// Returns true if a BRE '$' just consumed is an anchor, i.e. it ends
+    /// the expression or subexpression.
+    fn dollar_is_anchor(&self) -> bool {
+        matches!((self.peek(0), self.peek(1)),
+                 (None, _) | (Some(b'\\'), Some(b')')) | (Some(b'\\'), Some(b'|')))
+    }
+
+    fn literal(&self, c: u8) -> Node {
+        if self.icase && c.is_ascii_alphabetic() {
+            let mut set = Box::new([false; 256]);
+            set[c.to_ascii_lowercase() as usize] = true;
+            set[c.to_ascii_uppercase() as usize] = true;
+            Node::Set(set)
+        } else {
+            Node::Byte(c)
+        }
+    }
+
+    fn parse_atom(&mut self, seq: &[Node]) -> Result<Node, Error> {
+        let c = self.pat[self.pos];
+        self.pos += 1;
+        let at_start = seq.is_empty() || (seq.len() == 1 && matches!(seq[0], Node::Bol));
+        Ok(match c {
+            b'^' if self.ere() || seq.is_empty() => Node::Bol,
+            b'$' if self.ere() || self.dollar_is_anchor() => Node::Eol,
+            b'.' => Node::Any,
+            b'[' => self.parse_bracket()?,
+            b'*' if at_start => self.literal(c),
+            b'*' => return error("invalid use of *"),
+            b'+' | b'?' if self.ere() && at_start => self.literal(c),
+            b'+' | b'?' if self.ere() => return error("invalid use of repetition operator"),
+            b'(' if self.ere() => self.parse_group()?,
+            b'\\' => self.parse_escape()?,
+            _ => self.literal(c),
+        })
+    }
+
+    fn parse_group(&mut self) -> Result<Node, Error> {
+        self.groups += 1;
+        let n = self.groups;
+        let alts = self.parse_alts(1)?;
+        let len = self.close_len();
+        if len == 0 {
+            return error(if self.ere() { "unmatched (" } else { "unmatched \\(" });
+        }
+        self.pos += len;
+        Ok(Node::Group(Some(n), alts))
+    }
+
+    fn parse_escape(&mut self) -> Result<Node, Error> {
+        let c = match self.peek(0) {
+            Some(c) => c,
+            None => return error("trailing backslash"),
+        };
+        self.pos += 1;
+        Ok(match c {
+            b'(' if !self.ere() => self.parse_group()?,
+            b'1'..=b'9' if !self.ere() => {
+                let n = (c - b'0') as usize;
+                if n > self.groups {
+                    return error("invalid back reference");
+                }
+                Node::Backref(n)
+            },
+            b'<' => Node::WordStart,
+            b'>' => Node::WordEnd,
+            b'n' => Node::Byte(b'\n'),
+            b't' => Node::Byte(b'\t'),
+            _ => self.literal(c),
+        })
+    }
+
+    /// Parses an interval expression's bounds. The opening brace has
+    /// already been consumed. Returns None if the text isn't a valid
+    /// interval, in which case the position is left unchanged.
+    fn parse_interval(&mut self) -> Result<Option<(usize, Option<usize>)>, Error> {
+        let start = self.pos;
+        let number = |p: &mut Parser| -> Option<usize> {
+            let begin = p.pos;
+            while let Some(b'0'..=b'9') = p.peek(0) {
+                p.pos += 1;
+            }
+            std::str::from_utf8(&p.pat[begin..p.pos]).ok()?.parse().ok()
+        };
+        let min = number(self);
+        let max = if self.peek(0) == Some(b',') {
+            self.pos += 1;
+            number(self)
+        } else {
+            min
+        };
+        let closed = if self.ere() {
+            if self.peek(0) == Some(b'}') {
+                self.pos += 1;
+                true
+            } else {
+                false
+            }
+        } else if self.peek(0) == Some(b'\\') && self.peek(1) == Some(b'}') {
+            self.pos += 2;
+            true
+        } else {
+            false
+        };
+        match (min, closed) {
+            (Some(min), true) => {
+                if max.is_some_and(|m| m < min) {
+                    return error("invalid interval");
+                }
+                Ok(Some((min, max)))
+            },
+            _ if self.ere() => {
+                // Treat the brace as an ordinary character.
+                self.pos = start;
+                Ok(None)
+            },
+            _ => error("invalid interval"),
+        }
+    }
+
+    fn parse_quantifiers(&mut self, mut atom: Node) -> Result<Node, Error> {
+        loop {
+            let (min, max) = match (self.ere(), self.peek(0), self.peek(1)) {
+                (_, Some(b'*'), _) => {
+                    self.pos += 1;
+                    (0, None)
+                },
+                (true, Some(b'+'), _) => {
+                    self.pos += 1;
+                    (1, None)
+                },
+                (true, Some(b'?'), _) => {
+                    self.pos += 1;
+                    (0, Some(1))
+                },
+                (false, Some(b'\\'), Some(b'+')) => {
+                    self.pos += 2;
+                    (1, None)
+                },
+                (false, Some(b'\\'), Some(b'?')) => {
+                    self.pos += 2;
+                    (0, Some(1))
+                },
+                (true, Some(b'{'), _) => {
+                    self.pos += 1;
+                    match self.parse_interval()? {
+                        Some(bounds) => bounds,
+                        None => {
+                            self.pos -= 1;
+                            break;
+                        }
+                    }
+                },
+                (false, Some(b'\\'), Some(b'{')) => {
+                    self.pos += 2;
+                    match self.parse_interval()? {
+                        Some(bounds) => bounds,
+                        None => break,
+                    }
+                },
+                _ => break,
+            };
+            atom = Node::Repeat(Box::new(atom), min, max);
+        }
+        Ok(atom)
+    }
+
+    fn parse_bracket(&mut self) -> Result<Node, Error> {
+        let mut set = Box::new([false; 256]);
+        let negate = self.peek(0) == Some(b'^');
+        if negate {
+            self.pos += 1;
+        }
+        let mut first = true;
+        loop {
+            let c = match self.peek(0) {
+                Some(c) => c,
+                None => return error("unmatched ["),
+            };
+            if c == b']' && !first {
+                self.pos += 1;
+                break;
+            }
+            first = false;
+            if c == b'[' && matches!(self.peek(1), Some(b':') | Some(b'=') | Some(b'.')) {
+                let kind = self.peek(1).unwrap();
+                let begin = self.pos + 2;
+                let mut end = begin;
+                while end + 1 < self.pat.len()
+                    && !(self.pat[end] == kind && self.pat[end + 1] == b']') {
+                    end += 1;
+                }
+                if end + 1 >= self.pat.len() {
+                    return error("unterminated character class");
+                }
+                let name = &self.pat[begin..end];
+                self.pos = end + 2;
+                if kind == b':' {
+                    let name = std::str::from_utf8(name).unwrap_or("");
+                    if in_class(name, 0).is_none() {
+                        return error("invalid character class");
+                    }
+                    for b in 0..=255u8 {
+                        if in_class(name, b) == Some(true) {
+                            set[b as usize] = true;
+                        }
+                    }
+                } else {
+                    for &b in name {
+                        set[b as usize] = true;
+                    }
+                }
+                continue;
+            }
+            let lo = self.bracket_char();
+            if self.peek(0) == Some(b'-') && self.peek(1).is_some_and(|c| c != b']') {
+                self.pos += 1;
+                let hi = self.bracket_char();
+                if hi < lo {
+                    return error("invalid range");
+                }
+                for b in lo..=hi {
+                    set[b as usize] = true;
+                }
+            } else {
+                set[lo as usize] = true;
+            }
+        }
+        if self.icase {
+            for b in b'a'..=b'z' {
+                let u = b.to_ascii_uppercase();
+                if set[b as usize] || set[u as usize] {
+                    set[b as usize] = true;
+                    set[u as usize] = true;
+                }
+            }
+        }
+        if negate {
+            for b in set.iter_mut() {
+                *b = !*b;
+            }
+        }
+        Ok(Node::Set(set))
+    }
+
+    /// Consumes a single (possibly escaped) character inside a bracket
+    /// expression. Backslash escapes are only recognised in extended
+    /// expressions, except for \n which sed scripts commonly rely on.
+    fn bracket_char(&mut self) -> u8 {
+        let c = self.pat[self.pos];
+        self.pos += 1;
+        if c == b'\\' {
+            match self.peek(0) {
+                Some(b'n') => {
+                    self.pos += 1;
+                    return b'\n';
+                },
+                Some(b't') if self.ere() => {
+                    self.pos += 1;
+                    return b'\t';
+                },
+                Some(e) if self.ere() => {
+                    self.pos += 1;
+                    return e;
+                },
+                _ => {}
+            }
+        }
+        c
+    }
+}
+
+type Locs = Vec<Option<(usize, usize)>>;
+type Cont<'k> = dyn FnMut(usize, &mut Locs) -> bool + 'k;
+
+struct Matcher<'a> {
+    text: &'a [u8],
+}
+
+impl<'a> Matcher<'a> {
+    fn single(&self, node: &Node, pos: usize) -> bool {
+        match self.text.get(pos) {
+            None => false,
+            Some(&c) => match node {
+                Node::Byte(b) => c == *b,
+                Node::Any => true,
+                Node::Set(set) => set[c as usize],
+                _ => false,
+            },
+        }
+    }
+
+    /// Matches a sequence of nodes starting at pos, calling k with the end
+    /// position of each successful match until it returns true.
+    fn seq(&self, nodes: &[Node], pos: usize, locs: &mut Locs, k: &mut Cont) -> bool {
+        let (first, rest) = match nodes.split_first() {
+            Some(split) => split,
+            None => return k(pos, locs),
+        };
+        let text = self.text;
+        match first {
+            Node::Byte(_) | Node::Any | Node::Set(_) => {
+                self.single(first, pos) && self.seq(rest, pos + 1, locs, k)
+            },
+            Node::Bol => pos == 0 && self.seq(rest, pos, locs, k),
+            Node::Eol => pos == text.len() && self.seq(rest, pos, locs, k),
+            Node::WordStart => {
+                pos < text.len() && is_word(text[pos])
+                    && (pos == 0 || !is_word(text[pos - 1]))
+                    && self.seq(rest, pos, locs, k)
+            },
+            Node::WordEnd => {
+                pos > 0 && is_word(text[pos - 1])
+                    && (pos == text.len() || !is_word(text[pos]))
+                    && self.seq(rest, pos, locs, k)
+            },
+            Node::Backref(n) => match locs[*n] {
+                Some((s, e)) => {
+                    text[pos..].starts_with(&text[s..e])
+                        && self.seq(rest, pos + e - s, locs, k)
+                },
+                None => false,
+            },
+            Node::Group(n, alts) => {
+                for alt in alts {
+                    let matched = self.seq(alt, pos, locs, &mut |p, locs: &mut Locs| {
+                        match n {
+                            Some(n) => {
+                                let saved = locs[*n];
+                                locs[*n] = Some((pos, p));
+                                if self.seq(rest, p, locs, k) {
+                                    return true;
+                                }
+                                locs[*n] = saved;
+                                false
+                            },
+                            None => self.seq(rest, p, locs, k),
+                        }
+                    });
+                    if matched {
+                        return true;
+                    }
+                }
+                false
+            },
+            Node::Repeat(node, min, max) => {
+                if node.is_single() {
+                    // Fast path: count how many bytes match, then back off.
+                    let mut n = 0;
+                    while max.is_none_or(|m| n < m) && self.single(node, pos + n) {
+                        n += 1;
+                    }
+                    if n < *min {
+                        return false;
+                    }
+                    loop {
+                        if self.seq(rest, pos + n, locs, k) {
+                            return true;
+                        }
+                        if n == *min {
+                            return false;
+                        }
+                        n -= 1;
+                    }
+                } else {
+                    self.repeat(node, *min, *max, 0, pos, rest, locs, k)
+                }
+            },
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn repeat(&self, node: &Node, min: usize, max: Option<usize>, count: usize,
+              pos: usize, rest: &[Node], locs: &mut Locs, k: &mut Cont) -> bool {
+        if max.is_none_or(|m| count < m) {
+            let matched = self.seq(std::slice::from_ref(node), pos, locs,
+                                   &mut |p, locs: &mut Locs| {
+                // An empty iteration can't make progress.
+                if p == pos && count >= min {
+                    return false;
+                }
+                self.repeat(node, min, max, count + 1, p, rest, locs, k)
+            });
+            if matched {
+                return true;
+            }
+        }
+        count >= min && self.seq(rest, pos, locs, k)
+    }
+}
+
+impl Regex {
+    /// Compiles a basic regular expression.
+    pub fn new(pattern: &str) -> Result<Regex, Error> {
+        Regex::with_syntax(pattern.as_bytes(), Syntax::Basic, false)
+    }
+
+    /// Compiles an extended regular expression.
+    pub fn new_extended(pattern: &str) -> Result<Regex, Error> {
+        Regex::with_syntax(pattern.as_bytes(), Syntax::Extended, false)
+    }
+
+    /// Compiles a regular expression using the given syntax, optionally
+    /// ignoring case.
+    pub fn with_syntax(pattern: &[u8], syntax: Syntax, icase: bool)
+                       -> Result<Regex, Error> {
+        let mut parser = Parser {
+            pat: pattern,
+            pos: 0,
+            syntax,
+            icase,
+            groups: 0,
+            has_alt: false,
+        };
+        let mut alts = parser.parse_alts(0)?;
+        if parser.pos < pattern.len() {
+            return error("unmatched )");
+        }
+        let nodes = if alts.len() == 1 {
+            alts.pop().unwrap()
+        } else {
+            vec![Node::Group(None, alts)]
+        };
+        Ok(Regex {
+            nodes,
+            groups: parser.groups,
+            // POSIX requires the longest of the leftmost matches. Without
+            // alternation, greedy backtracking finds it anyway.
+            longest: syntax == Syntax::Extended && parser.has_alt,
+        })
+    }
+
+    /// Returns the number of parenthesised subexpressions.
+    pub fn groups(&self) -> usize {
+        self.groups
+    }
+
+    /// Returns true if the expression matches anywhere in the text.
+    pub fn is_match(&self, text: &[u8]) -> bool {
+        self.find_at(text, 0).is_some()
+    }
+
+    /// Finds the leftmost match starting at or after the given offset. The
+    /// ^ anchor only ever matches at the start of the text.
+    pub fn find_at(&self, text: &[u8], start: usize) -> Option<Captures> {
+        let matcher = Matcher { text };
+        let anchored = matches!(self.nodes.first(), Some(Node::Bol));
+        let first = match self.nodes.first() {
+            Some(Node::Byte(b)) => Some(*b),
+            _ => None,
+        };
+        let mut s = start;
+        while s <= text.len() {
+            if anchored && s > 0 {
+                return None;
+            }
+            if let Some(b) = first {
+                match text[s..].iter().position(|&c| c == b) {
+                    Some(i) => s += i,
+                    None => return None,
+                }
+            }
+            let mut locs = vec![None; self.groups + 1];
+            let mut best: Option<Locs> = None;
+            let longest = self.longest;
+            matcher.seq(&self.nodes, s, &mut locs, &mut |p, locs: &mut Locs| {
+                if best.as_ref().is_none_or(|b| b[0].is_some_and(|(_, e)| p > e)) {
+                    let mut found = locs.clone();
+                    found[0] = Some((s, p));
+                    best = Some(found);
+                }
+                !longest
+            });
+            if let Some(locs) = best {
+                return Some(Captures { locs });
+            }
+            s += 1;
+        }
+        None
+    }
+}
+
+#[test]
+fn test_regex() {
+    let re = Regex::new("a\\(b*\\)c").unwrap();
+    let m = re.find_at(b"xxabbbc", 0).unwrap();
+    assert_eq!(m.get(0), Some((2, 7)));
+    assert_eq!(m.get(1), Some((3, 6)));
+
+    let re = Regex::new("^\\(.*\\)\\1$").unwrap();
+    assert!(re.is_match(b"abcabc"));
+    assert!(!re.is_match(b"abcab"));
+
+    let re = Regex::new("[[:digit:]]\\{2,3\\}").unwrap();
+    assert_eq!(re.find_at(b"a1b2345", 0).unwrap().get(0), Some((3, 6)));
+
+    let re = Regex::new_extended("x(a|ab)(c|bcd)").unwrap();
+    assert_eq!(re.find_at(b"xabcd", 0).unwrap().get(0), Some((0, 5)));
+
+    let re = Regex::new("a*b$").unwrap();
+    assert!(re.is_match(b"caab"));
+    assert!(!re.is_match(b"caab$"));
+    assert!(Regex::new("a$b").unwrap().is_match(b"a$b"));
+}
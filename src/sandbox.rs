@@ -0,0 +1,244 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+//! Confinement of filters that, once started, need nothing but the
+//! descriptors they already have. A command that only reads its standard
+//! input and writes its standard output calls [`filter`] when it has
+//! parsed its arguments, after which, if `V7_CONFINE` is set to anything
+//! but the empty string, it can open no files, change nothing in the file
+//! system, make no network connections and run no programs, however
+//! hostile its input. On OpenBSD this is done with unveil(2) and pledge(2)
+//! and on Linux with a seccomp(2) filter; elsewhere, and where the system
+//! refuses, the command runs unconfined as before.
+use std::env;
+
+/// Returns whether the commands are to confine themselves.
+pub fn enabled() -> bool {
+    env::var_os("V7_CONFINE").is_some_and(|val| !val.is_empty())
+}
+
+/// Confines the process, if asked to, to reading and writing the
+/// descriptors it has open. Reads and writes of those, memory allocation
+/// and the like go on as before; anything else that the sandbox forbids
+/// fails with EPERM.
+pub fn filter() {
+    if enabled() {
+        imp::filter();
+    }
+}
+
+#[cfg(target_os = "openbsd")]
+mod imp {
+    use std::ptr;
+
+    pub fn filter() {
+        // SAFETY: the promises are a NUL-terminated string, and null
+        // pointers ask unveil to forbid any more unveiling and pledge to
+        // leave the promises for exec alone.
+        unsafe {
+            libc::unveil(ptr::null(), ptr::null());
+            libc::pledge(b"stdio\0".as_ptr() as *const libc::c_char, ptr::null());
+        }
+    }
+}
+
+#[cfg(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64")))]
+mod imp {
+    // The classic BPF instructions the filter is made of, and what a
+    // seccomp filter returns; see linux/filter.h and linux/seccomp.h.
+    const BPF_LD_W_ABS: u16 = 0x20;
+    const BPF_JMP_JEQ_K: u16 = 0x15;
+    const BPF_JMP_JGE_K: u16 = 0x35;
+    const BPF_JMP_JSET_K: u16 = 0x45;
+    const BPF_RET_K: u16 = 0x06;
+    const SECCOMP_RET_KILL_PROCESS: u32 = 0x8000_0000;
+    const SECCOMP_RET_ERRNO: u32 = 0x0005_0000;
+    const SECCOMP_RET_ALLOW: u32 = 0x7fff_0000;
+
+    // The offsets of the system call's number and architecture in the
+    // seccomp_data the filter examines.
+    const NR: u32 = 0;
+    const ARCH: u32 = 4;
+    /// The low half of the fourth argument, on these little-endian
+    /// machines.
+    const ARG3: u32 = 16 + 3 * 8;
+
+    #[cfg(target_arch = "x86_64")]
+    const AUDIT_ARCH: u32 = 0xc000_003e;
+    #[cfg(target_arch = "aarch64")]
+    const AUDIT_ARCH: u32 = 0xc000_00b7;
+
+    /// The system calls a filter needs: reading and writing the
+    /// descriptors it has, or copying between them, allocating memory,
+    /// handling signals and exiting. Any other fails with EPERM.
+    const ALLOWED: &[libc::c_long] = &[
+        libc::SYS_read,
+        libc::SYS_write,
+        libc::SYS_readv,
+        libc::SYS_writev,
+        libc::SYS_pread64,
+        libc::SYS_lseek,
+        libc::SYS_copy_file_range,
+        libc::SYS_splice,
+        libc::SYS_fstat,
+        libc::SYS_close,
+        libc::SYS_mmap,
+        libc::SYS_munmap,
+        libc::SYS_mremap,
+        libc::SYS_mprotect,
+        libc::SYS_brk,
+        libc::SYS_madvise,
+        libc::SYS_futex,
+        libc::SYS_getrandom,
+        libc::SYS_clock_gettime,
+        libc::SYS_rt_sigaction,
+        libc::SYS_rt_sigprocmask,
+        libc::SYS_rt_sigreturn,
+        libc::SYS_sigaltstack,
+        libc::SYS_restart_syscall,
+        libc::SYS_exit,
+        libc::SYS_exit_group,
+    ];
+
+    fn stmt(code: u16, k: u32) -> libc::sock_filter {
+        libc::sock_filter { code, jt: 0, jf: 0, k }
+    }
+
+    fn jump(code: u16, k: u32, jt: u8, jf: u8) -> libc::sock_filter {
+        libc::sock_filter { code, jt, jf, k }
+    }
+
+    /// Returns the filter's program. A system call made through another
+    /// architecture's interface, such as the 32-bit one on x86_64, kills
+    /// the process, since the numbers below would mean other calls there.
+    pub fn program() -> Vec<libc::sock_filter> {
+        let mut prog = vec![
+            stmt(BPF_LD_W_ABS, ARCH),
+            jump(BPF_JMP_JEQ_K, AUDIT_ARCH, 1, 0),
+            stmt(BPF_RET_K, SECCOMP_RET_KILL_PROCESS),
+            stmt(BPF_LD_W_ABS, NR),
+        ];
+        // The x32 interface shares x86_64's architecture, but has numbers
+        // with this bit set.
+        #[cfg(target_arch = "x86_64")]
+        prog.extend([
+            jump(BPF_JMP_JGE_K, 0x4000_0000, 0, 1),
+            stmt(BPF_RET_K, SECCOMP_RET_KILL_PROCESS),
+        ]);
+        let deny = stmt(BPF_RET_K, SECCOMP_RET_ERRNO | libc::EPERM as u32);
+        // newfstatat is how fstat is made, with AT_EMPTY_PATH and the
+        // descriptor, but with a path it would say what is elsewhere.
+        prog.extend([
+            jump(BPF_JMP_JEQ_K, libc::SYS_newfstatat as u32, 0, 4),
+            stmt(BPF_LD_W_ABS, ARG3),
+            jump(BPF_JMP_JSET_K, libc::AT_EMPTY_PATH as u32, 0, 1),
+            stmt(BPF_RET_K, SECCOMP_RET_ALLOW),
+            deny,
+        ]);
+        for &nr in ALLOWED {
+            prog.push(jump(BPF_JMP_JEQ_K, nr as u32, 0, 1));
+            prog.push(stmt(BPF_RET_K, SECCOMP_RET_ALLOW));
+        }
+        prog.push(deny);
+        prog
+    }
+
+    /// Installs a program, returning whether the system took it. This
+    /// allocates nothing, so that a child forked by a test can call it.
+    pub fn install(prog: &mut [libc::sock_filter]) -> bool {
+        let fprog = libc::sock_fprog {
+            len: prog.len() as libc::c_ushort,
+            filter: prog.as_mut_ptr(),
+        };
+        // SAFETY: the program outlives the call, which copies it. Setting
+        // no_new_privs first lets an unprivileged process install the
+        // filter; if either fails, the process just goes unconfined.
+        unsafe {
+            libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1 as libc::c_ulong, 0 as libc::c_ulong,
+                        0 as libc::c_ulong, 0 as libc::c_ulong) == 0
+                && libc::prctl(libc::PR_SET_SECCOMP, libc::SECCOMP_MODE_FILTER as libc::c_ulong,
+                               &fprog as *const libc::sock_fprog) == 0
+        }
+    }
+
+    pub fn filter() {
+        install(&mut program());
+    }
+}
+
+#[cfg(not(any(target_os = "openbsd",
+              all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64")))))]
+mod imp {
+    pub fn filter() {}
+}
+
+#[cfg(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64")))]
+#[test]
+fn test_program() {
+    // Classic BPF jumps are forward only and the program is limited to
+    // 4096 instructions; the last must return.
+    let prog = imp::program();
+    assert!(prog.len() <= 4096);
+    assert_eq!(prog.last().map(|insn| insn.code), Some(0x06));
+}
+
+#[cfg(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64")))]
+#[test]
+fn test_confined() {
+    // The filter is installed in a child, so that the tests go on
+    // unconfined, which then tries what the filter denies and what it
+    // allows. The child's status is the number of the first check that
+    // failed, or 0 if none did.
+    let mut prog = imp::program();
+    // SAFETY: getpid can't fail.
+    let parent = unsafe { libc::getpid() };
+    // SAFETY: the child makes only system calls, and exits without
+    // returning into the test harness.
+    unsafe {
+        match libc::fork() {
+            0 => {
+                if !imp::install(&mut prog) {
+                    libc::_exit(99);
+                }
+                let eperm = |ret: libc::c_long| ret == -1 && *libc::__errno_location() == libc::EPERM;
+                if !eperm(libc::open(c"/".as_ptr(), libc::O_RDONLY).into()) {
+                    libc::_exit(1);
+                }
+                if !eperm(libc::syscall(libc::SYS_io_uring_setup, 1, std::ptr::null_mut::<u8>())) {
+                    libc::_exit(2);
+                }
+                if !eperm(libc::socket(libc::AF_UNIX, libc::SOCK_STREAM, 0).into()) {
+                    libc::_exit(3);
+                }
+                // fchmodat2, which kernels before 6.6 don't have.
+                if !eperm(libc::syscall(452, libc::AT_FDCWD, c"/".as_ptr(), 0o755, 0)) {
+                    libc::_exit(4);
+                }
+                if !eperm(libc::syscall(libc::SYS_pidfd_getfd, 0, 0, 0)) {
+                    libc::_exit(5);
+                }
+                if !eperm(libc::kill(parent, 0).into()) {
+                    libc::_exit(6);
+                }
+                let path = c"/".as_ptr();
+                let mut stat: libc::stat = std::mem::zeroed();
+                if !eperm(libc::syscall(libc::SYS_newfstatat, libc::AT_FDCWD, path, &mut stat, 0)) {
+                    libc::_exit(7);
+                }
+                // What a filter needs still works.
+                if libc::fstat(1, &mut stat) != 0 || libc::close(-1) != -1
+                    || *libc::__errno_location() != libc::EBADF {
+                    libc::_exit(8);
+                }
+                libc::_exit(0);
+            },
+            pid => {
+                assert!(pid > 0);
+                let mut status = 0;
+                assert_eq!(libc::waitpid(pid, &mut status, 0), pid);
+                assert!(libc::WIFEXITED(status));
+                assert_eq!(libc::WEXITSTATUS(status), 0);
+            },
+        }
+    }
+}
@@ -0,0 +1,80 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+//! Signal names and numbers, and the handling of SIGPIPE.
+
+/// The signals, by name without the SIG prefix, in the order of their
+/// traditional numbers.
+pub const SIGNALS: [(&str, libc::c_int); 29] = [
+    ("HUP", libc::SIGHUP),
+    ("INT", libc::SIGINT),
+    ("QUIT", libc::SIGQUIT),
+    ("ILL", libc::SIGILL),
+    ("TRAP", libc::SIGTRAP),
+    ("ABRT", libc::SIGABRT),
+    ("BUS", libc::SIGBUS),
+    ("FPE", libc::SIGFPE),
+    ("KILL", libc::SIGKILL),
+    ("USR1", libc::SIGUSR1),
+    ("SEGV", libc::SIGSEGV),
+    ("USR2", libc::SIGUSR2),
+    ("PIPE", libc::SIGPIPE),
+    ("ALRM", libc::SIGALRM),
+    ("TERM", libc::SIGTERM),
+    ("CHLD", libc::SIGCHLD),
+    ("CONT", libc::SIGCONT),
+    ("STOP", libc::SIGSTOP),
+    ("TSTP", libc::SIGTSTP),
+    ("TTIN", libc::SIGTTIN),
+    ("TTOU", libc::SIGTTOU),
+    ("URG", libc::SIGURG),
+    ("XCPU", libc::SIGXCPU),
+    ("XFSZ", libc::SIGXFSZ),
+    ("VTALRM", libc::SIGVTALRM),
+    ("PROF", libc::SIGPROF),
+    ("WINCH", libc::SIGWINCH),
+    ("IO", libc::SIGIO),
+    ("SYS", libc::SIGSYS),
+];
+
+/// Returns the number of the named signal. The name may have the SIG
+/// prefix, and case is ignored. A number is also accepted, as is 0, the
+/// null signal.
+pub fn number(name: &str) -> Option<libc::c_int> {
+    if let Ok(n) = name.parse::<libc::c_int>() {
+        return (n == 0 || SIGNALS.iter().any(|&(_, sig)| sig == n)).then_some(n);
+    }
+    let upper = name.to_ascii_uppercase();
+    let name = upper.strip_prefix("SIG").unwrap_or(&upper);
+    SIGNALS.iter().find(|&&(n, _)| n == name).map(|&(_, sig)| sig)
+}
+
+/// Returns the name of the numbered signal, without the SIG prefix.
+pub fn name(number: libc::c_int) -> Option<&'static str> {
+    SIGNALS.iter().find(|&&(_, sig)| sig == number).map(|&(name, _)| name)
+}
+
+/// Restores the default action for SIGPIPE, which the Rust runtime
+/// ignores, so that a command writing to a pipe that nothing reads any
+/// more is terminated quietly, as a C program would be, instead of
+/// reporting the EPIPE errors its writes fail with. Each command's binary
+/// calls this first. Elsewhere there is no SIGPIPE, and it does nothing.
+pub fn default_sigpipe() {
+    // SAFETY: setting a signal's action to the default has no
+    // preconditions.
+    #[cfg(unix)]
+    unsafe {
+        libc::signal(libc::SIGPIPE, libc::SIG_DFL);
+    }
+}
+
+#[test]
+fn test_signals() {
+    assert_eq!(number("TERM"), Some(libc::SIGTERM));
+    assert_eq!(number("sigkill"), Some(libc::SIGKILL));
+    assert_eq!(number("9"), Some(libc::SIGKILL));
+    assert_eq!(number("0"), Some(0));
+    assert_eq!(number("BOGUS"), None);
+    assert_eq!(name(libc::SIGHUP), Some("HUP"));
+    assert_eq!(name(0), None);
+}
@@ -0,0 +1,48 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+//! Parsing of sizes given on the command line or in the environment,
+//! such as "512", "20k" or "1m".
+
+/// Returns the multiplier for a size suffix: w for two-byte words, b for
+/// 512-byte blocks, k for kibibytes, m for mebibytes and g for gibibytes.
+/// Upper case is also accepted.
+pub fn multiplier(suffix: &str) -> Option<u64> {
+    match suffix {
+        "" => Some(1),
+        "w" | "W" => Some(2),
+        "b" | "B" => Some(512),
+        "k" | "K" => Some(1 << 10),
+        "m" | "M" => Some(1 << 20),
+        "g" | "G" => Some(1 << 30),
+        _ => None,
+    }
+}
+
+/// Parses a decimal number of bytes with an optional suffix.
+pub fn parse(s: &str) -> Option<u64> {
+    let n = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    if n == 0 {
+        return None;
+    }
+    let num: u64 = s[..n].parse().ok()?;
+    num.checked_mul(multiplier(&s[n..])?)
+}
+
+/// Parses a product of sizes separated by x, such as "2x512" or "10bx4".
+pub fn parse_product(s: &str) -> Option<u64> {
+    s.split('x').try_fold(1u64, |product, size| product.checked_mul(parse(size)?))
+}
+
+#[test]
+fn test_parse() {
+    assert_eq!(parse("512"), Some(512));
+    assert_eq!(parse("2b"), Some(1024));
+    assert_eq!(parse("4k"), Some(4096));
+    assert_eq!(parse("1M"), Some(1048576));
+    assert_eq!(parse("k"), None);
+    assert_eq!(parse("1x"), None);
+    assert_eq!(parse("99999999999999999999g"), None);
+    assert_eq!(parse_product("2x3w"), Some(12));
+    assert_eq!(parse_product("2x"), None);
+}
@@ -0,0 +1,184 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+//! Sparse files: files with holes, which read as NULs but take no space.
+//! A file's holes are found with lseek's SEEK_DATA and SEEK_HOLE where
+//! the system has them, so that a Reader need not read them; elsewhere
+//! the whole file is taken to be data. A Writer makes holes of the runs
+//! of NULs written to it, so that a copy of a sparse file is sparse too.
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+
+/// The size of the runs of NULs that a Writer skips, rather than writes.
+/// Filesystems allocate whole blocks, so shorter runs would save nothing.
+const BLOCK: usize = 4096;
+
+/// Returns the ranges of the file, from the current position to its end,
+/// that hold data.
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+pub fn data(file: &File) -> io::Result<Vec<Range<u64>>> {
+    use std::os::unix::io::AsRawFd;
+
+    let fd = file.as_raw_fd();
+    let seek = |offset: u64, whence| -> io::Result<Option<u64>> {
+        // SAFETY: lseek has no memory safety preconditions.
+        match unsafe { libc::lseek(fd, offset as libc::off_t, whence) } {
+            -1 => {
+                let e = io::Error::last_os_error();
+                // ENXIO means that there is no data after the offset.
+                if e.raw_os_error() == Some(libc::ENXIO) { Ok(None) } else { Err(e) }
+            },
+            n => Ok(Some(n as u64)),
+        }
+    };
+    let start = (&*file).stream_position()?;
+    let mut ranges = Vec::new();
+    let mut pos = start;
+    while let Some(begin) = seek(pos, libc::SEEK_DATA)? {
+        let end = seek(begin, libc::SEEK_HOLE)?.unwrap_or(begin);
+        if end <= begin {
+            break;
+        }
+        ranges.push(begin..end);
+        pos = end;
+    }
+    (&*file).seek(SeekFrom::Start(start))?;
+    Ok(ranges)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "freebsd")))]
+pub fn data(file: &File) -> io::Result<Vec<Range<u64>>> {
+    let start = (&*file).stream_position()?;
+    Ok(vec![start..file.metadata()?.len()])
+}
+
+/// Reads a file, giving NULs for its holes without reading them.
+pub struct Reader {
+    file: File,
+    /// The position of the next byte to be read.
+    pos: u64,
+    /// Whether the file itself is at that position, rather than behind it
+    /// for having skipped a hole.
+    in_step: bool,
+    len: u64,
+    /// The data still to be read, in order.
+    data: std::vec::IntoIter<Range<u64>>,
+    next: Option<Range<u64>>,
+}
+
+impl Reader {
+    pub fn new(mut file: File) -> io::Result<Reader> {
+        let mut data = data(&file)?.into_iter();
+        let next = data.next();
+        let pos = file.stream_position()?;
+        let len = file.metadata()?.len();
+        Ok(Reader { file, pos, in_step: true, len, data, next })
+    }
+
+    /// Reads from the file itself, up to the given limit.
+    fn read_file(&mut self, buf: &mut [u8], limit: u64) -> io::Result<usize> {
+        if !self.in_step {
+            self.file.seek(SeekFrom::Start(self.pos))?;
+            self.in_step = true;
+        }
+        let want = buf.len().min(usize::try_from(limit - self.pos).unwrap_or(usize::MAX));
+        let n = self.file.read(&mut buf[..want])?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl Read for Reader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.next.as_ref().is_some_and(|range| range.end <= self.pos) {
+            self.next = self.data.next();
+        }
+        match self.next.clone() {
+            Some(range) if range.start <= self.pos => self.read_file(buf, range.end),
+            // A file that has grown since it was opened is read to its end.
+            None if self.pos >= self.len => self.read_file(buf, u64::MAX),
+            // A hole, up to the next data or the end of the file.
+            next => {
+                let end = next.map_or(self.len, |range| range.start);
+                let n = buf.len().min(usize::try_from(end - self.pos).unwrap_or(usize::MAX));
+                buf[..n].fill(0);
+                self.pos += n as u64;
+                self.in_step = false;
+                Ok(n)
+            },
+        }
+    }
+}
+
+/// Writes to a file, leaving holes where whole blocks of NULs would be
+/// written. The file must be finished, to give it its full length if it
+/// ends in a hole.
+pub struct Writer<W: Write + Seek> {
+    inner: W,
+    /// The length of the hole to be left before the next data.
+    hole: u64,
+}
+
+impl<W: Write + Seek> Writer<W> {
+    pub fn new(inner: W) -> Writer<W> {
+        Writer { inner, hole: 0 }
+    }
+
+    fn skip_hole(&mut self) -> io::Result<()> {
+        if self.hole > 0 {
+            let hole = i64::try_from(self.hole).map_err(|_| io::Error::other("hole too large"))?;
+            self.inner.seek(SeekFrom::Current(hole))?;
+            self.hole = 0;
+        }
+        Ok(())
+    }
+
+    /// Extends the file to the end of any hole it ends in, by writing its
+    /// last byte, and returns the underlying writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        if self.hole > 0 {
+            self.hole -= 1;
+            self.skip_hole()?;
+            self.inner.write_all(&[0])?;
+        }
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write + Seek> Write for Writer<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for block in buf.chunks(BLOCK) {
+            if block.len() == BLOCK && block.iter().all(|&c| c == 0) {
+                self.hole += BLOCK as u64;
+            } else {
+                self.skip_hole()?;
+                self.inner.write_all(block)?;
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[test]
+fn test_sparse() {
+    use std::fs::OpenOptions;
+    use crate::tempfile::TempFile;
+
+    let tmp = TempFile::new("sparse").unwrap();
+    let mut writer = Writer::new(OpenOptions::new().write(true).open(tmp.path()).unwrap());
+    let mut data = vec![0; 3 * BLOCK];
+    data[BLOCK] = b'x';
+    writer.write_all(&data).unwrap();
+    writer.write_all(&[0; 2 * BLOCK]).unwrap();
+    writer.finish().unwrap();
+    let mut copy = Vec::new();
+    Reader::new(File::open(tmp.path()).unwrap()).unwrap().read_to_end(&mut copy).unwrap();
+    data.resize(5 * BLOCK, 0);
+    assert_eq!(copy, data);
+}
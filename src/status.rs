@@ -0,0 +1,21 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+//! Exit statuses. A command exits with SUCCESS if all went well, FAILURE
+//! if something went wrong and USAGE if it was invoked wrongly. Commands
+//! that answer a question, such as cmp, diff, look and test, exit with
+//! TRUE or FALSE for the answer, and TROUBLE if they can't tell.
+
+pub const SUCCESS: i32 = 0;
+pub const FAILURE: i32 = 1;
+pub const USAGE: i32 = 2;
+
+pub const TRUE: i32 = 0;
+pub const FALSE: i32 = 1;
+pub const TROUBLE: i32 = 2;
+
+/// The status of a shell, or a command like chroot that runs another,
+/// when the other command is found but can't be executed.
+pub const NOT_EXECUTABLE: i32 = 126;
+/// The status when the other command can't be found.
+pub const NOT_FOUND: i32 = 127;
@@ -0,0 +1,164 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+//! Temporary files. A temporary file is removed when it is dropped and,
+//! once `remove_on_signal` has been called, also when the process is
+//! terminated by SIGHUP, SIGINT or SIGTERM.
+use std::env;
+use std::ffi::CString;
+use std::fs;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::ptr;
+use std::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+
+/// The number of temporary files that can be removed by the signal
+/// handler. Files created beyond this are still removed when dropped.
+const SLOTS: usize = 64;
+
+/// The paths of live temporary files, for use by the signal handler.
+static PATHS: [AtomicPtr<libc::c_char>; SLOTS] =
+    [const { AtomicPtr::new(ptr::null_mut()) }; SLOTS];
+
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+fn register(path: &Path) -> Option<usize> {
+    let cpath = CString::new(path.as_os_str().as_bytes()).ok()?.into_raw();
+    for (i, slot) in PATHS.iter().enumerate() {
+        if slot.compare_exchange(ptr::null_mut(), cpath, Ordering::SeqCst,
+                                 Ordering::SeqCst).is_ok() {
+            return Some(i);
+        }
+    }
+    // SAFETY: the pointer came from into_raw above and was never shared.
+    drop(unsafe { CString::from_raw(cpath) });
+    None
+}
+
+fn unregister(slot: usize) {
+    // The path is deliberately leaked rather than freed, as the signal
+    // handler may be using it.
+    PATHS[slot].store(ptr::null_mut(), Ordering::SeqCst);
+}
+
+extern "C" fn on_signal(sig: libc::c_int) {
+    for slot in PATHS.iter() {
+        let path = slot.load(Ordering::SeqCst);
+        if !path.is_null() {
+            // SAFETY: unlink is async-signal-safe and registered paths
+            // are never freed.
+            unsafe {
+                libc::unlink(path);
+            }
+        }
+    }
+    // SAFETY: restore the default action and re-raise the signal so that
+    // the process terminates with the expected status.
+    unsafe {
+        libc::signal(sig, libc::SIG_DFL);
+        libc::raise(sig);
+    }
+}
+
+/// Installs handlers that remove temporary files before the process is
+/// terminated by SIGHUP, SIGINT or SIGTERM. Signals that are being
+/// ignored remain ignored.
+pub fn remove_on_signal() {
+    for sig in [libc::SIGHUP, libc::SIGINT, libc::SIGTERM] {
+        let handler = on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        // SAFETY: the handler only calls async-signal-safe functions.
+        unsafe {
+            if libc::signal(sig, handler) == libc::SIG_IGN {
+                libc::signal(sig, libc::SIG_IGN);
+            }
+        }
+    }
+}
+
+/// Returns the directory in which temporary files are created by
+/// default: $TMPDIR if set, otherwise /tmp.
+pub fn temp_dir() -> PathBuf {
+    match env::var_os("TMPDIR") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from("/tmp"),
+    }
+}
+
+/// A temporary file, opened for both reading and writing.
+pub struct TempFile {
+    path: PathBuf,
+    file: File,
+    slot: Option<usize>,
+}
+
+impl TempFile {
+    /// Creates a temporary file in the default directory whose name
+    /// starts with the given prefix.
+    pub fn new(prefix: &str) -> io::Result<TempFile> {
+        TempFile::new_in(temp_dir(), prefix)
+    }
+
+    /// Creates a temporary file in the given directory.
+    pub fn new_in<P: AsRef<Path>>(dir: P, prefix: &str) -> io::Result<TempFile> {
+        loop {
+            let n = COUNTER.fetch_add(1, Ordering::SeqCst);
+            let path = dir.as_ref().join(format!("{}{}.{}", prefix, process::id(), n));
+            let mut options = OpenOptions::new();
+            options.read(true).write(true).create_new(true);
+            match options.open(&path) {
+                Ok(file) => {
+                    let slot = register(&path);
+                    return Ok(TempFile { path, file, slot });
+                },
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Returns the path of the temporary file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Seeks back to the start of the file, typically so that what has
+    /// been written can be read back.
+    pub fn rewind(&mut self) -> io::Result<()> {
+        self.file.seek(SeekFrom::Start(0)).map(|_| ())
+    }
+}
+
+impl Read for TempFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.file.read(buf)
+    }
+}
+
+impl Write for TempFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.file.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+impl Seek for TempFile {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.file.seek(pos)
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+        if let Some(slot) = self.slot {
+            unregister(slot);
+        }
+    }
+}
@@ -0,0 +1,278 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+//! Broken-down times, in the local time zone or in UTC.
+use std::ffi::CStr;
+use std::mem;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const DAY_NAMES: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+pub const MONTH_NAMES: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+pub const LONG_DAY_NAMES: [&str; 7] = [
+    "Sunday", "Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday",
+];
+pub const LONG_MONTH_NAMES: [&str; 12] = [
+    "January", "February", "March", "April", "May", "June", "July", "August", "September",
+    "October", "November", "December",
+];
+
+/// A time broken down into its calendar fields.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Tm {
+    pub year: i32,
+    /// The month, from 1 to 12.
+    pub month: u32,
+    /// The day of the month, from 1.
+    pub day: u32,
+    pub hour: u32,
+    pub min: u32,
+    pub sec: u32,
+    /// The day of the week, with Sunday as 0.
+    pub wday: u32,
+    /// The day of the year, with 1 January as 0.
+    pub yday: u32,
+    /// The offset from UTC in seconds, positive to the east.
+    pub utc_offset: i64,
+    /// The abbreviated name of the time zone, such as "GMT".
+    pub zone: String,
+}
+
+/// Returns the current time in seconds and nanoseconds since the epoch.
+pub fn now() -> (i64, u32) {
+    match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(d) => (d.as_secs() as i64, d.subsec_nanos()),
+        Err(e) => (-(e.duration().as_secs() as i64), 0),
+    }
+}
+
+/// Returns the number of days from 1 January 1970 to the given date in
+/// the proleptic Gregorian calendar.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let month = month as i64;
+    let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+impl Tm {
+    fn convert(secs: i64, local: bool) -> Tm {
+        let t = secs as libc::time_t;
+        // SAFETY: tm is plain data, and localtime_r and gmtime_r only
+        // write to the structure they are given.
+        let tm = unsafe {
+            let mut tm: libc::tm = mem::zeroed();
+            if local {
+                libc::localtime_r(&t, &mut tm);
+            } else {
+                libc::gmtime_r(&t, &mut tm);
+            }
+            tm
+        };
+        let zone = if tm.tm_zone.is_null() {
+            String::new()
+        } else {
+            // SAFETY: a non-null tm_zone points to a static string.
+            unsafe { CStr::from_ptr(tm.tm_zone) }.to_string_lossy().into_owned()
+        };
+        Tm {
+            year: tm.tm_year + 1900,
+            month: tm.tm_mon as u32 + 1,
+            day: tm.tm_mday as u32,
+            hour: tm.tm_hour as u32,
+            min: tm.tm_min as u32,
+            sec: tm.tm_sec as u32,
+            wday: tm.tm_wday as u32,
+            yday: tm.tm_yday as u32,
+            utc_offset: tm.tm_gmtoff as i64,
+            zone,
+        }
+    }
+
+    /// Converts seconds since the epoch to local time.
+    pub fn local(secs: i64) -> Tm {
+        Tm::convert(secs, true)
+    }
+
+    /// Converts seconds since the epoch to UTC.
+    pub fn utc(secs: i64) -> Tm {
+        Tm::convert(secs, false)
+    }
+
+    /// Converts the time, taken to be local time, to seconds since the
+    /// epoch. The weekday, day of the year, offset and zone are ignored.
+    /// Returns None if any field is out of range, or if the time doesn't
+    /// exist, as on 30 February.
+    pub fn local_secs(&self) -> Option<i64> {
+        if !self.in_range() {
+            return None;
+        }
+        // SAFETY: tm is plain data, which mktime only reads and
+        // normalizes.
+        let secs = unsafe {
+            let mut tm: libc::tm = mem::zeroed();
+            tm.tm_year = self.year - 1900;
+            tm.tm_mon = self.month as i32 - 1;
+            tm.tm_mday = self.day as i32;
+            tm.tm_hour = self.hour as i32;
+            tm.tm_min = self.min as i32;
+            tm.tm_sec = self.sec as i32;
+            tm.tm_isdst = -1;
+            libc::mktime(&mut tm) as i64
+        };
+        let tm = Tm::local(secs);
+        if (tm.year, tm.month, tm.day) != (self.year, self.month, self.day) {
+            return None;
+        }
+        Some(secs)
+    }
+
+    /// Converts the time, taken to be UTC, to seconds since the epoch,
+    /// ignoring the same fields as local_secs, and returning None in the
+    /// same cases.
+    pub fn utc_secs(&self) -> Option<i64> {
+        if !self.in_range() {
+            return None;
+        }
+        let secs = Tm { utc_offset: 0, ..self.clone() }.secs();
+        let tm = Tm::utc(secs);
+        if (tm.year, tm.month, tm.day) != (self.year, self.month, self.day) {
+            return None;
+        }
+        Some(secs)
+    }
+
+    /// Returns true if the date and time fields are within their ranges,
+    /// allowing for a leap second.
+    fn in_range(&self) -> bool {
+        (1..=12).contains(&self.month) && (1..=31).contains(&self.day)
+            && self.hour <= 23 && self.min <= 59 && self.sec <= 60
+    }
+
+    /// Formats the time as ctime(3) does, for example
+    /// "Thu Mar  4 05:06:07 2021".
+    pub fn ctime(&self) -> String {
+        format!("{} {} {:2} {:02}:{:02}:{:02} {}",
+                DAY_NAMES[self.wday as usize], MONTH_NAMES[self.month as usize - 1],
+                self.day, self.hour, self.min, self.sec, self.year)
+    }
+
+    /// Returns the time in seconds since the epoch.
+    pub fn secs(&self) -> i64 {
+        let days = days_from_civil(self.year as i64, self.month, self.day);
+        days * 86400 + (self.hour * 3600 + self.min * 60 + self.sec) as i64 - self.utc_offset
+    }
+
+    /// Formats the time as strftime(3) does in the POSIX locale. The
+    /// conversions supported are:
+    ///
+    ///   %a %A  abbreviated and full weekday name
+    ///   %b %B  abbreviated and full month name (%h is %b)
+    ///   %c     date and time, as "%a %b %e %H:%M:%S %Y"
+    ///   %C     century
+    ///   %d %e  day of the month, padded with 0 or with a blank
+    ///   %D %x  date, as "%m/%d/%y"
+    ///   %F     date, as "%Y-%m-%d"
+    ///   %H %k  hour (00-23), padded with 0 or with a blank
+    ///   %I %l  hour (01-12), padded with 0 or with a blank
+    ///   %j     day of the year (001-366)
+    ///   %m %M  month and minute
+    ///   %n %t  newline and tab
+    ///   %p     AM or PM
+    ///   %r     time, as "%I:%M:%S %p"
+    ///   %R     time, as "%H:%M"
+    ///   %s     seconds since the epoch
+    ///   %S     second
+    ///   %T %X  time, as "%H:%M:%S"
+    ///   %u %w  weekday, with Monday as 1 or Sunday as 0
+    ///   %U %W  week of the year, starting on Sunday or on Monday
+    ///   %y %Y  year without and with the century
+    ///   %z %Z  offset from UTC and time zone name
+    ///   %%     a %
+    ///
+    /// Anything else is copied as is.
+    pub fn format(&self, fmt: &str) -> String {
+        let mut s = String::new();
+        let mut chars = fmt.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                s.push(c);
+                continue;
+            }
+            let hour12 = (self.hour + 11) % 12 + 1;
+            match chars.next() {
+                Some('a') => s.push_str(DAY_NAMES[self.wday as usize]),
+                Some('A') => s.push_str(LONG_DAY_NAMES[self.wday as usize]),
+                Some('b') | Some('h') => s.push_str(MONTH_NAMES[self.month as usize - 1]),
+                Some('B') => s.push_str(LONG_MONTH_NAMES[self.month as usize - 1]),
+                Some('c') => s.push_str(&self.format("%a %b %e %H:%M:%S %Y")),
+                Some('C') => s.push_str(&format!("{:02}", self.year.div_euclid(100))),
+                Some('d') => s.push_str(&format!("{:02}", self.day)),
+                Some('D') | Some('x') => s.push_str(&self.format("%m/%d/%y")),
+                Some('e') => s.push_str(&format!("{:2}", self.day)),
+                Some('F') => s.push_str(&self.format("%Y-%m-%d")),
+                Some('H') => s.push_str(&format!("{:02}", self.hour)),
+                Some('I') => s.push_str(&format!("{:02}", hour12)),
+                Some('j') => s.push_str(&format!("{:03}", self.yday + 1)),
+                Some('k') => s.push_str(&format!("{:2}", self.hour)),
+                Some('l') => s.push_str(&format!("{:2}", hour12)),
+                Some('m') => s.push_str(&format!("{:02}", self.month)),
+                Some('M') => s.push_str(&format!("{:02}", self.min)),
+                Some('n') => s.push('\n'),
+                Some('p') => s.push_str(if self.hour < 12 { "AM" } else { "PM" }),
+                Some('r') => s.push_str(&self.format("%I:%M:%S %p")),
+                Some('R') => s.push_str(&self.format("%H:%M")),
+                Some('s') => s.push_str(&self.secs().to_string()),
+                Some('S') => s.push_str(&format!("{:02}", self.sec)),
+                Some('t') => s.push('\t'),
+                Some('T') | Some('X') => s.push_str(&self.format("%H:%M:%S")),
+                Some('u') => s.push_str(&(if self.wday == 0 { 7 } else { self.wday }).to_string()),
+                Some('U') => s.push_str(&format!("{:02}", (self.yday + 7 - self.wday) / 7)),
+                Some('w') => s.push_str(&self.wday.to_string()),
+                Some('W') => {
+                    let monday = (self.wday + 6) % 7;
+                    s.push_str(&format!("{:02}", (self.yday + 7 - monday) / 7));
+                },
+                Some('y') => s.push_str(&format!("{:02}", self.year.rem_euclid(100))),
+                Some('Y') => s.push_str(&self.year.to_string()),
+                Some('z') => s.push_str(&self.numeric_zone()),
+                Some('Z') => s.push_str(&self.zone),
+                Some('%') => s.push('%'),
+                Some(c) => {
+                    s.push('%');
+                    s.push(c);
+                },
+                None => s.push('%'),
+            }
+        }
+        s
+    }
+
+    /// Formats the offset from UTC as [+|-]hhmm.
+    pub fn numeric_zone(&self) -> String {
+        let sign = if self.utc_offset < 0 { '-' } else { '+' };
+        let mins = self.utc_offset.abs() / 60;
+        format!("{}{:02}{:02}", sign, mins / 60, mins % 60)
+    }
+}
+
+#[test]
+fn test_utc() {
+    let tm = Tm::utc(1614834367);
+    assert_eq!(tm.ctime(), "Thu Mar  4 05:06:07 2021");
+    assert_eq!(tm.yday, 62);
+    assert_eq!(tm.numeric_zone(), "+0000");
+    assert_eq!(tm.secs(), 1614834367);
+    assert_eq!(tm.format("%A %d %B %Y %j %I%p %U %W %u %s %% %q"),
+               "Thursday 04 March 2021 063 05AM 09 09 4 1614834367 % %q");
+    assert_eq!(tm.format("%c|%D|%e|%k|%l|%Z"), "Thu Mar  4 05:06:07 2021|03/04/21| 4| 5| 5|GMT");
+    let feb30 = Tm { month: 2, day: 30, ..tm.clone() };
+    assert_eq!(feb30.local_secs(), None);
+    assert_eq!(feb30.utc_secs(), None);
+    assert_eq!(Tm { month: 13, ..tm.clone() }.local_secs(), None);
+    assert_eq!(Tm { utc_offset: 3600, ..tm }.utc_secs(), Some(1614834367));
+}
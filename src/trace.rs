@@ -0,0 +1,40 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+//! Tracing of the operations commands perform on the filesystem. When
+//! V7_TRACE is set to anything but the empty string, each file a command
+//! creates or opens for writing, and each it removes, renames, links or
+//! changes the mode of, is reported on the standard error with its
+//! arguments and result, as in
+//!
+//! ```text
+//! rm: unlink("d/x") = ok
+//! rm: rmdir("d") = Directory not empty (os error 39)
+//! ```
+//!
+//! so that what a command such as rm -r or tar x actually did can be seen.
+use std::env;
+use std::fmt;
+use std::io;
+use std::sync::OnceLock;
+
+use crate::diag;
+
+static ENABLED: OnceLock<bool> = OnceLock::new();
+
+/// Returns whether operations are being traced.
+pub fn enabled() -> bool {
+    *ENABLED.get_or_init(|| env::var_os("V7_TRACE").is_some_and(|val| !val.is_empty()))
+}
+
+/// Reports an operation, given by its name and arguments, and its result,
+/// if operations are being traced. Returns the result.
+pub fn op<T>(name: &str, args: fmt::Arguments, result: io::Result<T>) -> io::Result<T> {
+    if enabled() {
+        match &result {
+            Ok(_) => diag::report(false, format_args!("{}({}) = ok", name, args)),
+            Err(e) => diag::report(false, format_args!("{}({}) = {}", name, args, e)),
+        }
+    }
+    result
+}
@@ -0,0 +1,78 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+//! Terminals.
+use std::ffi::CStr;
+use std::io;
+use std::mem::MaybeUninit;
+use std::os::unix::io::RawFd;
+
+/// Returns the path name of the terminal open on a file descriptor, or
+/// None if it isn't a terminal.
+pub fn name(fd: RawFd) -> Option<String> {
+    let mut buf = [0 as libc::c_char; 256];
+    // SAFETY: buf is writable for its whole length, which is passed.
+    if unsafe { libc::ttyname_r(fd, buf.as_mut_ptr(), buf.len()) } != 0 {
+        return None;
+    }
+    // SAFETY: on success, ttyname_r leaves a NUL-terminated string in buf.
+    Some(unsafe { CStr::from_ptr(buf.as_ptr()) }.to_string_lossy().into_owned())
+}
+
+/// Returns the path name of the user's terminal: the first of the
+/// standard input, output and error that is a terminal.
+pub fn controlling() -> Option<String> {
+    (0..3).find_map(name)
+}
+
+/// Returns the number of rows and columns of the terminal open on a file
+/// descriptor, or None if it isn't a terminal or doesn't know its size.
+pub fn size(fd: RawFd) -> Option<(usize, usize)> {
+    let mut ws = libc::winsize { ws_row: 0, ws_col: 0, ws_xpixel: 0, ws_ypixel: 0 };
+    // SAFETY: TIOCGWINSZ writes a winsize, which ws is.
+    if unsafe { libc::ioctl(fd, libc::TIOCGWINSZ, &mut ws) } != 0 || ws.ws_row == 0 || ws.ws_col == 0 {
+        return None;
+    }
+    Some((ws.ws_row as usize, ws.ws_col as usize))
+}
+
+/// A terminal in raw mode, in which each key is read as it is typed,
+/// without being echoed or generating signals. The terminal's mode is
+/// restored when this is dropped.
+pub struct Raw {
+    fd: RawFd,
+    saved: libc::termios,
+}
+
+impl Raw {
+    /// Puts the terminal open on a file descriptor into raw mode.
+    pub fn new(fd: RawFd) -> io::Result<Raw> {
+        let mut saved = MaybeUninit::<libc::termios>::uninit();
+        // SAFETY: tcgetattr fills in the termios on success, and only then
+        // is it assumed to be initialized.
+        let saved = unsafe {
+            if libc::tcgetattr(fd, saved.as_mut_ptr()) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            saved.assume_init()
+        };
+        let mut raw = saved;
+        raw.c_lflag &= !(libc::ICANON | libc::ECHO | libc::ISIG);
+        raw.c_cc[libc::VMIN] = 1;
+        raw.c_cc[libc::VTIME] = 0;
+        // SAFETY: raw is a valid termios.
+        if unsafe { libc::tcsetattr(fd, libc::TCSADRAIN, &raw) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Raw { fd, saved })
+    }
+}
+
+impl Drop for Raw {
+    fn drop(&mut self) {
+        // SAFETY: saved is the termios tcgetattr returned.
+        unsafe {
+            libc::tcsetattr(self.fd, libc::TCSADRAIN, &self.saved);
+        }
+    }
+}
@@ -0,0 +1,74 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+//! User and group lookup, from the /etc/passwd and /etc/group files.
+use std::fs;
+
+const PASSWD: &str = "/etc/passwd";
+const GROUP: &str = "/etc/group";
+
+/// Returns the name and numeric id (the first and third fields) from the
+/// first line of the file for which they satisfy the predicate.
+fn lookup<F: Fn(&str, u32) -> bool>(file: &str, pred: F) -> Option<(String, u32)> {
+    let data = fs::read(file).ok()?;
+    String::from_utf8_lossy(&data).lines().find_map(|line| {
+        let mut fields = line.split(':');
+        let name = fields.next()?;
+        let id = fields.nth(1)?.parse().ok()?;
+        if pred(name, id) { Some((name.to_string(), id)) } else { None }
+    })
+}
+
+/// Returns the user id of the named user.
+pub fn uid(name: &str) -> Option<u32> {
+    lookup(PASSWD, |n, _| n == name).map(|(_, id)| id)
+}
+
+/// Returns the group id of the named group.
+pub fn gid(name: &str) -> Option<u32> {
+    lookup(GROUP, |n, _| n == name).map(|(_, id)| id)
+}
+
+/// Returns the user id of the user given by name or, if there is no such
+/// user, by number, as chown takes them.
+pub fn parse_uid(s: &str) -> Option<u32> {
+    uid(s).or_else(|| s.parse().ok())
+}
+
+/// Returns the group id of the group given by name or by number.
+pub fn parse_gid(s: &str) -> Option<u32> {
+    gid(s).or_else(|| s.parse().ok())
+}
+
+/// Returns the name of the user with the given id.
+pub fn user_name(uid: u32) -> Option<String> {
+    lookup(PASSWD, |_, id| id == uid).map(|(name, _)| name)
+}
+
+/// Returns the name of the group with the given id.
+pub fn group_name(gid: u32) -> Option<String> {
+    lookup(GROUP, |_, id| id == gid).map(|(name, _)| name)
+}
+
+/// Returns the login group id of the named user (the fourth field of its
+/// line in the password file).
+pub fn login_gid(name: &str) -> Option<u32> {
+    let data = fs::read(PASSWD).ok()?;
+    String::from_utf8_lossy(&data).lines().find_map(|line| {
+        let fields: Vec<&str> = line.split(':').collect();
+        if fields[0] == name { fields.get(3)?.parse().ok() } else { None }
+    })
+}
+
+/// Returns the ids of the groups that list the named user as a member.
+pub fn member_gids(name: &str) -> Vec<u32> {
+    let data = match fs::read(GROUP) {
+        Ok(data) => data,
+        Err(_) => return Vec::new(),
+    };
+    String::from_utf8_lossy(&data).lines().filter_map(|line| {
+        let fields: Vec<&str> = line.split(':').collect();
+        let members = fields.get(3)?;
+        if members.split(',').any(|member| member == name) { fields[2].parse().ok() } else { None }
+    }).collect()
+}
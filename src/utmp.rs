@@ -0,0 +1,102 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+//! Reading of the utmp and wtmp login records, in the format used by
+//! glibc on Linux.
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// The file recording who is logged in now.
+pub const UTMP_FILE: &str = "/var/run/utmp";
+/// The file recording logins and logouts.
+pub const WTMP_FILE: &str = "/var/log/wtmp";
+
+/// The size of a record.
+const RECORD_SIZE: usize = 384;
+
+pub const RUN_LVL: i16 = 1;
+pub const BOOT_TIME: i16 = 2;
+pub const INIT_PROCESS: i16 = 5;
+pub const LOGIN_PROCESS: i16 = 6;
+pub const USER_PROCESS: i16 = 7;
+pub const DEAD_PROCESS: i16 = 8;
+
+/// A login record.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Entry {
+    /// The type of the record, such as USER_PROCESS.
+    pub kind: i16,
+    pub pid: i32,
+    /// The terminal, without the /dev/ prefix.
+    pub line: String,
+    pub id: String,
+    pub user: String,
+    /// The remote host, if any.
+    pub host: String,
+    /// The time of the record in seconds since the epoch.
+    pub time: i64,
+}
+
+/// Returns a NUL-padded string field.
+fn field(data: &[u8]) -> String {
+    let end = data.iter().position(|&c| c == 0).unwrap_or(data.len());
+    String::from_utf8_lossy(&data[..end]).into_owned()
+}
+
+fn i16_at(data: &[u8], offset: usize) -> i16 {
+    i16::from_ne_bytes([data[offset], data[offset + 1]])
+}
+
+fn i32_at(data: &[u8], offset: usize) -> i32 {
+    i32::from_ne_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+impl Entry {
+    /// Parses a record, which must be RECORD_SIZE bytes long.
+    fn parse(data: &[u8]) -> Entry {
+        Entry {
+            kind: i16_at(data, 0),
+            pid: i32_at(data, 4),
+            line: field(&data[8..40]),
+            id: field(&data[40..44]),
+            user: field(&data[44..76]),
+            host: field(&data[76..332]),
+            time: i32_at(data, 340) as i64,
+        }
+    }
+}
+
+/// Parses the records in the contents of a utmp file. A partial record
+/// at the end is ignored.
+pub fn parse(data: &[u8]) -> Vec<Entry> {
+    data.chunks_exact(RECORD_SIZE).map(Entry::parse).collect()
+}
+
+/// Reads the records in the named utmp file.
+pub fn read<P: AsRef<Path>>(path: P) -> io::Result<Vec<Entry>> {
+    Ok(parse(&fs::read(path)?))
+}
+
+#[test]
+fn test_parse() {
+    let mut record = vec![0u8; RECORD_SIZE];
+    record[0..2].copy_from_slice(&USER_PROCESS.to_ne_bytes());
+    record[4..8].copy_from_slice(&1234i32.to_ne_bytes());
+    record[8..13].copy_from_slice(b"pts/0");
+    record[40..42].copy_from_slice(b"ts");
+    record[44..49].copy_from_slice(b"james");
+    record[76..85].copy_from_slice(b"localhost");
+    record[340..344].copy_from_slice(&1614834367i32.to_ne_bytes());
+    record.extend_from_slice(&[0; 10]);
+    let entries = parse(&record);
+    assert_eq!(entries, vec![Entry {
+        kind: USER_PROCESS,
+        pid: 1234,
+        line: "pts/0".to_string(),
+        id: "ts".to_string(),
+        user: "james".to_string(),
+        host: "localhost".to_string(),
+        time: 1614834367,
+    }]);
+}
@@ -0,0 +1,312 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+//! Recursive directory traversal. A `Walk` visits each of the paths it is
+//! given and, for those that are directories, everything beneath them.
+//! Symbolic links are followed according to a `Follow` policy, which the
+//! commands that walk trees set from -H, -L and -P in the same way.
+//! Directories are read through open directories, each opened by name in
+//! its parent and checked to be the directory that was visited, so that
+//! swapping a directory for a symbolic link mid-walk can't lead the walk
+//! out of the tree.
+use std::fmt;
+use std::fs;
+use std::ffi::OsString;
+use std::fs::Metadata;
+use std::io;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::vec;
+
+use crate::dir::Dir;
+
+/// Which symbolic links are followed: none of them (-P, the default),
+/// only those named as starting paths (-H), or all of them (-L). A
+/// followed link is visited as the file it refers to, unless it refers to
+/// nothing, in which case it is visited as the link itself.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Follow {
+    #[default]
+    Never,
+    Roots,
+    All,
+}
+
+impl Follow {
+    /// Sets the policy from one of the -H, -L and -P options, of which
+    /// the last given wins. Returns false for any other option.
+    pub fn set(&mut self, opt: char) -> bool {
+        *self = match opt {
+            'H' => Follow::Roots,
+            'L' => Follow::All,
+            'P' => Follow::Never,
+            _ => return false,
+        };
+        true
+    }
+
+    /// Returns whether a link at the given depth is followed.
+    pub fn follows(self, depth: usize) -> bool {
+        match self {
+            Follow::Never => false,
+            Follow::Roots => depth == 0,
+            Follow::All => true,
+        }
+    }
+}
+
+/// An error encountered during a walk, along with the path concerned.
+#[derive(Debug)]
+pub struct Error {
+    path: PathBuf,
+    err: io::Error,
+}
+
+impl Error {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn io_error(&self) -> &io::Error {
+        &self.err
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.err)
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// A file visited during a walk.
+#[derive(Debug, Clone)]
+pub struct Entry {
+    path: PathBuf,
+    depth: usize,
+    metadata: Metadata,
+}
+
+impl Entry {
+    /// Returns the path of the file, which is the path it was reached by:
+    /// one of the starting paths joined with the names of the directories
+    /// traversed.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the depth of the file below the starting path, which is
+    /// itself at depth 0.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Returns the metadata of the file, as returned by lstat, or by stat
+    /// if the file is a symbolic link that was followed.
+    pub fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    /// Returns the last component of the path, or the whole path if it
+    /// doesn't have one (such as "/").
+    pub fn name(&self) -> &std::ffi::OsStr {
+        self.path.file_name().unwrap_or(self.path.as_os_str())
+    }
+}
+
+/// A directory being read.
+struct Frame {
+    /// The names of the directory's entries still to be visited, or None
+    /// if the directory couldn't be read.
+    names: Option<vec::IntoIter<OsString>>,
+    /// The directory, open, so that its subdirectories can be opened in
+    /// it.
+    fd: Option<Dir>,
+    path: PathBuf,
+    depth: usize,
+    /// The device and inode numbers of the directory.
+    id: (u64, u64),
+    /// The directory itself, when it is to be returned after its
+    /// contents.
+    dir: Option<Entry>,
+}
+
+/// An iterator over the files below a set of starting paths. By default
+/// each directory is visited before its contents.
+pub struct Walk {
+    roots: vec::IntoIter<PathBuf>,
+    stack: Vec<Frame>,
+    /// The directory most recently returned, which is read next.
+    pending: Option<Entry>,
+    contents_first: bool,
+    same_file_system: bool,
+    follow: Follow,
+    /// The device of the current starting path.
+    dev: u64,
+}
+
+impl Walk {
+    pub fn new<I, P>(roots: I) -> Walk
+    where I: IntoIterator<Item = P>, P: AsRef<Path> {
+        let roots: Vec<PathBuf> = roots.into_iter().map(|p| p.as_ref().to_path_buf()).collect();
+        Walk {
+            roots: roots.into_iter(),
+            stack: Vec::new(),
+            pending: None,
+            contents_first: false,
+            same_file_system: false,
+            follow: Follow::Never,
+            dev: 0,
+        }
+    }
+
+    /// Visits each directory after, rather than before, its contents.
+    pub fn contents_first(mut self, yes: bool) -> Walk {
+        self.contents_first = yes;
+        self
+    }
+
+    /// Doesn't descend into directories on file systems other than that
+    /// of the starting path. Such directories are still visited.
+    pub fn same_file_system(mut self, yes: bool) -> Walk {
+        self.same_file_system = yes;
+        self
+    }
+
+    /// Follows symbolic links according to the policy.
+    pub fn follow(mut self, follow: Follow) -> Walk {
+        self.follow = follow;
+        self
+    }
+
+    /// Returns the open directory in which the file most recently returned
+    /// was found, or None if it was a starting path. A file changed by its
+    /// name in this directory, rather than by its path, can't be one
+    /// elsewhere that a directory swapped for a symbolic link leads to.
+    pub fn parent(&self) -> Option<&Dir> {
+        self.stack.last().and_then(|frame| frame.fd.as_ref())
+    }
+
+    /// Skips the contents of the directory that was just returned. This
+    /// has no effect when directories are visited after their contents.
+    pub fn skip_current_dir(&mut self) {
+        self.pending = None;
+    }
+
+    fn entry(&self, path: PathBuf, depth: usize) -> Result<Entry, Error> {
+        if self.follow.follows(depth) {
+            if let Ok(metadata) = fs::metadata(&path) {
+                return Ok(Entry { path, depth, metadata });
+            }
+        }
+        match fs::symlink_metadata(&path) {
+            Ok(metadata) => Ok(Entry { path, depth, metadata }),
+            Err(err) => Err(Error { path, err }),
+        }
+    }
+
+    /// Opens a directory that has been visited: a starting path by its
+    /// path, and anything else by its name in its parent, whose frame is
+    /// on top of the stack. Fails if what is opened isn't the directory
+    /// that was visited.
+    fn open_dir(&self, dir: &Entry) -> io::Result<Dir> {
+        let follow = self.follow.follows(dir.depth);
+        let fd = match self.stack.last().and_then(|frame| frame.fd.as_ref()) {
+            Some(parent) if dir.depth > 0 => parent.open_dir(dir.name(), follow)?,
+            _ => Dir::open(&dir.path)?,
+        };
+        let meta = fd.metadata()?;
+        if (meta.dev(), meta.ino()) != (dir.metadata.dev(), dir.metadata.ino()) {
+            return Err(io::Error::other("directory replaced while being walked"));
+        }
+        Ok(fd)
+    }
+
+    /// Starts reading a directory, returning the frame (with no entries
+    /// if the directory couldn't be read) and any error.
+    fn open(&self, dir: &Entry) -> (Frame, Option<Error>) {
+        let result = self.open_dir(dir).and_then(|fd| Ok((fd.names()?, fd)));
+        let (names, fd, err) = match result {
+            Ok((names, fd)) => (Some(names.into_iter()), Some(fd), None),
+            Err(err) => (None, None, Some(Error { path: dir.path.clone(), err })),
+        };
+        let frame = Frame {
+            names,
+            fd,
+            path: dir.path.clone(),
+            depth: dir.depth + 1,
+            id: (dir.metadata.dev(), dir.metadata.ino()),
+            dir: None,
+        };
+        (frame, err)
+    }
+
+    /// Returns what to yield on reaching the entry, arranging for it to
+    /// be descended into if it is a directory. Returns None if nothing is
+    /// to be yielded yet.
+    fn visit(&mut self, entry: Entry) -> Option<Result<Entry, Error>> {
+        let descend = entry.metadata.is_dir()
+            && (!self.same_file_system || entry.metadata.dev() == self.dev);
+        if !descend {
+            return Some(Ok(entry));
+        }
+        // Following links can lead back to a directory being read.
+        let id = (entry.metadata.dev(), entry.metadata.ino());
+        if self.follow == Follow::All && self.stack.iter().any(|frame| frame.id == id) {
+            let err = io::Error::other("directory cycle");
+            return Some(Err(Error { path: entry.path, err }));
+        }
+        if !self.contents_first {
+            self.pending = Some(entry.clone());
+            return Some(Ok(entry));
+        }
+        let (mut frame, err) = self.open(&entry);
+        frame.dir = Some(entry);
+        self.stack.push(frame);
+        err.map(Err)
+    }
+}
+
+impl Iterator for Walk {
+    type Item = Result<Entry, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(dir) = self.pending.take() {
+            match self.open(&dir) {
+                (frame, None) => self.stack.push(frame),
+                (_, Some(err)) => return Some(Err(err)),
+            }
+        }
+        loop {
+            let entry = match self.stack.last_mut() {
+                None => {
+                    let root = self.roots.next()?;
+                    match self.entry(root, 0) {
+                        Ok(entry) => {
+                            self.dev = entry.metadata.dev();
+                            entry
+                        },
+                        Err(e) => return Some(Err(e)),
+                    }
+                },
+                Some(frame) => match frame.names.as_mut().and_then(|names| names.next()) {
+                    Some(name) => {
+                        let (path, depth) = (frame.path.join(name), frame.depth);
+                        match self.entry(path, depth) {
+                            Ok(entry) => entry,
+                            Err(e) => return Some(Err(e)),
+                        }
+                    },
+                    None => match self.stack.pop().unwrap().dir {
+                        Some(dir) => return Some(Ok(dir)),
+                        None => continue,
+                    },
+                },
+            };
+            if let Some(result) = self.visit(entry) {
+                return Some(result);
+            }
+        }
+    }
+}
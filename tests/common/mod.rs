@@ -0,0 +1,202 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+//! Support for the integration tests, which run the commands' binaries
+//! with given arguments and standard input, in directories of files made
+//! for the purpose, and check what they write and how they exit.
+#![allow(dead_code)]
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::os::unix::fs::FileExt;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use rust_v7_cmd::tempfile;
+
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Returns the path of a file in tests/fixtures.
+pub fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name)
+}
+
+/// Returns the directory holding the binaries built for the tests.
+pub fn binaries() -> PathBuf {
+    // The tests themselves are in target/<profile>/deps.
+    let exe = env::current_exe().unwrap();
+    exe.parent().unwrap().parent().unwrap().to_path_buf()
+}
+
+/// A temporary directory, removed with everything in it when dropped.
+pub struct Tree {
+    path: PathBuf,
+}
+
+impl Tree {
+    pub fn new() -> Tree {
+        let n = COUNTER.fetch_add(1, Ordering::SeqCst);
+        let path = tempfile::temp_dir().join(format!("rust-v7-cmd.{}.{}", std::process::id(), n));
+        fs::create_dir(&path).unwrap();
+        Tree { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the path of a file in the tree.
+    pub fn join<P: AsRef<Path>>(&self, name: P) -> PathBuf {
+        self.path.join(name)
+    }
+
+    /// Creates a file, and any directories leading to it, with the given
+    /// contents.
+    pub fn file(&self, name: &str, contents: &str) -> &Tree {
+        let path = self.join(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+        self
+    }
+
+    /// Creates a sparse file of the given length, which is all hole but
+    /// for the given data at the given offsets.
+    pub fn sparse(&self, name: &str, len: u64, data: &[(u64, &str)]) -> &Tree {
+        let path = self.join(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let file = fs::File::create(path).unwrap();
+        file.set_len(len).unwrap();
+        for &(offset, text) in data {
+            file.write_all_at(text.as_bytes(), offset).unwrap();
+        }
+        self
+    }
+
+    /// Creates a directory, and any leading to it.
+    pub fn dir(&self, name: &str) -> &Tree {
+        fs::create_dir_all(self.join(name)).unwrap();
+        self
+    }
+
+    /// Returns the contents of a file in the tree.
+    pub fn read(&self, name: &str) -> String {
+        fs::read_to_string(self.join(name)).unwrap()
+    }
+}
+
+impl Drop for Tree {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+/// What a command wrote and how it exited.
+#[derive(Debug)]
+pub struct Output {
+    /// The exit status, or None if the command was killed by a signal.
+    pub status: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+impl Output {
+    /// Asserts that the command exited with the given status, having
+    /// written the given output.
+    pub fn expect(&self, status: i32, stdout: &str) -> &Output {
+        assert_eq!((self.status, self.stdout.as_str()), (Some(status), stdout),
+                   "stderr: {}", self.stderr);
+        self
+    }
+
+    /// Asserts that the command wrote nothing to the standard error.
+    pub fn quiet(&self) -> &Output {
+        assert_eq!(self.stderr, "");
+        self
+    }
+
+    /// Asserts that the command wrote something containing the given
+    /// text to the standard error.
+    pub fn complains(&self, text: &str) -> &Output {
+        assert!(self.stderr.contains(text), "stderr: {}", self.stderr);
+        self
+    }
+}
+
+/// A command to be run.
+pub struct Cmd {
+    command: Command,
+    stdin: Vec<u8>,
+}
+
+impl Cmd {
+    /// Returns the command to run a binary, as built for the tests.
+    pub fn new(name: &str) -> Cmd {
+        let mut command = Command::new(binaries().join(name));
+        command.env("LC_ALL", "C").env("TZ", "UTC").env_remove("POSIXLY_CORRECT");
+        Cmd { command, stdin: Vec::new() }
+    }
+
+    /// Returns the command to run the system's own version of a command,
+    /// found in PATH, or None if there is none.
+    pub fn system(name: &str) -> Option<Cmd> {
+        let path = env::var_os("PATH")?;
+        let exe = env::split_paths(&path).map(|dir| dir.join(name)).find(|exe| exe.is_file())?;
+        let mut command = Command::new(exe);
+        command.env("LC_ALL", "C").env("TZ", "UTC").env_remove("POSIXLY_CORRECT");
+        Some(Cmd { command, stdin: Vec::new() })
+    }
+
+    pub fn arg<S: AsRef<std::ffi::OsStr>>(mut self, arg: S) -> Cmd {
+        self.command.arg(arg);
+        self
+    }
+
+    pub fn args<S: AsRef<std::ffi::OsStr>>(mut self, args: &[S]) -> Cmd {
+        self.command.args(args);
+        self
+    }
+
+    /// Gives the command the text as its standard input, which is
+    /// otherwise empty.
+    pub fn stdin(mut self, text: &str) -> Cmd {
+        self.stdin = text.as_bytes().to_vec();
+        self
+    }
+
+    /// Runs the command in the given directory.
+    pub fn current_dir<P: AsRef<Path>>(mut self, dir: P) -> Cmd {
+        self.command.current_dir(dir);
+        self
+    }
+
+    pub fn env(mut self, name: &str, value: &str) -> Cmd {
+        self.command.env(name, value);
+        self
+    }
+
+    /// Starts the command, with its standard output and error piped, so
+    /// that the test can read as much of the output as it wants.
+    pub fn spawn(mut self) -> Child {
+        self.command.stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn().unwrap()
+    }
+
+    pub fn run(mut self) -> Output {
+        let mut child = self.command.stdin(Stdio::piped()).stdout(Stdio::piped())
+            .stderr(Stdio::piped()).spawn().unwrap();
+        // The input is written from another thread, so that a command
+        // that writes a lot before reading doesn't block.
+        let mut stdin = child.stdin.take().unwrap();
+        let input = std::mem::take(&mut self.stdin);
+        let writer = std::thread::spawn(move || {
+            let _ = stdin.write_all(&input);
+        });
+        let output = child.wait_with_output().unwrap();
+        writer.join().unwrap();
+        Output {
+            status: output.status.code(),
+            stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        }
+    }
+}
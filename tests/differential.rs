@@ -0,0 +1,156 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// Differential tests, which run randomly made inputs and options through
+// both these commands and the system's own, and compare what they write
+// and how they exit. They are opt-in, being only as good as the system's
+// commands, and run only when V7_DIFFERENTIAL is set:
+//
+//     V7_DIFFERENTIAL=1 cargo test --test differential
+//
+// A command the system lacks is skipped. The inputs are made from
+// V7_SEED, if it is set, and otherwise from the time; a divergence is
+// reported with the seed, so that it can be made again. Only the options
+// whose meaning the Seventh Edition and POSIX agree on are tried.
+mod common;
+
+use std::env;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use common::{Cmd, Output};
+
+/// The number of inputs tried for each command.
+const CASES: usize = 50;
+
+/// A xorshift generator, which is all that choosing inputs needs.
+struct Random(u64);
+
+impl Random {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// Returns a number below n.
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn choose<'a>(&mut self, items: &[&'a str]) -> &'a str {
+        items[self.below(items.len())]
+    }
+
+    /// Returns up to max characters drawn from the alphabet.
+    fn text(&mut self, alphabet: &str, max: usize) -> String {
+        let chars: Vec<char> = alphabet.chars().collect();
+        (0..self.below(max + 1)).map(|_| chars[self.below(chars.len())]).collect()
+    }
+
+    /// Returns some of the options, each with even odds.
+    fn options(&mut self, options: &[&str]) -> Vec<String> {
+        options.iter().filter(|_| self.below(2) == 0).map(|opt| opt.to_string()).collect()
+    }
+}
+
+/// Returns the seed, reporting it so that a failure can be reproduced.
+fn seed() -> u64 {
+    let seed = env::var("V7_SEED").ok().and_then(|seed| seed.parse().ok()).unwrap_or_else(|| {
+        SystemTime::now().duration_since(UNIX_EPOCH).map_or(1, |d| d.as_nanos() as u64)
+    });
+    eprintln!("V7_SEED={}", seed);
+    seed.max(1)
+}
+
+/// Runs a case through both commands, given the same arguments and input,
+/// and describes how they diverged, if they did. Their output is compared
+/// after normalizing it.
+fn compare(name: &str, args: &[String], input: &str, normalize: fn(&str) -> String) -> Option<String> {
+    let ours = Cmd::new(name).args(args).stdin(input).run();
+    let theirs = Cmd::system(name)?.args(args).stdin(input).run();
+    let same = |ours: &Output, theirs: &Output| {
+        ours.status == theirs.status && normalize(&ours.stdout) == normalize(&theirs.stdout)
+    };
+    if same(&ours, &theirs) {
+        return None;
+    }
+    Some(format!("{} {:?} with input {:?}:\n  ours:   {:?} {:?}\n  theirs: {:?} {:?}",
+                 name, args, input, ours.status, ours.stdout, theirs.status, theirs.stdout))
+}
+
+fn exact(text: &str) -> String {
+    text.to_string()
+}
+
+/// Reduces text to its words, for output whose spacing differs between
+/// implementations, such as wc's columns.
+fn words(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Runs CASES cases of a command, made by the function, and fails with the
+/// divergences. Does nothing unless V7_DIFFERENTIAL is set or if the system
+/// lacks the command.
+fn differ(name: &str, normalize: fn(&str) -> String,
+          case: fn(&mut Random) -> (Vec<String>, String)) {
+    if env::var_os("V7_DIFFERENTIAL").is_none() || Cmd::system(name).is_none() {
+        return;
+    }
+    let seed = seed();
+    let mut random = Random(seed);
+    let divergences: Vec<String> = (0..CASES).filter_map(|_| {
+        let (args, input) = case(&mut random);
+        compare(name, &args, &input, normalize)
+    }).collect();
+    assert!(divergences.is_empty(), "V7_SEED={}: {} of {} cases diverged:\n{}",
+            seed, divergences.len(), CASES, divergences.join("\n"));
+}
+
+/// Returns lines of words, numbers and blanks.
+fn lines(random: &mut Random) -> String {
+    let n = random.below(8);
+    (0..n).map(|_| random.text("ab AB01-. \t", 6) + "\n").collect()
+}
+
+#[test]
+fn test_sort() {
+    differ("sort", exact, |random| (random.options(&["-r", "-u", "-n"]), lines(random)));
+}
+
+#[test]
+fn test_tr() {
+    differ("tr", exact, |random| {
+        let set = random.text("abcde", 3) + random.choose(&["a", "b", "c", "d", "e"]);
+        let args = match random.below(3) {
+            0 => vec![set, random.text("vwxyz", 3) + random.choose(&["v", "w", "x", "y", "z"])],
+            1 => vec!["-d".to_string(), set],
+            _ => vec!["-s".to_string(), set],
+        };
+        (args, random.text("abcdexyz\n", 40))
+    });
+}
+
+#[test]
+fn test_wc() {
+    differ("wc", words, |random| (random.options(&["-l", "-w", "-c"]), lines(random)));
+}
+
+#[test]
+fn test_basename() {
+    differ("basename", exact, |random| {
+        let mut args = vec![random.text("ab/.", 8) + random.choose(&["x", "x.c", "/"])];
+        if random.below(2) == 0 {
+            args.push(random.choose(&[".c", "c", "x.c"]).to_string());
+        }
+        (args, String::new())
+    });
+}
+
+#[test]
+fn test_od() {
+    differ("od", exact, |random| {
+        let args = vec![random.choose(&["-b", "-o", "-x"]).to_string()];
+        (args, random.text("abc\n~ ", 40))
+    });
+}
@@ -0,0 +1,485 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// Tests of the commands that make, find and remove files.
+mod common;
+
+use common::{Cmd, Tree};
+
+#[test]
+fn test_find() {
+    let tree = Tree::new();
+    tree.file("a/f", "x\n").file("a/b/g", "y\n");
+    let out = Cmd::new("find").args(&[".", "-print"]).current_dir(tree.path()).run();
+    let mut found: Vec<&str> = out.stdout.lines().collect();
+    found.sort();
+    assert_eq!(found, [".", "./a", "./a/b", "./a/b/g", "./a/f"]);
+    Cmd::new("find").args(&[".", "-name", "g"]).current_dir(tree.path()).run().expect(0, "./a/b/g\n");
+}
+
+#[test]
+fn test_touch_rm() {
+    let tree = Tree::new();
+    Cmd::new("touch").arg("new").current_dir(tree.path()).run().expect(0, "").quiet();
+    assert!(tree.join("new").is_file());
+    Cmd::new("rm").arg("new").current_dir(tree.path()).run().expect(0, "").quiet();
+    assert!(!tree.join("new").exists());
+
+    tree.file("d/f", "");
+    Cmd::new("rm").args(&["d", "nonexistent"]).current_dir(tree.path()).run()
+        .expect(1, "").complains("directory").complains("nonexistent");
+    assert!(tree.join("d").exists());
+    Cmd::new("rm").args(&["-r", "d"]).current_dir(tree.path()).run().expect(0, "");
+    assert!(!tree.join("d").exists());
+
+    Cmd::new("rm").args(&["-f", "nonexistent"]).current_dir(tree.path()).run().expect(1, "").quiet();
+    Cmd::new("rm").args(&["-f", "nonexistent"]).env("POSIXLY_CORRECT", "").current_dir(tree.path()).run()
+        .expect(0, "").quiet();
+}
+
+#[test]
+fn test_non_utf8_name() {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    let tree = Tree::new();
+    let name = OsStr::from_bytes(b"caf\xe9");
+    std::fs::write(tree.join(name), "x\n").unwrap();
+    Cmd::new("cat").arg(name).current_dir(tree.path()).run().expect(0, "x\n").quiet();
+    Cmd::new("find").args(&[OsStr::new("."), OsStr::new("-name"), OsStr::from_bytes(b"*\xe9")])
+        .current_dir(tree.path()).run().expect(0, "./caf\u{fffd}\n").quiet();
+    Cmd::new("sort").arg(name).current_dir(tree.path()).run().expect(0, "x\n").quiet();
+    Cmd::new("tail").arg(name).current_dir(tree.path()).run().expect(0, "x\n").quiet();
+    Cmd::new("wc").args(&[OsStr::new("-l"), name]).current_dir(tree.path()).run()
+        .expect(0, "      1 caf\u{fffd}\n").quiet();
+    Cmd::new("head").args(&[name, name]).current_dir(tree.path()).run()
+        .expect(0, "==> caf\u{fffd} <==\nx\n\n==> caf\u{fffd} <==\nx\n").quiet();
+    Cmd::new("cmp").args(&[name, name]).current_dir(tree.path()).run().expect(0, "").quiet();
+    Cmd::new("awk").args(&[OsStr::new("{ print NR }"), name]).current_dir(tree.path()).run()
+        .expect(0, "1\n").quiet();
+
+    // Nor need the names options are given.
+    let other = OsStr::from_bytes(b"s\xe9d");
+    Cmd::new("sort").args(&[OsStr::new("-o"), other, name]).current_dir(tree.path()).run().expect(0, "").quiet();
+    assert_eq!(std::fs::read(tree.join(other)).unwrap(), b"x\n");
+    std::fs::write(tree.join(other), "s/x/y/\n").unwrap();
+    Cmd::new("sed").args(&[OsStr::new("-f"), other, name]).current_dir(tree.path()).run().expect(0, "y\n").quiet();
+    Cmd::new("rm").arg(name).current_dir(tree.path()).run().expect(0, "").quiet();
+    assert!(!tree.join(name).exists());
+}
+
+#[test]
+fn test_tar() {
+    let tree = Tree::new();
+    tree.file("src/d/x", "hello\n").dir("out");
+    Cmd::new("tar").args(&["cf", "../a.tar", "d"]).current_dir(tree.join("src")).run().expect(0, "");
+    Cmd::new("tar").args(&["tf", "a.tar"]).current_dir(tree.path()).run().expect(0, "d/\nd/x\n");
+    Cmd::new("tar").args(&["xf", "../a.tar"]).current_dir(tree.join("out")).run().expect(0, "");
+    assert_eq!(tree.read("out/d/x"), "hello\n");
+}
+
+#[test]
+fn test_mktemp() {
+    let tree = Tree::new();
+    let out = Cmd::new("mktemp").args(&["-p", tree.path().to_str().unwrap(), "t.XXXXXX"]).run();
+    let path = out.stdout.trim_end();
+    assert!(path.starts_with(tree.join("t.").to_str().unwrap()), "{}", path);
+    assert!(std::path::Path::new(path).is_file());
+}
+
+#[test]
+fn test_ed() {
+    let tree = Tree::new();
+    tree.file("e", "a\nb\nc\n");
+    Cmd::new("ed").args(&["-s", "e"]).stdin("2d\nw\nq\n").current_dir(tree.path()).run().expect(0, "");
+    assert_eq!(tree.read("e"), "a\nc\n");
+}
+
+#[test]
+fn test_json() {
+    let tree = Tree::new();
+    tree.file("a/f", "one two\nthree\n");
+    Cmd::new("wc").args(&["--json", "-lw", "a/f", "a/f"]).current_dir(tree.path()).run()
+        .expect(0, "{\"file\":\"a/f\",\"lines\":2,\"words\":3}\n{\"file\":\"a/f\",\"lines\":2,\"words\":3}\n");
+    let out = Cmd::new("du").args(&["--json", "-s", "a"]).current_dir(tree.path()).run();
+    assert!(out.stdout.starts_with("{\"path\":\"a\",\"blocks\":"), "{}", out.stdout);
+    let out = Cmd::new("find").args(&["--json", "a", "-name", "f"]).current_dir(tree.path()).run();
+    assert!(out.stdout.starts_with("{\"path\":\"a/f\",\"type\":\"f\",\"size\":14,"), "{}", out.stdout);
+    assert_eq!(out.stdout.lines().count(), 1);
+}
+
+#[test]
+fn test_nul_records() {
+    let tree = Tree::new();
+    tree.file("d/new\nline", "");
+    Cmd::new("find").args(&["d", "-type", "f", "-print0"]).current_dir(tree.path()).run()
+        .expect(0, "d/new\nline\0");
+    Cmd::new("sort").arg("-z").stdin("b\nx\0a\0").run().expect(0, "a\0b\nx\0");
+    Cmd::new("basename").args(&["-z", "d/new\nline"]).run().expect(0, "new\nline\0");
+    Cmd::new("basename").arg("-z").run().expect(0, "-z\n");
+    let out = Cmd::new("du").args(&["-a", "-z", "d/new\nline"]).current_dir(tree.path()).run();
+    assert!(out.stdout.ends_with("\td/new\nline\0"), "{:?}", out.stdout);
+}
+
+#[test]
+fn test_sparse() {
+    use std::os::unix::fs::MetadataExt;
+
+    let tree = Tree::new();
+    tree.sparse("src/s", 1 << 20, &[(1 << 19, "hello")]).dir("out");
+    Cmd::new("tar").args(&["cf", "../a.tar", "s"]).current_dir(tree.join("src")).run().expect(0, "");
+    Cmd::new("tar").args(&["xf", "../a.tar"]).current_dir(tree.join("out")).run().expect(0, "");
+    assert_eq!(std::fs::read(tree.join("out/s")).unwrap(), std::fs::read(tree.join("src/s")).unwrap());
+    assert!(std::fs::metadata(tree.join("out/s")).unwrap().blocks() < 64);
+    Cmd::new("du").args(&["-A", "-k", "out/s"]).current_dir(tree.path()).run().expect(0, "1024\tout/s\n");
+    let out = Cmd::new("du").args(&["-k", "out/s"]).current_dir(tree.path()).run();
+    assert!(out.stdout.split('\t').next().unwrap().parse::<u64>().unwrap() < 32, "{}", out.stdout);
+}
+
+#[test]
+fn test_large_offsets() {
+    let tree = Tree::new();
+    tree.sparse("a", (1 << 32) + 16, &[(1 << 32, "hello")]);
+    Cmd::new("od").args(&["-c", "a", "+40000000000"]).current_dir(tree.path()).run()
+        .expect(0, "40000000000   h   e   l   l   o 000 000 000 000 000 000 000 000 000 000 000\n\
+                    40000000020\n");
+    Cmd::new("dd").args(&["if=a", "bs=1", "skip=4294967296", "count=5", "status=none"])
+        .current_dir(tree.path()).run().expect(0, "hello");
+    Cmd::new("dd").args(&["of=c", "bs=1", "seek=4294967296", "status=none"]).stdin("hi")
+        .current_dir(tree.path()).run().expect(0, "");
+    Cmd::new("du").args(&["-A", "-k", "c"]).current_dir(tree.path()).run().expect(0, "4194305\tc\n");
+    Cmd::new("dd").args(&["seek=18014398509481984"]).current_dir(tree.path()).run()
+        .complains("seek=18014398509481984: offset too large");
+}
+
+#[test]
+fn test_dd() {
+    let tree = Tree::new();
+    tree.file("a", &"0123456789".repeat(130));
+    // A regular file is copied whole, its records counted afterwards as
+    // they are when read a block at a time from a pipe.
+    Cmd::new("dd").args(&["if=a", "of=b", "ibs=100"]).current_dir(tree.path()).run()
+        .expect(0, "").complains("13+0 records in\n2+1 records out\n");
+    assert_eq!(tree.read("b"), tree.read("a"));
+    Cmd::new("dd").args(&["of=c", "ibs=100"]).stdin(&tree.read("a")).current_dir(tree.path()).run()
+        .expect(0, "").complains("2+1 records out\n");
+    assert_eq!(tree.read("c"), tree.read("a"));
+    Cmd::new("dd").args(&["if=a", "of=b", "bs=512", "skip=2", "seek=1", "conv=notrunc", "status=noxfer"])
+        .current_dir(tree.path()).run().expect(0, "").complains("0+1 records in\n0+1 records out\n");
+    let (a, b) = (tree.read("a"), tree.read("b"));
+    assert_eq!((b.len(), &b[512..788], &b[788..]), (1300, &a[1024..], &a[788..]));
+    Cmd::new("dd").args(&["if=a", "bs=1000", "conv=ucase", "count=1", "status=none"])
+        .current_dir(tree.path()).run().expect(0, &"0123456789".repeat(100));
+}
+
+// cmp reads the 4GB before the difference, which takes a minute or more.
+#[test]
+#[ignore]
+fn test_large_cmp() {
+    let tree = Tree::new();
+    tree.sparse("a", (1 << 32) + 16, &[(1 << 32, "hello")])
+        .sparse("b", (1 << 32) + 16, &[(1 << 32, "hellp")]);
+    Cmd::new("cmp").args(&["-l", "a", "b"]).current_dir(tree.path()).run()
+        .expect(1, "4294967301 157 160\n");
+}
+
+#[test]
+fn test_follow() {
+    let tree = Tree::new();
+    tree.file("d/x", "hello\n");
+    std::os::unix::fs::symlink("d", tree.join("l")).unwrap();
+    Cmd::new("find").arg("l").current_dir(tree.path()).run().expect(0, "l\n");
+    Cmd::new("find").args(&["-H", "l"]).current_dir(tree.path()).run().expect(0, "l\nl/x\n");
+    Cmd::new("find").args(&["-L", "-P", "l", "-type", "l"]).current_dir(tree.path()).run().expect(0, "l\n");
+    std::os::unix::fs::symlink(".", tree.join("d/up")).unwrap();
+    Cmd::new("find").args(&["-L", "d", "-type", "f"]).current_dir(tree.path()).run()
+        .expect(1, "d/x\n").complains("d/up: directory cycle");
+    let out = Cmd::new("du").args(&["-H", "-s", "l"]).current_dir(tree.path()).run();
+    assert!(out.stdout.ends_with("\tl\n"), "{}", out.stdout);
+}
+
+#[test]
+fn test_trace() {
+    let tree = Tree::new();
+    tree.file("d/x", "");
+    Cmd::new("rm").args(&["-r", "d"]).env("V7_TRACE", "1").current_dir(tree.path()).run()
+        .expect(0, "").complains("rm: unlink(\"x\") = ok\n").complains("rm: rmdir(\"d\") = ok\n");
+    Cmd::new("rm").arg("d").env("V7_TRACE", "").current_dir(tree.path()).run()
+        .expect(1, "").complains("rm: d: No such file");
+    let out = Cmd::new("tee").arg("t").env("V7_TRACE", "1").current_dir(tree.path()).run();
+    assert_eq!(out.stderr, "tee: create(\"t\") = ok\n");
+}
+
+#[test]
+fn test_ls() {
+    let tree = Tree::new();
+    tree.file("d/b", "12345").file("d/a", "").file("d/.hidden", "").file("f", "x");
+    Cmd::new("ls").arg("d").current_dir(tree.path()).run().expect(0, "a\nb\n");
+    Cmd::new("ls").args(&["-r", "d"]).current_dir(tree.path()).run().expect(0, "b\na\n");
+    Cmd::new("ls").args(&["-a", "d"]).current_dir(tree.path()).run().expect(0, ".\n..\n.hidden\na\nb\n");
+    Cmd::new("ls").args(&["-d", "d"]).current_dir(tree.path()).run().expect(0, "d\n");
+    Cmd::new("ls").args(&["d", "f"]).current_dir(tree.path()).run().expect(0, "f\n\nd:\na\nb\n");
+    Cmd::new("ls").args(&["f", "nonexistent"]).current_dir(tree.path()).run()
+        .expect(1, "f\n").complains("nonexistent");
+
+    // Names are coloured by type only when asked, and not in a pipe unless
+    // forced.
+    tree.dir("d/e");
+    Cmd::new("ls").args(&["--color=always", "d"]).current_dir(tree.path()).run()
+        .expect(0, "a\nb\n\x1b[1;34me\x1b[0m\n");
+    Cmd::new("ls").args(&["--color", "d"]).current_dir(tree.path()).run().expect(0, "a\nb\ne\n");
+    Cmd::new("ls").arg("d").env("CLICOLOR", "1").current_dir(tree.path()).run().expect(0, "a\nb\ne\n");
+    Cmd::new("ls").arg("d").env("CLICOLOR_FORCE", "1").current_dir(tree.path()).run()
+        .expect(0, "a\nb\n\x1b[1;34me\x1b[0m\n");
+    Cmd::new("ls").arg("d").env("CLICOLOR_FORCE", "1").env("NO_COLOR", "1").current_dir(tree.path()).run()
+        .expect(0, "a\nb\ne\n");
+    Cmd::new("ls").args(&["--color=sometimes", "d"]).current_dir(tree.path()).run()
+        .expect(2, "").complains("sometimes: invalid --color");
+    std::fs::remove_dir(tree.join("d/e")).unwrap();
+
+    let out = Cmd::new("ls").args(&["-l", "d/b"]).current_dir(tree.path()).run();
+    assert!(out.stdout.starts_with("-rw"), "{}", out.stdout);
+    assert!(out.stdout.contains(" 5 ") && out.stdout.ends_with(" d/b\n"), "{}", out.stdout);
+    std::os::unix::fs::symlink("b", tree.join("d/l")).unwrap();
+    let out = Cmd::new("ls").args(&["-l", "d"]).current_dir(tree.path()).run();
+    assert!(out.stdout.starts_with("total "), "{}", out.stdout);
+    assert!(out.stdout.contains(" l -> b\n"), "{}", out.stdout);
+    let out = Cmd::new("ls").args(&["-lL", "d"]).current_dir(tree.path()).run();
+    assert!(!out.stdout.contains(" -> ") && out.stdout.contains("\n-rw"), "{}", out.stdout);
+
+    // A link named as an operand is followed unless listed with -l or -d,
+    // or with -P; -H follows it regardless.
+    std::os::unix::fs::symlink("d", tree.join("dl")).unwrap();
+    Cmd::new("ls").arg("dl").current_dir(tree.path()).run().expect(0, "a
+b
+l
+");
+    Cmd::new("ls").args(&["-P", "dl"]).current_dir(tree.path()).run().expect(0, "dl
+");
+    Cmd::new("ls").args(&["-dH", "dl"]).current_dir(tree.path()).run().expect(0, "dl
+");
+    let out = Cmd::new("ls").args(&["-lH", "dl"]).current_dir(tree.path()).run();
+    assert!(out.stdout.starts_with("total "), "{}", out.stdout);
+    Cmd::new("ls").args(&["-sh", "d/a"]).current_dir(tree.path()).run().expect(0, "   0 d/a\n");
+
+    let out = Cmd::new("ls").args(&["--json", "d/b", "dl"]).current_dir(tree.path()).run();
+    let lines: Vec<&str> = out.stdout.lines().collect();
+    assert_eq!(lines.len(), 4, "{}", out.stdout);
+    assert!(lines[0].starts_with(r#"{"path":"d/b","type":"f","size":5,"#), "{}", out.stdout);
+    assert!(lines[1].starts_with(r#"{"path":"dl/a","type":"f","size":0,"#), "{}", out.stdout);
+    assert!(lines[3].starts_with(r#"{"path":"dl/l","type":"l","#), "{}", out.stdout);
+
+    // Names are sorted by their bytes unless the locale says otherwise.
+    tree.file("c/B", "").file("c/a", "");
+    Cmd::new("ls").arg("c").env("LC_ALL", "C").current_dir(tree.path()).run().expect(0, "B\na\n");
+    Cmd::new("ls").arg("c").env("LC_ALL", "en_US.UTF-8").current_dir(tree.path()).run().expect(0, "a\nB\n");
+}
+
+#[test]
+fn test_cp() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let tree = Tree::new();
+    tree.file("a", "hello\n").file("d/x", "x\n").file("d/e/y", "y\n").dir("t");
+    std::fs::set_permissions(tree.join("a"), std::fs::Permissions::from_mode(0o751)).unwrap();
+    Cmd::new("cp").args(&["a", "b"]).current_dir(tree.path()).run().expect(0, "");
+    assert_eq!(std::fs::read_to_string(tree.join("b")).unwrap(), "hello\n");
+    assert_eq!(std::fs::metadata(tree.join("b")).unwrap().permissions().mode() & 0o777, 0o751);
+    Cmd::new("cp").args(&["a", "b", "t"]).current_dir(tree.path()).run().expect(0, "");
+    assert!(tree.join("t/a").is_file() && tree.join("t/b").is_file());
+    Cmd::new("cp").args(&["a", "a"]).current_dir(tree.path()).run().expect(1, "").complains("same file");
+    Cmd::new("cp").args(&["d", "c"]).current_dir(tree.path()).run().expect(1, "").complains("is a directory");
+    Cmd::new("cp").args(&["-r", "d", "c"]).current_dir(tree.path()).run().expect(0, "");
+    assert_eq!(std::fs::read_to_string(tree.join("c/e/y")).unwrap(), "y\n");
+    std::os::unix::fs::symlink("x", tree.join("d/l")).unwrap();
+    Cmd::new("cp").args(&["-r", "d", "t"]).current_dir(tree.path()).run().expect(0, "");
+    assert_eq!(std::fs::read_to_string(tree.join("t/d/x")).unwrap(), "x\n");
+    // Copying again keeps the links copied before, or replaces those that
+    // no longer match.
+    Cmd::new("cp").args(&["-r", "d", "t"]).current_dir(tree.path()).run().expect(0, "");
+    assert_eq!(std::fs::read_link(tree.join("t/d/l")).unwrap(), std::path::Path::new("x"));
+    std::fs::remove_file(tree.join("d/l")).unwrap();
+    std::os::unix::fs::symlink("e", tree.join("d/l")).unwrap();
+    Cmd::new("cp").args(&["-r", "d", "t"]).current_dir(tree.path()).run().expect(0, "");
+    assert_eq!(std::fs::read_link(tree.join("t/d/l")).unwrap(), std::path::Path::new("e"));
+    Cmd::new("cp").args(&["-r", "d", "d/e"]).current_dir(tree.path()).run().expect(1, "").complains("into itself");
+    // With -R, links named as operands are followed unless -P is given,
+    // and those below them only with -L.
+    std::os::unix::fs::symlink("d", tree.join("dl")).unwrap();
+    Cmd::new("cp").args(&["-R", "dl", "h"]).current_dir(tree.path()).run().expect(0, "");
+    assert!(std::fs::symlink_metadata(tree.join("h")).unwrap().is_dir());
+    assert_eq!(std::fs::read_link(tree.join("h/l")).unwrap(), std::path::Path::new("e"));
+    Cmd::new("cp").args(&["-RP", "dl", "p"]).current_dir(tree.path()).run().expect(0, "");
+    assert_eq!(std::fs::read_link(tree.join("p")).unwrap(), std::path::Path::new("d"));
+    Cmd::new("cp").args(&["-RL", "dl", "l"]).current_dir(tree.path()).run().expect(0, "");
+    assert!(std::fs::symlink_metadata(tree.join("l/l")).unwrap().is_dir());
+    assert_eq!(std::fs::read_to_string(tree.join("l/l/y")).unwrap(), "y\n");
+    Cmd::new("cp").args(&["a", "b", "a"]).current_dir(tree.path()).run().expect(1, "").complains("not a directory");
+}
+
+#[test]
+fn test_mv() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let tree = Tree::new();
+    tree.file("a", "a\n").file("b", "b\n").file("c", "c\n").dir("d");
+    Cmd::new("mv").args(&["a", "x"]).current_dir(tree.path()).run().expect(0, "");
+    assert!(!tree.join("a").exists());
+    assert_eq!(std::fs::read_to_string(tree.join("x")).unwrap(), "a\n");
+    Cmd::new("mv").args(&["x", "b", "d"]).current_dir(tree.path()).run().expect(0, "");
+    assert!(tree.join("d/x").is_file() && tree.join("d/b").is_file());
+    Cmd::new("mv").args(&["c", "c"]).current_dir(tree.path()).run().expect(1, "").complains("same file");
+    Cmd::new("mv").args(&["c", "c", "c"]).current_dir(tree.path()).run().expect(1, "").complains("not a directory");
+
+    std::fs::set_permissions(tree.join("d/x"), std::fs::Permissions::from_mode(0o444)).unwrap();
+    Cmd::new("mv").args(&["-f", "d/b", "d/x"]).current_dir(tree.path()).run().expect(0, "");
+    assert_eq!(std::fs::read_to_string(tree.join("d/x")).unwrap(), "b\n");
+    std::fs::set_permissions(tree.join("d/x"), std::fs::Permissions::from_mode(0o444)).unwrap();
+    Cmd::new("mv").args(&["c", "d/x"]).env("POSIXLY_CORRECT", "").current_dir(tree.path()).run().expect(0, "");
+    assert_eq!(std::fs::read_to_string(tree.join("d/x")).unwrap(), "c\n");
+}
+
+#[test]
+fn test_ln() {
+    use std::os::unix::fs::MetadataExt;
+
+    let tree = Tree::new();
+    tree.file("a", "a\n").file("b", "b\n").dir("d");
+    Cmd::new("ln").args(&["a", "h"]).current_dir(tree.path()).run().expect(0, "");
+    assert_eq!(std::fs::metadata(tree.join("a")).unwrap().ino(), std::fs::metadata(tree.join("h")).unwrap().ino());
+    Cmd::new("ln").args(&["b", "h"]).current_dir(tree.path()).run().expect(1, "").complains("h: File exists");
+    Cmd::new("ln").args(&["-f", "b", "h"]).current_dir(tree.path()).run().expect(0, "");
+    assert_eq!(std::fs::read_to_string(tree.join("h")).unwrap(), "b\n");
+    Cmd::new("ln").args(&["-f", "b", "h"]).current_dir(tree.path()).run().expect(1, "").complains("same file");
+    assert!(tree.join("h").exists());
+    Cmd::new("ln").args(&["-s", "../a", "../b", "d"]).current_dir(tree.path()).run().expect(0, "");
+    assert_eq!(std::fs::read_link(tree.join("d/a")).unwrap(), std::path::Path::new("../a"));
+    assert_eq!(std::fs::read_to_string(tree.join("d/b")).unwrap(), "b\n");
+    Cmd::new("ln").arg("../a").current_dir(tree.join("d")).run().expect(1, "").complains("File exists");
+}
+
+#[test]
+fn test_mkdir() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let tree = Tree::new();
+    let mode = |name: &str| std::fs::metadata(tree.join(name)).unwrap().permissions().mode() & 0o7777;
+    Cmd::new("mkdir").args(&["a", "b"]).current_dir(tree.path()).run().expect(0, "");
+    assert!(tree.join("a").is_dir() && tree.join("b").is_dir());
+    Cmd::new("mkdir").arg("a").current_dir(tree.path()).run().expect(1, "").complains("a: File exists");
+    Cmd::new("mkdir").args(&["x/y"]).current_dir(tree.path()).run().expect(1, "").complains("x/y");
+    Cmd::new("mkdir").args(&["-p", "a", "x/y/z"]).current_dir(tree.path()).run().expect(0, "");
+    assert!(tree.join("x/y/z").is_dir());
+    Cmd::new("mkdir").args(&["-m", "700", "c"]).current_dir(tree.path()).run().expect(0, "");
+    assert_eq!(mode("c"), 0o700);
+    Cmd::new("mkdir").args(&["-m", "go-w", "-p", "d/e"]).current_dir(tree.path()).run().expect(0, "");
+    assert_eq!(mode("d/e"), 0o755);
+    Cmd::new("mkdir").args(&["-m", "u=rwx,g=rx,o=", "f"]).current_dir(tree.path()).run().expect(0, "");
+    assert_eq!(mode("f"), 0o750);
+    Cmd::new("mkdir").args(&["-m", "rwx", "g"]).current_dir(tree.path()).run()
+        .expect(2, "").complains("invalid mode: rwx");
+    assert!(!tree.join("g").exists());
+}
+
+#[test]
+fn test_rmdir() {
+    let tree = Tree::new();
+    tree.dir("a/b/c").dir("e").file("f/x", "");
+    Cmd::new("rmdir").args(&["e", "f", "nonexistent"]).current_dir(tree.path()).run()
+        .expect(1, "").complains("f: Directory not empty").complains("nonexistent");
+    assert!(!tree.join("e").exists() && tree.join("f").exists());
+    Cmd::new("rmdir").arg("f/x").current_dir(tree.path()).run().expect(1, "").complains("Not a directory");
+    Cmd::new("rmdir").args(&["-p", "a/b/c"]).current_dir(tree.path()).run().expect(0, "");
+    assert!(!tree.join("a").exists());
+}
+
+#[test]
+fn test_chmod() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let tree = Tree::new();
+    tree.file("f", "").file("d/x", "").file("d/e/y", "");
+    let set = |name: &str, mode: u32| {
+        std::fs::set_permissions(tree.join(name), std::fs::Permissions::from_mode(mode)).unwrap()
+    };
+    let mode = |name: &str| std::fs::metadata(tree.join(name)).unwrap().permissions().mode() & 0o7777;
+    set("f", 0o644);
+    Cmd::new("chmod").args(&["751", "f"]).current_dir(tree.path()).run().expect(0, "");
+    assert_eq!(mode("f"), 0o751);
+    Cmd::new("chmod").args(&["u-x,go=r", "f"]).current_dir(tree.path()).run().expect(0, "");
+    assert_eq!(mode("f"), 0o644);
+    Cmd::new("chmod").args(&["-w", "f"]).current_dir(tree.path()).run().expect(0, "");
+    assert_eq!(mode("f"), 0o444);
+    Cmd::new("chmod").args(&["a=rwx", "f", "nonexistent"]).current_dir(tree.path()).run()
+        .expect(1, "").complains("nonexistent");
+    assert_eq!(mode("f"), 0o777);
+    Cmd::new("chmod").args(&["u+q", "f"]).current_dir(tree.path()).run().expect(2, "").complains("invalid mode");
+
+    set("d/x", 0o644);
+    set("d/e/y", 0o600);
+    std::os::unix::fs::symlink("../f", tree.join("d/l")).unwrap();
+    Cmd::new("chmod").args(&["-R", "go+rX", "d"]).current_dir(tree.path()).run().expect(0, "");
+    assert_eq!(mode("d/x"), 0o644);
+    assert_eq!(mode("d/e/y"), 0o644);
+    assert_eq!(mode("d/e") & 0o055, 0o055);
+    assert_eq!(mode("f"), 0o777);
+}
+
+#[test]
+fn test_chown() {
+    use std::os::unix::fs::MetadataExt;
+
+    let tree = Tree::new();
+    tree.file("f", "").file("d/x", "");
+    let meta = std::fs::metadata(tree.path()).unwrap();
+    let (uid, gid) = (meta.uid().to_string(), meta.gid().to_string());
+    Cmd::new("chown").args(&[uid.as_str(), "f"]).current_dir(tree.path()).run().expect(0, "");
+    Cmd::new("chown").args(&["-R", format!("{}:{}", uid, gid).as_str(), "d"]).current_dir(tree.path()).run()
+        .expect(0, "");
+    Cmd::new("chown").args(&[format!("{}:", uid).as_str(), "f"]).current_dir(tree.path()).run().expect(0, "");
+    Cmd::new("chown").args(&["54321:", "f"]).current_dir(tree.path()).run()
+        .expect(1, "").complains("54321: no login group");
+    Cmd::new("chown").args(&["no-such-user", "f"]).current_dir(tree.path()).run()
+        .expect(1, "").complains("no-such-user: unknown user");
+    Cmd::new("chown").args(&[format!("{}:no-such-group", uid).as_str(), "f"]).current_dir(tree.path()).run()
+        .expect(1, "").complains("no-such-group: unknown group");
+    Cmd::new("chown").args(&[uid.as_str(), "nonexistent"]).current_dir(tree.path()).run()
+        .expect(1, "").complains("nonexistent");
+    Cmd::new("chown").arg(&uid).current_dir(tree.path()).run().expect(2, "").complains("usage");
+}
+
+#[test]
+fn test_chgrp() {
+    use std::os::unix::fs::MetadataExt;
+
+    let tree = Tree::new();
+    tree.file("f", "").file("d/x", "");
+    let gid = std::fs::metadata(tree.path()).unwrap().gid().to_string();
+    Cmd::new("chgrp").args(&[gid.as_str(), "f"]).current_dir(tree.path()).run().expect(0, "");
+    Cmd::new("chgrp").args(&["-R", gid.as_str(), "d"]).current_dir(tree.path()).run().expect(0, "");
+    Cmd::new("chgrp").args(&["no-such-group", "f"]).current_dir(tree.path()).run()
+        .expect(1, "").complains("no-such-group: unknown group");
+    Cmd::new("chgrp").args(&[gid.as_str(), "nonexistent"]).current_dir(tree.path()).run()
+        .expect(1, "").complains("nonexistent");
+    Cmd::new("chgrp").arg(&gid).current_dir(tree.path()).run().expect(2, "").complains("usage");
+
+    // The links in the tree are followed only with -L, and those named
+    // unless -P is given. What was changed is told by its change time.
+    std::os::unix::fs::symlink("../f", tree.join("d/l")).unwrap();
+    std::os::unix::fs::symlink("d", tree.join("dl")).unwrap();
+    let ctime = |name: &str| {
+        let meta = std::fs::metadata(tree.join(name)).unwrap();
+        (meta.ctime(), meta.ctime_nsec())
+    };
+    let changed = |args: &[&str], name: &str| {
+        let before = ctime(name);
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        Cmd::new("chgrp").args(args).current_dir(tree.path()).run().expect(0, "");
+        ctime(name) != before
+    };
+    assert!(!changed(&["-R", gid.as_str(), "d"], "f"));
+    assert!(!changed(&["-RP", gid.as_str(), "d"], "f"));
+    assert!(changed(&["-RL", gid.as_str(), "d"], "f"));
+    assert!(changed(&["-R", gid.as_str(), "dl"], "d/x"));
+    assert!(!changed(&["-RP", gid.as_str(), "dl"], "d/x"));
+}
@@ -0,0 +1,291 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// Tests of the calculators, test, echo and the like, of the shell, and of
+// the v7 multicall binary.
+mod common;
+
+use std::ffi::CStr;
+use std::fs;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::io::FromRawFd;
+use std::os::unix::process::ExitStatusExt;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+use common::{binaries, fixture, Cmd, Tree};
+
+#[test]
+fn test_bc_dc() {
+    Cmd::new("bc").stdin("2^10\n").run().expect(0, "1024\n");
+    Cmd::new("dc").stdin("3 4 + p\n").run().expect(0, "7\n");
+}
+
+#[test]
+fn test_test() {
+    Cmd::new("test").args(&["-d", "/"]).run().expect(0, "");
+    Cmd::new("test").args(&["1", "-gt", "2"]).run().expect(1, "");
+    Cmd::new("test").args(&["abc", "=", "abc"]).run().expect(0, "");
+    Cmd::new("test").args(&["(", "1"]).run().expect(2, "");
+}
+
+#[test]
+fn test_echo_basename() {
+    Cmd::new("echo").args(&["a", "b"]).run().expect(0, "a b\n");
+    Cmd::new("echo").args(&["-n", "a"]).run().expect(0, "a");
+    Cmd::new("echo").args(&["--help", "-x"]).run().expect(0, "--help -x\n");
+    Cmd::new("echo").arg(r"a\tb\c").run().expect(0, "a\\tb\\c\n");
+    Cmd::new("echo").arg(r"a\tb\c").env("POSIXLY_CORRECT", "").run().expect(0, "a\tb");
+    Cmd::new("basename").args(&["/a/b/c.rs", ".rs"]).run().expect(0, "c\n");
+    Cmd::new("basename").args(&["a/x.c", "x.c"]).run().expect(0, "x.c\n");
+    Cmd::new("basename").arg("./").run().expect(0, ".\n");
+    Cmd::new("basename").arg("a/b//").run().expect(0, "b\n");
+    Cmd::new("basename").arg("//").run().expect(0, "/\n");
+}
+
+#[test]
+fn test_usage() {
+    for name in ["cmp", "sort", "wc"] {
+        Cmd::new(name).arg("-Z").run().expect(2, "");
+    }
+    Cmd::new("mknod").run().expect(2, "").complains("usage");
+}
+
+#[test]
+fn test_help_version() {
+    let out = Cmd::new("rm").arg("--help").run();
+    assert!(out.stdout.starts_with("rm - remove files\n\nusage: rm [-fri] file ...\n\n  -f  "), "{}", out.stdout);
+    out.quiet();
+    Cmd::new("cat").arg("--version").run()
+        .expect(0, concat!("cat (rust-v7-cmd) ", env!("CARGO_PKG_VERSION"), "\n"));
+    Cmd::new("v7").args(&["head", "--version"]).run()
+        .expect(0, concat!("head (rust-v7-cmd) ", env!("CARGO_PKG_VERSION"), "\n"));
+}
+
+#[test]
+fn test_primes() {
+    Cmd::new("primes").args(&["10", "30"]).run().expect(0, "11\n13\n17\n19\n23\n29\n");
+}
+
+#[test]
+fn test_sums() {
+    let fruit = fixture("fruit");
+    Cmd::new("sum").arg(&fruit).run().expect(0, &format!("15335     1 {}\n", fruit.display()));
+    Cmd::new("cksum").stdin("banana\napple\ncherry\napple\ndate\n").run().expect(0, "3541449975 31\n");
+    Cmd::new("digest").args(&["-a", "md5"]).stdin("").run()
+        .expect(0, "d41d8cd98f00b204e9800998ecf8427e  -\n");
+}
+
+#[test]
+fn test_sh() {
+    // The scripts run the commands built alongside the shell.
+    let sh = |script: &str| Cmd::new("sh").args(&["-c", script]).env("PATH", binaries().to_str().unwrap());
+    sh("echo a; test a = b || echo b && exit 3; echo c").run().expect(3, "a\nb\n");
+    sh("x=1; while test $x != 111; do x=1$x; done; echo $x").run().expect(0, "111\n");
+    sh("case ab in a*) echo yes;; *) echo no;; esac").run().expect(0, "yes\n");
+    sh("(exit 4); echo $?").run().expect(0, "4\n");
+    sh("echo `echo a` b | cat").run().expect(0, "a b\n");
+    sh("if then").run().expect(2, "").complains("syntax error");
+
+    // Here-documents have their parameters and commands substituted,
+    // unless the delimiter is quoted, and <<- strips leading tabs.
+    sh("x=a; cat <<EOF\n$x \\$x `echo b` \"c\" 'd'\nEOF\necho e").run().expect(0, "a $x b \"c\" 'd'\ne\n");
+    sh("x=a; cat <<'EOF'\n$x `echo b`\nEOF").run().expect(0, "$x `echo b`\n");
+    sh("cat <<\\EOF\n$x\nEOF").run().expect(0, "$x\n");
+    sh("if :; then\n\tcat <<-EOF\n\t\tindented\n\tEOF\nfi").run().expect(0, "indented\n");
+    sh("cat <<EOF; cat <<EOF\na\nEOF\nb\nEOF").run().expect(0, "a\nb\n");
+    Cmd::new("sh").stdin("read x <<EOF\nline\nEOF\necho $x\n").run().expect(0, "line\n");
+
+    // Traps run on the signals named, once the current command has
+    // finished, and on exit, without changing the exit status.
+    sh("trap 'echo bye $?' EXIT; echo hi; exit 5").run().expect(5, "hi\nbye 5\n");
+    sh("trap 'echo caught' USR1; kill -USR1 $$; echo after").run().expect(0, "caught\nafter\n");
+    sh("trap '' USR1; kill -USR1 $$; echo ignored").run().expect(0, "ignored\n");
+    let output = sh("trap 'echo x' TERM; trap - TERM; kill $$; echo survived").spawn().wait_with_output().unwrap();
+    assert_eq!(output.status.signal(), Some(libc::SIGTERM));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "");
+    sh(r#"trap 'echo "it'\''s"' INT EXIT; trap; (trap)"#).run()
+        .expect(0, "trap -- 'echo \"it'\\''s\"' EXIT\ntrap -- 'echo \"it'\\''s\"' INT\nit's\n");
+    sh("trap 'echo x' KILL").run().expect(1, "").complains("KILL: bad trap");
+}
+
+#[test]
+fn test_kill() {
+    Cmd::new("kill").args(&["-l", "137", "TERM"]).run().expect(0, "KILL\n15\n");
+    let mut child = Cmd::new("sleep").arg("100").spawn();
+    let pid = child.id().to_string();
+    Cmd::new("kill").args(&["-s", "INT", &pid]).run().expect(0, "").quiet();
+    assert_eq!(child.wait().unwrap().signal(), Some(libc::SIGINT));
+    let mut child = Cmd::new("sleep").arg("100").spawn();
+    let pid = child.id().to_string();
+    Cmd::new("kill").args(&["-KILL", &pid]).run().expect(0, "").quiet();
+    assert_eq!(child.wait().unwrap().signal(), Some(libc::SIGKILL));
+
+    Cmd::new("kill").args(&["-s", "BOGUS", "1"]).run().expect(1, "").complains("BOGUS: unknown signal");
+    Cmd::new("kill").arg("x").run().expect(1, "").complains("x: invalid process id");
+    // The usage message names the command, not the path it was run by.
+    let output = Cmd::new("kill").run();
+    assert!(output.expect(2, "").stderr.starts_with("usage: kill ["), "stderr: {}", output.stderr);
+}
+
+#[test]
+fn test_who() {
+    // A boot record, two logins, one of them remote, and a logout, all at
+    // the epoch.
+    let tree = Tree::new();
+    tree.sparse("utmp", 4 * 384, &[
+        (0, "\x02"), (44, "reboot"),
+        (384, "\x07"), (384 + 8, "pts/1"), (384 + 44, "alice"),
+        (768, "\x07"), (768 + 8, "pts/2"), (768 + 44, "bob"), (768 + 76, "example.org"),
+        (1152, "\x08"), (1152 + 8, "pts/3"), (1152 + 44, "carol"),
+    ]);
+    let utmp = tree.join("utmp");
+    Cmd::new("who").arg(&utmp).run()
+        .expect(0, "alice    pts/1        Jan  1 00:00\nbob      pts/2        Jan  1 00:00 (example.org)\n");
+    Cmd::new("who").arg("-q").arg(&utmp).run().expect(0, "alice bob\n# users=2\n");
+    Cmd::new("who").arg(tree.join("nonexistent")).run().expect(1, "").complains("nonexistent: ");
+    Cmd::new("who").args(&["a", "b", "c"]).run().expect(2, "").complains("usage: who");
+}
+
+/// Returns a 64-bit little-endian relocatable object, such as a compiler
+/// would make, defining symbols in its .text, .data and .bss sections,
+/// one of them local, and referring to one it doesn't define.
+fn object() -> Vec<u8> {
+    let mut symtab = vec![0u8; 24];
+    let mut strtab = vec![0u8];
+    // Name, value, binding and type, and section.
+    for (name, value, info, section) in [("helper", 8u64, 0x02u8, 1u16), ("main", 0, 0x12, 1),
+                                         ("counter", 8, 0x11, 2), ("buf", 0, 0x11, 3), ("printf", 0, 0x10, 0)] {
+        symtab.extend((strtab.len() as u32).to_le_bytes());
+        symtab.extend([info, 0]);
+        symtab.extend(section.to_le_bytes());
+        symtab.extend(value.to_le_bytes());
+        symtab.extend(0u64.to_le_bytes());
+        strtab.extend(name.as_bytes());
+        strtab.push(0);
+    }
+    let names = [".text", ".data", ".bss", ".symtab", ".strtab", ".shstrtab"];
+    let mut shstrtab = vec![0u8];
+    let mut name_offsets = Vec::new();
+    for name in names {
+        name_offsets.push(shstrtab.len() as u32);
+        shstrtab.extend(name.as_bytes());
+        shstrtab.push(0);
+    }
+    // Type, flags, contents, link and entry size. The .bss takes no room
+    // in the file.
+    let sections: [(u32, u64, &[u8], u32, u64); 6] = [
+        (1, 6, &[0; 16], 0, 0), (1, 3, &[0; 16], 0, 0), (8, 3, &[0; 8], 0, 0),
+        (2, 0, &symtab, 5, 24), (3, 0, &strtab, 0, 0), (3, 0, &shstrtab, 0, 0),
+    ];
+    let mut data = vec![0u8; 64];
+    let mut headers = vec![0u8; 64];
+    for (&name, &(kind, flags, contents, link, entsize)) in name_offsets.iter().zip(&sections) {
+        headers.extend(name.to_le_bytes());
+        headers.extend(kind.to_le_bytes());
+        headers.extend(flags.to_le_bytes());
+        headers.extend(0u64.to_le_bytes());
+        headers.extend((data.len() as u64).to_le_bytes());
+        headers.extend((contents.len() as u64).to_le_bytes());
+        headers.extend(link.to_le_bytes());
+        headers.extend(0u32.to_le_bytes());
+        headers.extend(1u64.to_le_bytes());
+        headers.extend(entsize.to_le_bytes());
+        if kind != 8 {
+            data.extend(contents);
+        }
+    }
+    data.resize(data.len().next_multiple_of(8), 0);
+    let shoff = data.len() as u64;
+    data.extend(headers);
+    data[..7].copy_from_slice(b"\x7fELF\x02\x01\x01");
+    data[16..18].copy_from_slice(&1u16.to_le_bytes());
+    data[18..20].copy_from_slice(&62u16.to_le_bytes());
+    data[20..24].copy_from_slice(&1u32.to_le_bytes());
+    data[40..48].copy_from_slice(&shoff.to_le_bytes());
+    data[52..54].copy_from_slice(&64u16.to_le_bytes());
+    data[58..60].copy_from_slice(&64u16.to_le_bytes());
+    data[60..62].copy_from_slice(&7u16.to_le_bytes());
+    data[62..64].copy_from_slice(&6u16.to_le_bytes());
+    data
+}
+
+#[test]
+fn test_nm() {
+    let tree = Tree::new();
+    fs::write(tree.join("a.out"), object()).unwrap();
+    // With no operands, nm reads a.out.
+    let nm = |args: &[&str]| Cmd::new("nm").args(args).current_dir(tree.path()).run();
+    nm(&[]).expect(0, "0000000000000000 B buf\n0000000000000008 D counter\n0000000000000008 t helper\n\
+                       0000000000000000 T main\n                 U printf\n");
+    nm(&["-g", "a.out"]).expect(0, "0000000000000000 B buf\n0000000000000008 D counter\n\
+                                   0000000000000000 T main\n                 U printf\n");
+    nm(&["-u", "a.out"]).expect(0, "                 U printf\n");
+    nm(&["-n", "a.out"]).expect(0, "                 U printf\n0000000000000000 B buf\n0000000000000000 T main\n\
+                                   0000000000000008 D counter\n0000000000000008 t helper\n");
+    tree.file("text", "not an object\n");
+    nm(&["-u", "a.out", "text"]).expect(1, "\na.out:\n                 U printf\n\ntext:\n")
+        .complains("text: file format not recognized");
+}
+
+/// Opens a pseudo-terminal, returning its controlling side, which must
+/// be kept open while the terminal is used, and the terminal's path.
+fn pty() -> (File, PathBuf) {
+    // SAFETY: the descriptor returned by posix_openpt is checked, and
+    // then belongs to the File; ptsname returns a NUL-terminated string.
+    unsafe {
+        let fd = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY);
+        assert!(fd != -1, "posix_openpt failed");
+        let master = File::from_raw_fd(fd);
+        assert!(libc::grantpt(fd) == 0 && libc::unlockpt(fd) == 0);
+        let path = CStr::from_ptr(libc::ptsname(fd)).to_str().unwrap().into();
+        (master, path)
+    }
+}
+
+#[test]
+fn test_mesg() {
+    let (_master, tty) = pty();
+    // The terminal is mesg's standard input, the first it looks at.
+    let mesg = |args: &[&str]| {
+        let output = Command::new(binaries().join("mesg")).args(args)
+            .stdin(File::open(&tty).unwrap()).stderr(Stdio::inherit()).output().unwrap();
+        (output.status.code(), String::from_utf8_lossy(&output.stdout).into_owned())
+    };
+    let group_write = || fs::metadata(&tty).unwrap().permissions().mode() & 0o020 != 0;
+    assert_eq!(mesg(&["n"]), (Some(1), String::new()));
+    assert!(!group_write());
+    assert_eq!(mesg(&[]), (Some(1), "is n\n".to_string()));
+    assert_eq!(mesg(&["y"]), (Some(0), String::new()));
+    assert!(group_write());
+    assert_eq!(mesg(&[]), (Some(0), "is y\n".to_string()));
+    Cmd::new("mesg").run().expect(2, "").complains("not a terminal");
+    Cmd::new("mesg").arg("x").run().expect(2, "").complains("usage: mesg");
+}
+
+#[test]
+fn test_sigpipe() {
+    // A command writing to a pipe nobody reads is killed quietly.
+    let mut child = Cmd::new("primes").args(&["1", "1000000000"]).spawn();
+    let mut line = String::new();
+    BufReader::new(child.stdout.take().unwrap()).read_line(&mut line).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(line, "2\n");
+    assert_eq!(output.status.signal(), Some(libc::SIGPIPE));
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "");
+}
+
+#[test]
+fn test_v7() {
+    Cmd::new("v7").args(&["echo", "hi"]).run().expect(0, "hi\n");
+    Cmd::new("v7").arg("nonexistent").run().expect(127, "").complains("no such command");
+    // Run as [, test wants its closing ].
+    Cmd::new("v7").args(&["[", "a", "=", "a", "]"]).run().expect(0, "");
+    Cmd::new("v7").args(&["[", "a", "=", "b", "]"]).run().expect(1, "");
+    Cmd::new("v7").args(&["[", "a", "=", "a"]).run().expect(2, "").complains("missing ]");
+    // Diagnostics name the command run, not the multicall binary.
+    let output = Cmd::new("v7").args(&["cat", "nonexistent"]).run();
+    assert!(output.expect(1, "").stderr.starts_with("cat: nonexistent: "), "stderr: {}", output.stderr);
+}
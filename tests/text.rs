@@ -0,0 +1,124 @@
+// Copyright 2021 James Bostock. See the LICENSE file at the top-level
+// directory of this distribution.
+
+// Tests of the commands that read and write text.
+mod common;
+
+use common::{fixture, Cmd, Tree};
+
+const FRUIT: &str = "banana\napple\ncherry\napple\ndate\n";
+
+#[test]
+fn test_cat() {
+    Cmd::new("cat").arg(fixture("fruit")).run().expect(0, FRUIT).quiet();
+    Cmd::new("cat").args(&["-", "-"]).stdin("in\n").run().expect(0, "in\n");
+    Cmd::new("cat").env("V7_CONFINE", "1").stdin("in\n").run().expect(0, "in\n").quiet();
+    // The other confined filters still work too.
+    Cmd::new("wc").env("V7_CONFINE", "1").stdin("a b\n").run().expect(0, "      1       2       4\n").quiet();
+    Cmd::new("tr").args(&["a-z", "A-Z"]).env("V7_CONFINE", "1").stdin("in\n").run().expect(0, "IN\n").quiet();
+    Cmd::new("od").args(&["-c"]).env("V7_CONFINE", "1").stdin("in\n").run()
+        .expect(0, "0000000   i   n  \\n\n0000003\n").quiet();
+    Cmd::new("cat").args(&["nonexistent", "-"]).stdin("in\n").run()
+        .expect(1, "in\n").complains("nonexistent");
+}
+
+#[test]
+fn test_head_tail() {
+    Cmd::new("head").arg("-2").arg(fixture("fruit")).run().expect(0, "banana\napple\n");
+    Cmd::new("tail").arg("-2").stdin(FRUIT).run().expect(0, "apple\ndate\n");
+}
+
+#[test]
+fn test_wc() {
+    let fruit = fixture("fruit");
+    Cmd::new("wc").arg(&fruit).run()
+        .expect(0, &format!("      5       5      31 {}\n", fruit.display()));
+    Cmd::new("wc").arg("-l").arg(&fruit).run().expect(0, &format!("      5 {}\n", fruit.display()));
+}
+
+#[test]
+fn test_dash() {
+    // "-" means the standard input, as does the lack of any file.
+    for name in ["cat", "head", "tail", "sort", "od", "sum", "cksum", "digest"] {
+        let none = Cmd::new(name).stdin(FRUIT).run();
+        let dash = Cmd::new(name).arg("-").stdin(FRUIT).run();
+        assert!(!none.stdout.is_empty(), "{}", name);
+        assert_eq!(none.stdout.replace(" -", ""), dash.stdout.replace(" -", ""), "{}", name);
+    }
+    Cmd::new("dc").arg("-").stdin("2 3+p\n").run().expect(0, "5\n");
+    Cmd::new("bc").arg("-").stdin("2+3\n").run().expect(0, "5\n");
+    Cmd::new("file").arg("-").stdin("#!/bin/sh\n").run().expect(0, "-:\ta /bin/sh script, ASCII text\n");
+}
+
+#[test]
+fn test_sort() {
+    Cmd::new("sort").arg(fixture("fruit")).run().expect(0, "apple\napple\nbanana\ncherry\ndate\n");
+    Cmd::new("sort").arg("-u").stdin(FRUIT).run().expect(0, "apple\nbanana\ncherry\ndate\n");
+    Cmd::new("sort").arg("-r").stdin(FRUIT).run().expect(0, "date\ncherry\nbanana\napple\napple\n");
+    Cmd::new("sort").arg("+1").stdin("b 1\na 2\n").run().expect(0, "b 1\na 2\n");
+    Cmd::new("sort").arg("+1").env("POSIXLY_CORRECT", "").run().expect(1, "").complains("+1");
+}
+
+#[test]
+fn test_collation() {
+    let words = "b\nB\na-c\nab\nA\n";
+    Cmd::new("sort").stdin(words).run().expect(0, "A\nB\na-c\nab\nb\n");
+    Cmd::new("sort").args(&["-L", "fold"]).stdin(words).run().expect(0, "A\na-c\nab\nB\nb\n");
+    Cmd::new("sort").env("LC_ALL", "en_US.UTF-8").stdin(words).run().expect(0, "A\nab\na-c\nb\nB\n");
+    Cmd::new("sort").args(&["-L", "dutch"]).run().expect(2, "").complains("dutch");
+}
+
+#[test]
+fn test_tr_sed() {
+    Cmd::new("tr").args(&["a-z", "A-Z"]).stdin("hello\n").run().expect(0, "HELLO\n");
+    Cmd::new("sed").args(&["-n", "2p"]).stdin(FRUIT).run().expect(0, "apple\n");
+    Cmd::new("sed").arg("s/an/AN/g").stdin("banana\n").run().expect(0, "bANANa\n");
+}
+
+#[test]
+fn test_sed_in_place() {
+    let tree = Tree::new();
+    tree.file("f", FRUIT).file("-i", "kept\n");
+    Cmd::new("sed").args(&["-ni.bak", "2p", "f"]).current_dir(tree.path()).run().expect(0, "");
+    assert_eq!(tree.read("f"), "apple\n");
+    assert_eq!(tree.read("f.bak"), FRUIT);
+    Cmd::new("sed").args(&["-ni", "1p", "f.bak"]).current_dir(tree.path()).run().expect(0, "");
+    assert_eq!(tree.read("f.bak"), "banana\n");
+    // Neither an operand nor what follows -- is an option.
+    Cmd::new("sed").args(&["-n", "p", "-i"]).current_dir(tree.path()).run().expect(0, "kept\n");
+    Cmd::new("sed").args(&["-n", "--", "p", "-i"]).current_dir(tree.path()).run().expect(0, "kept\n");
+    assert_eq!(tree.read("-i"), "kept\n");
+    Cmd::new("sed").args(&["-n", "-e", "-i", "f"]).current_dir(tree.path()).run()
+        .expect(1, "").complains("unknown command: '-'");
+    assert_eq!(tree.read("f"), "apple\n");
+}
+
+#[test]
+fn test_join() {
+    Cmd::new("join").arg(fixture("colours")).arg(fixture("counts")).run()
+        .expect(0, "apple red 3\ncherry red 7\n");
+}
+
+#[test]
+fn test_cmp_diff() {
+    let tree = Tree::new();
+    tree.file("a", FRUIT).file("b", "banana\napple\n");
+    Cmd::new("cmp").args(&["a", "a"]).current_dir(tree.path()).run().expect(0, "");
+    Cmd::new("cmp").args(&["a", "b"]).current_dir(tree.path()).run().complains("EOF on b");
+    Cmd::new("diff").args(&["a", "b"]).current_dir(tree.path()).run()
+        .expect(1, "3,5d2\n< cherry\n< apple\n< date\n");
+}
+
+#[test]
+fn test_tee() {
+    let tree = Tree::new();
+    Cmd::new("tee").args(&["x", "y"]).stdin("copied\n").current_dir(tree.path()).run()
+        .expect(0, "copied\n");
+    assert_eq!((tree.read("x"), tree.read("y")), ("copied\n".to_string(), "copied\n".to_string()));
+}
+
+#[test]
+fn test_awk() {
+    Cmd::new("awk").arg("{ n += $2 } END { print n }").arg(fixture("counts")).run().expect(0, "11\n");
+    Cmd::new("awk").args(&["-F", ":", "{ print $2 }"]).stdin("a:b:c\n").run().expect(0, "b\n");
+}